@@ -9,7 +9,7 @@ mod exponential_decay {
     #[divan::bench]
     fn bdf() {
         let mut s = Bdf::default();
-        let (problem, _soln) = exponential_decay_problem::<nalgebra::DMatrix<f64>>(false);
+        let (problem, _soln) = exponential_decay_problem::<nalgebra::DMatrix<f64>>();
         let _y = s.solve(&problem, 1.0);
     }
 
@@ -17,7 +17,7 @@ mod exponential_decay {
     #[divan::bench]
     fn sundials() {
         let mut s = diffsol::SundialsIda::default();
-        let (problem, _soln) = exponential_decay_problem::<diffsol::SundialsMatrix>(false);
+        let (problem, _soln) = exponential_decay_problem::<diffsol::SundialsMatrix>();
         let _y = s.solve(&problem, 1.0);
     }
 }
@@ -28,7 +28,7 @@ mod robertson_ode {
     #[divan::bench]
     fn bdf() {
         let mut s = Bdf::default();
-        let (problem, _soln) = robertson_ode::<nalgebra::DMatrix<f64>>(false);
+        let (problem, _soln) = robertson_ode::<nalgebra::DMatrix<f64>>();
         let _y = s.solve(&problem, 4.0000e+10);
     }
 
@@ -36,7 +36,7 @@ mod robertson_ode {
     #[divan::bench]
     fn sundials() {
         let mut s = diffsol::SundialsIda::default();
-        let (problem, _soln) = robertson_ode::<diffsol::SundialsMatrix>(false);
+        let (problem, _soln) = robertson_ode::<diffsol::SundialsMatrix>();
         let _y = s.solve(&problem, 4.0000e+10);
     }
 }
@@ -47,7 +47,7 @@ mod robertson {
     #[divan::bench]
     fn bdf() {
         let mut s = Bdf::default();
-        let (problem, _soln) = robertson::<nalgebra::DMatrix<f64>>(false);
+        let (problem, _soln) = robertson::<nalgebra::DMatrix<f64>>();
         let _y = s.solve(&problem, 4.0000e+10);
     }
 
@@ -55,7 +55,7 @@ mod robertson {
     #[divan::bench]
     fn sundials() {
         let mut s = diffsol::SundialsIda::default();
-        let (problem, _soln) = robertson::<diffsol::SundialsMatrix>(false);
+        let (problem, _soln) = robertson::<diffsol::SundialsMatrix>();
         let _y = s.solve(&problem, 4.0000e+10);
     }
 }
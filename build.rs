@@ -0,0 +1,51 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "suitesparse")]
+    klu::generate_bindings();
+}
+
+#[cfg(feature = "suitesparse")]
+mod klu {
+    use std::{env, path::PathBuf};
+
+    // Locate the KLU library from SuiteSparse and emit its link flags. pkg-config is used first (SuiteSparse >= 7 installs
+    // `KLU.pc`), otherwise the library is looked for in the install prefix given by the `SUITESPARSE_DIR` environment
+    // variable. Returns the include paths for `klu.h`.
+    fn find_klu() -> Vec<PathBuf> {
+        println!("cargo:rerun-if-env-changed=SUITESPARSE_DIR");
+        let pkg_config_error = match pkg_config::Config::new().probe("KLU") {
+            Ok(lib) => return lib.include_paths,
+            Err(e) => e,
+        };
+        let dir = match env::var("SUITESPARSE_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => panic!(
+                "Could not find KLU with pkg-config ({}), set SUITESPARSE_DIR to the SuiteSparse install prefix",
+                pkg_config_error
+            ),
+        };
+        for lib in ["lib", "lib64"] {
+            println!("cargo:rustc-link-search=native={}", dir.join(lib).display());
+        }
+        println!("cargo:rustc-link-lib=klu");
+        let include = dir.join("include");
+        vec![include.join("suitesparse"), include]
+    }
+
+    // Generate bindings to the 64-bit integer interface (`klu_l_*`) of KLU, used by `src/linear_solver/klu.rs`
+    pub fn generate_bindings() {
+        let include_paths = find_klu();
+        let bindings = bindgen::Builder::default()
+            .header_contents("klu_wrapper.h", "#include <klu.h>")
+            .clang_args(include_paths.iter().map(|p| format!("-I{}", p.display())))
+            .allowlist_function("klu_l_.*")
+            .allowlist_type("klu_l_.*")
+            .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
+            .generate()
+            .expect("Failed to generate the KLU bindings");
+        let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+        bindings
+            .write_to_file(out_dir.join("klu_bindings.rs"))
+            .expect("Failed to write the KLU bindings");
+    }
+}
@@ -5,7 +5,7 @@ pub enum PSError {
     #[error("Sensitivity solve failed")]
     SensitivityError,
     #[error("Tstop ({}) is less than current time t {}", tstop, t)]
-    StopBeforeCurrentTime { tstop: f64, t: f64 },
+    StopBeforeCurrentTime { tstop: String, t: String },
     #[error("Interpolation time is after current time")]
     InterpolationBeforeCurrentTime,
     #[error("Interpolation time is not within the current step. Step size is zero after calling state_mut()")]
@@ -14,12 +14,75 @@ pub enum PSError {
     StateNotSet,
     #[error("Absolute tolerance must be of length 1 or the same length as the state vector")]
     AbsoluteToleranceLengthMismatch,
+    #[error(
+        "Dimension mismatch in {}: expected length {}, found {}",
+        name,
+        expected,
+        found
+    )]
+    DimensionMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error(
+        "Parameter vector has length {}, but the equations expect {} parameters",
+        found,
+        expected
+    )]
+    ParameterLengthMismatch { expected: usize, found: usize },
+    #[error(
+        "State index {} is out of range for equations with {} states",
+        index,
+        nstates
+    )]
+    InvalidStateIndex { index: usize, nstates: usize },
+    #[error("Order {} is outside the allowed range {}..={}", order, min, max)]
+    InvalidOrder {
+        order: usize,
+        min: usize,
+        max: usize,
+    },
     #[error("Step size too small at t = {}", t)]
-    StepSizeTooSmall { t: f64 },
+    StepSizeTooSmall { t: String },
+    #[error(
+        "Nonlinear solver failed to converge at t = {} with a fixed step size",
+        t
+    )]
+    FixedStepNonlinearSolverFailure { t: String },
+    #[error(
+        "Solution exceeded its bound at t = {}, in components {:?}",
+        t,
+        indices
+    )]
+    SolutionBoundExceeded { t: String, indices: Vec<usize> },
+    #[error("Solution bound must be of length 1 or the same length as the state vector")]
+    SolutionBoundLengthMismatch,
+    #[error("Constraints must have the same length as the state vector, with each lower bound at most the upper bound")]
+    InvalidConstraints,
+    #[error("Nonlinear solver could not satisfy the constraints on the solution")]
+    ConstraintsNotSatisfied,
+    #[error("Root is not bracketed, the function has the same sign at both ends of the interval")]
+    RootNotBracketed,
+    #[error("Steady state not reached after {} dosing cycles", ncycles)]
+    SteadyStateNotReached { ncycles: usize },
+    #[error("Steady state not found after {} time steps", nsteps)]
+    SteadyStateNotFound { nsteps: usize },
+    #[error("Projection onto the invariants failed to converge at t = {}", t)]
+    InvariantProjectionFailed { t: String },
     #[error("LU not initialized")]
     LuNotInitialized,
     #[error("LU solve failed")]
     LuFailed,
+    #[error("Cholesky factorisation failed, the matrix is not positive definite")]
+    NotPositiveDefinite,
+    #[error("Linear solver error: {}", e)]
+    LinearSolverError { e: String },
+    #[error(
+        "Linear solver {} is not available for the matrix type of the problem",
+        kind
+    )]
+    UnsupportedLinearSolver { kind: String },
     #[error("Error: {}", e)]
     Other { e: String },
     #[error("Maximum number of iterations reached, solver did not converge.")]
@@ -28,6 +91,8 @@ pub enum PSError {
     LinearPSError,
     #[error("Failed to get mutable reference to equations, is there a solver created with this problem?")]
     MutableReferenceError,
+    #[error("Problem is not supported by the solver: {}", e)]
+    UnsupportedProblem { e: String },
     #[error("Sensitivity requested but equations do not support it")]
     SensitivityNotSupported,
     #[error("LU not initialized")]
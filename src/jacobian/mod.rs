@@ -2,8 +2,8 @@ use std::collections::HashSet;
 
 use crate::op::{LinearOp, Op};
 use crate::vector::Vector;
-use crate::Scalar;
-use crate::{op::NonLinearOp, Matrix, MatrixSparsityRef, VectorIndex};
+use crate::{errors::PSError, Scalar};
+use crate::{op::NonLinearOp, Matrix, MatrixSparsity, MatrixSparsityRef, VectorIndex};
 use num_traits::{One, Zero};
 
 use self::{coloring::nonzeros2graph, greedy_coloring::color_graph_greedy};
@@ -23,7 +23,7 @@ pub fn find_non_zeros_nonlinear<F: NonLinearOp + ?Sized>(
     let mut triplets = Vec::with_capacity(op.nstates());
     for j in 0..op.nstates() {
         v[j] = F::T::NAN;
-        op.jac_mul_inplace(x, t, &v, &mut col);
+        op.jac_mul_inplace(x, t.clone(), &v, &mut col);
         for i in 0..op.nout() {
             if col[i].is_nan() {
                 triplets.push((i, j));
@@ -42,7 +42,7 @@ pub fn find_non_zeros_linear<F: LinearOp + ?Sized>(op: &F, t: F::T) -> Vec<(usiz
     let mut triplets = Vec::with_capacity(op.nstates());
     for j in 0..op.nstates() {
         v[j] = F::T::NAN;
-        op.call_inplace(&v, t, &mut col);
+        op.call_inplace(&v, t.clone(), &mut col);
         for i in 0..op.nout() {
             if col[i].is_nan() {
                 triplets.push((i, j));
@@ -54,6 +54,23 @@ pub fn find_non_zeros_linear<F: LinearOp + ?Sized>(op: &F, t: F::T) -> Vec<(usiz
     triplets
 }
 
+/// Build the sparsity pattern of a `nout x nstates` operator from the (row, column) indices of its non-zero entries,
+/// along with the coloring used to assemble its jacobian (or matrix) from this pattern.
+pub fn sparsity_and_coloring<M: Matrix>(
+    nout: usize,
+    nstates: usize,
+    non_zeros: Vec<(usize, usize)>,
+) -> Result<(M::Sparsity, JacobianColoring<M>), PSError> {
+    if let Some((i, j)) = non_zeros.iter().find(|(i, j)| *i >= nout || *j >= nstates) {
+        return Err(PSError::SparsityPatternError {
+            e: format!("index ({}, {}) is out of bounds", i, j),
+        });
+    }
+    let sparsity = M::Sparsity::try_from_indices(nout, nstates, non_zeros.clone())?;
+    let coloring = JacobianColoring::new(&sparsity.as_ref(), non_zeros, nstates);
+    Ok((sparsity, coloring))
+}
+
 pub struct JacobianColoring<M: Matrix> {
     dst_indices_per_color: Vec<<M::V as Vector>::Index>,
     src_indices_per_color: Vec<<M::V as Vector>::Index>,
@@ -61,11 +78,28 @@ pub struct JacobianColoring<M: Matrix> {
 }
 
 impl<M: Matrix> JacobianColoring<M> {
-    pub fn new_from_non_zeros<F: Op<M = M>>(op: &F, non_zeros: Vec<(usize, usize)>) -> Self {
+    pub fn new_from_non_zeros<F: Op<M = M> + ?Sized>(
+        op: &F,
+        non_zeros: Vec<(usize, usize)>,
+    ) -> Self {
         let sparsity = op
             .sparsity()
             .expect("Jacobian sparsity not defined, cannot use coloring");
-        let ncols = op.nstates();
+        Self::new(&sparsity, non_zeros, op.nstates())
+    }
+
+    /// Create a new coloring using every entry of the given sparsity pattern as a non-zero.
+    pub fn new_from_sparsity(sparsity: &M::SparsityRef<'_>) -> Self {
+        Self::new(sparsity, sparsity.indices(), sparsity.ncols())
+    }
+
+    /// Create a new coloring for a matrix with the given sparsity pattern and `ncols` columns,
+    /// where `non_zeros` are the (row, column) entries that are structurally non-zero.
+    pub fn new(
+        sparsity: &M::SparsityRef<'_>,
+        non_zeros: Vec<(usize, usize)>,
+        ncols: usize,
+    ) -> Self {
         let graph = nonzeros2graph(non_zeros.as_slice(), ncols);
         let coloring = color_graph_greedy(&graph);
         let max_color = coloring.iter().max().copied().unwrap_or(0);
@@ -107,7 +141,7 @@ impl<M: Matrix> JacobianColoring<M> {
     //    Self::new_from_non_zeros(op, non_zeros)
     //}
 
-    pub fn jacobian_inplace<F: NonLinearOp<M = M, V = M::V, T = M::T>>(
+    pub fn jacobian_inplace<F: NonLinearOp<M = M, V = M::V, T = M::T> + ?Sized>(
         &self,
         op: &F,
         x: &F::V,
@@ -121,13 +155,13 @@ impl<M: Matrix> JacobianColoring<M> {
             let dst_indices = &self.dst_indices_per_color[c];
             let src_indices = &self.src_indices_per_color[c];
             v.assign_at_indices(input, F::T::one());
-            op.jac_mul_inplace(x, t, &v, &mut col);
+            op.jac_mul_inplace(x, t.clone(), &v, &mut col);
             y.set_data_with_indices(dst_indices, src_indices, &col);
             v.assign_at_indices(input, F::T::zero());
         }
     }
 
-    pub fn matrix_inplace<F: LinearOp<M = M, V = M::V, T = M::T>>(
+    pub fn matrix_inplace<F: LinearOp<M = M, V = M::V, T = M::T> + ?Sized>(
         &self,
         op: &F,
         t: F::T,
@@ -140,7 +174,7 @@ impl<M: Matrix> JacobianColoring<M> {
             let dst_indices = &self.dst_indices_per_color[c];
             let src_indices = &self.src_indices_per_color[c];
             v.assign_at_indices(input, F::T::one());
-            op.call_inplace(&v, t, &mut col);
+            op.call_inplace(&v, t.clone(), &mut col);
             y.set_data_with_indices(dst_indices, src_indices, &col);
             v.assign_at_indices(input, F::T::zero());
         }
@@ -157,11 +191,14 @@ mod tests {
     use crate::op::linear_closure::LinearClosure;
     use crate::op::{LinearOp, Op};
     use crate::vector::Vector;
+    #[cfg(feature = "faer")]
+    use crate::SparseColMat;
     use crate::{
         jacobian::{coloring::nonzeros2graph, greedy_coloring::color_graph_greedy},
         op::closure::Closure,
+        Scalar,
     };
-    use crate::{scale, NonLinearOp, SparseColMat};
+    use crate::{scale, NonLinearOp};
     use nalgebra::DMatrix;
     use num_traits::{One, Zero};
     use std::ops::MulAssign;
@@ -170,12 +207,12 @@ mod tests {
         triplets: &'a [(usize, usize, M::T)],
         nrows: usize,
         ncols: usize,
-    ) -> impl NonLinearOp<M = M, V = M::V, T = M::T> + '_ {
+    ) -> impl NonLinearOp<M = M, V = M::V, T = M::T> + 'a {
         let nstates = ncols;
         let nout = nrows;
         let f = move |x: &M::V, y: &mut M::V| {
             for (i, j, v) in triplets {
-                y[*i] += x[*j] * *v;
+                y[*i] += x[*j].clone() * v.clone();
             }
         };
         let mut ret = Closure::new(
@@ -201,12 +238,12 @@ mod tests {
         triplets: &'a [(usize, usize, M::T)],
         nrows: usize,
         ncols: usize,
-    ) -> impl LinearOp<M = M, V = M::V, T = M::T> + '_ {
+    ) -> impl LinearOp<M = M, V = M::V, T = M::T> + 'a {
         let nstates = ncols;
         let nout = nrows;
         let f = move |x: &M::V, y: &mut M::V| {
             for (i, j, v) in triplets {
-                y[*i] += x[*j] * *v;
+                y[*i] += x[*j].clone() * v.clone();
             }
         };
         let mut ret = LinearClosure::new(
@@ -255,6 +292,7 @@ mod tests {
         find_non_zeros::<DMatrix<f64>>();
     }
 
+    #[cfg(feature = "faer")]
     #[test]
     fn find_non_zeros_faer_sparse() {
         find_non_zeros::<SparseColMat<f64>>();
@@ -293,6 +331,7 @@ mod tests {
         build_coloring::<DMatrix<f64>>();
     }
 
+    #[cfg(feature = "faer")]
     #[test]
     fn build_coloring_faer_sparse() {
         build_coloring::<SparseColMat<f64>>();
@@ -307,10 +346,10 @@ mod tests {
             ],
             vec![(0, 0, M::T::one()), (1, 1, M::T::one())],
             vec![
-                (0, 0, M::T::from(0.9)),
-                (1, 0, M::T::from(2.0)),
-                (1, 1, M::T::from(1.1)),
-                (2, 2, M::T::from(1.4)),
+                (0, 0, M::T::cast(0.9)),
+                (1, 0, M::T::cast(2.0)),
+                (1, 1, M::T::cast(1.1)),
+                (2, 2, M::T::cast(1.4)),
             ],
         ];
         let n = 3;
@@ -320,32 +359,32 @@ mod tests {
             let op = helper_triplets2op_nonlinear::<M>(triplets.as_slice(), n, n);
             let y0 = M::V::zeros(n);
             let t0 = M::T::zero();
-            let non_zeros = find_non_zeros_nonlinear(&op, &y0, t0);
+            let non_zeros = find_non_zeros_nonlinear(&op, &y0, t0.clone());
             let coloring = JacobianColoring::new_from_non_zeros(&op, non_zeros);
             let mut jac = M::new_from_sparsity(3, 3, op.sparsity().map(|s| s.to_owned()));
-            coloring.jacobian_inplace(&op, &y0, t0, &mut jac);
+            coloring.jacobian_inplace(&op, &y0, t0.clone(), &mut jac);
             let mut gemv1 = M::V::zeros(n);
             let v = M::V::from_element(3, M::T::one());
             op.jac_mul_inplace(&y0, t0, &v, &mut gemv1);
             let mut gemv2 = M::V::zeros(n);
             jac.gemv(M::T::one(), &v, M::T::zero(), &mut gemv2);
-            gemv1.assert_eq_st(&gemv2, M::T::from(1e-10));
+            gemv1.assert_eq_st(&gemv2, M::T::cast(1e-10));
         }
 
         // test linear functions
         for triplets in test_triplets {
             let op = helper_triplets2op_linear::<M>(triplets.as_slice(), n, n);
             let t0 = M::T::zero();
-            let non_zeros = find_non_zeros_linear(&op, t0);
+            let non_zeros = find_non_zeros_linear(&op, t0.clone());
             let coloring = JacobianColoring::new_from_non_zeros(&op, non_zeros);
             let mut jac = M::new_from_sparsity(3, 3, op.sparsity().map(|s| s.to_owned()));
-            coloring.matrix_inplace(&op, t0, &mut jac);
+            coloring.matrix_inplace(&op, t0.clone(), &mut jac);
             let mut gemv1 = M::V::zeros(n);
             let v = M::V::from_element(3, M::T::one());
             op.gemv_inplace(&v, t0, M::T::zero(), &mut gemv1);
             let mut gemv2 = M::V::zeros(n);
             jac.gemv(M::T::one(), &v, M::T::zero(), &mut gemv2);
-            gemv1.assert_eq_st(&gemv2, M::T::from(1e-10));
+            gemv1.assert_eq_st(&gemv2, M::T::cast(1e-10));
         }
     }
 
@@ -354,8 +393,101 @@ mod tests {
         matrix_coloring::<DMatrix<f64>>();
     }
 
+    #[cfg(feature = "faer")]
     #[test]
     fn matrix_coloring_faer_sparse() {
         matrix_coloring::<SparseColMat<f64>>();
     }
+
+    fn default_assembly<M: Matrix>() {
+        let triplets = vec![
+            (0, 0, M::T::cast(0.9)),
+            (1, 0, M::T::cast(2.0)),
+            (1, 1, M::T::cast(1.1)),
+            (2, 2, M::T::cast(1.4)),
+        ];
+        let n = 3;
+        let y0 = M::V::zeros(n);
+        let t0 = M::T::zero();
+        let v = M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(2.0), M::T::cast(3.0)]);
+
+        // the default jacobian should match the jacobian action
+        let op = helper_triplets2op_nonlinear::<M>(triplets.as_slice(), n, n);
+        let mut jac = M::new_from_sparsity(n, n, op.sparsity().map(|s| s.to_owned()));
+        op._default_jacobian_inplace(&y0, t0.clone(), &mut jac);
+        let mut gemv1 = M::V::zeros(n);
+        op.jac_mul_inplace(&y0, t0.clone(), &v, &mut gemv1);
+        let mut gemv2 = M::V::zeros(n);
+        jac.gemv(M::T::one(), &v, M::T::zero(), &mut gemv2);
+        gemv1.assert_eq_st(&gemv2, M::T::cast(1e-10));
+
+        // the default matrix should match the operator
+        let op = helper_triplets2op_linear::<M>(triplets.as_slice(), n, n);
+        let mut mat = M::new_from_sparsity(n, n, op.sparsity().map(|s| s.to_owned()));
+        op._default_matrix_inplace(t0.clone(), &mut mat);
+        op.gemv_inplace(&v, t0, M::T::zero(), &mut gemv1);
+        mat.gemv(M::T::one(), &v, M::T::zero(), &mut gemv2);
+        gemv1.assert_eq_st(&gemv2, M::T::cast(1e-10));
+    }
+
+    #[test]
+    fn default_assembly_dmatrix() {
+        default_assembly::<DMatrix<f64>>();
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn default_assembly_faer_sparse() {
+        default_assembly::<SparseColMat<f64>>();
+    }
+
+    fn explicit_sparsity<M: Matrix>() {
+        let triplets = [
+            (0, 0, M::T::cast(0.9)),
+            (1, 0, M::T::cast(2.0)),
+            (1, 1, M::T::cast(1.1)),
+            (2, 2, M::T::cast(1.4)),
+        ];
+        let n = 3;
+        let y0 = M::V::zeros(n);
+        let t0 = M::T::zero();
+        let v = M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(2.0), M::T::cast(3.0)]);
+
+        // an explicit sparsity pattern gives the same jacobian as the calculated one
+        let f = |x: &M::V, y: &mut M::V| {
+            y.fill(M::T::zero());
+            for (i, j, v) in triplets.iter() {
+                y[*i] += x[*j].clone() * v.clone();
+            }
+        };
+        let mut op = Closure::new(
+            |x: &M::V, _p: &M::V, _t, y: &mut M::V| f(x, y),
+            |_x: &M::V, _p: &M::V, _t, v: &M::V, y: &mut M::V| f(v, y),
+            n,
+            n,
+            Rc::new(M::V::zeros(0)),
+        );
+        let non_zeros = triplets.iter().map(|(i, j, _)| (*i, *j)).collect();
+        op.set_sparsity(non_zeros).unwrap();
+        let jac: M = op.jacobian(&y0, t0.clone());
+        let mut gemv1 = M::V::zeros(n);
+        op.jac_mul_inplace(&y0, t0, &v, &mut gemv1);
+        let mut gemv2 = M::V::zeros(n);
+        jac.gemv(M::T::one(), &v, M::T::zero(), &mut gemv2);
+        gemv1.assert_eq_st(&gemv2, M::T::cast(1e-10));
+
+        // out of bounds indices are an error
+        assert!(op.set_sparsity(vec![(0, 0), (3, 1)]).is_err());
+    }
+
+    #[test]
+    fn explicit_sparsity_dmatrix() {
+        explicit_sparsity::<DMatrix<f64>>();
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn explicit_sparsity_faer_sparse() {
+        explicit_sparsity::<SparseColMat<f64>>();
+    }
 }
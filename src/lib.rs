@@ -11,23 +11,54 @@
 //! You will also need to choose a matrix type to use. DiffSol can use the [nalgebra](https://nalgebra.org) `DMatrix` type, the [faer](https://github.com/sarah-ek/faer-rs) `Mat` type, or any other type that implements the
 //! [Matrix] trait. You can also use the [sundials](https://computation.llnl.gov/projects/sundials) library for the matrix and vector types (see [SundialsMatrix]).
 //!
+//! Problems with complex-valued states (e.g. quantum dynamics or frequency-domain models) can be built using [OdeBuilder::build_ode_complex], which solves the equations
+//! as a real system with the real and imaginary parts of each state interleaved (see [ode_solver::complex]).
+//!
 //! ## Initial state
 //!
 //! The solver state is held in [OdeSolverState], and contains a state vector, the gradient of the state vector, the time, and the step size. You can intitialise a new state using [OdeSolverState::new],
 //! or create an uninitialised state using [OdeSolverState::new_without_initialise] and intitialise it manually or using the [OdeSolverState::set_consistent] and [OdeSolverState::set_step_size] methods.
 //!
+//! For more control over the consistent initialisation of a DAE (e.g. to fix the time derivative and calculate the full state vector, or to use different tolerances),
+//! use [InitialConditionSolver], which also reports which components of the state were modified.
+//!
+//! The consistent initialisation and the DAE solvers assume that DAEs have index 1. You can estimate the index of your equations using [DaeIndex::analyse],
+//! and reduce the index of a higher-index DAE to one using [OdeSolverProblem::reduce_index].
+//!
 //! ## The solver
 //!
 //! To solve the problem given the initial state, you need to choose a solver. DiffSol provides the following solvers:
 //! - A Backwards Difference Formulae [Bdf] solver, suitable for stiff problems and singular mass matrices.
 //! - A Singly Diagonally Implicit Runge-Kutta (SDIRK or ESDIRK) solver [Sdirk]. You can use your own butcher tableau using [Tableau] or use one of the provided ([Tableau::tr_bdf2], [Tableau::esdirk34]).
+//! - An explicit Runge-Kutta solver [Erk] with embedded error control, suitable for non-stiff problems without a mass matrix ([Tableau::dopri5], [Tableau::tsit5]).
+//! - An extrapolation (Gragg-Bulirsch-Stoer) solver [Extrapolation] with automatic order control, suitable for smooth non-stiff problems without a mass matrix where high accuracy is required.
+//! - A 3-stage Radau IIA fully implicit Runge-Kutta solver [Radau], suitable for very stiff problems and singular mass matrices.
+//! - A variable order Adams-Bashforth / Adams-Moulton multistep solver [Adams], suitable for non-stiff problems without a mass matrix. The corrector uses functional iteration by default ([FixedPointNonlinearSolver]).
+//! - A solver [SwitchingSolver] that automatically switches between a non-stiff ([Adams]) and a stiff ([Bdf]) method based on an estimate of the stiffness of the problem (similar to LSODA).
+//! - A Rosenbrock (linearly implicit) solver [Rosenbrock] that requires only one jacobian evaluation and linear solves per step, suitable for mildly stiff problems ([RosenbrockTableau::rodas3], [RosenbrockTableau::rodas4]).
+//! - An additive implicit-explicit (IMEX) Runge-Kutta solver [Imex], for problems with a right-hand side split into stiff and non-stiff parts using [OdeEquations::rhs_explicit_inplace] ([ImexTableau::ark436l2sa]).
+//! - A generalized-alpha solver [GeneralizedAlpha] (and the Newmark method) for linear second-order systems `M y'' + C y' + K y = f(t)` defined using the [SecondOrderOde] trait, as found in structural dynamics.
+//! - A second-order Runge-Kutta-Chebyshev solver [Rkc], a stabilized explicit method for mildly stiff problems (e.g. diffusion-dominated PDE discretisations) that chooses its number of stages from an estimate of the spectral radius of the jacobian, so no linear solves are required.
+//! - An exponential Rosenbrock solver [ExponentialIntegrator] for stiff semilinear problems without a mass matrix, where the products of the φ-functions of the jacobian with a vector are approximated in a Krylov subspace, so only jacobian-vector products are required.
+//! - A Crank-Nicolson solver [LinearOdeSolver] for linear problems `M y' = A y` with a constant matrix `A` (built using [OdeBuilder::build_ode_linear]), that assembles `A` once and only re-factorises when the step size changes.
+//! - A solver [AutoLinearSolver] that detects problems with a linear right-hand side and solves them with [LinearOdeSolver], and all other problems with [Bdf].
+//! - Fixed step implicit Euler and BDF2 steppers [FixedLowOrder] for real-time use (e.g. in a control loop), that allocate all their storage up front, do not allocate when stepping and limit the number of Newton iterations per step.
 //! - A BDF solver that wraps the IDA solver solver from the sundials library ([SundialsIda], requires the `sundials` feature).
 //!
 //! See the [OdeSolverMethod] trait for a more detailed description of the available methods on each solver. Possible workflows are:
 //! - Use the [OdeSolverMethod::step] method to step the solution forward in time with an internal time step chosen by the solver to meet the error tolerances.
 //! - Use the [OdeSolverMethod::interpolate] method to interpolate the solution between the last two time steps.
 //! - Use the [OdeSolverMethod::set_stop_time] method to stop the solver at a specific time (i.e. this will override the internal time step so that the solver stops at the specified time).
-//! - Alternatively, use the convenience function [OdeSolverMethod::solve]  that will both initialise the problem and solve the problem up to a specific time.
+//! - Alternatively, use the convenience function [OdeSolverMethod::solve]  that will both initialise the problem and solve the problem up to a specific time, returning the solution at each internal time step
+//!   as an [OdeSolverTrajectory], or [OdeSolverMethod::solve_dense] to return the solution interpolated at a given set of times.
+//!   Setting [OdeSolverOptions::global_error_factor] (or [OdeBuilder::global_error_factor]) makes these functions also estimate the global error at each output time
+//!   (see [OdeSolverTrajectory::global_error]), by solving the problem again with tighter tolerances.
+//! - To integrate backward in time from the initial time (e.g. for terminal-value or adjoint problems), use the [OdeBuilder::backward] option. The step size is then negative,
+//!   and the stop time and interpolation times must be before the current time. This is supported by the [Bdf] and [Sdirk] solvers.
+//! - If the equations have known discontinuities (e.g. dosing events or switching inputs), list their times in [OdeSolverProblem::breakpoints]. The convenience functions
+//!   then stop exactly at each breakpoint and restart the solver from there, and [OdeSolverMethod::solve_dense_with_breakpoints] also lets you modify the state at each breakpoint (e.g. to add an impulse).
+//! - If the equations have conservation laws (e.g. the total mass of a reaction network), use [OdeSolverProblem::with_invariant] to project the solution back onto the
+//!   invariants after each step, so that they are conserved to within the tolerances. This is supported by the [Bdf], [Sdirk] and [Erk] solvers.
 //!
 //! ## DiffSL
 //!
@@ -51,17 +82,32 @@
 //! Generally this requires `n` evaluations of the jacobian action for a system of size `n`, so it is often more efficient if the user can provide the jacobian matrix directly
 //! by also implementing the optional [NonLinearOp::jacobian_inplace] and the [LinearOp::matrix_inplace] (if applicable) functions.
 //!
-//! If this is not possible, DiffSol also provides an experimental feature to calculate sparse jacobians more efficiently by automatically detecting the sparsity pattern of the jacobian and using
-//! colouring \[1\] to reduce the number of jacobian evaluations. You can enable this feature by enabling [OdeBuilder::use_coloring()] option when building the ODE problem.
+//! If this is not possible, DiffSol also provides an experimental feature to calculate sparse jacobians more efficiently by using the sparsity pattern of the jacobian and
+//! colouring \[1\] to reduce the number of jacobian evaluations. Any op that exposes a sparsity pattern via [Op::sparsity] is assembled this way.
+//! For sparse matrix types the sparsity pattern is detected automatically when the ODE problem is built, and for any matrix type it can be given explicitly
+//! using the [OdeBuilder::jacobian_sparsity()] and [OdeBuilder::mass_sparsity()] options.
 //! Note that if your implementation of [NonLinearOp::jac_mul_inplace] uses any control flow that depends on the input vector (e.g. an if statement that depends on the value of `x`),
-//! the sparsity detection may not be accurate and you may need to provide the jacobian matrix directly.
+//! the sparsity detection may not be accurate and you may need to provide the jacobian matrix directly, or give the sparsity pattern explicitly.
+//! Linear operators on 2D tensor-product grids, with a jacobian of the form `A ⊗ I + I ⊗ B`, can be written as a [KroneckerOp], which multiplies by the jacobian
+//! using only the small factors `A` and `B`.
+//!
+//! By default [Bdf] only re-evaluates the jacobian when the Newton iteration fails to converge. If the jacobian changes quickly, it can be re-evaluated
+//! more often by setting a [JacobianUpdatePolicy] with [OdeBuilder::jacobian_update()].
 //!
 //! \[1\] Gebremedhin, A. H., Manne, F., & Pothen, A. (2005). What color is your Jacobian? Graph coloring for computing derivatives. SIAM review, 47(4), 629-705.
 //!
 //! ## Events / Root finding
 //!
 //! DiffSol provides a simple way to detect user-provided events during the integration of the ODEs. You can use this by providing a closure that has a zero-crossing at the event you want to detect, using the [OdeBuilder::build_ode_with_root] builder,
-//! or by providing a [NonLinearOp] that has a zero-crossing at the event you want to detect. To use the root finding feature while integrating with the solver, you can use the return value of [OdeSolverMethod::step] to check if an event has been detected.
+//! or by providing a [NonLinearOp] that has a zero-crossing at the event you want to detect, either as part of your [OdeEquations] or added to an existing problem using [OdeSolverProblem::with_root_fn].
+//! To use the root finding feature while integrating with the solver, you can use the return value of [OdeSolverMethod::step] to check if an event has been detected,
+//! [OdeSolverStopReason::RootFound] gives the index of the root function output that crossed zero and the time of the crossing.
+//! The time of the crossing is located using the [BrentSolver], which can also be used directly for other scalar root finding problems.
+//!
+//! ## Steady states
+//!
+//! To find an equilibrium of the equations (e.g. before running the dynamics from steady state), use [SteadyStateSolver]. This tries a Newton iteration from the initial condition,
+//! falling back to pseudo-transient continuation using one of the ODE solvers (e.g. [Bdf]) if the Newton iteration diverges.
 //!
 //! ## Forward Sensitivity Analysis
 //!
@@ -77,19 +123,59 @@
 //!
 //! The provided linear solvers are:
 //! - [NalgebraLU]: a direct solver that uses the LU decomposition implemented in the [nalgebra](https://nalgebra.org) library.
+//! - [QR]: a direct solver that uses the column-pivoted QR decomposition implemented in the [nalgebra](https://nalgebra.org) library, which returns a least-squares solution if the matrix is (nearly) singular, along with an estimate of its condition number.
+//! - [Cholesky]: a direct solver for symmetric positive-definite matrices that uses the Cholesky decomposition implemented in the [nalgebra](https://nalgebra.org) library. This is chosen with [LinearSolverKind::Cholesky] (see [OdeBuilder::linear_solver]), and is used by the default linear solver for `DMatrix` when the problem is declared symmetric using [OdeBuilder::symmetric] (falling back to the LU decomposition if the matrix is not positive definite).
+//! - [LDLT]: a direct solver for symmetric indefinite matrices (e.g. the saddle-point iteration matrices of constrained DAEs) that uses the Bunch–Kaufman `LDL^T` factorisation with symmetric pivoting.
+//! - [BlockDiagonalLU]: a direct solver for block-diagonal jacobians ([BlockDiagonalMatrix]), that factorises each block independently (optionally in parallel, with the `rayon` feature). This is used by the default linear solver for `DMatrix` when the block sizes are declared using [OdeBuilder::block_sizes].
 //! - [FaerLU]: a direct solver that uses the LU decomposition implemented in the [faer](https://github.com/sarah-ek/faer-rs) library.
+//! - [FaerSparseLU]: a direct solver for sparse matrices ([SparseColMat]) that uses the sparse LU decomposition implemented in the [faer](https://github.com/sarah-ek/faer-rs) library, reusing the symbolic factorisation when the matrix is refactorised.
+//! - [FaerCsrLU]: the same sparse LU decomposition for problems given as CSR matrices (`nalgebra_sparse::CsrMatrix`), which factorises a compressed sparse column copy of the matrix.
+//! - [KLU]: a direct solver for sparse matrices ([SparseColMat]) that uses the KLU solver from [SuiteSparse](https://github.com/DrTimothyAldenDavis/SuiteSparse), refactorising with the same pivot ordering when the matrix is updated (requires the `suitesparse` feature, which enables `faer`).
+//! - [Gmres]: a matrix-free iterative solver that uses the restarted GMRES method, with optional left or right preconditioning using the [Preconditioner] trait ([JacobiPreconditioner], [BlockJacobiPreconditioner], [Ilu0Preconditioner], [SpaiPreconditioner]).
+//! - [Broyden]: wraps another linear solver and applies rank-1 Broyden updates to its factorisation after each Newton iteration, so that the factorisation can be reused for longer before refactorising.
+//! - [BiCgStab] and [Tfqmr]: matrix-free iterative solvers using short-recurrence Krylov methods (BiCGStab and TFQMR), which use a fixed amount of memory and often work well for non-symmetric jacobians, with optional right preconditioning.
 //! - [SundialsLinearSolver]: a linear solver that uses the [sundials](https://computation.llnl.gov/projects/sundials) library (requires the `sundials` feature).
+//! - [CudaLU]: a direct solver for large dense matrices that offloads the LU factorisation and solve to a GPU using the [cuSOLVER](https://docs.nvidia.com/cuda/cusolver/) library (requires the `cuda` feature). This is not a GPU backend for the matrix and vector types: the matrices and vectors of the problem stay on the host, and the jacobian is copied to the device for each factorisation.
+//!
+//! Instead of passing a linear solver to the ODE solver constructors, the linear solver can also be chosen when building the problem using [OdeBuilder::linear_solver] (see [LinearSolverKind]). The default linear solver for the matrix type ([SelectedLinearSolver]) then builds the chosen solver when the problem is set.
+//!
+//! The iterative solvers apply the jacobian using [MatrixFreeLinearisedOp], so a Newton-Krylov iteration never forms the jacobian matrix
+//! (unless required by the preconditioner). Setting `finite_difference` on the solver approximates the jacobian-vector products
+//! using finite differences, so that only the right-hand side function is needed.
 //!
 //! The provided nonlinear solvers are:
-//! - [NewtonNonlinearSolver]: a nonlinear solver that uses the Newton method.
+//! - [NewtonNonlinearSolver]: a nonlinear solver that uses the Newton method. Setting a [LineSearch] in its [NewtonOptions] damps the Newton steps, which helps with difficult
+//!   nonlinear stages (e.g. after an increase in step size) that would otherwise diverge and cause the step to be rejected.
+//! - [FixedPointNonlinearSolver]: a nonlinear solver that uses functional (fixed-point) iteration, which requires no jacobian (suitable for non-stiff problems).
+//! - [AndersonSolver]: Anderson acceleration of the fixed-point iteration, which converges faster than [FixedPointNonlinearSolver] and also requires no jacobian.
+//! - [BroydenSolver]: a quasi-Newton (Broyden) method that builds up an approximate inverse jacobian from rank-1 updates, a cheaper option than the Newton method
+//!   for moderately stiff problems, as no jacobian evaluations or linear solves are required.
+//! - [DoglegSolver]: a trust-region (dogleg) nonlinear solver, which converges from poorer initial guesses than the Newton iteration. This can be used to calculate consistent
+//!   initial conditions for a DAE by setting [OdeBuilder::initialisation_solver].
+//!
+//! For over-determined problems, such as a consistent initialisation with more constraints than algebraic unknowns or fitting a few parameters to data,
+//! the [LmSolver] minimises the sum of squares of the residuals using the Levenberg–Marquardt method.
+//!
+//! The convergence test of the nonlinear solvers can be changed to the affine-invariant natural monotonicity test, which is more robust for badly scaled DAEs,
+//! using [OdeBuilder::convergence_criterion] (see [ConvergenceCriterion]).
+//!
+//! Bounds on the solution (e.g. non-negative concentrations) can be enforced during the Newton iteration of [Bdf] using [OdeSolverProblem::with_constraints] (see [Constraints]).
 //!
 //! ## Matrix and vector types
 //!
 //! When solving ODEs, you will need to choose a matrix and vector type to use. DiffSol uses the following types:
 //! - [nalgebra::DMatrix] and [nalgebra::DVector] from the [nalgebra](https://nalgebra.org) library.
-//! - [faer::Mat] and [faer::Col] from the [faer](https://github.com/sarah-ek/faer-rs) library.
+//! - [faer::Mat] and [faer::Col] from the [faer](https://github.com/sarah-ek/faer-rs) library (requires the `faer` feature, enabled by default). Without this feature faer is not a dependency, and scalar types only need to implement [Scalar].
+//! - `nalgebra_sparse::CsrMatrix` (with [nalgebra::DVector]), a compressed sparse row matrix with a parallel matrix-vector product (requires the `rayon` feature), for use with the iterative linear solvers (e.g. [Gmres]) or [FaerCsrLU].
 //! - [SundialsMatrix] and [SundialsVector] from the [sundials](https://computation.llnl.gov/projects/sundials) library (requires the `sundials` feature).
 //!
+//! For small systems (e.g. pharmacokinetic compartment models), the equations can be written using the static-size nalgebra types [nalgebra::SVector] and [nalgebra::SMatrix],
+//! which do not allocate, by building the problem with [OdeBuilder::build_ode_static]. The solvers still use one of the matrix and vector types above.
+//!
+//! The scalar type of the matrices and vectors is usually `f64`, but can be any type that implements [Scalar] (e.g. `f32`).
+//! For reference solutions to many more digits than `f64`, `scalar::BigFloat` is an arbitrary-precision float (requires the `dashu` feature),
+//! which can be used with the nalgebra types, e.g. with [Extrapolation] and a tight tolerance.
+//!
 //! If you wish to use your own matrix and vector types, you will need to implement the following traits:
 //! - For matrices: [Matrix], [MatrixView], [MatrixViewMut], [DenseMatrix], and [MatrixCommon].
 //! - For vectors: [Vector], [VectorIndex], [VectorView], [VectorViewMut], and [VectorCommon].
@@ -136,8 +222,27 @@ pub mod solver;
 pub mod vector;
 
 use linear_solver::LinearSolver;
-pub use linear_solver::{faer::sparse_lu::FaerSparseLU, FaerLU, NalgebraLU};
+pub use linear_solver::{
+    bicgstab::BiCgStab,
+    broyden::Broyden,
+    cholesky::Cholesky,
+    gmres::{Gmres, PreconditionerSide},
+    kind::{DirectLinearSolver, LinearSolverKind, PreconditionerKind, SelectedLinearSolver},
+    ldlt::LDLT,
+    preconditioner::{
+        BlockJacobiPreconditioner, IdentityPreconditioner, Ilu0Preconditioner,
+        JacobiPreconditioner, Preconditioner, SpaiPreconditioner,
+    },
+    qr::QR,
+    tfqmr::Tfqmr,
+    BlockDiagonalLU, LinearSolverReport, LinearSolverStatistics, NalgebraLU,
+};
+#[cfg(feature = "faer")]
+pub use linear_solver::{faer::csr_lu::FaerCsrLU, faer::sparse_lu::FaerSparseLU, FaerLU};
 
+pub use matrix::block_diagonal::BlockDiagonalMatrix;
+pub use matrix::diagonal::DiagonalMatrix;
+#[cfg(feature = "faer")]
 pub use matrix::sparse_faer::SparseColMat;
 
 #[cfg(feature = "sundials")]
@@ -149,6 +254,12 @@ pub use vector::sundials::SundialsVector;
 #[cfg(feature = "sundials")]
 pub use linear_solver::sundials::SundialsLinearSolver;
 
+#[cfg(feature = "suitesparse")]
+pub use linear_solver::klu::KLU;
+
+#[cfg(feature = "cuda")]
+pub use linear_solver::cuda::CudaLU;
+
 #[cfg(feature = "sundials")]
 pub use ode_solver::sundials::SundialsIda;
 
@@ -156,32 +267,136 @@ pub use ode_solver::sundials::SundialsIda;
 pub use ode_solver::diffsl::DiffSlContext;
 
 pub use matrix::default_solver::DefaultSolver;
+#[cfg(feature = "faer")]
+use matrix::sparsity::{Dense, DenseRef};
 use matrix::{
-    sparsity::Dense, sparsity::DenseRef, sparsity::MatrixSparsity, sparsity::MatrixSparsityRef,
-    DenseMatrix, Matrix, MatrixCommon, MatrixRef, MatrixView, MatrixViewMut,
+    sparsity::MatrixSparsity, sparsity::MatrixSparsityRef, DenseMatrix, Matrix, MatrixCommon,
+    MatrixRef, MatrixView, MatrixViewMut,
+};
+pub use nonlinear_solver::{
+    anderson::AndersonSolver,
+    broyden::BroydenSolver,
+    constraints::Constraints,
+    convergence::ConvergenceCriterion,
+    fixed_point::FixedPointNonlinearSolver,
+    kind::NonLinearSolverKind,
+    levenberg_marquardt::LmSolver,
+    newton::{LineSearch, NewtonNonlinearSolver, NewtonOptions},
+    scalar::BrentSolver,
+    trust_region::DoglegSolver,
+    NonLinearSolverStatistics,
 };
-pub use nonlinear_solver::newton::NewtonNonlinearSolver;
 use nonlinear_solver::{
     convergence::Convergence, convergence::ConvergenceStatus, newton::newton_iteration,
     root::RootFinder, NonLinearSolver,
 };
 pub use ode_solver::{
-    bdf::Bdf, builder::OdeBuilder, equations::OdeEquations, equations::OdeSolverEquations,
-    method::OdeSolverMethod, method::OdeSolverState, method::OdeSolverStopReason,
-    problem::OdeSolverProblem, sdirk::Sdirk, sens_equations::SensEquations,
-    sens_equations::SensInit, sens_equations::SensRhs, tableau::Tableau,
+    adams::Adams,
+    analytic::CompartmentModel,
+    analytic::CompartmentSolver,
+    auto_linear::AutoLinearSolver,
+    bdf::Bdf,
+    builder::OdeBuilder,
+    control::Controller,
+    control::PidController,
+    dae_index::DaeIndex,
+    dae_index::IndexReducedEquations,
+    dae_index::IndexReducedRhs,
+    dosing::Dose,
+    dosing::DosingRegimen,
+    dosing::DosingSchedule,
+    dosing::Infusion,
+    dosing::InfusionEquations,
+    dosing::Occasion,
+    dosing::SteadyStateOptions,
+    dosing::SteadyStateSolution,
+    ensemble::EnsembleStatistics,
+    equations::OdeEquations,
+    equations::OdeSolverEquations,
+    equations::{InvariantEquations, RootEquations},
+    erk::Erk,
+    error_model::ErrorModel,
+    exponential::ExponentialIntegrator,
+    extrapolation::Extrapolation,
+    fixed_low_order::FixedLowOrder,
+    fixed_low_order::FixedLowOrderMethod,
+    generalized_alpha::GeneralizedAlpha,
+    generalized_alpha::GeneralizedAlphaState,
+    imex::Imex,
+    initial_condition::InitialConditionMode,
+    initial_condition::InitialConditionReport,
+    initial_condition::InitialConditionSolver,
+    likelihood::LogLikelihood,
+    linear::LinearOdeSolver,
+    method::OdeSolverMethod,
+    method::OdeSolverState,
+    method::OdeSolverStopReason,
+    method::SolverCapabilities,
+    objective::Observation,
+    objective::ObservationData,
+    objective::PopulationObjective,
+    objective::PopulationResiduals,
+    objective::WeightedResiduals,
+    output::ObservationSchedule,
+    output::OdeSolverTrajectory,
+    output::OutputSolution,
+    population::Covariate,
+    population::Population,
+    population::PopulationRecord,
+    population::PopulationSolution,
+    population::Subject,
+    population::SubjectSolution,
+    problem::JacobianUpdatePolicy,
+    problem::OdeSolverOptions,
+    problem::OdeSolverProblem,
+    problem::StepControl,
+    radau::Radau,
+    reaction::HybridOptions,
+    reaction::HybridProblem,
+    reaction::Reaction,
+    reaction::ReactionNetwork,
+    rkc::Rkc,
+    rosenbrock::Rosenbrock,
+    sampling::ParameterBounds,
+    sampling::SamplingMethod,
+    sampling::SobolSequence,
+    sdirk::Sdirk,
+    second_order::SecondOrderOde,
+    second_order::SecondOrderOdeEquations,
+    sens_equations::SensEquations,
+    sens_equations::SensInit,
+    sens_equations::SensRhs,
+    sobol::SobolAnalysis,
+    sobol::SobolIndices,
+    steady_state::SteadyState,
+    steady_state::SteadyStateSolver,
+    switching::ActiveMethod,
+    switching::SwitchingSolver,
+    tableau::ImexTableau,
+    tableau::RosenbrockTableau,
+    tableau::Tableau,
+    transform::ParameterTransform,
+    transform::ParameterTransforms,
+    transit::TransitChain,
+    uncertainty::MonteCarloSolution,
+    uncertainty::ParameterDistribution,
 };
 pub use op::{
-    closure::Closure, constant_closure::ConstantClosure, linear_closure::LinearClosure,
+    closure::Closure, constant_closure::ConstantClosure, kronecker::KroneckerOp,
+    linear_closure::LinearClosure, linear_rhs::LinearRhs, matrix_free::MatrixFreeLinearisedOp,
     unit::UnitCallable, ConstantOp, LinearOp, NonLinearOp, Op,
 };
 use op::{
     closure_no_jac::ClosureNoJac, closure_with_sens::ClosureWithSens,
-    constant_closure_with_sens::ConstantClosureWithSens, init::InitOp,
+    constant_closure_with_sens::ConstantClosureWithSens, init::InitOp, init::StateInitOp,
     linear_closure_with_sens::LinearClosureWithSens,
 };
+#[cfg(feature = "faer")]
+use scalar::FaerScalar;
 use scalar::{IndexType, Scalar, Scale};
 use solver::SolverProblem;
-use vector::{Vector, VectorCommon, VectorIndex, VectorRef, VectorView, VectorViewMut};
+#[cfg(feature = "faer")]
+use vector::VectorCommon;
+use vector::{Vector, VectorIndex, VectorRef, VectorView, VectorViewMut};
 
 pub use scalar::scale;
@@ -0,0 +1,248 @@
+use std::cell::{Cell, RefCell};
+
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{
+        krylov::KrylovOperator,
+        preconditioner::{IdentityPreconditioner, Preconditioner},
+        LinearSolver, LinearSolverStatistics,
+    },
+    op::NonLinearOp,
+    Scalar, SolverProblem, Vector,
+};
+
+struct BiCgStabWorkspace<V: Vector> {
+    r: V,
+    r0: V,
+    p: V,
+    p_hat: V,
+    v: V,
+    s: V,
+    s_hat: V,
+    t: V,
+    x: V,
+}
+
+/// A matrix-free [LinearSolver] that uses the stabilised bi-conjugate gradient method (BiCGStab), where the operator is only
+/// applied using jacobian-vector products. Unlike [crate::Gmres], BiCGStab uses short recurrences, so the memory used is
+/// fixed (a few vectors) and does not grow with the number of iterations.
+///
+/// An optional [Preconditioner] is applied on the right, i.e. `A P^{-1} u = b` with `x = P^{-1} u`, so the convergence test
+/// is on the true residual: the iteration stops when the 2-norm of the residual is below [Self::tol] times the norm of `b`.
+/// Returns [PSError::LinearPSError] if this is not reached within [Self::max_iter] iterations or the iteration breaks down.
+pub struct BiCgStab<C, P = IdentityPreconditioner>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    /// Maximum number of iterations (default 500)
+    pub max_iter: usize,
+    /// Relative tolerance on the residual (default 1e-10)
+    pub tol: C::T,
+    /// Approximate the jacobian-vector products using finite differences of the operator, so that only calls to the
+    /// operator are required (default false). Takes effect at the next call to [LinearSolver::set_problem]. The products are
+    /// then only accurate to about the square root of machine precision, so [Self::tol] should be loosened to match.
+    pub finite_difference: bool,
+    op: KrylovOperator<C, P>,
+    workspace: RefCell<Option<BiCgStabWorkspace<C::V>>>,
+    number_of_iterations: Cell<usize>,
+}
+
+impl<C> Default for BiCgStab<C, IdentityPreconditioner>
+where
+    C: NonLinearOp,
+{
+    fn default() -> Self {
+        Self::new(IdentityPreconditioner)
+    }
+}
+
+impl<C, P> BiCgStab<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    pub fn new(preconditioner: P) -> Self {
+        Self {
+            max_iter: 500,
+            tol: C::T::cast(1e-10),
+            finite_difference: false,
+            op: KrylovOperator::new(preconditioner),
+            workspace: RefCell::new(None),
+            number_of_iterations: Cell::new(0),
+        }
+    }
+
+    pub fn preconditioner(&self) -> &P {
+        self.op.preconditioner()
+    }
+
+    /// Total number of BiCGStab iterations over all the solves since the problem was set
+    pub fn number_of_iterations(&self) -> usize {
+        self.number_of_iterations.get()
+    }
+}
+
+impl<C, P> LinearSolver<C> for BiCgStab<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        let n = self.op.set_problem(problem, self.finite_difference);
+        self.workspace.replace(Some(BiCgStabWorkspace {
+            r: C::V::zeros(n),
+            r0: C::V::zeros(n),
+            p: C::V::zeros(n),
+            p_hat: C::V::zeros(n),
+            v: C::V::zeros(n),
+            s: C::V::zeros(n),
+            s_hat: C::V::zeros(n),
+            t: C::V::zeros(n),
+            x: C::V::zeros(n),
+        }));
+        self.number_of_iterations.set(0);
+    }
+
+    fn clear_problem(&mut self) {
+        self.op.clear_problem();
+        self.workspace.replace(None);
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.op.set_linearisation(x, t);
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.op.statistics().solve(1, || {
+            self.op.check_setup()?;
+            let mut workspace = self.workspace.borrow_mut();
+            let BiCgStabWorkspace {
+                r,
+                r0,
+                p,
+                p_hat,
+                v,
+                s,
+                s_hat,
+                t,
+                x,
+            } = workspace.as_mut().unwrap();
+            let one = C::T::one();
+            let zero = C::T::zero();
+
+            // initial guess x = 0
+            x.fill(zero.clone());
+            r.copy_from(b);
+            r0.copy_from(b);
+            p.fill(zero.clone());
+            v.fill(zero.clone());
+            let tol = self.tol.clone() * b.norm();
+            let (mut rho, mut alpha, mut omega) = (one.clone(), one.clone(), one.clone());
+            let mut niter = 0;
+            let mut converged = r.norm() <= tol;
+            while !converged && niter < self.max_iter {
+                niter += 1;
+                let rho_new = r0.dot(r);
+                if rho_new == zero || omega == zero {
+                    break;
+                }
+                let beta = (rho_new.clone() / rho) * (alpha / omega.clone());
+                rho = rho_new;
+
+                // p = r + beta (p - omega v)
+                p.axpy(-omega, v, one.clone());
+                p.axpy(one.clone(), r, beta);
+                p_hat.copy_from(p);
+                self.op.precondition(p_hat);
+                self.op.call_inplace(p_hat, v);
+                let r0v = r0.dot(v);
+                if r0v == zero {
+                    break;
+                }
+                alpha = rho.clone() / r0v;
+
+                // s = r - alpha v
+                s.copy_from(r);
+                s.axpy(-alpha.clone(), v, one.clone());
+                if s.norm() <= tol {
+                    x.axpy(alpha, p_hat, one);
+                    converged = true;
+                    break;
+                }
+                s_hat.copy_from(s);
+                self.op.precondition(s_hat);
+                self.op.call_inplace(s_hat, t);
+                let tt = t.dot(t);
+                omega = if tt == zero {
+                    zero.clone()
+                } else {
+                    t.dot(s) / tt
+                };
+
+                x.axpy(alpha.clone(), p_hat, one.clone());
+                x.axpy(omega.clone(), s_hat, one.clone());
+
+                // r = s - omega t
+                r.copy_from(s);
+                r.axpy(-omega.clone(), t, one.clone());
+                converged = r.norm() <= tol;
+            }
+            self.number_of_iterations
+                .set(self.number_of_iterations.get() + niter);
+            if !converged {
+                return Err(PSError::LinearPSError);
+            }
+            b.copy_from(x);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        LinearSolverStatistics {
+            number_of_iterations: self.number_of_iterations(),
+            ..self.op.statistics().get()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::tests::{badly_scaled, convection_diffusion, dense_linear_problem},
+        BiCgStab, IdentityPreconditioner, Ilu0Preconditioner, JacobiPreconditioner, LinearSolver,
+        Preconditioner, Vector,
+    };
+
+    fn solve<P: Preconditioner<DMatrix<f64>>>(
+        a: DMatrix<f64>,
+        preconditioner: P,
+    ) -> Result<usize, PSError> {
+        let n = a.nrows();
+        let (p, solns) = dense_linear_problem(a);
+        let mut s = BiCgStab::new(preconditioner);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b)?;
+        x.assert_eq_st(&solns[0].x, 1e-6);
+        Ok(s.number_of_iterations())
+    }
+
+    #[test]
+    fn test_bicgstab() {
+        let n = 100;
+        let niter = solve(convection_diffusion(n), IdentityPreconditioner).unwrap_or(usize::MAX);
+        let niter_ilu = solve(convection_diffusion(n), Ilu0Preconditioner::default()).unwrap();
+        assert!(niter_ilu <= 2);
+        assert!(niter_ilu < niter);
+
+        let niter = solve(badly_scaled(n), IdentityPreconditioner).unwrap_or(usize::MAX);
+        let niter_jacobi = solve(badly_scaled(n), JacobiPreconditioner::default()).unwrap();
+        assert!(niter_jacobi < niter);
+    }
+}
@@ -0,0 +1,206 @@
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{LinearSolver, LinearSolverReport, LinearSolverStatistics},
+    op::Op,
+    Scalar, SolverProblem, Vector,
+};
+
+/// A [LinearSolver] that wraps another solver and improves its factorisation with rank-1 (Broyden) updates, instead of
+/// refactorising when the jacobian becomes stale.
+///
+/// Each update (see [LinearSolver::secant_update]) is the "good" Broyden update `A <- A + (y - A s) s^T / (s^T s)` of the
+/// factorised matrix, so that it satisfies the secant condition `A s = y`. The [crate::NewtonNonlinearSolver] applies an update
+/// after each Newton iteration, with `s` the Newton step and `y` the resulting change in the residual. The inverse of the
+/// updated matrix is applied using the Sherman–Morrison formula, i.e. a solve with the existing factorisation followed by a
+/// rank-1 correction for each update, so no refactorisation is needed. This improves the convergence of the Newton iteration
+/// as the solution moves away from the point at which the jacobian was evaluated, at the cost of a few vector operations per
+/// update and solve, which is worthwhile for large problems where each factorisation is expensive.
+///
+/// At most [Self::max_rank] updates are applied to a factorisation, any further updates are ignored, and all the updates are
+/// discarded when the matrix is refactorised.
+pub struct Broyden<C, LS>
+where
+    C: Op,
+    LS: LinearSolver<C>,
+{
+    /// Maximum number of rank-1 updates applied to a factorisation (default 4)
+    pub max_rank: usize,
+    linear_solver: LS,
+    // the updates H <- (I + a s^T) H of the inverse H of the matrix, as pairs (a, s)
+    updates: Vec<(C::V, C::V)>,
+}
+
+impl<C, LS> Default for Broyden<C, LS>
+where
+    C: Op,
+    LS: LinearSolver<C> + Default,
+{
+    fn default() -> Self {
+        Self::new(LS::default())
+    }
+}
+
+impl<C, LS> Broyden<C, LS>
+where
+    C: Op,
+    LS: LinearSolver<C>,
+{
+    pub fn new(linear_solver: LS) -> Self {
+        Self {
+            max_rank: 4,
+            linear_solver,
+            updates: Vec::new(),
+        }
+    }
+
+    /// The wrapped linear solver
+    pub fn linear_solver(&self) -> &LS {
+        &self.linear_solver
+    }
+
+    /// Number of updates applied to the current factorisation
+    pub fn rank(&self) -> usize {
+        self.updates.len()
+    }
+}
+
+impl<C, LS> LinearSolver<C> for Broyden<C, LS>
+where
+    C: Op,
+    LS: LinearSolver<C>,
+{
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.updates.clear();
+        self.linear_solver.set_problem(problem);
+    }
+
+    fn clear_problem(&mut self) {
+        self.updates.clear();
+        self.linear_solver.clear_problem();
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.updates.clear();
+        self.linear_solver.set_linearisation(x, t);
+    }
+
+    fn analyze(&mut self) {
+        self.linear_solver.analyze();
+    }
+
+    fn is_analyzed(&self) -> bool {
+        self.linear_solver.is_analyzed()
+    }
+
+    fn refactor(&mut self, x: &C::V, t: C::T) {
+        self.updates.clear();
+        self.linear_solver.refactor(x, t);
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.linear_solver.solve_in_place(b)?;
+        for (a, s) in self.updates.iter() {
+            b.axpy(s.dot(b), a, C::T::one());
+        }
+        Ok(())
+    }
+
+    fn report(&self) -> LinearSolverReport<C::T> {
+        self.linear_solver.report()
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.linear_solver.statistics()
+    }
+
+    fn supports_secant_update(&self) -> bool {
+        true
+    }
+
+    fn secant_update(&mut self, s: &C::V, y: &C::V) -> bool {
+        if self.updates.len() >= self.max_rank {
+            return false;
+        }
+        // H_new = H + (s - H y) s^T H / (s^T H y) = (I + a s^T) H, with a = (s - H y) / (s^T H y)
+        let mut a = y.clone();
+        if self.solve_in_place(&mut a).is_err() {
+            return false;
+        }
+        let denom = s.dot(&a);
+        if denom.clone().abs() <= C::T::EPSILON * s.norm() * a.norm() || denom.is_zero() {
+            return false;
+        }
+        let inv_denom = C::T::one() / denom;
+        a.axpy(inv_denom.clone(), s, -inv_denom);
+        self.updates.push((a, s.clone()));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        linear_solver::tests::{convection_diffusion, dense_linear_problem},
+        ode_solver::test_models::robertson::robertson,
+        Bdf, Broyden, LinearSolver, NalgebraLU, NewtonNonlinearSolver, OdeSolverMethod, Vector,
+    };
+
+    #[test]
+    fn test_broyden() {
+        let n = 10;
+        let a = convection_diffusion(n);
+        let (p, _solns) = dense_linear_problem(a.clone());
+        let mut s = Broyden::new(NalgebraLU::default());
+        s.max_rank = 1;
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+
+        // a single update along v recovers the rank-1 modification B = A + u v^T exactly
+        let u = DVector::from_fn(n, |i, _| 0.1 * (i as f64 + 1.0));
+        let v = DVector::from_fn(n, |i, _| (i as f64).cos());
+        let b_mat = &a + &u * v.transpose();
+        assert!(s.secant_update(&v, &(&b_mat * &v)));
+        assert_eq!(s.rank(), 1);
+        let b = DVector::from_element(n, 1.0);
+        let x = s.solve(&b).unwrap();
+        (&b_mat * &x).assert_eq_st(&b, 1e-10);
+
+        // further updates are ignored once the maximum rank is reached, and all are discarded on refactorisation
+        assert!(!s.secant_update(&u, &(&b_mat * &u)));
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        assert_eq!(s.rank(), 0);
+        (&a * s.solve(&b).unwrap()).assert_eq_st(&b, 1e-10);
+    }
+
+    #[test]
+    fn test_broyden_bdf() {
+        type M = DMatrix<f64>;
+        let (problem, soln) = robertson::<M>();
+        let t = soln.solution_points.last().unwrap().t;
+
+        let mut solver = Bdf::default();
+        let y_expect = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        let niter = solver
+            .get_statistics()
+            .number_of_nonlinear_solver_iterations;
+
+        let nonlinear_solver = NewtonNonlinearSolver::new(Broyden::new(NalgebraLU::default()));
+        let mut solver = Bdf::<M, _, _>::new(nonlinear_solver);
+        let y = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        y.assert_eq(&y_expect, &y_expect.map(|x| 1e-3 * x.abs() + 1e-8));
+        let broyden_niter = solver
+            .get_statistics()
+            .number_of_nonlinear_solver_iterations;
+        assert!(
+            broyden_niter < niter,
+            "{} Newton iterations with Broyden updates, {} without",
+            broyden_niter,
+            niter
+        );
+    }
+}
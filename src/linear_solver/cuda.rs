@@ -0,0 +1,374 @@
+use std::{cell::RefCell, ffi::c_int, rc::Rc, sync::Arc, time::Instant};
+
+use cudarc::{
+    cusolver::sys::{
+        cublasOperation_t, cudaStream_t, cusolverDnCreate, cusolverDnDestroy, cusolverDnDgetrf,
+        cusolverDnDgetrf_bufferSize, cusolverDnDgetrs, cusolverDnHandle_t, cusolverDnSetStream,
+        cusolverStatus_t,
+    },
+    driver::{CudaContext, CudaSlice, CudaStream, DevicePtr, DevicePtrMut, DriverError},
+};
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    errors::PSError,
+    op::{linearise::LinearisedOp, NonLinearOp},
+    LinearOp, Matrix, MatrixSparsityRef, Op, SolverProblem,
+};
+
+use super::{LinearSolver, LinearSolverStatistics, LinearSolverStatisticsRecorder};
+
+fn cusolver_check(status: cusolverStatus_t) -> Result<(), PSError> {
+    match status {
+        cusolverStatus_t::CUSOLVER_STATUS_SUCCESS => Ok(()),
+        status => Err(PSError::LinearSolverError {
+            e: format!("cuSOLVER error: {:?}", status),
+        }),
+    }
+}
+
+fn driver_check<T>(result: Result<T, DriverError>) -> Result<T, PSError> {
+    result.map_err(|e| PSError::LinearSolverError {
+        e: format!("CUDA driver error: {:?}", e),
+    })
+}
+
+// the message of a device error, so it can be returned again by each solve
+fn device_error(e: PSError) -> String {
+    match e {
+        PSError::LinearSolverError { e } => e,
+        e => e.to_string(),
+    }
+}
+
+// an owned cuSOLVER dense handle, destroyed when dropped so that it is not leaked if the setup fails part way through
+struct CusolverHandle(cusolverDnHandle_t);
+
+impl CusolverHandle {
+    fn new() -> Result<Self, PSError> {
+        let mut handle = std::ptr::null_mut();
+        cusolver_check(unsafe { cusolverDnCreate(&mut handle) })?;
+        Ok(Self(handle))
+    }
+}
+
+impl Drop for CusolverHandle {
+    fn drop(&mut self) {
+        unsafe { cusolverDnDestroy(self.0) };
+    }
+}
+
+// a cuSOLVER dense handle, bound to a stream, and the device buffers for the factorisation of an n x n matrix
+struct CudaLUData {
+    stream: Arc<CudaStream>,
+    handle: CusolverHandle,
+    n: usize,
+    a: CudaSlice<f64>,
+    ipiv: CudaSlice<c_int>,
+    work: CudaSlice<f64>,
+    b: RefCell<CudaSlice<f64>>,
+    info: RefCell<CudaSlice<c_int>>,
+}
+
+impl CudaLUData {
+    fn new(ordinal: usize, n: usize) -> Result<Self, PSError> {
+        let ctx = driver_check(CudaContext::new(ordinal))?;
+        let stream = ctx.default_stream();
+        let handle = CusolverHandle::new()?;
+        cusolver_check(unsafe {
+            cusolverDnSetStream(handle.0, stream.cu_stream() as cudaStream_t)
+        })?;
+        let mut a = driver_check(stream.alloc_zeros::<f64>(n * n))?;
+        let mut lwork: c_int = 0;
+        {
+            let (a_ptr, _record_a) = a.device_ptr_mut(&stream);
+            cusolver_check(unsafe {
+                cusolverDnDgetrf_bufferSize(
+                    handle.0,
+                    n as c_int,
+                    n as c_int,
+                    a_ptr as *mut f64,
+                    n as c_int,
+                    &mut lwork,
+                )
+            })?;
+        }
+        let ipiv = driver_check(stream.alloc_zeros::<c_int>(n))?;
+        let work = driver_check(stream.alloc_zeros::<f64>((lwork as usize).max(1)))?;
+        let b = RefCell::new(driver_check(stream.alloc_zeros::<f64>(n))?);
+        let info = RefCell::new(driver_check(stream.alloc_zeros::<c_int>(1))?);
+        Ok(Self {
+            stream,
+            handle,
+            n,
+            a,
+            ipiv,
+            work,
+            b,
+            info,
+        })
+    }
+
+    fn info(&self) -> Result<c_int, PSError> {
+        let info = driver_check(self.stream.memcpy_dtov(&*self.info.borrow()))?;
+        Ok(info[0])
+    }
+
+    // copy the column-major matrix to the device and factorise it in place, returns the LAPACK info value
+    fn factorise(&mut self, matrix: &DMatrix<f64>) -> Result<c_int, PSError> {
+        let n = self.n as c_int;
+        driver_check(self.stream.memcpy_htod(matrix.as_slice(), &mut self.a))?;
+        {
+            let info = &mut *self.info.borrow_mut();
+            let (a_ptr, _record_a) = self.a.device_ptr_mut(&self.stream);
+            let (work_ptr, _record_work) = self.work.device_ptr_mut(&self.stream);
+            let (ipiv_ptr, _record_ipiv) = self.ipiv.device_ptr_mut(&self.stream);
+            let (info_ptr, _record_info) = info.device_ptr_mut(&self.stream);
+            cusolver_check(unsafe {
+                cusolverDnDgetrf(
+                    self.handle.0,
+                    n,
+                    n,
+                    a_ptr as *mut f64,
+                    n,
+                    work_ptr as *mut f64,
+                    ipiv_ptr as *mut c_int,
+                    info_ptr as *mut c_int,
+                )
+            })?;
+        }
+        self.info()
+    }
+
+    fn solve(&self, state: &mut DVector<f64>) -> Result<c_int, PSError> {
+        let n = self.n as c_int;
+        let b = &mut *self.b.borrow_mut();
+        driver_check(self.stream.memcpy_htod(state.as_slice(), b))?;
+        {
+            let info = &mut *self.info.borrow_mut();
+            let (a_ptr, _record_a) = self.a.device_ptr(&self.stream);
+            let (ipiv_ptr, _record_ipiv) = self.ipiv.device_ptr(&self.stream);
+            let (b_ptr, _record_b) = b.device_ptr_mut(&self.stream);
+            let (info_ptr, _record_info) = info.device_ptr_mut(&self.stream);
+            cusolver_check(unsafe {
+                cusolverDnDgetrs(
+                    self.handle.0,
+                    cublasOperation_t::CUBLAS_OP_N,
+                    n,
+                    1,
+                    a_ptr as *const f64,
+                    n,
+                    ipiv_ptr as *const c_int,
+                    b_ptr as *mut f64,
+                    n,
+                    info_ptr as *mut c_int,
+                )
+            })?;
+        }
+        driver_check(self.stream.memcpy_dtoh(&*b, state.as_mut_slice()))?;
+        self.info()
+    }
+}
+
+/// A [LinearSolver] that offloads the dense LU factorisation and solve to a GPU, using the
+/// [cuSOLVER](https://docs.nvidia.com/cuda/cusolver/) library (requires the `cuda` feature). This is useful for problems with a
+/// large dense jacobian (e.g. discretised integro-differential equations), for which the factorisation dominates the cost
+/// of each step.
+///
+/// The jacobian is assembled on the host as a [DMatrix], and copied to the device before each factorisation, which then
+/// stays on the device for all the solves with the same linearisation. The right-hand side and solution vectors are copied
+/// to and from the device for each solve, since the state vectors are [DVector]s on the host. There are no device-resident
+/// [Matrix] or vector types, so only the factorisation and the triangular solves run on the GPU, while the rest of the
+/// iteration of the ODE solver (evaluating the equations and the jacobian, and the vector updates) runs on the host.
+///
+/// The device resources are allocated when the problem is set, using the device given to [Self::new] (device 0 by default).
+/// If this fails (e.g. no CUDA device is available), or the device fails during a factorisation, the error is returned as
+/// [PSError::LinearSolverError] by the next solve.
+pub struct CudaLU<C>
+where
+    C: NonLinearOp<M = DMatrix<f64>, V = DVector<f64>, T = f64>,
+{
+    ordinal: usize,
+    data: Option<CudaLUData>,
+    // the LAPACK info value of the last factorisation, non-zero if the matrix is singular
+    info: Option<c_int>,
+    // the error from setting up the device or the last factorisation, if any
+    error: Option<String>,
+    matrix: Option<DMatrix<f64>>,
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+}
+
+impl<C> Default for CudaLU<C>
+where
+    C: NonLinearOp<M = DMatrix<f64>, V = DVector<f64>, T = f64>,
+{
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<C> CudaLU<C>
+where
+    C: NonLinearOp<M = DMatrix<f64>, V = DVector<f64>, T = f64>,
+{
+    /// Create a new solver that uses the CUDA device with the given ordinal
+    pub fn new(ordinal: usize) -> Self {
+        Self {
+            ordinal,
+            data: None,
+            info: None,
+            error: None,
+            matrix: None,
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+        }
+    }
+}
+
+impl<C> LinearSolver<C> for CudaLU<C>
+where
+    C: NonLinearOp<M = DMatrix<f64>, V = DVector<f64>, T = f64>,
+{
+    fn solve_in_place(&self, state: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            if let Some(e) = self.error.as_ref() {
+                return Err(PSError::LinearSolverError { e: e.clone() });
+            }
+            let data = match (self.data.as_ref(), self.info) {
+                (Some(data), Some(0)) => data,
+                (_, Some(_)) => return Err(PSError::LuFailed),
+                _ => return Err(PSError::LuNotInitialized),
+            };
+            match data.solve(state)? {
+                0 => Ok(()),
+                _ => Err(PSError::LuFailed),
+            }
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn set_linearisation(&mut self, x: &<C as Op>::V, t: <C as Op>::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        // the device failed to set up, so the error from set_problem is returned by the next solve
+        let Some(data) = self.data.as_mut() else {
+            return;
+        };
+        match data.factorise(matrix) {
+            Ok(info) => {
+                self.info = Some(info);
+                self.error = None;
+            }
+            Err(e) => {
+                self.info = None;
+                self.error = Some(device_error(e));
+            }
+        }
+        self.statistics.factorisation(start);
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let ncols = linearised_problem.f.nstates();
+        let nrows = linearised_problem.f.nout();
+        let matrix = C::M::new_from_sparsity(
+            nrows,
+            ncols,
+            linearised_problem.f.sparsity().map(|s| s.to_owned()),
+        );
+        self.data = None;
+        self.error = None;
+        match CudaLUData::new(self.ordinal, nrows) {
+            Ok(data) => self.data = Some(data),
+            Err(e) => {
+                self.error = Some(format!(
+                    "failed to set up CUDA device {}: {}",
+                    self.ordinal,
+                    device_error(e)
+                ))
+            }
+        }
+        self.problem = Some(linearised_problem);
+        self.matrix = Some(matrix);
+        self.info = None;
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.data = None;
+        self.info = None;
+        self.error = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::tests::{convection_diffusion, dense_linear_problem},
+        Bdf, CudaLU, LinearSolver, NewtonNonlinearSolver, OdeBuilder, OdeSolverMethod, Vector,
+    };
+
+    #[test]
+    fn test_cuda_lu() {
+        let n = 50;
+        let (p, solns) = dense_linear_problem(convection_diffusion(n));
+        let mut s = CudaLU::default();
+        s.set_problem(&p);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::LuNotInitialized)
+        ));
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-10);
+
+        // a singular matrix fails to factorise
+        let (p, solns) = dense_linear_problem(DMatrix::zeros(n, n));
+        let mut s = CudaLU::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        assert!(matches!(s.solve(&solns[0].b), Err(PSError::LuFailed)));
+
+        // a device that does not exist is reported by the solve, rather than panicking
+        let (p, solns) = dense_linear_problem(convection_diffusion(n));
+        let mut s = CudaLU::new(1000);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::LinearSolverError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cuda_lu_bdf() {
+        let problem = OdeBuilder::new()
+            .p([0.1])
+            .build_ode::<DMatrix<f64>, _, _, _>(
+                |x, p, _t, y| y.copy_from(&(x * -p[0])),
+                |_x, p, _t, v, y| y.copy_from(&(v * -p[0])),
+                |_p, _t| DVector::from_element(10, 1.0),
+            )
+            .unwrap();
+        let t = 1.0;
+        let mut solver = Bdf::default();
+        let y_expect = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+
+        let nonlinear_solver = NewtonNonlinearSolver::new(CudaLU::default());
+        let mut solver = Bdf::<DMatrix<f64>, _, _>::new(nonlinear_solver);
+        let y = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        y.assert_eq_st(&y_expect, 1e-8);
+    }
+}
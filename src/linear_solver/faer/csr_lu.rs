@@ -0,0 +1,292 @@
+use std::{rc::Rc, time::Instant};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{
+        kind::DirectLinearSolver, LinearSolver, LinearSolverStatistics,
+        LinearSolverStatisticsRecorder,
+    },
+    matrix::sparsity::MatrixSparsityRef,
+    op::linearise::LinearisedOp,
+    scalar::IndexType,
+    solver::SolverProblem,
+    FaerScalar, LinearOp, Matrix, NonLinearOp, Op,
+};
+use faer::{
+    solvers::SpSolver,
+    sparse::{
+        linalg::solvers::{Lu, SymbolicLu},
+        SparseColMat, SymbolicSparseColMat,
+    },
+};
+use nalgebra::DVector;
+use nalgebra_sparse::CsrMatrix;
+
+/// A [LinearSolver] for problems given as compressed sparse row matrices (`nalgebra_sparse::CsrMatrix`), that uses the sparse LU
+/// decomposition in the [`faer`](https://github.com/sarah-ek/faer-rs) library.
+///
+/// The jacobian and mass operators (and so their products with a vector, e.g. in the iterative solvers) stay in CSR format,
+/// only the matrix being factorised (e.g. the iteration matrix `M - c J` of an implicit ODE solver) is copied to a compressed sparse column matrix.
+/// The CSC sparsity pattern, and the position in it of each CSR entry, are computed once by [LinearSolver::set_problem],
+/// so each refactorisation only copies the values. As in [crate::FaerSparseLU], the symbolic factorisation is computed once
+/// and reused for all numeric factorisations.
+pub struct FaerCsrLU<T, C>
+where
+    T: FaerScalar,
+    C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>,
+{
+    lu: Option<Lu<IndexType, T>>,
+    symbolic: Option<SymbolicLu<IndexType>>,
+    // set if the last analysis or factorisation failed, so that the error is returned by the next solve
+    factorisation_failed: bool,
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+    matrix: Option<CsrMatrix<T>>,
+    csc_matrix: Option<SparseColMat<IndexType, T>>,
+    // the index into the values of `csc_matrix` of each value of `matrix`
+    csc_indices: Vec<usize>,
+}
+
+impl<T, C> Default for FaerCsrLU<T, C>
+where
+    T: FaerScalar,
+    C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn default() -> Self {
+        Self {
+            lu: None,
+            symbolic: None,
+            factorisation_failed: false,
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+            matrix: None,
+            csc_matrix: None,
+            csc_indices: Vec::new(),
+        }
+    }
+}
+
+impl<T, C> FaerCsrLU<T, C>
+where
+    T: FaerScalar,
+    C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>,
+{
+    // the CSC matrix with the same sparsity pattern as `matrix`, and the index into its values of each value of `matrix`
+    fn csc_pattern(matrix: &CsrMatrix<T>) -> (SparseColMat<IndexType, T>, Vec<usize>) {
+        let (nrows, ncols) = (matrix.nrows(), matrix.ncols());
+        let mut col_ptrs = vec![0; ncols + 1];
+        for &j in matrix.col_indices() {
+            col_ptrs[j + 1] += 1;
+        }
+        for j in 0..ncols {
+            col_ptrs[j + 1] += col_ptrs[j];
+        }
+        // visiting the rows in order keeps the row indices of each column sorted
+        let mut next = col_ptrs.clone();
+        let mut row_indices = vec![0; matrix.nnz()];
+        let mut csc_indices = vec![0; matrix.nnz()];
+        for ((i, j, _), csc_index) in matrix.triplet_iter().zip(csc_indices.iter_mut()) {
+            row_indices[next[j]] = i;
+            *csc_index = next[j];
+            next[j] += 1;
+        }
+        let symbolic = SymbolicSparseColMat::new_checked(nrows, ncols, col_ptrs, None, row_indices);
+        let values = vec![T::zero(); matrix.nnz()];
+        (SparseColMat::new(symbolic, values), csc_indices)
+    }
+}
+
+impl<T, C> LinearSolver<C> for FaerCsrLU<T, C>
+where
+    T: FaerScalar,
+    C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.refactor(x, t);
+    }
+
+    fn analyze(&mut self) {
+        let matrix = self.csc_matrix.as_ref().expect("Matrix not set");
+        // on failure `symbolic` is left unset, so the analysis is retried (and its failure reported) by the next factorisation
+        self.symbolic = SymbolicLu::try_new(matrix.symbolic()).ok();
+        self.factorisation_failed = self.symbolic.is_none();
+    }
+
+    fn is_analyzed(&self) -> bool {
+        self.symbolic.is_some()
+    }
+
+    fn refactor(&mut self, x: &C::V, t: C::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        let csc_matrix = self.csc_matrix.as_mut().unwrap();
+        let csc_values = csc_matrix.values_mut();
+        for (&k, &v) in self.csc_indices.iter().zip(matrix.values().iter()) {
+            csc_values[k] = v;
+        }
+        if self.symbolic.is_none() {
+            self.analyze();
+        }
+        // on failure `lu` is left unset and the error is returned by the solve, so the caller can recover
+        // (e.g. by reducing the step size)
+        let csc_matrix = self.csc_matrix.as_ref().unwrap().as_ref();
+        self.lu = self
+            .symbolic
+            .clone()
+            .and_then(|symbolic| Lu::try_new_with_symbolic(symbolic, csc_matrix).ok());
+        self.factorisation_failed = self.lu.is_none();
+        self.statistics.factorisation(start);
+    }
+
+    fn solve_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            if self.factorisation_failed {
+                return Err(PSError::LuFailed);
+            }
+            if self.lu.is_none() {
+                return Err(PSError::LuNotInitialized);
+            }
+            let lu = self.lu.as_ref().unwrap();
+            let n = x.len();
+            lu.solve_in_place(faer::mat::from_column_major_slice_mut::<T>(
+                x.as_mut_slice(),
+                n,
+                1,
+            ));
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let ncols = linearised_problem.f.nstates();
+        let nrows = linearised_problem.f.nout();
+        let matrix = C::M::new_from_sparsity(
+            nrows,
+            ncols,
+            linearised_problem
+                .f
+                .sparsity()
+                .map(|s| MatrixSparsityRef::<CsrMatrix<T>>::to_owned(&s)),
+        );
+        let same_pattern = self
+            .matrix
+            .as_ref()
+            .is_some_and(|old| old.pattern() == matrix.pattern());
+        if !same_pattern {
+            let (csc_matrix, csc_indices) = Self::csc_pattern(&matrix);
+            self.csc_matrix = Some(csc_matrix);
+            self.csc_indices = csc_indices;
+            self.symbolic = None;
+        }
+        self.problem = Some(linearised_problem);
+        self.matrix = Some(matrix);
+        self.lu = None;
+        self.factorisation_failed = false;
+    }
+
+    fn clear_problem(&mut self) {
+        // the matrices and symbolic analysis hold no references to the problem, so they are kept for the next problem
+        self.problem = None;
+        self.lu = None;
+        self.factorisation_failed = false;
+    }
+}
+
+impl<T, C> DirectLinearSolver<C> for FaerCsrLU<T, C>
+where
+    T: FaerScalar,
+    C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{errors::PSError, op::closure::Closure, LinearSolver, SolverProblem, Vector};
+    use nalgebra::DVector;
+    use nalgebra_sparse::CsrMatrix;
+
+    use super::FaerCsrLU;
+
+    #[test]
+    fn test_csr_lu() {
+        // f_i(x) = x_i^2 + x_{i+1}, so the jacobian is upper bidiagonal, and its CSC copy has a different ordering of the values
+        type M = CsrMatrix<f64>;
+        let n = 3;
+        let mut op = Closure::<M, _, _>::new(
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+                for i in 0..x.len() {
+                    y[i] = x[i] * x[i] + if i + 1 < x.len() { x[i + 1] } else { 0.0 };
+                }
+            },
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                for i in 0..x.len() {
+                    y[i] = 2.0 * x[i] * v[i] + if i + 1 < x.len() { v[i + 1] } else { 0.0 };
+                }
+            },
+            n,
+            n,
+            Rc::new(DVector::zeros(0)),
+        );
+        let x0 = DVector::from_element(n, 1.0);
+        op.calculate_sparsity(&x0, 0.0);
+        let atol = Rc::new(DVector::from_element(n, 1e-6));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-6);
+        let mut solver = FaerCsrLU::default();
+        solver.set_problem(&problem);
+        // J = diag(2a) + superdiag(1), so J [1, 2, 3] = [2a + 2, 4a + 3, 6a]
+        let expect = DVector::from_vec(vec![1.0, 2.0, 3.0]);
+        for a in [1.0, 2.0, 3.0] {
+            solver.set_linearisation(&DVector::from_element(n, a), 0.0);
+            let b = DVector::from_vec(vec![2.0 * a + 2.0, 4.0 * a + 3.0, 6.0 * a]);
+            let x = solver.solve(&b).unwrap();
+            x.assert_eq_st(&expect, 1e-12);
+        }
+        assert!(solver.is_analyzed());
+
+        // setting the same problem again keeps the symbolic analysis
+        solver.set_problem(&problem);
+        assert!(solver.is_analyzed());
+    }
+
+    #[test]
+    fn test_csr_lu_singular() {
+        // f(x) = [x_0, 0], so the jacobian has an empty row and cannot be factorised
+        type M = CsrMatrix<f64>;
+        let n = 2;
+        let mut op = Closure::<M, _, _>::new(
+            |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+                y[0] = x[0];
+                y[1] = 0.0;
+            },
+            |_x: &DVector<f64>, _p: &DVector<f64>, _t, v: &DVector<f64>, y: &mut DVector<f64>| {
+                y[0] = v[0];
+                y[1] = 0.0;
+            },
+            n,
+            n,
+            Rc::new(DVector::zeros(0)),
+        );
+        op.calculate_sparsity(&DVector::from_element(n, 1.0), 0.0);
+        let atol = Rc::new(DVector::from_element(n, 1e-6));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-6);
+        let mut solver = FaerCsrLU::default();
+        solver.set_problem(&problem);
+        solver.set_linearisation(&DVector::from_element(n, 1.0), 0.0);
+        assert!(matches!(
+            solver.solve(&DVector::from_element(n, 1.0)),
+            Err(PSError::LuFailed)
+        ));
+    }
+}
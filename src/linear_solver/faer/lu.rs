@@ -1,8 +1,13 @@
-use std::rc::Rc;
+use std::{rc::Rc, time::Instant};
 
 use crate::{
-    linear_solver::LinearSolver, op::linearise::LinearisedOp, solver::SolverProblem, LinearOp,
-    Matrix, MatrixSparsityRef, NonLinearOp, Op, Scalar,
+    linear_solver::{
+        kind::DirectLinearSolver, LinearSolver, LinearSolverStatistics,
+        LinearSolverStatisticsRecorder,
+    },
+    op::linearise::LinearisedOp,
+    solver::SolverProblem,
+    FaerScalar, LinearOp, Matrix, MatrixSparsityRef, NonLinearOp, Op,
 };
 use faer::{linalg::solvers::FullPivLu, solvers::SpSolver, Col, Mat};
 
@@ -10,48 +15,59 @@ use crate::errors::PSError;
 /// A [LinearSolver] that uses the LU decomposition in the [`faer`](https://github.com/sarah-ek/faer-rs) library to solve the linear system.
 pub struct LU<T, C>
 where
-    T: Scalar,
+    T: FaerScalar,
     C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>,
 {
     lu: Option<FullPivLu<T>>,
+    statistics: LinearSolverStatisticsRecorder,
     problem: Option<SolverProblem<LinearisedOp<C>>>,
     matrix: Option<Mat<T>>,
 }
 
 impl<T, C> Default for LU<T, C>
 where
-    T: Scalar,
+    T: FaerScalar,
     C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>,
 {
     fn default() -> Self {
         Self {
             lu: None,
+            statistics: LinearSolverStatisticsRecorder::default(),
             problem: None,
             matrix: None,
         }
     }
 }
 
-impl<T: Scalar, C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> LinearSolver<C> for LU<T, C> {
+impl<T: FaerScalar, C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> LinearSolver<C> for LU<T, C> {
     fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        let start = Instant::now();
         Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
             .unwrap()
             .set_x(x);
         let matrix = self.matrix.as_mut().expect("Matrix not set");
         self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
         self.lu = Some(matrix.full_piv_lu());
+        self.statistics.factorisation(start);
     }
 
     fn solve_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
-        if self.lu.is_none() {
-            return Err(PSError::LuNotInitialized);
-        }
-        let lu = self.lu.as_ref().unwrap();
-        lu.solve_in_place(x);
-        Ok(())
+        self.statistics.solve(1, || {
+            if self.lu.is_none() {
+                return Err(PSError::LuNotInitialized);
+            }
+            let lu = self.lu.as_ref().unwrap();
+            lu.solve_in_place(x);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
     }
 
     fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
         let linearised_problem = problem.linearise();
         let ncols = linearised_problem.f.nstates();
         let nrows = linearised_problem.f.nout();
@@ -63,4 +79,14 @@ impl<T: Scalar, C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> LinearSolver<C> f
         self.problem = Some(linearised_problem);
         self.matrix = Some(matrix);
     }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.lu = None;
+    }
+}
+
+impl<T: FaerScalar, C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> DirectLinearSolver<C>
+    for LU<T, C>
+{
 }
@@ -1,2 +1,3 @@
+pub mod csr_lu;
 pub mod lu;
 pub mod sparse_lu;
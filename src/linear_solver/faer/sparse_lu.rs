@@ -1,59 +1,124 @@
-use std::rc::Rc;
+use std::{rc::Rc, time::Instant};
 
 use crate::{
-    errors::PSError, linear_solver::LinearSolver, matrix::sparsity::MatrixSparsityRef,
-    op::linearise::LinearisedOp, scalar::IndexType, solver::SolverProblem, LinearOp, Matrix,
-    NonLinearOp, Op, Scalar, SparseColMat,
+    errors::PSError,
+    linear_solver::{
+        kind::DirectLinearSolver, LinearSolver, LinearSolverStatistics,
+        LinearSolverStatisticsRecorder,
+    },
+    matrix::sparsity::MatrixSparsityRef,
+    op::linearise::LinearisedOp,
+    scalar::IndexType,
+    solver::SolverProblem,
+    FaerScalar, LinearOp, Matrix, NonLinearOp, Op, SparseColMat,
+};
+use faer::{
+    solvers::SpSolver,
+    sparse::linalg::solvers::{Lu, SymbolicLu},
+    Col,
 };
-use faer::{solvers::SpSolver, sparse::linalg::solvers::Lu, Col};
 
-/// A [LinearSolver] that uses the LU decomposition in the [`faer`](https://github.com/sarah-ek/faer-rs) library to solve the linear system.
+/// A [LinearSolver] that uses the sparse LU decomposition in the [`faer`](https://github.com/sarah-ek/faer-rs) library to solve the linear system.
+///
+/// The sparsity pattern of the matrix is fixed by [LinearSolver::set_problem], so the symbolic factorisation (fill-reducing
+/// ordering and elimination tree) is computed by [LinearSolver::analyze] (or at the first call to [LinearSolver::set_linearisation])
+/// and reused for all subsequent numeric factorisations, e.g. when the jacobian is updated during the Newton iterations of an
+/// implicit ODE solver. It is also kept if the problem is set again with the same sparsity pattern.
 pub struct FaerSparseLU<T, C>
 where
-    T: Scalar,
+    T: FaerScalar,
     C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>,
 {
     lu: Option<Lu<IndexType, T>>,
+    symbolic: Option<SymbolicLu<IndexType>>,
+    // set if the last analysis or factorisation failed, so that the error is returned by the next solve
+    factorisation_failed: bool,
+    statistics: LinearSolverStatisticsRecorder,
     problem: Option<SolverProblem<LinearisedOp<C>>>,
     matrix: Option<SparseColMat<T>>,
 }
 
 impl<T, C> Default for FaerSparseLU<T, C>
 where
-    T: Scalar,
+    T: FaerScalar,
     C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>,
 {
     fn default() -> Self {
         Self {
             lu: None,
+            symbolic: None,
+            factorisation_failed: false,
+            statistics: LinearSolverStatisticsRecorder::default(),
             problem: None,
             matrix: None,
         }
     }
 }
 
-impl<T: Scalar, C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> LinearSolver<C>
+impl<T: FaerScalar, C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> LinearSolver<C>
     for FaerSparseLU<T, C>
 {
     fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.refactor(x, t);
+    }
+
+    fn analyze(&mut self) {
+        let matrix = self
+            .matrix
+            .as_ref()
+            .expect("Matrix not set")
+            .faer()
+            .as_ref();
+        // on failure `symbolic` is left unset, so the analysis is retried (and its failure reported) by the next factorisation
+        self.symbolic = SymbolicLu::try_new(matrix.symbolic()).ok();
+        self.factorisation_failed = self.symbolic.is_none();
+    }
+
+    fn is_analyzed(&self) -> bool {
+        self.symbolic.is_some()
+    }
+
+    fn refactor(&mut self, x: &C::V, t: C::T) {
+        let start = Instant::now();
         Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
             .unwrap()
             .set_x(x);
         let matrix = self.matrix.as_mut().expect("Matrix not set");
         self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
-        self.lu = Some(matrix.faer().sp_lu().unwrap());
+        if self.symbolic.is_none() {
+            self.analyze();
+        }
+        // on failure `lu` is left unset and the error is returned by the solve, so the caller can recover
+        // (e.g. by reducing the step size)
+        let matrix = self.matrix.as_ref().unwrap().faer().as_ref();
+        self.lu = self
+            .symbolic
+            .clone()
+            .and_then(|symbolic| Lu::try_new_with_symbolic(symbolic, matrix).ok());
+        self.factorisation_failed = self.lu.is_none();
+        self.statistics.factorisation(start);
     }
 
     fn solve_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
-        if self.lu.is_none() {
-            return Err(PSError::LuNotInitialized);
-        }
-        let lu = self.lu.as_ref().unwrap();
-        lu.solve_in_place(x);
-        Ok(())
+        self.statistics.solve(1, || {
+            if self.factorisation_failed {
+                return Err(PSError::LuFailed);
+            }
+            if self.lu.is_none() {
+                return Err(PSError::LuNotInitialized);
+            }
+            let lu = self.lu.as_ref().unwrap();
+            lu.solve_in_place(x);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
     }
 
     fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
         let linearised_problem = problem.linearise();
         let ncols = linearised_problem.f.nstates();
         let nrows = linearised_problem.f.nout();
@@ -65,7 +130,125 @@ impl<T: Scalar, C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> LinearSo
                 .sparsity()
                 .map(|s| MatrixSparsityRef::<SparseColMat<T>>::to_owned(&s)),
         );
+        let same_pattern = self.matrix.as_ref().is_some_and(|old| {
+            let (old, new) = (old.faer(), matrix.faer());
+            old.nrows() == new.nrows()
+                && old.col_ptrs() == new.col_ptrs()
+                && old.row_indices() == new.row_indices()
+        });
+        if !same_pattern {
+            self.symbolic = None;
+        }
         self.problem = Some(linearised_problem);
         self.matrix = Some(matrix);
+        self.lu = None;
+        self.factorisation_failed = false;
+    }
+
+    fn clear_problem(&mut self) {
+        // the matrix and symbolic analysis hold no references to the problem, so they are kept for the next problem
+        self.problem = None;
+        self.lu = None;
+        self.factorisation_failed = false;
+    }
+}
+
+impl<T: FaerScalar, C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> DirectLinearSolver<C>
+    for FaerSparseLU<T, C>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        errors::PSError, op::closure::Closure, LinearSolver, SolverProblem, SparseColMat, Vector,
+    };
+    use faer::Col;
+
+    use super::FaerSparseLU;
+
+    #[test]
+    fn test_sparse_lu_refactorisation() {
+        // f_i(x) = x_i^2 + x_{i-1}, so the jacobian is lower bidiagonal and changes with x but its sparsity pattern does not
+        type M = SparseColMat<f64>;
+        let n = 3;
+        let mut op = Closure::<M, _, _>::new(
+            |x: &Col<f64>, _p: &Col<f64>, _t, y: &mut Col<f64>| {
+                for i in 0..x.len() {
+                    y[i] = x[i] * x[i] + if i > 0 { x[i - 1] } else { 0.0 };
+                }
+            },
+            |x: &Col<f64>, _p: &Col<f64>, _t, v: &Col<f64>, y: &mut Col<f64>| {
+                for i in 0..x.len() {
+                    y[i] = 2.0 * x[i] * v[i] + if i > 0 { v[i - 1] } else { 0.0 };
+                }
+            },
+            n,
+            n,
+            Rc::new(Col::zeros(0)),
+        );
+        let x0 = Col::from_vec(vec![1.0; n]);
+        op.calculate_sparsity(&x0, 0.0);
+        let atol = Rc::new(Col::from_vec(vec![1e-6; n]));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-6);
+        let mut solver = FaerSparseLU::default();
+        solver.set_problem(&problem);
+        let expect = Col::from_vec(vec![1.0; n]);
+        for a in [1.0, 2.0, 3.0] {
+            // J = diag(2a) + subdiag(1), so J [1, 1, 1] = [2a, 2a + 1, 2a + 1]
+            solver.set_linearisation(&Col::from_vec(vec![a; n]), 0.0);
+            let b = Col::from_vec(vec![2.0 * a, 2.0 * a + 1.0, 2.0 * a + 1.0]);
+            let x = solver.solve(&b).unwrap();
+            x.assert_eq_st(&expect, 1e-12);
+        }
+        assert!(solver.is_analyzed());
+
+        // setting the same problem again keeps the symbolic analysis
+        solver.set_problem(&problem);
+        assert!(solver.is_analyzed());
+        solver.refactor(&Col::from_vec(vec![1.0; n]), 0.0);
+        let x = solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).unwrap();
+        x.assert_eq_st(&expect, 1e-12);
+
+        // clearing the problem (e.g. to update the parameters of the equations) also keeps it
+        solver.clear_problem();
+        assert!(solver.is_analyzed());
+        solver.set_problem(&problem);
+        assert!(solver.is_analyzed());
+        solver.refactor(&Col::from_vec(vec![1.0; n]), 0.0);
+        let x = solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).unwrap();
+        x.assert_eq_st(&expect, 1e-12);
+    }
+
+    #[test]
+    fn test_sparse_lu_singular() {
+        // f(x) = [x_0, 0], so the jacobian has an empty row and cannot be factorised
+        type M = SparseColMat<f64>;
+        let n = 2;
+        let mut op = Closure::<M, _, _>::new(
+            |x: &Col<f64>, _p: &Col<f64>, _t, y: &mut Col<f64>| {
+                y[0] = x[0];
+                y[1] = 0.0;
+            },
+            |_x: &Col<f64>, _p: &Col<f64>, _t, v: &Col<f64>, y: &mut Col<f64>| {
+                y[0] = v[0];
+                y[1] = 0.0;
+            },
+            n,
+            n,
+            Rc::new(Col::zeros(0)),
+        );
+        op.calculate_sparsity(&Col::from_vec(vec![1.0; n]), 0.0);
+        let atol = Rc::new(Col::from_vec(vec![1e-6; n]));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-6);
+        let mut solver = FaerSparseLU::default();
+        solver.set_problem(&problem);
+        solver.set_linearisation(&Col::from_vec(vec![1.0; n]), 0.0);
+        assert!(matches!(
+            solver.solve(&Col::from_vec(vec![1.0; n])),
+            Err(PSError::LuFailed)
+        ));
     }
 }
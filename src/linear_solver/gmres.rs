@@ -0,0 +1,329 @@
+use std::cell::{Cell, RefCell};
+
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{
+        krylov::KrylovOperator,
+        preconditioner::{IdentityPreconditioner, Preconditioner},
+        LinearSolver, LinearSolverStatistics,
+    },
+    op::NonLinearOp,
+    scalar::scale,
+    Scalar, SolverProblem, Vector,
+};
+
+/// Which side the preconditioner `P` is applied to in [Gmres].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreconditionerSide {
+    /// Solve `P^{-1} A x = P^{-1} b`, the convergence test is on the preconditioned residual `P^{-1} (b - A x)`.
+    Left,
+    /// Solve `A P^{-1} u = b` with `x = P^{-1} u`, the convergence test is on the true residual `b - A x`.
+    Right,
+}
+
+// storage for the Krylov basis and the Hessenberg least-squares problem, allocated in set_problem
+struct GmresWorkspace<V: Vector> {
+    basis: Vec<V>,
+    // column-major (restart + 1) x restart upper Hessenberg matrix
+    hessenberg: Vec<V::T>,
+    cs: Vec<V::T>,
+    sn: Vec<V::T>,
+    g: Vec<V::T>,
+    w: V,
+    tmp: V,
+    x: V,
+}
+
+/// A matrix-free [LinearSolver] that uses the restarted GMRES method, where the operator is only applied using jacobian-vector products.
+/// An optional [Preconditioner] (e.g. [crate::JacobiPreconditioner] or [crate::Ilu0Preconditioner]) is set up each time the
+/// linearisation is updated, using the assembled jacobian (this is skipped for [IdentityPreconditioner]), and applied on
+/// the left or right (see [PreconditionerSide]).
+///
+/// The iteration stops when the 2-norm of the (preconditioned) residual is below [Self::tol] times its initial value, and
+/// returns [PSError::LinearPSError] if this is not reached within [Self::max_iter] iterations.
+pub struct Gmres<C, P = IdentityPreconditioner>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    /// Number of iterations before restarting (default 30)
+    pub restart: usize,
+    /// Maximum total number of iterations (default 500)
+    pub max_iter: usize,
+    /// Relative tolerance on the residual (default 1e-10)
+    pub tol: C::T,
+    /// Approximate the jacobian-vector products using finite differences of the operator, so that only calls to the
+    /// operator are required (default false). Takes effect at the next call to [LinearSolver::set_problem]. The products are
+    /// then only accurate to about the square root of machine precision, so [Self::tol] should be loosened to match.
+    pub finite_difference: bool,
+    op: KrylovOperator<C, P>,
+    side: PreconditionerSide,
+    workspace: RefCell<Option<GmresWorkspace<C::V>>>,
+    number_of_iterations: Cell<usize>,
+}
+
+impl<C> Default for Gmres<C, IdentityPreconditioner>
+where
+    C: NonLinearOp,
+{
+    fn default() -> Self {
+        Self::new(IdentityPreconditioner, PreconditionerSide::Right)
+    }
+}
+
+impl<C, P> Gmres<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    pub fn new(preconditioner: P, side: PreconditionerSide) -> Self {
+        Self {
+            restart: 30,
+            max_iter: 500,
+            tol: C::T::cast(1e-10),
+            finite_difference: false,
+            op: KrylovOperator::new(preconditioner),
+            side,
+            workspace: RefCell::new(None),
+            number_of_iterations: Cell::new(0),
+        }
+    }
+
+    pub fn preconditioner(&self) -> &P {
+        self.op.preconditioner()
+    }
+
+    /// Total number of GMRES iterations over all the solves since the problem was set
+    pub fn number_of_iterations(&self) -> usize {
+        self.number_of_iterations.get()
+    }
+
+    // y = A x (right preconditioning: y = A P^{-1} x, left preconditioning: y = P^{-1} A x)
+    fn apply_operator(&self, x: &C::V, y: &mut C::V, tmp: &mut C::V) {
+        match self.side {
+            PreconditionerSide::Left => {
+                self.op.call_inplace(x, y);
+                self.op.precondition(y);
+            }
+            PreconditionerSide::Right => {
+                tmp.copy_from(x);
+                self.op.precondition(tmp);
+                self.op.call_inplace(tmp, y);
+            }
+        }
+    }
+
+    // r = b - A x, preconditioned on the left if required
+    fn residual(&self, b: &C::V, x: &C::V, r: &mut C::V) {
+        self.op.call_inplace(x, r);
+        r.axpy(C::T::one(), b, -C::T::one());
+        if self.side == PreconditionerSide::Left {
+            self.op.precondition(r);
+        }
+    }
+}
+
+impl<C, P> LinearSolver<C> for Gmres<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        let n = self.op.set_problem(problem, self.finite_difference);
+        let m = self.restart;
+        self.workspace.replace(Some(GmresWorkspace {
+            basis: (0..=m).map(|_| C::V::zeros(n)).collect(),
+            hessenberg: vec![C::T::zero(); (m + 1) * m],
+            cs: vec![C::T::zero(); m],
+            sn: vec![C::T::zero(); m],
+            g: vec![C::T::zero(); m + 1],
+            w: C::V::zeros(n),
+            tmp: C::V::zeros(n),
+            x: C::V::zeros(n),
+        }));
+        self.number_of_iterations.set(0);
+    }
+
+    fn clear_problem(&mut self) {
+        self.op.clear_problem();
+        self.workspace.replace(None);
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.op.set_linearisation(x, t);
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.op.statistics().solve(1, || {
+            self.op.check_setup()?;
+            let mut workspace = self.workspace.borrow_mut();
+            let GmresWorkspace {
+                basis,
+                hessenberg,
+                cs,
+                sn,
+                g,
+                w,
+                tmp,
+                x,
+            } = workspace.as_mut().unwrap();
+            let m = self.restart;
+            let h = |i: usize, j: usize| i + j * (m + 1);
+
+            // initial guess x = 0
+            x.fill(C::T::zero());
+            basis[0].copy_from(b);
+            if self.side == PreconditionerSide::Left {
+                self.op.precondition(&mut basis[0]);
+            }
+            let mut beta = basis[0].norm();
+            let tol = self.tol.clone() * beta.clone();
+            let mut niter = 0;
+            while beta > tol {
+                if niter >= self.max_iter {
+                    self.number_of_iterations
+                        .set(self.number_of_iterations.get() + niter);
+                    return Err(PSError::LinearPSError);
+                }
+
+                // Arnoldi iteration, the least-squares problem is kept in upper triangular form using Givens rotations
+                basis[0] *= scale(C::T::one() / beta.clone());
+                g.fill(C::T::zero());
+                g[0] = beta;
+                let mut k = 0;
+                while k < m && niter < self.max_iter {
+                    let (done, rest) = basis.split_at_mut(k + 1);
+                    let v = &mut rest[0];
+                    self.apply_operator(&done[k], v, tmp);
+                    for (i, vi) in done.iter().enumerate() {
+                        let hij = v.dot(vi);
+                        hessenberg[h(i, k)] = hij.clone();
+                        v.axpy(-hij, vi, C::T::one());
+                    }
+                    let hnorm = v.norm();
+                    hessenberg[h(k + 1, k)] = hnorm.clone();
+                    if hnorm > C::T::zero() {
+                        *v *= scale(C::T::one() / hnorm.clone());
+                    }
+
+                    for i in 0..k {
+                        let (a, b) = (hessenberg[h(i, k)].clone(), hessenberg[h(i + 1, k)].clone());
+                        hessenberg[h(i, k)] = cs[i].clone() * a.clone() + sn[i].clone() * b.clone();
+                        hessenberg[h(i + 1, k)] = -sn[i].clone() * a + cs[i].clone() * b;
+                    }
+                    let (a, b) = (hessenberg[h(k, k)].clone(), hnorm.clone());
+                    let r = (a.clone() * a.clone() + b.clone() * b.clone()).sqrt();
+                    cs[k] = a / r.clone();
+                    sn[k] = b / r.clone();
+                    hessenberg[h(k, k)] = r;
+                    hessenberg[h(k + 1, k)] = C::T::zero();
+                    g[k + 1] = -sn[k].clone() * g[k].clone();
+                    g[k] *= cs[k].clone();
+
+                    k += 1;
+                    niter += 1;
+                    if g[k].clone().abs() <= tol || hnorm == C::T::zero() {
+                        break;
+                    }
+                }
+
+                // solve the upper triangular system H y = g (in place in g) and update the solution
+                for i in (0..k).rev() {
+                    let mut gi = g[i].clone();
+                    for j in i + 1..k {
+                        gi -= hessenberg[h(i, j)].clone() * g[j].clone();
+                    }
+                    g[i] = gi / hessenberg[h(i, i)].clone();
+                }
+                w.fill(C::T::zero());
+                for (i, vi) in basis.iter().take(k).enumerate() {
+                    w.axpy(g[i].clone(), vi, C::T::one());
+                }
+                if self.side == PreconditionerSide::Right {
+                    self.op.precondition(w);
+                }
+                x.axpy(C::T::one(), w, C::T::one());
+
+                // restart from the residual of the current solution
+                self.residual(b, x, &mut basis[0]);
+                beta = basis[0].norm();
+            }
+            self.number_of_iterations
+                .set(self.number_of_iterations.get() + niter);
+            b.copy_from(x);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        LinearSolverStatistics {
+            number_of_iterations: self.number_of_iterations(),
+            ..self.op.statistics().get()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::tests::{badly_scaled, convection_diffusion, dense_linear_problem},
+        Gmres, IdentityPreconditioner, Ilu0Preconditioner, JacobiPreconditioner, LinearSolver,
+        Preconditioner, PreconditionerSide, Vector,
+    };
+
+    type M = DMatrix<f64>;
+
+    // solve with a known solution, returning the number of GMRES iterations
+    fn solve<P: Preconditioner<M>>(
+        a: M,
+        preconditioner: P,
+        side: PreconditionerSide,
+    ) -> Result<usize, PSError> {
+        let n = a.nrows();
+        let (p, solns) = dense_linear_problem(a);
+        let mut s = Gmres::new(preconditioner, side);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b)?;
+        x.assert_eq_st(&solns[0].x, 1e-6);
+        Ok(s.number_of_iterations())
+    }
+
+    #[test]
+    fn test_gmres() {
+        // without a restart GMRES converges in at most n iterations
+        let niter = solve(
+            convection_diffusion(10),
+            IdentityPreconditioner,
+            PreconditionerSide::Right,
+        );
+        assert!(niter.unwrap() <= 10);
+
+        // for larger problems GMRES(30) stagnates without preconditioning
+        let n = 100;
+        let niter = solve(
+            convection_diffusion(n),
+            IdentityPreconditioner,
+            PreconditionerSide::Right,
+        );
+        assert!(matches!(niter, Err(PSError::LinearPSError)));
+
+        for side in [PreconditionerSide::Left, PreconditionerSide::Right] {
+            // ILU(0) is an exact factorisation of a tridiagonal matrix, so GMRES converges in a single iteration
+            let niter = solve(convection_diffusion(n), Ilu0Preconditioner::default(), side);
+            assert!(niter.unwrap() <= 2);
+
+            // Jacobi preconditioning undoes the bad scaling of the diagonal
+            let niter = solve(badly_scaled(n), IdentityPreconditioner, side).unwrap_or(usize::MAX);
+            let niter_jacobi =
+                solve(badly_scaled(n), JacobiPreconditioner::default(), side).unwrap();
+            assert!(niter_jacobi < niter);
+        }
+    }
+}
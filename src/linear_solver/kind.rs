@@ -0,0 +1,594 @@
+#[cfg(feature = "nalgebra")]
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{
+        bicgstab::BiCgStab,
+        gmres::{Gmres, PreconditionerSide},
+        preconditioner::{
+            BlockJacobiPreconditioner, IdentityPreconditioner, Ilu0Preconditioner,
+            JacobiPreconditioner, Preconditioner, SpaiPreconditioner,
+        },
+        tfqmr::Tfqmr,
+        LinearSolver, LinearSolverReport, LinearSolverStatistics,
+    },
+    op::NonLinearOp,
+    scalar::IndexType,
+    Matrix, SolverProblem,
+};
+#[cfg(feature = "nalgebra")]
+use crate::{BlockDiagonalLU, Cholesky, NalgebraLU, Scalar, LDLT, QR};
+
+/// The preconditioner of an iterative linear solver chosen with [LinearSolverKind].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreconditionerKind {
+    /// No preconditioning ([IdentityPreconditioner])
+    #[default]
+    Identity,
+    /// [JacobiPreconditioner]
+    Jacobi,
+    /// [BlockJacobiPreconditioner], with the block size set by [crate::OdeBuilder::preconditioner_block_size] (default 1)
+    BlockJacobi,
+    /// [Ilu0Preconditioner]
+    Ilu0,
+    /// [SpaiPreconditioner]
+    Spai,
+}
+
+/// The linear solver used by the implicit ODE solvers, chosen when the problem is built (see [crate::OdeBuilder::linear_solver])
+/// instead of being passed to the solver constructors. The default linear solver for the matrix type of the problem
+/// (see [crate::DefaultSolver]) is a [SelectedLinearSolver], which builds the chosen solver when the problem is set.
+///
+/// Not all kinds are available for all matrix types:
+/// - [Self::DenseLU] is the default for the dense matrix types (`DMatrix` and `faer::Mat`).
+/// - [Self::SparseLU] is the default for [crate::SparseColMat].
+/// - [Self::QR], [Self::Cholesky] and [Self::LDLT] require `DMatrix`.
+/// - the iterative solvers ([Self::Gmres], [Self::BiCgStab] and [Self::Tfqmr]) are available for all of these matrix types.
+///
+/// Building a problem with a kind that is not available for its matrix type (see [crate::Matrix::supports_linear_solver])
+/// returns [crate::errors::PSError::UnsupportedLinearSolver], as does creating a solver state with [crate::OdeSolverState::new]
+/// if the options of the problem are changed after it is built.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinearSolverKind {
+    /// Dense LU decomposition ([crate::NalgebraLU] or [crate::FaerLU])
+    DenseLU,
+    /// Sparse LU decomposition ([crate::FaerSparseLU])
+    SparseLU,
+    /// Column-pivoted QR decomposition ([crate::QR])
+    QR,
+    /// Cholesky decomposition for symmetric positive-definite matrices ([crate::Cholesky])
+    Cholesky,
+    /// Bunch–Kaufman decomposition for symmetric indefinite matrices ([crate::LDLT])
+    LDLT,
+    /// Restarted GMRES ([crate::Gmres]) with right preconditioning, restarting every `restart` iterations
+    Gmres {
+        restart: IndexType,
+        preconditioner: PreconditionerKind,
+    },
+    /// BiCGStab ([crate::BiCgStab])
+    BiCgStab { preconditioner: PreconditionerKind },
+    /// TFQMR ([crate::Tfqmr])
+    Tfqmr { preconditioner: PreconditionerKind },
+}
+
+impl LinearSolverKind {
+    /// Returns true for the matrix-free iterative solvers
+    pub fn is_iterative(&self) -> bool {
+        matches!(
+            self,
+            Self::Gmres { .. } | Self::BiCgStab { .. } | Self::Tfqmr { .. }
+        )
+    }
+}
+
+// a preconditioner chosen at runtime from a PreconditionerKind
+pub(crate) enum AnyPreconditioner<M: Matrix> {
+    Identity(IdentityPreconditioner),
+    Jacobi(JacobiPreconditioner<M::V>),
+    BlockJacobi(BlockJacobiPreconditioner<M::T>),
+    Ilu0(Ilu0Preconditioner<M::T>),
+    Spai(SpaiPreconditioner<M::T>),
+}
+
+impl<M: Matrix> AnyPreconditioner<M> {
+    fn new(kind: PreconditionerKind) -> Self {
+        match kind {
+            PreconditionerKind::Identity => Self::Identity(IdentityPreconditioner),
+            PreconditionerKind::Jacobi => Self::Jacobi(JacobiPreconditioner::default()),
+            PreconditionerKind::BlockJacobi => {
+                Self::BlockJacobi(BlockJacobiPreconditioner::default())
+            }
+            PreconditionerKind::Ilu0 => Self::Ilu0(Ilu0Preconditioner::default()),
+            PreconditionerKind::Spai => Self::Spai(SpaiPreconditioner::default()),
+        }
+    }
+
+    fn as_preconditioner(&self) -> &dyn Preconditioner<M> {
+        match self {
+            Self::Identity(p) => p,
+            Self::Jacobi(p) => p,
+            Self::BlockJacobi(p) => p,
+            Self::Ilu0(p) => p,
+            Self::Spai(p) => p,
+        }
+    }
+
+    fn as_preconditioner_mut(&mut self) -> &mut dyn Preconditioner<M> {
+        match self {
+            Self::Identity(p) => p,
+            Self::Jacobi(p) => p,
+            Self::BlockJacobi(p) => p,
+            Self::Ilu0(p) => p,
+            Self::Spai(p) => p,
+        }
+    }
+}
+
+impl<M: Matrix> Preconditioner<M> for AnyPreconditioner<M> {
+    fn requires_matrix(&self) -> bool {
+        self.as_preconditioner().requires_matrix()
+    }
+    fn setup(&mut self, a: &M) -> Result<(), PSError> {
+        self.as_preconditioner_mut().setup(a)
+    }
+    fn apply_in_place(&self, x: &mut M::V) {
+        self.as_preconditioner().apply_in_place(x)
+    }
+    fn set_block_size(&mut self, block_size: IndexType) {
+        self.as_preconditioner_mut().set_block_size(block_size)
+    }
+}
+
+/// An iterative [LinearSolver] chosen at runtime from a [LinearSolverKind], used by [SelectedLinearSolver].
+pub(crate) enum KrylovSolver<C: NonLinearOp> {
+    Gmres(Gmres<C, AnyPreconditioner<C::M>>),
+    BiCgStab(BiCgStab<C, AnyPreconditioner<C::M>>),
+    Tfqmr(Tfqmr<C, AnyPreconditioner<C::M>>),
+}
+
+impl<C: NonLinearOp> KrylovSolver<C> {
+    /// The solver for `kind`, or `None` if it is not an iterative solver
+    pub(crate) fn new(kind: LinearSolverKind) -> Option<Self> {
+        match kind {
+            LinearSolverKind::Gmres {
+                restart,
+                preconditioner,
+            } => {
+                let mut solver = Gmres::new(
+                    AnyPreconditioner::new(preconditioner),
+                    PreconditionerSide::Right,
+                );
+                solver.restart = restart;
+                Some(Self::Gmres(solver))
+            }
+            LinearSolverKind::BiCgStab { preconditioner } => Some(Self::BiCgStab(BiCgStab::new(
+                AnyPreconditioner::new(preconditioner),
+            ))),
+            LinearSolverKind::Tfqmr { preconditioner } => Some(Self::Tfqmr(Tfqmr::new(
+                AnyPreconditioner::new(preconditioner),
+            ))),
+            _ => None,
+        }
+    }
+
+    fn as_solver(&self) -> &dyn LinearSolver<C> {
+        match self {
+            Self::Gmres(s) => s,
+            Self::BiCgStab(s) => s,
+            Self::Tfqmr(s) => s,
+        }
+    }
+
+    fn as_solver_mut(&mut self) -> &mut dyn LinearSolver<C> {
+        match self {
+            Self::Gmres(s) => s,
+            Self::BiCgStab(s) => s,
+            Self::Tfqmr(s) => s,
+        }
+    }
+}
+
+impl<C: NonLinearOp> LinearSolver<C> for KrylovSolver<C> {
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.as_solver_mut().set_problem(problem)
+    }
+
+    fn clear_problem(&mut self) {
+        self.as_solver_mut().clear_problem()
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.as_solver_mut().set_linearisation(x, t)
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.as_solver().solve_in_place(b)
+    }
+
+    fn report(&self) -> LinearSolverReport<C::T> {
+        self.as_solver().report()
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.as_solver().statistics()
+    }
+}
+
+/// The direct linear solvers of a matrix type that can be chosen with a [LinearSolverKind], see [SelectedLinearSolver].
+pub trait DirectLinearSolver<C: NonLinearOp>: LinearSolver<C> + Default {
+    /// Switch to the direct solver chosen by the problem (see [SolverProblem::linear_solver]), before the problem is set. The
+    /// current solver is kept if it is already the chosen one, so that it keeps its options and any symbolic analysis.
+    /// The default implementation always keeps the current solver, for matrix types with a single direct solver.
+    fn select(&mut self, _problem: &SolverProblem<C>) {}
+}
+
+/// A [LinearSolver] that uses the solver chosen by the [LinearSolverKind] of the problem (see [SolverProblem::linear_solver]),
+/// which is either one of the direct solvers `D` of the matrix type, or an iterative solver. The solver is chosen each time the
+/// problem is set, and this is the default linear solver of the matrix types (see [crate::DefaultSolver]).
+///
+/// Kinds that are not available for the matrix type (see [crate::Matrix::supports_linear_solver]) are rejected when the problem
+/// is built, if the problem is set directly the default direct solver of the matrix type is used instead.
+pub struct SelectedLinearSolver<C: NonLinearOp, D: DirectLinearSolver<C>> {
+    direct: D,
+    krylov: Option<KrylovSolver<C>>,
+}
+
+impl<C: NonLinearOp, D: DirectLinearSolver<C>> Default for SelectedLinearSolver<C, D> {
+    fn default() -> Self {
+        Self::new(D::default())
+    }
+}
+
+impl<C: NonLinearOp, D: DirectLinearSolver<C>> SelectedLinearSolver<C, D> {
+    /// Create a new solver, using `direct` (e.g. with non-default options) if it is the direct solver chosen by the problem
+    pub fn new(direct: D) -> Self {
+        Self {
+            direct,
+            krylov: None,
+        }
+    }
+
+    fn as_solver(&self) -> &dyn LinearSolver<C> {
+        match self.krylov.as_ref() {
+            Some(krylov) => krylov,
+            None => &self.direct,
+        }
+    }
+
+    fn as_solver_mut(&mut self) -> &mut dyn LinearSolver<C> {
+        match self.krylov.as_mut() {
+            Some(krylov) => krylov,
+            None => &mut self.direct,
+        }
+    }
+}
+
+impl<C: NonLinearOp, D: DirectLinearSolver<C>> LinearSolver<C> for SelectedLinearSolver<C, D> {
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.krylov = problem.linear_solver.and_then(KrylovSolver::new);
+        match self.krylov.as_mut() {
+            Some(krylov) => {
+                self.direct.clear_problem();
+                krylov.set_problem(problem);
+            }
+            None => {
+                self.direct.select(problem);
+                self.direct.set_problem(problem);
+            }
+        }
+    }
+
+    fn clear_problem(&mut self) {
+        self.direct.clear_problem();
+        self.krylov = None;
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.as_solver_mut().set_linearisation(x, t)
+    }
+
+    fn analyze(&mut self) {
+        self.as_solver_mut().analyze()
+    }
+
+    fn is_analyzed(&self) -> bool {
+        self.as_solver().is_analyzed()
+    }
+
+    fn refactor(&mut self, x: &C::V, t: C::T) {
+        self.as_solver_mut().refactor(x, t)
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.as_solver().solve_in_place(b)
+    }
+
+    fn solve_matrix_in_place(&self, b: &mut C::M) -> Result<(), PSError> {
+        self.as_solver().solve_matrix_in_place(b)
+    }
+
+    fn report(&self) -> LinearSolverReport<C::T> {
+        self.as_solver().report()
+    }
+
+    fn supports_secant_update(&self) -> bool {
+        self.as_solver().supports_secant_update()
+    }
+
+    fn secant_update(&mut self, s: &C::V, y: &C::V) -> bool {
+        self.as_solver_mut().secant_update(s, y)
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.as_solver().statistics()
+    }
+}
+
+/// The direct solvers for `DMatrix` that can be chosen with a [LinearSolverKind]. Without a kind (or with
+/// [LinearSolverKind::DenseLU]) this is [NalgebraLU], or [BlockDiagonalLU] if the problem declares the sizes of the diagonal
+/// blocks of the jacobian (see [SolverProblem::block_sizes]). Without a kind, a problem declared symmetric (see
+/// [SolverProblem::symmetric]) uses [Cholesky], falling back to the LU decomposition if the matrix is not positive definite
+/// (see [Cholesky::lu_fallback]).
+#[cfg(feature = "nalgebra")]
+#[allow(clippy::upper_case_acronyms)]
+pub enum NalgebraDirectSolver<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    LU(NalgebraLU<T, C>),
+    BlockDiagonal(BlockDiagonalLU<T, C>),
+    QR(QR<T, C>),
+    Cholesky(Cholesky<T, C>),
+    LDLT(LDLT<T, C>),
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T, C> Default for NalgebraDirectSolver<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn default() -> Self {
+        Self::LU(NalgebraLU::default())
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T, C> NalgebraDirectSolver<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn as_solver(&self) -> &dyn LinearSolver<C> {
+        match self {
+            Self::LU(s) => s,
+            Self::BlockDiagonal(s) => s,
+            Self::QR(s) => s,
+            Self::Cholesky(s) => s,
+            Self::LDLT(s) => s,
+        }
+    }
+
+    fn as_solver_mut(&mut self) -> &mut dyn LinearSolver<C> {
+        match self {
+            Self::LU(s) => s,
+            Self::BlockDiagonal(s) => s,
+            Self::QR(s) => s,
+            Self::Cholesky(s) => s,
+            Self::LDLT(s) => s,
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T, C> DirectLinearSolver<C> for NalgebraDirectSolver<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn select(&mut self, problem: &SolverProblem<C>) {
+        let selected = match problem.linear_solver {
+            Some(LinearSolverKind::QR) => {
+                (!matches!(self, Self::QR(_))).then(|| Self::QR(QR::default()))
+            }
+            Some(LinearSolverKind::Cholesky) => {
+                (!matches!(self, Self::Cholesky(s) if !s.lu_fallback))
+                    .then(|| Self::Cholesky(Cholesky::default()))
+            }
+            Some(LinearSolverKind::LDLT) => {
+                (!matches!(self, Self::LDLT(_))).then(|| Self::LDLT(LDLT::default()))
+            }
+            // a symmetric problem without a chosen kind uses the Cholesky decomposition, falling back to LU if the
+            // iteration matrix is not positive definite
+            None if problem.symmetric && problem.block_sizes.is_none() => {
+                (!matches!(self, Self::Cholesky(s) if s.lu_fallback)).then(|| {
+                    let mut cholesky = Cholesky::default();
+                    cholesky.lu_fallback = true;
+                    Self::Cholesky(cholesky)
+                })
+            }
+            // the LU decomposition, also used for kinds that are not available for nalgebra matrices
+            _ if problem.block_sizes.is_some() => (!matches!(self, Self::BlockDiagonal(_)))
+                .then(|| Self::BlockDiagonal(BlockDiagonalLU::default())),
+            _ => (!matches!(self, Self::LU(_))).then(|| Self::LU(NalgebraLU::default())),
+        };
+        if let Some(selected) = selected {
+            self.clear_problem();
+            *self = selected;
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+impl<T, C> LinearSolver<C> for NalgebraDirectSolver<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.as_solver_mut().set_problem(problem)
+    }
+
+    fn clear_problem(&mut self) {
+        self.as_solver_mut().clear_problem()
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.as_solver_mut().set_linearisation(x, t)
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.as_solver().solve_in_place(b)
+    }
+
+    fn solve_matrix_in_place(&self, b: &mut C::M) -> Result<(), PSError> {
+        self.as_solver().solve_matrix_in_place(b)
+    }
+
+    fn report(&self) -> LinearSolverReport<C::T> {
+        self.as_solver().report()
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.as_solver().statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::{
+            kind::NalgebraDirectSolver,
+            tests::{convection_diffusion, dense_linear_problem},
+        },
+        Bdf, LinearSolver, LinearSolverKind, OdeBuilder, OdeSolverMethod, OdeSolverState,
+        PreconditionerKind, Sdirk, SelectedLinearSolver, Tableau, Vector,
+    };
+
+    #[test]
+    fn test_linear_solver_kind() {
+        // heat equation y' = -A y, with A the symmetric positive definite discrete laplacian
+        let n = 10;
+        let a = convection_diffusion(n);
+        let a = (&a + a.transpose()) * 0.5;
+        let a2 = a.clone();
+        let rhs = move |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+            a.mul_to(x, y);
+            y.neg_mut();
+        };
+        let rhs_jac = move |_x: &DVector<f64>,
+                            _p: &DVector<f64>,
+                            _t,
+                            v: &DVector<f64>,
+                            y: &mut DVector<f64>| {
+            a2.mul_to(v, y);
+            y.neg_mut();
+        };
+        let init = move |_p: &DVector<f64>, _t| DVector::from_fn(n, |i, _| (i as f64).sin());
+        let build = |kind: Option<LinearSolverKind>| {
+            let mut builder = OdeBuilder::new().rtol(1e-8).atol([1e-8]);
+            if let Some(kind) = kind {
+                builder = builder.linear_solver(kind);
+            }
+            builder
+                .build_ode::<DMatrix<f64>, _, _, _>(rhs.clone(), rhs_jac.clone(), init)
+                .unwrap()
+        };
+        let sdirk = || {
+            Sdirk::new(
+                Tableau::<DMatrix<f64>>::tr_bdf2(),
+                SelectedLinearSolver::<_, NalgebraDirectSolver<_, _>>::default(),
+            )
+        };
+        let y_expect = Bdf::default()
+            .solve(&build(None), 1.0)
+            .unwrap()
+            .y
+            .pop()
+            .unwrap();
+        let y_expect_sdirk = sdirk().solve(&build(None), 1.0).unwrap().y.pop().unwrap();
+        let kinds = [
+            LinearSolverKind::DenseLU,
+            LinearSolverKind::QR,
+            LinearSolverKind::Cholesky,
+            LinearSolverKind::LDLT,
+            LinearSolverKind::Gmres {
+                restart: 30,
+                preconditioner: PreconditionerKind::Ilu0,
+            },
+            LinearSolverKind::Gmres {
+                restart: 5,
+                preconditioner: PreconditionerKind::BlockJacobi,
+            },
+            LinearSolverKind::BiCgStab {
+                preconditioner: PreconditionerKind::Jacobi,
+            },
+            LinearSolverKind::Tfqmr {
+                preconditioner: PreconditionerKind::Identity,
+            },
+        ];
+        for kind in kinds {
+            let problem = build(Some(kind));
+            let mut solver = Bdf::default();
+            let y = solver.solve(&problem, 1.0).unwrap().y.pop().unwrap();
+            y.assert_eq_st(&y_expect, 1e-6);
+            let iterations = solver.get_statistics().linear_solver.number_of_iterations;
+            assert_eq!(iterations > 0, kind.is_iterative(), "{:?}", kind);
+
+            // the default linear solver passed to the solver constructor also builds the chosen solver
+            let y = sdirk().solve(&problem, 1.0).unwrap().y.pop().unwrap();
+            y.assert_eq_st(&y_expect_sdirk, 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_linear_solver_kind_unsupported() {
+        let build_ode = |kind: LinearSolverKind| {
+            OdeBuilder::new()
+                .linear_solver(kind)
+                .build_ode::<DMatrix<f64>, _, _, _>(
+                    |x, _p, _t, y| y.copy_from(x),
+                    |_x, _p, _t, v, y| y.copy_from(v),
+                    |_p, _t| DVector::from_element(2, 1.0),
+                )
+        };
+        // a kind that is not available for the matrix type is rejected when the problem is built
+        assert!(matches!(
+            build_ode(LinearSolverKind::SparseLU),
+            Err(PSError::UnsupportedLinearSolver { .. })
+        ));
+        #[cfg(feature = "faer")]
+        assert!(matches!(
+            OdeBuilder::new()
+                .linear_solver(LinearSolverKind::QR)
+                .build_ode::<faer::Mat<f64>, _, _, _>(
+                    |x, _p, _t, y| y.copy_from(x),
+                    |_x, _p, _t, v, y| y.copy_from(v),
+                    |_p, _t| faer::Col::from_fn(2, |_| 1.0),
+                ),
+            Err(PSError::UnsupportedLinearSolver { .. })
+        ));
+
+        // or when the state is created, if the options are changed after the problem is built
+        let mut problem = build_ode(LinearSolverKind::DenseLU).unwrap();
+        problem.options.linear_solver = Some(LinearSolverKind::SparseLU);
+        assert!(matches!(
+            OdeSolverState::new(&problem, &Bdf::default()),
+            Err(PSError::UnsupportedLinearSolver { .. })
+        ));
+
+        // if the problem is set directly, the default direct solver is used for a kind that is not available
+        let (mut p, solns) = dense_linear_problem(convection_diffusion(4));
+        p.linear_solver = Some(LinearSolverKind::SparseLU);
+        let mut s = SelectedLinearSolver::<_, NalgebraDirectSolver<_, _>>::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(4), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-10);
+    }
+}
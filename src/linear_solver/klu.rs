@@ -0,0 +1,345 @@
+use std::{cell::RefCell, ptr, rc::Rc, time::Instant};
+
+use faer::Col;
+
+use crate::{
+    errors::PSError,
+    linear_solver::{LinearSolver, LinearSolverStatistics, LinearSolverStatisticsRecorder},
+    matrix::sparsity::MatrixSparsityRef,
+    op::linearise::LinearisedOp,
+    solver::SolverProblem,
+    LinearOp, Matrix, NonLinearOp, Op, SparseColMat,
+};
+
+// bindings to the 64-bit integer interface (`klu_l_*`) of KLU from SuiteSparse, generated from `klu.h` by the build script
+#[allow(
+    non_camel_case_types,
+    non_upper_case_globals,
+    non_snake_case,
+    dead_code
+)]
+mod ffi {
+    include!(concat!(env!("OUT_DIR"), "/klu_bindings.rs"));
+
+    // the integer type of the `klu_l_*` interface (a macro in `SuiteSparse_config.h`, so it is not in the bindings)
+    pub type SuiteSparse_long = i64;
+}
+
+/// A [LinearSolver] for sparse matrices ([SparseColMat]) that uses the KLU solver from [SuiteSparse](https://github.com/DrTimothyAldenDavis/SuiteSparse)
+/// (requires the `suitesparse` feature). The KLU library is found using pkg-config, or in the SuiteSparse install prefix given by
+/// the `SUITESPARSE_DIR` environment variable.
+///
+/// The sparsity pattern of the matrix is fixed by [LinearSolver::set_problem]. The first call to [LinearSolver::set_linearisation]
+/// analyses the pattern and factorises the matrix, and subsequent calls only refactorise the matrix using the same pivot ordering,
+/// which is much cheaper (e.g. for the frequent jacobian updates of [crate::Bdf]). If the refactorisation fails or is badly
+/// conditioned (reciprocal condition number below [Self::rcond_tol]) the matrix is factorised again with a fresh pivot ordering.
+pub struct KLU<C>
+where
+    C: NonLinearOp<M = SparseColMat<f64>, V = Col<f64>, T = f64>,
+{
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+    matrix: Option<SparseColMat<f64>>,
+    col_ptrs: Vec<ffi::SuiteSparse_long>,
+    row_indices: Vec<ffi::SuiteSparse_long>,
+    symbolic: *mut ffi::klu_l_symbolic,
+    numeric: *mut ffi::klu_l_numeric,
+    common: RefCell<ffi::klu_l_common>,
+    rcond_tol: f64,
+    // true if the last factorisation failed (e.g. the matrix is singular), so the solve returns an error
+    factorisation_failed: bool,
+}
+
+impl<C> Default for KLU<C>
+where
+    C: NonLinearOp<M = SparseColMat<f64>, V = Col<f64>, T = f64>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> KLU<C>
+where
+    C: NonLinearOp<M = SparseColMat<f64>, V = Col<f64>, T = f64>,
+{
+    pub fn new() -> Self {
+        let mut common = std::mem::MaybeUninit::<ffi::klu_l_common>::uninit();
+        unsafe { ffi::klu_l_defaults(common.as_mut_ptr()) };
+        let common = unsafe { common.assume_init() };
+        Self {
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+            matrix: None,
+            col_ptrs: Vec::new(),
+            row_indices: Vec::new(),
+            symbolic: ptr::null_mut(),
+            numeric: ptr::null_mut(),
+            common: RefCell::new(common),
+            rcond_tol: 1e-12,
+            factorisation_failed: false,
+        }
+    }
+
+    /// The matrix is factorised with a fresh pivot ordering if the reciprocal condition number estimate
+    /// after a refactorisation is below this value (default 1e-12).
+    pub fn rcond_tol(&self) -> f64 {
+        self.rcond_tol
+    }
+
+    pub fn set_rcond_tol(&mut self, rcond_tol: f64) {
+        self.rcond_tol = rcond_tol;
+    }
+
+    fn free(&mut self) {
+        let common = self.common.get_mut();
+        unsafe {
+            if !self.numeric.is_null() {
+                ffi::klu_l_free_numeric(&mut self.numeric, common);
+            }
+            if !self.symbolic.is_null() {
+                ffi::klu_l_free_symbolic(&mut self.symbolic, common);
+            }
+        }
+        self.numeric = ptr::null_mut();
+        self.symbolic = ptr::null_mut();
+    }
+
+    fn symbolic_analysis(&mut self) -> Result<(), PSError> {
+        let ap = self.col_ptrs.as_mut_ptr();
+        let ai = self.row_indices.as_mut_ptr();
+        let n = (self.col_ptrs.len() - 1) as ffi::SuiteSparse_long;
+        let common = self.common.get_mut();
+        unsafe {
+            if !self.symbolic.is_null() {
+                ffi::klu_l_free_symbolic(&mut self.symbolic, common);
+            }
+            self.symbolic = ffi::klu_l_analyze(n, ap, ai, common);
+        }
+        if self.symbolic.is_null() {
+            return Err(PSError::Other {
+                e: format!("KLU analyze failed with status {}", common.status),
+            });
+        }
+        Ok(())
+    }
+
+    // factorise the matrix, reusing the symbolic analysis and the pivot ordering of the previous factorisation if possible
+    fn factorise(&mut self) -> Result<(), PSError> {
+        if self.symbolic.is_null() {
+            self.symbolic_analysis()?;
+        }
+        let ax = self
+            .matrix
+            .as_ref()
+            .expect("Matrix not set")
+            .faer()
+            .values()
+            .as_ptr() as *mut f64;
+        let ap = self.col_ptrs.as_mut_ptr();
+        let ai = self.row_indices.as_mut_ptr();
+        let n = (self.col_ptrs.len() - 1) as ffi::SuiteSparse_long;
+        let common = self.common.get_mut();
+        unsafe {
+            if !self.numeric.is_null() {
+                let ok = ffi::klu_l_refactor(ap, ai, ax, self.symbolic, self.numeric, common) == 1
+                    && ffi::klu_l_rcond(self.symbolic, self.numeric, common) == 1
+                    && common.rcond >= self.rcond_tol;
+                if ok {
+                    return Ok(());
+                }
+                ffi::klu_l_free_numeric(&mut self.numeric, common);
+            }
+            self.numeric = ffi::klu_l_factor(ap, ai, ax, self.symbolic, common);
+            if self.numeric.is_null() {
+                return Err(PSError::LuFailed);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C> Drop for KLU<C>
+where
+    C: NonLinearOp<M = SparseColMat<f64>, V = Col<f64>, T = f64>,
+{
+    fn drop(&mut self) {
+        self.free();
+    }
+}
+
+impl<C> LinearSolver<C> for KLU<C>
+where
+    C: NonLinearOp<M = SparseColMat<f64>, V = Col<f64>, T = f64>,
+{
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        // on failure `numeric` is left null and the error is returned by the solve, so the caller can
+        // recover (e.g. by reducing the step size)
+        self.factorisation_failed = self.factorise().is_err();
+        self.statistics.factorisation(start);
+    }
+
+    fn analyze(&mut self) {
+        if !self.numeric.is_null() {
+            unsafe { ffi::klu_l_free_numeric(&mut self.numeric, self.common.get_mut()) };
+            self.numeric = ptr::null_mut();
+        }
+        // on failure `symbolic` is left null, so the analysis is retried (and its failure reported) by the next factorisation
+        self.factorisation_failed = self.symbolic_analysis().is_err();
+    }
+
+    fn is_analyzed(&self) -> bool {
+        !self.symbolic.is_null()
+    }
+
+    fn solve_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            if self.factorisation_failed {
+                return Err(PSError::LuFailed);
+            }
+            if self.numeric.is_null() {
+                return Err(PSError::LuNotInitialized);
+            }
+            let n = x.nrows() as ffi::SuiteSparse_long;
+            let mut common = self.common.borrow_mut();
+            let status = unsafe {
+                ffi::klu_l_solve(
+                    self.symbolic,
+                    self.numeric,
+                    n,
+                    1,
+                    x.as_ptr_mut(),
+                    &mut *common,
+                )
+            };
+            if status != 1 {
+                return Err(PSError::LuFailed);
+            }
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let ncols = linearised_problem.f.nstates();
+        let nrows = linearised_problem.f.nout();
+        let matrix = C::M::new_from_sparsity(
+            nrows,
+            ncols,
+            linearised_problem
+                .f
+                .sparsity()
+                .map(|s| MatrixSparsityRef::<SparseColMat<f64>>::to_owned(&s)),
+        );
+        // KLU takes non-const pointers to the pattern, so keep our own copy
+        let col_ptrs: Vec<_> = matrix
+            .faer()
+            .col_ptrs()
+            .iter()
+            .map(|&i| i as ffi::SuiteSparse_long)
+            .collect();
+        let row_indices: Vec<_> = matrix
+            .faer()
+            .row_indices()
+            .iter()
+            .map(|&i| i as ffi::SuiteSparse_long)
+            .collect();
+        // the symbolic analysis and pivot ordering are kept if the sparsity pattern is unchanged
+        if col_ptrs != self.col_ptrs || row_indices != self.row_indices {
+            self.free();
+            self.col_ptrs = col_ptrs;
+            self.row_indices = row_indices;
+        }
+        self.problem = Some(linearised_problem);
+        self.matrix = Some(matrix);
+        self.factorisation_failed = false;
+    }
+
+    fn clear_problem(&mut self) {
+        // the symbolic analysis and pivot ordering hold no references to the problem, so they are kept for the next problem
+        // (they are freed on drop, or by set_problem if the sparsity pattern changes)
+        self.problem = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{op::closure::Closure, LinearSolver, SolverProblem, SparseColMat, Vector};
+    use faer::Col;
+
+    use super::KLU;
+
+    #[test]
+    fn test_klu_refactorisation() {
+        // f_i(x) = x_i^2 + x_{i-1}, so the jacobian is lower bidiagonal and changes with x but its sparsity pattern does not
+        type M = SparseColMat<f64>;
+        let n = 3;
+        let mut op = Closure::<M, _, _>::new(
+            |x: &Col<f64>, _p: &Col<f64>, _t, y: &mut Col<f64>| {
+                for i in 0..x.len() {
+                    y[i] = x[i] * x[i] + if i > 0 { x[i - 1] } else { 0.0 };
+                }
+            },
+            |x: &Col<f64>, _p: &Col<f64>, _t, v: &Col<f64>, y: &mut Col<f64>| {
+                for i in 0..x.len() {
+                    y[i] = 2.0 * x[i] * v[i] + if i > 0 { v[i - 1] } else { 0.0 };
+                }
+            },
+            n,
+            n,
+            Rc::new(Col::zeros(0)),
+        );
+        let x0 = Col::from_vec(vec![1.0; n]);
+        op.calculate_sparsity(&x0, 0.0);
+        let atol = Rc::new(Col::from_vec(vec![1e-6; n]));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-6);
+        let mut solver = KLU::default();
+        solver.set_problem(&problem);
+        let expect = Col::from_vec(vec![1.0; n]);
+        for a in [1.0, 2.0, 3.0] {
+            // J = diag(2a) + subdiag(1), so J [1, 1, 1] = [2a, 2a + 1, 2a + 1]
+            solver.set_linearisation(&Col::from_vec(vec![a; n]), 0.0);
+            let b = Col::from_vec(vec![2.0 * a, 2.0 * a + 1.0, 2.0 * a + 1.0]);
+            let x = solver.solve(&b).unwrap();
+            x.assert_eq_st(&expect, 1e-12);
+        }
+        assert!(solver.is_analyzed());
+
+        // setting the same problem again keeps the symbolic analysis
+        solver.set_problem(&problem);
+        assert!(solver.is_analyzed());
+        solver.refactor(&Col::from_vec(vec![1.0; n]), 0.0);
+        let x = solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).unwrap();
+        x.assert_eq_st(&expect, 1e-12);
+
+        // clearing the problem (e.g. to update the parameters of the equations) also keeps it
+        solver.clear_problem();
+        assert!(solver.is_analyzed());
+        solver.set_problem(&problem);
+        assert!(solver.is_analyzed());
+        solver.refactor(&Col::from_vec(vec![1.0; n]), 0.0);
+        let x = solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).unwrap();
+        x.assert_eq_st(&expect, 1e-12);
+
+        // a singular matrix (J = subdiag(1) at x = 0) gives an error from the solve rather than a panic, and the
+        // solver recovers on the next factorisation
+        solver.set_linearisation(&Col::from_vec(vec![0.0; n]), 0.0);
+        assert!(solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).is_err());
+        solver.set_linearisation(&Col::from_vec(vec![1.0; n]), 0.0);
+        let x = solver.solve(&Col::from_vec(vec![2.0, 3.0, 3.0])).unwrap();
+        x.assert_eq_st(&expect, 1e-12);
+    }
+}
@@ -0,0 +1,145 @@
+use std::{rc::Rc, time::Instant};
+
+use num_traits::Zero;
+
+use crate::{
+    linear_solver::{preconditioner::Preconditioner, LinearSolverStatisticsRecorder},
+    matrix::sparsity::MatrixSparsityRef,
+    op::{matrix_free::MatrixFreeLinearisedOp, NonLinearOp},
+    LinearOp, Matrix, Op, SolverProblem,
+};
+
+use crate::errors::PSError;
+
+/// The linearised operator `A` and its [Preconditioner], shared by the iterative (Krylov) linear solvers.
+/// `A` is applied matrix-free (see [MatrixFreeLinearisedOp]), and the matrix is only assembled (to set up the preconditioner)
+/// if [Preconditioner::requires_matrix] is true.
+pub(crate) struct KrylovOperator<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    preconditioner: P,
+    problem: Option<SolverProblem<MatrixFreeLinearisedOp<C>>>,
+    matrix: Option<C::M>,
+    t: C::T,
+    is_setup: bool,
+    // the error message if the last set up of the preconditioner failed
+    setup_error: Option<String>,
+    statistics: LinearSolverStatisticsRecorder,
+}
+
+impl<C, P> KrylovOperator<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    pub(crate) fn new(preconditioner: P) -> Self {
+        Self {
+            preconditioner,
+            problem: None,
+            matrix: None,
+            t: C::T::zero(),
+            is_setup: false,
+            setup_error: None,
+            statistics: LinearSolverStatisticsRecorder::default(),
+        }
+    }
+
+    pub(crate) fn preconditioner(&self) -> &P {
+        &self.preconditioner
+    }
+
+    /// The statistics of the solver, the set ups of the preconditioner are recorded as factorisations
+    pub(crate) fn statistics(&self) -> &LinearSolverStatisticsRecorder {
+        &self.statistics
+    }
+
+    /// Returns an error if the linearisation has not been set, or if the set up of the preconditioner failed
+    /// (e.g. a zero pivot in an incomplete factorisation), so that the caller can recover by reducing the step size.
+    pub(crate) fn check_setup(&self) -> Result<(), PSError> {
+        if let Some(e) = self.setup_error.as_ref() {
+            return Err(PSError::LinearSolverError {
+                e: format!("failed to set up the preconditioner: {}", e),
+            });
+        }
+        if !self.is_setup {
+            return Err(PSError::LuNotInitialized);
+        }
+        Ok(())
+    }
+
+    /// Set the problem and return the number of states. If `finite_difference` is true, the jacobian-vector products are
+    /// approximated using finite differences of the operator.
+    pub(crate) fn set_problem(
+        &mut self,
+        problem: &SolverProblem<C>,
+        finite_difference: bool,
+    ) -> usize {
+        self.statistics.reset();
+        let linearised_problem = SolverProblem::new_from_problem(
+            Rc::new(MatrixFreeLinearisedOp::new(
+                problem.f.clone(),
+                finite_difference,
+            )),
+            problem,
+        );
+        let n = linearised_problem.f.nstates();
+        if let Some(block_size) = problem.preconditioner_block_size {
+            self.preconditioner.set_block_size(block_size);
+        }
+        self.matrix = if self.preconditioner.requires_matrix() {
+            Some(C::M::new_from_sparsity(
+                n,
+                n,
+                linearised_problem.f.sparsity().map(|s| s.to_owned()),
+            ))
+        } else {
+            None
+        };
+        self.problem = Some(linearised_problem);
+        self.is_setup = false;
+        self.setup_error = None;
+        n
+    }
+
+    pub(crate) fn clear_problem(&mut self) {
+        self.problem = None;
+        self.matrix = None;
+        self.is_setup = false;
+        self.setup_error = None;
+    }
+
+    pub(crate) fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        let start = Instant::now();
+        Rc::<MatrixFreeLinearisedOp<C>>::get_mut(
+            &mut self.problem.as_mut().expect("Problem not set").f,
+        )
+        .unwrap()
+        .set_x(x);
+        self.t = t.clone();
+        self.setup_error = None;
+        if let Some(matrix) = self.matrix.as_mut() {
+            self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+            if let Err(e) = self.preconditioner.setup(matrix) {
+                self.setup_error = Some(e.to_string());
+            }
+        }
+        self.is_setup = true;
+        self.statistics.factorisation(start);
+    }
+
+    /// y = A x
+    pub(crate) fn call_inplace(&self, x: &C::V, y: &mut C::V) {
+        self.problem
+            .as_ref()
+            .unwrap()
+            .f
+            .call_inplace(x, self.t.clone(), y);
+    }
+
+    /// x <- P^{-1} x
+    pub(crate) fn precondition(&self, x: &mut C::V) {
+        self.preconditioner.apply_in_place(x);
+    }
+}
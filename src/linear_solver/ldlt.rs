@@ -0,0 +1,419 @@
+use std::{rc::Rc, time::Instant};
+
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    linear_solver::{LinearSolverStatistics, LinearSolverStatisticsRecorder},
+    matrix::sparsity::MatrixSparsityRef,
+    op::{linearise::LinearisedOp, NonLinearOp},
+    LinearOp, LinearSolver, Matrix, Op, Scalar, SolverProblem,
+};
+
+use crate::errors::PSError;
+
+/// The Bunch–Kaufman factorisation `P A P^T = L D L^T` of a symmetric matrix, where `P` is a permutation, `L` is unit lower
+/// triangular and `D` is block diagonal with 1x1 and 2x2 blocks.
+struct BunchKaufman<T: Scalar> {
+    // strictly lower part of L (the diagonal and the upper part are not used)
+    l: DMatrix<T>,
+    // the diagonal of D, and the sub-diagonal of the 2x2 blocks (zero for 1x1 blocks)
+    d: Vec<T>,
+    e: Vec<T>,
+    // perm[i] is the row of A that is row i of P A P^T
+    perm: Vec<usize>,
+}
+
+impl<T: Scalar> BunchKaufman<T> {
+    /// Factorise the symmetric matrix `a` (only the lower triangle is used), returns `None` if the matrix is singular
+    fn new(a: &DMatrix<T>) -> Option<Self> {
+        let n = a.nrows();
+        let alpha = (T::one() + T::cast(17.0).sqrt()) / T::cast(8.0);
+        let mut a = DMatrix::from_fn(n, n, |i, j| {
+            if i >= j {
+                a[(i, j)].clone()
+            } else {
+                a[(j, i)].clone()
+            }
+        });
+        let mut d = vec![T::zero(); n];
+        let mut e = vec![T::zero(); n];
+        let mut perm = (0..n).collect::<Vec<_>>();
+        // swap rows and columns i and j, keeping the trailing matrix symmetric and permuting the computed columns of L
+        let swap = |a: &mut DMatrix<T>, perm: &mut Vec<usize>, i: usize, j: usize| {
+            if i != j {
+                a.swap_rows(i, j);
+                a.swap_columns(i, j);
+                perm.swap(i, j);
+            }
+        };
+        let mut k = 0;
+        while k < n {
+            // the largest off-diagonal entry in column k of the trailing matrix
+            let absakk = a[(k, k)].clone().abs();
+            let (imax, colmax) = (k + 1..n)
+                .map(|i| (i, a[(i, k)].clone().abs()))
+                .fold((k, T::zero()), |acc, x| if x.1 > acc.1 { x } else { acc });
+            if absakk.clone().max(colmax.clone()).is_zero() {
+                return None;
+            }
+            let two_by_two = if absakk >= alpha.clone() * colmax.clone() {
+                false
+            } else {
+                // the largest off-diagonal entry in row imax of the trailing matrix
+                let rowmax = (k..n)
+                    .filter(|&j| j != imax)
+                    .map(|j| a[(imax, j)].clone().abs())
+                    .fold(T::zero(), |acc, x| acc.max(x));
+                if absakk * rowmax.clone() >= alpha.clone() * colmax.clone() * colmax {
+                    false
+                } else if a[(imax, imax)].clone().abs() >= alpha.clone() * rowmax {
+                    swap(&mut a, &mut perm, k, imax);
+                    false
+                } else {
+                    swap(&mut a, &mut perm, k + 1, imax);
+                    true
+                }
+            };
+            if !two_by_two {
+                let dkk = a[(k, k)].clone();
+                d[k] = dkk.clone();
+                for i in k + 1..n {
+                    a[(i, k)] /= dkk.clone();
+                }
+                for j in k + 1..n {
+                    let ajk = a[(j, k)].clone() * dkk.clone();
+                    for i in j..n {
+                        let aik = a[(i, k)].clone();
+                        a[(i, j)] -= aik * ajk.clone();
+                        a[(j, i)] = a[(i, j)].clone();
+                    }
+                }
+                k += 1;
+            } else {
+                let (d11, d21, d22) = (
+                    a[(k, k)].clone(),
+                    a[(k + 1, k)].clone(),
+                    a[(k + 1, k + 1)].clone(),
+                );
+                let det = d11.clone() * d22.clone() - d21.clone() * d21.clone();
+                if det.is_zero() {
+                    return None;
+                }
+                d[k] = d11.clone();
+                d[k + 1] = d22.clone();
+                e[k] = d21.clone();
+                a[(k + 1, k)] = T::zero();
+                // [l_ik, l_ik+1] = [a_ik, a_ik+1] D^{-1}, and the update of the trailing matrix uses the old values
+                let w = (k + 2..n)
+                    .map(|i| (a[(i, k)].clone(), a[(i, k + 1)].clone()))
+                    .collect::<Vec<_>>();
+                for (i, (wi0, wi1)) in (k + 2..n).zip(w.iter().cloned()) {
+                    a[(i, k)] =
+                        (wi0.clone() * d22.clone() - wi1.clone() * d21.clone()) / det.clone();
+                    a[(i, k + 1)] = (wi1 * d11.clone() - wi0 * d21.clone()) / det.clone();
+                }
+                for (j, (wj0, wj1)) in (k + 2..n).zip(w.iter()) {
+                    for i in j..n {
+                        let (lik0, lik1) = (a[(i, k)].clone(), a[(i, k + 1)].clone());
+                        a[(i, j)] -= lik0 * wj0.clone() + lik1 * wj1.clone();
+                        a[(j, i)] = a[(i, j)].clone();
+                    }
+                }
+                k += 2;
+            }
+        }
+        Some(Self { l: a, d, e, perm })
+    }
+
+    fn solve_in_place(&self, b: &mut DVector<T>) {
+        let n = self.d.len();
+        let mut y = DVector::from_fn(n, |i, _| b[self.perm[i]].clone());
+        // solve L z = y
+        for j in 0..n {
+            let yj = y[j].clone();
+            for i in j + 1..n {
+                y[i] -= self.l[(i, j)].clone() * yj.clone();
+            }
+        }
+        // solve D w = z, a 2x2 block is marked by a non-zero sub-diagonal entry
+        let mut k = 0;
+        while k < n {
+            if k + 1 < n && !self.e[k].is_zero() {
+                let (d11, d21, d22) = (self.d[k].clone(), self.e[k].clone(), self.d[k + 1].clone());
+                let det = d11.clone() * d22.clone() - d21.clone() * d21.clone();
+                let (y0, y1) = (y[k].clone(), y[k + 1].clone());
+                y[k] = (d22 * y0.clone() - d21.clone() * y1.clone()) / det.clone();
+                y[k + 1] = (d11 * y1 - d21 * y0) / det;
+                k += 2;
+            } else {
+                y[k] /= self.d[k].clone();
+                k += 1;
+            }
+        }
+        // solve L^T u = w
+        for j in (0..n).rev() {
+            let mut yj = y[j].clone();
+            for i in j + 1..n {
+                yj -= self.l[(i, j)].clone() * y[i].clone();
+            }
+            y[j] = yj;
+        }
+        for i in 0..n {
+            b[self.perm[i]] = y[i].clone();
+        }
+    }
+
+    /// The number of positive, negative and zero eigenvalues of `D` (and so of `A`, by Sylvester's law of inertia)
+    fn inertia(&self) -> (usize, usize, usize) {
+        let n = self.d.len();
+        let (mut pos, mut neg, mut zero) = (0, 0, 0);
+        let mut k = 0;
+        while k < n {
+            if k + 1 < n && !self.e[k].is_zero() {
+                // det(D_k) < 0 for a 2x2 block, so it has one positive and one negative eigenvalue
+                pos += 1;
+                neg += 1;
+                k += 2;
+            } else {
+                match &self.d[k] {
+                    x if *x > T::zero() => pos += 1,
+                    x if *x < T::zero() => neg += 1,
+
+                    _ => zero += 1,
+                }
+                k += 1;
+            }
+        }
+        (pos, neg, zero)
+    }
+}
+
+/// A [LinearSolver] for symmetric indefinite matrices, that uses the Bunch–Kaufman factorisation `P A P^T = L D L^T` with
+/// `L` unit lower triangular and `D` block diagonal with 1x1 and 2x2 blocks. The symmetric pivoting keeps the factorisation
+/// stable for indefinite matrices, for which the [crate::Cholesky] decomposition fails, at around half the cost of the LU
+/// decomposition. This is suitable for the saddle-point iteration matrices of constrained mechanical DAEs, e.g.
+/// `[[M + c K, c G^T], [c G, 0]]` with constraint jacobian `G`.
+///
+/// After each factorisation the inertia of the matrix (the number of positive, negative and zero eigenvalues) is given by
+/// [Self::inertia], e.g. a saddle-point matrix with `m` independent constraints and a positive definite upper block has
+/// exactly `m` negative eigenvalues. If the matrix is singular, [LinearSolver::solve_in_place] returns [PSError::LuFailed].
+pub struct LDLT<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    matrix: Option<DMatrix<T>>,
+    ldlt: Option<BunchKaufman<T>>,
+    is_factorised: bool,
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+}
+
+impl<T, C> Default for LDLT<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn default() -> Self {
+        Self {
+            matrix: None,
+            ldlt: None,
+            is_factorised: false,
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+        }
+    }
+}
+
+impl<T, C> LDLT<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    /// The number of positive, negative and zero eigenvalues of the last factorised matrix (`None` if not factorised or singular)
+    pub fn inertia(&self) -> Option<(usize, usize, usize)> {
+        self.ldlt.as_ref().map(|ldlt| ldlt.inertia())
+    }
+}
+
+impl<T, C> LinearSolver<C> for LDLT<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn solve_in_place(&self, state: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            if !self.is_factorised {
+                return Err(PSError::LuNotInitialized);
+            }
+            let ldlt = self.ldlt.as_ref().ok_or(PSError::LuFailed)?;
+            ldlt.solve_in_place(state);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn set_linearisation(&mut self, x: &<C as Op>::V, t: <C as Op>::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        self.ldlt = BunchKaufman::new(matrix);
+        self.is_factorised = true;
+        self.statistics.factorisation(start);
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let ncols = linearised_problem.f.nstates();
+        let nrows = linearised_problem.f.nout();
+        let matrix = C::M::new_from_sparsity(
+            nrows,
+            ncols,
+            linearised_problem.f.sparsity().map(|s| s.to_owned()),
+        );
+        self.problem = Some(linearised_problem);
+        self.matrix = Some(matrix);
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.ldlt = None;
+        self.is_factorised = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::{
+            ldlt::LDLT,
+            tests::{convection_diffusion, dense_linear_problem},
+        },
+        Bdf, Cholesky, LinearSolver, NewtonNonlinearSolver, OdeBuilder, OdeSolverMethod, Vector,
+    };
+
+    // the saddle-point matrix [[H, G^T], [G, -eps I]], with H symmetric positive definite and m constraints G
+    fn saddle_point(n: usize, m: usize, eps: f64) -> DMatrix<f64> {
+        let h = convection_diffusion(n);
+        let h = (&h + h.transpose()) * 0.5;
+        let g = DMatrix::from_fn(m, n, |i, j| {
+            if j == 2 * i || j == 2 * i + 1 {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        let mut a = DMatrix::zeros(n + m, n + m);
+        a.view_mut((0, 0), (n, n)).copy_from(&h);
+        a.view_mut((n, 0), (m, n)).copy_from(&g);
+        a.view_mut((0, n), (n, m)).copy_from(&g.transpose());
+        a.view_mut((n, n), (m, m)).fill_with_identity();
+        a.view_mut((n, n), (m, m)).scale_mut(-eps);
+        a
+    }
+
+    #[test]
+    fn test_ldlt() {
+        let (n, m) = (8, 3);
+        let a = saddle_point(n, m, 0.0);
+        let (p, solns) = dense_linear_problem(a);
+
+        // the matrix is indefinite, so the Cholesky decomposition fails
+        let mut s = Cholesky::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n + m), 0.0);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::NotPositiveDefinite)
+        ));
+
+        let mut s = LDLT::default();
+        s.set_problem(&p);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::LuNotInitialized)
+        ));
+        s.set_linearisation(&DVector::zeros(n + m), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-10);
+        assert_eq!(s.inertia(), Some((n, m, 0)));
+
+        // a zero diagonal needs a 2x2 pivot
+        let a = DMatrix::from_row_slice(3, 3, &[0.0, 1.0, 2.0, 1.0, 0.0, 3.0, 2.0, 3.0, 0.0]);
+        let (p, solns) = dense_linear_problem(a);
+        let mut s = LDLT::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(3), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-12);
+        assert_eq!(s.inertia(), Some((1, 2, 0)));
+
+        // a singular matrix fails to factorise
+        let (p, solns) = dense_linear_problem(DMatrix::zeros(3, 3));
+        let mut s = LDLT::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(3), 0.0);
+        assert!(matches!(s.solve(&solns[0].b), Err(PSError::LuFailed)));
+        assert_eq!(s.inertia(), None);
+    }
+
+    #[test]
+    fn test_ldlt_bdf() {
+        // y' = -H y - G^T z, 0 = -G y + eps z: a damped system with penalised constraints G y = 0, for which the
+        // iteration matrix [[I + c H, c G^T], [c G, -c eps I]] is symmetric indefinite
+        let (n, m) = (8, 3);
+        let a = -saddle_point(n, m, 1e-3);
+        let a2 = a.clone();
+        let rhs =
+            move |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| a.mul_to(x, y);
+        let rhs_jac = move |_x: &DVector<f64>,
+                            _p: &DVector<f64>,
+                            _t,
+                            v: &DVector<f64>,
+                            y: &mut DVector<f64>| { a2.mul_to(v, y) };
+        let mass = move |x: &DVector<f64>, _p: &DVector<f64>, _t, beta, y: &mut DVector<f64>| {
+            for i in 0..n + m {
+                y[i] = if i < n {
+                    x[i] + beta * y[i]
+                } else {
+                    beta * y[i]
+                };
+            }
+        };
+        // consistent initial conditions, with G y = 0 and z = 0
+        let init = move |_p: &DVector<f64>, _t| {
+            DVector::from_fn(n + m, |i, _| match i {
+                i if i < 2 * m => {
+                    if i % 2 == 0 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                i if i < n => 1.0,
+                _ => 0.0,
+            })
+        };
+        let problem = OdeBuilder::new()
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode_with_mass::<DMatrix<f64>, _, _, _, _>(rhs, rhs_jac, mass, init)
+            .unwrap();
+        let t = 1.0;
+        let y_lu = Bdf::default().solve(&problem, t).unwrap().y.pop().unwrap();
+        let nonlinear_solver = NewtonNonlinearSolver::new(LDLT::default());
+        let mut solver = Bdf::<DMatrix<f64>, _, _>::new(nonlinear_solver);
+        let y = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        y.assert_eq_st(&y_lu, 1e-6);
+    }
+}
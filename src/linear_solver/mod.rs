@@ -1,4 +1,13 @@
-use crate::{op::Op, solver::SolverProblem};
+use std::{
+    cell::Cell,
+    ops::Add,
+    time::{Duration, Instant},
+};
+
+use num_traits::Zero;
+use serde::Serialize;
+
+use crate::{op::Op, solver::SolverProblem, Matrix, MatrixCommon, Vector};
 
 #[cfg(feature = "nalgebra")]
 pub mod nalgebra;
@@ -6,11 +15,32 @@ pub mod nalgebra;
 #[cfg(feature = "faer")]
 pub mod faer;
 
+pub mod bicgstab;
+pub mod broyden;
+#[cfg(feature = "nalgebra")]
+pub mod cholesky;
+#[cfg(feature = "cuda")]
+pub mod cuda;
+pub mod gmres;
+pub mod kind;
+pub(crate) mod krylov;
+#[cfg(feature = "nalgebra")]
+pub mod ldlt;
+pub mod preconditioner;
+#[cfg(feature = "nalgebra")]
+pub mod qr;
+pub mod tfqmr;
+
 #[cfg(feature = "sundials")]
 pub mod sundials;
 
+#[cfg(feature = "suitesparse")]
+pub mod klu;
+
 use crate::errors::PSError;
+#[cfg(feature = "faer")]
 pub use faer::lu::LU as FaerLU;
+pub use nalgebra::block_diagonal::BlockDiagonalLU;
 pub use nalgebra::lu::LU as NalgebraLU;
 
 /// A solver for the linear problem `Ax = b`, where `A` is a linear operator that is obtained by taking the linearisation of a nonlinear operator `C`
@@ -19,9 +49,32 @@ pub trait LinearSolver<C: Op> {
     /// Any internal state of the solver is reset.
     fn set_problem(&mut self, problem: &SolverProblem<C>);
 
+    /// Clear the current problem, releasing any references to it held by the solver.
+    fn clear_problem(&mut self);
+
     // sets the point at which the linearisation of the operator is evaluated
     fn set_linearisation(&mut self, x: &C::V, t: C::T);
 
+    /// Perform the symbolic analysis of the sparsity pattern of the linearised operator (e.g. the fill-reducing ordering of a
+    /// sparse LU decomposition). This only depends on the pattern, so it is reused by all subsequent factorisations, and is kept
+    /// by [Self::set_problem] if the new problem has the same sparsity pattern. It is otherwise performed at the first call to
+    /// [Self::set_linearisation] or [Self::refactor].
+    /// The default implementation does nothing, as dense solvers have no symbolic phase.
+    fn analyze(&mut self) {}
+
+    /// Returns true if a symbolic analysis of the sparsity pattern is available (see [Self::analyze]).
+    /// The default implementation returns false.
+    fn is_analyzed(&self) -> bool {
+        false
+    }
+
+    /// Recompute the numeric factorisation of the linearisation at `x` and `t`, when only the values of the matrix have changed
+    /// since [Self::analyze] (e.g. the BDF iteration matrix `M - c J` when `c` changes). The default implementation calls
+    /// [Self::set_linearisation], which already reuses any symbolic analysis.
+    fn refactor(&mut self, x: &C::V, t: C::T) {
+        self.set_linearisation(x, t);
+    }
+
     /// Solve the problem `Ax = b` and return the solution `x`.
     /// panics if [Self::set_linearisation] has not been called previously
     fn solve(&self, b: &C::V) -> Result<C::V, PSError> {
@@ -31,6 +84,137 @@ pub trait LinearSolver<C: Op> {
     }
 
     fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError>;
+
+    /// Solve the problem `AX = B` for a matrix `B` with many columns, using the same factorisation for all of them.
+    /// The input `B` is provided in `b`, and the solution `X` is returned in `b`.
+    /// The default implementation solves for each column in turn using [Self::solve_in_place].
+    fn solve_matrix_in_place(&self, b: &mut C::M) -> Result<(), PSError> {
+        let mut col = C::V::zeros(b.nrows());
+        for j in 0..b.ncols() {
+            col.fill(C::T::zero());
+            b.add_column_to_vector(j, &mut col);
+            self.solve_in_place(&mut col)?;
+            b.set_column(j, &col);
+        }
+        Ok(())
+    }
+
+    /// Diagnostics for the current linearisation, see [LinearSolverReport].
+    /// The default implementation reports no condition estimate and no refinement steps.
+    fn report(&self) -> LinearSolverReport<C::T> {
+        LinearSolverReport::default()
+    }
+
+    /// Returns true if the solver accepts rank-1 updates of its factorisation using [Self::secant_update] (see [crate::Broyden]).
+    /// The default implementation returns false.
+    fn supports_secant_update(&self) -> bool {
+        false
+    }
+
+    /// Update the factorised matrix `A` so that it satisfies the secant condition `A s = y`, where `s` is a Newton step and `y` the
+    /// resulting change in the residual, without refactorising. Returns true if the update was applied.
+    /// The default implementation does nothing and returns false.
+    fn secant_update(&mut self, _s: &C::V, _y: &C::V) -> bool {
+        false
+    }
+
+    /// Counts and timings of the factorisations and solves since the last call to [Self::set_problem], see [LinearSolverStatistics].
+    /// The default implementation returns empty statistics.
+    fn statistics(&self) -> LinearSolverStatistics {
+        LinearSolverStatistics::default()
+    }
+}
+
+/// Counts and timings of the work done by a [LinearSolver], to see where the time of a solve goes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct LinearSolverStatistics {
+    /// Number of factorisations of the matrix (for iterative solvers, the number of set ups of the preconditioner),
+    /// i.e. calls to [LinearSolver::set_linearisation] or [LinearSolver::refactor]
+    pub number_of_factorisations: usize,
+    /// Number of right-hand sides solved for
+    pub number_of_solves: usize,
+    /// Total number of iterations of the iterative (Krylov) solvers over all the solves, zero for direct solvers
+    pub number_of_iterations: usize,
+    /// Time spent assembling and factorising the matrix
+    pub factorisation_time: Duration,
+    /// Time spent in the solves
+    pub solve_time: Duration,
+}
+
+impl LinearSolverStatistics {
+    /// Total time spent in the linear solver
+    pub fn total_time(&self) -> Duration {
+        self.factorisation_time + self.solve_time
+    }
+}
+
+impl Add for LinearSolverStatistics {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            number_of_factorisations: self.number_of_factorisations
+                + other.number_of_factorisations,
+            number_of_solves: self.number_of_solves + other.number_of_solves,
+            number_of_iterations: self.number_of_iterations + other.number_of_iterations,
+            factorisation_time: self.factorisation_time + other.factorisation_time,
+            solve_time: self.solve_time + other.solve_time,
+        }
+    }
+}
+
+/// Records the [LinearSolverStatistics] of a solver, through a shared reference since the solves only borrow the solver
+#[derive(Default)]
+pub(crate) struct LinearSolverStatisticsRecorder {
+    statistics: Cell<LinearSolverStatistics>,
+}
+
+impl LinearSolverStatisticsRecorder {
+    pub(crate) fn get(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    pub(crate) fn reset(&self) {
+        self.statistics.set(LinearSolverStatistics::default());
+    }
+
+    /// Record a factorisation that was started at `start`
+    pub(crate) fn factorisation(&self, start: Instant) {
+        let mut statistics = self.statistics.get();
+        statistics.number_of_factorisations += 1;
+        statistics.factorisation_time += start.elapsed();
+        self.statistics.set(statistics);
+    }
+
+    /// Record the solve `f` for `nrhs` right-hand sides, returning its result
+    pub(crate) fn solve<R>(&self, nrhs: usize, f: impl FnOnce() -> R) -> R {
+        let start = Instant::now();
+        let result = f();
+        let mut statistics = self.statistics.get();
+        statistics.number_of_solves += nrhs;
+        statistics.solve_time += start.elapsed();
+        self.statistics.set(statistics);
+        result
+    }
+}
+
+/// Diagnostics reported by a [LinearSolver] for its current linearisation
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearSolverReport<T> {
+    /// Estimate of the reciprocal condition number of the factorised matrix (`None` if the solver does not provide one).
+    /// Values close to zero indicate a nearly singular matrix.
+    pub rcond: Option<T>,
+    /// Number of iterative refinement steps taken by the solves since the last call to [LinearSolver::set_linearisation]
+    pub refinement_iters: usize,
+}
+
+impl<T> Default for LinearSolverReport<T> {
+    fn default() -> Self {
+        Self {
+            rcond: None,
+            refinement_iters: 0,
+        }
+    }
 }
 
 pub struct LinearSolveSolution<V> {
@@ -49,21 +233,24 @@ pub mod tests {
     use std::rc::Rc;
 
     use crate::{
-        linear_solver::{FaerLU, NalgebraLU},
+        linear_solver::{BlockDiagonalLU, NalgebraLU},
         op::{closure::Closure, NonLinearOp},
         scalar::scale,
         vector::VectorRef,
-        DenseMatrix, LinearSolver, SolverProblem, Vector,
+        Bdf, DenseMatrix, Gmres, Ilu0Preconditioner, LinearSolver, OdeBuilder, OdeSolverMethod,
+        PreconditionerSide, Scalar, SolverProblem, Vector, QR,
     };
+    use nalgebra::{DMatrix, DVector};
     use num_traits::{One, Zero};
 
     use super::LinearSolveSolution;
 
+    #[allow(clippy::type_complexity)]
     fn linear_problem<M: DenseMatrix + 'static>() -> (
         SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
         Vec<LinearSolveSolution<M::V>>,
     ) {
-        let diagonal = M::V::from_vec(vec![2.0.into(), 2.0.into()]);
+        let diagonal = M::V::from_vec(vec![M::T::cast(2.0), M::T::cast(2.0)]);
         let jac1 = M::from_diagonal(&diagonal);
         let jac2 = M::from_diagonal(&diagonal);
         let p = Rc::new(M::V::zeros(0));
@@ -75,12 +262,12 @@ pub mod tests {
             2,
             p,
         ));
-        let rtol = M::T::from(1e-6);
-        let atol = Rc::new(M::V::from_vec(vec![1e-6.into(), 1e-6.into()]));
+        let rtol = M::T::cast(1e-6);
+        let atol = Rc::new(M::V::from_vec(vec![M::T::cast(1e-6), M::T::cast(1e-6)]));
         let problem = SolverProblem::new(op, atol, rtol);
         let solns = vec![LinearSolveSolution::new(
-            M::V::from_vec(vec![2.0.into(), 4.0.into()]),
-            M::V::from_vec(vec![1.0.into(), 2.0.into()]),
+            M::V::from_vec(vec![M::T::cast(2.0), M::T::cast(4.0)]),
+            M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(2.0)]),
         )];
         (problem, solns)
     }
@@ -99,12 +286,117 @@ pub mod tests {
         solver.set_linearisation(&x, t);
         for soln in solns {
             let x = solver.solve(&soln.b).unwrap();
-            let tol = { &soln.x * scale(problem.rtol) + problem.atol.as_ref() };
+            let tol = { &soln.x * scale(problem.rtol.clone()) + problem.atol.as_ref() };
             x.assert_eq(&soln.x, &tol);
         }
     }
 
+    // 1D discrete convection-diffusion operator, -u'' + c u', which is non-symmetric and has a condition number growing with n^2
+    pub fn convection_diffusion(n: usize) -> DMatrix<f64> {
+        let c = 20.0 / (n as f64);
+        DMatrix::from_fn(n, n, |i, j| {
+            if i == j {
+                2.0
+            } else if j + 1 == i {
+                -1.0 - c
+            } else if i + 1 == j {
+                -1.0 + c
+            } else {
+                0.0
+            }
+        })
+    }
+
+    // diagonally dominant matrix with a diagonal ranging over several orders of magnitude
+    pub fn badly_scaled(n: usize) -> DMatrix<f64> {
+        let mut a = convection_diffusion(n);
+        for i in 0..n {
+            a[(i, i)] = 3.0 * 10f64.powf(3.0 * (i as f64) / (n as f64));
+        }
+        a
+    }
+
+    // the linear problem `Ax = b` with a known solution `x`
+    #[allow(clippy::type_complexity)]
+    pub fn dense_linear_problem(
+        a: DMatrix<f64>,
+    ) -> (
+        SolverProblem<impl NonLinearOp<M = DMatrix<f64>, V = DVector<f64>, T = f64>>,
+        Vec<LinearSolveSolution<DVector<f64>>>,
+    ) {
+        let n = a.nrows();
+        let x = DVector::from_fn(n, |i, _| 1.0 + (i as f64).sin());
+        let b = &a * &x;
+        let a2 = a.clone();
+        let op = Closure::<DMatrix<f64>, _, _>::new(
+            move |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| a.mul_to(x, y),
+            move |_x: &DVector<f64>,
+                  _p: &DVector<f64>,
+                  _t,
+                  v: &DVector<f64>,
+                  y: &mut DVector<f64>| { a2.mul_to(v, y) },
+            n,
+            n,
+            Rc::new(DVector::zeros(0)),
+        );
+        let atol = Rc::new(DVector::from_element(n, 1e-8));
+        let problem = SolverProblem::new(Rc::new(op), atol, 1e-8);
+        (problem, vec![LinearSolveSolution::new(b, x)])
+    }
+
+    #[test]
+    fn test_linear_solver_statistics() {
+        let n = 10;
+        let (p, solns) = dense_linear_problem(convection_diffusion(n));
+        let mut s = NalgebraLU::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        s.solve(&solns[0].b).unwrap();
+        let mut b = DMatrix::from_fn(n, 3, |i, _| solns[0].b[i]);
+        s.solve_matrix_in_place(&mut b).unwrap();
+        let stats = s.statistics();
+        assert_eq!(stats.number_of_factorisations, 1);
+        assert_eq!(stats.number_of_solves, 4);
+        assert_eq!(stats.number_of_iterations, 0);
+        assert!(stats.total_time() >= stats.solve_time);
+        s.set_problem(&p);
+        assert_eq!(s.statistics().number_of_solves, 0);
+
+        // the iterations of the Krylov solvers are included
+        let mut s = Gmres::new(Ilu0Preconditioner::default(), PreconditionerSide::Right);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        s.solve(&solns[0].b).unwrap();
+        s.solve(&solns[0].b).unwrap();
+        let stats = s.statistics();
+        assert_eq!(stats.number_of_factorisations, 1);
+        assert_eq!(stats.number_of_solves, 2);
+        assert_eq!(stats.number_of_iterations, s.number_of_iterations());
+        assert!(stats.number_of_iterations > 0);
+
+        // and are mirrored in the statistics of the ode solvers
+        let problem = OdeBuilder::new()
+            .p([0.1])
+            .build_ode::<DMatrix<f64>, _, _, _>(
+                |x, p, _t, y| y.copy_from(&(x * -p[0])),
+                |_x, p, _t, v, y| y.copy_from(&(v * -p[0])),
+                |_p, _t| DVector::from_element(2, 1.0),
+            )
+            .unwrap();
+        let mut solver = Bdf::default();
+        solver.solve(&problem, 1.0).unwrap();
+        let stats = solver.get_statistics();
+        assert!(
+            stats.linear_solver.number_of_factorisations >= stats.number_of_linear_solver_setups
+        );
+        assert_eq!(
+            stats.linear_solver.number_of_solves,
+            stats.number_of_nonlinear_solver_iterations
+        );
+    }
+
     type MCpuNalgebra = nalgebra::DMatrix<f64>;
+    #[cfg(feature = "faer")]
     type MCpuFaer = faer::Mat<f64>;
 
     #[test]
@@ -114,9 +406,49 @@ pub mod tests {
         test_linear_solver(s, p, solns);
     }
     #[test]
+    fn test_solve_matrix_in_place() {
+        let n = 6;
+        let (mut p, _solns) = dense_linear_problem(badly_scaled(n));
+        let a = badly_scaled(n);
+        let x = DMatrix::from_fn(n, 4, |i, j| 1.0 + ((i * 4 + j) as f64).sin());
+        let b = &a * &x;
+        let check = |s: &dyn LinearSolver<_>| {
+            let mut y = b.clone();
+            s.solve_matrix_in_place(&mut y).unwrap();
+            for j in 0..x.ncols() {
+                let col = DVector::from_column_slice(y.column(j).as_slice());
+                let expect = DVector::from_column_slice(x.column(j).as_slice());
+                col.assert_eq_st(&expect, 1e-10);
+            }
+        };
+        for (iterative_refinement, equilibrate) in [(false, false), (true, false), (false, true)] {
+            let mut s = NalgebraLU::default();
+            s.iterative_refinement = iterative_refinement;
+            s.equilibrate = equilibrate;
+            s.set_problem(&p);
+            s.set_linearisation(&DVector::zeros(n), 0.0);
+            check(&s);
+        }
+
+        // the default implementation solves each column in turn
+        let mut s = QR::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        check(&s);
+
+        p.block_sizes = Some(vec![n]);
+        let mut s = BlockDiagonalLU::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        check(&s);
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
     fn test_lu_faer() {
         let (p, solns) = linear_problem::<MCpuFaer>();
-        let s = FaerLU::default();
+        let s = crate::linear_solver::FaerLU::default();
+
         test_linear_solver(s, p, solns);
     }
 }
@@ -0,0 +1,281 @@
+use std::{rc::Rc, time::Instant};
+
+use nalgebra::{DMatrix, DVector, Dyn};
+
+use crate::{
+    linear_solver::{LinearSolverStatistics, LinearSolverStatisticsRecorder},
+    matrix::block_diagonal::BlockDiagonalMatrix,
+    op::{linearise::LinearisedOp, NonLinearOp},
+    LinearOp, LinearSolver, Op, Scalar, SolverProblem,
+};
+
+use crate::errors::PSError;
+
+/// A [LinearSolver] for block-diagonal jacobians, which factorises each diagonal block independently using the LU decomposition
+/// in the [`nalgebra` library](https://nalgebra.org/). The block sizes are taken from [SolverProblem::block_sizes] (if not set,
+/// the whole matrix is a single block), and any coupling between the blocks is ignored.
+///
+/// The blocks are factorised in parallel on the [`rayon`](https://docs.rs/rayon) thread pool if [Self::parallel] is true
+/// and the `rayon` feature is enabled.
+pub struct BlockDiagonalLU<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    /// Factorise the blocks in parallel, ignored without the `rayon` feature (default false)
+    pub parallel: bool,
+    matrix: Option<DMatrix<T>>,
+    // the sum of the block sizes of the problem, if it does not match the number of states
+    invalid_block_sizes: Option<usize>,
+    blocks: Option<BlockDiagonalMatrix<DMatrix<T>>>,
+    lus: Vec<nalgebra::LU<T, Dyn, Dyn>>,
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+}
+
+impl<T, C> Default for BlockDiagonalLU<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn default() -> Self {
+        Self {
+            parallel: false,
+            matrix: None,
+            invalid_block_sizes: None,
+            blocks: None,
+            lus: Vec::new(),
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+        }
+    }
+}
+
+impl<T, C> BlockDiagonalLU<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    /// The block-diagonal matrix that was last factorised
+    pub fn matrix(&self) -> Option<&BlockDiagonalMatrix<DMatrix<T>>> {
+        self.blocks.as_ref()
+    }
+
+    #[cfg_attr(not(feature = "rayon"), allow(unused_variables))]
+    fn factorise(blocks: &[DMatrix<T>], parallel: bool) -> Vec<nalgebra::LU<T, Dyn, Dyn>> {
+        #[cfg(feature = "rayon")]
+        if parallel {
+            use rayon::prelude::*;
+            return blocks.par_iter().map(|b| b.clone().lu()).collect();
+        }
+        blocks.iter().map(|b| b.clone().lu()).collect()
+    }
+
+    fn check_block_sizes(&self) -> Result<(), PSError> {
+        match (self.invalid_block_sizes, self.matrix.as_ref()) {
+            (Some(found), Some(matrix)) => Err(PSError::DimensionMismatch {
+                name: "sum of the block sizes".to_string(),
+                expected: matrix.nrows(),
+                found,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<T, C> LinearSolver<C> for BlockDiagonalLU<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn solve_in_place(&self, state: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            self.check_block_sizes()?;
+            let blocks = match self.blocks.as_ref() {
+                Some(blocks) if !self.lus.is_empty() => blocks,
+                _ => return Err(PSError::LuNotInitialized),
+            };
+            for (i, lu) in self.lus.iter().enumerate() {
+                let offset = blocks.block_offset(i);
+                let n = blocks.blocks()[i].nrows();
+                if !lu.solve_mut(&mut state.rows_mut(offset, n)) {
+                    return Err(PSError::LuFailed);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn solve_matrix_in_place(&self, b: &mut C::M) -> Result<(), PSError> {
+        self.statistics.solve(b.ncols(), || {
+            self.check_block_sizes()?;
+            let blocks = match self.blocks.as_ref() {
+                Some(blocks) if !self.lus.is_empty() => blocks,
+                _ => return Err(PSError::LuNotInitialized),
+            };
+            for (i, lu) in self.lus.iter().enumerate() {
+                let offset = blocks.block_offset(i);
+                let n = blocks.blocks()[i].nrows();
+                if !lu.solve_mut(&mut b.rows_mut(offset, n)) {
+                    return Err(PSError::LuFailed);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn set_linearisation(&mut self, x: &<C as Op>::V, t: <C as Op>::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        // with invalid block sizes there is nothing to factorise, the error is returned by the solve
+        let Some(blocks) = self.blocks.as_mut() else {
+            return;
+        };
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        blocks.copy_from_dense(matrix);
+        self.lus = Self::factorise(blocks.blocks(), self.parallel);
+        self.statistics.factorisation(start);
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let n = linearised_problem.f.nstates();
+        let block_sizes = problem.block_sizes.clone().unwrap_or_else(|| vec![n]);
+        // the block sizes are checked by the builder, but can be set directly on the problem
+        let sum = block_sizes.iter().sum::<usize>();
+        self.invalid_block_sizes = (sum != n).then_some(sum);
+        self.matrix = Some(DMatrix::zeros(n, n));
+        self.blocks = self
+            .invalid_block_sizes
+            .is_none()
+            .then(|| BlockDiagonalMatrix::zeros(&block_sizes));
+        self.problem = Some(linearised_problem);
+        self.lus.clear();
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.matrix = None;
+        self.invalid_block_sizes = None;
+        self.blocks = None;
+        self.lus.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError, linear_solver::tests::dense_linear_problem,
+        matrix::block_diagonal::block_diagonal_indices, Bdf, BlockDiagonalLU, LinearSolver,
+        OdeBuilder, OdeSolverMethod, Vector,
+    };
+
+    #[test]
+    fn test_block_diagonal_lu() {
+        let block_sizes = vec![2, 3, 1, 3];
+        let n = block_sizes.iter().sum();
+        let mut a = DMatrix::<f64>::zeros(n, n);
+        for (i, j) in block_diagonal_indices(&block_sizes) {
+            a[(i, j)] = if i == j {
+                4.0
+            } else {
+                1.0 / (1 + i + j) as f64
+            };
+        }
+        let (mut p, solns) = dense_linear_problem(a);
+        p.block_sizes = Some(block_sizes);
+        for parallel in [false, true] {
+            let mut s = BlockDiagonalLU {
+                parallel,
+                ..Default::default()
+            };
+            s.set_problem(&p);
+            assert!(matches!(
+                s.solve(&solns[0].b),
+                Err(PSError::LuNotInitialized)
+            ));
+            s.set_linearisation(&DVector::zeros(n), 0.0);
+            assert_eq!(s.matrix().unwrap().nblocks(), 4);
+            let x = s.solve(&solns[0].b).unwrap();
+            x.assert_eq_st(&solns[0].x, 1e-10);
+        }
+
+        // without block sizes the whole matrix is a single block
+        p.block_sizes = None;
+        let mut s = BlockDiagonalLU::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        assert_eq!(s.matrix().unwrap().block_sizes(), vec![n]);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-10);
+
+        // block sizes that do not add up to the number of states are reported by the solve
+        p.block_sizes = Some(vec![2, 3]);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::DimensionMismatch {
+                expected: 9,
+                found: 5,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_block_diagonal_bdf() {
+        // uncoupled copies of the stiff system y0' = -k y0 + y1, y1' = -y1, y(0) = [1, 1] with different k
+        let ks = [2.0, 10.0, 100.0];
+        let rhs = move |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+            for (b, k) in ks.iter().enumerate() {
+                y[2 * b] = -k * x[2 * b] + x[2 * b + 1];
+                y[2 * b + 1] = -x[2 * b + 1];
+            }
+        };
+        let rhs_jac = move |_x: &DVector<f64>,
+                            p: &DVector<f64>,
+                            t,
+                            v: &DVector<f64>,
+                            y: &mut DVector<f64>| { rhs(v, p, t, y) };
+        let init = |_p: &DVector<f64>, _t| DVector::from_element(6, 1.0);
+        let t = 1.0;
+        for block_sizes in [None, Some(vec![2, 2, 2])] {
+            let mut builder = OdeBuilder::new().rtol(1e-8).atol([1e-8]);
+            if let Some(block_sizes) = block_sizes {
+                builder = builder.block_sizes(block_sizes);
+            }
+            let problem = builder
+                .build_ode::<DMatrix<f64>, _, _, _>(rhs, rhs_jac, init)
+                .unwrap();
+            let y = Bdf::default().solve(&problem, t).unwrap().y.pop().unwrap();
+            for (b, k) in ks.iter().enumerate() {
+                let y0 = (-t).exp() / (k - 1.0) + (1.0 - 1.0 / (k - 1.0)) * (-k * t).exp();
+                assert!((y[2 * b] - y0).abs() < 1e-6);
+                assert!((y[2 * b + 1] - (-t).exp()).abs() < 1e-6);
+            }
+        }
+
+        let problem = OdeBuilder::new()
+            .block_sizes([2, 3])
+            .build_ode::<DMatrix<f64>, _, _, _>(rhs, rhs_jac, init);
+        assert!(matches!(
+            problem,
+            Err(PSError::DimensionMismatch {
+                expected: 6,
+                found: 5,
+                ..
+            })
+        ));
+    }
+}
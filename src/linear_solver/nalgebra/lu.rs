@@ -1,8 +1,18 @@
-use std::rc::Rc;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    time::Instant,
+};
 
-use nalgebra::{DMatrix, DVector, Dyn};
+use nalgebra::{
+    allocator::Allocator, DMatrix, DVector, DefaultAllocator, Dim, Dyn, StorageMut, U1,
+};
 
 use crate::{
+    linear_solver::{
+        kind::DirectLinearSolver, LinearSolverReport, LinearSolverStatistics,
+        LinearSolverStatisticsRecorder,
+    },
     matrix::sparsity::MatrixSparsityRef,
     op::{linearise::LinearisedOp, NonLinearOp},
     LinearOp, LinearSolver, Matrix, Op, Scalar, SolverProblem,
@@ -10,14 +20,58 @@ use crate::{
 
 use crate::errors::PSError;
 
+// maximum number of refinement iterations of a mixed precision solve (as for LAPACK's dsgesv)
+const MIXED_PRECISION_MAX_ITERS: usize = 30;
+// below this condition estimate of the single precision factorisation, the matrix is factorised in the working precision
+const MIXED_PRECISION_MIN_RCOND: f64 = 1e-4;
+
+fn to_f32<T: Scalar>(x: T) -> f32 {
+    let x: f64 = x.to_f64();
+    x as f32
+}
+
 /// A [LinearSolver] that uses the LU decomposition in the [`nalgebra` library](https://nalgebra.org/) to solve the linear system.
+///
+/// After each factorisation a cheap estimate of the reciprocal condition number of the matrix (the ratio of the smallest
+/// to the largest diagonal entry of the triangular factor) is available from [LinearSolver::report]. If
+/// [Self::iterative_refinement] is true, each solve is followed by one step of iterative refinement, which reduces the
+/// error of the solution for badly conditioned matrices at the cost of an extra matrix-vector product and triangular solve.
+///
+/// If [Self::equilibrate] is true, the rows and columns of the matrix are scaled before the factorisation, i.e. `D_r A D_c`
+/// is factorised with diagonal `D_r` and `D_c` chosen so that the largest entry in each row and column has magnitude one
+/// (for a symmetric problem, see [SolverProblem::symmetric], a single symmetric scaling `D A D` with `D = |diag(A)|^{-1/2}` is
+/// used instead, which keeps the scaled matrix symmetric). The right-hand side and solution are rescaled transparently. This is
+/// useful for stiff kinetics where the entries of the jacobian span many orders of magnitude; note that the condition
+/// estimate is then that of the scaled matrix.
+///
+/// If [Self::mixed_precision] is true, the matrix is factorised in single precision (`f32`), which roughly halves the time
+/// of the factorisation of a large matrix and the memory of its factors (the matrix itself is still kept in the working precision
+/// to compute the residuals), and each solve recovers the accuracy of the working precision by iterative refinement, stopping when `|r|_inf <= sqrt(n) eps |A|_inf |x|_inf` for the residual `r` (as for LAPACK's `dsgesv`).
+/// Matrices that are too badly conditioned for the refinement to converge (based on the condition estimate of the single precision
+/// factorisation) are factorised in the working precision instead.
 pub struct LU<T, C>
 where
     T: Scalar,
     C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
 {
+    /// Apply one step of iterative refinement after each solve (default false)
+    pub iterative_refinement: bool,
+    /// Scale the rows and columns of the matrix before the factorisation (default false)
+    pub equilibrate: bool,
+    /// Factorise the matrix in single precision and refine the solution to the working precision (default false)
+    pub mixed_precision: bool,
     matrix: Option<DMatrix<T>>,
     lu: Option<nalgebra::LU<T, Dyn, Dyn>>,
+    lu32: Option<nalgebra::LU<f32, Dyn, Dyn>>,
+    rcond: Option<T>,
+    // the infinity norm of the matrix, used for the stopping criterion of the mixed precision refinement
+    matrix_norm: T,
+    // work vectors for the right-hand side and residual of the mixed precision refinement
+    work: RefCell<(DVector<T>, DVector<T>)>,
+    // the row and column scalings D_r and D_c of the factorised matrix, if equilibrated
+    scaling: Option<(DVector<T>, DVector<T>)>,
+    refinement_iters: Cell<usize>,
+    statistics: LinearSolverStatisticsRecorder,
     problem: Option<SolverProblem<LinearisedOp<C>>>,
 }
 
@@ -28,37 +82,223 @@ where
 {
     fn default() -> Self {
         Self {
+            iterative_refinement: false,
+            equilibrate: false,
+            mixed_precision: false,
             lu: None,
+            lu32: None,
+            rcond: None,
+            matrix_norm: T::zero(),
+            work: RefCell::new((DVector::zeros(0), DVector::zeros(0))),
+            scaling: None,
+            refinement_iters: Cell::new(0),
+            statistics: LinearSolverStatisticsRecorder::default(),
             problem: None,
             matrix: None,
         }
     }
 }
 
+impl<T, C> LU<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    // solve for each column of `state` using the factorisation
+    fn solve_factorised<C2: Dim, S: StorageMut<T, Dyn, C2>>(
+        &self,
+        state: &mut nalgebra::Matrix<T, Dyn, C2, S>,
+    ) -> Result<(), PSError>
+    where
+        DefaultAllocator: Allocator<f32, Dyn, C2>,
+    {
+        // A x = b is solved as (D_r A D_c) y = D_r b, with x = D_c y
+        if let Some((row_scale, _)) = self.scaling.as_ref() {
+            for mut col in state.column_iter_mut() {
+                col.component_mul_assign(row_scale);
+            }
+        }
+        if let Some(lu32) = self.lu32.as_ref() {
+            let mut state32 = state.map(to_f32);
+            if !lu32.solve_mut(&mut state32) {
+                return Err(PSError::LuFailed);
+            }
+            state.zip_apply(&state32, |x, x32| *x = T::cast(f64::from(x32)));
+        } else {
+            let lu = self.lu.as_ref().ok_or(PSError::LuNotInitialized)?;
+            if !lu.solve_mut(state) {
+                return Err(PSError::LuFailed);
+            }
+        }
+        if let Some((_, col_scale)) = self.scaling.as_ref() {
+            for mut col in state.column_iter_mut() {
+                col.component_mul_assign(col_scale);
+            }
+        }
+        Ok(())
+    }
+
+    // solve for each column of `state`, followed by a step of iterative refinement if enabled
+    fn solve_refined<C2: Dim, S: StorageMut<T, Dyn, C2>>(
+        &self,
+        state: &mut nalgebra::Matrix<T, Dyn, C2, S>,
+    ) -> Result<(), PSError>
+    where
+        DefaultAllocator: Allocator<T, Dyn, C2> + Allocator<f32, Dyn, C2>,
+    {
+        if self.lu32.is_some() {
+            return self.solve_mixed_precision(state);
+        }
+        if !self.iterative_refinement {
+            return self.solve_factorised(state);
+        }
+        let mut residual = state.clone_owned();
+        self.solve_factorised(state)?;
+        // r = b - A x, then x += A^{-1} r
+        let matrix = self.matrix.as_ref().expect("Matrix not set");
+        residual.gemm(-T::one(), matrix, state, T::one());
+        self.solve_factorised(&mut residual)?;
+        *state += residual;
+        self.refinement_iters.set(self.refinement_iters.get() + 1);
+        Ok(())
+    }
+
+    // solve for each column of `state` using the single precision factorisation, refining until the residual is at the level
+    // of the working precision
+    fn solve_mixed_precision<C2: Dim, S: StorageMut<T, Dyn, C2>>(
+        &self,
+        state: &mut nalgebra::Matrix<T, Dyn, C2, S>,
+    ) -> Result<(), PSError>
+    where
+        DefaultAllocator: Allocator<T, Dyn, C2> + Allocator<f32, Dyn, C2>,
+    {
+        let matrix = self.matrix.as_ref().expect("Matrix not set");
+        let tol = T::cast((matrix.nrows() as f64).sqrt()) * T::EPSILON * self.matrix_norm.clone();
+        let mut work = self.work.borrow_mut();
+        let (b, residual) = &mut *work;
+        'columns: for mut x in state.column_iter_mut() {
+            b.copy_from(&x);
+            self.solve_factorised::<U1, _>(&mut x)?;
+            for _ in 0..MIXED_PRECISION_MAX_ITERS {
+                // r = b - A x, then x += A^{-1} r
+                residual.copy_from(b);
+                residual.gemv(-T::one(), matrix, &x, T::one());
+                if residual.amax() <= tol.clone() * x.amax() {
+                    continue 'columns;
+                }
+                self.solve_factorised::<U1, _>(residual)?;
+                x += &*residual;
+                self.refinement_iters.set(self.refinement_iters.get() + 1);
+            }
+            return Err(PSError::LuFailed);
+        }
+        Ok(())
+    }
+
+    /// Row and column scalings `D_r` and `D_c` so that the largest entry in each row and column of `D_r A D_c` has magnitude one,
+    /// or a symmetric scaling `D_r = D_c = |diag(A)|^{-1/2}` if `symmetric` is true. Zero rows, columns or diagonal entries are not scaled.
+    fn scaling(matrix: &DMatrix<T>, symmetric: bool) -> (DVector<T>, DVector<T>) {
+        let inv = |x: T| if x.is_zero() { T::one() } else { T::one() / x };
+        if symmetric {
+            let d = DVector::from_fn(matrix.nrows(), |i, _| {
+                inv(matrix[(i, i)].clone().abs().sqrt())
+            });
+            return (d.clone(), d);
+        }
+        let row_scale = DVector::from_fn(matrix.nrows(), |i, _| inv(matrix.row(i).amax()));
+        let col_scale = DVector::from_fn(matrix.ncols(), |j, _| {
+            inv(matrix.column(j).component_mul(&row_scale).amax())
+        });
+        (row_scale, col_scale)
+    }
+
+    fn diagonal_ratio(diag: DVector<T>) -> T {
+        let diag = diag.abs();
+        let (min, max) = (diag.min(), diag.max());
+        if max.is_zero() {
+            T::zero()
+        } else {
+            min / max
+        }
+    }
+}
+
 impl<T: Scalar, C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>> LinearSolver<C>
     for LU<T, C>
 {
     fn solve_in_place(&self, state: &mut C::V) -> Result<(), PSError> {
-        if self.lu.is_none() {
-            return Err(PSError::LuNotInitialized);
-        }
-        let lu = self.lu.as_ref().unwrap();
-        match lu.solve_mut(state) {
-            true => Ok(()),
-            false => Err(PSError::LuFailed),
+        self.statistics.solve(1, || self.solve_refined(state))
+    }
+
+    fn solve_matrix_in_place(&self, b: &mut C::M) -> Result<(), PSError> {
+        self.statistics.solve(b.ncols(), || self.solve_refined(b))
+    }
+
+    fn report(&self) -> LinearSolverReport<T> {
+        LinearSolverReport {
+            rcond: self.rcond.clone(),
+            refinement_iters: self.refinement_iters.get(),
         }
     }
 
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
     fn set_linearisation(&mut self, x: &<C as Op>::V, t: <C as Op>::T) {
+        let start = Instant::now();
         Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
             .unwrap()
             .set_x(x);
         let matrix = self.matrix.as_mut().expect("Matrix not set");
-        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
-        self.lu = Some(matrix.clone().lu());
+        let problem = self.problem.as_ref().unwrap();
+        problem.f.matrix_inplace(t, matrix);
+        self.scaling = if self.equilibrate {
+            Some(Self::scaling(matrix, problem.symmetric))
+        } else {
+            None
+        };
+        // the matrix is only copied to be scaled, or for the decomposition that is computed in place (the unscaled matrix is
+        // kept for the refinement and the next linearisation)
+        let scaled = self.scaling.as_ref().map(|(row_scale, col_scale)| {
+            let mut scaled = matrix.clone();
+            for (j, mut col) in scaled.column_iter_mut().enumerate() {
+                col.component_mul_assign(row_scale);
+                col *= col_scale[j].clone();
+            }
+            scaled
+        });
+        let a = scaled.as_ref().unwrap_or(matrix);
+        self.lu32 = if self.mixed_precision {
+            let lu32 = a.map(to_f32).lu();
+            let rcond = Self::diagonal_ratio(lu32.u().diagonal().map(|x| T::cast(f64::from(x))));
+            (rcond > T::cast(MIXED_PRECISION_MIN_RCOND)).then_some(lu32)
+        } else {
+            None
+        };
+        if self.lu32.is_some() {
+            self.matrix_norm = matrix
+                .row_iter()
+                .map(|row| row.abs().sum())
+                .fold(T::zero(), T::max);
+        }
+        self.lu = match self.lu32.as_ref() {
+            None => Some(scaled.unwrap_or_else(|| matrix.clone()).lu()),
+            Some(_) => None,
+        };
+        self.rcond = match (self.lu.as_ref(), self.lu32.as_ref()) {
+            (Some(lu), _) => Some(Self::diagonal_ratio(lu.u().diagonal())),
+            (None, Some(lu32)) => Some(Self::diagonal_ratio(
+                lu32.u().diagonal().map(|x| T::cast(f64::from(x))),
+            )),
+            (None, None) => None,
+        };
+        self.refinement_iters.set(0);
+        self.statistics.factorisation(start);
     }
 
     fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
         let linearised_problem = problem.linearise();
         let ncols = linearised_problem.f.nstates();
         let nrows = linearised_problem.f.nout();
@@ -69,5 +309,154 @@ impl<T: Scalar, C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>> LinearSol
         );
         self.problem = Some(linearised_problem);
         self.matrix = Some(matrix);
+        self.work = RefCell::new((DVector::zeros(nrows), DVector::zeros(nrows)));
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.lu = None;
+        self.lu32 = None;
+        self.rcond = None;
+        self.scaling = None;
+    }
+}
+
+impl<T: Scalar, C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>> DirectLinearSolver<C>
+    for LU<T, C>
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        linear_solver::tests::{badly_scaled, convection_diffusion, dense_linear_problem},
+        ode_solver::test_models::robertson::robertson,
+        Bdf, LinearSolver, NalgebraLU, NewtonNonlinearSolver, OdeSolverMethod, Vector,
+    };
+
+    #[test]
+    fn test_lu_report() {
+        let n = 10;
+        let (p, solns) = dense_linear_problem(badly_scaled(n));
+        for iterative_refinement in [false, true] {
+            let mut s = NalgebraLU {
+                iterative_refinement,
+                ..Default::default()
+            };
+            s.set_problem(&p);
+            assert_eq!(s.report().rcond, None);
+            s.set_linearisation(&DVector::zeros(n), 0.0);
+            let rcond = s.report().rcond.unwrap();
+            assert!(rcond > 0.0 && rcond < 1e-2);
+            let x = s.solve(&solns[0].b).unwrap();
+            x.assert_eq_st(&solns[0].x, 1e-10);
+            let x = s.solve(&solns[0].b).unwrap();
+            x.assert_eq_st(&solns[0].x, 1e-10);
+            let expect = if iterative_refinement { 2 } else { 0 };
+            assert_eq!(s.report().refinement_iters, expect);
+            s.set_linearisation(&DVector::zeros(n), 0.0);
+            assert_eq!(s.report().refinement_iters, 0);
+        }
+
+        // a nearly singular matrix has a tiny condition estimate
+        let a = DMatrix::from_row_slice(2, 2, &[1.0, 1.0, 1.0, 1.0 + 1e-14]);
+        let (p, _solns) = dense_linear_problem(a);
+        let mut s = NalgebraLU::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(2), 0.0);
+        assert!(s.report().rcond.unwrap() < 1e-12);
+    }
+
+    #[test]
+    fn test_lu_equilibrate() {
+        // entries spanning 12 orders of magnitude, as for the jacobian of a stiff kinetics problem
+        let n = 8;
+        let a = DMatrix::from_fn(n, n, |i, j| {
+            if i == j {
+                4.0
+            } else {
+                1.0 / (1 + i + j) as f64
+            }
+        });
+        let d = DVector::from_fn(n, |i, _| 10f64.powf(12.0 * i as f64 / (n - 1) as f64 - 6.0));
+        for symmetric in [false, true] {
+            // scale the rows, or the rows and columns symmetrically
+            let a = if symmetric {
+                let d = d.map(|x| x.sqrt());
+                DMatrix::from_diagonal(&d) * &a * DMatrix::from_diagonal(&d)
+            } else {
+                DMatrix::from_diagonal(&d) * &a
+            };
+            let (mut p, solns) = dense_linear_problem(a);
+            p.symmetric = symmetric;
+            let mut rconds = Vec::new();
+            for equilibrate in [false, true] {
+                let mut s = NalgebraLU {
+                    equilibrate,
+                    ..Default::default()
+                };
+                s.set_problem(&p);
+                s.set_linearisation(&DVector::zeros(n), 0.0);
+                let x = s.solve(&solns[0].b).unwrap();
+                // without scaling the LU decomposition of the badly conditioned matrix loses a few digits
+                let tol = if equilibrate { 1e-8 } else { 1e-6 };
+                x.assert_eq_st(&solns[0].x, tol);
+                rconds.push(s.report().rcond.unwrap());
+            }
+            assert!(rconds[0] < 1e-6);
+            assert!(rconds[1] > 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_lu_mixed_precision() {
+        let n = 50;
+        let (p, solns) = dense_linear_problem(convection_diffusion(n));
+        let mut s = NalgebraLU {
+            mixed_precision: true,
+            ..Default::default()
+        };
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-12);
+        assert!(s.report().refinement_iters > 0);
+        let mut b = DMatrix::from_fn(n, 2, |i, j| (j + 1) as f64 * solns[0].b[i]);
+        s.solve_matrix_in_place(&mut b).unwrap();
+        b.column(1)
+            .into_owned()
+            .assert_eq_st(&(&solns[0].x * 2.0), 1e-12);
+
+        // a badly conditioned matrix is factorised in the working precision
+        let d = DVector::from_fn(n, |i, _| 10f64.powf(-8.0 * i as f64 / (n - 1) as f64));
+        let (p, solns) = dense_linear_problem(convection_diffusion(n) * DMatrix::from_diagonal(&d));
+        let mut s = NalgebraLU {
+            mixed_precision: true,
+            ..Default::default()
+        };
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-6);
+        assert!(s.report().rcond.unwrap() < 1e-4);
+        assert_eq!(s.report().refinement_iters, 0);
+    }
+
+    #[test]
+    fn test_lu_mixed_precision_bdf() {
+        type M = DMatrix<f64>;
+        let (problem, soln) = robertson::<M>();
+        let t = soln.solution_points.last().unwrap().t;
+        let y_expect = Bdf::default().solve(&problem, t).unwrap().y.pop().unwrap();
+
+        let lu = NalgebraLU {
+            mixed_precision: true,
+            ..Default::default()
+        };
+        let mut solver = Bdf::<M, _, _>::new(NewtonNonlinearSolver::new(lu));
+        let y = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        y.assert_eq(&y_expect, &y_expect.map(|x| 1e-6 * x.abs() + 1e-12));
     }
 }
@@ -1 +1,2 @@
+pub mod block_diagonal;
 pub mod lu;
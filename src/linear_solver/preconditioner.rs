@@ -0,0 +1,678 @@
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{errors::PSError, scalar::IndexType, Matrix, Scalar, Vector};
+
+/// A preconditioner `P ≈ A` for the iterative solution of the linear system `Ax = b` (see [crate::Gmres]).
+pub trait Preconditioner<M: Matrix> {
+    /// Whether [Self::setup] needs the assembled matrix `A`. If `false`, the iterative solver does not assemble `A` and
+    /// [Self::setup] is never called.
+    fn requires_matrix(&self) -> bool {
+        true
+    }
+
+    /// Set up the preconditioner for the matrix `A`, called each time the linearisation of the operator changes.
+    fn setup(&mut self, a: &M) -> Result<(), PSError>;
+
+    /// Apply the inverse of the preconditioner in place, i.e. `x <- P^{-1} x`.
+    fn apply_in_place(&self, x: &mut M::V);
+
+    /// Set the size of the diagonal blocks used by block preconditioners (see [BlockJacobiPreconditioner]), called by the
+    /// iterative solver when the problem sets [crate::SolverProblem::preconditioner_block_size]. The default implementation does nothing.
+    fn set_block_size(&mut self, _block_size: IndexType) {}
+}
+
+/// The identity preconditioner `P = I`, i.e. no preconditioning.
+#[derive(Clone, Debug, Default)]
+pub struct IdentityPreconditioner;
+
+impl<M: Matrix> Preconditioner<M> for IdentityPreconditioner {
+    fn requires_matrix(&self) -> bool {
+        false
+    }
+    fn setup(&mut self, _a: &M) -> Result<(), PSError> {
+        Ok(())
+    }
+    fn apply_in_place(&self, _x: &mut M::V) {}
+}
+
+/// The Jacobi (diagonal) preconditioner `P = diag(A)`.
+#[derive(Clone, Debug)]
+pub struct JacobiPreconditioner<V: Vector> {
+    inv_diagonal: Option<V>,
+}
+
+impl<V: Vector> Default for JacobiPreconditioner<V> {
+    fn default() -> Self {
+        Self { inv_diagonal: None }
+    }
+}
+
+impl<M: Matrix> Preconditioner<M> for JacobiPreconditioner<M::V> {
+    fn setup(&mut self, a: &M) -> Result<(), PSError> {
+        let diagonal = a.diagonal();
+        if let Some(i) = (0..diagonal.len()).find(|&i| diagonal[i] == M::T::zero()) {
+            return Err(PSError::Other {
+                e: format!(
+                    "zero on the diagonal in row {} of the Jacobi preconditioner",
+                    i
+                ),
+            });
+        }
+        let mut inv_diagonal = M::V::from_element(diagonal.len(), M::T::one());
+        inv_diagonal.component_div_assign(&diagonal);
+        self.inv_diagonal = Some(inv_diagonal);
+        Ok(())
+    }
+    fn apply_in_place(&self, x: &mut M::V) {
+        x.component_mul_assign(
+            self.inv_diagonal
+                .as_ref()
+                .expect("Jacobi preconditioner not set up"),
+        );
+    }
+}
+
+/// The incomplete LU factorisation with zero fill-in, ILU(0), `P = LU` where `L` (unit lower triangular) and `U` (upper triangular)
+/// have the same sparsity pattern as the lower and upper parts of `A`. The factors are stored in compressed sparse row format,
+/// so for a dense matrix this is an LU factorisation without pivoting.
+#[derive(Clone, Debug)]
+pub struct Ilu0Preconditioner<T> {
+    // compressed sparse row storage of L (strictly lower part, unit diagonal not stored) and U, with sorted column indices
+    row_ptrs: Vec<IndexType>,
+    col_indices: Vec<IndexType>,
+    values: Vec<T>,
+    // index of the diagonal entry in each row
+    diag: Vec<IndexType>,
+}
+
+impl<T> Default for Ilu0Preconditioner<T> {
+    fn default() -> Self {
+        Self {
+            row_ptrs: Vec::new(),
+            col_indices: Vec::new(),
+            values: Vec::new(),
+            diag: Vec::new(),
+        }
+    }
+}
+
+impl<M: Matrix> Preconditioner<M> for Ilu0Preconditioner<M::T> {
+    fn setup(&mut self, a: &M) -> Result<(), PSError> {
+        let n = a.nrows();
+        let mut triplets = a
+            .triplet_iter()
+            .map(|(i, j, v)| (i, j, v.clone()))
+            .collect::<Vec<_>>();
+        triplets.sort_by_key(|&(i, j, _)| (i, j));
+
+        self.row_ptrs = vec![0; n + 1];
+        for &(i, _, _) in triplets.iter() {
+            self.row_ptrs[i + 1] += 1;
+        }
+        for i in 0..n {
+            self.row_ptrs[i + 1] += self.row_ptrs[i];
+        }
+        self.col_indices = triplets.iter().map(|&(_, j, _)| j).collect();
+        self.values = triplets.into_iter().map(|(_, _, v)| v).collect();
+        self.diag = vec![0; n];
+        for i in 0..n {
+            let row = self.row_ptrs[i]..self.row_ptrs[i + 1];
+            self.diag[i] = row
+                .clone()
+                .find(|&k| self.col_indices[k] == i)
+                .ok_or_else(|| PSError::Other {
+                    e: format!(
+                        "no diagonal entry in row {} of the ILU(0) preconditioner",
+                        i
+                    ),
+                })?;
+        }
+
+        // IKJ variant of the incomplete factorisation, `pos[j]` is the position of column j in the current row (or None)
+        let mut pos: Vec<Option<IndexType>> = vec![None; n];
+        for i in 0..n {
+            let row = self.row_ptrs[i]..self.row_ptrs[i + 1];
+            for k in row.clone() {
+                pos[self.col_indices[k]] = Some(k);
+            }
+            for k in self.row_ptrs[i]..self.diag[i] {
+                let kcol = self.col_indices[k];
+                let pivot = self.values[self.diag[kcol]].clone();
+                if pivot == M::T::zero() {
+                    return Err(PSError::Other {
+                        e: format!("zero pivot in row {} of the ILU(0) preconditioner", kcol),
+                    });
+                }
+                let lik = self.values[k].clone() / pivot;
+                self.values[k] = lik.clone();
+                for kj in self.diag[kcol] + 1..self.row_ptrs[kcol + 1] {
+                    if let Some(ij) = pos[self.col_indices[kj]] {
+                        let ukj = self.values[kj].clone();
+                        self.values[ij] -= lik.clone() * ukj;
+                    }
+                }
+            }
+            for k in row {
+                pos[self.col_indices[k]] = None;
+            }
+        }
+        if let Some(i) = (0..n).find(|&i| self.values[self.diag[i]] == M::T::zero()) {
+            return Err(PSError::Other {
+                e: format!("zero pivot in row {} of the ILU(0) preconditioner", i),
+            });
+        }
+        Ok(())
+    }
+
+    fn apply_in_place(&self, x: &mut M::V) {
+        let n = self.diag.len();
+        // solve L y = x
+        for i in 0..n {
+            let mut xi = x[i].clone();
+            for k in self.row_ptrs[i]..self.diag[i] {
+                xi -= self.values[k].clone() * x[self.col_indices[k]].clone();
+            }
+            x[i] = xi;
+        }
+        // solve U x = y
+        for i in (0..n).rev() {
+            let mut xi = x[i].clone();
+            for k in self.diag[i] + 1..self.row_ptrs[i + 1] {
+                xi -= self.values[k].clone() * x[self.col_indices[k]].clone();
+            }
+            x[i] = xi / self.values[self.diag[i]].clone();
+        }
+    }
+}
+
+/// The block-Jacobi preconditioner `P = blockdiag(A)`, made up of the dense square blocks of size [Self::block_size] along the
+/// diagonal of `A` (the last block is smaller if the block size does not divide the number of rows). Each block is factorised
+/// independently using the LU decomposition with partial pivoting, so unlike [Ilu0Preconditioner] the set up and the application
+/// of the preconditioner are not sequential over the rows. This suits method-of-lines discretisations of reaction–diffusion
+/// systems with the species at each grid point numbered consecutively, using the number of species as the block size, so that
+/// the (stiff) local reactions are solved exactly.
+///
+/// The block size is given to [Self::new], or set from the problem (see [crate::OdeBuilder::preconditioner_block_size]).
+/// The default block size of 1 gives the [JacobiPreconditioner].
+#[derive(Clone, Debug)]
+pub struct BlockJacobiPreconditioner<T> {
+    block_size: IndexType,
+    // LU factors of each block in column-major order (unit lower triangular L below the diagonal), and the row permutations
+    factors: Vec<Vec<T>>,
+    pivots: Vec<Vec<IndexType>>,
+}
+
+impl<T> Default for BlockJacobiPreconditioner<T> {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+impl<T> BlockJacobiPreconditioner<T> {
+    /// Create a new block-Jacobi preconditioner with diagonal blocks of size `block_size`
+    pub fn new(block_size: IndexType) -> Self {
+        assert!(block_size > 0, "block size must be positive");
+        Self {
+            block_size,
+            factors: Vec::new(),
+            pivots: Vec::new(),
+        }
+    }
+
+    pub fn block_size(&self) -> IndexType {
+        self.block_size
+    }
+
+    pub fn nblocks(&self) -> usize {
+        self.factors.len()
+    }
+}
+
+impl<M: Matrix> Preconditioner<M> for BlockJacobiPreconditioner<M::T> {
+    fn set_block_size(&mut self, block_size: IndexType) {
+        assert!(block_size > 0, "block size must be positive");
+        self.block_size = block_size;
+        self.factors.clear();
+        self.pivots.clear();
+    }
+
+    fn setup(&mut self, a: &M) -> Result<(), PSError> {
+        let n = a.nrows();
+        let bs = self.block_size;
+        let nblocks = n.div_ceil(bs);
+        let size = |b: usize| bs.min(n - b * bs);
+        self.factors = (0..nblocks)
+            .map(|b| vec![M::T::zero(); size(b) * size(b)])
+            .collect();
+        for (i, j, v) in a.triplet_iter() {
+            let b = i / bs;
+            if j / bs == b {
+                self.factors[b][i % bs + (j % bs) * size(b)] = v.clone();
+            }
+        }
+        self.pivots = Vec::with_capacity(nblocks);
+        for (b, lu) in self.factors.iter_mut().enumerate() {
+            let m = size(b);
+            let mut pivots = vec![0; m];
+            for k in 0..m {
+                let p = (k..m)
+                    .max_by(|&p, &q| {
+                        lu[p + k * m]
+                            .clone()
+                            .abs()
+                            .partial_cmp(&lu[q + k * m].clone().abs())
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .unwrap();
+                if lu[p + k * m] == M::T::zero() {
+                    return Err(PSError::Other {
+                        e: format!("singular block {} of the block-Jacobi preconditioner", b),
+                    });
+                }
+                pivots[k] = p;
+                if p != k {
+                    for j in 0..m {
+                        lu.swap(k + j * m, p + j * m);
+                    }
+                }
+                let pivot = lu[k + k * m].clone();
+                for i in k + 1..m {
+                    lu[i + k * m] /= pivot.clone();
+                }
+                for j in k + 1..m {
+                    let ukj = lu[k + j * m].clone();
+                    for i in k + 1..m {
+                        let lik = lu[i + k * m].clone();
+                        lu[i + j * m] -= lik * ukj.clone();
+                    }
+                }
+            }
+            self.pivots.push(pivots);
+        }
+        Ok(())
+    }
+
+    fn apply_in_place(&self, x: &mut M::V) {
+        assert!(
+            self.pivots.len() == x.len().div_ceil(self.block_size),
+            "block-Jacobi preconditioner not set up"
+        );
+        for (b, (lu, pivots)) in self.factors.iter().zip(self.pivots.iter()).enumerate() {
+            let offset = b * self.block_size;
+            let m = pivots.len();
+            for (k, &p) in pivots.iter().enumerate() {
+                if p != k {
+                    let tmp = x[offset + k].clone();
+                    x[offset + k] = x[offset + p].clone();
+                    x[offset + p] = tmp;
+                }
+            }
+            // solve L y = x
+            for j in 0..m {
+                let xj = x[offset + j].clone();
+                for i in j + 1..m {
+                    x[offset + i] -= lu[i + j * m].clone() * xj.clone();
+                }
+            }
+            // solve U x = y
+            for j in (0..m).rev() {
+                let xj = x[offset + j].clone() / lu[j + j * m].clone();
+                x[offset + j] = xj.clone();
+                for i in 0..j {
+                    x[offset + i] -= lu[i + j * m].clone() * xj.clone();
+                }
+            }
+        }
+    }
+}
+
+/// The sparse approximate inverse (SPAI) preconditioner, an explicit sparse matrix `P^{-1} ≈ A^{-1}` which has the sparsity
+/// pattern of the non-zero entries of `A` (plus the diagonal). Each column `m_j` of the approximate inverse independently minimises
+/// `||A m_j - e_j||_2` over this pattern, by solving a small dense least-squares problem with the Householder QR decomposition.
+/// Applying the preconditioner is a sparse matrix-vector product, so unlike the triangular solves of [Ilu0Preconditioner] it
+/// has no sequential dependency between the rows, which makes it a good choice for the jacobians of 2D and 3D reaction–diffusion
+/// problems. The setup is more expensive than ILU(0), and since every entry of a dense matrix is non-zero this is only practical
+/// for sparse matrices (or small dense ones).
+#[derive(Clone, Debug)]
+pub struct SpaiPreconditioner<T> {
+    // compressed sparse column storage of the approximate inverse, with sorted row indices
+    col_ptrs: Vec<IndexType>,
+    row_indices: Vec<IndexType>,
+    values: Vec<T>,
+}
+
+impl<T> Default for SpaiPreconditioner<T> {
+    fn default() -> Self {
+        Self {
+            col_ptrs: Vec::new(),
+            row_indices: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T> SpaiPreconditioner<T> {
+    /// The number of non-zero entries of the approximate inverse
+    pub fn nnz(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl<M: Matrix> Preconditioner<M> for SpaiPreconditioner<M::T> {
+    fn setup(&mut self, a: &M) -> Result<(), PSError> {
+        let n = a.nrows();
+        // the non-zero entries of A in compressed sparse column format
+        let mut triplets = a
+            .triplet_iter()
+            .filter(|(_, _, v)| **v != M::T::zero())
+            .map(|(i, j, v)| (i, j, v.clone()))
+            .collect::<Vec<_>>();
+        triplets.sort_by_key(|&(i, j, _)| (j, i));
+        let mut a_col_ptrs = vec![0; n + 1];
+        for &(_, j, _) in triplets.iter() {
+            a_col_ptrs[j + 1] += 1;
+        }
+        for j in 0..n {
+            a_col_ptrs[j + 1] += a_col_ptrs[j];
+        }
+        let a_col = |j: usize| &triplets[a_col_ptrs[j]..a_col_ptrs[j + 1]];
+
+        self.col_ptrs = vec![0];
+        self.row_indices.clear();
+        self.values.clear();
+        // `pos[i]` is the position of row i in the rows `rows` of the current least-squares problem (or None)
+        let mut pos: Vec<Option<IndexType>> = vec![None; n];
+        for j in 0..n {
+            // the pattern of column j of A (and the diagonal), and the rows of A that are non-zero in these columns
+            let mut cols = a_col(j).iter().map(|&(i, _, _)| i).collect::<Vec<_>>();
+            if let Err(k) = cols.binary_search(&j) {
+                cols.insert(k, j);
+            }
+            let mut rows = Vec::new();
+            for &k in cols.iter() {
+                for &(i, _, _) in a_col(k) {
+                    if pos[i].is_none() {
+                        pos[i] = Some(rows.len());
+                        rows.push(i);
+                    }
+                }
+            }
+            let (nr, nc) = (rows.len(), cols.len());
+            if nr < nc {
+                return Err(PSError::Other {
+                    e: format!("rank-deficient column {} of the SPAI preconditioner", j),
+                });
+            }
+
+            // min ||A(rows, cols) m - e_j(rows)||, with the dense column-major matrix `qr` and right-hand side `rhs`
+            let mut qr = vec![M::T::zero(); nr * nc];
+            for (c, &k) in cols.iter().enumerate() {
+                for (i, _, v) in a_col(k) {
+                    qr[pos[*i].unwrap() + c * nr] = v.clone();
+                }
+            }
+            let mut rhs = vec![M::T::zero(); nr];
+            if let Some(r) = pos[j] {
+                rhs[r] = M::T::one();
+            }
+            for &i in rows.iter() {
+                pos[i] = None;
+            }
+
+            // Householder QR, applying the reflections to the right-hand side
+            for k in 0..nc {
+                let norm = (k..nr)
+                    .map(|i| qr[i + k * nr].clone() * qr[i + k * nr].clone())
+                    .fold(M::T::zero(), |acc, x| acc + x)
+                    .sqrt();
+                if norm == M::T::zero() {
+                    return Err(PSError::Other {
+                        e: format!("rank-deficient column {} of the SPAI preconditioner", j),
+                    });
+                }
+                let alpha = if qr[k + k * nr] > M::T::zero() {
+                    -norm
+                } else {
+                    norm
+                };
+                // v = x - alpha e_k is stored in place of the column, R[k, k] = alpha
+                qr[k + k * nr] -= alpha.clone();
+                let vtv = (k..nr)
+                    .map(|i| qr[i + k * nr].clone() * qr[i + k * nr].clone())
+                    .fold(M::T::zero(), |acc, x| acc + x);
+                let reflect = |y: &mut [M::T], v: &[M::T]| {
+                    let vty = (k..nr)
+                        .map(|i| v[i].clone() * y[i].clone())
+                        .fold(M::T::zero(), |acc, x| acc + x);
+                    let f = M::T::cast(2.0) * vty / vtv.clone();
+
+                    for i in k..nr {
+                        y[i] -= f.clone() * v[i].clone();
+                    }
+                };
+                let (left, right) = qr.split_at_mut((k + 1) * nr);
+                let v = &left[k * nr..];
+                for y in right.chunks_mut(nr) {
+                    reflect(y, v);
+                }
+                reflect(&mut rhs, v);
+                qr[k + k * nr] = alpha;
+            }
+            // back substitution with R
+            for k in (0..nc).rev() {
+                let mut sum = rhs[k].clone();
+                for c in k + 1..nc {
+                    sum -= qr[k + c * nr].clone() * rhs[c].clone();
+                }
+                rhs[k] = sum / qr[k + k * nr].clone();
+            }
+            self.row_indices.extend_from_slice(&cols);
+            self.values.extend_from_slice(&rhs[..nc]);
+            self.col_ptrs.push(self.row_indices.len());
+        }
+        Ok(())
+    }
+
+    fn apply_in_place(&self, x: &mut M::V) {
+        let n = self
+            .col_ptrs
+            .len()
+            .checked_sub(1)
+            .expect("SPAI preconditioner not set up");
+        let mut y = vec![M::T::zero(); n];
+        for j in 0..n {
+            let xj = x[j].clone();
+            for k in self.col_ptrs[j]..self.col_ptrs[j + 1] {
+                y[self.row_indices[k]] += self.values[k].clone() * xj.clone();
+            }
+        }
+        for (i, yi) in y.into_iter().enumerate() {
+            x[i] = yi;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::tests::{convection_diffusion, dense_linear_problem},
+        Bdf, BlockJacobiPreconditioner, Gmres, IdentityPreconditioner, Ilu0Preconditioner,
+        JacobiPreconditioner, LinearSolver, NewtonNonlinearSolver, OdeBuilder, OdeSolverMethod,
+        Preconditioner, PreconditionerSide, SpaiPreconditioner, Vector,
+    };
+
+    #[test]
+    fn test_ilu0_tridiagonal() {
+        // the LU factors of a tridiagonal matrix have no fill-in, so ILU(0) is exact
+        let a = DMatrix::from_row_slice(3, 3, &[4.0, -1.0, 0.0, -1.0, 4.0, -1.0, 0.0, -1.0, 4.0]);
+        let mut ilu = Ilu0Preconditioner::default();
+        Preconditioner::<DMatrix<f64>>::setup(&mut ilu, &a).unwrap();
+        let x = DVector::from_vec(vec![1.0, 2.0, 3.0]);
+        let mut b = &a * &x;
+        Preconditioner::<DMatrix<f64>>::apply_in_place(&ilu, &mut b);
+        b.assert_eq_st(&x, 1e-12);
+
+        let mut jacobi = JacobiPreconditioner::default();
+        Preconditioner::<DMatrix<f64>>::setup(&mut jacobi, &a).unwrap();
+        let mut b = DVector::from_vec(vec![4.0, 8.0, 12.0]);
+        Preconditioner::<DMatrix<f64>>::apply_in_place(&jacobi, &mut b);
+        b.assert_eq_st(&x, 1e-12);
+    }
+
+    #[test]
+    fn test_block_jacobi() {
+        // block-diagonal matrix with blocks of size 3 (the last of size 2), the first block needs pivoting
+        let block_size = 3;
+        let n = 8;
+        let a = DMatrix::from_fn(n, n, |i, j| {
+            if i / block_size != j / block_size {
+                0.0
+            } else if i == j {
+                if i == 0 {
+                    0.0
+                } else {
+                    4.0 + i as f64
+                }
+            } else {
+                1.0 / (1 + i + 2 * j) as f64
+            }
+        });
+        let x = DVector::from_fn(n, |i, _| 1.0 + i as f64);
+        let mut precon = BlockJacobiPreconditioner::new(block_size);
+        Preconditioner::<DMatrix<f64>>::setup(&mut precon, &a).unwrap();
+        assert_eq!(precon.nblocks(), 3);
+        let mut b = &a * &x;
+        Preconditioner::<DMatrix<f64>>::apply_in_place(&precon, &mut b);
+        b.assert_eq_st(&x, 1e-12);
+
+        // with blocks of size 1 this is the Jacobi preconditioner, which fails on the zero diagonal
+        Preconditioner::<DMatrix<f64>>::set_block_size(&mut precon, 1);
+        assert_eq!(precon.block_size(), 1);
+        assert!(Preconditioner::<DMatrix<f64>>::setup(&mut precon, &a).is_err());
+
+        // the block size is set from the problem by the iterative solvers
+        let (mut p, solns) = dense_linear_problem(a);
+        p.preconditioner_block_size = Some(block_size);
+        let mut s = Gmres::new(
+            BlockJacobiPreconditioner::default(),
+            PreconditionerSide::Right,
+        );
+        s.set_problem(&p);
+        assert_eq!(s.preconditioner().block_size(), block_size);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-8);
+        assert!(s.number_of_iterations() <= 2);
+    }
+
+    #[test]
+    fn test_preconditioner_setup_failure() {
+        // the Jacobi preconditioner cannot be set up for a matrix with a zero on the diagonal, this is returned as an error
+        // from the solve (so e.g. an ODE solver can retry with a smaller step) rather than a panic
+        let a = DMatrix::from_row_slice(2, 2, &[0.0, 1.0, 1.0, 1.0]);
+        let (p, solns) = dense_linear_problem(a);
+        let mut s = Gmres::new(JacobiPreconditioner::default(), PreconditionerSide::Right);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(2), 0.0);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::LinearSolverError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_spai() {
+        // the approximate inverse of a diagonal matrix is exact
+        let a = DMatrix::from_diagonal(&DVector::from_vec(vec![2.0, -4.0, 0.5]));
+        let mut spai = SpaiPreconditioner::default();
+        Preconditioner::<DMatrix<f64>>::setup(&mut spai, &a).unwrap();
+        assert_eq!(spai.nnz(), 3);
+        let mut b = DVector::from_vec(vec![2.0, -8.0, 1.5]);
+        Preconditioner::<DMatrix<f64>>::apply_in_place(&spai, &mut b);
+        b.assert_eq_st(&DVector::from_vec(vec![1.0, 2.0, 3.0]), 1e-12);
+
+        // for a tridiagonal matrix the approximate inverse is tridiagonal, and GMRES(30) converges where it otherwise stagnates
+        let n = 100;
+        let a = convection_diffusion(n);
+        let mut spai = SpaiPreconditioner::default();
+        Preconditioner::<DMatrix<f64>>::setup(&mut spai, &a).unwrap();
+        assert_eq!(spai.nnz(), 3 * n - 2);
+        let (p, solns) = dense_linear_problem(a);
+        let niter = |s: &mut dyn LinearSolver<_>| {
+            s.set_problem(&p);
+            s.set_linearisation(&DVector::zeros(n), 0.0);
+            s.solve(&solns[0].b).is_ok()
+        };
+        assert!(!niter(&mut Gmres::new(
+            IdentityPreconditioner,
+            PreconditionerSide::Right
+        )));
+        assert!(niter(&mut Gmres::new(
+            SpaiPreconditioner::default(),
+            PreconditionerSide::Right
+        )));
+    }
+
+    #[test]
+    fn test_block_jacobi_bdf() {
+        // 2D reaction-diffusion of two species on an n x n grid, with a stiff reversible reaction u <-> v at each grid point
+        let n = 6;
+        let (d, k1, k2) = (0.1, 1000.0, 10.0);
+        let rhs = move |x: &DVector<f64>, _p: &DVector<f64>, _t, y: &mut DVector<f64>| {
+            let h2 = 1.0 / ((n + 1) * (n + 1)) as f64;
+            for i in 0..n {
+                for j in 0..n {
+                    let k = 2 * (i * n + j);
+                    let (u, v) = (x[k], x[k + 1]);
+                    y[k] = -k1 * u + k2 * v;
+                    y[k + 1] = k1 * u - k2 * v;
+                    for s in 0..2 {
+                        let neighbour = |di: isize, dj: isize| {
+                            let (ni, nj) = (i as isize + di, j as isize + dj);
+                            if ni < 0 || nj < 0 || ni >= n as isize || nj >= n as isize {
+                                0.0
+                            } else {
+                                x[2 * (ni as usize * n + nj as usize) + s]
+                            }
+                        };
+                        let lap =
+                            neighbour(-1, 0) + neighbour(1, 0) + neighbour(0, -1) + neighbour(0, 1)
+                                - 4.0 * x[k + s];
+                        y[k + s] += d * lap / h2;
+                    }
+                }
+            }
+        };
+        let rhs_jac = move |_x: &DVector<f64>,
+                            p: &DVector<f64>,
+                            t,
+                            v: &DVector<f64>,
+                            y: &mut DVector<f64>| { rhs(v, p, t, y) };
+        let init = move |_p: &DVector<f64>, _t| {
+            DVector::from_fn(
+                2 * n * n,
+                |k, _| if k % 2 == 0 { (k as f64).sin() } else { 0.0 },
+            )
+        };
+        let problem = OdeBuilder::new()
+            .rtol(1e-6)
+            .atol([1e-8])
+            .preconditioner_block_size(2)
+            .build_ode::<DMatrix<f64>, _, _, _>(rhs, rhs_jac, init)
+            .unwrap();
+        let t = 0.1;
+        let y_lu = Bdf::default().solve(&problem, t).unwrap().y.pop().unwrap();
+
+        let nonlinear_solver = NewtonNonlinearSolver::new(Gmres::new(
+            BlockJacobiPreconditioner::default(),
+            PreconditionerSide::Right,
+        ));
+        let mut solver = Bdf::<DMatrix<f64>, _, _>::new(nonlinear_solver);
+        let y = solver.solve(&problem, t).unwrap().y.pop().unwrap();
+        y.assert_eq_st(&y_lu, 1e-6);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{rc::Rc, time::Instant};
+
+use nalgebra::{ColPivQR, DMatrix, DVector, Dyn};
+
+use crate::{
+    linear_solver::{LinearSolverReport, LinearSolverStatistics, LinearSolverStatisticsRecorder},
+    matrix::sparsity::MatrixSparsityRef,
+    op::{linearise::LinearisedOp, NonLinearOp},
+    LinearOp, LinearSolver, Matrix, Op, Scalar, SolverProblem,
+};
+
+use crate::errors::PSError;
+
+/// A [LinearSolver] that uses the column-pivoted Householder QR decomposition in the [`nalgebra` library](https://nalgebra.org/).
+/// This is more expensive than [crate::NalgebraLU], but is more robust when the iteration matrix is nearly singular (e.g. near a
+/// bifurcation): the diagonal entries of `R` that are below [Self::rcond_tol] times the largest are treated as zero, and
+/// the solve returns the corresponding basic least-squares solution instead of failing.
+///
+/// After each factorisation, an estimate of the reciprocal condition number of the matrix (the ratio of the smallest
+/// to the largest diagonal entry of `R`) is given by [Self::rcond], and its numerical rank by [Self::rank].
+pub struct QR<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    /// Relative tolerance on the diagonal of `R`, below which the matrix is treated as rank-deficient (default 1e-12)
+    pub rcond_tol: T,
+    matrix: Option<DMatrix<T>>,
+    qr: Option<ColPivQR<T, Dyn, Dyn>>,
+    r: Option<DMatrix<T>>,
+    rank: usize,
+    rcond: T,
+    statistics: LinearSolverStatisticsRecorder,
+    problem: Option<SolverProblem<LinearisedOp<C>>>,
+}
+
+impl<T, C> Default for QR<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn default() -> Self {
+        Self {
+            rcond_tol: T::cast(1e-12),
+            matrix: None,
+            qr: None,
+            r: None,
+            rank: 0,
+            rcond: T::zero(),
+            statistics: LinearSolverStatisticsRecorder::default(),
+            problem: None,
+        }
+    }
+}
+
+impl<T, C> QR<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    /// Estimate of the reciprocal condition number of the last factorised matrix (`None` if not factorised)
+    pub fn rcond(&self) -> Option<T> {
+        self.qr.as_ref().map(|_| self.rcond.clone())
+    }
+
+    /// Numerical rank of the last factorised matrix, using [Self::rcond_tol] (`None` if not factorised)
+    pub fn rank(&self) -> Option<usize> {
+        self.qr.as_ref().map(|_| self.rank)
+    }
+}
+
+impl<T, C> LinearSolver<C> for QR<T, C>
+where
+    T: Scalar,
+    C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>,
+{
+    fn solve_in_place(&self, state: &mut C::V) -> Result<(), PSError> {
+        self.statistics.solve(1, || {
+            let (qr, r) = match (self.qr.as_ref(), self.r.as_ref()) {
+                (Some(qr), Some(r)) => (qr, r),
+                _ => return Err(PSError::LuNotInitialized),
+            };
+            qr.q_tr_mul(state);
+            // back substitution with R, setting the components for negligible diagonal entries to zero
+            let tol = self.rcond_tol.clone() * r.diagonal().amax();
+            for i in (0..r.nrows()).rev() {
+                let rii = r[(i, i)].clone();
+                if rii.clone().abs() <= tol {
+                    state[i] = T::zero();
+                    continue;
+                }
+                let mut sum = state[i].clone();
+                for j in i + 1..r.ncols() {
+                    sum -= r[(i, j)].clone() * state[j].clone();
+                }
+                state[i] = sum / rii;
+            }
+            qr.p().inv_permute_rows(state);
+            if state.iter().any(|x| x.is_nan()) {
+                return Err(PSError::LuFailed);
+            }
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        self.statistics.get()
+    }
+
+    fn report(&self) -> LinearSolverReport<T> {
+        LinearSolverReport {
+            rcond: self.rcond(),
+            refinement_iters: 0,
+        }
+    }
+
+    fn set_linearisation(&mut self, x: &<C as Op>::V, t: <C as Op>::T) {
+        let start = Instant::now();
+        Rc::<LinearisedOp<C>>::get_mut(&mut self.problem.as_mut().expect("Problem not set").f)
+            .unwrap()
+            .set_x(x);
+        let matrix = self.matrix.as_mut().expect("Matrix not set");
+        self.problem.as_ref().unwrap().f.matrix_inplace(t, matrix);
+        let qr = matrix.clone().col_piv_qr();
+        let r = qr.r();
+        let diag = r.diagonal().abs();
+        let (min, max) = (diag.min(), diag.max());
+        self.rcond = if max.is_zero() {
+            T::zero()
+        } else {
+            min / max.clone()
+        };
+        self.rank = diag
+            .iter()
+            .filter(|&d| *d > self.rcond_tol.clone() * max.clone())
+            .count();
+
+        self.qr = Some(qr);
+        self.r = Some(r);
+        self.statistics.factorisation(start);
+    }
+
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.statistics.reset();
+        let linearised_problem = problem.linearise();
+        let ncols = linearised_problem.f.nstates();
+        let nrows = linearised_problem.f.nout();
+        let matrix = C::M::new_from_sparsity(
+            nrows,
+            ncols,
+            linearised_problem.f.sparsity().map(|s| s.to_owned()),
+        );
+        self.problem = Some(linearised_problem);
+        self.matrix = Some(matrix);
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.qr = None;
+        self.r = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::{
+            qr::QR,
+            tests::{convection_diffusion, dense_linear_problem},
+        },
+        LinearSolver, NalgebraLU, Vector,
+    };
+
+    #[test]
+    fn test_qr() {
+        let n = 10;
+        let (p, solns) = dense_linear_problem(convection_diffusion(n));
+        let mut s = QR::default();
+        s.set_problem(&p);
+        assert!(matches!(
+            s.solve(&solns[0].b),
+            Err(PSError::LuNotInitialized)
+        ));
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b).unwrap();
+        x.assert_eq_st(&solns[0].x, 1e-10);
+        assert_eq!(s.rank(), Some(n));
+        assert!(s.rcond().unwrap() > 1e-3);
+    }
+
+    #[test]
+    fn test_qr_rank_deficient() {
+        // the last two rows are identical, so the matrix is singular but b is in its range
+        let a = DMatrix::from_row_slice(3, 3, &[2.0, 1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0]);
+        let b = DVector::from_vec(vec![3.0, 3.0, 3.0]);
+        let (p, _solns) = dense_linear_problem(a.clone());
+
+        let mut lu = NalgebraLU::default();
+        lu.set_problem(&p);
+        lu.set_linearisation(&DVector::zeros(3), 0.0);
+        assert!(lu.solve(&b).is_err());
+
+        let mut s = QR::default();
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(3), 0.0);
+        assert_eq!(s.rank(), Some(2));
+        assert!(s.rcond().unwrap() < 1e-12);
+        let x = s.solve(&b).unwrap();
+        (&a * &x).assert_eq_st(&b, 1e-10);
+    }
+}
@@ -0,0 +1,263 @@
+use std::cell::{Cell, RefCell};
+
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{
+        krylov::KrylovOperator,
+        preconditioner::{IdentityPreconditioner, Preconditioner},
+        LinearSolver, LinearSolverStatistics,
+    },
+    op::NonLinearOp,
+    Scalar, SolverProblem, Vector,
+};
+
+struct TfqmrWorkspace<V: Vector> {
+    r0: V,
+    w: V,
+    y1: V,
+    y2: V,
+    u1: V,
+    u2: V,
+    v: V,
+    d: V,
+    tmp: V,
+    x: V,
+}
+
+/// A matrix-free [LinearSolver] that uses the transpose-free quasi-minimal residual method (TFQMR, Freund 1993), where the operator
+/// is only applied using jacobian-vector products. Like [crate::BiCgStab], TFQMR uses short recurrences so the memory used is
+/// fixed, but its residual converges more smoothly.
+///
+/// An optional [Preconditioner] is applied on the right, i.e. `A P^{-1} u = b` with `x = P^{-1} u`. The iteration stops when
+/// the quasi-residual bound on the 2-norm of the residual is below [Self::tol] times the norm of `b`, and returns
+/// [PSError::LinearPSError] if this is not reached within [Self::max_iter] iterations or the iteration breaks down.
+pub struct Tfqmr<C, P = IdentityPreconditioner>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    /// Maximum number of iterations (default 500), each iteration applies the operator twice
+    pub max_iter: usize,
+    /// Relative tolerance on the residual (default 1e-10)
+    pub tol: C::T,
+    /// Approximate the jacobian-vector products using finite differences of the operator, so that only calls to the
+    /// operator are required (default false). Takes effect at the next call to [LinearSolver::set_problem]. The products are
+    /// then only accurate to about the square root of machine precision, so [Self::tol] should be loosened to match.
+    pub finite_difference: bool,
+    op: KrylovOperator<C, P>,
+    workspace: RefCell<Option<TfqmrWorkspace<C::V>>>,
+    number_of_iterations: Cell<usize>,
+}
+
+impl<C> Default for Tfqmr<C, IdentityPreconditioner>
+where
+    C: NonLinearOp,
+{
+    fn default() -> Self {
+        Self::new(IdentityPreconditioner)
+    }
+}
+
+impl<C, P> Tfqmr<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    pub fn new(preconditioner: P) -> Self {
+        Self {
+            max_iter: 500,
+            tol: C::T::cast(1e-10),
+            finite_difference: false,
+            op: KrylovOperator::new(preconditioner),
+            workspace: RefCell::new(None),
+            number_of_iterations: Cell::new(0),
+        }
+    }
+
+    pub fn preconditioner(&self) -> &P {
+        self.op.preconditioner()
+    }
+
+    /// Total number of TFQMR iterations over all the solves since the problem was set
+    pub fn number_of_iterations(&self) -> usize {
+        self.number_of_iterations.get()
+    }
+
+    // y = A P^{-1} x
+    fn apply_operator(&self, x: &C::V, y: &mut C::V, tmp: &mut C::V) {
+        tmp.copy_from(x);
+        self.op.precondition(tmp);
+        self.op.call_inplace(tmp, y);
+    }
+}
+
+impl<C, P> LinearSolver<C> for Tfqmr<C, P>
+where
+    C: NonLinearOp,
+    P: Preconditioner<C::M>,
+{
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        let n = self.op.set_problem(problem, self.finite_difference);
+        self.workspace.replace(Some(TfqmrWorkspace {
+            r0: C::V::zeros(n),
+            w: C::V::zeros(n),
+            y1: C::V::zeros(n),
+            y2: C::V::zeros(n),
+            u1: C::V::zeros(n),
+            u2: C::V::zeros(n),
+            v: C::V::zeros(n),
+            d: C::V::zeros(n),
+            tmp: C::V::zeros(n),
+            x: C::V::zeros(n),
+        }));
+        self.number_of_iterations.set(0);
+    }
+
+    fn clear_problem(&mut self) {
+        self.op.clear_problem();
+        self.workspace.replace(None);
+    }
+
+    fn set_linearisation(&mut self, x: &C::V, t: C::T) {
+        self.op.set_linearisation(x, t);
+    }
+
+    fn solve_in_place(&self, b: &mut C::V) -> Result<(), PSError> {
+        self.op.statistics().solve(1, || {
+            self.op.check_setup()?;
+            let mut workspace = self.workspace.borrow_mut();
+            let TfqmrWorkspace {
+                r0,
+                w,
+                y1,
+                y2,
+                u1,
+                u2,
+                v,
+                d,
+                tmp,
+                x,
+            } = workspace.as_mut().unwrap();
+            let one = C::T::one();
+            let zero = C::T::zero();
+
+            // initial guess x = 0, x is the solution of the right preconditioned system until the end
+            x.fill(zero.clone());
+            d.fill(zero.clone());
+            r0.copy_from(b);
+            w.copy_from(b);
+            y1.copy_from(b);
+            self.apply_operator(y1, v, tmp);
+            u1.copy_from(v);
+            let mut tau = b.norm();
+            let tol = self.tol.clone() * tau.clone();
+            let (mut theta, mut eta) = (zero.clone(), zero.clone());
+            let mut rho = r0.dot(r0);
+            let mut niter = 0;
+            let mut converged = tau <= tol;
+            while !converged && niter < self.max_iter {
+                niter += 1;
+                let sigma = r0.dot(v);
+                if sigma == zero {
+                    break;
+                }
+                let alpha = rho.clone() / sigma;
+                for j in 0..2 {
+                    if j == 1 {
+                        // y2 = y1 - alpha v
+                        y2.copy_from(y1);
+                        y2.axpy(-alpha.clone(), v, one.clone());
+                        self.apply_operator(y2, u2, tmp);
+                    }
+                    let (y, u) = if j == 0 { (&*y1, &*u1) } else { (&*y2, &*u2) };
+                    w.axpy(-alpha.clone(), u, one.clone());
+                    d.axpy(one.clone(), y, theta.clone() * theta * eta / alpha.clone());
+                    theta = w.norm() / tau.clone();
+                    let c = one.clone() / (one.clone() + theta.clone() * theta.clone()).sqrt();
+                    tau *= theta.clone() * c.clone();
+                    eta = c.clone() * c * alpha.clone();
+                    x.axpy(eta.clone(), d, one.clone());
+                    // the residual norm is bounded by tau sqrt(m + 1), with m the number of half-steps
+                    let m = C::T::cast((2 * niter + j + 1) as f64);
+                    if tau.clone() * m.sqrt() <= tol {
+                        converged = true;
+                        break;
+                    }
+                }
+                if converged {
+                    break;
+                }
+                let rho_new = r0.dot(w);
+                if rho == zero {
+                    break;
+                }
+                let beta = rho_new.clone() / rho;
+                rho = rho_new;
+
+                // y1 = w + beta y2, v = A y1 + beta (A y2 + beta v)
+                y1.copy_from(w);
+                y1.axpy(beta.clone(), y2, one.clone());
+                self.apply_operator(y1, u1, tmp);
+                v.axpy(one.clone(), u2, beta.clone());
+                v.axpy(one.clone(), u1, beta);
+            }
+            self.number_of_iterations
+                .set(self.number_of_iterations.get() + niter);
+            if !converged {
+                return Err(PSError::LinearPSError);
+            }
+            self.op.precondition(x);
+            b.copy_from(x);
+            Ok(())
+        })
+    }
+
+    fn statistics(&self) -> LinearSolverStatistics {
+        LinearSolverStatistics {
+            number_of_iterations: self.number_of_iterations(),
+            ..self.op.statistics().get()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{
+        errors::PSError,
+        linear_solver::tests::{badly_scaled, convection_diffusion, dense_linear_problem},
+        IdentityPreconditioner, Ilu0Preconditioner, JacobiPreconditioner, LinearSolver,
+        Preconditioner, Tfqmr, Vector,
+    };
+
+    fn solve<P: Preconditioner<DMatrix<f64>>>(
+        a: DMatrix<f64>,
+        preconditioner: P,
+    ) -> Result<usize, PSError> {
+        let n = a.nrows();
+        let (p, solns) = dense_linear_problem(a);
+        let mut s = Tfqmr::new(preconditioner);
+        s.set_problem(&p);
+        s.set_linearisation(&DVector::zeros(n), 0.0);
+        let x = s.solve(&solns[0].b)?;
+        x.assert_eq_st(&solns[0].x, 1e-6);
+        Ok(s.number_of_iterations())
+    }
+
+    #[test]
+    fn test_tfqmr() {
+        let n = 100;
+        let niter = solve(convection_diffusion(n), IdentityPreconditioner).unwrap_or(usize::MAX);
+        let niter_ilu = solve(convection_diffusion(n), Ilu0Preconditioner::default()).unwrap();
+        assert!(niter_ilu <= 2);
+        assert!(niter_ilu < niter);
+
+        let niter = solve(badly_scaled(n), IdentityPreconditioner).unwrap_or(usize::MAX);
+        let niter_jacobi = solve(badly_scaled(n), JacobiPreconditioner::default()).unwrap();
+        assert!(niter_jacobi < niter);
+    }
+}
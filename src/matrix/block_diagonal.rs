@@ -0,0 +1,163 @@
+use num_traits::Zero;
+
+use crate::{errors::PSError, scalar::IndexType};
+
+use super::DenseMatrix;
+
+/// The (row, column) indices of all the entries within the blocks of a block-diagonal matrix with the given block sizes
+pub fn block_diagonal_indices(block_sizes: &[IndexType]) -> Vec<(IndexType, IndexType)> {
+    let mut indices = Vec::new();
+    let mut offset = 0;
+    for &n in block_sizes {
+        for j in 0..n {
+            for i in 0..n {
+                indices.push((offset + i, offset + j));
+            }
+        }
+        offset += n;
+    }
+    indices
+}
+
+/// A square block-diagonal matrix, stored as a list of dense square blocks along the diagonal. All the entries outside
+/// the blocks are zero, so each block can be factorised (and solved) independently (see [crate::BlockDiagonalLU]).
+#[derive(Clone, Debug)]
+pub struct BlockDiagonalMatrix<M: DenseMatrix> {
+    blocks: Vec<M>,
+    // offsets[i] is the index of the first row (and column) of block i, offsets[nblocks] is the number of rows
+    offsets: Vec<IndexType>,
+}
+
+impl<M: DenseMatrix> BlockDiagonalMatrix<M> {
+    /// Create a new block-diagonal matrix filled with zeros, with blocks of the given sizes along the diagonal
+    pub fn zeros(block_sizes: &[IndexType]) -> Self {
+        let blocks = block_sizes.iter().map(|&n| M::zeros(n, n)).collect();
+        let mut offsets = Vec::with_capacity(block_sizes.len() + 1);
+        offsets.push(0);
+        for &n in block_sizes {
+            offsets.push(offsets.last().unwrap() + n);
+        }
+        Self { blocks, offsets }
+    }
+
+    /// Copy the diagonal blocks of the dense matrix `m`, the entries outside the blocks are ignored.
+    /// Returns an error if the sizes of the blocks do not add up to the number of rows and columns of `m`.
+    pub fn try_from_dense(m: &M, block_sizes: &[IndexType]) -> Result<Self, PSError> {
+        let n = block_sizes.iter().sum::<IndexType>();
+        if m.nrows() != n || m.ncols() != n {
+            return Err(PSError::DimensionMismatch {
+                name: "sum of the block sizes".to_string(),
+                expected: m.nrows(),
+                found: n,
+            });
+        }
+        let mut ret = Self::zeros(block_sizes);
+        ret.copy_from_dense(m);
+        Ok(ret)
+    }
+
+    /// Copy the diagonal blocks of the dense matrix `m` (which must have the same number of rows and columns as this matrix),
+    /// the entries outside the blocks are ignored.
+    pub fn copy_from_dense(&mut self, m: &M) {
+        for (block, &offset) in self.blocks.iter_mut().zip(self.offsets.iter()) {
+            for j in 0..block.ncols() {
+                for i in 0..block.nrows() {
+                    block[(i, j)] = m[(offset + i, offset + j)].clone();
+                }
+            }
+        }
+    }
+
+    pub fn nrows(&self) -> IndexType {
+        *self.offsets.last().unwrap()
+    }
+
+    pub fn ncols(&self) -> IndexType {
+        self.nrows()
+    }
+
+    pub fn nblocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn block_sizes(&self) -> Vec<IndexType> {
+        self.blocks.iter().map(|b| b.nrows()).collect()
+    }
+
+    pub fn max_block_size(&self) -> IndexType {
+        self.blocks.iter().map(|b| b.nrows()).max().unwrap_or(0)
+    }
+
+    /// The index of the first row (and column) of block `i`
+    pub fn block_offset(&self, i: usize) -> IndexType {
+        self.offsets[i]
+    }
+
+    pub fn blocks(&self) -> &[M] {
+        self.blocks.as_slice()
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut [M] {
+        self.blocks.as_mut_slice()
+    }
+
+    /// Perform a matrix-vector multiplication `y = alpha * self * x + beta * y`.
+    pub fn gemv(&self, alpha: M::T, x: &M::V, beta: M::T, y: &mut M::V) {
+        for (block, &offset) in self.blocks.iter().zip(self.offsets.iter()) {
+            for i in 0..block.nrows() {
+                let mut sum = M::T::zero();
+                for j in 0..block.ncols() {
+                    sum += block[(i, j)].clone() * x[offset + j].clone();
+                }
+                y[offset + i] = alpha.clone() * sum + beta.clone() * y[offset + i].clone();
+            }
+        }
+    }
+
+    /// Convert to a dense matrix
+    pub fn to_dense(&self) -> M {
+        let n = self.nrows();
+        let mut m = M::zeros(n, n);
+        for (block, &offset) in self.blocks.iter().zip(self.offsets.iter()) {
+            for j in 0..block.ncols() {
+                for i in 0..block.nrows() {
+                    m[(offset + i, offset + j)] = block[(i, j)].clone();
+                }
+            }
+        }
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::{matrix::Matrix, Vector};
+
+    use super::{block_diagonal_indices, BlockDiagonalMatrix};
+
+    #[test]
+    fn test_block_diagonal_matrix() {
+        let block_sizes = [2, 3, 1];
+        let mut dense = DMatrix::<f64>::zeros(6, 6);
+        for (i, j) in block_diagonal_indices(&block_sizes) {
+            dense[(i, j)] = (1 + i + 6 * j) as f64;
+        }
+        let m = BlockDiagonalMatrix::try_from_dense(&dense, &block_sizes).unwrap();
+        assert_eq!(m.nblocks(), 3);
+        assert_eq!(m.block_sizes(), block_sizes.to_vec());
+        assert_eq!(m.max_block_size(), 3);
+        assert_eq!(m.block_offset(2), 5);
+        assert_eq!(m.to_dense(), dense);
+        assert!(BlockDiagonalMatrix::try_from_dense(&dense, &[2, 3]).is_err());
+
+        // y = 2 A x + y
+        let x = DVector::from_vec(vec![1.0, -1.0, 2.0, 0.5, -2.0, 3.0]);
+        let mut y = DVector::from_element(6, 1.0);
+        let mut expect = y.clone();
+        m.gemv(2.0, &x, 1.0, &mut y);
+        dense.gemv(2.0, &x, 1.0, &mut expect);
+        y.assert_eq_st(&expect, 1e-12);
+    }
+}
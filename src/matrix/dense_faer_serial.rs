@@ -3,20 +3,21 @@ use std::ops::{AddAssign, Mul, MulAssign};
 use super::default_solver::DefaultSolver;
 use super::{DenseMatrix, Matrix, MatrixCommon, MatrixView, MatrixViewMut};
 use crate::errors::PSError;
+use crate::linear_solver::kind::{LinearSolverKind, SelectedLinearSolver};
 use crate::op::NonLinearOp;
-use crate::scalar::{IndexType, Scalar, Scale};
+use crate::scalar::{FaerScalar, IndexType, Scale};
 use crate::FaerLU;
 use crate::{Dense, DenseRef, Vector};
 use faer::{linalg::matmul::matmul, Col, ColMut, ColRef, Mat, MatMut, MatRef, Parallelism};
 use faer::{unzipped, zipped};
 
-impl<T: Scalar> DefaultSolver for Mat<T> {
-    type LS<C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> = FaerLU<T, C>;
+impl<T: FaerScalar> DefaultSolver for Mat<T> {
+    type LS<C: NonLinearOp<M = Mat<T>, V = Col<T>, T = T>> = SelectedLinearSolver<C, FaerLU<T, C>>;
 }
 
 macro_rules! impl_matrix_common {
     ($mat_type:ty) => {
-        impl<'a, T: Scalar> MatrixCommon for $mat_type {
+        impl<'a, T: FaerScalar> MatrixCommon for $mat_type {
             type T = T;
             type V = Col<T>;
 
@@ -36,7 +37,7 @@ impl_matrix_common!(Mat<T>);
 
 macro_rules! impl_mul_scale {
     ($mat_type:ty) => {
-        impl<'a, T: Scalar> Mul<Scale<T>> for $mat_type {
+        impl<'a, T: FaerScalar> Mul<Scale<T>> for $mat_type {
             type Output = Mat<T>;
 
             fn mul(self, rhs: Scale<T>) -> Self::Output {
@@ -50,14 +51,14 @@ impl_mul_scale!(MatRef<'a, T>);
 impl_mul_scale!(Mat<T>);
 impl_mul_scale!(&Mat<T>);
 
-impl<'a, T: Scalar> MulAssign<Scale<T>> for MatMut<'a, T> {
+impl<'a, T: FaerScalar> MulAssign<Scale<T>> for MatMut<'a, T> {
     fn mul_assign(&mut self, rhs: Scale<T>) {
         let scale: faer::Scale<T> = rhs.into();
         *self *= scale;
     }
 }
 
-impl<'a, T: Scalar> MatrixView<'a> for MatRef<'a, T> {
+impl<'a, T: FaerScalar> MatrixView<'a> for MatRef<'a, T> {
     type Owned = Mat<T>;
 
     fn gemv_o(&self, alpha: Self::T, x: &Self::V, beta: Self::T, y: &mut Self::V) {
@@ -74,7 +75,7 @@ impl<'a, T: Scalar> MatrixView<'a> for MatRef<'a, T> {
     }
 }
 
-impl<'a, T: Scalar> MatrixViewMut<'a> for MatMut<'a, T> {
+impl<'a, T: FaerScalar> MatrixViewMut<'a> for MatMut<'a, T> {
     type Owned = Mat<T>;
     type View = MatRef<'a, T>;
 
@@ -100,7 +101,7 @@ impl<'a, T: Scalar> MatrixViewMut<'a> for MatMut<'a, T> {
     }
 }
 
-impl<T: Scalar> DenseMatrix for Mat<T> {
+impl<T: FaerScalar> DenseMatrix for Mat<T> {
     type View<'a> = MatRef<'a, T>;
     type ViewMut<'a> = MatMut<'a, T>;
 
@@ -130,7 +131,7 @@ impl<T: Scalar> DenseMatrix for Mat<T> {
     }
 }
 
-impl<T: Scalar> Matrix for Mat<T> {
+impl<T: FaerScalar> Matrix for Mat<T> {
     type Sparsity = Dense<Self>;
     type SparsityRef<'a> = DenseRef<'a, Self>;
 
@@ -138,6 +139,10 @@ impl<T: Scalar> Matrix for Mat<T> {
         None
     }
 
+    fn supports_linear_solver(kind: LinearSolverKind) -> bool {
+        kind == LinearSolverKind::DenseLU || kind.is_iterative()
+    }
+
     fn set_data_with_indices(
         &mut self,
         dst_indices: &<Self::V as Vector>::Index,
@@ -155,6 +160,10 @@ impl<T: Scalar> Matrix for Mat<T> {
         v.add_assign(&self.column(j));
     }
 
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        Self::from_fn(rows.len(), cols.len(), |k, l| self[(rows[k], cols[l])])
+    }
+
     fn triplet_iter(&self) -> impl Iterator<Item = (IndexType, IndexType, &Self::T)> {
         (0..self.nrows()).flat_map(move |i| (0..self.ncols()).map(move |j| (i, j, &self[(i, j)])))
     }
@@ -194,6 +203,17 @@ impl<T: Scalar> Matrix for Mat<T> {
         zipped!(self, x, y).for_each(|unzipped!(mut s, x, y)| s.write(x.read() + beta * y.read()));
     }
 
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        zipped!(self, x)
+            .for_each(|unzipped!(mut s, x)| s.write(alpha * x.read() + beta * s.read()));
+    }
+
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for &(i, j, v) in triplets {
+            self[(i, j)] += v;
+        }
+    }
+
     fn new_from_sparsity(
         nrows: IndexType,
         ncols: IndexType,
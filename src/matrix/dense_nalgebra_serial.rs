@@ -6,14 +6,16 @@ use crate::op::NonLinearOp;
 use crate::vector::Vector;
 use crate::{scalar::Scale, IndexType, Scalar};
 
-use crate::{DenseMatrix, Matrix, MatrixCommon, MatrixView, MatrixViewMut, NalgebraLU};
+use crate::{DenseMatrix, Matrix, MatrixCommon, MatrixView, MatrixViewMut};
 
 use super::default_solver::DefaultSolver;
 use super::sparsity::{Dense, DenseRef};
 use crate::errors::PSError;
+use crate::linear_solver::kind::{LinearSolverKind, NalgebraDirectSolver, SelectedLinearSolver};
 
 impl<T: Scalar> DefaultSolver for DMatrix<T> {
-    type LS<C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>> = NalgebraLU<T, C>;
+    type LS<C: NonLinearOp<M = DMatrix<T>, V = DVector<T>, T = T>> =
+        SelectedLinearSolver<C, NalgebraDirectSolver<T, C>>;
 }
 
 macro_rules! impl_matrix_common {
@@ -101,6 +103,10 @@ impl<T: Scalar> Matrix for DMatrix<T> {
         None
     }
 
+    fn supports_linear_solver(kind: LinearSolverKind) -> bool {
+        kind != LinearSolverKind::SparseLU
+    }
+
     fn set_data_with_indices(
         &mut self,
         dst_indices: &<Self::V as Vector>::Index,
@@ -110,7 +116,7 @@ impl<T: Scalar> Matrix for DMatrix<T> {
         for (dst_i, src_i) in dst_indices.iter().zip(src_indices.iter()) {
             let i = dst_i % self.nrows();
             let j = dst_i / self.nrows();
-            self[(i, j)] = data[*src_i];
+            self[(i, j)] = data[*src_i].clone();
         }
     }
 
@@ -118,6 +124,12 @@ impl<T: Scalar> Matrix for DMatrix<T> {
         v.add_assign(&self.column(j));
     }
 
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        Self::from_fn(rows.len(), cols.len(), |k, l| {
+            self[(rows[k], cols[l])].clone()
+        })
+    }
+
     fn triplet_iter(&self) -> impl Iterator<Item = (IndexType, IndexType, &Self::T)> {
         let n = self.ncols();
         let m = self.nrows();
@@ -159,6 +171,16 @@ impl<T: Scalar> Matrix for DMatrix<T> {
         self.mul_assign(beta);
         self.add_assign(x);
     }
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        for (s, x) in self.iter_mut().zip(x.iter()) {
+            *s = alpha.clone() * x.clone() + beta.clone() * s.clone();
+        }
+    }
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for (i, j, v) in triplets {
+            self[(*i, *j)] += v.clone();
+        }
+    }
     fn new_from_sparsity(
         nrows: IndexType,
         ncols: IndexType,
@@ -191,3 +213,46 @@ impl<T: Scalar> DenseMatrix for DMatrix<T> {
         self.columns(start, ncols)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use crate::Matrix;
+
+    #[test]
+    fn test_select_and_permute() {
+        let a = DMatrix::from_fn(4, 4, |i, j| (10 * i + j) as f64);
+        let rows = DVector::from_vec(vec![3, 1]);
+        let cols = DVector::from_vec(vec![0, 2, 3]);
+        let expect = DMatrix::from_row_slice(2, 3, &[30.0, 32.0, 33.0, 10.0, 12.0, 13.0]);
+        assert_eq!(a.select(&rows, &cols), expect);
+
+        let p = DVector::from_vec(vec![2, 0, 3, 1]);
+        let pa = a.permute(&p);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_eq!(pa[(i, j)], a[(p[i], p[j])]);
+            }
+        }
+
+        // split and combine at indices that are not at the end
+        let indices = DVector::from_vec(vec![2, 0]);
+        let (ul, ur, ll, lr) = a.split_at_indices(&indices);
+        assert_eq!(ul, DMatrix::from_row_slice(2, 2, &[11.0, 13.0, 31.0, 33.0]));
+        assert_eq!(ur, DMatrix::from_row_slice(2, 2, &[10.0, 12.0, 30.0, 32.0]));
+        assert_eq!(ll, DMatrix::from_row_slice(2, 2, &[1.0, 3.0, 21.0, 23.0]));
+        assert_eq!(lr, DMatrix::from_row_slice(2, 2, &[0.0, 2.0, 20.0, 22.0]));
+        assert_eq!(DMatrix::combine_at_indices(&ul, &ur, &ll, &lr, &indices), a);
+    }
+
+    #[test]
+    fn test_scale_add_and_triplets() {
+        let mut a = DMatrix::from_row_slice(2, 2, &[1.0, 2.0, 3.0, 4.0]);
+        let x = DMatrix::from_row_slice(2, 2, &[1.0, 0.0, 0.0, 1.0]);
+        a.scale_add(2.0, &x, -1.0);
+        assert_eq!(a, DMatrix::from_row_slice(2, 2, &[1.0, -2.0, -3.0, -2.0]));
+        a.add_assign_triplets(&[(0, 1, 1.0), (1, 0, 0.5), (0, 1, 1.0)]);
+        assert_eq!(a, DMatrix::from_row_slice(2, 2, &[1.0, 0.0, -2.5, -2.0]));
+    }
+}
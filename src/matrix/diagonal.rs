@@ -0,0 +1,106 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    matrix::sparsity::MatrixSparsity,
+    op::{LinearOp, Op, OpStatistics},
+    Matrix, Vector,
+};
+
+/// A constant diagonal matrix `diag(d)`, stored as the vector `d`, for example the mass matrix of a semi-explicit DAE, where
+/// `d` is one for the differential states and zero for the algebraic states (see [crate::OdeBuilder::build_ode_with_diagonal_mass]).
+///
+/// As a [LinearOp] the product with a vector only takes `O(n)` operations, and the matrix representation (of type `M`) is only
+/// assembled when the implicit solvers form their iteration matrix. If `M` is sparse the matrix has a diagonal sparsity pattern.
+/// The matrix does not depend on the parameters of the equations.
+pub struct DiagonalMatrix<M: Matrix> {
+    diagonal: M::V,
+    sparsity: Option<M::Sparsity>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M: Matrix> DiagonalMatrix<M> {
+    pub fn new(diagonal: M::V) -> Self {
+        let sparsity = if M::is_sparse() {
+            Some(M::Sparsity::new_diagonal(diagonal.len()))
+        } else {
+            None
+        };
+        Self {
+            diagonal,
+            sparsity,
+            statistics: RefCell::new(OpStatistics::default()),
+        }
+    }
+
+    pub fn diagonal(&self) -> &M::V {
+        &self.diagonal
+    }
+}
+
+impl<M: Matrix> Op for DiagonalMatrix<M> {
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.diagonal.len()
+    }
+    fn nout(&self) -> usize {
+        self.diagonal.len()
+    }
+    // the matrix is constant, so the parameters of the equations are ignored
+    fn set_params(&mut self, _p: Rc<M::V>) {}
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.sparsity.as_ref().map(|s| s.as_ref())
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M: Matrix> LinearOp for DiagonalMatrix<M> {
+    fn gemv_inplace(&self, x: &M::V, _t: M::T, beta: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        for i in 0..self.diagonal.len() {
+            y[i] = self.diagonal[i].clone() * x[i].clone() + beta.clone() * y[i].clone();
+        }
+    }
+    fn matrix_inplace(&self, _t: M::T, y: &mut M) {
+        self.statistics.borrow_mut().increment_matrix();
+        y.copy_from(&M::from_diagonal(&self.diagonal));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+
+    use super::DiagonalMatrix;
+    #[cfg(feature = "faer")]
+    use crate::SparseColMat;
+    use crate::{LinearOp, Op};
+
+    #[test]
+    fn test_diagonal_matrix() {
+        let d = DVector::from_vec(vec![1.0, 0.0, 2.0]);
+        let m = DiagonalMatrix::<DMatrix<f64>>::new(d.clone());
+        let x = DVector::from_vec(vec![1.0, 2.0, 3.0]);
+        let mut y = DVector::from_vec(vec![1.0, 1.0, 1.0]);
+        m.gemv_inplace(&x, 0.0, 2.0, &mut y);
+        assert_eq!(y, DVector::from_vec(vec![3.0, 2.0, 8.0]));
+        assert_eq!(m.matrix(0.0), DMatrix::from_diagonal(&d));
+        assert!(m.sparsity().is_none());
+        assert_eq!(m.statistics().number_of_calls, 1);
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn test_diagonal_matrix_sparse() {
+        // sparse matrices only store the diagonal
+        let m =
+            DiagonalMatrix::<SparseColMat<f64>>::new(faer::Col::from_fn(3, |i| [1.0, 0.0, 2.0][i]));
+        let mat = m.matrix(0.0);
+        assert_eq!(mat.faer().compute_nnz(), 3);
+        assert_eq!(mat.faer()[(2, 2)], 2.0);
+        assert_eq!(m.statistics().number_of_matrix_evals, 1);
+    }
+}
@@ -2,6 +2,7 @@ use std::fmt::Debug;
 use std::ops::{Add, AddAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
 use crate::errors::PSError;
+use crate::linear_solver::kind::LinearSolverKind;
 use crate::scalar::Scale;
 use crate::{IndexType, Scalar, Vector, VectorIndex};
 use num_traits::{One, Zero};
@@ -16,7 +17,10 @@ mod dense_faer_serial;
 #[cfg(feature = "faer")]
 pub mod sparse_faer;
 
+pub mod block_diagonal;
 pub mod default_solver;
+pub mod diagonal;
+mod sparse_csr;
 mod sparse_serial;
 pub mod sparsity;
 
@@ -31,7 +35,7 @@ pub trait MatrixCommon: Sized + Debug {
     fn ncols(&self) -> IndexType;
 }
 
-impl<'a, M> MatrixCommon for &'a M
+impl<M> MatrixCommon for &M
 where
     M: MatrixCommon,
 {
@@ -46,7 +50,7 @@ where
     }
 }
 
-impl<'a, M> MatrixCommon for &'a mut M
+impl<M> MatrixCommon for &mut M
 where
     M: MatrixCommon,
 {
@@ -111,6 +115,16 @@ pub trait MatrixView<'a>:
     fn gemv_o(&self, alpha: Self::T, x: &Self::V, beta: Self::T, y: &mut Self::V);
 }
 
+/// The indices in `0..n` that are not in `indices`, and `indices` itself, both sorted in increasing order
+fn complement_indices<V: Vector>(n: IndexType, indices: &V::Index) -> (V::Index, V::Index) {
+    let mut indices = indices.clone_as_vec();
+    indices.sort();
+    let diff = (0..n)
+        .filter(|i| indices.binary_search(i).is_err())
+        .collect::<Vec<_>>();
+    (V::Index::from_slice(&diff), V::Index::from_slice(&indices))
+}
+
 /// A base matrix trait (including sparse and dense matrices)
 pub trait Matrix: MatrixCommon + Mul<Scale<Self::T>, Output = Self> + Clone {
     type Sparsity: MatrixSparsity<Self>;
@@ -125,7 +139,39 @@ pub trait Matrix: MatrixCommon + Mul<Scale<Self::T>, Output = Self> + Clone {
         Self::zeros(1, 1).sparsity().is_some()
     }
 
-    /// Split the current matrix into four submatrices at the given indices
+    /// Returns true if the default linear solver of this matrix type (see [crate::DefaultSolver]) can build the linear
+    /// solver `kind` chosen with [crate::OdeBuilder::linear_solver]. The default implementation returns false, for matrix types
+    /// whose default linear solver is fixed.
+    fn supports_linear_solver(_kind: LinearSolverKind) -> bool {
+        false
+    }
+
+    /// Extract the submatrix with the given (distinct) rows and columns, in the given order, i.e. `ret[(k, l)] = self[(rows[k], cols[l])]`.
+    /// The default implementation filters [Self::triplet_iter], so it only visits the non-zeros of a sparse matrix.
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        let mut row_map = vec![None; self.nrows()];
+        for k in 0..rows.len() {
+            row_map[rows[k]] = Some(k);
+        }
+        let mut col_map = vec![None; self.ncols()];
+        for l in 0..cols.len() {
+            col_map[cols[l]] = Some(l);
+        }
+        let triplets = self
+            .triplet_iter()
+            .filter_map(|(i, j, v)| Some((row_map[i]?, col_map[j]?, v.clone())))
+            .collect();
+        Self::try_from_triplets(rows.len(), cols.len(), triplets).unwrap()
+    }
+
+    /// Permute the rows and columns of a square matrix, i.e. `ret[(i, j)] = self[(p[i], p[j])]`, or `P A P^T` where `P` is the
+    /// permutation matrix with a one at `(i, p[i])` in each row `i`.
+    fn permute(&self, p: &<Self::V as Vector>::Index) -> Self {
+        self.select(p, p)
+    }
+
+    /// Split the current (square) matrix into four submatrices at the given indices, the upper left block contains the rows
+    /// and columns not in `indices`, and the lower right block the rows and columns in `indices` (both in increasing order).
     fn split_at_indices(
         &self,
         indices: &<Self::V as crate::vector::Vector>::Index,
@@ -134,37 +180,16 @@ pub trait Matrix: MatrixCommon + Mul<Scale<Self::T>, Output = Self> + Clone {
         if n != self.ncols() {
             panic!("Matrix must be square");
         }
-        let ni = indices.len();
-        let nni = n - ni;
-        let mut indices = indices.clone_as_vec();
-        indices.sort();
-        let cat = (0..n)
-            .map(|i| indices.as_slice().binary_search(&i).is_ok())
-            .collect::<Vec<_>>();
-        let mut ur_triplets = Vec::new();
-        let mut ul_triplets = Vec::new();
-        let mut lr_triplets = Vec::new();
-        let mut ll_triplets = Vec::new();
-        for (i, j, &v) in self.triplet_iter() {
-            if !cat[i] && !cat[j] {
-                ul_triplets.push((i, j, v));
-            } else if !cat[i] && cat[j] {
-                ur_triplets.push((i, j - nni, v));
-            } else if cat[i] && !cat[j] {
-                ll_triplets.push((i - nni, j, v));
-            } else {
-                lr_triplets.push((i - nni, j - nni, v));
-            }
-        }
+        let (diff, indices) = complement_indices::<Self::V>(n, indices);
         (
-            Self::try_from_triplets(nni, nni, ul_triplets).unwrap(),
-            Self::try_from_triplets(nni, ni, ur_triplets).unwrap(),
-            Self::try_from_triplets(ni, nni, ll_triplets).unwrap(),
-            Self::try_from_triplets(ni, ni, lr_triplets).unwrap(),
+            self.select(&diff, &diff),
+            self.select(&diff, &indices),
+            self.select(&indices, &diff),
+            self.select(&indices, &indices),
         )
     }
 
-    /// Combine four matrices into a single matrix at the given indices
+    /// Combine four matrices into a single matrix at the given indices, this is the inverse of [Self::split_at_indices]
     fn combine_at_indices(
         ul: &Self,
         ur: &Self,
@@ -181,30 +206,16 @@ pub trait Matrix: MatrixCommon + Mul<Scale<Self::T>, Output = Self> + Clone {
         {
             panic!("Matrices must have the same shape");
         }
+        let (diff, indices) = complement_indices::<Self::V>(n, indices);
         let mut triplets = Vec::new();
-        let mut indices = indices.clone_as_vec();
-        indices.sort();
-        let cat = (0..n)
-            .map(|i| indices.as_slice().binary_search(&i).is_ok())
-            .collect::<Vec<_>>();
-        for (i, j, &v) in ul.triplet_iter() {
-            if !cat[i] && !cat[j] {
-                triplets.push((i, j, v));
-            }
-        }
-        for (i, j, &v) in ur.triplet_iter() {
-            if !cat[i] && cat[j + ul.ncols()] {
-                triplets.push((i, j + ul.ncols(), v));
-            }
-        }
-        for (i, j, &v) in ll.triplet_iter() {
-            if cat[i + ul.nrows()] && !cat[j] {
-                triplets.push((i + ul.nrows(), j, v));
-            }
-        }
-        for (i, j, &v) in lr.triplet_iter() {
-            if cat[i + ul.nrows()] && cat[j + ul.ncols()] {
-                triplets.push((i + ul.nrows(), j + ul.ncols(), v));
+        for (block, rows, cols) in [
+            (ul, &diff, &diff),
+            (ur, &diff, &indices),
+            (ll, &indices, &diff),
+            (lr, &indices, &indices),
+        ] {
+            for (i, j, v) in block.triplet_iter() {
+                triplets.push((rows[i], cols[j], v.clone()));
             }
         }
         Self::try_from_triplets(n, m, triplets).unwrap()
@@ -247,6 +258,15 @@ pub trait Matrix: MatrixCommon + Mul<Scale<Self::T>, Output = Self> + Clone {
     /// Panics if the sparsity of self, x, and y do not match (i.e. sparsity of self must be the union of the sparsity of x and y)
     fn scale_add_and_assign(&mut self, x: &Self, beta: Self::T, y: &Self);
 
+    /// Perform the assignment self = alpha * x + beta * self in place, without allocating a new matrix.
+    /// The sparsity of x should be contained in the sparsity of self, if not then [crate::SparseColMat] panics, and the nalgebra
+    /// sparse matrices reallocate self with the union of the two sparsity patterns
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T);
+
+    /// Add the values of the triplets (i, j, value) to the existing entries of self in place, duplicate entries are summed.
+    /// Panics if an entry is outside the sparsity pattern of self
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]);
+
     fn triplet_iter(&self) -> impl Iterator<Item = (IndexType, IndexType, &Self::T)>;
 
     /// Create a new matrix from a vector of triplets (i, j, value) where i and j are the row and column indices of the value
@@ -0,0 +1,422 @@
+use std::ops::Mul;
+
+use nalgebra::DVector;
+use nalgebra_sparse::{pattern::SparsityPattern, CooMatrix, CsrMatrix, SparseEntryMut};
+
+use crate::{
+    linear_solver::kind::LinearSolverKind, scalar::Scale, vector::Vector, IndexType, Scalar,
+};
+#[cfg(feature = "faer")]
+use crate::{
+    linear_solver::kind::SelectedLinearSolver, DefaultSolver, FaerCsrLU, FaerScalar, NonLinearOp,
+};
+
+use super::{
+    sparsity::{MatrixSparsity, MatrixSparsityRef},
+    Matrix, MatrixCommon, PSError,
+};
+
+/// With the `rayon` feature, matrices with at least this many non-zeros are multiplied with a vector in parallel
+#[cfg(feature = "rayon")]
+const PARALLEL_GEMV_MIN_NNZ: usize = 100_000;
+
+// add `v` to the entry (i, j) of `m`, panics if it is not in the sparsity pattern
+fn add_to_entry<T: Scalar>(m: &mut CsrMatrix<T>, i: IndexType, j: IndexType, v: T) {
+    match m.get_entry_mut(i, j) {
+        Some(SparseEntryMut::NonZero(value)) => *value += v,
+        _ => panic!("entry ({}, {}) is not in the sparsity pattern", i, j),
+    }
+}
+
+// y[i] = alpha * (A x)[i] + beta * y[i] for the rows `first_row..first_row + y.len()` of A
+fn gemv_rows<T: Scalar>(
+    a: &CsrMatrix<T>,
+    first_row: IndexType,
+    alpha: T,
+    x: &DVector<T>,
+    beta: T,
+    y: &mut [T],
+) {
+    for (i, yi) in y.iter_mut().enumerate() {
+        let row = a.row(first_row + i);
+        let mut sum = T::cast(0.0);
+        for (&j, v) in row.col_indices().iter().zip(row.values().iter()) {
+            sum += v.clone() * x[j].clone();
+        }
+        *yi = alpha.clone() * sum + beta.clone() * yi.clone();
+    }
+}
+
+// the (row, column) indices of a CSR sparsity pattern (rows are major, columns are minor)
+fn csr_indices(pattern: &SparsityPattern) -> Vec<(IndexType, IndexType)> {
+    let mut indices = Vec::with_capacity(pattern.nnz());
+    for i in 0..pattern.major_dim() {
+        for &j in pattern.lane(i) {
+            indices.push((i, j));
+        }
+    }
+    indices
+}
+
+#[cfg(feature = "faer")]
+impl<T: FaerScalar> DefaultSolver for CsrMatrix<T> {
+    type LS<C: NonLinearOp<M = CsrMatrix<T>, V = DVector<T>, T = T>> =
+        SelectedLinearSolver<C, FaerCsrLU<T, C>>;
+}
+
+impl<T: Scalar> MatrixCommon for CsrMatrix<T> {
+    type V = DVector<T>;
+    type T = T;
+
+    fn ncols(&self) -> IndexType {
+        self.ncols()
+    }
+    fn nrows(&self) -> IndexType {
+        self.nrows()
+    }
+}
+
+impl<T: Scalar> Mul<Scale<T>> for CsrMatrix<T> {
+    type Output = CsrMatrix<T>;
+    fn mul(self, rhs: Scale<T>) -> Self::Output {
+        self * rhs.value()
+    }
+}
+
+impl<T: Scalar> Mul<Scale<T>> for &CsrMatrix<T> {
+    type Output = CsrMatrix<T>;
+    fn mul(self, rhs: Scale<T>) -> Self::Output {
+        self * rhs.value()
+    }
+}
+
+impl<T: Scalar> MatrixSparsity<CsrMatrix<T>> for SparsityPattern {
+    fn union(self, other: &SparsityPattern) -> Result<SparsityPattern, PSError> {
+        let mut major_offsets = Vec::with_capacity(self.major_dim() + 1);
+        let mut minor_indices = Vec::with_capacity(self.nnz().max(other.nnz()));
+
+        // loop through rows, calculate the (sorted) union of columns
+        for i in 0..self.major_dim() {
+            major_offsets.push(minor_indices.len());
+            let mut lane = self
+                .lane(i)
+                .iter()
+                .chain(other.lane(i).iter())
+                .copied()
+                .collect::<Vec<_>>();
+            lane.sort_unstable();
+            lane.dedup();
+            minor_indices.append(&mut lane);
+        }
+        major_offsets.push(minor_indices.len());
+        SparsityPattern::try_from_offsets_and_indices(
+            self.major_dim(),
+            self.minor_dim(),
+            major_offsets,
+            minor_indices,
+        )
+        .map_err(|err| PSError::Unknown { e: err.to_string() })
+    }
+    fn as_ref(&self) -> &SparsityPattern {
+        self
+    }
+
+    fn nrows(&self) -> IndexType {
+        self.major_dim()
+    }
+
+    fn ncols(&self) -> IndexType {
+        self.minor_dim()
+    }
+
+    fn is_sparse() -> bool {
+        true
+    }
+
+    fn indices(&self) -> Vec<(IndexType, IndexType)> {
+        csr_indices(self)
+    }
+
+    fn try_from_indices(
+        nrows: IndexType,
+        ncols: IndexType,
+        indices: Vec<(IndexType, IndexType)>,
+    ) -> Result<Self, PSError> {
+        // use a CSR sparsity pattern (so rows are major, cols are minor)
+        let mut indices = indices;
+        indices.sort_unstable();
+        indices.dedup();
+
+        let mut curr_row = 0;
+        let mut major_offsets = Vec::with_capacity(nrows + 1);
+        let mut minor_indices = Vec::with_capacity(indices.len());
+        for (i, j) in indices {
+            while curr_row <= i {
+                major_offsets.push(minor_indices.len());
+                curr_row += 1;
+            }
+            minor_indices.push(j);
+        }
+        while curr_row <= nrows {
+            major_offsets.push(minor_indices.len());
+            curr_row += 1;
+        }
+
+        SparsityPattern::try_from_offsets_and_indices(nrows, ncols, major_offsets, minor_indices)
+            .map_err(|err| PSError::Unknown { e: err.to_string() })
+    }
+
+    fn new_diagonal(n: IndexType) -> Self {
+        let major_offsets = (0..=n).collect::<Vec<_>>();
+        let minor_indices = (0..n).collect::<Vec<_>>();
+        SparsityPattern::try_from_offsets_and_indices(n, n, major_offsets, minor_indices).unwrap()
+    }
+}
+
+impl<'a, T: Scalar> MatrixSparsityRef<'a, CsrMatrix<T>> for &'a SparsityPattern {
+    fn to_owned(&self) -> SparsityPattern {
+        SparsityPattern::clone(self)
+    }
+
+    fn get_index(&self, rows: &[IndexType], cols: &[IndexType]) -> DVector<IndexType> {
+        let mut index = DVector::<IndexType>::zeros(rows.len());
+        for ((&i, &j), ii) in rows.iter().zip(cols.iter()).zip(index.iter_mut()) {
+            let offset = self.major_offsets()[i];
+            let lane_j = self.lane(i).iter().position(|&x| x == j).unwrap();
+            *ii = offset + lane_j;
+        }
+        index
+    }
+
+    fn nrows(&self) -> IndexType {
+        self.major_dim()
+    }
+
+    fn ncols(&self) -> IndexType {
+        self.minor_dim()
+    }
+
+    fn is_sparse() -> bool {
+        true
+    }
+
+    fn indices(&self) -> Vec<(IndexType, IndexType)> {
+        csr_indices(self)
+    }
+}
+
+/// A compressed sparse row matrix from [nalgebra_sparse]. The rows are stored contiguously, so the product with a vector
+/// is calculated row by row, and is split over the available cores for matrices with at least `100_000` non-zeros.
+/// This makes it suitable for the matrix-free iterative solvers (e.g. [crate::Gmres]), which only need products with
+/// the jacobian. The direct solver ([crate::FaerCsrLU], requires the `faer` feature) factorises a compressed sparse column copy
+/// of the matrix, so the jacobian and mass operators stay in CSR format.
+impl<T: Scalar> Matrix for CsrMatrix<T> {
+    type Sparsity = SparsityPattern;
+    type SparsityRef<'a> = &'a SparsityPattern;
+
+    fn supports_linear_solver(kind: LinearSolverKind) -> bool {
+        (cfg!(feature = "faer") && kind == LinearSolverKind::SparseLU) || kind.is_iterative()
+    }
+
+    fn sparsity(&self) -> Option<Self::SparsityRef<'_>> {
+        Some(self.pattern())
+    }
+
+    fn set_data_with_indices(
+        &mut self,
+        dst_indices: &<Self::V as Vector>::Index,
+        src_indices: &<Self::V as Vector>::Index,
+        data: &Self::V,
+    ) {
+        let values = self.values_mut();
+        for (&dst_i, &src_i) in dst_indices.iter().zip(src_indices.iter()) {
+            values[dst_i] = data[src_i].clone();
+        }
+    }
+
+    fn triplet_iter(&self) -> impl Iterator<Item = (IndexType, IndexType, &Self::T)> {
+        self.triplet_iter()
+    }
+
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        // only visit the selected rows
+        let mut col_map = vec![None; self.ncols()];
+        for (l, &j) in cols.iter().enumerate() {
+            col_map[j] = Some(l);
+        }
+        let mut coo = CooMatrix::new(rows.len(), cols.len());
+        for (k, &i) in rows.iter().enumerate() {
+            let row = self.row(i);
+            for (&j, v) in row.col_indices().iter().zip(row.values().iter()) {
+                if let Some(l) = col_map[j] {
+                    coo.push(k, l, v.clone());
+                }
+            }
+        }
+        CsrMatrix::from(&coo)
+    }
+
+    fn add_column_to_vector(&self, j: IndexType, v: &mut Self::V) {
+        for (i, _j, val) in self.triplet_iter().filter(|&(_i, jj, _v)| jj == j) {
+            v[i] += val.clone();
+        }
+    }
+
+    fn try_from_triplets(
+        nrows: IndexType,
+        ncols: IndexType,
+        triplets: Vec<(IndexType, IndexType, T)>,
+    ) -> Result<Self, PSError> {
+        let mut coo = CooMatrix::new(nrows, ncols);
+        for (i, j, v) in triplets {
+            coo.push(i, j, v);
+        }
+        Ok(CsrMatrix::from(&coo))
+    }
+    fn zeros(nrows: IndexType, ncols: IndexType) -> Self {
+        Self::zeros(nrows, ncols)
+    }
+    fn copy_from(&mut self, other: &Self) {
+        if self.pattern() == other.pattern() {
+            self.values_mut().clone_from_slice(other.values());
+        } else {
+            self.clone_from(other);
+        }
+    }
+    fn gemv(&self, alpha: Self::T, x: &Self::V, beta: Self::T, y: &mut Self::V) {
+        // split the rows into blocks on the rayon thread pool
+        #[cfg(feature = "rayon")]
+        if self.nnz() >= PARALLEL_GEMV_MIN_NNZ && self.nrows() > 0 {
+            use rayon::prelude::*;
+            let chunk_size = self.nrows().div_ceil(rayon::current_num_threads());
+            y.as_mut_slice()
+                .par_chunks_mut(chunk_size)
+                .enumerate()
+                .for_each(|(c, chunk)| {
+                    gemv_rows(self, c * chunk_size, alpha.clone(), x, beta.clone(), chunk)
+                });
+            return;
+        }
+        gemv_rows(self, 0, alpha, x, beta, y.as_mut_slice());
+    }
+
+    fn from_diagonal(v: &DVector<T>) -> Self {
+        let n = v.len();
+        let mut coo = CooMatrix::<T>::new(n, n);
+        for (i, v) in v.iter().enumerate() {
+            coo.push(i, i, v.clone());
+        }
+        CsrMatrix::from(&coo)
+    }
+    fn diagonal(&self) -> Self::V {
+        let mut ret = DVector::zeros(self.nrows());
+        for (i, _j, v) in self.diagonal_as_csr().triplet_iter() {
+            ret[i] = v.clone();
+        }
+        ret
+    }
+    fn set_column(&mut self, j: IndexType, v: &Self::V) {
+        assert_eq!(v.len(), self.nrows());
+        for i in 0..self.nrows() {
+            if let Some(SparseEntryMut::NonZero(value)) = self.get_entry_mut(i, j) {
+                *value = v[i].clone();
+            }
+        }
+    }
+    fn scale_add_and_assign(&mut self, x: &Self, beta: Self::T, y: &Self) {
+        // update the values in place if the patterns match, otherwise the result has the union of the patterns
+        if self.pattern() == x.pattern() && self.pattern() == y.pattern() {
+            for ((s, x), y) in self.values_mut().iter_mut().zip(x.values()).zip(y.values()) {
+                *s = x.clone() + beta.clone() * y.clone();
+            }
+        } else {
+            *self = x + y * beta;
+        }
+    }
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        if self.pattern() == x.pattern() {
+            for (s, x) in self.values_mut().iter_mut().zip(x.values()) {
+                *s = alpha.clone() * x.clone() + beta.clone() * s.clone();
+            }
+        } else {
+            let x = x * alpha;
+            *self = &x + &*self * beta;
+        }
+    }
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for (i, j, v) in triplets {
+            add_to_entry(self, *i, *j, v.clone());
+        }
+    }
+    fn new_from_sparsity(
+        nrows: IndexType,
+        ncols: IndexType,
+        sparsity: Option<Self::Sparsity>,
+    ) -> Self {
+        let sparsity = sparsity.expect("Sparsity pattern required to create a sparse matrix");
+        assert_eq!(sparsity.major_dim(), nrows);
+        assert_eq!(sparsity.minor_dim(), ncols);
+        let values = vec![T::cast(0.0); sparsity.nnz()];
+        CsrMatrix::try_from_pattern_and_values(sparsity, values).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{DMatrix, DVector};
+    use nalgebra_sparse::CsrMatrix;
+
+    use crate::{matrix::sparsity::MatrixSparsity, Matrix, Vector};
+
+    #[test]
+    fn test_csr_matrix() {
+        // the tridiagonal second-difference matrix
+        let n = 400;
+        let triplets = (0..n)
+            .flat_map(|i| {
+                [(i, i, -2.0), (i, i + 1, 1.0), (i + 1, i, 1.0)]
+                    .into_iter()
+                    .filter(|&(i, j, _)| i < n && j < n)
+            })
+            .collect::<Vec<_>>();
+        let a = <CsrMatrix<f64> as Matrix>::try_from_triplets(n, n, triplets.clone()).unwrap();
+        let mut dense = DMatrix::<f64>::zeros(n, n);
+        for &(i, j, v) in &triplets {
+            dense[(i, j)] = v;
+        }
+        assert_eq!(Matrix::diagonal(&a), DVector::from_element(n, -2.0));
+        let sparsity = a.sparsity().unwrap();
+        assert_eq!(
+            MatrixSparsity::<CsrMatrix<f64>>::indices(sparsity).len(),
+            3 * n - 2
+        );
+
+        // y = 2 A x + y, in serial and (with the rayon feature, for a large enough matrix) in parallel
+        let x = DVector::from_fn(n, |i, _| (i as f64).sin());
+        let mut expect = DVector::from_element(n, 1.0);
+        dense.gemv(2.0, &x, 1.0, &mut expect);
+        let mut y = DVector::from_element(n, 1.0);
+        Matrix::gemv(&a, 2.0, &x, 1.0, &mut y);
+        y.assert_eq_st(&expect, 1e-12);
+
+        let big = n * 300;
+        let big_triplets = (0..big).map(|i| (i, i, 1.0 + i as f64)).collect();
+        let b = <CsrMatrix<f64> as Matrix>::try_from_triplets(big, big, big_triplets).unwrap();
+        let x = DVector::from_element(big, 1.0);
+        let mut y = DVector::zeros(big);
+        Matrix::gemv(&b, 1.0, &x, 0.0, &mut y);
+        y.assert_eq_st(&DVector::from_fn(big, |i, _| 1.0 + i as f64), 1e-12);
+
+        // M - c J in place
+        let mut m = <CsrMatrix<f64> as Matrix>::new_from_sparsity(n, n, Some(sparsity.clone()));
+        m.scale_add_and_assign(
+            &<CsrMatrix<f64> as Matrix>::from_diagonal(&DVector::from_element(n, 1.0)),
+            -0.5,
+            &a,
+        );
+        let expect = DMatrix::identity(n, n) - &dense * 0.5;
+        for (i, j, &v) in m.triplet_iter() {
+            assert_eq!(v, expect[(i, j)]);
+        }
+    }
+}
@@ -3,21 +3,22 @@ use std::ops::Mul;
 
 use super::sparsity::MatrixSparsityRef;
 use super::{Matrix, MatrixCommon, MatrixSparsity, PSError};
+use crate::linear_solver::kind::{LinearSolverKind, SelectedLinearSolver};
 use crate::vector::Vector;
-use crate::{DefaultSolver, FaerSparseLU, IndexType, NonLinearOp, Scalar, Scale};
+use crate::{DefaultSolver, FaerScalar, FaerSparseLU, IndexType, NonLinearOp, Scale};
 use faer::sparse::ops::{ternary_op_assign_into, union_symbolic};
 use faer::sparse::{SymbolicSparseColMat, SymbolicSparseColMatRef};
 use faer::Col;
 
-pub struct SparseColMat<T: Scalar>(faer::sparse::SparseColMat<IndexType, T>);
+pub struct SparseColMat<T: FaerScalar>(faer::sparse::SparseColMat<IndexType, T>);
 
-impl<T: Scalar> Debug for SparseColMat<T> {
+impl<T: FaerScalar> Debug for SparseColMat<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
 }
 
-impl<T: Scalar> Clone for SparseColMat<T> {
+impl<T: FaerScalar> Clone for SparseColMat<T> {
     fn clone(&self) -> Self {
         let sparsity = self.0.symbolic().to_owned().unwrap();
         let values = self.0.values().to_vec();
@@ -25,17 +26,30 @@ impl<T: Scalar> Clone for SparseColMat<T> {
     }
 }
 
-impl<T: Scalar> SparseColMat<T> {
+impl<T: FaerScalar> SparseColMat<T> {
     pub fn faer(&self) -> &faer::sparse::SparseColMat<IndexType, T> {
         &self.0
     }
+
+    // the index into the values of the entry (i, j), panics if it is not in the sparsity pattern
+    fn value_index(&self, i: IndexType, j: IndexType) -> usize {
+        self.0
+            .col_range(j)
+            .find(|&k| self.0.row_indices()[k] == i)
+            .unwrap_or_else(|| panic!("entry ({}, {}) is not in the sparsity pattern", i, j))
+    }
+
+    fn has_same_sparsity(&self, other: &Self) -> bool {
+        self.0.col_ptrs() == other.0.col_ptrs() && self.0.row_indices() == other.0.row_indices()
+    }
 }
 
-impl<T: Scalar> DefaultSolver for SparseColMat<T> {
-    type LS<C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> = FaerSparseLU<T, C>;
+impl<T: FaerScalar> DefaultSolver for SparseColMat<T> {
+    type LS<C: NonLinearOp<M = SparseColMat<T>, V = Col<T>, T = T>> =
+        SelectedLinearSolver<C, FaerSparseLU<T, C>>;
 }
 
-impl<T: Scalar> MatrixCommon for SparseColMat<T> {
+impl<T: FaerScalar> MatrixCommon for SparseColMat<T> {
     type T = T;
     type V = Col<T>;
 
@@ -47,7 +61,7 @@ impl<T: Scalar> MatrixCommon for SparseColMat<T> {
     }
 }
 
-impl<T: Scalar> MatrixSparsity<SparseColMat<T>> for SymbolicSparseColMat<IndexType> {
+impl<T: FaerScalar> MatrixSparsity<SparseColMat<T>> for SymbolicSparseColMat<IndexType> {
     fn union(
         self,
         other: SymbolicSparseColMatRef<IndexType>,
@@ -55,7 +69,7 @@ impl<T: Scalar> MatrixSparsity<SparseColMat<T>> for SymbolicSparseColMat<IndexTy
         union_symbolic(self.as_ref(), other).map_err(|err| PSError::Other { e: err.to_string() })
     }
 
-    fn as_ref(&self) -> SymbolicSparseColMatRef<IndexType> {
+    fn as_ref(&self) -> SymbolicSparseColMatRef<'_, IndexType> {
         self.as_ref()
     }
 
@@ -74,8 +88,8 @@ impl<T: Scalar> MatrixSparsity<SparseColMat<T>> for SymbolicSparseColMat<IndexTy
     fn indices(&self) -> Vec<(IndexType, IndexType)> {
         let mut indices = Vec::with_capacity(self.compute_nnz());
         for col_i in 0..self.ncols() {
-            for row_j in self.col_range(col_i) {
-                indices.push((row_j, col_i));
+            for k in self.col_range(col_i) {
+                indices.push((self.row_indices()[k], col_i));
             }
         }
         indices
@@ -100,7 +114,7 @@ impl<T: Scalar> MatrixSparsity<SparseColMat<T>> for SymbolicSparseColMat<IndexTy
     }
 }
 
-impl<'a, T: Scalar> MatrixSparsityRef<'a, SparseColMat<T>>
+impl<'a, T: FaerScalar> MatrixSparsityRef<'a, SparseColMat<T>>
     for SymbolicSparseColMatRef<'a, IndexType>
 {
     fn to_owned(&self) -> SymbolicSparseColMat<IndexType> {
@@ -121,8 +135,8 @@ impl<'a, T: Scalar> MatrixSparsityRef<'a, SparseColMat<T>>
     fn indices(&self) -> Vec<(IndexType, IndexType)> {
         let mut indices = Vec::with_capacity(self.compute_nnz());
         for col_i in 0..self.ncols() {
-            for row_j in self.col_range(col_i) {
-                indices.push((row_j, col_i));
+            for k in self.col_range(col_i) {
+                indices.push((self.row_indices()[k], col_i));
             }
         }
         indices
@@ -155,7 +169,7 @@ impl<'a, T: Scalar> MatrixSparsityRef<'a, SparseColMat<T>>
     }
 }
 
-impl<T: Scalar> Mul<Scale<T>> for SparseColMat<T> {
+impl<T: FaerScalar> Mul<Scale<T>> for SparseColMat<T> {
     type Output = SparseColMat<T>;
 
     fn mul(mut self, rhs: Scale<T>) -> Self::Output {
@@ -166,7 +180,7 @@ impl<T: Scalar> Mul<Scale<T>> for SparseColMat<T> {
     }
 }
 
-impl<T: Scalar> Mul<Scale<T>> for &SparseColMat<T> {
+impl<T: FaerScalar> Mul<Scale<T>> for &SparseColMat<T> {
     type Output = SparseColMat<T>;
 
     fn mul(self, rhs: Scale<T>) -> Self::Output {
@@ -174,7 +188,7 @@ impl<T: Scalar> Mul<Scale<T>> for &SparseColMat<T> {
     }
 }
 
-impl<T: Scalar> Matrix for SparseColMat<T> {
+impl<T: FaerScalar> Matrix for SparseColMat<T> {
     type Sparsity = SymbolicSparseColMat<IndexType>;
     type SparsityRef<'a> = SymbolicSparseColMatRef<'a, IndexType>;
 
@@ -182,6 +196,10 @@ impl<T: Scalar> Matrix for SparseColMat<T> {
         Some(self.0.symbolic())
     }
 
+    fn supports_linear_solver(kind: LinearSolverKind) -> bool {
+        kind == LinearSolverKind::SparseLU || kind.is_iterative()
+    }
+
     fn set_data_with_indices(
         &mut self,
         dst_indices: &<Self::V as Vector>::Index,
@@ -194,6 +212,23 @@ impl<T: Scalar> Matrix for SparseColMat<T> {
         }
     }
 
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        // only visit the selected columns
+        let mut row_map = vec![None; self.nrows()];
+        for (k, &i) in rows.iter().enumerate() {
+            row_map[i] = Some(k);
+        }
+        let mut triplets = Vec::new();
+        for (l, &j) in cols.iter().enumerate() {
+            for k in self.0.col_range(j) {
+                if let Some(i) = row_map[self.0.row_indices()[k]] {
+                    triplets.push((i, l, self.0.values()[k]));
+                }
+            }
+        }
+        Self::try_from_triplets(rows.len(), cols.len(), triplets).unwrap()
+    }
+
     fn add_column_to_vector(&self, j: IndexType, v: &mut Self::V) {
         for i in self.0.col_range(j) {
             let row = self.0.row_indices()[i];
@@ -228,10 +263,15 @@ impl<T: Scalar> Matrix for SparseColMat<T> {
         Self(faer::sparse::SparseColMat::try_new_from_triplets(nrows, ncols, &[]).unwrap())
     }
     fn copy_from(&mut self, other: &Self) {
-        self.0 = faer::sparse::SparseColMat::new(
-            other.0.symbolic().to_owned().unwrap(),
-            other.0.values().to_vec(),
-        )
+        // only reallocate if the sparsity pattern has changed
+        if self.has_same_sparsity(other) {
+            self.0.values_mut().copy_from_slice(other.0.values());
+        } else {
+            self.0 = faer::sparse::SparseColMat::new(
+                other.0.symbolic().to_owned().unwrap(),
+                other.0.values().to_vec(),
+            )
+        }
     }
     fn from_diagonal(v: &Col<T>) -> Self {
         let dim = v.nrows();
@@ -264,6 +304,29 @@ impl<T: Scalar> Matrix for SparseColMat<T> {
         });
     }
 
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        if self.has_same_sparsity(x) {
+            for (s, &x) in self.0.values_mut().iter_mut().zip(x.0.values()) {
+                *s = alpha * x + beta * *s;
+            }
+            return;
+        }
+        for s in self.0.values_mut() {
+            *s *= beta;
+        }
+        for (i, j, &v) in x.triplet_iter() {
+            let k = self.value_index(i, j);
+            self.0.values_mut()[k] += alpha * v;
+        }
+    }
+
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for &(i, j, v) in triplets {
+            let k = self.value_index(i, j);
+            self.0.values_mut()[k] += v;
+        }
+    }
+
     fn new_from_sparsity(
         ncols: IndexType,
         nrows: IndexType,
@@ -279,3 +342,54 @@ impl<T: Scalar> Matrix for SparseColMat<T> {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SparseColMat;
+    use crate::Matrix;
+
+    #[test]
+    fn test_scale_add_and_triplets() {
+        // a has a tridiagonal pattern, x only the diagonal
+        let mut a = SparseColMat::<f64>::try_from_triplets(
+            3,
+            3,
+            vec![
+                (0, 0, 1.0),
+                (1, 0, 2.0),
+                (0, 1, 3.0),
+                (1, 1, 4.0),
+                (2, 1, 5.0),
+                (1, 2, 6.0),
+                (2, 2, 7.0),
+            ],
+        )
+        .unwrap();
+        let x = SparseColMat::<f64>::try_from_triplets(
+            3,
+            3,
+            vec![(0, 0, 1.0), (1, 1, 1.0), (2, 2, 1.0)],
+        )
+        .unwrap();
+        a.scale_add(2.0, &x, -1.0);
+        a.add_assign_triplets(&[(2, 1, 1.0), (2, 1, 1.0)]);
+        let expect = [
+            (0, 0, 1.0),
+            (1, 0, -2.0),
+            (0, 1, -3.0),
+            (1, 1, -2.0),
+            (2, 1, -3.0),
+            (1, 2, -6.0),
+            (2, 2, -5.0),
+        ];
+        for ((i, j, &v), (ei, ej, ev)) in a.triplet_iter().zip(expect) {
+            assert_eq!((i, j, v), (ei, ej, ev));
+        }
+
+        // the copy is in place if the sparsity patterns match
+        let mut b = a.clone();
+        b.scale_add(0.0, &x, 0.0);
+        b.copy_from(&a);
+        assert_eq!(b.faer().values(), a.faer().values());
+    }
+}
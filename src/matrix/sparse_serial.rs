@@ -1,7 +1,7 @@
 use std::{collections::HashSet, ops::Mul};
 
 use nalgebra::DVector;
-use nalgebra_sparse::{pattern::SparsityPattern, CooMatrix, CscMatrix};
+use nalgebra_sparse::{pattern::SparsityPattern, CooMatrix, CscMatrix, SparseEntryMut};
 
 use crate::{scalar::Scale, vector::Vector, IndexType, Scalar};
 
@@ -10,6 +10,14 @@ use super::{
     Matrix, MatrixCommon, PSError,
 };
 
+// add `v` to the entry (i, j) of `m`, panics if it is not in the sparsity pattern
+fn add_to_entry<T: Scalar>(m: &mut CscMatrix<T>, i: IndexType, j: IndexType, v: T) {
+    match m.get_entry_mut(i, j) {
+        Some(SparseEntryMut::NonZero(value)) => *value += v,
+        _ => panic!("entry ({}, {}) is not in the sparsity pattern", i, j),
+    }
+}
+
 impl<T: Scalar> MatrixCommon for CscMatrix<T> {
     type V = DVector<T>;
     type T = T;
@@ -201,7 +209,7 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
     ) {
         let values = self.values_mut();
         for (&dst_i, &src_i) in dst_indices.iter().zip(src_indices.iter()) {
-            values[dst_i] = data[src_i];
+            values[dst_i] = data[src_i].clone();
         }
     }
 
@@ -209,10 +217,28 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
         self.triplet_iter()
     }
 
+    fn select(&self, rows: &<Self::V as Vector>::Index, cols: &<Self::V as Vector>::Index) -> Self {
+        // only visit the selected columns
+        let mut row_map = vec![None; self.nrows()];
+        for (k, &i) in rows.iter().enumerate() {
+            row_map[i] = Some(k);
+        }
+        let mut coo = CooMatrix::new(rows.len(), cols.len());
+        for (l, &j) in cols.iter().enumerate() {
+            let col = self.col(j);
+            for (&i, v) in col.row_indices().iter().zip(col.values().iter()) {
+                if let Some(k) = row_map[i] {
+                    coo.push(k, l, v.clone());
+                }
+            }
+        }
+        CscMatrix::from(&coo)
+    }
+
     fn add_column_to_vector(&self, j: IndexType, v: &mut Self::V) {
         let col = self.col(j);
-        for (&i, &val) in col.row_indices().iter().zip(col.values().iter()) {
-            v[i] += val;
+        for (&i, val) in col.row_indices().iter().zip(col.values().iter()) {
+            v[i] += val.clone();
         }
     }
 
@@ -235,7 +261,7 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
     }
     fn gemv(&self, alpha: Self::T, x: &Self::V, beta: Self::T, y: &mut Self::V) {
         let mut tmp = self * x;
-        tmp *= alpha;
+        tmp *= alpha.clone();
         y.axpy(alpha, &tmp, beta);
     }
 
@@ -243,15 +269,15 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
         let nrows = v.len();
         let ncols = v.len();
         let mut coo = CooMatrix::<T>::new(nrows, ncols);
-        for (i, &v) in v.into_iter().enumerate() {
-            coo.push(i, i, v);
+        for (i, v) in v.into_iter().enumerate() {
+            coo.push(i, i, v.clone());
         }
         CscMatrix::from(&coo)
     }
     fn diagonal(&self) -> Self::V {
         let mut ret = DVector::zeros(self.nrows());
-        for (i, _j, &v) in self.diagonal_as_csc().triplet_iter() {
-            ret[i] = v;
+        for (i, _j, v) in self.diagonal_as_csc().triplet_iter() {
+            ret[i] = v.clone();
         }
         ret
     }
@@ -264,11 +290,33 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
 
         // copy across the non-zero values
         for (&dst_i, dst_v) in dst_row_indices.iter().zip(dst_values.iter_mut()) {
-            *dst_v = v[dst_i];
+            *dst_v = v[dst_i].clone();
         }
     }
     fn scale_add_and_assign(&mut self, x: &Self, beta: Self::T, y: &Self) {
-        *self = x + y * beta;
+        // update the values in place if the patterns match, otherwise the result has the union of the patterns
+        if self.pattern() == x.pattern() && self.pattern() == y.pattern() {
+            for ((s, x), y) in self.values_mut().iter_mut().zip(x.values()).zip(y.values()) {
+                *s = x.clone() + beta.clone() * y.clone();
+            }
+        } else {
+            *self = x + y * beta;
+        }
+    }
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        if self.pattern() == x.pattern() {
+            for (s, x) in self.values_mut().iter_mut().zip(x.values()) {
+                *s = alpha.clone() * x.clone() + beta.clone() * s.clone();
+            }
+        } else {
+            let x = x * alpha;
+            *self = &x + &*self * beta;
+        }
+    }
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for (i, j, v) in triplets {
+            add_to_entry(self, *i, *j, v.clone());
+        }
     }
     fn new_from_sparsity(
         nrows: IndexType,
@@ -282,3 +330,33 @@ impl<T: Scalar> Matrix for CscMatrix<T> {
         CscMatrix::try_from_pattern_and_values(sparsity.clone(), values).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_sparse::CscMatrix;
+
+    use crate::Matrix;
+
+    #[test]
+    fn test_scale_add_different_sparsity() {
+        let a = <CscMatrix<f64> as Matrix>::try_from_triplets(2, 2, vec![(0, 0, 1.0)]).unwrap();
+        let b = <CscMatrix<f64> as Matrix>::try_from_triplets(2, 2, vec![(1, 1, 2.0), (0, 1, 3.0)])
+            .unwrap();
+
+        // the result has the union of the sparsity patterns
+        let mut c = a.clone();
+        c.scale_add_and_assign(&a, 2.0, &b);
+        assert_eq!(c.nnz(), 3);
+        assert_eq!(Matrix::diagonal(&c).as_slice(), &[1.0, 4.0]);
+        let mut d = a.clone();
+        d.scale_add(2.0, &b, 3.0);
+        assert_eq!(d.nnz(), 3);
+        assert_eq!(Matrix::diagonal(&d).as_slice(), &[3.0, 4.0]);
+
+        // with the same sparsity the values are updated in place
+        let mut e = b.clone();
+        e.scale_add_and_assign(&b, -1.0, &b);
+        assert_eq!(e.nnz(), 2);
+        assert!(e.values().iter().all(|&v| v == 0.0));
+    }
+}
@@ -295,6 +295,20 @@ impl Matrix for SundialsMatrix {
         sundials_check(unsafe { SUNMatScaleAdd(beta, self.sm, x.sm) }).unwrap();
     }
 
+    fn scale_add(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
+        for j in 0..self.ncols() {
+            for i in 0..self.nrows() {
+                self[(i, j)] = alpha * x[(i, j)] + beta * self[(i, j)];
+            }
+        }
+    }
+
+    fn add_assign_triplets(&mut self, triplets: &[(IndexType, IndexType, Self::T)]) {
+        for &(i, j, v) in triplets {
+            self[(i, j)] += v;
+        }
+    }
+
     fn zeros(nrows: IndexType, ncols: IndexType) -> Self {
         let m = SundialsMatrix::new_dense(nrows, ncols);
         unsafe { SUNMatZero(m.sm) };
@@ -0,0 +1,174 @@
+use std::collections::VecDeque;
+
+use nalgebra::{DMatrix, DVector};
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError, op::NonLinearOp, Convergence, ConvergenceStatus, NonLinearSolver,
+    NonLinearSolverStatistics, SolverProblem, Vector,
+};
+
+/// A nonlinear solver that uses Anderson acceleration of the functional (fixed-point) iteration `x_{n+1} = G(x_n) = x_n - F(x_n)`.
+///
+/// Instead of taking `G(x_n)` as the next iterate, as the [crate::FixedPointNonlinearSolver] does, the next iterate is the combination
+/// of the last `depth + 1` values of `G` that minimises the linearised residual, i.e. `x_{n+1} = G(x_n) - ΔG γ`, where `γ` minimises
+/// `|F(x_n) - ΔF γ|` and the columns of `ΔF` and `ΔG` are the differences between successive values of `F` and `G`. The small
+/// least-squares problem is solved using its normal equations.
+///
+/// Like the fixed-point iteration, no jacobian evaluations or linear solves are required, but the iteration usually converges
+/// in fewer iterations, which makes it suitable for the nonlinear stages of non-stiff implicit methods, e.g. the corrector of
+/// [crate::Adams].
+pub struct AndersonSolver<C: NonLinearOp> {
+    /// Number of previous iterates used to accelerate each iteration (default 3), with zero this is the fixed-point iteration
+    pub depth: usize,
+    convergence: Option<Convergence<C::V>>,
+    problem: Option<SolverProblem<C>>,
+    max_iter: usize,
+    max_rate: C::T,
+    niter: usize,
+    statistics: NonLinearSolverStatistics,
+}
+
+impl<C: NonLinearOp> Default for AndersonSolver<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: NonLinearOp> AndersonSolver<C> {
+    pub fn new() -> Self {
+        Self {
+            depth: 3,
+            problem: None,
+            convergence: None,
+            max_iter: 100,
+            max_rate: C::T::one(),
+            niter: 0,
+            statistics: NonLinearSolverStatistics::default(),
+        }
+    }
+
+    fn iterate(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("AndersonSolver::solve() called before set_problem");
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!(
+                "AndersonSolver::solve() called with state of wrong size, expected {}, got {}",
+                self.problem.as_ref().unwrap().f.nstates(),
+                xn.len()
+            );
+        }
+        let problem = self.problem.as_ref().unwrap();
+        let convergence = self.convergence.as_mut().unwrap();
+        convergence.reset();
+
+        // the differences (ΔF, ΔG) between successive iterates, oldest first
+        let mut history: VecDeque<(C::V, C::V)> = VecDeque::with_capacity(self.depth + 1);
+        let mut f = xn.clone();
+        let mut f_old = xn.clone();
+        let mut dx = xn.clone();
+        self.niter = 0;
+        loop {
+            self.niter += 1;
+            problem.f.call_inplace(xn, t.clone(), &mut f);
+
+            if self.depth > 0 && self.niter > 1 {
+                // ΔF = F(x_n) - F(x_n-1), ΔG = (x_n - x_n-1) - ΔF
+                f_old.axpy(C::T::one(), &f, -C::T::one());
+                dx.axpy(-C::T::one(), &f_old, C::T::one());
+                if history.len() == self.depth {
+                    history.pop_front();
+                }
+                history.push_back((f_old.clone(), dx.clone()));
+            }
+            f_old.copy_from(&f);
+
+            // dx = G(x_n) - x_n - ΔG γ = -F(x_n) - ΔG γ
+            dx.axpy(-C::T::one(), &f, C::T::zero());
+            let m = history.len();
+            if m > 0 {
+                let gram = DMatrix::from_fn(m, m, |i, j| history[i].0.dot(&history[j].0));
+                let rhs = DVector::from_fn(m, |i, _| history[i].0.dot(&f));
+                match gram.cholesky() {
+                    Some(cholesky) => {
+                        let gamma = cholesky.solve(&rhs);
+                        for (i, (_df, dg)) in history.iter().enumerate() {
+                            dx.axpy(-gamma[i].clone(), dg, C::T::one());
+                        }
+                    }
+                    // the differences are linearly dependent, restart from the fixed-point step
+                    None => history.clear(),
+                }
+            }
+            *xn += &dx;
+
+            let res = convergence.check_new_iteration(&mut dx, xn);
+            match res {
+                ConvergenceStatus::Continue => continue,
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => break,
+                ConvergenceStatus::MaximumIterations => break,
+            }
+        }
+        Err(PSError::MaxIterReached)
+    }
+}
+
+impl<C: NonLinearOp> NonLinearSolver<C> for AndersonSolver<C> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.max_rate = max_rate;
+    }
+    fn max_rate(&self) -> C::T {
+        self.max_rate.clone()
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.convergence.as_ref().and_then(|c| c.rate())
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.statistics
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("AndersonSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.statistics = NonLinearSolverStatistics::default();
+        let problem = self.problem.as_ref().unwrap();
+        let mut convergence = Convergence::new_from_problem(problem, self.max_iter);
+        convergence.set_max_rate(self.max_rate.clone());
+        self.convergence = Some(convergence);
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.convergence = None;
+    }
+
+    fn reset_jacobian(&mut self, _x: &C::V, _t: C::T) {
+        // no jacobian is used, so there is nothing to do
+    }
+
+    fn solve_linearised_in_place(&self, _x: &mut C::V) -> Result<(), PSError> {
+        Ok(())
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        let result = self.iterate(xn, t);
+        let rate = self.convergence_rate();
+        self.statistics
+            .record_solve(self.niter, rate, result.is_ok());
+        result
+    }
+}
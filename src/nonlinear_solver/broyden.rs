@@ -0,0 +1,191 @@
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError, op::NonLinearOp, Convergence, ConvergenceStatus, NonLinearSolver,
+    NonLinearSolverStatistics, Scalar, SolverProblem, Vector,
+};
+
+/// A quasi-Newton nonlinear solver that uses Broyden's method, i.e. a Newton iteration `x_{n+1} = x_n - H F(x_n)` where the
+/// approximate inverse jacobian `H` is built up from rank-1 updates instead of being evaluated and factorised.
+///
+/// The approximation starts from the identity, so that the first iteration is the fixed-point iteration used by the
+/// [crate::FixedPointNonlinearSolver], and after each iteration the "good" Broyden update `H <- (I + a s^T) H`, with
+/// `a = (s - H y) / (s^T H y)`, is applied so that `H y = s` for the step `s` and the resulting change `y` in the residual.
+/// The updates are kept between solves, since successive nonlinear stages of an ODE solver have similar jacobians, and are
+/// discarded when [NonLinearSolver::reset_jacobian] is called (e.g. after a convergence failure or a change in step size),
+/// when a solve fails, or once [Self::max_rank] updates have been applied.
+///
+/// No jacobian evaluations or linear solves are required, and the iteration converges for moderately stiff problems where the
+/// fixed-point iteration diverges, so this is a cheaper option than the [crate::NewtonNonlinearSolver] when the jacobian is
+/// expensive to evaluate or factorise and the problem is not too stiff.
+pub struct BroydenSolver<C: NonLinearOp> {
+    /// Maximum number of rank-1 updates applied to the approximate inverse jacobian before it is reset to the identity (default 20)
+    pub max_rank: usize,
+    convergence: Option<Convergence<C::V>>,
+    problem: Option<SolverProblem<C>>,
+    // the updates H <- (I + a s^T) H of the approximate inverse jacobian H, as pairs (a, s)
+    updates: Vec<(C::V, C::V)>,
+    max_iter: usize,
+    max_rate: C::T,
+    niter: usize,
+    statistics: NonLinearSolverStatistics,
+}
+
+impl<C: NonLinearOp> Default for BroydenSolver<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: NonLinearOp> BroydenSolver<C> {
+    pub fn new() -> Self {
+        Self {
+            max_rank: 20,
+            problem: None,
+            convergence: None,
+            updates: Vec::new(),
+            max_iter: 100,
+            max_rate: C::T::one(),
+            niter: 0,
+            statistics: NonLinearSolverStatistics::default(),
+        }
+    }
+
+    /// Number of updates applied to the approximate inverse jacobian
+    pub fn rank(&self) -> usize {
+        self.updates.len()
+    }
+
+    fn iterate(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("BroydenSolver::solve() called before set_problem");
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!(
+                "BroydenSolver::solve() called with state of wrong size, expected {}, got {}",
+                self.problem.as_ref().unwrap().f.nstates(),
+                xn.len()
+            );
+        }
+        let problem = self.problem.as_ref().unwrap();
+        let convergence = self.convergence.as_mut().unwrap();
+        convergence.reset();
+
+        let mut f = xn.clone();
+        let mut f_old = xn.clone();
+        let mut dx = xn.clone();
+        self.niter = 0;
+        loop {
+            self.niter += 1;
+            problem.f.call_inplace(xn, t.clone(), &mut f);
+
+            if self.niter > 1 {
+                // y = F(x_n) - F(x_n-1) for the step dx = x_n - x_n-1
+                f_old.axpy(C::T::one(), &f, -C::T::one());
+                update(&mut self.updates, self.max_rank, &dx, &mut f_old);
+            }
+            f_old.copy_from(&f);
+
+            // dx = -H F(x_n)
+            dx.axpy(-C::T::one(), &f, C::T::zero());
+            apply_inverse(&self.updates, &mut dx);
+            *xn += &dx;
+
+            let res = convergence.check_new_iteration(&mut dx, xn);
+            match res {
+                ConvergenceStatus::Continue => continue,
+                ConvergenceStatus::Converged => return Ok(()),
+                ConvergenceStatus::Diverged => break,
+                ConvergenceStatus::MaximumIterations => break,
+            }
+        }
+        // the approximation is not good enough, so start again from the identity on the next solve
+        self.updates.clear();
+        Err(PSError::MaxIterReached)
+    }
+}
+
+// applies the approximate inverse jacobian H = (I + a_k s_k^T) ... (I + a_1 s_1^T) to `x`
+fn apply_inverse<V: Vector>(updates: &[(V, V)], x: &mut V) {
+    for (a, s) in updates.iter() {
+        x.axpy(s.dot(x), a, V::T::one());
+    }
+}
+
+// applies the update for the step `s`, given the resulting change in residual in `y`, which is overwritten
+fn update<V: Vector>(updates: &mut Vec<(V, V)>, max_rank: usize, s: &V, y: &mut V) {
+    if updates.len() >= max_rank {
+        updates.clear();
+    }
+    apply_inverse(updates, y);
+    let denom = s.dot(y);
+    if denom.clone().abs() <= V::T::EPSILON * s.norm() * y.norm() || denom.is_zero() {
+        return;
+    }
+    let inv_denom = V::T::one() / denom;
+    y.axpy(inv_denom.clone(), s, -inv_denom);
+    updates.push((y.clone(), s.clone()));
+}
+
+impl<C: NonLinearOp> NonLinearSolver<C> for BroydenSolver<C> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.max_rate = max_rate;
+    }
+    fn max_rate(&self) -> C::T {
+        self.max_rate.clone()
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.convergence.as_ref().and_then(|c| c.rate())
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.statistics
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("BroydenSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.statistics = NonLinearSolverStatistics::default();
+        self.updates.clear();
+        let problem = self.problem.as_ref().unwrap();
+        let mut convergence = Convergence::new_from_problem(problem, self.max_iter);
+        convergence.set_max_rate(self.max_rate.clone());
+        self.convergence = Some(convergence);
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.convergence = None;
+        self.updates.clear();
+    }
+
+    fn reset_jacobian(&mut self, _x: &C::V, _t: C::T) {
+        self.updates.clear();
+        self.statistics.number_of_jacobian_updates += 1;
+    }
+
+    fn solve_linearised_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
+        apply_inverse(&self.updates, x);
+        Ok(())
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        let result = self.iterate(xn, t);
+        let rate = self.convergence_rate();
+        self.statistics
+            .record_solve(self.niter, rate, result.is_ok());
+        result
+    }
+}
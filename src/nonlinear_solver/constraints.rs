@@ -0,0 +1,112 @@
+use num_traits::{One, Zero};
+
+use crate::{errors::PSError, Vector};
+
+/// Lower and upper bounds on each component of the solution of a nonlinear solve, e.g. to keep concentrations non-negative
+/// (see [crate::OdeSolverProblem::with_constraints]). Components without a bound use an infinite lower or upper bound.
+///
+/// The [crate::NewtonNonlinearSolver] keeps its iterates within the bounds, similar to `IDASetConstraints` in IDA: the initial
+/// guess is clipped to the bounds, and each Newton step is shortened so that no component crosses its bound. If a component
+/// is already on its bound and the Newton step points out of the bounds, the constraints cannot be satisfied and the solve
+/// fails with [PSError::ConstraintsNotSatisfied], so that the ODE solver rejects the step and retries with a smaller step size.
+#[derive(Clone, Debug)]
+pub struct Constraints<V: Vector> {
+    lower: V,
+    upper: V,
+}
+
+impl<V: Vector> Constraints<V> {
+    /// Create the constraints `lower <= x <= upper`, returning an error if the bounds are of different lengths, or `lower > upper`
+    /// for any component.
+    pub fn new(lower: V, upper: V) -> Result<Self, PSError> {
+        if lower.len() != upper.len() || (0..lower.len()).any(|i| lower[i] > upper[i]) {
+            return Err(PSError::InvalidConstraints);
+        }
+        Ok(Self { lower, upper })
+    }
+
+    pub fn lower(&self) -> &V {
+        &self.lower
+    }
+
+    pub fn upper(&self) -> &V {
+        &self.upper
+    }
+
+    pub fn len(&self) -> usize {
+        self.lower.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lower.is_empty()
+    }
+
+    /// Returns true if `x` is within the bounds
+    pub fn is_satisfied(&self, x: &V) -> bool {
+        (0..x.len()).all(|i| self.lower[i] <= x[i] && x[i] <= self.upper[i])
+    }
+
+    /// Clip each component of `x` to its bounds
+    pub fn clip(&self, x: &mut V) {
+        for i in 0..x.len() {
+            if x[i] < self.lower[i] {
+                x[i] = self.lower[i].clone();
+            } else if x[i] > self.upper[i] {
+                x[i] = self.upper[i].clone();
+            }
+        }
+    }
+
+    /// The largest `λ` in `[0, 1]` such that `x + λ dx` is within the bounds, for `x` within the bounds
+    pub fn max_step(&self, x: &V, dx: &V) -> V::T {
+        let mut lambda = V::T::one();
+        for i in 0..x.len() {
+            let bound = if dx[i] < V::T::zero() {
+                self.lower[i].clone()
+            } else if dx[i] > V::T::zero() {
+                self.upper[i].clone()
+            } else {
+                continue;
+            };
+            let fraction = (bound - x[i].clone()) / dx[i].clone();
+            if fraction < lambda {
+                lambda = if fraction > V::T::zero() {
+                    fraction
+                } else {
+                    V::T::zero()
+                };
+            }
+        }
+        lambda
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+
+    use super::Constraints;
+
+    #[test]
+    fn test_constraints() {
+        assert!(
+            Constraints::new(DVector::from_vec(vec![1.0]), DVector::from_vec(vec![0.0])).is_err()
+        );
+        let c = Constraints::new(
+            DVector::from_vec(vec![0.0, -1.0]),
+            DVector::from_vec(vec![f64::INFINITY, 1.0]),
+        )
+        .unwrap();
+        let mut x = DVector::from_vec(vec![-1.0, 2.0]);
+        assert!(!c.is_satisfied(&x));
+        c.clip(&mut x);
+        assert_eq!(x, DVector::from_vec(vec![0.0, 1.0]));
+
+        // the step is shortened to stop at the first bound it reaches
+        let x = DVector::from_vec(vec![1.0, 0.0]);
+        assert_eq!(c.max_step(&x, &DVector::from_vec(vec![-4.0, 1.0])), 0.25);
+        assert_eq!(c.max_step(&x, &DVector::from_vec(vec![10.0, 0.5])), 1.0);
+        let x = DVector::from_vec(vec![0.0, 0.0]);
+        assert_eq!(c.max_step(&x, &DVector::from_vec(vec![-1.0, 0.0])), 0.0);
+    }
+}
@@ -4,13 +4,32 @@ use std::rc::Rc;
 
 use crate::{scalar::IndexType, solver::SolverProblem, NonLinearOp, Scalar, Vector};
 
+/// The test used by [Convergence] to decide whether an iteration has converged or is diverging.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConvergenceCriterion {
+    /// The iteration has converged once the error, estimated from the weighted norm of the update `|dy|` and the convergence rate
+    /// `θ` as `θ / (1 - θ) |dy|`, is below the tolerance, and is aborted early if it is not predicted to converge within the
+    /// maximum number of iterations (the default).
+    #[default]
+    UpdateNorm,
+    /// The affine-invariant natural monotonicity test of Deuflhard: the iteration is diverging if the ratio `θ` of the norms of
+    /// successive (simplified) Newton corrections exceeds the maximum rate (i.e. the corrections are not decreasing), and has
+    /// converged once the norm of the correction itself is below the tolerance. The test does not extrapolate from the estimated
+    /// rate, which is more robust for badly scaled DAEs where the rate estimates of the first iterations are unreliable.
+    NaturalMonotonicity,
+}
+
 pub struct Convergence<V: Vector> {
     rtol: V::T,
     atol: Rc<V>,
     tol: V::T,
     max_iter: IndexType,
+    max_rate: V::T,
     iter: IndexType,
     old_norm: Option<V::T>,
+    rate: Option<V::T>,
+    niter: IndexType,
+    criterion: ConvergenceCriterion,
 }
 
 pub enum ConvergenceStatus {
@@ -25,14 +44,16 @@ impl<V: Vector> Convergence<V> {
         problem: &SolverProblem<C>,
         max_iter: IndexType,
     ) -> Self {
-        let rtol = problem.rtol;
+        let rtol = problem.rtol.clone();
         let atol = problem.atol.clone();
-        Self::new(rtol, atol, max_iter)
+        let mut convergence = Self::new(rtol, atol, max_iter);
+        convergence.set_criterion(problem.convergence_criterion);
+        convergence
     }
     pub fn new(rtol: V::T, atol: Rc<V>, max_iter: usize) -> Self {
-        let minimum_tol = V::T::from(10.0) * V::T::EPSILON / rtol;
-        let maximum_tol = V::T::from(0.03);
-        let mut tol = V::T::from(0.5) * rtol.pow(V::T::from(0.5));
+        let minimum_tol = V::T::cast(10.0) * V::T::EPSILON / rtol.clone();
+        let maximum_tol = V::T::cast(0.03);
+        let mut tol = V::T::cast(0.5) * rtol.clone().pow(V::T::cast(0.5));
         if tol > maximum_tol {
             tol = maximum_tol;
         }
@@ -44,42 +65,79 @@ impl<V: Vector> Convergence<V> {
             atol,
             tol,
             max_iter,
+            max_rate: V::T::one(),
             old_norm: None,
+            rate: None,
             iter: 0,
+            niter: 0,
+            criterion: ConvergenceCriterion::default(),
         }
     }
+    /// Set the maximum convergence rate, above which the iteration is considered to be diverging.
+    pub fn set_max_rate(&mut self, max_rate: V::T) {
+        self.max_rate = max_rate;
+    }
+    /// Set the test used to decide whether the iteration has converged (see [ConvergenceCriterion]).
+    pub fn set_criterion(&mut self, criterion: ConvergenceCriterion) {
+        self.criterion = criterion;
+    }
+    pub fn criterion(&self) -> ConvergenceCriterion {
+        self.criterion
+    }
     pub fn reset(&mut self) {
         self.iter = 0;
         self.old_norm = None;
+        self.rate = None;
+        self.niter = 0;
+    }
+    /// The estimated (linear) convergence rate of the iteration, i.e. the ratio of the norms of the last two updates,
+    /// or `None` if fewer than two iterations have been checked since the last [Self::reset].
+    pub fn rate(&self) -> Option<V::T> {
+        self.rate.clone()
+    }
+    /// The number of iterations checked since the last [Self::reset]
+    pub fn niter(&self) -> IndexType {
+        self.niter
     }
     pub fn check_new_iteration(&mut self, dy: &mut V, y: &V) -> ConvergenceStatus {
-        let norm = dy.squared_norm(y, &self.atol, self.rtol).sqrt();
+        self.niter += 1;
+        let norm = dy.squared_norm(y, &self.atol, self.rtol.clone()).sqrt();
         // if norm is zero then we are done
         if norm <= V::T::EPSILON {
             return ConvergenceStatus::Converged;
         }
-        if let Some(old_norm) = self.old_norm {
-            let rate = norm / old_norm;
+        if let Some(old_norm) = self.old_norm.clone() {
+            let rate = norm.clone() / old_norm;
+            self.rate = Some(rate.clone());
 
-            if rate > V::T::from(1.0) {
+            if rate > self.max_rate {
                 return ConvergenceStatus::Diverged;
             }
 
-            // if converged then break out of iteration successfully
-            if rate / (V::T::one() - rate) * norm < self.tol {
-                return ConvergenceStatus::Converged;
-            }
+            // the following estimates are only valid for a contracting iteration
+            if self.criterion == ConvergenceCriterion::UpdateNorm && rate < V::T::one() {
+                // if converged then break out of iteration successfully
+                if rate.clone() / (V::T::one() - rate.clone()) * norm.clone() < self.tol {
+                    return ConvergenceStatus::Converged;
+                }
 
-            // if iteration is not going to converge in NEWTON_MAXITER
-            // (assuming the current rate), then abort
-            if rate.pow(i32::try_from(self.max_iter - self.iter).unwrap())
-                / (V::T::from(1.0) - rate)
-                * norm
-                > self.tol
-            {
-                return ConvergenceStatus::Diverged;
+                // if the iteration is not predicted to converge in the remaining iterations
+                // (assuming the current rate), then abort early rather than wasting them
+                if rate
+                    .clone()
+                    .pow(i32::try_from(self.max_iter - self.iter).unwrap())
+                    / (V::T::cast(1.0) - rate)
+                    * norm.clone()
+                    > self.tol
+                {
+                    return ConvergenceStatus::Diverged;
+                }
             }
         }
+        // the natural monotonicity test only needs the norm of the current correction, so can converge in 1 iteration
+        if self.criterion == ConvergenceCriterion::NaturalMonotonicity && norm < self.tol {
+            return ConvergenceStatus::Converged;
+        }
         // TODO: at the moment need 2 iterations to check convergence, should be able to do it in 1?
         self.iter += 1;
         self.old_norm = Some(norm);
@@ -90,3 +148,81 @@ impl<V: Vector> Convergence<V> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::DVector;
+
+    use super::{Convergence, ConvergenceCriterion, ConvergenceStatus};
+
+    #[test]
+    fn test_convergence_rate() {
+        let atol = Rc::new(DVector::from_element(1, 1e-3_f64));
+        let y = DVector::from_element(1, 1.0);
+        let mut convergence = Convergence::new(1e-3, atol, 4);
+
+        // the rate is estimated from the second iteration, and a fast rate converges
+        let mut dy = DVector::from_element(1, 1e-2);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Continue
+        ));
+        assert!(convergence.rate().is_none());
+        let mut dy = DVector::from_element(1, 1e-5);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Converged
+        ));
+        assert!((convergence.rate().unwrap() - 1e-3).abs() < 1e-12);
+
+        // a slow rate is predicted not to converge within the maximum number of iterations, so the iteration is aborted early
+        convergence.reset();
+        assert!(convergence.rate().is_none());
+        let mut dy = DVector::from_element(1, 1e-2);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Continue
+        ));
+        let mut dy = DVector::from_element(1, 9e-3);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Diverged
+        ));
+        assert!((convergence.rate().unwrap() - 0.9).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_natural_monotonicity() {
+        let atol = Rc::new(DVector::from_element(1, 1e-3_f64));
+        let y = DVector::from_element(1, 1.0);
+        let mut convergence = Convergence::new(1e-3, atol, 4);
+        convergence.set_criterion(ConvergenceCriterion::NaturalMonotonicity);
+
+        // a small correction converges in a single iteration
+        let mut dy = DVector::from_element(1, 1e-5);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Converged
+        ));
+
+        // a slow rate is not extrapolated, so the iteration continues as long as the corrections decrease
+        convergence.reset();
+        for dy in [1e-2, 9e-3, 8.1e-3] {
+            let mut dy = DVector::from_element(1, dy);
+            assert!(matches!(
+                convergence.check_new_iteration(&mut dy, &y),
+                ConvergenceStatus::Continue
+            ));
+        }
+        assert!((convergence.rate().unwrap() - 0.9).abs() < 1e-12);
+
+        // and diverges once a correction is larger than the previous one
+        let mut dy = DVector::from_element(1, 1e-2);
+        assert!(matches!(
+            convergence.check_new_iteration(&mut dy, &y),
+            ConvergenceStatus::Diverged
+        ));
+    }
+}
@@ -0,0 +1,110 @@
+use num_traits::One;
+
+use crate::{
+    errors::PSError, op::NonLinearOp, Convergence, NonLinearSolver, NonLinearSolverStatistics,
+    SolverProblem, Vector,
+};
+
+use super::newton::newton_iteration;
+
+/// A nonlinear solver that uses functional (fixed-point) iteration `x_{n+1} = x_n - F(x_n)`.
+///
+/// This is equivalent to a Newton iteration where the jacobian of `F` is approximated by the identity,
+/// so no jacobian evaluations or linear solves are required. The iteration only converges if `I - dF/dx` is a contraction,
+/// which is the case for the corrector equations of explicit-like (e.g. Adams) methods applied to non-stiff problems.
+pub struct FixedPointNonlinearSolver<C: NonLinearOp> {
+    convergence: Option<Convergence<C::V>>,
+    problem: Option<SolverProblem<C>>,
+    max_iter: usize,
+    max_rate: C::T,
+    niter: usize,
+    statistics: NonLinearSolverStatistics,
+}
+
+impl<C: NonLinearOp> Default for FixedPointNonlinearSolver<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: NonLinearOp> FixedPointNonlinearSolver<C> {
+    pub fn new() -> Self {
+        Self {
+            problem: None,
+            convergence: None,
+            max_iter: 100,
+            max_rate: C::T::one(),
+            niter: 0,
+            statistics: NonLinearSolverStatistics::default(),
+        }
+    }
+}
+
+impl<C: NonLinearOp> NonLinearSolver<C> for FixedPointNonlinearSolver<C> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.max_rate = max_rate;
+    }
+    fn max_rate(&self) -> C::T {
+        self.max_rate.clone()
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.convergence.as_ref().and_then(|c| c.rate())
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.statistics
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("FixedPointNonlinearSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        let problem = self.problem.as_ref().unwrap();
+        let mut convergence = Convergence::new_from_problem(problem, self.max_iter);
+        convergence.set_max_rate(self.max_rate.clone());
+        self.convergence = Some(convergence);
+        self.statistics = NonLinearSolverStatistics::default();
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.convergence = None;
+    }
+
+    fn reset_jacobian(&mut self, _x: &C::V, _t: C::T) {
+        // the jacobian is approximated by the identity, so there is nothing to do
+    }
+
+    fn solve_linearised_in_place(&self, _x: &mut C::V) -> Result<(), PSError> {
+        Ok(())
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("FixedPointNonlinearSolver::solve() called before set_problem");
+        }
+        if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
+            panic!("FixedPointNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
+        }
+        let identity = |_x: &mut C::V| Ok(());
+        let problem = self.problem.as_ref().unwrap();
+        let fun = |x: &C::V, y: &mut C::V| problem.f.call_inplace(x, t.clone(), y);
+
+        let convergence = self.convergence.as_mut().unwrap();
+        let result = newton_iteration(xn, fun, identity, convergence);
+        self.statistics
+            .record_solve(convergence.niter(), convergence.rate(), result.is_ok());
+        self.niter = result?;
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    errors::PSError,
+    linear_solver::{LinearSolverReport, LinearSolverStatistics},
+    op::NonLinearOp,
+    DoglegSolver, LinearSolver, NewtonNonlinearSolver, NonLinearSolver, NonLinearSolverStatistics,
+    SolverProblem,
+};
+
+/// The nonlinear solver used to calculate consistent initial conditions for a DAE, chosen when the problem is built
+/// (see [crate::OdeBuilder::initialisation_solver]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NonLinearSolverKind {
+    /// Newton iteration ([NewtonNonlinearSolver])
+    #[default]
+    Newton,
+    /// Powell's dogleg trust-region method ([DoglegSolver]), which converges from poorer initial guesses than the Newton iteration
+    Dogleg,
+}
+
+/// A [NonLinearSolver] chosen at runtime from a [NonLinearSolverKind], using the given linear solver.
+pub(crate) enum AnyNonLinearSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
+    Newton(NewtonNonlinearSolver<C, Ls>),
+    Dogleg(DoglegSolver<C, Ls>),
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> AnyNonLinearSolver<C, Ls> {
+    pub(crate) fn new(kind: NonLinearSolverKind, linear_solver: Ls) -> Self {
+        match kind {
+            NonLinearSolverKind::Newton => Self::Newton(NewtonNonlinearSolver::new(linear_solver)),
+            NonLinearSolverKind::Dogleg => Self::Dogleg(DoglegSolver::new(linear_solver)),
+        }
+    }
+
+    fn as_solver(&self) -> &dyn NonLinearSolver<C> {
+        match self {
+            Self::Newton(s) => s,
+            Self::Dogleg(s) => s,
+        }
+    }
+
+    fn as_solver_mut(&mut self) -> &mut dyn NonLinearSolver<C> {
+        match self {
+            Self::Newton(s) => s,
+            Self::Dogleg(s) => s,
+        }
+    }
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for AnyNonLinearSolver<C, Ls> {
+    fn problem(&self) -> &SolverProblem<C> {
+        self.as_solver().problem()
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.as_solver_mut().set_problem(problem)
+    }
+    fn clear_problem(&mut self) {
+        self.as_solver_mut().clear_problem()
+    }
+    fn reset_jacobian(&mut self, x: &C::V, t: C::T) {
+        self.as_solver_mut().reset_jacobian(x, t)
+    }
+    fn solve_in_place(&mut self, x: &mut C::V, t: C::T) -> Result<(), PSError> {
+        self.as_solver_mut().solve_in_place(x, t)
+    }
+    fn solve_linearised_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
+        self.as_solver().solve_linearised_in_place(x)
+    }
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.as_solver_mut().set_max_iter(max_iter)
+    }
+    fn max_iter(&self) -> usize {
+        self.as_solver().max_iter()
+    }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.as_solver_mut().set_max_rate(max_rate)
+    }
+    fn max_rate(&self) -> C::T {
+        self.as_solver().max_rate()
+    }
+    fn niter(&self) -> usize {
+        self.as_solver().niter()
+    }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.as_solver().convergence_rate()
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.as_solver().get_statistics()
+    }
+    fn linear_solver_report(&self) -> LinearSolverReport<C::T> {
+        self.as_solver().linear_solver_report()
+    }
+    fn linear_solver_statistics(&self) -> LinearSolverStatistics {
+        self.as_solver().linear_solver_statistics()
+    }
+}
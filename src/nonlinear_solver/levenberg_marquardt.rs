@@ -0,0 +1,215 @@
+use nalgebra::{DMatrix, DVector};
+
+use crate::{
+    errors::PSError, matrix::sparsity::MatrixSparsityRef, op::NonLinearOp, Matrix, Scalar, Vector,
+};
+
+/// A solver for the nonlinear least-squares problem of minimising `|F(x)|^2`, where `F` maps `n` unknowns to `m >= n` residuals,
+/// using the Levenberg–Marquardt method.
+///
+/// Each iteration solves the damped normal equations `(J^T J + λ diag(J^T J)) δ = -J^T F` for the step `δ`, where `J` is the
+/// jacobian of `F` at the current point. The damping `λ` is increased until the step reduces `|F|`, and decreased after each
+/// successful step, so that the iteration moves from gradient descent far from the solution to the Gauss–Newton method close to it.
+/// The normal equations are formed and factorised as dense `n x n` matrices, so this is intended for problems with a small
+/// number of unknowns, e.g. consistent initialisation with more constraints than algebraic unknowns, or fitting a few
+/// parameters to a handful of data points.
+///
+/// The iteration stops successfully once the gradient `J^T F` is small, the step is small relative to `x`, or the relative
+/// reduction in `|F|^2` over a step is small, i.e. at a (local) minimum of `|F|^2` rather than necessarily at a zero of `F`.
+pub struct LmSolver<T: Scalar> {
+    /// Maximum number of trial steps (default 100)
+    pub max_iter: usize,
+    /// Tolerance on the largest component of the gradient `J^T F` (default 1e-10)
+    pub gtol: T,
+    /// Tolerance on the step, relative to `|x|` (default 1e-10)
+    pub xtol: T,
+    /// Tolerance on the relative reduction in `|F|^2` over a step (default 1e-14)
+    pub ftol: T,
+    /// Damping `λ` used for the first step (default 1e-3)
+    pub initial_damping: T,
+    niter: usize,
+}
+
+impl<T: Scalar> Default for LmSolver<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> LmSolver<T> {
+    pub fn new() -> Self {
+        Self {
+            max_iter: 100,
+            gtol: T::cast(1e-10),
+            xtol: T::cast(1e-10),
+            ftol: T::cast(1e-14),
+            initial_damping: T::cast(1e-3),
+            niter: 0,
+        }
+    }
+
+    /// Number of trial steps taken by the last solve
+    pub fn niter(&self) -> usize {
+        self.niter
+    }
+
+    /// Minimise `|F(x, t)|^2` for the operator `f`, starting from and overwriting `x`, and return the norm of the final residual.
+    pub fn solve<C: NonLinearOp<T = T>>(
+        &mut self,
+        f: &C,
+        x: &mut C::V,
+        t: T,
+    ) -> Result<T, PSError> {
+        if x.len() != f.nstates() {
+            panic!(
+                "LmSolver::solve() called with state of wrong size, expected {}, got {}",
+                f.nstates(),
+                x.len()
+            );
+        }
+        let (m, n) = (f.nout(), f.nstates());
+        let ten = T::cast(10.0);
+        let mut jac = C::M::new_from_sparsity(m, n, f.sparsity().map(|s| s.to_owned()));
+        let mut r = f.call(x, t.clone());
+        let mut cost = r.dot(&r);
+        let mut r_trial = r.clone();
+        let mut x_trial = x.clone();
+        let mut lambda = self.initial_damping.clone();
+        self.niter = 0;
+        loop {
+            f.jacobian_inplace(x, t.clone(), &mut jac);
+            let mut jd = DMatrix::<T>::zeros(m, n);
+            for (i, j, v) in jac.triplet_iter() {
+                jd[(i, j)] += v.clone();
+            }
+            let rd = DVector::from_fn(m, |i, _| r[i].clone());
+            let g = jd.tr_mul(&rd);
+            if g.amax() <= self.gtol {
+                return Ok(cost.sqrt());
+            }
+            let jtj = jd.tr_mul(&jd);
+
+            // increase the damping until the step reduces the residual
+            loop {
+                if self.niter >= self.max_iter {
+                    return Err(PSError::MaxIterReached);
+                }
+                self.niter += 1;
+                let mut a = jtj.clone();
+                for k in 0..n {
+                    let d = jtj[(k, k)].clone();
+                    a[(k, k)] += lambda.clone() * if d > T::EPSILON { d } else { T::EPSILON };
+                }
+                let delta = match a.cholesky() {
+                    Some(cholesky) => -cholesky.solve(&g),
+                    None => {
+                        lambda *= ten.clone();
+                        continue;
+                    }
+                };
+                x_trial.copy_from(x);
+                for k in 0..n {
+                    x_trial[k] += delta[k].clone();
+                }
+                f.call_inplace(&x_trial, t.clone(), &mut r_trial);
+                let cost_trial = r_trial.dot(&r_trial);
+                let small_step = delta.norm() <= self.xtol.clone() * (x.norm() + self.xtol.clone());
+                if cost_trial.is_finite() && cost_trial < cost {
+                    let reduction = (cost.clone() - cost_trial.clone()) / cost;
+                    x.copy_from(&x_trial);
+                    std::mem::swap(&mut r, &mut r_trial);
+                    cost = cost_trial;
+                    lambda /= ten.clone();
+                    if small_step || reduction <= self.ftol {
+                        return Ok(cost.sqrt());
+                    }
+                    break;
+                }
+                // no step in the descent direction reduces the residual, so this is a minimum to within the tolerance
+                if small_step {
+                    return Ok(cost.sqrt());
+                }
+                lambda *= ten.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::DVector;
+
+    use super::LmSolver;
+    use crate::Closure;
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = DVector<f64>;
+
+    #[test]
+    fn test_lm_overdetermined() {
+        // three consistent equations in two unknowns, x0 + x1 = 3, x0 - x1 = 1, x0 x1 = 2
+        let f = Closure::<M, _, _>::new(
+            |x: &V, _p: &V, _t: f64, y: &mut V| {
+                y[0] = x[0] + x[1] - 3.0;
+                y[1] = x[0] - x[1] - 1.0;
+                y[2] = x[0] * x[1] - 2.0;
+            },
+            |x: &V, _p: &V, _t: f64, v: &V, y: &mut V| {
+                y[0] = v[0] + v[1];
+                y[1] = v[0] - v[1];
+                y[2] = x[1] * v[0] + x[0] * v[1];
+            },
+            2,
+            3,
+            Rc::new(V::zeros(0)),
+        );
+        let mut solver = LmSolver::new();
+        let mut x = DVector::from_vec(vec![5.0, -4.0]);
+        let residual = solver.solve(&f, &mut x, 0.0).unwrap();
+        assert!(residual < 1e-8, "residual {}", residual);
+        assert!((x[0] - 2.0).abs() < 1e-8 && (x[1] - 1.0).abs() < 1e-8);
+    }
+
+    #[test]
+    fn test_lm_fit() {
+        // fit y = a exp(b t) to noisy data, the minimum has a non-zero residual
+        let ts: [f64; 6] = [0.0, 0.5, 1.0, 1.5, 2.0, 3.0];
+        let noise = [0.01, -0.02, 0.015, 0.0, -0.01, 0.005];
+        let data: Vec<f64> = ts
+            .iter()
+            .zip(noise.iter())
+            .map(|(t, e)| 2.0 * (-0.5 * t).exp() + e)
+            .collect();
+        let f = Closure::<M, _, _>::new(
+            move |x: &V, _p: &V, _t: f64, y: &mut V| {
+                for i in 0..ts.len() {
+                    y[i] = x[0] * (x[1] * ts[i]).exp() - data[i];
+                }
+            },
+            move |x: &V, _p: &V, _t: f64, v: &V, y: &mut V| {
+                for i in 0..ts.len() {
+                    let e = (x[1] * ts[i]).exp();
+                    y[i] = e * v[0] + x[0] * ts[i] * e * v[1];
+                }
+            },
+            2,
+            6,
+            Rc::new(V::zeros(0)),
+        );
+        let mut solver = LmSolver::new();
+        let mut x = DVector::from_vec(vec![1.0, 0.0]);
+        let residual = solver.solve(&f, &mut x, 0.0).unwrap();
+        assert!(residual > 0.0 && residual < 0.05, "residual {}", residual);
+        assert!((x[0] - 2.0).abs() < 0.05 && (x[1] + 0.5).abs() < 0.05);
+
+        // the gradient of |F|^2 vanishes at the minimum
+        let mut x_perturbed = x.clone();
+        x_perturbed[1] += 1e-4;
+        let mut solver = LmSolver::new();
+        let residual_perturbed = solver.solve(&f, &mut x_perturbed, 0.0).unwrap();
+        assert!((residual_perturbed - residual).abs() < 1e-10);
+        assert!((&x_perturbed - &x).norm() < 1e-6);
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{errors::PSError, op::Op, solver::SolverProblem};
+use serde::Serialize;
+
+use crate::{
+    errors::PSError,
+    linear_solver::{LinearSolverReport, LinearSolverStatistics},
+    op::Op,
+    solver::SolverProblem,
+    Scalar,
+};
 
 pub struct NonLinearSolveSolution<V> {
     pub x0: V,
@@ -11,6 +19,47 @@ impl<V> NonLinearSolveSolution<V> {
     }
 }
 
+/// Counts of the work done by a [NonLinearSolver] since its problem was set (see [NonLinearSolver::get_statistics]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct NonLinearSolverStatistics {
+    /// Number of calls to [NonLinearSolver::solve_in_place]
+    pub number_of_solves: usize,
+    /// Total number of iterations over all the solves, including those that failed
+    pub number_of_iterations: usize,
+    /// Number of solves that failed to converge
+    pub number_of_failures: usize,
+    /// Number of times the jacobian (or its approximation) was recalculated
+    pub number_of_jacobian_updates: usize,
+    /// Sum of the convergence rates estimated by the solves (see [NonLinearSolver::convergence_rate])
+    pub total_convergence_rate: f64,
+    /// Number of solves for which the convergence rate was estimated
+    pub number_of_rate_estimates: usize,
+}
+
+impl NonLinearSolverStatistics {
+    /// The average of the convergence rates estimated by the solves, or `None` if no rate was estimated
+    pub fn average_convergence_rate(&self) -> Option<f64> {
+        if self.number_of_rate_estimates == 0 {
+            None
+        } else {
+            Some(self.total_convergence_rate / self.number_of_rate_estimates as f64)
+        }
+    }
+
+    /// Record a solve that took `niter` iterations, with the estimated convergence rate `rate` (if any)
+    pub fn record_solve<T: Scalar>(&mut self, niter: usize, rate: Option<T>, converged: bool) {
+        self.number_of_solves += 1;
+        self.number_of_iterations += niter;
+        if !converged {
+            self.number_of_failures += 1;
+        }
+        if let Some(rate) = rate {
+            self.total_convergence_rate += rate.to_f64();
+            self.number_of_rate_estimates += 1;
+        }
+    }
+}
+
 /// A solver for the nonlinear problem `F(x) = 0`.
 pub trait NonLinearSolver<C: Op> {
     /// Get the problem to be solved.
@@ -19,6 +68,9 @@ pub trait NonLinearSolver<C: Op> {
     /// Set the problem to be solved, any previous problem is discarded.
     fn set_problem(&mut self, problem: &SolverProblem<C>);
 
+    /// Clear the current problem, releasing any references to it held by the solver.
+    fn clear_problem(&mut self);
+
     /// Reset the approximation of the Jacobian matrix.
     fn reset_jacobian(&mut self, x: &C::V, t: C::T);
 
@@ -42,30 +94,76 @@ pub trait NonLinearSolver<C: Op> {
     // Get the maximum number of iterations for the solver.
     fn max_iter(&self) -> usize;
 
+    // Set the maximum convergence rate, above which the iteration is considered to be diverging.
+    fn set_max_rate(&mut self, max_rate: C::T);
+
+    // Get the maximum convergence rate for the solver.
+    fn max_rate(&self) -> C::T;
+
     // Get the number of iterations taken by the solver on the last call to `solve`.
     fn niter(&self) -> usize;
+
+    /// The convergence rate (the ratio of the norms of successive updates) estimated on the last call to `solve`, or `None` if
+    /// it was not estimated, e.g. if fewer than two iterations were taken. The default implementation returns `None`.
+    fn convergence_rate(&self) -> Option<C::T> {
+        None
+    }
+
+    /// Statistics of the solves since the problem was set (see [NonLinearSolverStatistics]).
+    /// The default implementation returns empty statistics.
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        NonLinearSolverStatistics::default()
+    }
+
+    /// Diagnostics of the linear solver for the current approximation of the Jacobian (see [LinearSolverReport]).
+    /// The default implementation returns an empty report.
+    fn linear_solver_report(&self) -> LinearSolverReport<C::T> {
+        LinearSolverReport::default()
+    }
+
+    /// Statistics of the linear solver since the problem was set (see [LinearSolverStatistics]).
+    /// The default implementation returns empty statistics.
+    fn linear_solver_statistics(&self) -> LinearSolverStatistics {
+        LinearSolverStatistics::default()
+    }
 }
 
+pub mod anderson;
+pub mod broyden;
+pub mod constraints;
 pub mod convergence;
+pub mod fixed_point;
+pub mod kind;
+pub mod levenberg_marquardt;
 pub mod newton;
 pub mod root;
+pub mod scalar;
+pub mod trust_region;
 
 //tests
 #[cfg(test)]
 pub mod tests {
     use std::rc::Rc;
 
-    use self::newton::NewtonNonlinearSolver;
+    use self::{
+        anderson::AndersonSolver,
+        broyden::BroydenSolver,
+        fixed_point::FixedPointNonlinearSolver,
+        newton::{LineSearch, NewtonNonlinearSolver, NewtonOptions},
+        trust_region::DoglegSolver,
+    };
     use crate::{
         linear_solver::nalgebra::lu::LU,
         matrix::MatrixCommon,
         op::{closure::Closure, NonLinearOp},
-        scale, DenseMatrix, Vector,
+        scale, DenseMatrix, Gmres, Vector,
     };
 
     use super::*;
+    use nalgebra::ComplexField;
     use num_traits::{One, Zero};
 
+    #[allow(clippy::type_complexity)]
     pub fn get_square_problem<M>() -> (
         SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
         Vec<NonLinearSolveSolution<M::V>>,
@@ -73,7 +171,7 @@ pub mod tests {
     where
         M: DenseMatrix + 'static,
     {
-        let jac1 = M::from_diagonal(&M::V::from_vec(vec![2.0.into(), 2.0.into()]));
+        let jac1 = M::from_diagonal(&M::V::from_vec(vec![M::T::cast(2.0), M::T::cast(2.0)]));
         let jac2 = jac1.clone();
         let p = Rc::new(M::V::zeros(0));
         let op = Closure::new(
@@ -81,23 +179,114 @@ pub mod tests {
             move |x: &<M as MatrixCommon>::V, _p: &<M as MatrixCommon>::V, _t, y| {
                 jac1.gemv(M::T::one(), x, M::T::zero(), y); // y = J * x
                 y.component_mul_assign(x);
-                y.add_scalar_mut(M::T::from(-8.0));
+                y.add_scalar_mut(M::T::cast(-8.0));
             },
             // J = 2 * J * x * dx
             move |x: &<M as MatrixCommon>::V, _p: &<M as MatrixCommon>::V, _t, v, y| {
-                jac2.gemv(M::T::from(2.0), x, M::T::zero(), y); // y = 2 * J * x
+                jac2.gemv(M::T::cast(2.0), x, M::T::zero(), y); // y = 2 * J * x
                 y.component_mul_assign(v);
             },
             2,
             2,
             p,
         );
-        let rtol = M::T::from(1e-6);
-        let atol = M::V::from_vec(vec![1e-6.into(), 1e-6.into()]);
+        let rtol = M::T::cast(1e-6);
+        let atol = M::V::from_vec(vec![M::T::cast(1e-6), M::T::cast(1e-6)]);
+        let problem = SolverProblem::new(Rc::new(op), Rc::new(atol), rtol);
+        let solns = vec![NonLinearSolveSolution::new(
+            M::V::from_vec(vec![M::T::cast(2.1), M::T::cast(2.1)]),
+            M::V::from_vec(vec![M::T::cast(2.0), M::T::cast(2.0)]),
+        )];
+        (problem, solns)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn get_contraction_problem<M>() -> (
+        SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
+        Vec<NonLinearSolveSolution<M::V>>,
+    )
+    where
+        M: DenseMatrix + 'static,
+    {
+        let p = Rc::new(M::V::zeros(0));
+        let op = Closure::new(
+            // 0 = x - (x * x + 2) / 4
+            move |x: &<M as MatrixCommon>::V,
+                  _p: &<M as MatrixCommon>::V,
+                  _t,
+                  y: &mut <M as MatrixCommon>::V| {
+                y.copy_from(x);
+                y.component_mul_assign(x); // y = x * x
+                y.axpy(M::T::one(), x, M::T::cast(-0.25)); // y = x - x * x / 4
+                y.add_scalar_mut(M::T::cast(-0.5));
+            },
+            // J = (1 - x / 2) * dx
+            move |x: &<M as MatrixCommon>::V,
+                  _p: &<M as MatrixCommon>::V,
+                  _t,
+                  v: &<M as MatrixCommon>::V,
+                  y: &mut <M as MatrixCommon>::V| {
+                y.copy_from(x);
+                y.component_mul_assign(v); // y = x * v
+                y.axpy(M::T::one(), v, M::T::cast(-0.5)); // y = v - x * v / 2
+            },
+            2,
+            2,
+            p,
+        );
+        let rtol = M::T::cast(1e-6);
+        let atol = M::V::from_vec(vec![M::T::cast(1e-6), M::T::cast(1e-6)]);
+        let problem = SolverProblem::new(Rc::new(op), Rc::new(atol), rtol);
+        let x = M::T::cast(2.0 - 2.0_f64.sqrt());
+        let solns = vec![NonLinearSolveSolution::new(
+            M::V::from_vec(vec![M::T::cast(0.0), M::T::cast(1.0)]),
+            M::V::from_vec(vec![x.clone(), x]),
+        )];
+        (problem, solns)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn get_exponential_problem<M>() -> (
+        SolverProblem<impl NonLinearOp<M = M, V = M::V, T = M::T>>,
+        Vec<NonLinearSolveSolution<M::V>>,
+    )
+    where
+        M: DenseMatrix + 'static,
+    {
+        let p = Rc::new(M::V::zeros(0));
+        let op = Closure::new(
+            // 0 = exp(x) + x / 2 - 1
+            move |x: &<M as MatrixCommon>::V,
+                  _p: &<M as MatrixCommon>::V,
+                  _t,
+                  y: &mut <M as MatrixCommon>::V| {
+                for i in 0..x.len() {
+                    let xi: M::T = x[i].clone();
+                    y[i] = ComplexField::exp(xi.clone()) + xi * M::T::cast(0.5) - M::T::one();
+                }
+            },
+            // J = (exp(x) + 1 / 2) * dx
+            move |x: &<M as MatrixCommon>::V,
+                  _p: &<M as MatrixCommon>::V,
+                  _t,
+                  v: &<M as MatrixCommon>::V,
+                  y: &mut <M as MatrixCommon>::V| {
+                for i in 0..x.len() {
+                    let xi: M::T = x[i].clone();
+                    y[i] = (ComplexField::exp(xi) + M::T::cast(0.5)) * v[i].clone();
+                }
+            },
+            2,
+            2,
+            p,
+        );
+        let rtol = M::T::cast(1e-6);
+        let atol = M::V::from_vec(vec![M::T::cast(1e-6), M::T::cast(1e-6)]);
         let problem = SolverProblem::new(Rc::new(op), Rc::new(atol), rtol);
+        // the jacobian at the initial guess is much smaller than at the solution, so the full Newton step overshoots
         let solns = vec![NonLinearSolveSolution::new(
-            M::V::from_vec(vec![2.1.into(), 2.1.into()]),
-            M::V::from_vec(vec![2.0.into(), 2.0.into()]),
+            M::V::from_vec(vec![M::T::cast(-2.0), M::T::cast(-2.0)]),
+            M::V::from_vec(vec![M::T::cast(0.0), M::T::cast(0.0)]),
         )];
         (problem, solns)
     }
@@ -112,8 +301,8 @@ pub mod tests {
         solver.set_problem(&problem);
         let t = C::T::zero();
         for soln in solns {
-            let x = solver.solve(&soln.x0, t).unwrap();
-            let tol = x.clone() * scale(problem.rtol) + problem.atol.as_ref();
+            let x = solver.solve(&soln.x0, t.clone()).unwrap();
+            let tol = x.clone() * scale(problem.rtol.clone()) + problem.atol.as_ref();
             x.assert_eq(&soln.x, &tol);
         }
     }
@@ -127,4 +316,140 @@ pub mod tests {
         let s = NewtonNonlinearSolver::new(lu);
         test_nonlinear_solver(s, prob, soln);
     }
+
+    #[test]
+    fn test_newton_min_rcond() {
+        for (min_rcond, expect) in [(0.0, 1), (2.0, 2)] {
+            let (prob, soln) = get_square_problem::<MCpu>();
+            let f = prob.f.clone();
+            let mut s = NewtonNonlinearSolver::new(LU::default());
+            s.set_min_rcond(min_rcond);
+            s.set_problem(&prob);
+            for _ in 0..2 {
+                s.solve(&soln[0].x0, 0.0).unwrap();
+            }
+            assert!(s.linear_solver_report().rcond.is_some());
+            // the jacobian is only recalculated when its condition estimate is below min_rcond
+            assert_eq!(f.statistics().number_of_matrix_evals, expect);
+        }
+    }
+
+    #[test]
+    fn test_newton_statistics() {
+        let (prob, soln) = get_square_problem::<MCpu>();
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_problem(&prob);
+        let mut niter = 0;
+        for _ in 0..2 {
+            s.solve(&soln[0].x0, 0.0).unwrap();
+            niter += s.niter();
+        }
+        let stats = s.get_statistics();
+        assert_eq!(stats.number_of_solves, 2);
+        assert_eq!(stats.number_of_iterations, niter);
+        assert_eq!(stats.number_of_failures, 0);
+        // the jacobian is only calculated for the first solve
+        assert_eq!(stats.number_of_jacobian_updates, 1);
+
+        // failed solves are counted, including their iterations
+        let (prob, soln) = get_exponential_problem::<MCpu>();
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_problem(&prob);
+        assert!(s.solve(&soln[0].x0, 0.0).is_err());
+        let stats = s.get_statistics();
+        assert_eq!(stats.number_of_solves, 1);
+        assert_eq!(stats.number_of_failures, 1);
+        assert!(stats.number_of_iterations > 0);
+    }
+
+    #[test]
+    fn test_newton_gmres_matrix_free() {
+        let (prob, soln) = get_square_problem::<MCpu>();
+        let f = prob.f.clone();
+        let mut gmres = Gmres::default();
+        gmres.finite_difference = true;
+        gmres.tol = 1e-6;
+        let s = NewtonNonlinearSolver::new(gmres);
+        test_nonlinear_solver(s, prob, soln);
+        // only calls to the operator are used, the jacobian is never formed
+        assert_eq!(f.statistics().number_of_matrix_evals, 0);
+        assert_eq!(f.statistics().number_of_jac_muls, 0);
+    }
+
+    #[test]
+    fn test_newton_line_search() {
+        // the full Newton step overshoots into a region where the residual is larger, and the iteration diverges
+        let (prob, soln) = get_exponential_problem::<MCpu>();
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_problem(&prob);
+        assert!(s.solve(&soln[0].x0, 0.0).is_err());
+
+        // backtracking shortens the step until the residual decreases
+        let mut s = NewtonNonlinearSolver::new(LU::default());
+        s.set_options(NewtonOptions {
+            line_search: LineSearch::backtracking(),
+        });
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_dogleg() {
+        let (prob, soln) = get_square_problem::<MCpu>();
+        let s = DoglegSolver::new(LU::default());
+        test_nonlinear_solver(s, prob, soln);
+
+        // converges from the initial guess for which the newton iteration diverges
+        let (prob, soln) = get_exponential_problem::<MCpu>();
+        let s = DoglegSolver::new(LU::default());
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_fixed_point_cpu_contraction() {
+        let (prob, soln) = get_contraction_problem::<MCpu>();
+        let s = FixedPointNonlinearSolver::new();
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_anderson_cpu_contraction() {
+        let (prob, soln) = get_contraction_problem::<MCpu>();
+        let mut fixed_point = FixedPointNonlinearSolver::new();
+        fixed_point.set_problem(&prob);
+        fixed_point.solve(&soln[0].x0, 0.0).unwrap();
+
+        let mut s = AndersonSolver::new();
+        s.set_problem(&prob);
+        s.solve(&soln[0].x0, 0.0).unwrap();
+        assert!(
+            s.niter() < fixed_point.niter(),
+            "{} iterations with Anderson acceleration, {} without",
+            s.niter(),
+            fixed_point.niter()
+        );
+        test_nonlinear_solver(s, prob, soln);
+    }
+
+    #[test]
+    fn test_broyden_cpu_contraction() {
+        let (prob, soln) = get_contraction_problem::<MCpu>();
+        let mut fixed_point = FixedPointNonlinearSolver::new();
+        fixed_point.set_problem(&prob);
+        fixed_point.solve(&soln[0].x0, 0.0).unwrap();
+
+        let mut s = BroydenSolver::new();
+        s.set_problem(&prob);
+        s.solve(&soln[0].x0, 0.0).unwrap();
+        assert!(
+            s.niter() < fixed_point.niter(),
+            "{} iterations with Broyden's method, {} with fixed-point iteration",
+            s.niter(),
+            fixed_point.niter()
+        );
+        // the approximate jacobian is kept for the next solve, and discarded when the jacobian is reset
+        assert!(s.rank() > 0);
+        s.reset_jacobian(&soln[0].x0, 0.0);
+        assert_eq!(s.rank(), 0);
+        test_nonlinear_solver(s, prob, soln);
+    }
 }
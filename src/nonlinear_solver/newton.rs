@@ -1,6 +1,11 @@
+use num_traits::{One, Zero};
+
 use crate::{
-    errors::PSError, op::NonLinearOp, Convergence, ConvergenceStatus, LinearSolver,
-    NonLinearSolver, SolverProblem, Vector,
+    errors::PSError,
+    linear_solver::{LinearSolverReport, LinearSolverStatistics},
+    op::{NonLinearOp, Op},
+    scale, Constraints, Convergence, ConvergenceStatus, JacobianUpdatePolicy, LinearSolver,
+    NonLinearSolver, NonLinearSolverStatistics, Scalar, SolverProblem, Vector,
 };
 
 pub fn newton_iteration<V: Vector>(
@@ -34,13 +39,212 @@ pub fn newton_iteration<V: Vector>(
     Err(PSError::MaxIterReached)
 }
 
+/// The same iteration as [newton_iteration], but the linear solver is refactorised at the current iterate before every solve,
+/// i.e. the full rather than the modified Newton method (see [JacobianUpdatePolicy::EveryIteration]).
+pub fn full_newton_iteration<C: Op, LS: LinearSolver<C>>(
+    xn: &mut C::V,
+    t: C::T,
+    fun: impl Fn(&C::V, &mut C::V),
+    linear_solver: &mut LS,
+    convergence: &mut Convergence<C::V>,
+    constraints: Option<&Constraints<C::V>>,
+) -> Result<usize, PSError> {
+    convergence.reset();
+    if let Some(constraints) = constraints {
+        constraints.clip(xn);
+    }
+    let mut tmp = xn.clone();
+    let mut niter = 0;
+    loop {
+        niter += 1;
+        linear_solver.refactor(xn, t.clone());
+        fun(xn, &mut tmp);
+        linear_solver.solve_in_place(&mut tmp)?;
+        let lambda = constrained_step(xn, &tmp, constraints)?;
+        xn.axpy(-lambda, &tmp, C::T::one());
+        if let Some(constraints) = constraints {
+            constraints.clip(xn);
+        }
+
+        let res = convergence.check_new_iteration(&mut tmp, xn);
+        match res {
+            ConvergenceStatus::Continue => continue,
+            ConvergenceStatus::Converged => return Ok(niter),
+            ConvergenceStatus::Diverged => break,
+            ConvergenceStatus::MaximumIterations => break,
+        }
+    }
+    Err(PSError::MaxIterReached)
+}
+
+/// The fraction of the Newton step `x - delta` that keeps `x` within the constraints (if any), or an error if no step can be taken
+/// because a component on its bound is pushed out of the bounds.
+fn constrained_step<V: Vector>(
+    x: &V,
+    delta: &V,
+    constraints: Option<&Constraints<V>>,
+) -> Result<V::T, PSError> {
+    let constraints = match constraints {
+        Some(constraints) => constraints,
+        None => return Ok(V::T::one()),
+    };
+    let mut dx = delta.clone();
+    dx *= scale(-V::T::one());
+    let lambda = constraints.max_step(x, &dx);
+    if lambda <= V::T::zero() {
+        return Err(PSError::ConstraintsNotSatisfied);
+    }
+    Ok(lambda)
+}
+
+/// The line search applied to each step of the [NewtonNonlinearSolver] (see [NewtonOptions]).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineSearch<T> {
+    /// Always take the full Newton step (the default)
+    None,
+    /// Armijo backtracking on the norm of the residual `F`: starting from the full step `d`, the step `x + λ d` is accepted once
+    /// `|F(x + λ d)| <= (1 - sufficient_decrease λ) |F(x)|`, otherwise `λ` is multiplied by `contraction`, up to `max_iter` times
+    /// (after which the last step is taken regardless).
+    Backtracking {
+        sufficient_decrease: T,
+        contraction: T,
+        max_iter: usize,
+    },
+}
+
+impl<T: Scalar> LineSearch<T> {
+    /// Armijo backtracking with the usual parameters: a sufficient decrease of `1e-4`, halving the step up to 10 times
+    pub fn backtracking() -> Self {
+        Self::Backtracking {
+            sufficient_decrease: T::cast(1e-4),
+            contraction: T::cast(0.5),
+            max_iter: 10,
+        }
+    }
+}
+
+/// Options for the [NewtonNonlinearSolver]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NewtonOptions<T> {
+    /// The line search applied to each Newton step (default [LineSearch::None])
+    pub line_search: LineSearch<T>,
+}
+
+impl<T> Default for NewtonOptions<T> {
+    fn default() -> Self {
+        Self {
+            line_search: LineSearch::None,
+        }
+    }
+}
+
+/// The same iteration as [newton_iteration], but each step is globalised using the given [LineSearch], and if the linear solver
+/// supports it, the linear solver is updated after each step with the secant condition `A s = y` (see [LinearSolver::secant_update]),
+/// where `s` is the step and `y` the resulting change in `fun`.
+///
+/// Convergence is tested on the full Newton step, rather than the (possibly shortened) step that is taken, so that a damped
+/// step is not mistaken for convergence.
+pub fn damped_newton_iteration<C: Op, LS: LinearSolver<C>>(
+    xn: &mut C::V,
+    fun: impl Fn(&C::V, &mut C::V),
+    linear_solver: &mut LS,
+    convergence: &mut Convergence<C::V>,
+    line_search: LineSearch<C::T>,
+    constraints: Option<&Constraints<C::V>>,
+) -> Result<usize, PSError> {
+    convergence.reset();
+    if let Some(constraints) = constraints {
+        constraints.clip(xn);
+    }
+    let secant_update = linear_solver.supports_secant_update();
+    let mut f = xn.clone();
+    let mut f_old = xn.clone();
+    let mut delta = xn.clone();
+    let mut step = xn.clone();
+    let mut x_trial = xn.clone();
+    let mut is_f_evaluated = false;
+    let mut niter = 0;
+    loop {
+        niter += 1;
+        if !is_f_evaluated {
+            fun(xn, &mut f);
+        }
+        //f = f_at_n
+
+        if secant_update {
+            if niter > 1 {
+                // f_old = f_at_n - f_at_n-1
+                f_old.axpy(C::T::one(), &f, -C::T::one());
+                linear_solver.secant_update(&step, &f_old);
+            }
+            f_old.copy_from(&f);
+        }
+
+        delta.copy_from(&f);
+        linear_solver.solve_in_place(&mut delta)?;
+        //delta = -delta_n
+
+        let max_lambda = constrained_step(xn, &delta, constraints)?;
+        let lambda = match &line_search {
+            LineSearch::None => {
+                xn.axpy(-max_lambda.clone(), &delta, C::T::one());
+                is_f_evaluated = false;
+                max_lambda
+            }
+            LineSearch::Backtracking {
+                sufficient_decrease,
+                contraction,
+                max_iter,
+            } => {
+                let f_norm = f.norm();
+                let mut lambda = max_lambda;
+                for i in 0..=*max_iter {
+                    x_trial.copy_from(xn);
+                    x_trial.axpy(-lambda.clone(), &delta, C::T::one());
+                    fun(&x_trial, &mut f);
+                    // the comparison is false if the residual is not finite, so the step is shortened
+                    if f.norm()
+                        <= (C::T::one() - sufficient_decrease.clone() * lambda.clone())
+                            * f_norm.clone()
+                        || i == *max_iter
+                    {
+                        break;
+                    }
+                    lambda *= contraction.clone();
+                }
+                xn.copy_from(&x_trial);
+                is_f_evaluated = true;
+                lambda
+            }
+        };
+        // xn = xn + lambda * delta_n
+        if let Some(constraints) = constraints {
+            constraints.clip(xn);
+        }
+
+        step.axpy(-lambda, &delta, C::T::zero());
+        let res = convergence.check_new_iteration(&mut delta, xn);
+        match res {
+            ConvergenceStatus::Continue => continue,
+            ConvergenceStatus::Converged => return Ok(niter),
+            ConvergenceStatus::Diverged => break,
+            ConvergenceStatus::MaximumIterations => break,
+        }
+    }
+    Err(PSError::MaxIterReached)
+}
+
 pub struct NewtonNonlinearSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
     convergence: Option<Convergence<C::V>>,
     linear_solver: Ls,
     problem: Option<SolverProblem<C>>,
     max_iter: usize,
+    max_rate: C::T,
     niter: usize,
+    statistics: NonLinearSolverStatistics,
     is_jacobian_set: bool,
+    min_rcond: C::T,
+    options: NewtonOptions<C::T>,
 }
 
 impl<C: NonLinearOp, Ls: LinearSolver<C>> NewtonNonlinearSolver<C, Ls> {
@@ -50,10 +254,38 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NewtonNonlinearSolver<C, Ls> {
             convergence: None,
             linear_solver,
             max_iter: 100,
+            max_rate: C::T::one(),
             niter: 0,
+            statistics: NonLinearSolverStatistics::default(),
             is_jacobian_set: false,
+            min_rcond: C::T::zero(),
+            options: NewtonOptions::default(),
         }
     }
+
+    /// Set the options of the Newton iteration, e.g. a line search (see [NewtonOptions])
+    pub fn set_options(&mut self, options: NewtonOptions<C::T>) {
+        self.options = options;
+    }
+
+    pub fn options(&self) -> &NewtonOptions<C::T> {
+        &self.options
+    }
+
+    /// Set the smallest acceptable estimate of the reciprocal condition number of the Jacobian (default 0, i.e. disabled).
+    /// If the linear solver reports a smaller value (see [LinearSolver::report]), the Jacobian is recalculated at the current
+    /// point at the start of the next solve, rather than reusing a nearly singular factorisation.
+    pub fn set_min_rcond(&mut self, min_rcond: C::T) {
+        self.min_rcond = min_rcond;
+    }
+
+    pub fn min_rcond(&self) -> C::T {
+        self.min_rcond.clone()
+    }
+
+    fn is_jacobian_ill_conditioned(&self) -> bool {
+        matches!(self.linear_solver.report().rcond, Some(rcond) if rcond < self.min_rcond)
+    }
 }
 
 impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinearSolver<C, Ls> {
@@ -63,9 +295,27 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinear
     fn max_iter(&self) -> usize {
         self.max_iter
     }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.max_rate = max_rate;
+    }
+    fn max_rate(&self) -> C::T {
+        self.max_rate.clone()
+    }
     fn niter(&self) -> usize {
         self.niter
     }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.convergence.as_ref().and_then(|c| c.rate())
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.statistics
+    }
+    fn linear_solver_report(&self) -> LinearSolverReport<C::T> {
+        self.linear_solver.report()
+    }
+    fn linear_solver_statistics(&self) -> LinearSolverStatistics {
+        self.linear_solver.statistics()
+    }
     fn problem(&self) -> &SolverProblem<C> {
         self.problem
             .as_ref()
@@ -74,14 +324,28 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinear
     fn set_problem(&mut self, problem: &SolverProblem<C>) {
         self.problem = Some(problem.clone());
         self.linear_solver.set_problem(problem);
+        if !self.linear_solver.is_analyzed() {
+            self.linear_solver.analyze();
+        }
         let problem = self.problem.as_ref().unwrap();
-        self.convergence = Some(Convergence::new_from_problem(problem, self.max_iter));
+        let mut convergence = Convergence::new_from_problem(problem, self.max_iter);
+        convergence.set_max_rate(self.max_rate.clone());
+        self.convergence = Some(convergence);
+        self.is_jacobian_set = false;
+        self.statistics = NonLinearSolverStatistics::default();
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.convergence = None;
+        self.linear_solver.clear_problem();
         self.is_jacobian_set = false;
     }
 
     fn reset_jacobian(&mut self, x: &C::V, t: C::T) {
-        self.linear_solver.set_linearisation(x, t);
+        self.linear_solver.refactor(x, t);
         self.is_jacobian_set = true;
+        self.statistics.number_of_jacobian_updates += 1;
     }
 
     fn solve_linearised_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
@@ -92,17 +356,56 @@ impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for NewtonNonlinear
         if self.convergence.is_none() || self.problem.is_none() {
             panic!("NewtonNonlinearSolver::solve() called before set_problem");
         }
-        if !self.is_jacobian_set {
-            self.reset_jacobian(xn, t);
+        let full_newton = matches!(
+            self.problem.as_ref().unwrap().jacobian_update,
+            JacobianUpdatePolicy::EveryIteration
+        );
+        if !full_newton && (!self.is_jacobian_set || self.is_jacobian_ill_conditioned()) {
+            self.reset_jacobian(xn, t.clone());
         }
         if xn.len() != self.problem.as_ref().unwrap().f.nstates() {
             panic!("NewtonNonlinearSolver::solve() called with state of wrong size, expected {}, got {}", self.problem.as_ref().unwrap().f.nstates(), xn.len());
         }
-        let linear_solver = |x: &mut C::V| self.linear_solver.solve_in_place(x);
         let problem = self.problem.as_ref().unwrap();
-        let fun = |x: &C::V, y: &mut C::V| problem.f.call_inplace(x, t, y);
+        let fun = |x: &C::V, y: &mut C::V| problem.f.call_inplace(x, t.clone(), y);
+
         let convergence = self.convergence.as_mut().unwrap();
-        self.niter = newton_iteration(xn, fun, linear_solver, convergence)?;
+        let line_search = self.options.line_search.clone();
+        let constraints = problem.constraints.as_deref();
+        let result = if full_newton {
+            self.is_jacobian_set = true;
+            full_newton_iteration(
+                xn,
+                t.clone(),
+                fun,
+                &mut self.linear_solver,
+                convergence,
+                constraints,
+            )
+        } else if self.linear_solver.supports_secant_update()
+            || !matches!(line_search, LineSearch::None)
+            || constraints.is_some()
+        {
+            damped_newton_iteration(
+                xn,
+                fun,
+                &mut self.linear_solver,
+                convergence,
+                line_search,
+                constraints,
+            )
+        } else {
+            let linear_solver = |x: &mut C::V| self.linear_solver.solve_in_place(x);
+            newton_iteration(xn, fun, linear_solver, convergence)
+        };
+        let convergence = self.convergence.as_ref().unwrap();
+        if full_newton {
+            // the jacobian is recalculated at every iteration
+            self.statistics.number_of_jacobian_updates += convergence.niter();
+        }
+        self.statistics
+            .record_solve(convergence.niter(), convergence.rate(), result.is_ok());
+        self.niter = result?;
         Ok(())
     }
 }
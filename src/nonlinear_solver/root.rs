@@ -5,7 +5,9 @@ use crate::{
     scalar::{IndexType, Scalar},
     NonLinearOp, Vector,
 };
-use num_traits::{abs, One, Zero};
+use num_traits::{abs, Zero};
+
+use super::scalar::BrentSolver;
 
 pub struct RootFinder<V: Vector> {
     t0: RefCell<V::T>,
@@ -27,34 +29,39 @@ impl<V: Vector> RootFinder<V> {
     /// Set the lower boundary of the root search.
     /// This function should be called first after [Self::new]
     pub fn init(&self, root_fn: &impl NonLinearOp<V = V, T = V::T>, y: &V, t: V::T) {
-        root_fn.call_inplace(y, t, &mut self.g0.borrow_mut());
+        root_fn.call_inplace(y, t.clone(), &mut self.g0.borrow_mut());
         self.t0.replace(t);
     }
 
     /// Set the upper boundary of the root search and checks for a zero crossing.
-    /// If a zero crossing is found, the index of the crossing is returned
+    /// If a zero crossing is found, the index of the root function that crosses zero and the time of the crossing are returned.
+    /// If more than one root function crosses zero within the interval, the first crossing in time is returned.
     ///
     /// This function assumes that g0 and t0 have already beeen set via [Self::init]
     /// or previous iterations of [Self::check_root]
     ///
-    /// We find the root of a function using the method proposed by Sundials [docs](https://sundials.readthedocs.io/en/latest/cvode/Mathematics_link.html#rootfinding)
+    /// Sign changes are detected as proposed by Sundials [docs](https://sundials.readthedocs.io/en/latest/cvode/Mathematics_link.html#rootfinding),
+    /// and the time of the crossing is found using a [BrentSolver] on the interpolated solution.
+    /// Returns an error if the interpolation fails, or if the [BrentSolver] does not converge.
     pub fn check_root(
         &self,
         interpolate: &impl Fn(V::T) -> Result<V, PSError>,
         root_fn: &impl NonLinearOp<V = V, T = V::T>,
         y: &V,
         t: V::T,
-    ) -> Option<V::T> {
+    ) -> Result<Option<(IndexType, V::T)>, PSError> {
         let g1 = &mut *self.g1.borrow_mut();
         let g0 = &mut *self.g0.borrow_mut();
         let gmid = &mut *self.gmid.borrow_mut();
-        root_fn.call_inplace(y, t, g1);
+        root_fn.call_inplace(y, t.clone(), g1);
 
-        let sign_change_fn = |mut acc: (bool, V::T, i32), g0: V::T, g1: V::T, i: IndexType| {
+        let sign_change_fn = |mut acc: (i32, V::T, i32), g0: V::T, g1: V::T, i: IndexType| {
             if g1 == V::T::zero() {
-                acc.0 = true;
-            } else if g0 * g1 < V::T::zero() {
-                let gfrac = abs(g1 / (g1 - g0));
+                if acc.0 < 0 {
+                    acc.0 = i32::try_from(i).unwrap();
+                }
+            } else if g0.clone() * g1.clone() < V::T::zero() {
+                let gfrac = abs(g1.clone() / (g1 - g0));
                 if gfrac > acc.1 {
                     acc.1 = gfrac;
                     acc.2 = i32::try_from(i).unwrap();
@@ -62,97 +69,76 @@ impl<V: Vector> RootFinder<V> {
             }
             acc
         };
-        let (rootfnd, _gfracmax, imax) =
-            (*g0).binary_fold(g1, (false, V::T::zero(), -1), sign_change_fn);
+        let (izero, _gfracmax, imax) =
+            (*g0).binary_fold(g1, (-1, V::T::zero(), -1), sign_change_fn);
 
         // if no sign change we don't need to find the root
         if imax < 0 {
             // setup g0 for next iteration
             std::mem::swap(g0, g1);
-            self.t0.replace(t);
-            return if rootfnd {
+            self.t0.replace(t.clone());
+            return Ok(if izero >= 0 {
                 // found a root at the upper boundary and no other sign change, return the root
-                Some(t)
+                Some((IndexType::try_from(izero).unwrap(), t))
             } else {
                 // no root found or sign change, return None
                 None
-            };
+            });
         }
 
-        // otherwise we need to do the modified secant method to find the root
+        // otherwise find the root of the function with the largest relative change in sign using Brent's method, then check
+        // that none of the other functions crosses zero before it, in which case the search is repeated for that function
         let mut imax = IndexType::try_from(imax).unwrap();
-        let mut alpha = V::T::one();
-        let mut sign_change = [false, true];
-        let mut i = 0;
-        let mut t1 = t;
-        let mut t0 = *self.t0.borrow();
-        let tol = V::T::from(100.0) * V::T::EPSILON * (abs(t1) + abs(t1 - t0));
-        let half = V::T::from(0.5);
-        let double = V::T::from(2.0);
-        let five = V::T::from(5.0);
-        let pntone = V::T::from(0.1);
-        while abs(t1 - t0) > tol {
-            let mut t_mid = t1 - (t1 - t0) * g1[imax] / (g1[imax] - alpha * g0[imax]);
-
-            // adjust t_mid away from the boundaries
-            if abs(t_mid - t0) < half * tol {
-                let fracint = abs(t1 - t0) / tol;
-                let fracsub = if fracint > five {
-                    pntone
-                } else {
-                    half / fracint
-                };
-                t_mid = t0 + fracsub * (t1 - t0);
+        let mut t1 = t.clone();
+        let t0 = self.t0.borrow().clone();
+        let mut brent = BrentSolver::new();
+        brent.atol =
+            V::T::cast(100.0) * V::T::EPSILON * (abs(t1.clone()) + abs(t1.clone() - t0.clone()));
+        let root = loop {
+            // an interpolation error is returned after the solve, which it stops by reporting a root
+            let mut interpolate_err = None;
+            let t_root = brent.solve_bracketed(
+                |t_mid| match interpolate(t_mid.clone()) {
+                    Ok(ymid) => {
+                        root_fn.call_inplace(&ymid, t_mid, gmid);
+                        gmid[imax].clone()
+                    }
+                    Err(e) => {
+                        interpolate_err.get_or_insert(e);
+                        V::T::zero()
+                    }
+                },
+                (t0.clone(), g0[imax].clone()),
+                (t1.clone(), g1[imax].clone()),
+            );
+            if let Some(e) = interpolate_err {
+                return Err(e);
             }
-            if abs(t1 - t_mid) < half * tol {
-                let fracint = abs(t1 - t0) / tol;
-                let fracsub = if fracint > five {
-                    pntone
-                } else {
-                    half / fracint
-                };
-                t_mid = t1 - fracsub * (t1 - t0);
+            let t_root = t_root?;
+            if t_root == t1 {
+                break (imax, t_root);
             }
-
-            let ymid = interpolate(t_mid).unwrap();
-            root_fn.call_inplace(&ymid, t_mid, gmid);
-
-            let (rootfnd, _gfracmax, imax_i32) =
-                (*g0).binary_fold(gmid, (false, V::T::zero(), -1), sign_change_fn);
-            let lower = imax_i32 >= 0;
-
-            if lower {
-                // Sign change found in (tlo,tmid); replace thi with tmid.
-                t1 = t_mid;
+            let ymid = interpolate(t_root.clone())?;
+            root_fn.call_inplace(&ymid, t_root.clone(), gmid);
+            let gmax = gmid[imax].clone();
+            gmid[imax] = g0[imax].clone();
+            let (izero, _gfracmax, imax_i32) =
+                (*g0).binary_fold(gmid, (-1, V::T::zero(), -1), sign_change_fn);
+            gmid[imax] = gmax;
+            if imax_i32 >= 0 {
+                // another function crosses zero in (t0, t_root), so search for its root in the smaller interval
+                t1 = t_root;
                 imax = IndexType::try_from(imax_i32).unwrap();
                 std::mem::swap(g1, gmid);
-            } else if rootfnd {
-                // we are returning so make sure g0 is set for next iteration
-                root_fn.call_inplace(y, t, g0);
-
-                // No sign change in (tlo,tmid), but g = 0 at tmid; return root tmid.
-                return Some(t_mid);
+            } else if izero >= 0 {
+                break (IndexType::try_from(izero).unwrap(), t_root);
             } else {
-                // No sign change in (tlo,tmid), and no zero at tmid. Sign change must be in (tmid,thi).  Replace tlo with tmid.
-                t0 = t_mid;
-                std::mem::swap(g0, gmid);
+                break (imax, t_root);
             }
-
-            sign_change[i % 2] = lower;
-            if i >= 2 {
-                alpha = if sign_change[0] != sign_change[1] {
-                    V::T::one()
-                } else if sign_change[0] {
-                    half * alpha
-                } else {
-                    double * alpha
-                };
-            }
-            i += 1;
-        }
+        };
         // we are returning so make sure g0 is set for next iteration
         root_fn.call_inplace(y, t, g0);
-        Some(t1)
+        Ok(Some(root))
     }
 }
 
@@ -179,19 +165,84 @@ mod tests {
         // check no root
         let root_finder = RootFinder::new(1);
         root_finder.init(&root_fn, &Vector::from_vec(vec![0.0]), 0.0);
-        let root =
-            root_finder.check_root(&interpolate, &root_fn, &Vector::from_vec(vec![0.3]), 0.3);
+        let root = root_finder
+            .check_root(&interpolate, &root_fn, &Vector::from_vec(vec![0.3]), 0.3)
+            .unwrap();
         assert_eq!(root, None);
 
         // check root
         let root_finder = RootFinder::new(1);
         root_finder.init(&root_fn, &Vector::from_vec(vec![0.0]), 0.0);
-        let root =
-            root_finder.check_root(&interpolate, &root_fn, &Vector::from_vec(vec![1.3]), 1.3);
-        if let Some(root) = root {
+        let root = root_finder
+            .check_root(&interpolate, &root_fn, &Vector::from_vec(vec![1.3]), 1.3)
+            .unwrap();
+        if let Some((index, root)) = root {
+            assert_eq!(index, 0);
+            assert!((root - 0.4).abs() < 1e-10);
+        } else {
+            unreachable!();
+        }
+    }
+
+    #[test]
+    fn test_root_index() {
+        type V = nalgebra::DVector<f64>;
+        type M = nalgebra::DMatrix<f64>;
+        let interpolate = |t: f64| -> Result<V, PSError> { Ok(Vector::from_vec(vec![t])) };
+        let root_fn = ClosureNoJac::<M, _>::new(
+            |y: &V, _p: &V, _t: f64, g: &mut V| {
+                g[0] = y[0] - 0.8;
+                g[1] = y[0] - 0.4;
+                g[2] = y[0] - 1.0;
+            },
+            1,
+            3,
+            Rc::new(V::zeros(0)),
+        );
+
+        // both the first and second root function cross zero, the earliest crossing is returned
+        let root_finder = RootFinder::new(3);
+        root_finder.init(&root_fn, &Vector::from_vec(vec![0.0]), 0.0);
+        let root = root_finder
+            .check_root(&interpolate, &root_fn, &Vector::from_vec(vec![0.9]), 0.9)
+            .unwrap();
+        if let Some((index, root)) = root {
+            assert_eq!(index, 1);
             assert!((root - 0.4).abs() < 1e-10);
         } else {
             unreachable!();
         }
+
+        // a root function that is exactly zero at the end of the interval
+        let root = root_finder
+            .check_root(&interpolate, &root_fn, &Vector::from_vec(vec![1.0]), 1.0)
+            .unwrap();
+        assert_eq!(root, Some((2, 1.0)));
+    }
+
+    #[test]
+    fn test_root_interpolation_error() {
+        type V = nalgebra::DVector<f64>;
+        type M = nalgebra::DMatrix<f64>;
+        let interpolate =
+            |_t: f64| -> Result<V, PSError> { Err(PSError::InterpolationOutsideCurrentStep) };
+        let root_fn = ClosureNoJac::<M, _>::new(
+            |y: &V, _p: &V, _t: f64, g: &mut V| {
+                g[0] = y[0] - 0.4;
+            },
+            1,
+            1,
+            Rc::new(V::zeros(0)),
+        );
+
+        // the error from interpolating within the step is returned instead of a root
+        let root_finder = RootFinder::new(1);
+        root_finder.init(&root_fn, &Vector::from_vec(vec![0.0]), 0.0);
+        let root =
+            root_finder.check_root(&interpolate, &root_fn, &Vector::from_vec(vec![1.3]), 1.3);
+        assert!(matches!(
+            root,
+            Err(PSError::InterpolationOutsideCurrentStep)
+        ));
     }
 }
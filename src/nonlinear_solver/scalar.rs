@@ -0,0 +1,189 @@
+use num_traits::abs;
+
+use crate::{errors::PSError, Scalar};
+
+/// A solver for the scalar equation `f(x) = 0` on an interval `[a, b]` where `f(a)` and `f(b)` have opposite signs,
+/// using Brent's method.
+///
+/// Each iteration takes an inverse quadratic interpolation or secant step when it lies within the current bracket and reduces
+/// it quickly enough, and a bisection step otherwise, so the root is always bracketed and the convergence is superlinear for
+/// smooth functions while never being slower than bisection. This is used to locate the zero crossings of the root functions
+/// of an ODE on the interpolant of the solution (see [crate::OdeSolverStopReason::RootFound]), and can be used directly for
+/// other one-dimensional problems, e.g. finding the time at which a solution reaches a given value.
+///
+/// The iteration stops once the bracket is shorter than `4 * EPSILON * |x| + atol`, where `x` is the current estimate of the root.
+pub struct BrentSolver<T: Scalar> {
+    /// Absolute tolerance on the root (default 0, i.e. the root is found to machine precision)
+    pub atol: T,
+    /// Maximum number of evaluations of `f` in addition to those at the ends of the interval (default 100)
+    pub max_iter: usize,
+    niter: usize,
+}
+
+impl<T: Scalar> Default for BrentSolver<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> BrentSolver<T> {
+    pub fn new() -> Self {
+        Self {
+            atol: T::zero(),
+            max_iter: 100,
+            niter: 0,
+        }
+    }
+
+    /// Number of evaluations of `f` (in addition to those at the ends of the interval) taken by the last solve
+    pub fn niter(&self) -> usize {
+        self.niter
+    }
+
+    /// Find a root of `f` in the interval between `a` and `b`, returning [PSError::RootNotBracketed] if `f(a)` and `f(b)`
+    /// have the same sign.
+    pub fn solve(&mut self, mut f: impl FnMut(T) -> T, a: T, b: T) -> Result<T, PSError> {
+        let fa = f(a.clone());
+        let fb = f(b.clone());
+        self.solve_bracketed(f, (a, fa), (b, fb))
+    }
+
+    /// Find a root of `f` in the interval between `a` and `b`, given the values `fa = f(a)` and `fb = f(b)`, which avoids
+    /// re-evaluating `f` at the ends of the interval if these are already known.
+    pub fn solve_bracketed(
+        &mut self,
+        mut f: impl FnMut(T) -> T,
+        (a, fa): (T, T),
+        (b, fb): (T, T),
+    ) -> Result<T, PSError> {
+        self.niter = 0;
+        if fa == T::zero() {
+            return Ok(a);
+        }
+        if fb == T::zero() {
+            return Ok(b);
+        }
+        if (fa > T::zero()) == (fb > T::zero()) {
+            return Err(PSError::RootNotBracketed);
+        }
+        let two = T::cast(2.0);
+        let three = T::cast(3.0);
+        let half = T::cast(0.5);
+
+        // b is the current estimate of the root, with the root bracketed by b and c, and a is the previous estimate
+        let (mut a, mut fa, mut b, mut fb) = (a, fa, b, fb);
+        let (mut c, mut fc) = (a.clone(), fa.clone());
+        let mut d = b.clone() - a.clone();
+        let mut e = d.clone();
+        loop {
+            if (fb > T::zero()) == (fc > T::zero()) {
+                c = a.clone();
+                fc = fa.clone();
+                d = b.clone() - a.clone();
+                e = d.clone();
+            }
+            if abs(fc.clone()) < abs(fb.clone()) {
+                a = b;
+                b = c;
+                c = a.clone();
+                fa = fb;
+                fb = fc;
+                fc = fa.clone();
+            }
+            let tol = two.clone() * T::EPSILON * abs(b.clone()) + half.clone() * self.atol.clone();
+            let m = half.clone() * (c.clone() - b.clone());
+            if abs(m.clone()) <= tol || fb == T::zero() {
+                return Ok(b);
+            }
+            if self.niter >= self.max_iter {
+                return Err(PSError::MaxIterReached);
+            }
+
+            if abs(e.clone()) >= tol && abs(fa.clone()) > abs(fb.clone()) {
+                // secant step if only two distinct points are known, inverse quadratic interpolation otherwise
+                let s = fb.clone() / fa.clone();
+                let (mut p, mut q) = if a == c {
+                    (two.clone() * m.clone() * s.clone(), T::one() - s)
+                } else {
+                    let q = fa / fc.clone();
+                    let r = fb.clone() / fc.clone();
+                    (
+                        s.clone()
+                            * (two.clone() * m.clone() * q.clone() * (q.clone() - r.clone())
+                                - (b.clone() - a) * (r.clone() - T::one())),
+                        (q - T::one()) * (r - T::one()) * (s - T::one()),
+                    )
+                };
+                if p > T::zero() {
+                    q = -q;
+                } else {
+                    p = -p;
+                }
+                // only accept the interpolated step if it stays within the bracket and the steps are decreasing fast enough
+                let min = three.clone() * m.clone() * q.clone() - abs(tol.clone() * q.clone());
+                let min = if min < abs(e.clone() * q.clone()) {
+                    min
+                } else {
+                    abs(e * q.clone())
+                };
+                if two.clone() * p.clone() < min {
+                    e = d;
+                    d = p / q;
+                } else {
+                    d = m.clone();
+                    e = d.clone();
+                }
+            } else {
+                d = m.clone();
+                e = d.clone();
+            }
+
+            a = b.clone();
+            fa = fb;
+            if abs(d.clone()) > tol {
+                b += d.clone();
+            } else if m > T::zero() {
+                b += tol;
+            } else {
+                b -= tol;
+            }
+            fb = f(b.clone());
+            self.niter += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::errors::PSError;
+
+    use super::BrentSolver;
+
+    #[test]
+    fn test_brent() {
+        let mut solver = BrentSolver::new();
+        let root = solver.solve(|x: f64| x * x - 2.0, 0.0, 2.0).unwrap();
+        assert!((root - 2.0f64.sqrt()).abs() < 1e-14);
+        assert!(solver.niter() < 10, "{} iterations", solver.niter());
+
+        // the interval can be given in either order, and roots at the ends of the interval are found without iterating
+        let root = solver.solve(|x: f64| x.cos() - x, 1.0, 0.0).unwrap();
+        assert!((root.cos() - root).abs() < 1e-14);
+        assert_eq!(solver.solve(|x: f64| x - 1.0, 0.0, 1.0).unwrap(), 1.0);
+        assert_eq!(solver.niter(), 0);
+
+        // a loose tolerance takes fewer iterations
+        let niter = solver
+            .solve(|x: f64| x.powi(3) - 0.5, 0.0, 1.0)
+            .map(|_| solver.niter());
+        solver.atol = 1e-3;
+        let root = solver.solve(|x: f64| x.powi(3) - 0.5, 0.0, 1.0).unwrap();
+        assert!((root - 0.5f64.cbrt()).abs() < 1e-3);
+        assert!(solver.niter() <= niter.unwrap());
+
+        assert!(matches!(
+            solver.solve(|x: f64| x * x + 1.0, -1.0, 1.0),
+            Err(PSError::RootNotBracketed)
+        ));
+    }
+}
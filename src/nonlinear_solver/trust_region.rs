@@ -0,0 +1,253 @@
+use nalgebra::{ComplexField, RealField};
+use num_traits::{One, Zero};
+
+use crate::{
+    errors::PSError,
+    linear_solver::{LinearSolverReport, LinearSolverStatistics},
+    matrix::sparsity::MatrixSparsityRef,
+    op::NonLinearOp,
+    Convergence, ConvergenceStatus, LinearSolver, Matrix, NonLinearSolver,
+    NonLinearSolverStatistics, Scalar, SolverProblem, Vector,
+};
+
+/// A nonlinear solver that uses Powell's dogleg trust-region method to minimise `|F(x)|^2`.
+///
+/// At each iteration the jacobian is recalculated at the current point, and the step is chosen along the "dogleg" path from the
+/// steepest descent (Cauchy) step to the Newton step, with a length limited by the trust region radius. The radius is shrunk
+/// whenever the step does not reduce `|F|` as predicted by the linearisation, and grown when the prediction is good, so
+/// unlike the [crate::NewtonNonlinearSolver] the iteration converges from poor initial guesses, as long as the jacobian
+/// is not singular along the way. Close to the solution the Newton step is always taken, and the convergence is quadratic.
+///
+/// Each iteration is more expensive than a Newton iteration, since the jacobian is factorised at every iteration and also
+/// formed as a matrix to calculate the steepest descent direction `J^T F`, so this is mainly useful for one-off solves
+/// such as the consistent initialisation of a DAE (see [crate::OdeBuilder::initialisation_solver]), rather than for the
+/// nonlinear stages of the implicit ODE solvers.
+pub struct DoglegSolver<C: NonLinearOp, Ls: LinearSolver<C>> {
+    /// Initial radius of the trust region, relative to `max(|x0|, 1)` for the initial guess `x0` (default 100)
+    pub initial_radius: C::T,
+    convergence: Option<Convergence<C::V>>,
+    linear_solver: Ls,
+    problem: Option<SolverProblem<C>>,
+    jacobian: Option<C::M>,
+    max_iter: usize,
+    max_rate: C::T,
+    niter: usize,
+    statistics: NonLinearSolverStatistics,
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> DoglegSolver<C, Ls> {
+    pub fn new(linear_solver: Ls) -> Self {
+        Self {
+            initial_radius: C::T::cast(100.0),
+            problem: None,
+            convergence: None,
+            linear_solver,
+            jacobian: None,
+            max_iter: 100,
+            max_rate: C::T::one(),
+            niter: 0,
+            statistics: NonLinearSolverStatistics::default(),
+        }
+    }
+
+    fn iterate(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        if self.convergence.is_none() || self.problem.is_none() {
+            panic!("DoglegSolver::solve() called before set_problem");
+        }
+        let problem = self.problem.as_ref().unwrap();
+        if xn.len() != problem.f.nstates() {
+            panic!(
+                "DoglegSolver::solve() called with state of wrong size, expected {}, got {}",
+                problem.f.nstates(),
+                xn.len()
+            );
+        }
+        let jac = self.jacobian.as_mut().unwrap();
+        let convergence = self.convergence.as_mut().unwrap();
+        convergence.reset();
+
+        let mut f = problem.f.call(xn, t.clone());
+        let mut f_trial = f.clone();
+        let mut newton = f.clone();
+        let mut grad = f.clone();
+        let mut jac_grad = f.clone();
+        let mut dogleg = f.clone();
+        let mut step = f.clone();
+        let mut x_trial = xn.clone();
+        let mut radius = self.initial_radius.clone() * xn.norm().max(C::T::one());
+        let quarter = C::T::cast(0.25);
+        self.niter = 0;
+        loop {
+            // linearise at the current point, the newton step is -J^{-1} F
+            self.linear_solver.set_linearisation(xn, t.clone());
+            problem.f.jacobian_inplace(xn, t.clone(), jac);
+            self.statistics.number_of_jacobian_updates += 1;
+            newton.copy_from(&f);
+            let has_newton =
+                self.linear_solver.solve_in_place(&mut newton).is_ok() && newton.norm().is_finite();
+            let newton_norm = newton.norm();
+
+            // the steepest descent direction of |F|^2 / 2 is -J^T F, and the Cauchy step minimises the linearisation along it
+            grad.fill(C::T::zero());
+            for (i, j, v) in jac.triplet_iter() {
+                grad[j] += v.clone() * f[i].clone();
+            }
+            jac.gemv(C::T::one(), &grad, C::T::zero(), &mut jac_grad);
+            let grad_norm = grad.norm();
+            let jac_grad_norm = jac_grad.norm();
+            if !has_newton && (grad_norm.is_zero() || jac_grad_norm.is_zero()) {
+                return Err(PSError::LuFailed);
+            }
+            let cauchy =
+                grad_norm.clone() * grad_norm.clone() / (jac_grad_norm.clone() * jac_grad_norm);
+
+            // shrink the trust region until the step reduces |F|
+            let mut is_newton_step;
+            loop {
+                self.niter += 1;
+                is_newton_step = has_newton && newton_norm <= radius;
+                if is_newton_step {
+                    step.axpy(-C::T::one(), &newton, C::T::zero());
+                } else if !has_newton || cauchy.clone() * grad_norm.clone() >= radius {
+                    step.axpy(-radius.clone() / grad_norm.clone(), &grad, C::T::zero());
+                } else {
+                    // the point on the segment from the Cauchy step to the newton step at distance `radius`
+                    dogleg.copy_from(&grad);
+                    dogleg.axpy(-C::T::one(), &newton, cauchy.clone());
+                    let a = dogleg.dot(&dogleg);
+                    let b = -C::T::cast(2.0) * cauchy.clone() * grad.dot(&dogleg);
+                    let c = cauchy.clone() * cauchy.clone() * grad_norm.clone() * grad_norm.clone()
+                        - radius.clone() * radius.clone();
+                    let tau = (-b.clone()
+                        + (b.clone() * b - C::T::cast(4.0) * a.clone() * c).sqrt())
+                        / (C::T::cast(2.0) * a);
+                    step.axpy(-cauchy.clone(), &grad, C::T::zero());
+                    step.axpy(tau, &dogleg, C::T::one());
+                }
+                let step_norm = step.norm();
+
+                x_trial.copy_from(xn);
+                x_trial += &step;
+                problem.f.call_inplace(&x_trial, t.clone(), &mut f_trial);
+
+                // ratio of the actual to the predicted reduction in |F|^2
+                let f_norm2 = f.dot(&f);
+                let actual = f_norm2.clone() - f_trial.dot(&f_trial);
+                dogleg.copy_from(&f);
+                jac.gemv(C::T::one(), &step, C::T::one(), &mut dogleg);
+                let predicted = f_norm2 - dogleg.dot(&dogleg);
+                let rho = if predicted > C::T::zero() {
+                    actual / predicted
+                } else {
+                    -C::T::one()
+                };
+
+                // a residual that is not finite gives a ratio that is not finite, so the trust region is shrunk
+                if !rho.is_finite() || rho < quarter {
+                    radius = quarter.clone() * step_norm;
+                } else if rho > C::T::cast(0.75) && step_norm >= C::T::cast(0.99) * radius.clone() {
+                    radius *= C::T::cast(2.0);
+                }
+                if rho > C::T::cast(1e-4) {
+                    xn.copy_from(&x_trial);
+                    std::mem::swap(&mut f, &mut f_trial);
+                    break;
+                }
+                if self.niter >= self.max_iter
+                    || radius <= C::T::EPSILON * xn.norm().max(C::T::one())
+                {
+                    return Err(PSError::MaxIterReached);
+                }
+            }
+
+            // close to the solution the newton step is taken, and convergence is tested as for the newton iteration
+            if is_newton_step {
+                if let ConvergenceStatus::Converged = convergence.check_new_iteration(&mut step, xn)
+                {
+                    return Ok(());
+                }
+            } else {
+                convergence.reset();
+            }
+            if self.niter >= self.max_iter {
+                return Err(PSError::MaxIterReached);
+            }
+        }
+    }
+}
+
+impl<C: NonLinearOp, Ls: LinearSolver<C>> NonLinearSolver<C> for DoglegSolver<C, Ls> {
+    fn set_max_iter(&mut self, max_iter: usize) {
+        self.max_iter = max_iter;
+    }
+    fn max_iter(&self) -> usize {
+        self.max_iter
+    }
+    fn set_max_rate(&mut self, max_rate: C::T) {
+        self.max_rate = max_rate;
+    }
+    fn max_rate(&self) -> C::T {
+        self.max_rate.clone()
+    }
+    fn niter(&self) -> usize {
+        self.niter
+    }
+    fn convergence_rate(&self) -> Option<C::T> {
+        self.convergence.as_ref().and_then(|c| c.rate())
+    }
+    fn get_statistics(&self) -> NonLinearSolverStatistics {
+        self.statistics
+    }
+    fn linear_solver_report(&self) -> LinearSolverReport<C::T> {
+        self.linear_solver.report()
+    }
+    fn linear_solver_statistics(&self) -> LinearSolverStatistics {
+        self.linear_solver.statistics()
+    }
+    fn problem(&self) -> &SolverProblem<C> {
+        self.problem
+            .as_ref()
+            .expect("DoglegSolver::problem() called before set_problem")
+    }
+    fn set_problem(&mut self, problem: &SolverProblem<C>) {
+        self.problem = Some(problem.clone());
+        self.statistics = NonLinearSolverStatistics::default();
+        self.linear_solver.set_problem(problem);
+        if !self.linear_solver.is_analyzed() {
+            self.linear_solver.analyze();
+        }
+        let problem = self.problem.as_ref().unwrap();
+        self.jacobian = Some(C::M::new_from_sparsity(
+            problem.f.nout(),
+            problem.f.nstates(),
+            problem.f.sparsity().map(|s| s.to_owned()),
+        ));
+        let mut convergence = Convergence::new_from_problem(problem, self.max_iter);
+        convergence.set_max_rate(self.max_rate.clone());
+        self.convergence = Some(convergence);
+    }
+
+    fn clear_problem(&mut self) {
+        self.problem = None;
+        self.convergence = None;
+        self.jacobian = None;
+        self.linear_solver.clear_problem();
+    }
+
+    fn reset_jacobian(&mut self, x: &C::V, t: C::T) {
+        self.linear_solver.refactor(x, t);
+        self.statistics.number_of_jacobian_updates += 1;
+    }
+
+    fn solve_linearised_in_place(&self, x: &mut C::V) -> Result<(), PSError> {
+        self.linear_solver.solve_in_place(x)
+    }
+
+    fn solve_in_place(&mut self, xn: &mut C::V, t: C::T) -> Result<(), PSError> {
+        let result = self.iterate(xn, t);
+        let rate = self.convergence_rate();
+        self.statistics
+            .record_solve(self.niter, rate, result.is_ok());
+        result
+    }
+}
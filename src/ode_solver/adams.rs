@@ -0,0 +1,753 @@
+use nalgebra::ComplexField;
+use std::ops::MulAssign;
+use std::rc::Rc;
+
+use num_traits::{One, Pow, Zero};
+
+use super::bdf::{compute_r, update_diff, update_diff_for_step_size, BdfStatistics};
+use super::equations::OdeEquations;
+use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::{
+    matrix::MatrixRef, nonlinear_solver::root::RootFinder, op::bdf::BdfCallable,
+    scalar::compensated_add, scale, vector::DefaultDenseMatrix, DenseMatrix,
+    FixedPointNonlinearSolver, IndexType, NonLinearOp, NonLinearSolver, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, Scalar, SolverCapabilities,
+    SolverProblem, Vector, VectorRef, VectorView, VectorViewMut,
+};
+
+/// Implements a variable order, variable step Adams-Bashforth / Adams-Moulton multistep integrator for non-stiff problems.
+///
+/// The solver stores the backward differences of the rhs `f` at the previous time points, and shares the difference-array
+/// machinery (step size changes via the R and U matrices of \[1\]) with [crate::Bdf]. At order `k` each step
+///
+/// - predicts the new solution using the `k`th order Adams-Bashforth formula `y^0_{n+1} = y_n + h sum_{j=0}^{k-1} gamma_j D^j f_n`,
+/// - corrects this by solving the `k+1`th order Adams-Moulton formula `y_{n+1} = y^0_{n+1} + h gamma_k (f(y_{n+1}) - f^0_{n+1})`,
+///   where `f^0_{n+1}` is the extrapolated rhs,
+/// - estimates the local error of the `k`th order Adams-Moulton formula using the difference of these two, and controls the
+///   step size and order in the same manner as [crate::Bdf].
+///
+/// By default the corrector equation is solved using functional (fixed-point) iteration ([FixedPointNonlinearSolver]), which
+/// does not require the jacobian. A [crate::NewtonNonlinearSolver] can be used instead via [Adams::new] for mildly stiff problems.
+///
+/// Restrictions:
+/// - The problem must not have a mass matrix or sensitivities.
+///
+/// # References
+///
+/// \[1\] Byrne, G. D., & Hindmarsh, A. C. (1975). A polyalgorithm for the numerical solution of ordinary differential equations. ACM Transactions on Mathematical Software (TOMS), 1(1), 71-96.
+/// \[2\] Hairer, E., Nørsett, S. P., & Wanner, G. (1993). Solving Ordinary Differential Equations I: Nonstiff Problems. Springer.
+pub struct Adams<
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+> {
+    nonlinear_solver: Nls,
+    ode_problem: Option<OdeSolverProblem<Eqn>>,
+    order: usize,
+    n_equal_steps: usize,
+    diff: M,
+    diff_tmp: M,
+    u: M,
+    y_delta: Eqn::V,
+    f_delta: Eqn::V,
+    gamma: Vec<Eqn::T>,
+    gamma_star: Vec<Eqn::T>,
+    error_const2: Vec<Eqn::T>,
+    statistics: BdfStatistics<Eqn::T>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    is_state_modified: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn> Default
+    for Adams<<Eqn::V as DefaultDenseMatrix>::M, Eqn, FixedPointNonlinearSolver<BdfCallable<Eqn>>>
+where
+    Eqn: OdeEquations,
+    Eqn::V: DefaultDenseMatrix,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn default() -> Self {
+        Self::new(FixedPointNonlinearSolver::new())
+    }
+}
+
+impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> Adams<M, Eqn, Nls>
+where
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+{
+    const MAX_ORDER: IndexType = 12;
+    const CORRECTOR_MAXITER: IndexType = 4;
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-32;
+
+    /// Create a new Adams solver, using `nonlinear_solver` to solve the corrector equation.
+    pub fn new(mut nonlinear_solver: Nls) -> Self {
+        let n = 1;
+        nonlinear_solver.set_max_iter(Self::CORRECTOR_MAXITER);
+
+        // coefficients of the adams-bashforth (gamma) and adams-moulton (gamma_star) formulas
+        // in backward difference form, see eq (1.6) and (1.10) of chapter III.1 in [2]
+        let mut gamma = vec![Eqn::T::one()];
+        let mut gamma_star = vec![Eqn::T::one()];
+        for j in 1..=Self::MAX_ORDER + 1 {
+            let mut sum = Eqn::T::zero();
+            let mut sum_star = Eqn::T::zero();
+            for i in 0..j {
+                let denom = Eqn::T::cast((j + 1 - i) as f64);
+                sum += gamma[i].clone() / denom.clone();
+                sum_star += gamma_star[i].clone() / denom;
+            }
+            gamma.push(Eqn::T::one() - sum);
+            gamma_star.push(-sum_star);
+        }
+
+        // the error of the order k corrector is h gamma_star_k D^k f_{n+1} = gamma_star_k / gamma_k (y_{n+1} - y^0_{n+1})
+        let error_const2 = (0..=Self::MAX_ORDER)
+            .map(|k| (gamma_star[k].clone() / gamma[k].clone()).powi(2))
+            .collect();
+
+        Self {
+            ode_problem: None,
+            nonlinear_solver,
+            order: 1,
+            n_equal_steps: 0,
+            diff: M::zeros(n, Self::MAX_ORDER + 3),
+            diff_tmp: M::zeros(n, Self::MAX_ORDER + 3),
+            u: M::zeros(Self::MAX_ORDER + 1, Self::MAX_ORDER + 1),
+            y_delta: Eqn::V::zeros(n),
+            f_delta: Eqn::V::zeros(n),
+            gamma,
+            gamma_star,
+            error_const2,
+            statistics: BdfStatistics::default(),
+            state: None,
+            tstop: None,
+            root_finder: None,
+            is_state_modified: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    fn nonlinear_problem_op(&self) -> &Rc<BdfCallable<Eqn>> {
+        &self.nonlinear_solver.problem().f
+    }
+
+    fn update_step_size(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor.clone();
+        self.n_equal_steps = 0;
+
+        // the differences of f at the last `order + 1` points define the interpolating polynomial
+        // used by the corrector, so update D[0:order+1] using equations in section 3.2 of [1]
+        self.u = compute_r::<M>(self.order, Eqn::T::one());
+        let r = compute_r::<M>(self.order, factor);
+        let ru = r.mat_mul(&self.u);
+        update_diff_for_step_size(&ru, &mut self.diff, &mut self.diff_tmp, self.order);
+
+        // reset nonlinear's linear solver problem as (I - c * J) has changed
+        // use any x and t as they won't be used
+        self.nonlinear_problem_op().set_c(
+            self.state.as_ref().unwrap().h.clone(),
+            self.gamma[self.order].clone(),
+        );
+        let t = self.state.as_ref().unwrap().t.clone();
+        let x = &self.state.as_ref().unwrap().y;
+        self.nonlinear_solver.reset_jacobian(x, t);
+    }
+
+    // predict forward to new step using the adams-bashforth formula,
+    // and setup the corrector equation y - y^0 - h * gamma_k * (f(y) - f^0) = 0
+    fn predict_forward(&mut self) -> (Eqn::V, Eqn::T) {
+        let state = self.state.as_ref().unwrap();
+        let h = state.h.clone();
+        let mut y_predict = state.y.clone();
+        let mut f_predict = <Eqn::V as Vector>::zeros(state.y.len());
+        for j in 0..self.order {
+            y_predict += self.diff.column(j) * scale(h.clone() * self.gamma[j].clone());
+            f_predict += self.diff.column(j);
+        }
+
+        // the corrector is solved using F(y) = y + psi - y0 - c f(y), with c = h * gamma_k
+        let c = h.clone() * self.gamma[self.order].clone();
+        f_predict *= scale(c);
+        let op = self.nonlinear_problem_op();
+        op.set_c(h, self.gamma[self.order].clone());
+        op.set_psi_and_y0(&f_predict, &y_predict);
+
+        // update time (using compensated summation, see [Self::step])
+        let t_new = state.t.clone() + (state.h.clone() - self.t_compensation.clone());
+        (y_predict, t_new)
+    }
+
+    fn initialise_to_first_order(&mut self) {
+        if self.state.as_ref().unwrap().y.len() != self.problem().unwrap().eqn.rhs().nstates() {
+            panic!("State vector length does not match number of states in problem");
+        }
+        let state = self.state.as_ref().unwrap();
+        self.order = 1usize;
+        self.n_equal_steps = 0;
+
+        // the only difference available is f at the initial point
+        self.diff.column_mut(0).copy_from(&state.dy);
+        self.diff.column_mut(1).mul_assign(scale(Eqn::T::zero()));
+
+        // setup U
+        self.u = compute_r::<M>(self.order, Eqn::T::one());
+
+        // update statistics
+        self.statistics.initial_step_size = state.h.clone();
+
+        self.t_compensation = Eqn::T::zero();
+        self.is_state_modified = false;
+    }
+
+    // interpolate solution at time values t* where t-h < t* < t by integrating the
+    // interpolating polynomial of f through the last order + 1 points:
+    // y(t*) = y_{n+1} + h sum_j D^j f_{n+1} int_0^s prod_{i<j} (sigma + i) / (i + 1) dsigma, with s = (t* - t) / h
+    fn interpolate_from_diff(
+        t: Eqn::T,
+        diff: &M,
+        y1: &Eqn::V,
+        t1: Eqn::T,
+        h: Eqn::T,
+        order: usize,
+    ) -> Eqn::V {
+        let s = (t - t1) / h.clone();
+        let mut poly = vec![Eqn::T::one()];
+        let mut ret = y1.clone();
+        for j in 0..=order {
+            // integrate the polynomial from 0 to s
+            let mut integral = Eqn::T::zero();
+            let mut s_pow = s.clone();
+            for (k, c) in poly.iter().enumerate() {
+                integral += c.clone() * s_pow.clone() / Eqn::T::cast((k + 1) as f64);
+                s_pow *= s.clone();
+            }
+            ret += diff.column(j) * scale(h.clone() * integral);
+
+            // multiply the polynomial by (sigma + j) / (j + 1)
+            let j_t = Eqn::T::cast(j as f64);
+            let denom = j_t.clone() + Eqn::T::one();
+            let mut next = vec![Eqn::T::zero(); poly.len() + 1];
+            for (k, c) in poly.iter().enumerate() {
+                next[k] += c.clone() * j_t.clone() / denom.clone();
+                next[k + 1] += c.clone() / denom.clone();
+            }
+            poly = next;
+        }
+        ret
+    }
+}
+
+impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> OdeSolverMethod<Eqn>
+    for Adams<M, Eqn, Nls>
+where
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn order(&self) -> usize {
+        self.order
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: false,
+            max_order: Self::MAX_ORDER,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
+        // state must be set
+        let state = self.state.as_ref().ok_or(PSError::StateNotSet)?;
+        if self.is_state_modified {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+        // check that t is before the current time
+        if t > state.t {
+            return Err(PSError::InterpolationBeforeCurrentTime);
+        }
+
+        Ok(Self::interpolate_from_diff(
+            t,
+            &self.diff,
+            &state.y,
+            state.t.clone(),
+            state.h.clone(),
+            self.order,
+        ))
+    }
+
+    fn interpolate_sens(&self, _t: <Eqn as OdeEquations>::T) -> Result<Vec<Eqn::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.ode_problem.as_ref()
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.ode_problem = None;
+        self.root_finder = None;
+        self.nonlinear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_modified = true;
+        self.state.as_mut()
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+        self.ode_problem = Some(problem.clone());
+
+        // setup corrector for first step
+        let callable = Rc::new(BdfCallable::new(problem));
+        callable.set_c(state.h.clone(), self.gamma[1].clone());
+
+        let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
+        let max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::CORRECTOR_MAXITER);
+        self.nonlinear_solver.set_max_iter(max_iter);
+        self.nonlinear_solver
+            .set_max_rate(problem.options.max_convergence_rate.clone());
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // store state and setup root solver
+        self.state = Some(state);
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+
+        // allocate internal state
+        let nstates = problem.eqn.rhs().nstates();
+        if self.diff.nrows() != nstates {
+            self.diff = M::zeros(nstates, Self::MAX_ORDER + 3);
+            self.diff_tmp = M::zeros(nstates, Self::MAX_ORDER + 3);
+            self.y_delta = <Eqn::V as Vector>::zeros(nstates);
+            self.f_delta = <Eqn::V as Vector>::zeros(nstates);
+        }
+
+        // initialise solver to first order
+        self.statistics = BdfStatistics::default();
+        self.initialise_to_first_order();
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        let mut safety: Eqn::T;
+        let mut error_norm: Eqn::T;
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+
+        if self.is_state_modified {
+            // f at the new state is needed to restart the difference array
+            let state = self.state.as_mut().unwrap();
+            let eqn = &self.ode_problem.as_ref().unwrap().eqn;
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+            self.initialise_to_first_order();
+            let t = self.state.as_ref().unwrap().t.clone();
+            let x = &self.state.as_ref().unwrap().y;
+            self.nonlinear_solver.reset_jacobian(x, t);
+        }
+
+        let (mut y_predict, mut t_new) = self.predict_forward();
+
+        // loop until step is accepted
+        let y_new = loop {
+            let mut y_new = y_predict.clone();
+
+            // solve the corrector equation using the prediction as starting point
+            let solve_result = self.nonlinear_solver.solve_in_place(&mut y_new, t_new);
+            // update statistics
+            self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
+
+            if solve_result.is_ok() {
+                self.y_delta.copy_from(&y_new);
+                self.y_delta -= &y_predict;
+
+                // calculate error norm
+                let rtol = self.problem().as_ref().unwrap().rtol.clone();
+                let atol = self.ode_problem.as_ref().unwrap().atol.as_ref();
+                error_norm = self.y_delta.squared_norm(&y_new, atol, rtol)
+                    * self.error_const2[self.order].clone();
+            } else {
+                // corrector did not converge, so reduce step size by 0.3 and try again
+                // (the jacobian is re-evaluated if the nonlinear solver uses it)
+                self.statistics.number_of_nonlinear_solver_fails += 1;
+                self.nonlinear_problem_op().set_jacobian_is_stale();
+                self.update_step_size(Eqn::T::cast(0.3));
+
+                // if step size too small, then fail
+                let state = self.state.as_ref().unwrap();
+                if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                    return Err(PSError::StepSizeTooSmall {
+                        t: state.t.to_string(),
+                    });
+                }
+
+                (y_predict, t_new) = self.predict_forward();
+                continue;
+            }
+
+            // need to caulate safety even if step is accepted
+            let maxiter = self.nonlinear_solver.max_iter() as f64;
+            let niter = self.nonlinear_solver.niter() as f64;
+            let safety_factor: f64 = self.problem().unwrap().options.safety_factor.to_f64();
+            safety = Eqn::T::cast(safety_factor * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
+
+            // do the error test
+            if error_norm <= Eqn::T::cast(1.0) {
+                // step is accepted
+                break y_new;
+            } else {
+                // step is rejected, reduce step size and try again
+                let order = self.order as f64;
+                let mut factor = safety * error_norm.pow(Eqn::T::cast(-0.5 / (order + 1.0)));
+                if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                    factor = Eqn::T::cast(Self::MIN_FACTOR);
+                }
+                self.update_step_size(factor);
+
+                // if step size too small, then fail
+                let state = self.state.as_ref().unwrap();
+                if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                    return Err(PSError::StepSizeTooSmall {
+                        t: state.t.to_string(),
+                    });
+                }
+
+                // new prediction
+                (y_predict, t_new) = self.predict_forward();
+
+                // update statistics
+                self.statistics.number_of_error_test_failures += 1;
+            }
+        };
+
+        // take the accepted step, the new difference is
+        // D^k f_{n+1} = f_{n+1} - f^0_{n+1} = (y_{n+1} - y^0_{n+1}) / (h gamma_k)
+        {
+            let h = self.state.as_ref().unwrap().h.clone();
+            self.f_delta.copy_from(&self.y_delta);
+            self.f_delta *= scale(Eqn::T::one() / (h * self.gamma[self.order].clone()));
+            update_diff(self.order - 1, &self.f_delta, &mut self.diff);
+        }
+
+        {
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            state.y = y_new;
+            state.t = compensated_add(state.t.clone(), state.h.clone(), &mut self.t_compensation);
+            state.dy.copy_from_view(&self.diff.column(0));
+        }
+
+        // update statistics
+        self.statistics.number_of_linear_solver_setups =
+            self.nonlinear_problem_op().number_of_jac_evals();
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.nonlinear_solver.linear_solver_statistics();
+        self.statistics.nonlinear_solver = self.nonlinear_solver.get_statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // a change in order is only done after running at order k for k + 1 steps
+        self.n_equal_steps += 1;
+
+        if self.n_equal_steps > self.order {
+            let state = self.state.as_ref().unwrap();
+            let h = state.h.clone();
+            let atol = self.problem().as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem().as_ref().unwrap().rtol.clone();
+            let order = self.order;
+            // the error of the order j corrector is h gamma_star_j D^j f_{n+1}, so estimate
+            // the error for orders k-1 and k+1 from the updated differences
+            let error_m_norm = if order > 1 {
+                self.diff
+                    .column(order - 1)
+                    .squared_norm(&state.y, atol, rtol.clone())
+                    * (h.clone() * self.gamma_star[order - 1].clone()).powi(2)
+            } else {
+                Eqn::T::INFINITY
+            };
+            let error_p_norm = if order < Self::MAX_ORDER {
+                self.diff
+                    .column(order + 1)
+                    .squared_norm(&state.y, atol, rtol)
+                    * (h * self.gamma_star[order + 1].clone()).powi(2)
+            } else {
+                Eqn::T::INFINITY
+            };
+
+            let error_norms = [error_m_norm, error_norm, error_p_norm];
+            let factors = error_norms
+                .into_iter()
+                .enumerate()
+                .map(|(i, error_norm)| {
+                    error_norm.pow(Eqn::T::cast(-0.5 / (i as f64 + order as f64)))
+                })
+                .collect::<Vec<_>>();
+
+            // pick the order that maximises the resultant step size
+            let max_index = factors
+                .iter()
+                .enumerate()
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .unwrap()
+                .0;
+            if max_index == 0 {
+                self.order -= 1;
+            } else {
+                self.order += max_index - 1;
+            }
+
+            let mut factor = safety * factors[max_index].clone();
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+            self.update_step_size(factor);
+        }
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+}
+
+impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> StepChecks<Eqn>
+    for Adams<M, Eqn, Nls>
+where
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.update_step_size(factor);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                gaussian_decay::gaussian_decay_problem,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        Adams, AndersonSolver, NalgebraLU, NewtonNonlinearSolver, OdeEquations, OdeSolverMethod,
+        OdeSolverState, Op,
+    };
+
+    use num_traits::abs;
+    use std::rc::Rc;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn adams_no_set_problem() {
+        test_no_set_problem::<M, _>(Adams::default())
+    }
+    #[test]
+    fn adams_state_mut() {
+        test_state_mut::<M, _>(Adams::default())
+    }
+    #[test]
+    fn adams_test_interpolate() {
+        test_interpolate::<M, _>(Adams::default())
+    }
+
+    // the order used for each step when solving dy/dt = -0.1 y up to t = 10 with the relative tolerance rtol
+    fn adams_orders(rtol: f64) -> Vec<usize> {
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.rtol = rtol;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(2, 1e-2 * rtol));
+        let mut s = Adams::default();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        let mut orders = vec![s.order()];
+        while s.state().unwrap().t < 10.0 {
+            s.step().unwrap();
+            orders.push(s.order());
+        }
+        orders
+    }
+
+    #[test]
+    fn adams_test_order_switching() {
+        let orders = adams_orders(1e-10);
+        assert_eq!(orders[0], 1);
+        // the order changes by at most one, and only after running at order k for k + 1 steps
+        let mut n_equal_steps = 0;
+        for w in orders.windows(2) {
+            n_equal_steps += 1;
+            if w[1] != w[0] {
+                assert!(
+                    w[1] + 1 == w[0] || w[1] == w[0] + 1,
+                    "orders = {:?}",
+                    orders
+                );
+                assert!(n_equal_steps > w[0], "orders = {:?}", orders);
+                n_equal_steps = 0;
+            }
+        }
+        // tighter tolerances select higher orders
+        let max_order = |orders: &[usize]| *orders.iter().max().unwrap();
+        assert!(max_order(&orders) >= 4, "orders = {:?}", orders);
+        assert!(max_order(&orders) > max_order(&adams_orders(1e-4)));
+    }
+
+    #[test]
+    fn adams_test_nalgebra_exponential_decay() {
+        let mut s = Adams::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 18
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 44
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0004472135954999579
+        final_step_size: 1.7668984290100334
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 46
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn adams_test_nalgebra_gaussian_decay() {
+        let mut s = Adams::default();
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 51
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 132
+        number_of_nonlinear_solver_fails: 4
+        initial_step_size: 0.00009999999999999999
+        final_step_size: 0.1690974420344363
+        "###);
+    }
+
+    #[test]
+    fn adams_anderson_test_nalgebra_gaussian_decay() {
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        let mut s = Adams::default();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        let niter = s.get_statistics().number_of_nonlinear_solver_iterations;
+
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        let mut s = Adams::<M, _, _>::new(AndersonSolver::new());
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        let anderson_niter = s.get_statistics().number_of_nonlinear_solver_iterations;
+        assert!(
+            anderson_niter < niter,
+            "{} iterations with Anderson acceleration, {} without",
+            anderson_niter,
+            niter
+        );
+    }
+
+    #[test]
+    fn adams_newton_test_nalgebra_exponential_decay() {
+        let nonlinear_solver = NewtonNonlinearSolver::new(NalgebraLU::default());
+        let mut s = Adams::<M, _, _>::new(nonlinear_solver);
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 5
+        number_of_steps: 18
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 36
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0004472135954999579
+        final_step_size: 1.7688258295004535
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_adams() {
+        let mut s = Adams::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_adams() {
+        let mut s = Adams::default();
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,655 @@
+use num_traits::{abs, Zero};
+
+use crate::{
+    errors::PSError, scalar::Scalar, scale, ConstantOp, NonLinearOp, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, SolverCapabilities, Vector,
+};
+
+/// Standard linear compartment models with a closed-form solution. All states are amounts of drug in each compartment,
+/// with first-order transfer between compartments given by the rate constants. Any constant input into the states (e.g. an infusion)
+/// is taken from the right-hand side of the equations (see [CompartmentSolver]).
+///
+/// - `OneCompartment`: states `[central]`, with elimination rate `k`
+/// - `OneCompartmentAbsorption`: states `[depot, central]`, with first-order absorption rate `ka` from the depot and elimination rate `k`
+/// - `TwoCompartment`: states `[central, peripheral]`, with elimination rate `k10` and distribution rates `k12` (central to peripheral) and `k21` (peripheral to central)
+/// - `TwoCompartmentAbsorption`: states `[depot, central, peripheral]`, as `TwoCompartment` with first-order absorption rate `ka` from the depot
+#[derive(Clone, Debug)]
+pub enum CompartmentModel<T: Scalar> {
+    OneCompartment { k: T },
+    OneCompartmentAbsorption { ka: T, k: T },
+    TwoCompartment { k10: T, k12: T, k21: T },
+    TwoCompartmentAbsorption { ka: T, k10: T, k12: T, k21: T },
+}
+
+impl<T: Scalar> CompartmentModel<T> {
+    pub fn nstates(&self) -> usize {
+        match self {
+            Self::OneCompartment { .. } => 1,
+            Self::OneCompartmentAbsorption { .. } | Self::TwoCompartment { .. } => 2,
+            Self::TwoCompartmentAbsorption { .. } => 3,
+        }
+    }
+
+    /// The system matrix `A` of the model `dy/dt = A y + r`, stored row-major.
+    pub fn system_matrix(&self) -> Vec<T> {
+        let z = T::zero();
+        match self.clone() {
+            Self::OneCompartment { k } => vec![-k],
+            Self::OneCompartmentAbsorption { ka, k } => vec![-ka.clone(), z, ka, -k],
+            Self::TwoCompartment { k10, k12, k21 } => {
+                vec![-(k10 + k12.clone()), k21.clone(), k12, -k21]
+            }
+            Self::TwoCompartmentAbsorption { ka, k10, k12, k21 } => {
+                vec![
+                    -ka.clone(),
+                    z.clone(),
+                    z.clone(),
+                    ka,
+                    -(k10 + k12.clone()),
+                    k21.clone(),
+                    z,
+                    k12,
+                    -k21,
+                ]
+            }
+        }
+    }
+
+    /// The eigenvalues of the system matrix, these are the (negated) macro rate constants of the model.
+    pub fn eigenvalues(&self) -> Vec<T> {
+        // the two-compartment disposition rates are the roots of l^2 + (k10 + k12 + k21) l + k10 k21 = 0
+        let disposition = |k10: T, k12: T, k21: T| {
+            let s = k10.clone() + k12 + k21.clone();
+            let d = (s.clone() * s.clone() - T::cast(4.0) * k10 * k21).sqrt();
+            let half = T::cast(0.5);
+            [-half.clone() * (s.clone() - d.clone()), -half * (s + d)]
+        };
+        match self.clone() {
+            Self::OneCompartment { k } => vec![-k],
+            Self::OneCompartmentAbsorption { ka, k } => vec![-ka, -k],
+            Self::TwoCompartment { k10, k12, k21 } => disposition(k10, k12, k21).to_vec(),
+            Self::TwoCompartmentAbsorption { ka, k10, k12, k21 } => {
+                let [alpha, beta] = disposition(k10, k12, k21);
+                vec![-ka, alpha, beta]
+            }
+        }
+    }
+
+    /// The rate constants of the model, in the order of the fields of each variant.
+    pub fn rate_constants(&self) -> Vec<T> {
+        match self.clone() {
+            Self::OneCompartment { k } => vec![k],
+            Self::OneCompartmentAbsorption { ka, k } => vec![ka, k],
+            Self::TwoCompartment { k10, k12, k21 } => vec![k10, k12, k21],
+            Self::TwoCompartmentAbsorption { ka, k10, k12, k21 } => vec![ka, k10, k12, k21],
+        }
+    }
+
+    /// Check that the model has a closed-form solution, i.e. all rate constants are positive and no eigenvalue is repeated more than twice.
+    /// A zero rate constant (e.g. `k10 == 0`) gives a zero eigenvalue, so is not supported.
+    pub fn check(&self) -> Result<(), PSError> {
+        self.distinct_eigenvalues().map(|_| ())
+    }
+
+    // the distinct eigenvalues and their multiplicities, eigenvalues that coincide to within a relative tolerance are merged
+    // (e.g. `ka == k` for the one compartment model with absorption)
+    fn distinct_eigenvalues(&self) -> Result<Vec<(T, usize)>, PSError> {
+        if self
+            .rate_constants()
+            .iter()
+            .any(|k| *k <= T::zero() || k.is_nan())
+        {
+            return Err(PSError::UnsupportedProblem {
+                e: "compartment model rate constants must be positive".to_string(),
+            });
+        }
+        let lambda = self.eigenvalues();
+        let lmax = lambda
+            .iter()
+            .fold(T::zero(), |acc, l| acc.max(abs(l.clone())));
+        let mut distinct: Vec<(T, usize)> = Vec::new();
+        for l in lambda.into_iter() {
+            match distinct
+                .iter_mut()
+                .find(|(d, _)| abs(d.clone() - l.clone()) <= T::cast(1e-8) * lmax.clone())
+            {
+                Some((d, m)) => {
+                    *d = (d.clone() * T::cast(*m as f64) + l) / T::cast((*m + 1) as f64);
+                    *m += 1;
+                }
+                None => distinct.push((l, 1)),
+            }
+        }
+        if distinct.iter().any(|&(_, m)| m > 2) {
+            return Err(PSError::UnsupportedProblem {
+                e: "compartment model has a triple rate constant, so has no closed-form solution"
+                    .to_string(),
+            });
+        }
+        Ok(distinct)
+    }
+
+    /// Returns true if the right-hand side of `problem` has the structure of this model, i.e. it is of the form `A y + r`,
+    /// where `A` is the [Self::system_matrix] and `r` is a constant input, so that the problem can be solved using the closed-form solution.
+    pub fn is_compatible<Eqn: OdeEquations<T = T>>(&self, problem: &OdeSolverProblem<Eqn>) -> bool {
+        let n = self.nstates();
+        let rhs = problem.eqn.rhs();
+        if rhs.nstates() != n || problem.eqn.mass().is_some() || self.check().is_err() {
+            return false;
+        }
+        let t = problem.t0.clone();
+        let y0 = problem.eqn.init().call(t.clone());
+        let a = self.system_matrix();
+        let close = |x: T, y: T| abs(x - y.clone()) <= T::cast(1e-10) * (T::one() + abs(y));
+
+        // the jacobian must match the system matrix
+        let mut v = Eqn::V::zeros(n);
+        let mut col = Eqn::V::zeros(n);
+        for j in 0..n {
+            v[j] = T::one();
+            rhs.jac_mul_inplace(&y0, t.clone(), &v, &mut col);
+            if (0..n).any(|i| !close(col[i].clone(), a[i * n + j].clone())) {
+                return false;
+            }
+            v[j] = T::zero();
+        }
+
+        // and the rhs must be linear in the state
+        let r = rhs.call(&Eqn::V::zeros(n), t.clone());
+        let f = rhs.call(&y0, t);
+        let ay = Self::mul(&a, &y0);
+        (0..n).all(|i| close(f[i].clone(), ay[i].clone() + r[i].clone()))
+    }
+
+    fn mul<V: Vector<T = T>>(a: &[T], x: &V) -> V {
+        let n = x.len();
+        let mut y = V::zeros(n);
+        for i in 0..n {
+            for j in 0..n {
+                y[i] += a[i * n + j].clone() * x[j].clone();
+            }
+        }
+        y
+    }
+
+    /// Calculate the solution at time `dt` after a state `y0`, with a constant input `r` into each state.
+    ///
+    /// This uses Sylvester's formula to write the matrix exponential in terms of the distinct eigenvalues `l_i` of the system matrix `A`, i.e.
+    /// `y(dt) = sum_i P_i (exp(l_i dt) y0 + (exp(l_i dt) - 1) / l_i r)`, where `P_i = prod_{j != i} (A - l_j I) / (l_i - l_j)`.
+    /// A repeated eigenvalue `l` (e.g. `ka == k` for the one compartment model with absorption) uses the confluent form of the formula,
+    /// which adds the derivative with respect to `l` times `(A - l I)` to its term, e.g. giving the central amount `ka D t exp(-k t)`
+    /// after a dose `D` into the depot.
+    ///
+    /// Returns an error if the model has no closed-form solution (see [Self::check]).
+    pub fn solve<V: Vector<T = T>>(&self, y0: &V, r: &V, dt: T) -> Result<V, PSError> {
+        let n = self.nstates();
+        let a = self.system_matrix();
+        let lambda = self.distinct_eigenvalues()?;
+        // z = (A - l I) z
+        let shift = |z: &V, l: T| {
+            let mut az = Self::mul(&a, z);
+            az.axpy(-l, z, T::one());
+            az
+        };
+        let mut y = V::zeros(n);
+        for (i, (li, mi)) in lambda.iter().enumerate() {
+            let e = (li.clone() * dt.clone()).exp();
+            let phi = (li.clone() * dt.clone()).exp_m1() / li.clone();
+            let mut z = y0.clone() * scale(e.clone());
+            z.axpy(phi.clone(), r, T::one());
+            if *mi == 2 {
+                // the derivatives of exp(l dt) and (exp(l dt) - 1) / l with respect to l, less the derivative of the
+                // normalisation of the product over the other eigenvalues
+                let s = lambda
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .fold(T::zero(), |acc, (_, (lj, mj))| {
+                        acc + T::cast(*mj as f64) / (li.clone() - lj.clone())
+                    });
+                let de = dt.clone() * e.clone() - s.clone() * e.clone();
+                let dphi = (dt.clone() * e - phi.clone()) / li.clone() - s * phi;
+                let mut dz = y0.clone() * scale(de);
+                dz.axpy(dphi, r, T::one());
+                z += &shift(&dz, li.clone());
+            }
+            for (j, (lj, mj)) in lambda.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                // z = (A - l_j I) z / (l_i - l_j), for each repeat of l_j
+                for _ in 0..*mj {
+                    z = shift(&z, lj.clone()) * scale(T::one() / (li.clone() - lj.clone()));
+                }
+            }
+            y += &z;
+        }
+        Ok(y)
+    }
+}
+
+/// A solver for the standard linear compartment models given by [CompartmentModel], using their closed-form solution.
+///
+/// This implements [OdeSolverMethod] and so can be used in place of [crate::Bdf] or [crate::Sdirk], including with a [crate::DosingSchedule].
+/// Each step is exact, so the solution does not depend on the step size or tolerances.
+/// The problem must have the structure of the model (see [CompartmentModel::is_compatible]), otherwise [OdeSolverState::new] returns an error.
+/// This can be used to dispatch to the closed-form solution when possible, and fall back to a numerical solver otherwise, e.g.
+///
+/// ```
+/// use diffsol::{Bdf, CompartmentModel, CompartmentSolver, OdeBuilder, OdeSolverMethod};
+/// type M = nalgebra::DMatrix<f64>;
+/// type V = nalgebra::DVector<f64>;
+///
+/// let problem = OdeBuilder::new()
+///   .p([0.1])
+///   .build_ode::<M, _, _, _>(
+///     |x: &V, p: &V, _t, y: &mut V| y[0] = -p[0] * x[0],
+///     |_x: &V, p: &V, _t, v: &V, y: &mut V| y[0] = -p[0] * v[0],
+///     |_p: &V, _t| V::from_vec(vec![10.0]),
+///   ).unwrap();
+/// let model = CompartmentModel::OneCompartment { k: 0.1 };
+/// let soln = if model.is_compatible(&problem) {
+///   CompartmentSolver::new(model).solve(&problem, 10.0).unwrap()
+/// } else {
+///   Bdf::default().solve(&problem, 10.0).unwrap()
+/// };
+/// let y = soln.y.last().unwrap();
+/// assert!((y[0] - 10.0 * f64::exp(-1.0)).abs() < 1e-12);
+/// ```
+pub struct CompartmentSolver<Eqn: OdeEquations> {
+    model: CompartmentModel<Eqn::T>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    rate: Eqn::V,
+    tstop: Option<Eqn::T>,
+    max_h: Option<Eqn::T>,
+    is_state_modified: bool,
+}
+
+impl<Eqn: OdeEquations> CompartmentSolver<Eqn> {
+    pub fn new(model: CompartmentModel<Eqn::T>) -> Self {
+        Self {
+            model,
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: Eqn::V::zeros(0),
+            rate: Eqn::V::zeros(0),
+            tstop: None,
+            max_h: None,
+            is_state_modified: false,
+        }
+    }
+
+    pub fn model(&self) -> &CompartmentModel<Eqn::T> {
+        &self.model
+    }
+
+    /// The constant input into each state over the current step is the right-hand side evaluated at zero
+    fn update_rate(&mut self) {
+        let problem = self.problem.as_ref().unwrap();
+        let state = self.state.as_ref().unwrap();
+        let n = state.y.len();
+        self.rate = problem.eqn.rhs().call(&Eqn::V::zeros(n), state.t.clone());
+        self.old_t = state.t.clone();
+        self.old_y = state.y.clone();
+        self.is_state_modified = false;
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverMethod<Eqn> for CompartmentSolver<Eqn> {
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+        self.problem = Some(problem.clone());
+        self.state = Some(state);
+        self.tstop = None;
+        self.max_h = None;
+        self.update_rate();
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // the input may have changed along with the state (e.g. the start of an infusion)
+        self.update_rate();
+        let state = self.state.as_mut().unwrap();
+        let (t_new, reason) = match self.tstop.clone() {
+            Some(tstop) if state.t.clone() + state.h.clone() >= tstop => {
+                self.tstop = None;
+                (tstop, OdeSolverStopReason::TstopReached)
+            }
+            _ => (
+                state.t.clone() + state.h.clone(),
+                OdeSolverStopReason::InternalTimestep,
+            ),
+        };
+        state.y = self
+            .model
+            .solve(&self.old_y, &self.rate, t_new.clone() - state.t.clone())?;
+        state.t = t_new;
+        // steps are exact, so the step size is only limited by the need to find outputs with interpolation. It is
+        // doubled each step, but not beyond the next stop time or the last interval between stop times (e.g. the
+        // output interval)
+        state.h *= Eqn::T::cast(2.0);
+        if let Some(max_h) = self.max_h.clone() {
+            if state.h > max_h {
+                state.h = max_h;
+            }
+        }
+        if let Some(tstop) = self.tstop.clone() {
+            if state.h > tstop.clone() - state.t.clone() {
+                state.h = tstop - state.t.clone();
+            }
+        }
+        let problem = self.problem.as_ref().unwrap();
+        problem
+            .eqn
+            .rhs()
+            .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        problem.check_state_bound(&state.y, state.t.clone())?;
+        Ok(reason)
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<(), PSError> {
+        let state = self.state.as_ref().ok_or(PSError::StateNotSet)?;
+        if tstop <= state.t {
+            return Err(PSError::StopBeforeCurrentTime {
+                tstop: tstop.to_string(),
+                t: state.t.to_string(),
+            });
+        }
+        self.max_h = Some(tstop.clone() - state.t.clone());
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
+        let state = self.state.as_ref().ok_or(PSError::StateNotSet)?;
+        if self.is_state_modified {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+        if t > state.t {
+            return Err(PSError::InterpolationBeforeCurrentTime);
+        }
+        if t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        self.model
+            .solve(&self.old_y, &self.rate, t - self.old_t.clone())
+    }
+
+    fn interpolate_sens(&self, _t: Eqn::T) -> Result<Vec<Eqn::V>, PSError> {
+        Ok(vec![])
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_modified = true;
+        self.state.as_mut()
+    }
+
+    fn order(&self) -> usize {
+        1
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: false,
+            sensitivities: false,
+            stiff: true,
+            max_order: usize::MAX,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn check_problem(&self, problem: &OdeSolverProblem<Eqn>) -> Result<(), PSError> {
+        if problem.eqn.root().is_some() || problem.eqn_sens.is_some() {
+            return Err(PSError::UnsupportedProblem {
+                e: "closed-form compartment solver does not support root finding or sensitivities"
+                    .to_string(),
+            });
+        }
+        self.model.check()?;
+        if !self.model.is_compatible(problem) {
+            return Err(PSError::UnsupportedProblem {
+                e: "equations do not match the structure of the compartment model".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        Option::take(&mut self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::analytic::{CompartmentModel, CompartmentSolver},
+        scale, Bdf, DosingSchedule, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+        OdeSolverState, OdeSolverStopReason, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // two compartment model with absorption, p = [ka, k10, k12, k21]
+    fn two_compartment_absorption() -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        OdeBuilder::new()
+            .p([1.5, 0.2, 0.3, 0.1])
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode::<M, _, _, _>(
+                |x: &V, p: &V, _t, y: &mut V| {
+                    y[0] = -p[0] * x[0];
+                    y[1] = p[0] * x[0] - (p[1] + p[2]) * x[1] + p[3] * x[2];
+                    y[2] = p[2] * x[1] - p[3] * x[2];
+                },
+                |_x: &V, p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -p[0] * v[0];
+                    y[1] = p[0] * v[0] - (p[1] + p[2]) * v[1] + p[3] * v[2];
+                    y[2] = p[2] * v[1] - p[3] * v[2];
+                },
+                |_p: &V, _t| V::from_vec(vec![0.0, 0.0, 0.0]),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_one_compartment_closed_form() {
+        let model = CompartmentModel::OneCompartment { k: 0.1 };
+        let y0 = V::from_vec(vec![10.0]);
+        let r = V::from_vec(vec![2.0]);
+        let y = model.solve(&y0, &r, 3.0).unwrap();
+        let expect = 10.0 * f64::exp(-0.3) + 2.0 / 0.1 * (1.0 - f64::exp(-0.3));
+        y.assert_eq_st(&V::from_vec(vec![expect]), 1e-12);
+    }
+
+    #[test]
+    fn test_one_compartment_absorption_equal_rates() {
+        // with ka == k the central amount after a dose D into the depot is ka D t exp(-k t)
+        let (k, d, t) = (0.3, 100.0, 2.0);
+        let model = CompartmentModel::OneCompartmentAbsorption { ka: k, k };
+        let y = model
+            .solve(&V::from_vec(vec![d, 0.0]), &V::zeros(2), t)
+            .unwrap();
+        let expect = V::from_vec(vec![d * f64::exp(-k * t), k * d * t * f64::exp(-k * t)]);
+        y.assert_eq_st(&expect, 1e-12);
+
+        // with a constant input r into the depot, the central amount is r / k (1 - exp(-k t) - k t exp(-k t))
+        let r = 2.0;
+        let y = model
+            .solve(&V::zeros(2), &V::from_vec(vec![r, 0.0]), t)
+            .unwrap();
+        let expect = V::from_vec(vec![
+            r / k * (1.0 - f64::exp(-k * t)),
+            r / k * (1.0 - f64::exp(-k * t) - k * t * f64::exp(-k * t)),
+        ]);
+        y.assert_eq_st(&expect, 1e-12);
+
+        // ka == alpha in the two compartment model matches the limit of nearby distinct rates (the mean of the solutions
+        // either side of alpha is accurate to second order in the distance from alpha)
+        let disposition = CompartmentModel::TwoCompartment {
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        let alpha: f64 = -disposition.eigenvalues()[0];
+        let y0 = V::from_vec(vec![d, 0.0, 0.0]);
+        let r = V::from_vec(vec![1.0, 0.5, 0.0]);
+        let model = |ka| CompartmentModel::TwoCompartmentAbsorption {
+            ka,
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        let y = model(alpha).solve(&y0, &r, t).unwrap();
+        let above = model(alpha * (1.0 + 1e-5)).solve(&y0, &r, t).unwrap();
+        let below = model(alpha * (1.0 - 1e-5)).solve(&y0, &r, t).unwrap();
+        let nearby = (above + below) * scale(0.5);
+        y.assert_eq_st(&nearby, 1e-6);
+    }
+
+    #[test]
+    fn test_compartment_model_check() {
+        let model = CompartmentModel::OneCompartmentAbsorption { ka: 0.1, k: -0.1 };
+        assert!(model.check().is_err());
+        let model = CompartmentModel::OneCompartmentAbsorption { ka: 0.1, k: 0.1 };
+        assert!(model.check().is_ok());
+        let model = CompartmentModel::TwoCompartment {
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        assert!(model.check().is_ok());
+        // the eigenvalues of a two compartment model sum to the trace of the system matrix
+        let lambda: Vec<f64> = model.eigenvalues();
+        assert!((lambda[0] + lambda[1] + 0.6).abs() < 1e-12);
+
+        // a zero or negative rate constant anywhere in the model is rejected, including by solve
+        let model = CompartmentModel::TwoCompartment {
+            k10: 0.0,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        assert!(model.check().is_err());
+        let y0 = V::from_vec(vec![1.0, 0.0]);
+        assert!(model.solve(&y0, &V::zeros(2), 1.0).is_err());
+        let model = CompartmentModel::TwoCompartment {
+            k10: 0.2,
+            k12: -0.3,
+            k21: 0.1,
+        };
+        assert!(model.check().is_err());
+    }
+
+    #[test]
+    fn test_compartment_solver_matches_bdf() {
+        let mut problem = two_compartment_absorption().with_infusions().unwrap();
+        let model = CompartmentModel::TwoCompartmentAbsorption {
+            ka: 1.5,
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        assert!(model.is_compatible(&problem));
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose(0.0, 100.0, 0).unwrap();
+        schedule.add_dose(12.0, 100.0, 0).unwrap();
+        schedule.add_infusion(6.0, 2.0, 10.0, 1).unwrap();
+        let t_eval = [0.5, 1.0, 6.0, 7.0, 8.0, 12.0, 13.0, 24.0];
+        let mut solver = CompartmentSolver::new(model);
+        let exact = schedule.solve(&mut solver, &mut problem, &t_eval).unwrap();
+        let mut bdf = Bdf::default();
+        let numerical = schedule.solve(&mut bdf, &mut problem, &t_eval).unwrap();
+        for (e, n) in exact.iter().zip(numerical.iter()) {
+            n.assert_eq_st(e, 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_compartment_solver_interpolate() {
+        let problem = two_compartment_absorption();
+        let model = CompartmentModel::TwoCompartmentAbsorption {
+            ka: 1.5,
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        let mut solver = CompartmentSolver::new(model.clone());
+        let mut state = OdeSolverState::new(&problem, &solver).unwrap();
+        state.y = V::from_vec(vec![100.0, 0.0, 0.0]);
+        state.h = 1.0;
+        solver.set_problem(state, &problem);
+        solver.step().unwrap();
+        solver.step().unwrap();
+        let y = solver.interpolate(2.0).unwrap();
+        let zero = V::zeros(3);
+        let expect = model
+            .solve(&V::from_vec(vec![100.0, 0.0, 0.0]), &zero, 2.0)
+            .unwrap();
+        y.assert_eq_st(&expect, 1e-10);
+    }
+
+    #[test]
+    fn test_compartment_solver_step_size_limited_by_stop_time() {
+        let problem = two_compartment_absorption();
+        let model = CompartmentModel::TwoCompartmentAbsorption {
+            ka: 1.5,
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        let mut solver = CompartmentSolver::new(model);
+        let mut state = OdeSolverState::new(&problem, &solver).unwrap();
+        state.h = 1.0;
+        solver.set_problem(state, &problem);
+        solver.set_stop_time(10.0).unwrap();
+        // the step size doubles (1, 2, 4), then is cut to land on the stop time, and is then limited to the interval
+        let mut steps = 0;
+        while !matches!(solver.step().unwrap(), OdeSolverStopReason::TstopReached) {
+            steps += 1;
+        }
+        assert_eq!(steps, 3);
+        assert_eq!(solver.state().unwrap().t, 10.0);
+        for _ in 0..100 {
+            solver.step().unwrap();
+            assert!(solver.state().unwrap().h <= 10.0);
+        }
+    }
+
+    #[test]
+    fn test_compartment_solver_incompatible() {
+        let problem = two_compartment_absorption();
+        let model = CompartmentModel::TwoCompartmentAbsorption {
+            ka: 1.0,
+            k10: 0.2,
+            k12: 0.3,
+            k21: 0.1,
+        };
+        assert!(!model.is_compatible(&problem));
+        let solver = CompartmentSolver::new(model);
+        assert!(OdeSolverState::new(&problem, &solver).is_err());
+    }
+}
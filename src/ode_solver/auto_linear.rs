@@ -0,0 +1,226 @@
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, matrix::MatrixRef,
+    op::bdf::BdfCallable, op::sdirk::SdirkCallable, vector::DefaultDenseMatrix, Bdf,
+    LinearOdeSolver, NewtonNonlinearSolver, NonLinearOp, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, OdeSolverStopReason, SolverCapabilities, VectorRef,
+};
+
+/// An ODE solver that detects problems with a linear right-hand side and solves them with a solver specialised for linear problems
+/// (by default [LinearOdeSolver]), and solves all other problems with a general method (by default [Bdf]).
+///
+/// The method is chosen when the problem is set, the linear method is used if the rhs reports [NonLinearOp::is_linear]
+/// (e.g. problems built using [crate::OdeBuilder::build_ode_linear]) and the linear method supports all the other features of the problem
+/// (see [OdeSolverMethod::check_problem]), so for example problems with sensitivities are always solved with the general method.
+pub struct AutoLinearSolver<Eqn, Linear, General>
+where
+    Eqn: OdeEquations,
+    Linear: OdeSolverMethod<Eqn>,
+    General: OdeSolverMethod<Eqn>,
+{
+    linear: Linear,
+    general: General,
+    use_linear: bool,
+    _phantom: std::marker::PhantomData<Eqn>,
+}
+
+impl<Eqn> Default
+    for AutoLinearSolver<
+        Eqn,
+        LinearOdeSolver<Eqn, <Eqn::M as DefaultSolver>::LS<SdirkCallable<Eqn>>>,
+        Bdf<
+            <Eqn::V as DefaultDenseMatrix>::M,
+            Eqn,
+            NewtonNonlinearSolver<
+                BdfCallable<Eqn>,
+                <Eqn::M as DefaultSolver>::LS<BdfCallable<Eqn>>,
+            >,
+        >,
+    >
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    Eqn::V: DefaultDenseMatrix,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn default() -> Self {
+        Self::new(
+            LinearOdeSolver::new(Eqn::M::default_solver()),
+            Bdf::default(),
+        )
+    }
+}
+
+impl<Eqn, Linear, General> AutoLinearSolver<Eqn, Linear, General>
+where
+    Eqn: OdeEquations,
+    Linear: OdeSolverMethod<Eqn>,
+    General: OdeSolverMethod<Eqn>,
+{
+    pub fn new(linear: Linear, general: General) -> Self {
+        Self {
+            linear,
+            general,
+            use_linear: false,
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns true if the linear method is used for the current problem (or the last problem that was set).
+    pub fn uses_linear_solver(&self) -> bool {
+        self.use_linear
+    }
+
+    pub fn linear_solver(&self) -> &Linear {
+        &self.linear
+    }
+
+    pub fn general_solver(&self) -> &General {
+        &self.general
+    }
+
+    fn is_linear_problem(&self, problem: &OdeSolverProblem<Eqn>) -> bool {
+        problem.eqn.rhs().is_linear() && self.linear.check_problem(problem).is_ok()
+    }
+
+    fn active_solver(&self) -> &dyn OdeSolverMethod<Eqn> {
+        if self.use_linear {
+            &self.linear
+        } else {
+            &self.general
+        }
+    }
+
+    fn active_solver_mut(&mut self) -> &mut dyn OdeSolverMethod<Eqn> {
+        if self.use_linear {
+            &mut self.linear
+        } else {
+            &mut self.general
+        }
+    }
+}
+
+impl<Eqn, Linear, General> OdeSolverMethod<Eqn> for AutoLinearSolver<Eqn, Linear, General>
+where
+    Eqn: OdeEquations,
+    Linear: OdeSolverMethod<Eqn>,
+    General: OdeSolverMethod<Eqn>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.active_solver().problem()
+    }
+
+    fn order(&self) -> usize {
+        self.active_solver().order()
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        self.active_solver().capabilities()
+    }
+
+    fn check_problem(&self, problem: &OdeSolverProblem<Eqn>) -> Result<(), PSError> {
+        if self.is_linear_problem(problem) {
+            Ok(())
+        } else {
+            self.general.check_problem(problem)
+        }
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+        // clear any previous problem from the method that is not used
+        self.active_solver_mut().take_state();
+        self.use_linear = self.is_linear_problem(problem);
+        self.active_solver_mut().set_problem(state, problem);
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        self.active_solver_mut().step()
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<(), PSError> {
+        self.active_solver_mut().set_stop_time(tstop)
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
+        self.active_solver().interpolate(t)
+    }
+
+    fn interpolate_sens(&self, t: Eqn::T) -> Result<Vec<Eqn::V>, PSError> {
+        self.active_solver().interpolate_sens(t)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.active_solver().state()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.active_solver_mut().state_mut()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.active_solver_mut().take_state()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{exponential_decay_problem, exponential_decay_problem_linear},
+                heat1d::heat1d_linear_problem,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver},
+        },
+        AutoLinearSolver, OdeEquations, Op,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn auto_linear_no_set_problem() {
+        test_no_set_problem::<M, _>(AutoLinearSolver::default())
+    }
+
+    #[test]
+    fn auto_linear_test_interpolate() {
+        test_interpolate::<M, _>(AutoLinearSolver::default())
+    }
+
+    #[test]
+    fn auto_linear_test_nalgebra_exponential_decay_linear() {
+        let mut s = AutoLinearSolver::default();
+        let (problem, soln) = exponential_decay_problem_linear::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(s.uses_linear_solver());
+        // the matrix of the linear rhs is only assembled once
+        assert_eq!(
+            problem
+                .eqn
+                .as_ref()
+                .rhs()
+                .statistics()
+                .number_of_matrix_evals,
+            1
+        );
+    }
+
+    #[test]
+    fn auto_linear_test_nalgebra_heat1d() {
+        let mut s = AutoLinearSolver::default();
+        let (problem, soln) = heat1d_linear_problem::<M>(20);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(s.uses_linear_solver());
+        assert_eq!(s.general_solver().get_statistics().number_of_steps, 0);
+    }
+
+    #[test]
+    fn auto_linear_test_nalgebra_exponential_decay() {
+        // the rhs is not declared as linear, so the general method is used
+        let mut s = AutoLinearSolver::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(!s.uses_linear_solver());
+        assert_eq!(s.linear_solver().get_statistics().number_of_steps, 0);
+    }
+}
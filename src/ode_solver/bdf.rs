@@ -10,16 +10,18 @@ use crate::{
     newton_iteration,
     nonlinear_solver::root::RootFinder,
     op::bdf::BdfCallable,
-    scalar::scale,
+    scalar::{compensated_add, scale},
     vector::DefaultDenseMatrix,
-    Convergence, DenseMatrix, IndexType, MatrixViewMut, NewtonNonlinearSolver, NonLinearSolver,
-    OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, Scalar,
-    SolverProblem, Vector, VectorRef, VectorView, VectorViewMut,
+    Convergence, DenseMatrix, IndexType, LinearSolverStatistics, MatrixViewMut,
+    NewtonNonlinearSolver, NonLinearSolver, NonLinearSolverStatistics, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, Scalar, SolverCapabilities,
+    SolverProblem, StepControl, Vector, VectorRef, VectorView, VectorViewMut,
 };
 use crate::{NonLinearOp, SensEquations};
 
 use super::equations::OdeEquations;
 use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
 
 #[derive(Clone, Debug, Serialize)]
 pub struct BdfStatistics<T: Scalar> {
@@ -30,6 +32,14 @@ pub struct BdfStatistics<T: Scalar> {
     pub number_of_nonlinear_solver_fails: usize,
     pub initial_step_size: T,
     pub final_step_size: T,
+    /// Statistics of the linear solver of the implicit solvers (see [LinearSolverStatistics]), updated after each step.
+    /// This is not serialised, as the timings vary between runs.
+    #[serde(skip)]
+    pub linear_solver: LinearSolverStatistics,
+    /// Statistics of the nonlinear solver of the implicit solvers (see [NonLinearSolverStatistics]), updated after each step.
+    /// This is not serialised, as it duplicates the counts above.
+    #[serde(skip)]
+    pub nonlinear_solver: NonLinearSolverStatistics,
 }
 
 impl<T: Scalar> Default for BdfStatistics<T> {
@@ -42,6 +52,8 @@ impl<T: Scalar> Default for BdfStatistics<T> {
             number_of_nonlinear_solver_fails: 0,
             initial_step_size: T::zero(),
             final_step_size: T::zero(),
+            linear_solver: LinearSolverStatistics::default(),
+            nonlinear_solver: NonLinearSolverStatistics::default(),
         }
     }
 }
@@ -69,10 +81,19 @@ pub struct Bdf<
     nonlinear_solver: Nls,
     ode_problem: Option<OdeSolverProblem<Eqn>>,
     order: usize,
+    min_order: usize,
+    max_order: usize,
     n_equal_steps: usize,
     diff: M,
     y_delta: Eqn::V,
+    // storage for the predicted and corrected states and the psi term of each step, so stepping does not allocate new vectors
+    y_predict: Eqn::V,
+    y_new: Eqn::V,
+    psi: Eqn::V,
     sdiff: Vec<M>,
+    // as above, for the predicted sensitivities and their psi term (reused for each parameter)
+    s_predict: Eqn::V,
+    s_psi: Eqn::V,
     s_op: Option<BdfCallable<SensEquations<Eqn>>>,
     s_deltas: Vec<Eqn::V>,
     diff_tmp: M,
@@ -85,6 +106,7 @@ pub struct Bdf<
     tstop: Option<Eqn::T>,
     root_finder: Option<RootFinder<Eqn::V>>,
     is_state_modified: bool,
+    t_compensation: Eqn::T,
 }
 
 impl<Eqn> Default
@@ -119,18 +141,19 @@ where
     const MAX_FACTOR: f64 = 10.0;
     const MIN_TIMESTEP: f64 = 1e-32;
 
-    fn new(mut nonlinear_solver: Nls) -> Self {
+    /// Create a new BDF solver, using `nonlinear_solver` to solve the implicit equation at each step.
+    pub fn new(mut nonlinear_solver: Nls) -> Self {
         let n = 1;
         nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
 
         // kappa values for difference orders, taken from Table 1 of [1]
         let kappa = [
-            Eqn::T::from(0.0),
-            Eqn::T::from(-0.1850),
-            Eqn::T::from(-1.0) / Eqn::T::from(9.0),
-            Eqn::T::from(-0.0823),
-            Eqn::T::from(-0.0415),
-            Eqn::T::from(0.0),
+            Eqn::T::cast(0.0),
+            Eqn::T::cast(-0.1850),
+            Eqn::T::cast(-1.0) / Eqn::T::cast(9.0),
+            Eqn::T::cast(-0.0823),
+            Eqn::T::cast(-0.0415),
+            Eqn::T::cast(0.0),
         ];
         let mut alpha = vec![Eqn::T::zero()];
         let mut gamma = vec![Eqn::T::zero()];
@@ -138,12 +161,12 @@ where
 
         #[allow(clippy::needless_range_loop)]
         for i in 1..=Self::MAX_ORDER {
-            let i_t = Eqn::T::from(i as f64);
-            let one_over_i = Eqn::T::one() / i_t;
+            let i_t = Eqn::T::cast(i as f64);
+            let one_over_i = Eqn::T::one() / i_t.clone();
             let one_over_i_plus_one = Eqn::T::one() / (i_t + Eqn::T::one());
-            gamma.push(gamma[i - 1] + one_over_i);
-            alpha.push(Eqn::T::one() / ((Eqn::T::one() - kappa[i]) * gamma[i]));
-            error_const2.push((kappa[i] * gamma[i] + one_over_i_plus_one).powi(2));
+            gamma.push(gamma[i - 1].clone() + one_over_i);
+            alpha.push(Eqn::T::one() / ((Eqn::T::one() - kappa[i].clone()) * gamma[i].clone()));
+            error_const2.push((kappa[i].clone() * gamma[i].clone() + one_over_i_plus_one).powi(2));
         }
 
         Self {
@@ -151,11 +174,18 @@ where
             ode_problem: None,
             nonlinear_solver,
             order: 1,
+            min_order: 1,
+            max_order: Self::MAX_ORDER,
             n_equal_steps: 0,
             diff: M::zeros(n, Self::MAX_ORDER + 3), //DMatrix::<T>::zeros(n, Self::MAX_ORDER + 3),
             diff_tmp: M::zeros(n, Self::MAX_ORDER + 3),
             sdiff: Vec::new(),
             y_delta: Eqn::V::zeros(n),
+            y_predict: Eqn::V::zeros(n),
+            y_new: Eqn::V::zeros(n),
+            psi: Eqn::V::zeros(n),
+            s_predict: Eqn::V::zeros(n),
+            s_psi: Eqn::V::zeros(n),
             s_deltas: Vec::new(),
             gamma,
             alpha,
@@ -166,6 +196,7 @@ where
             tstop: None,
             root_finder: None,
             is_state_modified: false,
+            t_compensation: Eqn::T::zero(),
         }
     }
 
@@ -173,34 +204,55 @@ where
         &self.statistics
     }
 
+    /// Set the maximum order of the method (between 1 and 5, default 5), similar to `CVodeSetMaxOrd` in CVODE.
+    /// Capping the order at 2 keeps the method A-stable, which can be useful for stiff problems with eigenvalues
+    /// close to the imaginary axis. If the current order is above the new maximum it is reduced immediately.
+    pub fn set_max_order(&mut self, max_order: usize) -> Result<(), PSError> {
+        if max_order < self.min_order || max_order > Self::MAX_ORDER {
+            return Err(PSError::InvalidOrder {
+                order: max_order,
+                min: self.min_order,
+                max: Self::MAX_ORDER,
+            });
+        }
+        self.max_order = max_order;
+        if self.state.is_some() && self.order > max_order {
+            self.order = max_order;
+            self._update_step_size(Eqn::T::one());
+        }
+        Ok(())
+    }
+
+    /// Set the minimum order of the method (between 1 and the maximum order, default 1). The solver always starts
+    /// at first order and increases the order as the step history builds up, but once the order has reached the
+    /// minimum it is not decreased below it.
+    pub fn set_min_order(&mut self, min_order: usize) -> Result<(), PSError> {
+        if min_order < 1 || min_order > self.max_order {
+            return Err(PSError::InvalidOrder {
+                order: min_order,
+                min: 1,
+                max: self.max_order,
+            });
+        }
+        self.min_order = min_order;
+        Ok(())
+    }
+
     fn nonlinear_problem_op(&self) -> &Rc<BdfCallable<Eqn>> {
         &self.nonlinear_solver.problem().f
     }
 
-    fn _compute_r(order: usize, factor: Eqn::T) -> M {
-        //computes the R matrix with entries
-        //given by the first equation on page 8 of [1]
-        //
-        //This is used to update the differences matrix when step size h is varied
-        //according to factor = h_{n+1} / h_n
-        //
-        //Note that the U matrix also defined in the same section can be also be
-        //found using factor = 1, which corresponds to R with a constant step size
-        let mut r = M::zeros(order + 1, order + 1);
-
-        // r[0, 0:order] = 1
-        for j in 0..=order {
-            r[(0, j)] = M::T::one();
-        }
-        // r[i, j] = r[i, j-1] * (j - 1 - factor * i) / j
-        for i in 1..=order {
-            for j in 1..=order {
-                let i_t = M::T::from(i as f64);
-                let j_t = M::T::from(j as f64);
-                r[(i, j)] = r[(i - 1, j)] * (i_t - M::T::one() - factor * j_t) / i_t;
-            }
+    /// The step size factor for which the convergence rate of the Newton iteration on the last step, assumed to be proportional
+    /// to the step size, is predicted to reach the limit set by [crate::OdeSolverOptions::convergence_rate_step_limit].
+    /// Returns `None` if no limit is set or the rate was not estimated.
+    fn convergence_rate_factor(&self) -> Option<Eqn::T> {
+        let options = &self.problem().unwrap().options;
+        let limit = options.convergence_rate_step_limit.clone()?;
+        let rate = self.nonlinear_solver.convergence_rate()?;
+        if rate <= Eqn::T::zero() {
+            return None;
         }
-        r
+        Some(limit * options.max_convergence_rate.clone() / rate)
     }
 
     fn _update_step_size(&mut self, factor: Eqn::T) {
@@ -210,45 +262,31 @@ where
         //- constant c = h / (1-kappa) gamma_k term
         //- lu factorisation of (M - c * J) used in newton iteration (same equation)
 
-        self.state.as_mut().unwrap().h *= factor;
+        self.state.as_mut().unwrap().h *= factor.clone();
         self.n_equal_steps = 0;
 
         // update D using equations in section 3.2 of [1]
         // TODO: move this to whereever we change order
-        self.u = Self::_compute_r(self.order, Eqn::T::one());
-        let r = Self::_compute_r(self.order, factor);
+        self.u = compute_r::<M>(self.order, Eqn::T::one());
+        let r = compute_r::<M>(self.order, factor);
         let ru = r.mat_mul(&self.u);
-        Self::_update_diff_for_step_size(&ru, &mut self.diff, &mut self.diff_tmp, self.order);
+        update_diff_for_step_size(&ru, &mut self.diff, &mut self.diff_tmp, self.order);
         for i in 0..self.sdiff.len() {
-            Self::_update_diff_for_step_size(
-                &ru,
-                &mut self.sdiff[i],
-                &mut self.diff_tmp,
-                self.order,
-            );
+            update_diff_for_step_size(&ru, &mut self.sdiff[i], &mut self.diff_tmp, self.order);
         }
 
-        self.nonlinear_problem_op()
-            .set_c(self.state.as_ref().unwrap().h, self.alpha[self.order]);
+        self.nonlinear_problem_op().set_c(
+            self.state.as_ref().unwrap().h.clone(),
+            self.alpha[self.order].clone(),
+        );
 
         // reset nonlinear's linear solver problem as lu factorisation has changed
         // use any x and t as they won't be used
-        let t = self.state.as_ref().unwrap().t;
+        let t = self.state.as_ref().unwrap().t.clone();
         let x = &self.state.as_ref().unwrap().y;
         self.nonlinear_solver.reset_jacobian(x, t);
     }
 
-    fn _update_diff_for_step_size(ru: &M, diff: &mut M, diff_tmp: &mut M, order: usize) {
-        // D[0:order+1] = R * U * D[0:order+1]
-        {
-            let d_zero_order = diff.columns(0, order + 1);
-            let mut d_zero_order_tmp = diff_tmp.columns_mut(0, order + 1);
-            d_zero_order_tmp.gemm_vo(Eqn::T::one(), &d_zero_order, ru, Eqn::T::zero());
-            // diff_sub = diff * RU
-        }
-        std::mem::swap(diff, diff_tmp);
-    }
-
     fn _update_sens_step_size(&mut self, factor: Eqn::T) {
         //If step size h is changed then also need to update the terms in
         //the first equation of page 9 of [1]:
@@ -257,99 +295,58 @@ where
         //- lu factorisation of (M - c * J) used in newton iteration (same equation)
 
         // update D using equations in section 3.2 of [1]
-        let r = Self::_compute_r(self.order, factor);
+        let r = compute_r::<M>(self.order, factor);
         let ru = r.mat_mul(&self.u);
         for sdiff in self.sdiff.iter_mut() {
-            Self::_update_diff_for_step_size(&ru, sdiff, &mut self.diff_tmp, self.order);
+            update_diff_for_step_size(&ru, sdiff, &mut self.diff_tmp, self.order);
         }
     }
 
     fn update_differences(&mut self) {
-        Self::_update_diff(self.order, &self.y_delta, &mut self.diff);
+        update_diff(self.order, &self.y_delta, &mut self.diff);
         for i in 0..self.sdiff.len() {
-            Self::_update_diff(self.order, &self.s_deltas[i], &mut self.sdiff[i]);
-        }
-    }
-
-    fn _update_diff(order: usize, d: &Eqn::V, diff: &mut M) {
-        //update of difference equations can be done efficiently
-        //by reusing d and D.
-        //
-        //From first equation on page 4 of [1]:
-        //d = y_n - y^0_n = D^{k + 1} y_n
-        //
-        //Standard backwards difference gives
-        //D^{j + 1} y_n = D^{j} y_n - D^{j} y_{n - 1}
-        //
-        //Combining these gives the following algorithm
-        let d_minus_order_plus_one = d - diff.column(order + 1);
-        diff.column_mut(order + 2)
-            .copy_from(&d_minus_order_plus_one);
-        diff.column_mut(order + 1).copy_from(d);
-        for i in (0..=order).rev() {
-            let tmp = diff.column(i + 1).into_owned();
-            diff.column_mut(i).add_assign(&tmp);
+            update_diff(self.order, &self.s_deltas[i], &mut self.sdiff[i]);
         }
     }
 
     // predict forward to new step (eq 2 in [1])
-    fn _predict_using_diff(diff: &M, order: usize) -> Eqn::V {
-        let mut y_predict = <Eqn::V as Vector>::zeros(diff.nrows());
+    fn _predict_using_diff(y_predict: &mut Eqn::V, diff: &M, order: usize) {
+        y_predict.fill(Eqn::T::zero());
         for i in 0..=order {
-            y_predict += diff.column(i);
+            *y_predict += diff.column(i);
         }
-        y_predict
     }
 
     // update psi term as defined in second equation on page 9 of [1]
-    fn _calculate_psi(&self, diff: &M) -> Eqn::V {
-        let mut psi = diff.column(1) * scale(self.gamma[1]);
-        for (i, &gamma_i) in self.gamma.iter().enumerate().take(self.order + 1).skip(2) {
-            psi += diff.column(i) * scale(gamma_i);
+    fn _calculate_psi(psi: &mut Eqn::V, diff: &M, gamma: &[Eqn::T], alpha: Eqn::T, order: usize) {
+        psi.fill(Eqn::T::zero());
+        for (i, gamma_i) in gamma.iter().enumerate().take(order + 1).skip(1) {
+            psi.axpy_v(gamma_i.clone(), &diff.column(i), Eqn::T::one());
         }
-        psi *= scale(self.alpha[self.order]);
-        psi
+        *psi *= scale(alpha);
     }
 
-    fn _predict_forward(&mut self) -> (Eqn::V, Eqn::T) {
-        let y_predict = Self::_predict_using_diff(&self.diff, self.order);
+    // predict the state at the new step into `self.y_predict`, and return the new time
+    fn _predict_forward(&mut self) -> Eqn::T {
+        Self::_predict_using_diff(&mut self.y_predict, &self.diff, self.order);
 
         // update psi and c (h, D, y0 has changed)
-        let psi = self._calculate_psi(&self.diff);
-        self.nonlinear_problem_op().set_psi_and_y0(psi, &y_predict);
+        Self::_calculate_psi(
+            &mut self.psi,
+            &self.diff,
+            &self.gamma,
+            self.alpha[self.order].clone(),
+            self.order,
+        );
+        self.nonlinear_problem_op()
+            .set_psi_and_y0(&self.psi, &self.y_predict);
 
-        // update time
+        // update time (using compensated summation, see [Self::step])
         let t_new = {
             let state = self.state.as_ref().unwrap();
-            state.t + state.h
+            state.t.clone() + (state.h.clone() - self.t_compensation.clone())
         };
-        (y_predict, t_new)
-    }
-
-    fn handle_tstop(
-        &mut self,
-        tstop: Eqn::T,
-    ) -> Result<Option<OdeSolverStopReason<Eqn::T>>, PSError> {
-        // check if the we are at tstop
-        let state = self.state.as_ref().unwrap();
-        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
-        if abs(state.t - tstop) <= troundoff {
-            self.tstop = None;
-            return Ok(Some(OdeSolverStopReason::TstopReached));
-        } else if tstop < state.t - troundoff {
-            self.tstop = None;
-            return Err(PSError::StopBeforeCurrentTime {
-                tstop: tstop.into(),
-                t: state.t.into(),
-            });
-        }
-
-        // check if the next step will be beyond tstop, if so adjust the step size
-        if state.t + state.h > tstop + troundoff {
-            let factor = (tstop - state.t) / state.h;
-            self._update_step_size(factor);
-        }
-        Ok(None)
+        t_new
     }
 
     fn initialise_to_first_order(&mut self) {
@@ -362,7 +359,7 @@ where
 
         self.diff.column_mut(0).copy_from(&state.y);
         self.diff.column_mut(1).copy_from(&state.dy);
-        self.diff.column_mut(1).mul_assign(scale(state.h));
+        self.diff.column_mut(1).mul_assign(scale(state.h.clone()));
         if self.ode_problem.as_ref().unwrap().eqn_sens.is_some() {
             let nparams = self.ode_problem.as_ref().unwrap().eqn.rhs().nparams();
             for i in 0..nparams {
@@ -371,39 +368,41 @@ where
                 let ds = &state.ds[i];
                 sdiff.column_mut(0).copy_from(s);
                 sdiff.column_mut(1).copy_from(ds);
-                sdiff.column_mut(1).mul_assign(scale(state.h));
+                sdiff.column_mut(1).mul_assign(scale(state.h.clone()));
             }
         }
 
         // setup U
-        self.u = Self::_compute_r(self.order, Eqn::T::one());
+        self.u = compute_r::<M>(self.order, Eqn::T::one());
 
         // update statistics
-        self.statistics.initial_step_size = state.h;
+        self.statistics.initial_step_size = state.h.clone();
 
+        self.t_compensation = Eqn::T::zero();
         self.is_state_modified = false;
     }
 
     //interpolate solution at time values t* where t-h < t* < t
     //definition of the interpolating polynomial can be found on page 7 of [1]
     fn interpolate_from_diff(t: Eqn::T, diff: &M, t1: Eqn::T, h: Eqn::T, order: usize) -> Eqn::V {
-        let mut time_factor = Eqn::T::from(1.0);
+        let mut time_factor = Eqn::T::cast(1.0);
         let mut order_summation = diff.column(0).into_owned();
         for i in 0..order {
-            let i_t = Eqn::T::from(i as f64);
-            time_factor *= (t - (t1 - h * i_t)) / (h * (Eqn::T::one() + i_t));
-            order_summation += diff.column(i + 1) * scale(time_factor);
+            let i_t = Eqn::T::cast(i as f64);
+            time_factor *= (t.clone() - (t1.clone() - h.clone() * i_t.clone()))
+                / (h.clone() * (Eqn::T::one() + i_t));
+            order_summation += diff.column(i + 1) * scale(time_factor.clone());
         }
         order_summation
     }
 
+    // solve for the sensitivities at the corrected state `self.y_new`
     fn sensitivity_solve(
         &mut self,
-        y_new: &Eqn::V,
         t_new: Eqn::T,
         mut error_norm: Eqn::T,
     ) -> Result<Eqn::T, PSError> {
-        let h = self.state.as_ref().unwrap().h;
+        let h = self.state.as_ref().unwrap().h.clone();
 
         // update for new state
         {
@@ -415,7 +414,7 @@ where
                 .as_ref()
                 .unwrap()
                 .rhs()
-                .update_state(y_new, &dy_new, t_new);
+                .update_state(&self.y_new, &dy_new, t_new.clone());
         }
 
         // reuse linear solver from nonlinear solver
@@ -425,47 +424,122 @@ where
 
         // construct bdf discretisation of sensitivity equations
         let op = self.s_op.as_ref().unwrap();
-        op.set_c(h, self.alpha[self.order]);
+        op.set_c(h, self.alpha[self.order].clone());
 
         // solve for sensitivities equations discretised using BDF
-        let fun = |x: &Eqn::V, y: &mut Eqn::V| op.call_inplace(x, t_new, y);
-        let rtol = self.problem().as_ref().unwrap().rtol;
+        let fun = |x: &Eqn::V, y: &mut Eqn::V| op.call_inplace(x, t_new.clone(), y);
+        let rtol = self.problem().as_ref().unwrap().rtol.clone();
         let atol = self.problem().as_ref().unwrap().atol.clone();
         let maxiter = self.nonlinear_solver.max_iter();
-        let mut convergence = Convergence::new(rtol, atol.clone(), maxiter);
+        let mut convergence = Convergence::new(rtol.clone(), atol.clone(), maxiter);
+        convergence.set_max_rate(self.nonlinear_solver.max_rate());
+        convergence.set_criterion(self.nonlinear_solver.problem().convergence_criterion);
         let nparams = self.problem().as_ref().unwrap().eqn.rhs().nparams();
         for i in 0..nparams {
             // predict forward to new step
-            let s_predict = Self::_predict_using_diff(&self.sdiff[i], self.order);
+            Self::_predict_using_diff(&mut self.s_predict, &self.sdiff[i], self.order);
 
             // setup op
-            let psi = self._calculate_psi(&self.sdiff[i]);
-            op.set_psi_and_y0(psi, &s_predict);
+            Self::_calculate_psi(
+                &mut self.s_psi,
+                &self.sdiff[i],
+                &self.gamma,
+                self.alpha[self.order].clone(),
+                self.order,
+            );
+            op.set_psi_and_y0(&self.s_psi, &self.s_predict);
             op.eqn().as_ref().rhs().set_param_index(i);
 
             // solve
             {
                 let s_new = &mut self.state.as_mut().unwrap().s[i];
-                s_new.copy_from(&s_predict);
+                s_new.copy_from(&self.s_predict);
                 let niter = newton_iteration(s_new, fun, ls, &mut convergence)?;
                 self.statistics.number_of_nonlinear_solver_iterations += niter;
-                let s_new = &*s_new;
-                self.s_deltas[i].copy_from(&(s_new - &s_predict));
+                self.s_deltas[i].copy_from(s_new);
+                self.s_deltas[i] -= &self.s_predict;
             }
 
             let s_new = &self.state.as_ref().unwrap().s[i];
 
             if self.problem().as_ref().unwrap().sens_error_control {
-                error_norm += self.s_deltas[i].squared_norm(s_new, atol.as_ref(), rtol);
+                error_norm += self.s_deltas[i].squared_norm(s_new, atol.as_ref(), rtol.clone());
             }
         }
         if self.problem().as_ref().unwrap().sens_error_control {
-            error_norm /= Eqn::T::from(nparams as f64 + 1.0);
+            error_norm /= Eqn::T::cast(nparams as f64 + 1.0);
         }
         Ok(error_norm)
     }
 }
 
+/// Computes the R matrix with entries given by the first equation on page 8 of \[1\] (see [Bdf]).
+///
+/// This is used to update the differences matrix when step size h is varied
+/// according to factor = h_{n+1} / h_n.
+/// Note that the U matrix also defined in the same section can be also be
+/// found using factor = 1, which corresponds to R with a constant step size.
+pub(crate) fn compute_r<M: DenseMatrix>(order: usize, factor: M::T) -> M {
+    let mut r = M::zeros(order + 1, order + 1);
+
+    // r[0, 0:order] = 1
+    for j in 0..=order {
+        r[(0, j)] = M::T::one();
+    }
+    // r[i, j] = r[i, j-1] * (j - 1 - factor * i) / j
+    for i in 1..=order {
+        for j in 1..=order {
+            let i_t = M::T::cast(i as f64);
+            let j_t = M::T::cast(j as f64);
+            r[(i, j)] =
+                r[(i - 1, j)].clone() * (i_t.clone() - M::T::one() - factor.clone() * j_t) / i_t;
+        }
+    }
+    r
+}
+
+/// Updates the first `order + 1` columns of the differences matrix `diff` for a change in step size,
+/// i.e. D\[0:order+1\] = D\[0:order+1\] * R * U, using `diff_tmp` as workspace.
+pub(crate) fn update_diff_for_step_size<M: DenseMatrix>(
+    ru: &M,
+    diff: &mut M,
+    diff_tmp: &mut M,
+    order: usize,
+) {
+    {
+        let d_zero_order = diff.columns(0, order + 1);
+        let mut d_zero_order_tmp = diff_tmp.columns_mut(0, order + 1);
+        d_zero_order_tmp.gemm_vo(M::T::one(), &d_zero_order, ru, M::T::zero());
+        // diff_sub = diff * RU
+    }
+    std::mem::swap(diff, diff_tmp);
+}
+
+/// Updates the differences matrix `diff` after an accepted step, given `d = D^{order + 1}` of the new point.
+pub(crate) fn update_diff<M: DenseMatrix>(order: usize, d: &M::V, diff: &mut M)
+where
+    for<'b> &'b M::V: VectorRef<M::V>,
+{
+    //update of difference equations can be done efficiently
+    //by reusing d and D.
+    //
+    //From first equation on page 4 of [1]:
+    //d = y_n - y^0_n = D^{k + 1} y_n
+    //
+    //Standard backwards difference gives
+    //D^{j + 1} y_n = D^{j} y_n - D^{j} y_{n - 1}
+    //
+    //Combining these gives the following algorithm
+    let d_minus_order_plus_one = d - diff.column(order + 1);
+    diff.column_mut(order + 2)
+        .copy_from(&d_minus_order_plus_one);
+    diff.column_mut(order + 1).copy_from(d);
+    for i in (0..=order).rev() {
+        let tmp = diff.column(i + 1).into_owned();
+        diff.column_mut(i).add_assign(&tmp);
+    }
+}
+
 impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> OdeSolverMethod<Eqn>
     for Bdf<M, Eqn, Nls>
 where
@@ -477,6 +551,22 @@ where
         self.order
     }
 
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: true,
+            singular_mass_matrix: true,
+            roots: true,
+            sensitivities: true,
+            stiff: true,
+            max_order: self.max_order,
+            dense_output: true,
+            fixed_step: true,
+            nonlinear_rhs: true,
+            backward: true,
+            invariants: true,
+        }
+    }
+
     fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
         // state must be set
         let state = self.state.as_ref().ok_or(PSError::StateNotSet)?;
@@ -487,13 +577,17 @@ where
                 return Err(PSError::InterpolationOutsideCurrentStep);
             }
         }
-        // check that t is before the current time
-        if t > state.t {
+        // check that t is before the current time (in the direction of integration)
+        if (t.clone() - state.t.clone()) * state.h.clone() > Eqn::T::zero() {
             return Err(PSError::InterpolationBeforeCurrentTime);
         }
 
         Ok(Self::interpolate_from_diff(
-            t, &self.diff, state.t, state.h, self.order,
+            t,
+            &self.diff,
+            state.t.clone(),
+            state.h.clone(),
+            self.order,
         ))
     }
 
@@ -507,18 +601,18 @@ where
                 return Err(PSError::InterpolationOutsideCurrentStep);
             }
         }
-        // check that t is before the current time
-        if t > state.t {
+        // check that t is before the current time (in the direction of integration)
+        if (t.clone() - state.t.clone()) * state.h.clone() > Eqn::T::zero() {
             return Err(PSError::InterpolationBeforeCurrentTime);
         }
 
         let mut s = Vec::with_capacity(state.s.len());
         for i in 0..state.s.len() {
             s.push(Self::interpolate_from_diff(
-                t,
+                t.clone(),
                 &self.sdiff[i],
-                state.t,
-                state.h,
+                state.t.clone(),
+                state.h.clone(),
                 self.order,
             ));
         }
@@ -533,6 +627,10 @@ where
         self.state.as_ref()
     }
     fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.ode_problem = None;
+        self.s_op = None;
+        self.root_finder = None;
+        self.nonlinear_solver.clear_problem();
         Option::take(&mut self.state)
     }
 
@@ -541,14 +639,28 @@ where
         self.state.as_mut()
     }
 
-    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+    fn set_problem(&mut self, mut state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
         self.ode_problem = Some(problem.clone());
 
+        // with a fixed step size the initial step size is ignored
+        if let StepControl::Fixed(h) = problem.options.step_control.clone() {
+            state.h = h * problem.direction();
+        }
+
         // setup linear solver for first step
         let bdf_callable = Rc::new(BdfCallable::new(problem));
-        bdf_callable.set_c(state.h, self.alpha[self.order]);
-
-        let nonlinear_problem = SolverProblem::new_from_ode_problem(bdf_callable, problem);
+        bdf_callable.set_c(state.h.clone(), self.alpha[self.order].clone());
+
+        let mut nonlinear_problem = SolverProblem::new_from_ode_problem(bdf_callable, problem);
+        nonlinear_problem.jacobian_update = problem.options.jacobian_update.clone();
+        nonlinear_problem.constraints = problem.constraints.clone();
+        let max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::NEWTON_MAXITER);
+        self.nonlinear_solver.set_max_iter(max_iter);
+        self.nonlinear_solver
+            .set_max_rate(problem.options.max_convergence_rate.clone());
         self.nonlinear_solver.set_problem(&nonlinear_problem);
 
         // store state and setup root solver
@@ -559,7 +671,7 @@ where
             self.root_finder
                 .as_ref()
                 .unwrap()
-                .init(root_fn.as_ref(), &state.y, state.t);
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
         }
 
         // allocate internal state
@@ -568,6 +680,11 @@ where
             self.diff = M::zeros(nstates, Self::MAX_ORDER + 3);
             self.diff_tmp = M::zeros(nstates, Self::MAX_ORDER + 3);
             self.y_delta = <Eqn::V as Vector>::zeros(nstates);
+            self.y_predict = <Eqn::V as Vector>::zeros(nstates);
+            self.y_new = <Eqn::V as Vector>::zeros(nstates);
+            self.psi = <Eqn::V as Vector>::zeros(nstates);
+            self.s_predict = <Eqn::V as Vector>::zeros(nstates);
+            self.s_psi = <Eqn::V as Vector>::zeros(nstates);
         }
 
         // allocate internal state for sensitivities
@@ -602,22 +719,40 @@ where
         if self.state.is_none() {
             return Err(PSError::StateNotSet);
         }
+        let step_control = self.problem().unwrap().options.step_control.clone();
 
         if self.is_state_modified {
             self.initialise_to_first_order();
+            // the step size and order may have changed, so the nonlinear problem needs updating
+            self.nonlinear_problem_op().set_c(
+                self.state.as_ref().unwrap().h.clone(),
+                self.alpha[self.order].clone(),
+            );
+            let t = self.state.as_ref().unwrap().t.clone();
+            let x = &self.state.as_ref().unwrap().y;
+            self.nonlinear_solver.reset_jacobian(x, t);
         }
 
-        let (mut y_predict, mut t_new) = self._predict_forward();
+        let mut t_new = self._predict_forward();
+
+        // re-evaluate the jacobian at the start of the step if required by the jacobian update policy
+        if self.nonlinear_problem_op().new_step() {
+            self.nonlinear_solver
+                .reset_jacobian(&self.y_predict, self.state.as_ref().unwrap().t.clone());
+            updated_jacobian = true;
+        }
 
         // loop until step is accepted
-        let y_new = loop {
-            let mut y_new = y_predict.clone();
+        loop {
+            self.y_new.copy_from(&self.y_predict);
 
             // initialise error_norm to quieten the compiler
-            error_norm = Eqn::T::from(2.0);
+            error_norm = Eqn::T::cast(2.0);
 
             // solve BDF equation using y0 as starting point
-            let mut solve_result = self.nonlinear_solver.solve_in_place(&mut y_new, t_new);
+            let mut solve_result = self
+                .nonlinear_solver
+                .solve_in_place(&mut self.y_new, t_new.clone());
             // update statistics
             self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
 
@@ -627,26 +762,26 @@ where
                 // combine eq 3, 4 and 6 from [1] to obtain error
                 // Note that error = C_k * h^{k+1} y^{k+1}
                 // and d = D^{k+1} y_{n+1} \approx h^{k+1} y^{k+1}
-                self.y_delta.copy_from(&y_new);
-                self.y_delta -= &y_predict;
+                self.y_delta.copy_from(&self.y_new);
+                self.y_delta -= &self.y_predict;
 
                 // calculate error norm
                 {
-                    let rtol = self.problem().as_ref().unwrap().rtol;
+                    let rtol = self.problem().as_ref().unwrap().rtol.clone();
                     let atol = self.ode_problem.as_ref().unwrap().atol.as_ref();
-                    error_norm = self.y_delta.squared_norm(&y_new, atol, rtol)
-                        * self.error_const2[self.order];
+                    error_norm = self.y_delta.squared_norm(&self.y_new, atol, rtol)
+                        * self.error_const2[self.order].clone();
                 }
 
                 // only bother doing sensitivity calculations if we might keep the step
                 if self.ode_problem.as_ref().unwrap().eqn_sens.is_some()
-                    && error_norm <= Eqn::T::from(1.0)
+                    && error_norm <= Eqn::T::cast(1.0)
                 {
-                    error_norm = match self.sensitivity_solve(&y_new, t_new, error_norm) {
+                    error_norm = match self.sensitivity_solve(t_new.clone(), error_norm) {
                         Ok(en) => en,
                         Err(_) => {
                             solve_result = Err(PSError::SensitivityError);
-                            Eqn::T::from(2.0)
+                            Eqn::T::cast(2.0)
                         }
                     }
                 }
@@ -656,19 +791,36 @@ where
             if solve_result.is_err() {
                 self.statistics.number_of_nonlinear_solver_fails += 1;
                 if updated_jacobian {
+                    // the step size cannot be reduced if it is fixed, so fail
+                    if let StepControl::Fixed(_) = step_control {
+                        return Err(PSError::FixedStepNonlinearSolverFailure {
+                            t: self.state.as_ref().unwrap().t.to_string(),
+                        });
+                    }
                     // newton iteration did not converge, but jacobian has already been
-                    // evaluated so reduce step size by 0.3 (as per [1]) and try again
-                    self._update_step_size(Eqn::T::from(0.3));
+                    // evaluated so reduce step size by 0.3 (as per [1]), or further if the
+                    // convergence rate predicts it, and try again
+                    let mut factor = Eqn::T::cast(0.3);
+                    if let Some(rate_factor) = self.convergence_rate_factor() {
+                        if rate_factor < factor {
+                            factor = if rate_factor > Eqn::T::cast(Self::MIN_FACTOR) {
+                                rate_factor
+                            } else {
+                                Eqn::T::cast(Self::MIN_FACTOR)
+                            };
+                        }
+                    }
+                    self._update_step_size(factor);
 
                     // new prediction
-                    (y_predict, t_new) = self._predict_forward();
+                    t_new = self._predict_forward();
 
                     // update statistics
                 } else {
                     // newton iteration did not converge, so update jacobian and try again
                     self.nonlinear_problem_op().set_jacobian_is_stale();
                     self.nonlinear_solver
-                        .reset_jacobian(&y_predict, self.state.as_ref().unwrap().t);
+                        .reset_jacobian(&self.y_predict, self.state.as_ref().unwrap().t.clone());
                     updated_jacobian = true;
                     // same prediction as last time
                 }
@@ -678,53 +830,70 @@ where
             // need to caulate safety even if step is accepted
             let maxiter = self.nonlinear_solver.max_iter() as f64;
             let niter = self.nonlinear_solver.niter() as f64;
-            safety = Eqn::T::from(0.9 * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
+            let safety_factor: f64 = self.problem().unwrap().options.safety_factor.to_f64();
+            safety = Eqn::T::cast(safety_factor * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
 
-            // do the error test
-            if error_norm <= Eqn::T::from(1.0) {
+            // do the error test (there is no error control with a fixed step size)
+            if error_norm <= Eqn::T::cast(1.0) || matches!(step_control, StepControl::Fixed(_)) {
                 // step is accepted
-                break y_new;
+                break;
             } else {
                 // step is rejected
                 // calculate optimal step size factor as per eq 2.46 of [2]
                 // and reduce step size and try again
                 let order = self.order as f64;
-                let mut factor = safety * error_norm.pow(Eqn::T::from(-0.5 / (order + 1.0)));
-                if factor < Eqn::T::from(Self::MIN_FACTOR) {
-                    factor = Eqn::T::from(Self::MIN_FACTOR);
+                let mut factor = safety * error_norm.pow(Eqn::T::cast(-0.5 / (order + 1.0)));
+                if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                    factor = Eqn::T::cast(Self::MIN_FACTOR);
                 }
                 // todo, do we need to update the linear solver problem here since we converged?
                 self._update_step_size(factor);
 
                 // if step size too small, then fail
                 let state = self.state.as_ref().unwrap();
-                if state.h < Eqn::T::from(Self::MIN_TIMESTEP) {
-                    return Err(PSError::StepSizeTooSmall { t: state.t.into() });
+                if abs(state.h.clone()) < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                    return Err(PSError::StepSizeTooSmall {
+                        t: state.t.to_string(),
+                    });
                 }
 
                 // new prediction
-                (y_predict, t_new) = self._predict_forward();
+                t_new = self._predict_forward();
 
                 // update statistics
                 self.statistics.number_of_error_test_failures += 1;
             }
-        };
+        }
         // take the accepted step
         self.update_differences();
 
         {
+            // accumulate time using compensated summation to avoid drift over many small steps
             let state = self.state.as_mut().unwrap();
-            state.y = y_new;
-            state.t += state.h;
+            std::mem::swap(&mut state.y, &mut self.y_new);
+            state.t = compensated_add(state.t.clone(), state.h.clone(), &mut self.t_compensation);
             state.dy.copy_from_view(&self.diff.column(1));
-            state.dy *= scale(Eqn::T::one() / state.h);
+            state.dy *= scale(Eqn::T::one() / state.h.clone());
+        }
+
+        // project the solution onto the invariants of the equations (if any), the differences are
+        // updated so that the next prediction starts from the projected solution
+        if self.ode_problem.as_ref().unwrap().eqn.has_invariants() {
+            let state = self.state.as_mut().unwrap();
+            self.ode_problem
+                .as_ref()
+                .unwrap()
+                .project(&mut state.y, state.t.clone())?;
+            self.diff.column_mut(0).copy_from(&state.y);
         }
 
         // update statistics
         self.statistics.number_of_linear_solver_setups =
             self.nonlinear_problem_op().number_of_jac_evals();
         self.statistics.number_of_steps += 1;
-        self.statistics.final_step_size = self.state.as_ref().unwrap().h;
+        self.statistics.linear_solver = self.nonlinear_solver.linear_solver_statistics();
+        self.statistics.nonlinear_solver = self.nonlinear_solver.get_statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
 
         // a change in order is only done after running at order k for k + 1 steps
         // (see page 83 of [2])
@@ -733,51 +902,52 @@ where
         if self.n_equal_steps > self.order {
             let state = self.state.as_ref().unwrap();
             let atol = self.problem().as_ref().unwrap().atol.as_ref();
-            let rtol = self.problem().as_ref().unwrap().rtol;
+            let rtol = self.problem().as_ref().unwrap().rtol.clone();
             let order = self.order;
             // similar to the optimal step size factor we calculated above for the current
             // order k, we need to calculate the optimal step size factors for orders
             // k-1 and k+1. To do this, we note that the error = C_k * D^{k+1} y_n
-            let error_m_norm = if order > 1 {
-                let mut error_m_norm = self.diff.column(order).squared_norm(&state.y, atol, rtol)
-                    * self.error_const2[order - 1];
+            let error_m_norm = if order > self.min_order {
+                let mut error_m_norm =
+                    self.diff
+                        .column(order)
+                        .squared_norm(&state.y, atol, rtol.clone())
+                        * self.error_const2[order - 1].clone();
                 for i in 0..self.sdiff.len() {
                     error_m_norm +=
                         self.sdiff[i]
                             .column(order)
-                            .squared_norm(&state.s[i], atol, rtol)
-                            * self.error_const2[order - 1];
+                            .squared_norm(&state.s[i], atol, rtol.clone())
+                            * self.error_const2[order - 1].clone();
                 }
-                error_m_norm / Eqn::T::from((self.sdiff.len() + 1) as f64)
+                error_m_norm / Eqn::T::cast((self.sdiff.len() + 1) as f64)
             } else {
                 Eqn::T::INFINITY
             };
-            let error_p_norm = if order < Self::MAX_ORDER {
-                let mut error_p_norm = self
-                    .diff
-                    .column(order + 2)
-                    .squared_norm(&state.y, atol, rtol)
-                    * self.error_const2[order + 1];
+            let error_p_norm = if order < self.max_order {
+                let mut error_p_norm =
+                    self.diff
+                        .column(order + 2)
+                        .squared_norm(&state.y, atol, rtol.clone())
+                        * self.error_const2[order + 1].clone();
                 for i in 0..self.sdiff.len() {
-                    error_p_norm =
-                        self.sdiff[i]
-                            .column(order + 2)
-                            .squared_norm(&state.s[i], atol, rtol)
-                            * self.error_const2[order + 1];
+                    error_p_norm = self.sdiff[i].column(order + 2).squared_norm(
+                        &state.s[i],
+                        atol,
+                        rtol.clone(),
+                    ) * self.error_const2[order + 1].clone();
                 }
-                error_p_norm / Eqn::T::from((self.sdiff.len() + 1) as f64)
+                error_p_norm / Eqn::T::cast((self.sdiff.len() + 1) as f64)
             } else {
                 Eqn::T::INFINITY
             };
 
             let error_norms = [error_m_norm, error_norm, error_p_norm];
-            let factors = error_norms
-                .into_iter()
-                .enumerate()
-                .map(|(i, error_norm)| {
-                    error_norm.pow(Eqn::T::from(-0.5 / (i as f64 + order as f64)))
-                })
-                .collect::<Vec<_>>();
+            let factors: [Eqn::T; 3] = std::array::from_fn(|i| {
+                error_norms[i]
+                    .clone()
+                    .pow(Eqn::T::cast(-0.5 / (i as f64 + order as f64)))
+            });
 
             // now we have the three factors for orders k-1, k and k+1, pick the maximum in
             // order to maximise the resultant step size
@@ -793,48 +963,76 @@ where
                 self.order += max_index - 1;
             }
 
-            let mut factor = safety * factors[max_index];
-            if factor > Eqn::T::from(Self::MAX_FACTOR) {
-                factor = Eqn::T::from(Self::MAX_FACTOR);
-            }
+            let factor = match step_control {
+                StepControl::Adaptive => {
+                    let mut factor = safety * factors[max_index].clone();
+                    if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                        factor = Eqn::T::cast(Self::MAX_FACTOR);
+                    }
+                    // the convergence rate of the Newton iteration grows roughly in proportion to the step size, so
+                    // optionally limit any increase so that the predicted rate stays below a fraction of the maximum rate
+                    if let Some(rate_factor) = self.convergence_rate_factor() {
+                        if factor > Eqn::T::one() && factor > rate_factor {
+                            factor = if rate_factor > Eqn::T::one() {
+                                rate_factor
+                            } else {
+                                Eqn::T::one()
+                            };
+                        }
+                    }
+                    factor
+                }
+                // the order can still change, but the step size is restored to the fixed step size
+                StepControl::Fixed(h) => {
+                    h * self.problem().unwrap().direction() / self.state.as_ref().unwrap().h.clone()
+                }
+            };
             self._update_step_size(factor);
-        }
-
-        // check for root within accepted step
-        if let Some(root_fn) = self.problem().as_ref().unwrap().eqn.root() {
-            let ret = self.root_finder.as_ref().unwrap().check_root(
-                &|t| self.interpolate(t),
-                root_fn.as_ref(),
-                &self.state.as_ref().unwrap().y,
-                self.state.as_ref().unwrap().t,
-            );
-            if let Some(root) = ret {
-                return Ok(OdeSolverStopReason::RootFound(root));
-            }
-        }
-
-        if let Some(tstop) = self.tstop {
-            if let Some(reason) = self.handle_tstop(tstop).unwrap() {
-                return Ok(reason);
+        } else if let StepControl::Fixed(h) = step_control {
+            // restore the fixed step size if the last step was shortened to stop at tstop
+            let h = h * self.problem().unwrap().direction();
+            let state_h = self.state.as_ref().unwrap().h.clone();
+            if state_h != h {
+                self._update_step_size(h / state_h);
             }
         }
 
-        // just a normal step, no roots or tstop reached
-        Ok(OdeSolverStopReason::InternalTimestep)
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
     }
 
     fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
-        self.tstop = Some(tstop);
-        if let Some(OdeSolverStopReason::TstopReached) = self.handle_tstop(tstop)? {
-            return {
-                self.tstop = None;
-                Err(PSError::StopBeforeCurrentTime {
-                    tstop: tstop.into(),
-                    t: self.state.as_ref().unwrap().t.into(),
-                })
-            };
-        }
-        Ok(())
+        method::set_stop_time(self, tstop)
+    }
+}
+
+impl<M: DenseMatrix<T = Eqn::T, V = Eqn::V>, Eqn: OdeEquations, Nls> StepChecks<Eqn>
+    for Bdf<M, Eqn, Nls>
+where
+    Nls: NonLinearSolver<BdfCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self._update_step_size(factor);
     }
 }
 
@@ -843,30 +1041,43 @@ mod test {
     use crate::{
         ode_solver::{
             test_models::{
-                dydt_y2::dydt_y2_problem,
+                dydt_y2::{dydt_y2_problem, dydt_y2_problem_colored},
                 exponential_decay::{
                     exponential_decay_problem, exponential_decay_problem_sens,
-                    exponential_decay_problem_with_root,
+                    exponential_decay_problem_with_root, exponential_decay_problem_with_tol,
                 },
                 exponential_decay_with_algebraic::{
                     exponential_decay_with_algebraic_problem,
                     exponential_decay_with_algebraic_problem_sens,
                 },
                 gaussian_decay::gaussian_decay_problem,
-                robertson::robertson,
+                harmonic_oscillator::harmonic_oscillator_problem,
+                robertson::{robertson, robertson_colored, robertson_with_builder},
                 robertson_ode::robertson_ode,
                 robertson_ode_with_sens::robertson_ode_with_sens,
                 robertson_sens::robertson_sens,
             },
             tests::{
-                test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut,
-                test_state_mut_on_problem,
+                test_backward_exponential_decay, test_breakpoints_exponential_decay,
+                test_capabilities, test_global_error_exponential_decay, test_interpolate,
+                test_invariant_harmonic_oscillator, test_no_set_problem, test_ode_solver,
+                test_root_fn_exponential_decay, test_solution_bound_exponential_decay,
+                test_solve_sweep_exponential_decay, test_state_mut, test_state_mut_on_problem,
             },
         },
-        Bdf, FaerSparseLU, NewtonNonlinearSolver, OdeEquations, Op, SparseColMat,
+        Bdf, BroydenSolver, ConvergenceCriterion, JacobianUpdatePolicy, Matrix, NonLinearSolver,
+        OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op,
+        StepControl, Vector,
     };
+    #[cfg(feature = "faer")]
+    use crate::{FaerSparseLU, NewtonNonlinearSolver, SparseColMat};
 
+    use super::BdfStatistics;
+    #[cfg(feature = "faer")]
     use faer::Mat;
+    #[cfg(feature = "faer")]
+    use nalgebra_sparse::CsrMatrix;
+
     use num_traits::abs;
 
     type M = nalgebra::DMatrix<f64>;
@@ -883,17 +1094,90 @@ mod test {
         test_interpolate::<M, _>(Bdf::default())
     }
 
+    #[test]
+    fn bdf_test_capabilities_robertson() {
+        let (problem, _soln) = robertson::<M>();
+        test_capabilities(Bdf::default(), problem);
+    }
+
     #[test]
     fn bdf_test_state_mut_exponential_decay() {
-        let (p, soln) = exponential_decay_problem::<M>(false);
+        let (p, soln) = exponential_decay_problem::<M>();
         let s = Bdf::default();
         test_state_mut_on_problem(s, p, soln);
     }
 
+    #[test]
+    fn bdf_test_solve_sweep_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_solve_sweep_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_solve_sweep_exponential_decay_with_algebraic() {
+        // dy/dt = -a y, 0 = z - y, so each solve after the first starts from the previous algebraic state z
+        let (mut problem, _soln) = exponential_decay_with_algebraic_problem::<M>();
+        let t = 1.0;
+        let params = [0.1, 0.2, 0.5]
+            .iter()
+            .map(|&a| nalgebra::DVector::from_vec(vec![a]))
+            .collect::<Vec<_>>();
+        let solns = Bdf::default()
+            .solve_sweep(&mut problem, &params, t)
+            .unwrap();
+        for (p, soln) in params.iter().zip(solns.iter()) {
+            let expect = nalgebra::DVector::from_element(3, (-p[0] * t).exp());
+            soln.assert_eq_st(&expect, 1e-4);
+        }
+    }
+
+    #[test]
+    fn bdf_test_solution_bound_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_solution_bound_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_backward_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_backward_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_backward_fixed_step_exponential_decay() {
+        let (mut p, _soln) = exponential_decay_problem::<M>();
+        p.options.step_control = StepControl::Fixed(0.1);
+        test_backward_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_breakpoints_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_breakpoints_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_root_fn_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_root_fn_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_global_error_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_global_error_exponential_decay(Bdf::default(), p);
+    }
+
+    #[test]
+    fn bdf_test_invariant_harmonic_oscillator() {
+        let (p, soln) = harmonic_oscillator_problem::<M>();
+        test_invariant_harmonic_oscillator(Bdf::default(), p, soln);
+    }
+
     #[test]
     fn bdf_test_nalgebra_exponential_decay() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -913,20 +1197,43 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn bdf_broyden_test_nalgebra_exponential_decay() {
+        let mut s = Bdf::<M, _, _>::new(BroydenSolver::new());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        // the jacobian is never evaluated
+        assert_eq!(
+            problem
+                .eqn
+                .as_ref()
+                .rhs()
+                .statistics()
+                .number_of_matrix_evals,
+            0
+        );
+        assert_eq!(
+            problem.eqn.as_ref().rhs().statistics().number_of_jac_muls,
+            0
+        );
+    }
+
+    #[cfg(feature = "faer")]
     #[test]
     fn bdf_test_faer_sparse_exponential_decay() {
         let linear_solver = FaerSparseLU::default();
         let nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
         let mut s = Bdf::<Mat<f64>, _, _>::new(nonlinear_solver);
-        let (problem, soln) = exponential_decay_problem::<SparseColMat<f64>>(false);
+        let (problem, soln) = exponential_decay_problem::<SparseColMat<f64>>();
         test_ode_solver(&mut s, &problem, soln, None, false);
     }
 
+    #[cfg(feature = "faer")]
     #[test]
     fn bdf_test_faer_exponential_decay() {
         type M = faer::Mat<f64>;
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -946,10 +1253,27 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn bdf_test_f32_exponential_decay() {
+        // f32 is not convertible from f64, so this only relies on the RealField conversions of the scalar.
+        // The default tolerances are close to the f32 rounding error, so looser ones are used here
+        let mut s = Bdf::default();
+        let (problem, soln) =
+            exponential_decay_problem_with_tol::<nalgebra::DMatrix<f32>>(1e-4, 1e-4);
+        test_ode_solver(&mut s, &problem, soln, Some(1e-3), false);
+
+        #[cfg(feature = "faer")]
+        {
+            let mut s = Bdf::default();
+            let (problem, soln) = exponential_decay_problem_with_tol::<faer::Mat<f32>>(1e-4, 1e-4);
+            test_ode_solver(&mut s, &problem, soln, Some(1e-3), false);
+        }
+    }
+
     #[test]
     fn bdf_test_nalgebra_exponential_decay_sens() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_problem_sens::<M>(false);
+        let (problem, soln) = exponential_decay_problem_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -972,7 +1296,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_exponential_decay_algebraic() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_with_algebraic_problem::<M>(false);
+        let (problem, soln) = exponential_decay_with_algebraic_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -992,19 +1316,20 @@ mod test {
         "###);
     }
 
+    #[cfg(feature = "faer")]
     #[test]
     fn bdf_test_faer_sparse_exponential_decay_algebraic() {
         let linear_solver = FaerSparseLU::default();
         let nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
         let mut s = Bdf::<Mat<f64>, _, _>::new(nonlinear_solver);
-        let (problem, soln) = exponential_decay_with_algebraic_problem::<SparseColMat<f64>>(false);
+        let (problem, soln) = exponential_decay_with_algebraic_problem::<SparseColMat<f64>>();
         test_ode_solver(&mut s, &problem, soln, None, false);
     }
 
     #[test]
     fn test_bdf_nalgebra_exponential_decay_algebraic_sens() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_with_algebraic_problem_sens::<M>(false);
+        let (problem, soln) = exponential_decay_with_algebraic_problem_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1027,7 +1352,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_robertson() {
         let mut s = Bdf::default();
-        let (problem, soln) = robertson::<M>(false);
+        let (problem, soln) = robertson::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1047,19 +1372,79 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn test_bdf_nalgebra_robertson_max_iter() {
+        let mut s = Bdf::default();
+        let (mut problem, soln) = robertson::<M>();
+        problem.options.max_nonlinear_solver_iterations = Some(10);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert_eq!(s.nonlinear_solver.max_iter(), 10);
+    }
+
+    fn robertson_statistics(builder: OdeBuilder) -> BdfStatistics<f64> {
+        let mut s = Bdf::default();
+        let (problem, soln) = robertson_with_builder::<M>(builder);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        s.get_statistics().clone()
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_safety_factor() {
+        // a smaller safety factor gives more cautious step sizes, so more steps are taken
+        let default = robertson_statistics(OdeBuilder::new());
+        let cautious = robertson_statistics(OdeBuilder::new().safety_factor(0.5));
+        assert!(
+            cautious.number_of_steps > default.number_of_steps,
+            "{} <= {}",
+            cautious.number_of_steps,
+            default.number_of_steps
+        );
+    }
+
+    #[test]
+    fn test_bdf_nalgebra_robertson_max_convergence_rate() {
+        // a smaller maximum convergence rate treats slowly converging Newton iterations as failures
+        let default = robertson_statistics(OdeBuilder::new());
+        let strict = robertson_statistics(OdeBuilder::new().max_convergence_rate(0.05));
+        assert!(
+            strict.number_of_nonlinear_solver_fails > default.number_of_nonlinear_solver_fails,
+            "{} <= {}",
+            strict.number_of_nonlinear_solver_fails,
+            default.number_of_nonlinear_solver_fails
+        );
+    }
+
+    #[cfg(feature = "faer")]
     #[test]
     fn bdf_test_faer_sparse_robertson() {
         let linear_solver = FaerSparseLU::default();
         let nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
         let mut s = Bdf::<Mat<f64>, _, _>::new(nonlinear_solver);
-        let (problem, soln) = robertson::<SparseColMat<f64>>(false);
+        let (problem, soln) = robertson::<SparseColMat<f64>>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn bdf_test_csr_exponential_decay() {
+        // the default linear solver for CSR matrices factorises a CSC copy of the iteration matrix
+        let mut s = Bdf::default();
+        let (problem, soln) = exponential_decay_problem::<CsrMatrix<f64>>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn bdf_test_csr_robertson() {
+        let mut s = Bdf::default();
+        let (problem, soln) = robertson::<CsrMatrix<f64>>();
         test_ode_solver(&mut s, &problem, soln, None, false);
     }
 
     #[test]
     fn test_bdf_nalgebra_robertson_sens() {
         let mut s = Bdf::default();
-        let (problem, soln) = robertson_sens::<M>(false);
+        let (problem, soln) = robertson_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1082,7 +1467,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_robertson_colored() {
         let mut s = Bdf::default();
-        let (problem, soln) = robertson::<M>(true);
+        let (problem, soln) = robertson_colored::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1097,7 +1482,7 @@ mod test {
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
         number_of_calls: 860
-        number_of_jac_muls: 60
+        number_of_jac_muls: 57
         number_of_matrix_evals: 19
         "###);
     }
@@ -1105,7 +1490,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_robertson_ode() {
         let mut s = Bdf::default();
-        let (problem, soln) = robertson_ode::<M>(false);
+        let (problem, soln) = robertson_ode::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1128,7 +1513,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_robertson_ode_sens() {
         let mut s = Bdf::default();
-        let (problem, soln) = robertson_ode_with_sens::<M>(false);
+        let (problem, soln) = robertson_ode_with_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1151,7 +1536,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_dydt_y2() {
         let mut s = Bdf::default();
-        let (problem, soln) = dydt_y2_problem::<M>(false, 10);
+        let (problem, soln) = dydt_y2_problem::<M>(10);
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1174,7 +1559,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_dydt_y2_colored() {
         let mut s = Bdf::default();
-        let (problem, soln) = dydt_y2_problem::<M>(true, 10);
+        let (problem, soln) = dydt_y2_problem_colored::<M>(10);
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1189,7 +1574,7 @@ mod test {
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
         number_of_calls: 452
-        number_of_jac_muls: 14
+        number_of_jac_muls: 4
         number_of_matrix_evals: 4
         "###);
     }
@@ -1197,7 +1582,7 @@ mod test {
     #[test]
     fn test_bdf_nalgebra_gaussian_decay() {
         let mut s = Bdf::default();
-        let (problem, soln) = gaussian_decay_problem::<M>(false, 10);
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -1207,7 +1592,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 148
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.00009999999999999999
-        final_step_size: 0.24155004935215604
+        final_step_size: 0.2415500493515227
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -1220,14 +1605,325 @@ mod test {
     #[test]
     fn test_tstop_bdf() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, true);
     }
 
+    #[test]
+    fn test_bdf_fixed_step_exponential_decay() {
+        let mut s = Bdf::default();
+        let (mut problem, soln) = exponential_decay_problem::<M>();
+        problem.options.step_control = StepControl::Fixed(0.3);
+        test_ode_solver(&mut s, &problem, soln, Some(1e-3), true);
+
+        // each unit interval between the stop times is covered by three steps of 0.3 and one shortened step of 0.1,
+        // after which the fixed step size is restored
+        let statistics = s.get_statistics();
+        assert_eq!(statistics.number_of_steps, 36);
+        assert_eq!(statistics.number_of_error_test_failures, 0);
+        assert_eq!(statistics.initial_step_size, 0.3);
+        assert!(abs(s.state().unwrap().h - 0.3) < 1e-12);
+    }
+
+    #[test]
+    fn test_bdf_max_order_robertson_ode() {
+        let mut s = Bdf::default();
+        s.set_max_order(2).unwrap();
+        assert_eq!(
+            s.set_max_order(6).unwrap_err().to_string(),
+            "Order 6 is outside the allowed range 1..=5"
+        );
+        let (problem, soln) = robertson_ode::<M>();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        let mut max_order = 0;
+        for point in soln.solution_points.iter() {
+            while s.state().unwrap().t < point.t {
+                s.step().unwrap();
+                max_order = max_order.max(s.order());
+            }
+            let error = s.interpolate(point.t).unwrap() - &point.state;
+            let error_norm = error
+                .squared_norm(&point.state, &problem.atol, problem.rtol)
+                .sqrt();
+            assert!(
+                error_norm < 15.0,
+                "error_norm: {} at t = {}",
+                error_norm,
+                point.t
+            );
+        }
+        assert_eq!(max_order, 2);
+    }
+
+    #[test]
+    fn test_bdf_min_order_exponential_decay() {
+        let mut s = Bdf::default();
+        s.set_min_order(3).unwrap();
+        assert!(s.set_max_order(2).is_err());
+        assert!(s.set_min_order(0).is_err());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+
+        // the order starts at 1 and increases, but once it reaches the minimum it stays at or above it
+        let mut reached_min_order = false;
+        for point in soln.solution_points.iter() {
+            while s.state().unwrap().t < point.t {
+                s.step().unwrap();
+                reached_min_order |= s.order() >= 3;
+                assert!(!reached_min_order || s.order() >= 3);
+            }
+            let error = s.interpolate(point.t).unwrap() - &point.state;
+            let error_norm = error
+                .squared_norm(&point.state, &problem.atol, problem.rtol)
+                .sqrt();
+            assert!(
+                error_norm < 15.0,
+                "error_norm: {} at t = {}",
+                error_norm,
+                point.t
+            );
+        }
+        assert!(reached_min_order);
+    }
+
+    fn diagonal_mass_problem<M: Matrix<T = f64>>(
+    ) -> OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = f64>> {
+        // dy/dt = -y, 0 = z - y
+        OdeBuilder::new()
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode_with_diagonal_mass::<M, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = -x[0];
+                    y[1] = x[1] - x[0];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = -v[0];
+                    y[1] = v[1] - v[0];
+                },
+                vec![1.0, 0.0],
+                |_p, _t| M::V::from_element(2, 1.0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_bdf_diagonal_mass() {
+        let problem = diagonal_mass_problem::<M>();
+        let y = Bdf::default()
+            .solve(&problem, 1.0)
+            .unwrap()
+            .y
+            .pop()
+            .unwrap();
+        let expect = (-1.0f64).exp();
+        assert!((y[0] - expect).abs() < 1e-5 && (y[1] - expect).abs() < 1e-5);
+        assert!(
+            problem
+                .eqn
+                .mass()
+                .unwrap()
+                .statistics()
+                .number_of_matrix_evals
+                > 0
+        );
+
+        // the diagonal must have an entry for each state
+        assert!(OdeBuilder::new()
+            .build_ode_with_diagonal_mass::<M, _, _, _>(
+                |x, _p, _t, y| y.copy_from(x),
+                |_x, _p, _t, v, y| y.copy_from(v),
+                vec![1.0],
+                |_p, _t| nalgebra::DVector::from_element(2, 1.0),
+            )
+            .is_err());
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn test_bdf_diagonal_mass_sparse() {
+        let problem = diagonal_mass_problem::<SparseColMat<f64>>();
+        let nonlinear_solver = NewtonNonlinearSolver::new(FaerSparseLU::default());
+        let mut s = Bdf::<Mat<f64>, _, _>::new(nonlinear_solver);
+        let y = s.solve(&problem, 1.0).unwrap().y.pop().unwrap();
+        let expect = (-1.0f64).exp();
+        assert!((y[0] - expect).abs() < 1e-5 && (y[1] - expect).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_bdf_natural_monotonicity_robertson() {
+        let (mut problem, soln) = robertson::<M>();
+        problem.options.convergence_criterion = ConvergenceCriterion::NaturalMonotonicity;
+        let mut s = Bdf::default();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert_eq!(
+            s.nonlinear_solver.problem().convergence_criterion,
+            ConvergenceCriterion::NaturalMonotonicity
+        );
+    }
+
+    #[test]
+    fn test_bdf_nonlinear_solver_statistics() {
+        let (problem, soln) = robertson_ode::<M>();
+        let t = soln.solution_points.last().unwrap().t;
+        let mut s = Bdf::default();
+        s.solve(&problem, t).unwrap();
+        let stats = s.get_statistics();
+        let nls = stats.nonlinear_solver;
+        assert!(nls.number_of_solves >= stats.number_of_steps);
+        assert!(nls.number_of_iterations >= nls.number_of_solves);
+        assert_eq!(
+            nls.number_of_failures,
+            stats.number_of_nonlinear_solver_fails
+        );
+        assert!(nls.number_of_jacobian_updates > 0);
+        let rate = nls.average_convergence_rate().unwrap();
+        assert!(rate > 0.0 && rate < 1.0);
+    }
+
+    #[test]
+    fn test_bdf_jacobian_update_policy() {
+        let solve = |policy: JacobianUpdatePolicy<f64>| {
+            let (mut problem, soln) = robertson_ode::<M>();
+            problem.options.jacobian_update = policy;
+            let t = soln.solution_points.last().unwrap().t;
+            let mut s = Bdf::default();
+            let y = s.solve(&problem, t).unwrap().y.pop().unwrap();
+            let stats = s.get_statistics().clone();
+            let evals = problem.eqn.rhs().statistics().number_of_matrix_evals;
+            (y, stats, evals)
+        };
+        let (y_expect, stats, evals) = solve(JacobianUpdatePolicy::OnConvergenceFailure);
+        let niter = stats.number_of_nonlinear_solver_iterations;
+
+        // the jacobian is re-evaluated at least as often as the policy requires, and the fresher jacobian means fewer iterations
+        let (y, stats, every_iteration_evals) = solve(JacobianUpdatePolicy::EveryIteration);
+        y.assert_eq(&y_expect, &y_expect.map(|x| 1e-3 * x.abs() + 1e-8));
+        assert!(every_iteration_evals >= stats.number_of_nonlinear_solver_iterations);
+        assert!(stats.number_of_nonlinear_solver_iterations < niter);
+
+        let (y, stats, every_step_evals) = solve(JacobianUpdatePolicy::EveryStep);
+        y.assert_eq(&y_expect, &y_expect.map(|x| 1e-3 * x.abs() + 1e-8));
+        assert!(every_step_evals >= stats.number_of_steps);
+        assert!(every_step_evals < every_iteration_evals);
+        assert!(stats.number_of_nonlinear_solver_iterations < niter);
+
+        for policy in [
+            JacobianUpdatePolicy::AfterSteps(10),
+            JacobianUpdatePolicy::OnStepSizeChange(0.3),
+        ] {
+            let (y, stats, policy_evals) = solve(policy);
+            y.assert_eq(&y_expect, &y_expect.map(|x| 1e-3 * x.abs() + 1e-8));
+            if let JacobianUpdatePolicy::AfterSteps(n) = policy {
+                assert!(policy_evals >= stats.number_of_steps / n);
+            }
+            assert!(policy_evals > evals && policy_evals < every_step_evals);
+            assert!(stats.number_of_nonlinear_solver_iterations < niter);
+        }
+    }
+
+    #[test]
+    fn test_bdf_convergence_rate_step_limit() {
+        let solve = |limit: Option<f64>| {
+            let (mut problem, soln) = robertson_ode::<M>();
+            problem.options.convergence_rate_step_limit = limit;
+            let t = soln.solution_points.last().unwrap().t;
+            let mut s = Bdf::default();
+            let y = s.solve(&problem, t).unwrap().y.pop().unwrap();
+            (y, s.get_statistics().number_of_nonlinear_solver_fails)
+        };
+        let (y_expect, fails) = solve(None);
+        // limiting the step size using the convergence rate avoids some of the convergence failures
+        let (y, limited_fails) = solve(Some(0.5));
+        y.assert_eq(&y_expect, &y_expect.map(|x| 1e-3 * x.abs() + 1e-8));
+        assert!(
+            limited_fails < fails,
+            "{} convergence failures with the step size limited by the convergence rate, {} without",
+            limited_fails,
+            fails
+        );
+    }
+
+    #[test]
+    fn test_bdf_constraints() {
+        // an enzyme-catalysed reaction y' = -y / (k + y) uses up the substrate y at a near constant rate, then decays quickly,
+        // the nonlinear iterates tend to overshoot below zero where the rate is wrong (and singular at y = -k)
+        type V = nalgebra::DVector<f64>;
+        let k = 1e-4;
+        let rhs = move |x: &V, _p: &V, _t, y: &mut V| y[0] = -x[0] / (k + x[0]);
+        let rhs_jac =
+            move |x: &V, _p: &V, _t, v: &V, y: &mut V| y[0] = -k / ((k + x[0]) * (k + x[0])) * v[0];
+        let solve = |constrained: bool| {
+            let mut problem = OdeBuilder::new()
+                .rtol(1e-3)
+                .atol([1e-6])
+                .build_ode::<M, _, _, _>(rhs, rhs_jac, |_p: &V, _t| V::from_element(1, 1.0))
+                .unwrap();
+            if constrained {
+                problem = problem
+                    .with_constraints(V::from_element(1, 0.0), V::from_element(1, f64::INFINITY))
+                    .unwrap();
+            }
+            let mut s = Bdf::default();
+            let state = OdeSolverState::new(&problem, &s).unwrap();
+            s.set_problem(state, &problem);
+            let mut min_state = f64::INFINITY;
+            while s.state().unwrap().t < 2.0 && s.step().is_ok() {
+                min_state = min_state.min(s.state().unwrap().y[0]);
+            }
+            let state = s.state().unwrap();
+            (min_state, state.y[0], state.t)
+        };
+        let (min_state, _y, _t) = solve(false);
+        assert!(min_state < 0.0);
+        let (min_state, y, t) = solve(true);
+        assert!(min_state >= 0.0);
+        assert!(t >= 2.0 && y < 1e-3, "y = {} at t = {}", y, t);
+
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        assert!(problem
+            .with_constraints(V::from_element(1, 0.0), V::from_element(1, 1.0))
+            .is_err());
+    }
+
+    #[test]
+    fn test_bdf_static_one_compartment() {
+        // one compartment model with first-order absorption from the depot, p = [ka, k]
+        type V = nalgebra::DVector<f64>;
+        type SV = nalgebra::SVector<f64, 2>;
+        let a = |p: &V| nalgebra::SMatrix::<f64, 2, 2>::new(-p[0], 0.0, p[0], -p[1]);
+        let (ka, k) = (1.0, 0.1);
+        let problem = OdeBuilder::new()
+            .p([ka, k])
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode_static::<M, 2, _, _, _>(
+                move |x: &SV, p: &V, _t, y: &mut SV| *y = a(p) * x,
+                move |_x: &SV, p: &V, _t, v: &SV, y: &mut SV| *y = a(p) * v,
+                |_p: &V, _t| SV::new(100.0, 0.0),
+            )
+            .unwrap();
+        let mut s = Bdf::default();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        let t = 5.0;
+        s.set_stop_time(t).unwrap();
+        while s.state().unwrap().t < t {
+            s.step().unwrap();
+        }
+        let y_expect = V::from_vec(vec![
+            100.0 * (-ka * t).exp(),
+            100.0 * ka / (ka - k) * ((-k * t).exp() - (-ka * t).exp()),
+        ]);
+        s.state().unwrap().y.assert_eq_st(&y_expect, 1e-5);
+    }
+
     #[test]
     fn test_root_finder_bdf() {
         let mut s = Bdf::default();
-        let (problem, soln) = exponential_decay_problem_with_root::<M>(false);
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
         let y = test_ode_solver(&mut s, &problem, soln, None, false);
         assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
     }
@@ -1,12 +1,16 @@
-use std::rc::Rc;
+use std::{cell::RefCell, rc::Rc};
+
+use nalgebra::{Complex, DVector, SVector};
 
 use crate::{
-    errors::PSError, vector::DefaultDenseMatrix, Closure, ClosureNoJac, ClosureWithSens,
-    ConstantClosure, ConstantClosureWithSens, LinearClosure, LinearClosureWithSens, Matrix,
-    OdeEquations, OdeSolverProblem, Op, UnitCallable, Vector,
+    errors::PSError, matrix::block_diagonal::block_diagonal_indices, vector::DefaultDenseMatrix,
+    Closure, ClosureNoJac, ClosureWithSens, ConstantClosure, ConstantClosureWithSens,
+    ConvergenceCriterion, DiagonalMatrix, JacobianUpdatePolicy, LinearClosure,
+    LinearClosureWithSens, LinearRhs, LinearSolverKind, Matrix, NonLinearSolverKind, OdeEquations,
+    OdeSolverOptions, OdeSolverProblem, Op, Scalar, StepControl, UnitCallable, Vector,
 };
 
-use super::equations::OdeSolverEquations;
+use super::{complex, equations::OdeSolverEquations};
 
 /// Builder for ODE problems. Use methods to set parameters and then call one of the build methods when done.
 pub struct OdeBuilder {
@@ -15,9 +19,15 @@ pub struct OdeBuilder {
     rtol: f64,
     atol: Vec<f64>,
     p: Vec<f64>,
-    use_coloring: bool,
+    jacobian_sparsity: Option<Vec<(usize, usize)>>,
+    mass_sparsity: Option<Vec<(usize, usize)>>,
     sensitivities: bool,
     sensitivities_error_control: bool,
+    options: OdeSolverOptions<f64>,
+    max_abs_state: Option<Vec<f64>>,
+    backward: bool,
+    block_sizes: Option<Vec<usize>>,
+    symmetric: bool,
 }
 
 impl Default for OdeBuilder {
@@ -61,8 +71,6 @@ impl Default for OdeBuilder {
 /// }
 /// let y = solver.interpolate(t);
 /// ```
-///
-
 impl OdeBuilder {
     /// Create a new builder with default parameters:
     /// - t0 = 0.0
@@ -70,8 +78,14 @@ impl OdeBuilder {
     /// - rtol = 1e-6
     /// - atol = [1e-6]
     /// - p = []
-    /// - use_coloring = false
+    /// - jacobian_sparsity = None
+    /// - mass_sparsity = None
     /// - constant_mass = false
+    /// - solver options (see [OdeSolverOptions])
+    /// - max_abs_state = None
+    /// - backward = false
+    /// - block_sizes = None
+    /// - symmetric = false
     pub fn new() -> Self {
         Self {
             t0: 0.0,
@@ -79,9 +93,15 @@ impl OdeBuilder {
             rtol: 1e-6,
             atol: vec![1e-6],
             p: vec![],
-            use_coloring: false,
+            jacobian_sparsity: None,
+            mass_sparsity: None,
             sensitivities: false,
             sensitivities_error_control: false,
+            options: OdeSolverOptions::default(),
+            max_abs_state: None,
+            backward: false,
+            block_sizes: None,
+            symmetric: false,
         }
     }
 
@@ -133,25 +153,241 @@ impl OdeBuilder {
         self
     }
 
-    /// Set whether to use coloring when computing the Jacobian.
-    /// This is always true if matrix type is sparse, but can be set to true for dense matrices as well.
-    /// This can speed up the computation of the Jacobian for large sparse systems.
-    /// However, it relys on the sparsity of the Jacobian being constant,
-    /// and for certain systems it may detect the wrong sparsity pattern.
-    pub fn use_coloring(mut self, use_coloring: bool) -> Self {
-        self.use_coloring = use_coloring;
+    pub(crate) fn params(&self) -> &[f64] {
+        &self.p
+    }
+
+    /// Set the sparsity pattern of the Jacobian of the right-hand side, given as the (row, column) indices of the non-zero entries.
+    /// If set, the Jacobian is assembled using coloring of this pattern, for any matrix type. Otherwise the pattern is only detected
+    /// automatically for sparse matrices (which relies on the Jacobian action propagating NaN values), and dense Jacobians are
+    /// assembled column by column.
+    pub fn jacobian_sparsity<I>(mut self, non_zeros: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        self.jacobian_sparsity = Some(non_zeros.into_iter().collect());
+        self
+    }
+
+    /// Set the sparsity pattern of the mass matrix, given as the (row, column) indices of the non-zero entries.
+    /// This is only used when building a problem with a mass matrix, see [Self::jacobian_sparsity].
+    pub fn mass_sparsity<I>(mut self, non_zeros: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, usize)>,
+    {
+        self.mass_sparsity = Some(non_zeros.into_iter().collect());
+        self
+    }
+
+    /// Declare that the Jacobian is block-diagonal, with square blocks of the given sizes along the diagonal (in order), for
+    /// example for a model made up of uncoupled compartments or subjects. The sizes must add up to the number of states.
+    /// The default dense linear solver ([crate::NalgebraLU]) then factorises each block independently and in parallel
+    /// (see [crate::BlockDiagonalLU]). Unless set by [Self::jacobian_sparsity], the sparsity pattern of the Jacobian is also set to the blocks.
+    pub fn block_sizes<I>(mut self, block_sizes: I) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let block_sizes = block_sizes.into_iter().collect::<Vec<_>>();
+        if self.jacobian_sparsity.is_none() {
+            self.jacobian_sparsity = Some(block_diagonal_indices(&block_sizes));
+        }
+        self.block_sizes = Some(block_sizes);
+        self
+    }
+
+    /// Declare that the Jacobian of the right-hand side and the mass matrix are symmetric (see [OdeEquations::is_symmetric]),
+    /// for example for a gradient flow or a diffusion problem. Unless a solver is chosen with [Self::linear_solver], the default linear solver
+    /// of `DMatrix` then uses the cheaper Cholesky decomposition of the iteration matrix, falling back to the LU decomposition if it is not positive
+    /// definite (see [crate::Cholesky::lu_fallback]), and the equilibration of [crate::NalgebraLU] uses a symmetric scaling.
+    pub fn symmetric(mut self, symmetric: bool) -> Self {
+        self.symmetric = symmetric;
+        self
+    }
+
+    /// Set the size of the diagonal blocks of block preconditioners (see [crate::BlockJacobiPreconditioner]) used by an iterative
+    /// linear solver such as [crate::Gmres], for example the number of species of a reaction–diffusion system discretised with the
+    /// method of lines (with the species at each grid point numbered consecutively).
+    pub fn preconditioner_block_size(mut self, block_size: usize) -> Self {
+        self.options.preconditioner_block_size = Some(block_size);
+        self
+    }
+
+    /// Set the linear solver used by the implicit solvers (see [LinearSolverKind]), for example
+    /// `LinearSolverKind::Gmres { restart: 30, preconditioner: PreconditionerKind::Ilu0 }`. The default linear solver of the
+    /// matrix type ([crate::SelectedLinearSolver]) then builds it when the problem is set, so solvers created with `default()` (e.g. [crate::Bdf::default])
+    /// use it without being given a linear solver explicitly.
+    pub fn linear_solver(mut self, kind: LinearSolverKind) -> Self {
+        self.options.linear_solver = Some(kind);
+        self
+    }
+
+    /// Set the nonlinear solver used to make the initial state consistent with the algebraic constraints of a DAE
+    /// (see [NonLinearSolverKind]), for example [NonLinearSolverKind::Dogleg] if the Newton iteration diverges from a poor
+    /// initial guess of the algebraic states.
+    pub fn initialisation_solver(mut self, kind: NonLinearSolverKind) -> Self {
+        self.options.initialisation_solver = kind;
+        self
+    }
+
+    /// Set when the jacobian is re-evaluated by [crate::Bdf] (see [JacobianUpdatePolicy]), for example
+    /// `JacobianUpdatePolicy::AfterSteps(20)` to also refresh the jacobian periodically, rather than only after a convergence failure.
+    pub fn jacobian_update(mut self, policy: JacobianUpdatePolicy<f64>) -> Self {
+        self.options.jacobian_update = policy;
+        self
+    }
+
+    /// Set the test used by the nonlinear solvers of the implicit ODE solvers to decide whether the iteration has converged
+    /// (see [ConvergenceCriterion]), for example [ConvergenceCriterion::NaturalMonotonicity] for a badly scaled DAE.
+    pub fn convergence_criterion(mut self, criterion: ConvergenceCriterion) -> Self {
+        self.options.convergence_criterion = criterion;
+        self
+    }
+
+    /// Set the maximum number of Newton iterations per step of the solver.
+    /// If not set, the default for the solver is used.
+    pub fn max_nonlinear_solver_iterations(mut self, max_iter: usize) -> Self {
+        self.options.max_nonlinear_solver_iterations = Some(max_iter);
+        self
+    }
+
+    /// Set the safety factor applied to the optimal step size factor.
+    pub fn safety_factor(mut self, safety_factor: f64) -> Self {
+        self.options.safety_factor = safety_factor;
+        self
+    }
+
+    /// Set the maximum convergence rate of the Newton iteration, above which the iteration is considered to be diverging.
+    pub fn max_convergence_rate(mut self, max_convergence_rate: f64) -> Self {
+        self.options.max_convergence_rate = max_convergence_rate;
+        self
+    }
+
+    /// Limit the step size of [crate::Bdf] so that the predicted convergence rate of the Newton iteration stays below `fraction` of the
+    /// maximum convergence rate (see [OdeSolverOptions::convergence_rate_step_limit]), e.g. 0.5.
+    pub fn convergence_rate_step_limit(mut self, fraction: f64) -> Self {
+        self.options.convergence_rate_step_limit = Some(fraction);
+        self
+    }
+
+    /// Take every step with the constant step size `h`, without error control (see [StepControl::Fixed]).
+    /// Only supported by solvers with the [crate::SolverCapabilities::fixed_step] capability, i.e. [crate::Bdf] and [crate::Sdirk].
+    pub fn fixed_step(mut self, h: f64) -> Self {
+        self.options.step_control = StepControl::Fixed(h);
         self
     }
 
+    /// Estimate the global error of the solution returned by [crate::OdeSolverMethod::solve] and [crate::OdeSolverMethod::solve_dense],
+    /// using a reference solution calculated with the tolerances divided by `factor` (see [OdeSolverOptions::global_error_factor]).
+    pub fn global_error_factor(mut self, factor: f64) -> Self {
+        self.options.global_error_factor = Some(factor);
+        self
+    }
+
+    /// Set a bound on the absolute value of the solution, either a single value for all states or one per state.
+    /// If the solution exceeds this bound (or becomes NaN) the solver will stop with an error.
+    pub fn max_abs_state<V, T>(mut self, max_abs_state: V) -> Self
+    where
+        V: IntoIterator<Item = T>,
+        f64: From<T>,
+    {
+        self.max_abs_state = Some(max_abs_state.into_iter().map(|x| f64::from(x)).collect());
+        self
+    }
+
+    /// Set whether to integrate backward in time from the initial time `t0`, i.e. with a negative step size.
+    /// Only supported by solvers with the [crate::SolverCapabilities::backward] capability, i.e. [crate::Bdf] and [crate::Sdirk].
+    pub fn backward(mut self, backward: bool) -> Self {
+        self.backward = backward;
+        self
+    }
+
+    fn build_max_abs_state<V: Vector>(
+        max_abs_state: Option<Vec<f64>>,
+        nstates: usize,
+    ) -> Result<Option<Rc<V>>, PSError> {
+        let max_abs_state = match max_abs_state {
+            Some(max_abs_state) => max_abs_state,
+            None => return Ok(None),
+        };
+        if max_abs_state.len() == 1 {
+            Ok(Some(Rc::new(V::from_element(
+                nstates,
+                V::T::cast(max_abs_state[0]),
+            ))))
+        } else if max_abs_state.len() != nstates {
+            Err(PSError::SolutionBoundLengthMismatch)
+        } else {
+            let mut v = V::zeros(nstates);
+            for (i, &a) in max_abs_state.iter().enumerate() {
+                v[i] = V::T::cast(a);
+            }
+            Ok(Some(Rc::new(v)))
+        }
+    }
+
+    fn build_block_sizes(
+        block_sizes: Option<Vec<usize>>,
+        nstates: usize,
+    ) -> Result<Option<Vec<usize>>, PSError> {
+        match block_sizes {
+            Some(block_sizes) if block_sizes.iter().sum::<usize>() != nstates => {
+                Err(PSError::DimensionMismatch {
+                    name: "sum of the block sizes".to_string(),
+                    expected: nstates,
+                    found: block_sizes.iter().sum(),
+                })
+            }
+            block_sizes => Ok(block_sizes),
+        }
+    }
+
+    fn build_options<M: Matrix>(
+        options: &OdeSolverOptions<f64>,
+    ) -> Result<OdeSolverOptions<M::T>, PSError> {
+        if let Some(kind) = options.linear_solver {
+            if !M::supports_linear_solver(kind) {
+                return Err(PSError::UnsupportedLinearSolver {
+                    kind: format!("{:?}", kind),
+                });
+            }
+        }
+        Ok(OdeSolverOptions {
+            max_nonlinear_solver_iterations: options.max_nonlinear_solver_iterations,
+            safety_factor: M::T::cast(options.safety_factor),
+            max_convergence_rate: M::T::cast(options.max_convergence_rate),
+            step_control: match options.step_control {
+                StepControl::Adaptive => StepControl::Adaptive,
+                StepControl::Fixed(h) => StepControl::Fixed(M::T::cast(h)),
+            },
+            global_error_factor: options.global_error_factor.map(M::T::cast),
+            preconditioner_block_size: options.preconditioner_block_size,
+            linear_solver: options.linear_solver,
+            initialisation_solver: options.initialisation_solver,
+            jacobian_update: match options.jacobian_update {
+                JacobianUpdatePolicy::EveryIteration => JacobianUpdatePolicy::EveryIteration,
+                JacobianUpdatePolicy::EveryStep => JacobianUpdatePolicy::EveryStep,
+                JacobianUpdatePolicy::OnConvergenceFailure => {
+                    JacobianUpdatePolicy::OnConvergenceFailure
+                }
+                JacobianUpdatePolicy::AfterSteps(n) => JacobianUpdatePolicy::AfterSteps(n),
+                JacobianUpdatePolicy::OnStepSizeChange(x) => {
+                    JacobianUpdatePolicy::OnStepSizeChange(M::T::cast(x))
+                }
+            },
+            convergence_rate_step_limit: options.convergence_rate_step_limit.map(M::T::cast),
+            convergence_criterion: options.convergence_criterion,
+        })
+    }
+
     fn build_atol<V: Vector>(atol: Vec<f64>, nstates: usize) -> Result<V, PSError> {
         if atol.len() == 1 {
-            Ok(V::from_element(nstates, V::T::from(atol[0])))
+            Ok(V::from_element(nstates, V::T::cast(atol[0])))
         } else if atol.len() != nstates {
             Err(PSError::AbsoluteToleranceLengthMismatch)
         } else {
             let mut v = V::zeros(nstates);
             for (i, &a) in atol.iter().enumerate() {
-                v[i] = V::T::from(a);
+                v[i] = V::T::cast(a);
             }
             Ok(v)
         }
@@ -160,7 +396,7 @@ impl OdeBuilder {
     fn build_p<V: Vector>(p: Vec<f64>) -> V {
         let mut v = V::zeros(p.len());
         for (i, &p) in p.iter().enumerate() {
-            v[i] = V::T::from(p);
+            v[i] = V::T::cast(p);
         }
         v
     }
@@ -227,30 +463,142 @@ impl OdeBuilder {
         I: Fn(&M::V, M::T) -> M::V,
     {
         let p = Rc::new(Self::build_p(self.p));
-        let t0 = M::T::from(self.t0);
-        let y0 = init(&p, t0);
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
         let mut mass = LinearClosure::new(mass, nstates, nstates, p.clone());
         let init = ConstantClosure::new(init, p.clone());
-        if self.use_coloring || M::is_sparse() {
-            rhs.calculate_sparsity(&y0, t0);
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
+            rhs.calculate_sparsity(&y0, t0.clone());
+        }
+        if let Some(non_zeros) = self.mass_sparsity {
+            mass.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
             mass.calculate_sparsity(t0);
         }
         let mass = Some(Rc::new(mass));
         let rhs = Rc::new(rhs);
         let init = Rc::new(init);
-        let eqn = OdeSolverEquations::new(rhs, mass, None, init, p);
+        let mut eqn = OdeSolverEquations::new(rhs, mass, None, init, p);
+        eqn.set_symmetric(self.symmetric);
         let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            M::T::from(self.rtol),
+            M::T::cast(self.rtol),
             atol,
-            M::T::from(self.t0),
-            M::T::from(self.h0),
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
             false,
             self.sensitivities_error_control,
-        )
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
+    }
+
+    /// Build an ODE problem with a constant diagonal mass matrix, given as the vector of its diagonal entries
+    /// (e.g. one for each differential state and zero for each algebraic state). The mass matrix is stored as a
+    /// [DiagonalMatrix], so it takes `O(n)` storage and operations rather than being stored and multiplied as a general matrix.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &V, p: &V, t: S, y: &mut V) that computes the right-hand side of the ODE.
+    /// - `rhs_jac`: Function of type Fn(x: &V, p: &V, t: S, v: &V, y: &mut V) that computes the multiplication of the Jacobian of the right-hand side with the vector v.
+    /// - `mass`: The diagonal of the mass matrix, which must have the same length as the state vector.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -y
+    /// // 0 = z - y
+    /// // y(0) = 0.1
+    /// // z(0) = 0.1
+    /// let problem = OdeBuilder::new()
+    ///   .build_ode_with_diagonal_mass::<M, _, _, _>(
+    ///       |x, _p, _t, y| {
+    ///           y[0] = -x[0];
+    ///           y[1] = x[1] - x[0];
+    ///       },
+    ///       |x, _p, _t, v, y|  {
+    ///           y[0] = -v[0];
+    ///           y[1] = v[1] - v[0];
+    ///       },
+    ///       vec![1.0, 0.0],
+    ///       |p, _t| DVector::from_element(2, 0.1),
+    /// );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_with_diagonal_mass<M, F, G, I>(
+        self,
+        rhs: F,
+        rhs_jac: G,
+        mass: Vec<f64>,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<M, Closure<M, F, G>, ConstantClosure<M, I>, DiagonalMatrix<M>>,
+        >,
+        PSError,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, &mut M::V),
+        G: Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
+        let nstates = y0.len();
+        if mass.len() != nstates {
+            return Err(PSError::DimensionMismatch {
+                name: "diagonal of the mass matrix".to_string(),
+                expected: nstates,
+                found: mass.len(),
+            });
+        }
+        let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
+        let mass = DiagonalMatrix::new(M::V::from_vec(mass.into_iter().map(M::T::cast).collect()));
+        let init = ConstantClosure::new(init, p.clone());
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
+            rhs.calculate_sparsity(&y0, t0);
+        }
+        let mass = Some(Rc::new(mass));
+        let rhs = Rc::new(rhs);
+        let init = Rc::new(init);
+        let mut eqn = OdeSolverEquations::new(rhs, mass, None, init, p);
+        eqn.set_symmetric(self.symmetric);
+        let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
+        let mut problem = OdeSolverProblem::new(
+            eqn,
+            M::T::cast(self.rtol),
+            atol,
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
+            false,
+            self.sensitivities_error_control,
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
     }
 
     /// Build an ODE problem with a mass matrix and sensitivities.
@@ -335,30 +683,44 @@ impl OdeBuilder {
         L: Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
     {
         let p = Rc::new(Self::build_p(self.p));
-        let t0 = M::T::from(self.t0);
-        let y0 = init(&p, t0);
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
         let nstates = y0.len();
         let mut rhs = ClosureWithSens::new(rhs, rhs_jac, rhs_sens, nstates, nstates, p.clone());
         let mut mass = LinearClosureWithSens::new(mass, mass_sens, nstates, nstates, p.clone());
         let init = ConstantClosureWithSens::new(init, init_sens, nstates, nstates, p.clone());
-        if self.use_coloring || M::is_sparse() {
-            rhs.calculate_sparsity(&y0, t0);
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
+            rhs.calculate_sparsity(&y0, t0.clone());
+        }
+        if let Some(non_zeros) = self.mass_sparsity {
+            mass.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
             mass.calculate_sparsity(t0);
         }
         let mass = Some(Rc::new(mass));
         let rhs = Rc::new(rhs);
         let init = Rc::new(init);
-        let eqn = OdeSolverEquations::new(rhs, mass, None, init, p);
+        let mut eqn = OdeSolverEquations::new(rhs, mass, None, init, p);
+        eqn.set_symmetric(self.symmetric);
         let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            M::T::from(self.rtol),
+            M::T::cast(self.rtol),
             atol,
-            M::T::from(self.t0),
-            M::T::from(self.h0),
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
             true,
             self.sensitivities_error_control,
-        )
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
     }
 
     /// Build an ODE problem with a mass matrix that is the identity matrix.
@@ -409,27 +771,117 @@ impl OdeBuilder {
         I: Fn(&M::V, M::T) -> M::V,
     {
         let p = Rc::new(Self::build_p(self.p));
-        let t0 = M::T::from(self.t0);
-        let y0 = init(&p, t0);
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
         let init = ConstantClosure::new(init, p.clone());
-        if self.use_coloring || M::is_sparse() {
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
             rhs.calculate_sparsity(&y0, t0);
         }
         let rhs = Rc::new(rhs);
         let init = Rc::new(init);
-        let eqn = OdeSolverEquations::new(rhs, None, None, init, p);
+        let mut eqn = OdeSolverEquations::new(rhs, None, None, init, p);
+        eqn.set_symmetric(self.symmetric);
         let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            M::T::from(self.rtol),
+            M::T::cast(self.rtol),
             atol,
-            M::T::from(self.t0),
-            M::T::from(self.h0),
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
             false,
             self.sensitivities_error_control,
-        )
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
+    }
+
+    /// Build an ODE problem with a linear right-hand side `F(t, y) = A y`, where `A` is a constant matrix, and a mass matrix that is the identity matrix.
+    /// The right-hand side is wrapped in a [LinearRhs], so the problem can be solved using [crate::LinearOdeSolver], which only assembles the matrix `A` once.
+    /// The linear rhs is detected by [crate::AutoLinearSolver], which uses [crate::LinearOdeSolver] for these problems.
+    /// Any other solver can also be used to solve the problem.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(v: &V, p: &V, t: S, beta: S, y: &mut V) that computes a gemv multiplication of the matrix `A` with the vector v (i.e. y = A * v + beta * y).
+    /// - `init`: Function of type Fn(p: &V, t: S) -> V that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_ode_linear::<M, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::DVector;
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -a y
+    /// // y(0) = 0.1
+    /// let problem = OdeBuilder::new()
+    ///    .p([0.1])
+    ///    .build_ode_linear::<M, _, _>(
+    ///        |v, p, _t, beta, y| y[0] = -p[0] * v[0] + beta * y[0],
+    ///        |p, _t| DVector::from_element(1, 0.1),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_linear<M, F, I>(
+        self,
+        rhs: F,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<M, LinearRhs<LinearClosure<M, F>>, ConstantClosure<M, I>>,
+        >,
+        PSError,
+    >
+    where
+        M: Matrix,
+        F: Fn(&M::V, &M::V, M::T, M::T, &mut M::V),
+        I: Fn(&M::V, M::T) -> M::V,
+    {
+        let p = Rc::new(Self::build_p(self.p));
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
+        let nstates = y0.len();
+        let mut rhs = LinearClosure::new(rhs, nstates, nstates, p.clone());
+        let init = ConstantClosure::new(init, p.clone());
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
+            rhs.calculate_sparsity(t0);
+        }
+        let rhs = Rc::new(LinearRhs::new(rhs));
+        let init = Rc::new(init);
+        let mut eqn = OdeSolverEquations::new(rhs, None, None, init, p);
+        eqn.set_symmetric(self.symmetric);
+        let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
+        let mut problem = OdeSolverProblem::new(
+            eqn,
+            M::T::cast(self.rtol),
+            atol,
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
+            false,
+            self.sensitivities_error_control,
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
     }
 
     /// Build an ODE problem with a mass matrix that is the identity matrix and sensitivities.
@@ -461,7 +913,6 @@ impl OdeBuilder {
     ///        |p, t, v, y| y.fill(0.0),
     ///    );
     /// ```
-
     #[allow(clippy::type_complexity)]
     pub fn build_ode_with_sens<M, F, G, I, J, K>(
         self,
@@ -485,27 +936,37 @@ impl OdeBuilder {
         K: Fn(&M::V, M::T, &M::V, &mut M::V),
     {
         let p = Rc::new(Self::build_p(self.p));
-        let t0 = M::T::from(self.t0);
-        let y0 = init(&p, t0);
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
         let nstates = y0.len();
         let init = ConstantClosureWithSens::new(init, init_sens, nstates, nstates, p.clone());
         let mut rhs = ClosureWithSens::new(rhs, rhs_jac, rhs_sens, nstates, nstates, p.clone());
-        if self.use_coloring || M::is_sparse() {
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
             rhs.calculate_sparsity(&y0, t0);
         }
         let rhs = Rc::new(rhs);
         let init = Rc::new(init);
-        let eqn = OdeSolverEquations::new(rhs, None, None, init, p);
+        let mut eqn = OdeSolverEquations::new(rhs, None, None, init, p);
+        eqn.set_symmetric(self.symmetric);
         let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            M::T::from(self.rtol),
+            M::T::cast(self.rtol),
             atol,
-            M::T::from(self.t0),
-            M::T::from(self.h0),
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
             true,
             self.sensitivities_error_control,
-        )
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
     }
 
     /// Build an ODE problem with an event.
@@ -544,7 +1005,6 @@ impl OdeBuilder {
     ///        1,
     ///    );
     /// ```
-
     #[allow(clippy::type_complexity)]
     pub fn build_ode_with_root<M, F, G, I, H>(
         self,
@@ -573,27 +1033,222 @@ impl OdeBuilder {
         I: Fn(&M::V, M::T) -> M::V,
     {
         let p = Rc::new(Self::build_p(self.p));
-        let t0 = M::T::from(self.t0);
-        let y0 = init(&p, t0);
+        let t0 = M::T::cast(self.t0);
+        let y0 = init(&p, t0.clone());
         let nstates = y0.len();
         let mut rhs = Closure::new(rhs, rhs_jac, nstates, nstates, p.clone());
         let root = Rc::new(ClosureNoJac::new(root, nstates, nroots, p.clone()));
         let init = ConstantClosure::new(init, p.clone());
-        if self.use_coloring || M::is_sparse() {
+        if let Some(non_zeros) = self.jacobian_sparsity {
+            rhs.set_sparsity(non_zeros)?;
+        } else if M::is_sparse() {
             rhs.calculate_sparsity(&y0, t0);
         }
         let rhs = Rc::new(rhs);
         let init = Rc::new(init);
-        let eqn = OdeSolverEquations::new(rhs, None, Some(root), init, p);
+        let mut eqn = OdeSolverEquations::new(rhs, None, Some(root), init, p);
+        eqn.set_symmetric(self.symmetric);
         let atol = Self::build_atol(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            M::T::from(self.rtol),
+            M::T::cast(self.rtol),
             atol,
-            M::T::from(self.t0),
-            M::T::from(self.h0),
+            M::T::cast(self.t0),
+            M::T::cast(self.h0),
             false,
             self.sensitivities_error_control,
+        )?;
+        problem.options = Self::build_options::<M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
+    }
+
+    /// Build an ODE problem with complex-valued states (e.g. a Schrödinger equation) and a mass matrix that is the identity matrix.
+    ///
+    /// The complex equations are solved as a real system of twice the size, with the real and imaginary parts of each state interleaved
+    /// (see [crate::ode_solver::complex::complex_to_real]), so any of the solvers can be used. Use [crate::ode_solver::complex::to_complex]
+    /// to convert the solution back to the complex states. The jacobian action of the real system is calculated from the complex jacobian
+    /// action, which assumes that the right-hand side is a holomorphic function of the states (e.g. it must not depend on the complex conjugate of a state).
+    ///
+    /// The absolute tolerance, the bound on the solution ([Self::max_abs_state]), the jacobian sparsity, the block sizes and the preconditioner block size are given
+    /// for the complex states and apply to both the real and imaginary parts of each state. The time, step size and tolerances are real.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type `Fn(x: &DVector<Complex<S>>, p: &V, t: S, y: &mut DVector<Complex<S>>)` that computes the right-hand side of the ODE.
+    /// - `rhs_jac`: Function of type `Fn(x: &DVector<Complex<S>>, p: &V, t: S, v: &DVector<Complex<S>>, y: &mut DVector<Complex<S>>)` that computes the multiplication of the (complex) Jacobian of the right-hand side with the vector v.
+    /// - `init`: Function of type `Fn(p: &V, t: S) -> DVector<Complex<S>>` that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait for the real system. Often this must be provided explicitly (i.e. `type M = DMatrix<f64>; builder.build_ode_complex::<M, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::{Complex, DVector};
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // dy/dt = -i w y
+    /// // y(0) = 1
+    /// let problem = OdeBuilder::new()
+    ///    .p([2.0])
+    ///    .build_ode_complex::<M, _, _, _>(
+    ///        |x, p, _t, y| y[0] = Complex::new(0.0, -p[0]) * x[0],
+    ///        |_x, p, _t, v, y| y[0] = Complex::new(0.0, -p[0]) * v[0],
+    ///        |_p, _t| DVector::from_element(1, Complex::new(1.0, 0.0)),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_complex<M, F, G, I>(
+        mut self,
+        rhs: F,
+        rhs_jac: G,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                ConstantClosure<M, impl Fn(&M::V, M::T) -> M::V>,
+            >,
+        >,
+        PSError,
+    >
+    where
+        M: Matrix,
+        F: Fn(&DVector<Complex<M::T>>, &M::V, M::T, &mut DVector<Complex<M::T>>),
+        G: Fn(
+            &DVector<Complex<M::T>>,
+            &M::V,
+            M::T,
+            &DVector<Complex<M::T>>,
+            &mut DVector<Complex<M::T>>,
+        ),
+        I: Fn(&M::V, M::T) -> DVector<Complex<M::T>>,
+    {
+        self.atol = complex::real_values(self.atol);
+        self.max_abs_state = self.max_abs_state.map(complex::real_values);
+        self.jacobian_sparsity = self.jacobian_sparsity.map(complex::real_sparsity);
+        self.block_sizes = self
+            .block_sizes
+            .map(|sizes| sizes.into_iter().map(|n| 2 * n).collect());
+        self.options.preconditioner_block_size =
+            self.options.preconditioner_block_size.map(|n| 2 * n);
+        // the complex states and outputs are converted in buffers kept by the closures, so evaluating them does not allocate
+        let rhs_buffers = RefCell::new([DVector::zeros(0), DVector::zeros(0)]);
+        let jac_buffers = RefCell::new([DVector::zeros(0), DVector::zeros(0), DVector::zeros(0)]);
+        self.build_ode(
+            move |x: &M::V, p: &M::V, t: M::T, y: &mut M::V| {
+                let [z, w] = &mut *rhs_buffers.borrow_mut();
+                complex::real_to_complex_resize(x, z);
+                complex::resize(w, y.len() / 2);
+                rhs(z, p, t, w);
+                complex::complex_to_real(w, y);
+            },
+            move |x: &M::V, p: &M::V, t: M::T, v: &M::V, y: &mut M::V| {
+                let [zx, zv, w] = &mut *jac_buffers.borrow_mut();
+                complex::real_to_complex_resize(x, zx);
+                complex::real_to_complex_resize(v, zv);
+                complex::resize(w, y.len() / 2);
+                rhs_jac(zx, p, t, zv, w);
+                complex::complex_to_real(w, y);
+            },
+            move |p: &M::V, t: M::T| complex::to_real(&init(p, t)),
+        )
+    }
+
+    /// Build an ODE problem with a fixed number of states `N` and a mass matrix that is the identity matrix, where the right-hand side,
+    /// its jacobian action and the initial state are given using the static-size nalgebra vectors [SVector] (e.g. for pharmacokinetic compartment models with 2–10 states).
+    /// The closures can then use static-size vectors and matrices (e.g. an [nalgebra::SMatrix] of rate constants), which are stored on the stack,
+    /// so evaluating them does not allocate. The states are copied to and from the vectors of the matrix type `M` used by the solvers.
+    ///
+    /// # Arguments
+    ///
+    /// - `rhs`: Function of type Fn(x: &SVector<S, N>, p: &V, t: S, y: &mut SVector<S, N>) that computes the right-hand side of the ODE.
+    /// - `rhs_jac`: Function of type Fn(x: &SVector<S, N>, p: &V, t: S, v: &SVector<S, N>, y: &mut SVector<S, N>) that computes the multiplication of the Jacobian of the right-hand side with the vector v.
+    /// - `init`: Function of type Fn(p: &V, t: S) -> SVector<S, N> that computes the initial state.
+    ///
+    /// # Generic Arguments
+    ///
+    /// - `M`: Type that implements the `Matrix` trait. Often this must be provided explicitly, along with the number of states (i.e. `type M = DMatrix<f64>; builder.build_ode_static::<M, 2, _, _, _>`).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use diffsol::OdeBuilder;
+    /// use nalgebra::{SMatrix, SVector};
+    /// type M = nalgebra::DMatrix<f64>;
+    ///
+    /// // one compartment model with first-order absorption from the depot, dy/dt = A y with p = [ka, k]
+    /// let a = |p: &nalgebra::DVector<f64>| SMatrix::<f64, 2, 2>::new(-p[0], 0.0, p[0], -p[1]);
+    /// let problem = OdeBuilder::new()
+    ///    .p([1.0, 0.1])
+    ///    .build_ode_static::<M, 2, _, _, _>(
+    ///        move |x, p, _t, y| *y = a(p) * x,
+    ///        move |_x, p, _t, v, y| *y = a(p) * v,
+    ///        |_p, _t| SVector::<f64, 2>::new(100.0, 0.0),
+    ///    );
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn build_ode_static<M, const N: usize, F, G, I>(
+        self,
+        rhs: F,
+        rhs_jac: G,
+        init: I,
+    ) -> Result<
+        OdeSolverProblem<
+            OdeSolverEquations<
+                M,
+                Closure<
+                    M,
+                    impl Fn(&M::V, &M::V, M::T, &mut M::V),
+                    impl Fn(&M::V, &M::V, M::T, &M::V, &mut M::V),
+                >,
+                ConstantClosure<M, impl Fn(&M::V, M::T) -> M::V>,
+            >,
+        >,
+        PSError,
+    >
+    where
+        M: Matrix,
+        F: Fn(&SVector<M::T, N>, &M::V, M::T, &mut SVector<M::T, N>),
+        G: Fn(&SVector<M::T, N>, &M::V, M::T, &SVector<M::T, N>, &mut SVector<M::T, N>),
+        I: Fn(&M::V, M::T) -> SVector<M::T, N>,
+    {
+        fn to_static<V: Vector, const N: usize>(x: &V) -> SVector<V::T, N> {
+            SVector::from_fn(|i, _| x[i].clone())
+        }
+        fn from_static<V: Vector, const N: usize>(x: &SVector<V::T, N>, y: &mut V) {
+            for (i, xi) in x.iter().enumerate() {
+                y[i] = xi.clone();
+            }
+        }
+        self.build_ode(
+            move |x: &M::V, p: &M::V, t: M::T, y: &mut M::V| {
+                let mut w = SVector::zeros();
+                rhs(&to_static(x), p, t, &mut w);
+                from_static(&w, y);
+            },
+            move |x: &M::V, p: &M::V, t: M::T, v: &M::V, y: &mut M::V| {
+                let mut w = SVector::zeros();
+                rhs_jac(&to_static(x), p, t, &to_static(v), &mut w);
+                from_static(&w, y);
+            },
+            move |p: &M::V, t: M::T| {
+                let mut y0 = M::V::zeros(N);
+                from_static(&init(p, t), &mut y0);
+                y0
+            },
         )
     }
 
@@ -628,17 +1283,31 @@ impl OdeBuilder {
         type V = diffsl::V;
         type T = diffsl::T;
         let p = Self::build_p::<V>(self.p);
-        let mut eqn = diffsl::DiffSl::new(context, self.use_coloring);
+        let mut eqn = diffsl::DiffSl::new(context);
+        if p.len() != eqn.rhs().nparams() {
+            return Err(PSError::ParameterLengthMismatch {
+                expected: eqn.rhs().nparams(),
+                found: p.len(),
+            }
+            .into());
+        }
         eqn.set_params(p);
         let atol = Self::build_atol::<V>(self.atol, eqn.rhs().nstates())?;
-        OdeSolverProblem::new(
+        let mut problem = OdeSolverProblem::new(
             eqn,
-            T::from(self.rtol),
+            T::cast(self.rtol),
             atol,
-            T::from(self.t0),
-            T::from(self.h0),
+            T::cast(self.t0),
+            T::cast(self.h0),
             self.sensitivities,
             self.sensitivities_error_control,
-        )
+        )?;
+        problem.options = Self::build_options::<diffsl::M>(&self.options)?;
+        problem.max_abs_state =
+            Self::build_max_abs_state(self.max_abs_state, problem.eqn.rhs().nstates())?;
+        problem.backward = self.backward;
+        problem.block_sizes =
+            Self::build_block_sizes(self.block_sizes, problem.eqn.rhs().nstates())?;
+        Ok(problem)
     }
 }
@@ -0,0 +1,140 @@
+use nalgebra::{Complex, DVector};
+
+use crate::{IndexType, Scalar, Vector};
+
+/// Write the complex vector `z` to the real vector `y` of twice its length, with the real and imaginary parts of each element interleaved
+/// (i.e. `y[2 i] = Re(z[i])` and `y[2 i + 1] = Im(z[i])`). This is the layout of the states of a problem built with [crate::OdeBuilder::build_ode_complex].
+pub fn complex_to_real<V: Vector>(z: &DVector<Complex<V::T>>, y: &mut V) {
+    assert_eq!(y.len(), 2 * z.len(), "real vector must be twice the length");
+    for (i, zi) in z.iter().enumerate() {
+        y[2 * i] = zi.re.clone();
+        y[2 * i + 1] = zi.im.clone();
+    }
+}
+
+/// Write the real vector `y` with interleaved real and imaginary parts (see [complex_to_real]) to the complex vector `z` of half its length
+pub fn real_to_complex<V: Vector>(y: &V, z: &mut DVector<Complex<V::T>>) {
+    assert_eq!(y.len(), 2 * z.len(), "real vector must be twice the length");
+    for (i, zi) in z.iter_mut().enumerate() {
+        *zi = Complex::new(y[2 * i].clone(), y[2 * i + 1].clone());
+    }
+}
+
+/// Resize the complex vector `z` to length `n` if needed, e.g. a buffer that is reused between evaluations of the equations
+pub(crate) fn resize<T: Scalar>(z: &mut DVector<Complex<T>>, n: IndexType) {
+    if z.len() != n {
+        *z = DVector::zeros(n);
+    }
+}
+
+/// As [real_to_complex], but first resizing `z` to half the length of `y` if needed (see [resize])
+pub(crate) fn real_to_complex_resize<V: Vector>(y: &V, z: &mut DVector<Complex<V::T>>) {
+    resize(z, y.len() / 2);
+    real_to_complex(y, z);
+}
+
+/// Return the complex vector with the interleaved real and imaginary parts in `y` (see [complex_to_real]), e.g. to convert the solution
+/// of a problem built with [crate::OdeBuilder::build_ode_complex] back to the complex states
+pub fn to_complex<V: Vector>(y: &V) -> DVector<Complex<V::T>> {
+    let mut z = DVector::zeros(y.len() / 2);
+    real_to_complex(y, &mut z);
+    z
+}
+
+/// Return the real vector with interleaved real and imaginary parts (see [complex_to_real]) of the complex vector `z`
+pub fn to_real<V: Vector>(z: &DVector<Complex<V::T>>) -> V {
+    let mut y = V::zeros(2 * z.len());
+    complex_to_real(z, &mut y);
+    y
+}
+
+/// The per-state values (e.g. the absolute tolerances) of the real system, given the values for the complex states. A single value applies to all the states,
+/// otherwise each value is used for both the real and imaginary parts of its state.
+pub(crate) fn real_values(values: Vec<f64>) -> Vec<f64> {
+    match values.len() {
+        1 => values,
+        _ => values.into_iter().flat_map(|x| [x, x]).collect(),
+    }
+}
+
+/// The non-zeros of the jacobian of the real system, given the non-zeros `(i, j)` of the complex jacobian. Each complex entry gives a 2x2 block,
+/// as the real and imaginary parts of state `i` both depend on the real and imaginary parts of state `j`.
+pub(crate) fn real_sparsity(non_zeros: Vec<(IndexType, IndexType)>) -> Vec<(IndexType, IndexType)> {
+    non_zeros
+        .into_iter()
+        .flat_map(|(i, j)| {
+            [
+                (2 * i, 2 * j),
+                (2 * i, 2 * j + 1),
+                (2 * i + 1, 2 * j),
+                (2 * i + 1, 2 * j + 1),
+            ]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Complex, DVector};
+
+    use crate::{Bdf, OdeBuilder, OdeSolverMethod, OdeSolverState, Vector};
+
+    use super::{real_sparsity, real_values, to_complex, to_real};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+    type C = DVector<Complex<f64>>;
+
+    #[test]
+    fn test_interleaved_layout() {
+        let z = C::from_vec(vec![Complex::new(1.0, 2.0), Complex::new(3.0, -4.0)]);
+        let y: V = to_real(&z);
+        assert_eq!(y, V::from_vec(vec![1.0, 2.0, 3.0, -4.0]));
+        assert_eq!(to_complex(&y), z);
+        assert_eq!(
+            real_sparsity(vec![(1, 0)]),
+            vec![(2, 0), (2, 1), (3, 0), (3, 1)]
+        );
+        assert_eq!(real_values(vec![1e-6]), vec![1e-6]);
+        assert_eq!(real_values(vec![1.0, 2.0]), vec![1.0, 1.0, 2.0, 2.0]);
+    }
+
+    // Rabi oscillation of a two-level system, i dψ/dt = H ψ with H = [[0, Ω / 2], [Ω / 2, 0]] and ψ(0) = [1, 0],
+    // so the populations are cos²(Ω t / 2) and sin²(Ω t / 2)
+    #[test]
+    fn test_rabi_oscillation() {
+        let omega = 2.0;
+        let mi = Complex::new(0.0, -1.0);
+        let h = move |x: &C, p: &V, y: &mut C| {
+            y[0] = mi * Complex::from(p[0] / 2.0) * x[1];
+            y[1] = mi * Complex::from(p[0] / 2.0) * x[0];
+        };
+        let problem = OdeBuilder::new()
+            .p([omega])
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode_complex::<M, _, _, _>(
+                move |x: &C, p: &V, _t, y: &mut C| h(x, p, y),
+                move |_x: &C, p: &V, _t, v: &C, y: &mut C| h(v, p, y),
+                |_p: &V, _t| C::from_vec(vec![Complex::from(1.0), Complex::from(0.0)]),
+            )
+            .unwrap();
+        let mut solver = Bdf::default();
+        let state = OdeSolverState::new(&problem, &solver).unwrap();
+        solver.set_problem(state, &problem);
+        let t = 1.3;
+        solver.set_stop_time(t).unwrap();
+        while solver.state().unwrap().t < t {
+            solver.step().unwrap();
+        }
+        let psi = to_complex(&solver.state().unwrap().y);
+        let populations = V::from_vec(vec![psi[0].norm_sqr(), psi[1].norm_sqr()]);
+        let expect = V::from_vec(vec![
+            (omega * t / 2.0).cos().powi(2),
+            (omega * t / 2.0).sin().powi(2),
+        ]);
+        populations.assert_eq_st(&expect, 1e-5);
+        // the phase of the first state is real, and of the second is imaginary
+        assert!(psi[0].im.abs() < 1e-5 && psi[1].re.abs() < 1e-5);
+    }
+}
@@ -0,0 +1,197 @@
+use num_traits::Zero;
+
+use crate::{scalar::Scalar, Vector};
+
+/// A state-feedback controller, which calculates a control input from the (measured) state of the system.
+///
+/// The controller is sampled every [Self::period] by [crate::DosingSchedule::solve] once registered with
+/// [crate::OdeSolverProblem::set_controller], and the control input `u` is held
+/// constant until the next sample (i.e. a zero-order hold). The input is added to the right-hand side of the equations, so `u[i]` is a rate
+/// into the state at index `i` (e.g. an infusion rate for closed-loop dosing).
+pub trait Controller<V: Vector> {
+    /// The time between samples of the controller
+    fn period(&self) -> V::T;
+
+    /// Update the control input `u` given the state `y` at time `t`. `u` holds the previous control input, which can be modified in place.
+    fn update(&mut self, t: V::T, y: &V, u: &mut V);
+}
+
+/// A discrete PID controller that measures the state at index `measured_index`, and actuates the state at index `input_index`
+/// to drive the measured state towards the `setpoint`.
+///
+/// For the error `e = setpoint - y[measured_index]`, the control input is `u = kp e + ki sum(e dt) + kd de/dt`, where the sum and derivative
+/// are approximated using the values at each sample. The input is clamped to the limits `[u_min, u_max]` (by default `[0, inf)`, as e.g. an
+/// infusion rate cannot be negative), and the integral term is not updated while the input is saturated, to avoid integrator windup.
+#[derive(Clone, Debug)]
+pub struct PidController<T: Scalar> {
+    pub kp: T,
+    pub ki: T,
+    pub kd: T,
+    pub setpoint: T,
+    pub measured_index: usize,
+    pub input_index: usize,
+    pub period: T,
+    pub u_min: T,
+    pub u_max: T,
+    integral: T,
+    prev_error: Option<T>,
+}
+
+impl<T: Scalar> PidController<T> {
+    pub fn new(
+        kp: T,
+        ki: T,
+        kd: T,
+        setpoint: T,
+        measured_index: usize,
+        input_index: usize,
+        period: T,
+    ) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            setpoint,
+            measured_index,
+            input_index,
+            period,
+            u_min: T::zero(),
+            u_max: T::cast(f64::INFINITY),
+            integral: T::zero(),
+            prev_error: None,
+        }
+    }
+
+    /// Set the limits of the control input
+    pub fn with_limits(mut self, u_min: T, u_max: T) -> Self {
+        self.u_min = u_min;
+        self.u_max = u_max;
+        self
+    }
+
+    /// Reset the integral and derivative terms, e.g. before starting a new simulation
+    pub fn reset(&mut self) {
+        self.integral = T::zero();
+        self.prev_error = None;
+    }
+}
+
+impl<V: Vector> Controller<V> for PidController<V::T> {
+    fn period(&self) -> V::T {
+        self.period.clone()
+    }
+
+    fn update(&mut self, _t: V::T, y: &V, u: &mut V) {
+        let error = self.setpoint.clone() - y[self.measured_index].clone();
+        let derivative = match self.prev_error.clone() {
+            Some(prev) => (error.clone() - prev) / self.period.clone(),
+            None => V::T::zero(),
+        };
+        self.prev_error = Some(error.clone());
+        let integral = self.integral.clone() + error.clone() * self.period.clone();
+        let unclamped = self.kp.clone() * error
+            + self.ki.clone() * integral.clone()
+            + self.kd.clone() * derivative;
+        let clamped = if unclamped > self.u_max {
+            self.u_max.clone()
+        } else if unclamped < self.u_min {
+            self.u_min.clone()
+        } else {
+            self.integral = integral;
+            unclamped
+        };
+        u[self.input_index] = clamped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        ode_solver::{
+            control::{Controller, PidController},
+            test_models::exponential_decay::exponential_decay_problem,
+        },
+        Bdf, DosingSchedule,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_pid_controller_update() {
+        let mut pid = PidController::new(2.0, 0.5, 0.0, 1.0, 0, 1, 0.1).with_limits(0.0, 1.0);
+        let mut u = V::zeros(2);
+        pid.update(0.0, &V::from_vec(vec![0.8, 0.0]), &mut u);
+        // u = 2 * 0.2 + 0.5 * 0.02
+        assert!((u[1] - 0.41).abs() < 1e-12);
+        assert_eq!(u[0], 0.0);
+        pid.update(0.1, &V::from_vec(vec![-10.0, 0.0]), &mut u);
+        assert_eq!(u[1], 1.0);
+        pid.update(0.2, &V::from_vec(vec![2.0, 0.0]), &mut u);
+        assert_eq!(u[1], 0.0);
+        assert_eq!(Controller::<V>::period(&pid), 0.1);
+    }
+
+    #[test]
+    fn test_closed_loop_infusion() {
+        // dy/dt = -0.1 y + u, with a PI controller holding the first state at 2
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut pid = PidController::new(1.0, 0.5, 0.0, 2.0, 0, 0, 0.1);
+        problem.set_controller(pid.clone()).unwrap();
+        let t_eval = [1.0, 60.0, 80.0];
+        let soln = DosingSchedule::new()
+            .solve(&mut solver, &mut problem, &t_eval)
+            .unwrap();
+        assert!(soln[0][0] > 1.0);
+        for y in soln[1..].iter() {
+            assert!((y[0] - 2.0).abs() < 1e-2, "y = {}", y[0]);
+        }
+        // the uncontrolled state decays as before
+        assert!((soln[2][1] - f64::exp(-8.0)).abs() < 1e-4);
+
+        pid.period = 0.0;
+        assert!(problem.set_controller(pid).is_err());
+    }
+
+    struct SampleRecorder {
+        times: Rc<RefCell<Vec<f64>>>,
+    }
+
+    impl Controller<V> for SampleRecorder {
+        fn period(&self) -> f64 {
+            0.1
+        }
+
+        fn update(&mut self, t: f64, _y: &V, _u: &mut V) {
+            self.times.borrow_mut().push(t);
+        }
+    }
+
+    #[test]
+    fn test_controller_sample_times() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let times = Rc::new(RefCell::new(Vec::new()));
+        problem
+            .set_controller(SampleRecorder {
+                times: times.clone(),
+            })
+            .unwrap();
+        // outputs and a dose that fall on sample times should not cause missed or repeated samples
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose(0.5, 1.0, 0).unwrap();
+        let mut solver = Bdf::default();
+        schedule
+            .solve(&mut solver, &mut problem, &[0.3, 1.05, 2.0])
+            .unwrap();
+        let times = times.borrow();
+        assert_eq!(times.len(), 21);
+        for (k, t) in times.iter().enumerate() {
+            assert!((t - 0.1 * k as f64).abs() < 1e-8, "t = {t}, k = {k}");
+        }
+    }
+}
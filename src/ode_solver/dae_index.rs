@@ -0,0 +1,607 @@
+use std::{ops::MulAssign, rc::Rc};
+
+use nalgebra::ComplexField;
+use num_traits::{abs, One, Zero};
+
+use crate::{
+    errors::PSError, op::OpStatistics, scalar::Scalar, scale, ConstantOp, LinearOp, Matrix,
+    NonLinearOp, OdeEquations, OdeSolverProblem, Op, Vector,
+};
+
+/// The result of the DAE index analysis done by [DaeIndex::analyse].
+///
+/// As for the consistent initialisation of the solvers, the algebraic states are taken to be those with a zero on the diagonal of the mass matrix,
+/// and the algebraic equations are the corresponding rows of the right-hand side.
+#[derive(Clone, Debug)]
+pub struct DaeIndex {
+    /// The estimated (differentiation) index, 0 if there are no algebraic states (i.e. the equations are ODEs)
+    pub index: usize,
+    /// The indices of the algebraic states and equations
+    pub algebraic_indices: Vec<usize>,
+    /// The number of times each algebraic equation (in the same order as [Self::algebraic_indices]) must be differentiated to
+    /// obtain an index-1 system, see [OdeSolverProblem::reduce_index]
+    pub differentiations: Vec<usize>,
+    /// Warnings found during the analysis, i.e. if the index exceeds 1 so that the equations cannot be solved directly
+    pub warnings: Vec<String>,
+}
+
+impl DaeIndex {
+    /// Estimate the index of the equations of `problem`, using rank tests on the mass matrix and on the jacobian of the algebraic
+    /// equations with respect to the algebraic states, linearised at the initial state.
+    ///
+    /// If the jacobian of the algebraic equations is singular, the algebraic equations that do not depend on the algebraic states
+    /// are differentiated along the solution (in the style of the Pantelides algorithm), and the rank test is repeated on the
+    /// differentiated equations. The index is then one more than the largest number of differentiations. Returns an error if
+    /// the jacobian is singular but no equation can be differentiated (i.e. the equations are not in Hessenberg form), or if the
+    /// equations are still singular after differentiating as many times as there are states.
+    ///
+    /// The time derivatives of the algebraic equations are taken along `M^{-1} f`, so only diagonal mass matrices are supported,
+    /// and an error is returned if the mass matrix has a non-zero off-diagonal entry.
+    pub fn analyse<Eqn: OdeEquations>(problem: &OdeSolverProblem<Eqn>) -> Result<Self, PSError> {
+        let t0 = problem.t0.clone();
+        let n = problem.eqn.rhs().nstates();
+        let mass = match problem.eqn.mass() {
+            Some(mass) => {
+                let mass = mass.matrix(t0.clone());
+                if mass
+                    .triplet_iter()
+                    .any(|(i, j, v)| i != j && *v != Eqn::T::zero())
+                {
+                    return Err(PSError::UnsupportedProblem {
+                        e: "DAE index analysis requires a diagonal mass matrix".to_string(),
+                    });
+                }
+                mass.diagonal()
+            }
+            None => Eqn::V::from_element(n, Eqn::T::one()),
+        };
+        let algebraic_indices = (0..n)
+            .filter(|&i| mass[i] == Eqn::T::zero())
+            .collect::<Vec<_>>();
+        if algebraic_indices.is_empty() {
+            return Ok(Self {
+                index: 0,
+                algebraic_indices,
+                differentiations: Vec::new(),
+                warnings: Vec::new(),
+            });
+        }
+        let differential_indices = (0..n)
+            .filter(|&i| mass[i] != Eqn::T::zero())
+            .collect::<Vec<_>>();
+
+        let y0 = problem.eqn.init().call(t0.clone());
+        let jac = dense(&problem.eqn.rhs().jacobian(&y0, t0));
+        let jac_max = jac.iter().flatten().fold(Eqn::T::one(), |acc, x| {
+            if abs(x.clone()) > acc {
+                abs(x.clone())
+            } else {
+                acc
+            }
+        });
+        let tol = Eqn::T::cast(1000.0 * n as f64) * Eqn::T::EPSILON * jac_max;
+
+        // rows of the jacobian of the (possibly differentiated) algebraic equations
+        let mut rows = algebraic_indices
+            .iter()
+            .map(|&i| jac[i].clone())
+            .collect::<Vec<_>>();
+        let mut differentiations = vec![0; algebraic_indices.len()];
+        loop {
+            let jac_alg = rows
+                .iter()
+                .map(|row| algebraic_indices.iter().map(|&j| row[j].clone()).collect())
+                .collect();
+            if rank(jac_alg, tol.clone()) == algebraic_indices.len() {
+                break;
+            }
+            if differentiations.iter().any(|&k| k >= n) {
+                return Err(PSError::Other {
+                    e: "DAE is structurally singular, the algebraic equations remain singular after differentiation"
+                        .to_string(),
+                });
+            }
+            // differentiate the equations that do not depend on the algebraic states, the derivative of g along
+            // the solution is dg/dy M^{-1} f, where only the differential states have a non-zero time derivative
+            let mut differentiated = false;
+            for (row, k) in rows.iter_mut().zip(differentiations.iter_mut()) {
+                if algebraic_indices.iter().any(|&j| abs(row[j].clone()) > tol) {
+                    continue;
+                }
+                let mut new_row = vec![Eqn::T::zero(); n];
+                for &l in differential_indices.iter() {
+                    let c = row[l].clone() / mass[l].clone();
+                    for (new, jlj) in new_row.iter_mut().zip(jac[l].iter()) {
+                        *new += c.clone() * jlj.clone();
+                    }
+                }
+                *row = new_row;
+                *k += 1;
+                differentiated = true;
+            }
+            if !differentiated {
+                return Err(PSError::Other {
+                    e: "cannot determine the DAE index, the jacobian of the algebraic equations is singular but all depend on the algebraic states"
+                        .to_string(),
+                });
+            }
+        }
+        let index = differentiations.iter().max().unwrap() + 1;
+        let mut warnings = Vec::new();
+        if index > 1 {
+            warnings.push(format!(
+                "DAE has index {}, but the solvers only support index-1 DAEs. Use OdeSolverProblem::reduce_index to obtain an index-1 system",
+                index
+            ));
+        }
+        Ok(Self {
+            index,
+            algebraic_indices,
+            differentiations,
+            warnings,
+        })
+    }
+}
+
+/// Copy `m` into a dense row-major array
+fn dense<M: Matrix>(m: &M) -> Vec<Vec<M::T>> {
+    let mut ret = vec![vec![M::T::zero(); m.ncols()]; m.nrows()];
+    for (i, j, v) in m.triplet_iter() {
+        ret[i][j] += v.clone();
+    }
+    ret
+}
+
+/// The rank of the dense matrix `a` using gaussian elimination with partial pivoting, entries smaller than `tol` are treated as zero
+fn rank<T: Scalar>(mut a: Vec<Vec<T>>, tol: T) -> usize {
+    let nrows = a.len();
+    let ncols = a.first().map_or(0, |row| row.len());
+    let mut rank = 0;
+    for j in 0..ncols {
+        if rank == nrows {
+            break;
+        }
+        let pivot = (rank..nrows)
+            .max_by(|&r1, &r2| {
+                abs(a[r1][j].clone())
+                    .partial_cmp(&abs(a[r2][j].clone()))
+                    .unwrap()
+            })
+            .unwrap();
+        if abs(a[pivot][j].clone()) <= tol {
+            continue;
+        }
+        a.swap(rank, pivot);
+        let (top, bottom) = a.split_at_mut(rank + 1);
+        let pivot_row = &top[rank];
+        for row in bottom.iter_mut() {
+            let factor = row[j].clone() / pivot_row[j].clone();
+            for (x, p) in row[j..].iter_mut().zip(pivot_row[j..].iter()) {
+                *x -= factor.clone() * p.clone();
+            }
+        }
+        rank += 1;
+    }
+    rank
+}
+
+/// The right-hand side of an index-reduced DAE (see [IndexReducedEquations]), where each algebraic equation `g` is replaced by its
+/// `k`-th time derivative along the solution. The first derivative is `dg/dy M^{-1} f` (calculated using [NonLinearOp::jac_mul_inplace]),
+/// and higher derivatives and their jacobians are approximated using finite differences.
+pub struct IndexReducedRhs<Rhs: NonLinearOp> {
+    rhs: Rc<Rhs>,
+    inv_mass: Rhs::V,
+    differentiations: Vec<usize>,
+}
+
+impl<Rhs: NonLinearOp> IndexReducedRhs<Rhs> {
+    /// Create the reduced right-hand side. `inv_mass` is the inverse of the diagonal of the mass matrix for the differential states
+    /// and zero for the algebraic states, and `differentiations` gives the number of times each equation is differentiated.
+    pub fn new(rhs: Rc<Rhs>, inv_mass: Rhs::V, differentiations: Vec<usize>) -> Self {
+        Self {
+            rhs,
+            inv_mass,
+            differentiations,
+        }
+    }
+
+    /// The `k`-th time derivative of the right-hand side along the solution
+    fn flow_derivative(&self, k: usize, x: &Rhs::V, t: Rhs::T) -> Rhs::V {
+        let f = self.rhs.call(x, t.clone());
+        if k == 0 {
+            return f;
+        }
+        let mut v = f;
+        v.component_mul_assign(&self.inv_mass);
+        self.directional_derivative(k - 1, x, t, &v)
+    }
+
+    /// The derivative of the `k`-th time derivative of the right-hand side in the direction `v`
+    fn directional_derivative(&self, k: usize, x: &Rhs::V, t: Rhs::T, v: &Rhs::V) -> Rhs::V {
+        if k == 0 {
+            return self.rhs.jac_mul(x, t, v);
+        }
+        let vnorm = v.norm();
+        if vnorm == Rhs::T::zero() {
+            return Rhs::V::zeros(x.len());
+        }
+        let xnorm = x.norm();
+        let scale_x = if xnorm > Rhs::T::one() {
+            xnorm
+        } else {
+            Rhs::T::one()
+        };
+        let eps = Rhs::T::EPSILON.sqrt() * scale_x / vnorm;
+        let mut xp = x.clone();
+        xp.axpy(eps.clone(), v, Rhs::T::one());
+        let mut ret = self.flow_derivative(k, &xp, t.clone());
+        ret.axpy(
+            -Rhs::T::one(),
+            &self.flow_derivative(k, x, t),
+            Rhs::T::one(),
+        );
+        ret.mul_assign(scale(Rhs::T::one() / eps));
+        ret
+    }
+
+    /// The distinct (non-zero) numbers of differentiations
+    fn orders(&self) -> Vec<usize> {
+        let mut orders = self
+            .differentiations
+            .iter()
+            .copied()
+            .filter(|&k| k > 0)
+            .collect::<Vec<_>>();
+        orders.sort();
+        orders.dedup();
+        orders
+    }
+}
+
+impl<Rhs: NonLinearOp> Op for IndexReducedRhs<Rhs> {
+    type V = Rhs::V;
+    type T = Rhs::T;
+    type M = Rhs::M;
+    fn nstates(&self) -> usize {
+        self.rhs.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.rhs.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.rhs.nparams()
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.rhs.statistics()
+    }
+}
+
+impl<Rhs: NonLinearOp> NonLinearOp for IndexReducedRhs<Rhs> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.rhs.call_inplace(x, t.clone(), y);
+        for k in self.orders() {
+            let d = self.flow_derivative(k, x, t.clone());
+            for (i, _) in self
+                .differentiations
+                .iter()
+                .enumerate()
+                .filter(|(_, &ki)| ki == k)
+            {
+                y[i] = d[i].clone();
+            }
+        }
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.rhs.jac_mul_inplace(x, t.clone(), v, y);
+        for k in self.orders() {
+            let d = self.directional_derivative(k, x, t.clone(), v);
+            for (i, _) in self
+                .differentiations
+                .iter()
+                .enumerate()
+                .filter(|(_, &ki)| ki == k)
+            {
+                y[i] = d[i].clone();
+            }
+        }
+    }
+}
+
+/// Wraps the equations `eqn` of a high-index DAE so that each algebraic equation is replaced by its time derivative, differentiated the
+/// number of times given by the index analysis (see [DaeIndex] and [IndexReducedRhs]), giving an index-1 DAE that can be solved by the
+/// DAE solvers. All the other equations (mass, root, initial condition) are unchanged.
+///
+/// The algebraic equations are assumed not to depend explicitly on time. Note that the reduced equations only enforce the derivatives
+/// of the original algebraic equations, so the solution can slowly drift away from the original constraints.
+pub struct IndexReducedEquations<Eqn: OdeEquations> {
+    eqn: Eqn,
+    t0: Eqn::T,
+    differentiations: Vec<usize>,
+    rhs: Option<Rc<IndexReducedRhs<Eqn::Rhs>>>,
+}
+
+impl<Eqn: OdeEquations> IndexReducedEquations<Eqn> {
+    /// Create the reduced equations, `differentiations` gives the number of times each equation is differentiated (zero for the
+    /// differential equations), and the mass matrix is evaluated at time `t0`. The mass matrix must be diagonal (as checked by
+    /// [DaeIndex::analyse]), only its diagonal is used.
+    pub fn new(eqn: Eqn, t0: Eqn::T, differentiations: Vec<usize>) -> Self {
+        let rhs = Some(Self::build_rhs(&eqn, t0.clone(), differentiations.clone()));
+        Self {
+            eqn,
+            t0,
+            differentiations,
+            rhs,
+        }
+    }
+
+    fn build_rhs(
+        eqn: &Eqn,
+        t0: Eqn::T,
+        differentiations: Vec<usize>,
+    ) -> Rc<IndexReducedRhs<Eqn::Rhs>> {
+        let n = eqn.rhs().nstates();
+        let mut inv_mass = match eqn.mass() {
+            Some(mass) => mass.matrix(t0).diagonal(),
+            None => Eqn::V::from_element(n, Eqn::T::one()),
+        };
+        for i in 0..n {
+            if inv_mass[i] != Eqn::T::zero() {
+                inv_mass[i] = Eqn::T::one() / inv_mass[i].clone();
+            }
+        }
+        Rc::new(IndexReducedRhs::new(
+            eqn.rhs().clone(),
+            inv_mass,
+            differentiations,
+        ))
+    }
+
+    /// The wrapped equations
+    pub fn eqn(&self) -> &Eqn {
+        &self.eqn
+    }
+}
+
+impl<Eqn: OdeEquations> OdeEquations for IndexReducedEquations<Eqn> {
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = IndexReducedRhs<Eqn::Rhs>;
+    type Mass = Eqn::Mass;
+    type Root = Eqn::Root;
+    type Init = Eqn::Init;
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.rhs.as_ref().unwrap()
+    }
+    fn mass(&self) -> Option<&Rc<Self::Mass>> {
+        self.eqn.mass()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.eqn.root()
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        self.eqn.init()
+    }
+    fn has_invariants(&self) -> bool {
+        self.eqn.has_invariants()
+    }
+    fn project_inplace(
+        &self,
+        y: &mut Self::V,
+        t: Self::T,
+        atol: &Self::V,
+        rtol: Self::T,
+    ) -> Result<(), PSError> {
+        self.eqn.project_inplace(y, t, atol, rtol)
+    }
+    fn set_params(&mut self, p: Self::V) {
+        // the reduced rhs holds a reference to the wrapped rhs, so is rebuilt afterwards
+        self.rhs = None;
+        self.eqn.set_params(p);
+        self.rhs = Some(Self::build_rhs(
+            &self.eqn,
+            self.t0.clone(),
+            self.differentiations.clone(),
+        ));
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
+    /// Reduce the index of a high-index DAE to one, by analysing the equations with [DaeIndex::analyse] and replacing each algebraic
+    /// equation by its time derivative the required number of times (see [IndexReducedEquations]). All the other settings of the problem
+    /// are kept. This requires that no other references to the equations exist (e.g. held by a solver).
+    pub fn reduce_index(self) -> Result<OdeSolverProblem<IndexReducedEquations<Eqn>>, PSError> {
+        let analysis = DaeIndex::analyse(&self)?;
+        let mut differentiations = vec![0; self.eqn.rhs().nstates()];
+        for (&i, &k) in analysis
+            .algebraic_indices
+            .iter()
+            .zip(analysis.differentiations.iter())
+        {
+            differentiations[i] = k;
+        }
+        let with_sensitivity = self.eqn_sens.is_some();
+        let Self {
+            eqn,
+            rtol,
+            atol,
+            t0,
+            h0,
+            eqn_sens,
+            sens_error_control,
+            options,
+            max_abs_state,
+            backward,
+            breakpoints,
+            block_sizes,
+            constraints,
+        } = self;
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        drop(eqn_sens);
+        let eqn = Rc::try_unwrap(eqn).map_err(|_| PSError::MutableReferenceError)?;
+        let atol = Rc::try_unwrap(atol).unwrap_or_else(|atol| atol.as_ref().clone());
+        let mut problem = OdeSolverProblem::new(
+            IndexReducedEquations::new(eqn, t0.clone(), differentiations),
+            rtol,
+            atol,
+            t0,
+            h0,
+            with_sensitivity,
+            sens_error_control,
+        )?;
+        problem.options = options;
+        problem.max_abs_state = max_abs_state;
+        problem.backward = backward;
+        problem.breakpoints = breakpoints;
+        problem.block_sizes = block_sizes;
+        problem.constraints = constraints;
+        Ok(problem)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+
+    use crate::{
+        errors::PSError,
+        ode_solver::test_models::{
+            exponential_decay::exponential_decay_problem, robertson::robertson,
+        },
+        Bdf, DaeIndex, OdeBuilder, OdeSolverMethod, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_dae_index_ode_and_index_one() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let analysis = DaeIndex::analyse(&problem).unwrap();
+        assert_eq!(analysis.index, 0);
+        assert!(analysis.algebraic_indices.is_empty());
+
+        let (problem, _soln) = robertson::<M>();
+        let analysis = DaeIndex::analyse(&problem).unwrap();
+        assert_eq!(analysis.index, 1);
+        assert_eq!(analysis.algebraic_indices, vec![2]);
+        assert_eq!(analysis.differentiations, vec![0]);
+        assert!(analysis.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_dae_index_two() {
+        // dx/dt = z
+        // dy/dt = -y
+        // 0 = x - y
+        // so x = y = e^{-t}, z = -e^{-t}
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = x[2];
+                    y[1] = -x[1];
+                    y[2] = x[0] - x[1];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = v[2];
+                    y[1] = -v[1];
+                    y[2] = v[0] - v[1];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + beta * y[0];
+                    y[1] = v[1] + beta * y[1];
+                    y[2] *= beta;
+                },
+                |_p, _t| DVector::from_vec(vec![1.0, 1.0, -1.0]),
+            )
+            .unwrap();
+        let analysis = DaeIndex::analyse(&problem).unwrap();
+        assert_eq!(analysis.index, 2);
+        assert_eq!(analysis.differentiations, vec![1]);
+        assert_eq!(analysis.warnings.len(), 1);
+
+        let problem = problem.reduce_index().unwrap();
+        let analysis = DaeIndex::analyse(&problem).unwrap();
+        assert_eq!(analysis.index, 1);
+        let mut s = Bdf::default();
+        let y = s.solve(&problem, 1.0).unwrap().y.pop().unwrap();
+        let e = f64::exp(-1.0);
+        y.assert_eq_st(&V::from_vec(vec![e, e, -e]), 1e-4);
+    }
+
+    #[test]
+    fn test_dae_index_three() {
+        // dx/dt = v
+        // dv/dt = z
+        // dy/dt = -y
+        // 0 = x - y
+        // so x = y = z = e^{-t}, v = -e^{-t}
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = x[1];
+                    y[1] = x[3];
+                    y[2] = -x[2];
+                    y[3] = x[0] - x[2];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = v[1];
+                    y[1] = v[3];
+                    y[2] = -v[2];
+                    y[3] = v[0] - v[2];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + beta * y[0];
+                    y[1] = v[1] + beta * y[1];
+                    y[2] = v[2] + beta * y[2];
+                    y[3] *= beta;
+                },
+                |_p, _t| DVector::from_vec(vec![1.0, -1.0, 1.0, 1.0]),
+            )
+            .unwrap();
+        let analysis = DaeIndex::analyse(&problem).unwrap();
+        assert_eq!(analysis.index, 3);
+        assert_eq!(analysis.algebraic_indices, vec![3]);
+        assert_eq!(analysis.differentiations, vec![2]);
+        assert!(!analysis.warnings.is_empty());
+
+        let problem = problem.reduce_index().unwrap();
+        let mut s = Bdf::default();
+        let y = s.solve(&problem, 1.0).unwrap().y.pop().unwrap();
+        let e = f64::exp(-1.0);
+        y.assert_eq_st(&V::from_vec(vec![e, -e, e, e]), 1e-4);
+    }
+
+    #[test]
+    fn test_dae_index_non_diagonal_mass() {
+        // dx/dt + dy/dt = -x
+        // dy/dt = -y
+        // 0 = x - z
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = -x[0];
+                    y[1] = -x[1];
+                    y[2] = x[0] - x[2];
+                },
+                |_x, _p, _t, v, y| {
+                    y[0] = -v[0];
+                    y[1] = -v[1];
+                    y[2] = v[0] - v[2];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + v[1] + beta * y[0];
+                    y[1] = v[1] + beta * y[1];
+                    y[2] *= beta;
+                },
+                |_p, _t| DVector::from_vec(vec![1.0, 1.0, 1.0]),
+            )
+            .unwrap();
+        assert!(matches!(
+            DaeIndex::analyse(&problem),
+            Err(PSError::UnsupportedProblem { .. })
+        ));
+        assert!(problem.reduce_index().is_err());
+    }
+}
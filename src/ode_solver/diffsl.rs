@@ -3,9 +3,11 @@ use std::{cell::RefCell, rc::Rc};
 use diffsl::execution::Compiler;
 
 use crate::{
-    jacobian::{find_non_zeros_linear, find_non_zeros_nonlinear, JacobianColoring},
+    jacobian::{
+        find_non_zeros_linear, find_non_zeros_nonlinear, sparsity_and_coloring, JacobianColoring,
+    },
     op::{LinearOp, NonLinearOp, Op},
-    ConstantOp, OdeEquations,
+    ConstantOp, Matrix, OdeEquations,
 };
 
 pub type T = f64;
@@ -89,9 +91,9 @@ pub struct DiffSl<'a> {
 }
 
 impl<'a> DiffSl<'a> {
-    pub fn new(context: &'a DiffSlContext, use_coloring: bool) -> Self {
-        let rhs = Rc::new(DiffSlRhs::new(context, use_coloring));
-        let mass = DiffSlMass::new(context, use_coloring).map(Rc::new);
+    pub fn new(context: &'a DiffSlContext) -> Self {
+        let rhs = Rc::new(DiffSlRhs::new(context));
+        let mass = DiffSlMass::new(context).map(Rc::new);
         let root = Rc::new(DiffSlRoot::new(context));
         let init = Rc::new(DiffSlInit::new(context));
         Self {
@@ -111,11 +113,13 @@ pub struct DiffSlRoot<'a> {
 pub struct DiffSlRhs<'a> {
     context: &'a DiffSlContext,
     coloring: Option<JacobianColoring<M>>,
+    sparsity: Option<<M as Matrix>::Sparsity>,
 }
 
 pub struct DiffSlMass<'a> {
     context: &'a DiffSlContext,
     coloring: Option<JacobianColoring<M>>,
+    sparsity: Option<<M as Matrix>::Sparsity>,
 }
 
 pub struct DiffSlInit<'a> {
@@ -135,37 +139,41 @@ impl<'a> DiffSlInit<'a> {
 }
 
 impl<'a> DiffSlRhs<'a> {
-    pub fn new(context: &'a DiffSlContext, use_coloring: bool) -> Self {
+    /// The sparsity pattern of the jacobian is detected from the compiled equations, and used to assemble the jacobian with coloring.
+    pub fn new(context: &'a DiffSlContext) -> Self {
         let mut ret = Self {
             context,
             coloring: None,
+            sparsity: None,
         };
-
-        if use_coloring {
-            let x0 = V::zeros(context.nstates);
-            let t0 = 0.0;
-            let non_zeros = find_non_zeros_nonlinear(&ret, &x0, t0);
-            ret.coloring = Some(JacobianColoring::new_from_non_zeros(&ret, non_zeros));
-        }
+        let x0 = V::zeros(context.nstates);
+        let t0 = 0.0;
+        let non_zeros = find_non_zeros_nonlinear(&ret, &x0, t0);
+        let (sparsity, coloring) = sparsity_and_coloring(ret.nout(), ret.nstates(), non_zeros)
+            .expect("invalid sparsity pattern");
+        ret.sparsity = Some(sparsity);
+        ret.coloring = Some(coloring);
         ret
     }
 }
 
 impl<'a> DiffSlMass<'a> {
-    pub fn new(context: &'a DiffSlContext, use_coloring: bool) -> Option<Self> {
+    /// The sparsity pattern of the mass matrix is detected from the compiled equations, and used to assemble the matrix with coloring.
+    pub fn new(context: &'a DiffSlContext) -> Option<Self> {
         if !context.compiler.has_mass() {
             return None;
         }
         let mut ret = Self {
             context,
             coloring: None,
+            sparsity: None,
         };
-
-        if use_coloring {
-            let t0 = 0.0;
-            let non_zeros = find_non_zeros_linear(&ret, t0);
-            ret.coloring = Some(JacobianColoring::new_from_non_zeros(&ret, non_zeros));
-        }
+        let t0 = 0.0;
+        let non_zeros = find_non_zeros_linear(&ret, t0);
+        let (sparsity, coloring) = sparsity_and_coloring(ret.nout(), ret.nstates(), non_zeros)
+            .expect("invalid sparsity pattern");
+        ret.sparsity = Some(sparsity);
+        ret.coloring = Some(coloring);
         Some(ret)
     }
 }
@@ -188,10 +196,30 @@ macro_rules! impl_op_for_diffsl {
             }
         }
     };
+    ($name:ident, sparsity) => {
+        impl Op for $name<'_> {
+            type M = M;
+            type T = T;
+            type V = V;
+
+            fn nstates(&self) -> usize {
+                self.context.nstates
+            }
+            fn nout(&self) -> usize {
+                self.context.nstates
+            }
+            fn nparams(&self) -> usize {
+                self.context.nparams
+            }
+            fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+                self.sparsity.as_ref().map(|s| s.as_ref())
+            }
+        }
+    };
 }
 
-impl_op_for_diffsl!(DiffSlRhs);
-impl_op_for_diffsl!(DiffSlMass);
+impl_op_for_diffsl!(DiffSlRhs, sparsity);
+impl_op_for_diffsl!(DiffSlMass, sparsity);
 impl_op_for_diffsl!(DiffSlInit);
 
 impl Op for DiffSlRoot<'_> {
@@ -376,7 +404,7 @@ mod tests {
         let k = 1.0;
         let r = 1.0;
         let context = DiffSlContext::new(text).unwrap();
-        let mut eqn = DiffSl::new(&context, false);
+        let mut eqn = DiffSl::new(&context);
         let p = DVector::from_vec(vec![r, k]);
         eqn.set_params(p);
 
@@ -402,7 +430,7 @@ mod tests {
         let problem = OdeBuilder::new().p([r, k]).build_diffsl(&context).unwrap();
         let mut solver = Bdf::default();
         let t = 1.0;
-        let state = solver.solve(&problem, t).unwrap();
+        let state = solver.solve(&problem, t).unwrap().y.pop().unwrap();
         let y_expect = k / (1.0 + (k - y0) * (-r * t).exp() / y0);
         let z_expect = 2.0 * y_expect;
         let expected_state = DVector::from_vec(vec![y_expect, z_expect]);
@@ -0,0 +1,1068 @@
+use std::{
+    cell::{RefCell, RefMut},
+    rc::Rc,
+};
+
+use num_traits::Zero;
+
+use crate::{
+    errors::PSError,
+    matrix::default_solver::DefaultSolver,
+    nonlinear_solver::kind::AnyNonLinearSolver,
+    op::infusion::{InfusionRhs, InfusionRoot},
+    scalar::Scalar,
+    Controller, NewtonNonlinearSolver, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, OdeSolverStopReason, Op, SensEquations, Vector,
+};
+
+/// Options for the steady-state search in [DosingRegimen::steady_state].
+#[derive(Clone, Debug)]
+pub struct SteadyStateOptions<T: Scalar> {
+    /// Relative tolerance used to compare the trough of successive dosing cycles (default 1e-6).
+    pub rtol: T,
+    /// Absolute tolerance used to compare the trough of successive dosing cycles (default 1e-6).
+    pub atol: T,
+    /// Maximum number of dosing cycles to simulate before giving up (default 1000).
+    pub max_cycles: usize,
+    /// Number of equally spaced output points within each dosing cycle, the profile of the returned
+    /// cycle contains this many points in addition to the post-dose state at the start of the cycle (default 10).
+    pub npoints: usize,
+}
+
+impl<T: Scalar> Default for SteadyStateOptions<T> {
+    fn default() -> Self {
+        Self {
+            rtol: T::cast(1e-6),
+            atol: T::cast(1e-6),
+            max_cycles: 1000,
+            npoints: 10,
+        }
+    }
+}
+
+/// The steady-state dosing cycle found by [DosingRegimen::steady_state].
+#[derive(Clone, Debug)]
+pub struct SteadyStateSolution<V: Vector> {
+    /// Number of dosing cycles simulated to reach steady state (including the returned cycle)
+    pub ncycles: usize,
+    /// Output times within the steady-state cycle, starting at the time of the last dose and ending at the trough
+    pub t: Vec<V::T>,
+    /// Solution at each of the output times, the first is the state just after the dose is given
+    pub y: Vec<V>,
+}
+
+impl<V: Vector> SteadyStateSolution<V> {
+    /// The trough of the steady-state cycle, i.e. the state just before the next dose would be given
+    pub fn trough(&self) -> &V {
+        self.y.last().unwrap()
+    }
+}
+
+/// A repeated bolus dosing regimen, where a dose of size `amount` is added to the state at `state_index`
+/// every `interval`, starting at the initial time of the problem.
+#[derive(Clone, Debug)]
+pub struct DosingRegimen<T: Scalar> {
+    pub amount: T,
+    pub interval: T,
+    pub state_index: usize,
+}
+
+impl<T: Scalar> DosingRegimen<T> {
+    pub fn new(amount: T, interval: T, state_index: usize) -> Self {
+        Self {
+            amount,
+            interval,
+            state_index,
+        }
+    }
+
+    /// Simulate the dosing regimen until the trough-to-trough change between successive dosing cycles is within the
+    /// tolerances given in `options`, returning the profile of the final (steady-state) cycle.
+    /// Returns [PSError::SteadyStateNotReached] if the solution has not converged after `options.max_cycles` cycles.
+    pub fn steady_state<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+        options: &SteadyStateOptions<T>,
+    ) -> Result<SteadyStateSolution<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let nstates = problem.eqn.rhs().nstates();
+        if self.state_index >= nstates {
+            return Err(PSError::InvalidStateIndex {
+                index: self.state_index,
+                nstates,
+            });
+        }
+        if self.interval <= T::zero() || options.npoints == 0 {
+            return Err(PSError::Other {
+                e: "dosing interval and number of output points must be positive".to_string(),
+            });
+        }
+        let state = OdeSolverState::new(problem, solver)?;
+        solver.set_problem(state, problem);
+        let atol = Eqn::V::from_element(nstates, options.atol.clone());
+        let mut prev_trough: Option<Eqn::V> = None;
+        for ncycles in 1..=options.max_cycles {
+            self.apply_dose(solver, problem)?;
+            let t_dose = solver.state().unwrap().t.clone();
+            let mut t = Vec::with_capacity(options.npoints + 1);
+            let mut y = Vec::with_capacity(options.npoints + 1);
+            t.push(t_dose.clone());
+            y.push(solver.state().unwrap().y.clone());
+            for i in 1..=options.npoints {
+                let t_out = t_dose.clone()
+                    + self.interval.clone() * T::cast(i as f64) / T::cast(options.npoints as f64);
+                solver.set_stop_time(t_out.clone())?;
+                while !matches!(solver.step()?, OdeSolverStopReason::TstopReached) {}
+                t.push(t_out);
+                y.push(solver.state().unwrap().y.clone());
+            }
+            let trough = y.last().unwrap();
+            let converged = match prev_trough {
+                Some(ref prev) => {
+                    let diff = trough.clone() - prev;
+                    diff.squared_norm(trough, &atol, options.rtol.clone()) <= T::one()
+                }
+                None => false,
+            };
+            if converged {
+                return Ok(SteadyStateSolution { ncycles, t, y });
+            }
+            prev_trough = Some(trough.clone());
+        }
+        Err(PSError::SteadyStateNotReached {
+            ncycles: options.max_cycles,
+        })
+    }
+
+    /// Add a single dose to the current state of `solver`, recalculating the time derivatives of the state
+    /// (and sensitivities) so that the solver restarts consistently from the new state.
+    pub fn apply_dose<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<(), PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let state = solver.state_mut().ok_or(PSError::StateNotSet)?;
+        state.y[self.state_index] += self.amount.clone();
+        let mut root_solver = AnyNonLinearSolver::new(
+            problem.options.initialisation_solver,
+            <Eqn::M as DefaultSolver>::default_solver(),
+        );
+        state.set_consistent(problem, &mut root_solver)?;
+        let mut root_solver_sens =
+            NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+        state.set_consistent_sens(problem, &mut root_solver_sens)?;
+        Ok(())
+    }
+}
+
+/// A single bolus dose of size `amount`, given at time `t` and added to the state at `state_index` after a lag time `lag`
+/// (e.g. the delay before absorption starts for an oral dose).
+#[derive(Clone, Debug)]
+pub struct Dose<T: Scalar> {
+    pub t: T,
+    pub amount: T,
+    pub state_index: usize,
+    pub lag: T,
+}
+
+impl<T: Scalar> Dose<T> {
+    /// The time at which the dose is added to the state (i.e. the dose time plus the lag time)
+    pub fn t_effective(&self) -> T {
+        self.t.clone() + self.lag.clone()
+    }
+}
+
+/// A constant-rate infusion of `rate` into the state at `state_index`, starting at time `t` and lasting for `duration`.
+#[derive(Clone, Debug)]
+pub struct Infusion<T: Scalar> {
+    pub t: T,
+    pub duration: T,
+    pub rate: T,
+    pub state_index: usize,
+}
+
+impl<T: Scalar> Infusion<T> {
+    /// The time at which the infusion stops
+    pub fn t_end(&self) -> T {
+        self.t.clone() + self.duration.clone()
+    }
+
+    /// Returns true if the infusion is running over the interval starting at `t` (i.e. the infusion has started at or before `t`, and stops after `t`)
+    pub fn is_active(&self, t: T) -> bool {
+        self.t <= t && t < self.t_end()
+    }
+}
+
+/// Wraps the equations `eqn` so that the right-hand side includes a piecewise-constant infusion rate (see [InfusionRhs]).
+/// All the other equations (mass, initial condition) are unchanged.
+/// The rate is set by [DosingSchedule::solve], problems using these equations can otherwise be solved as normal (with a zero infusion rate).
+///
+/// A state-feedback [Controller] can be registered on the equations using [OdeSolverProblem::set_controller], in which case the root function
+/// (see [InfusionRoot]) has an extra output after those of `eqn`, which crosses zero at each sample time of the controller.
+pub struct InfusionEquations<Eqn: OdeEquations> {
+    eqn: Eqn,
+    rhs: Option<Rc<InfusionRhs<Eqn::Rhs>>>,
+    root: Option<Rc<InfusionRoot<Eqn::Root>>>,
+    controller: Option<RefCell<Box<dyn Controller<Eqn::V>>>>,
+}
+
+impl<Eqn: OdeEquations> InfusionEquations<Eqn> {
+    pub fn new(eqn: Eqn) -> Self {
+        let rhs = Some(Rc::new(InfusionRhs::new(eqn.rhs().clone())));
+        let root = Self::build_root(&eqn, false);
+        Self {
+            eqn,
+            rhs,
+            root,
+            controller: None,
+        }
+    }
+
+    fn build_root(eqn: &Eqn, has_clock: bool) -> Option<Rc<InfusionRoot<Eqn::Root>>> {
+        (eqn.root().is_some() || has_clock).then(|| {
+            Rc::new(InfusionRoot::new(
+                eqn.root().cloned(),
+                eqn.rhs().nstates(),
+                has_clock,
+            ))
+        })
+    }
+
+    /// The wrapped equations
+    pub fn eqn(&self) -> &Eqn {
+        &self.eqn
+    }
+
+    /// The registered controller, if any (see [OdeSolverProblem::set_controller])
+    pub fn controller(&self) -> Option<RefMut<'_, Box<dyn Controller<Eqn::V>>>> {
+        self.controller.as_ref().map(|c| c.borrow_mut())
+    }
+}
+
+impl<Eqn: OdeEquations> OdeEquations for InfusionEquations<Eqn> {
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = InfusionRhs<Eqn::Rhs>;
+    type Mass = Eqn::Mass;
+    type Root = InfusionRoot<Eqn::Root>;
+    type Init = Eqn::Init;
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.rhs.as_ref().unwrap()
+    }
+    fn mass(&self) -> Option<&Rc<Self::Mass>> {
+        self.eqn.mass()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.root.as_ref()
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        self.eqn.init()
+    }
+    fn rhs_explicit_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        // the infusion rate is treated implicitly along with the rest of the stiff part
+        self.eqn.rhs_explicit_inplace(x, t, y)
+    }
+    fn is_symmetric(&self) -> bool {
+        self.eqn.is_symmetric()
+    }
+    fn has_invariants(&self) -> bool {
+        self.eqn.has_invariants()
+    }
+    fn project_inplace(
+        &self,
+        y: &mut Self::V,
+        t: Self::T,
+        atol: &Self::V,
+        rtol: Self::T,
+    ) -> Result<(), PSError> {
+        self.eqn.project_inplace(y, t, atol, rtol)
+    }
+    fn set_params(&mut self, p: Self::V) {
+        // the infusion rhs and root hold references to the wrapped rhs and root, so are rebuilt afterwards
+        let rate = self.rhs.take().unwrap().rate();
+        let next_sample = self.root.take().map(|root| root.next_sample());
+        self.eqn.set_params(p);
+        let rhs = InfusionRhs::new(self.eqn.rhs().clone());
+        rhs.set_rate(&rate);
+        self.rhs = Some(Rc::new(rhs));
+        self.root = Self::build_root(&self.eqn, self.controller.is_some());
+        if let (Some(root), Some(t)) = (self.root.as_ref(), next_sample) {
+            root.set_next_sample(t);
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverProblem<InfusionEquations<Eqn>> {
+    /// Register a state-feedback `controller` on the problem (replacing any previous controller), whose control input is added to the infusion rate
+    /// when the problem is solved using [DosingSchedule::solve]. The controller is sampled at the initial time (after any doses given at that time)
+    /// and then every [Controller::period]. Each later sample time is located by the event system of the solver, using an extra output of the root
+    /// function of the equations (see [InfusionRoot]), and the solver is restarted from the state at that time with the new control input.
+    /// Returns an error if the period of the controller is not positive. This requires that no other references to the equations exist
+    /// (e.g. held by a solver).
+    pub fn set_controller<C>(&mut self, controller: C) -> Result<(), PSError>
+    where
+        C: Controller<Eqn::V> + 'static,
+    {
+        if controller.period() <= Eqn::T::zero() {
+            return Err(PSError::Other {
+                e: "controller period must be positive".to_string(),
+            });
+        }
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        let with_sensitivity = self.eqn_sens.take().is_some();
+        let ret = match Rc::get_mut(&mut self.eqn) {
+            Some(eqn) => {
+                eqn.root = None;
+                eqn.root = InfusionEquations::build_root(&eqn.eqn, true);
+                eqn.controller = Some(RefCell::new(Box::new(controller)));
+                Ok(())
+            }
+            None => Err(PSError::MutableReferenceError),
+        };
+        if with_sensitivity {
+            self.eqn_sens = Some(Rc::new(SensEquations::new(&self.eqn)));
+        }
+        ret
+    }
+}
+
+impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
+    /// Convert this problem into one whose right-hand side includes an infusion rate (see [InfusionEquations]), so that
+    /// it can be solved using [DosingSchedule::solve]. All the other settings of the problem are kept.
+    /// This requires that no other references to the equations exist (e.g. held by a solver).
+    pub fn with_infusions(self) -> Result<OdeSolverProblem<InfusionEquations<Eqn>>, PSError> {
+        let with_sensitivity = self.eqn_sens.is_some();
+        let Self {
+            eqn,
+            rtol,
+            atol,
+            t0,
+            h0,
+            eqn_sens,
+            sens_error_control,
+            options,
+            max_abs_state,
+            backward,
+            breakpoints,
+            block_sizes,
+            constraints,
+        } = self;
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        drop(eqn_sens);
+        let eqn = Rc::try_unwrap(eqn).map_err(|_| PSError::MutableReferenceError)?;
+        let atol = Rc::try_unwrap(atol).unwrap_or_else(|atol| atol.as_ref().clone());
+        let mut problem = OdeSolverProblem::new(
+            InfusionEquations::new(eqn),
+            rtol,
+            atol,
+            t0,
+            h0,
+            with_sensitivity,
+            sens_error_control,
+        )?;
+        problem.options = options;
+        problem.max_abs_state = max_abs_state;
+        problem.backward = backward;
+        problem.breakpoints = breakpoints;
+        problem.block_sizes = block_sizes;
+        problem.constraints = constraints;
+        Ok(problem)
+    }
+}
+
+/// An occasion (e.g. a dosing interval or study visit) starting at time `t`, from which the parameters of the equations are set to `params`.
+#[derive(Clone, Debug)]
+pub struct Occasion<V: Vector> {
+    pub t: V::T,
+    pub params: V,
+}
+
+impl<V: Vector> Occasion<V> {
+    pub fn new(t: V::T, params: V) -> Self {
+        Self { t, params }
+    }
+}
+
+/// A dosing schedule made up of bolus doses and constant-rate infusions.
+///
+/// Each infusion introduces a discontinuity in the right-hand side of the equations when it starts and stops,
+/// and each bolus dose a discontinuity in the state. [Self::solve] stops the solver exactly at each of these times
+/// and restarts it from the updated state, so the solver never needs to step over a discontinuity.
+#[derive(Clone, Debug)]
+pub struct DosingSchedule<T: Scalar> {
+    pub doses: Vec<Dose<T>>,
+    pub infusions: Vec<Infusion<T>>,
+}
+
+impl<T: Scalar> Default for DosingSchedule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> DosingSchedule<T> {
+    pub fn new() -> Self {
+        Self {
+            doses: Vec::new(),
+            infusions: Vec::new(),
+        }
+    }
+
+    /// Add a bolus dose of size `amount` to the state at `state_index` at time `t`.
+    /// Returns an error if `t` is not finite.
+    pub fn add_dose(&mut self, t: T, amount: T, state_index: usize) -> Result<(), PSError> {
+        self.add_dose_with_lag(t, amount, state_index, T::zero())
+    }
+
+    /// Add a bolus dose of size `amount` given at time `t`, which is added to the state at `state_index` after the lag time `lag`.
+    /// Returns an error if `t` or `lag` is not finite, or `lag` is negative.
+    pub fn add_dose_with_lag(
+        &mut self,
+        t: T,
+        amount: T,
+        state_index: usize,
+        lag: T,
+    ) -> Result<(), PSError> {
+        let dose = Dose {
+            t,
+            amount,
+            state_index,
+            lag,
+        };
+        Self::check_dose(&dose)?;
+        self.doses.push(dose);
+        Ok(())
+    }
+
+    /// Add an infusion at a constant `rate` into the state at `state_index`, starting at time `t` and lasting for `duration`.
+    /// Returns an error if `t` or `duration` is not finite, or `duration` is negative.
+    pub fn add_infusion(
+        &mut self,
+        t: T,
+        duration: T,
+        rate: T,
+        state_index: usize,
+    ) -> Result<(), PSError> {
+        let infusion = Infusion {
+            t,
+            duration,
+            rate,
+            state_index,
+        };
+        Self::check_infusion(&infusion)?;
+        self.infusions.push(infusion);
+        Ok(())
+    }
+
+    /// The times at which the schedule introduces a discontinuity (i.e. dose times and infusion start/stop times), sorted and without duplicates.
+    pub fn discontinuities(&self) -> Vec<T> {
+        let mut times = self
+            .doses
+            .iter()
+            .map(|d| d.t_effective())
+            .chain(self.infusions.iter().flat_map(|i| [i.t.clone(), i.t_end()]))
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.total_cmp(b));
+        times.dedup();
+        times
+    }
+
+    /// The times at which each dose enters the system (i.e. the effective time of each bolus dose, and the start of each infusion), sorted.
+    pub fn dose_times(&self) -> Vec<T> {
+        let mut times = self
+            .doses
+            .iter()
+            .map(|d| d.t_effective())
+            .chain(self.infusions.iter().map(|i| i.t.clone()))
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.total_cmp(b));
+        times
+    }
+
+    /// The number of doses (bolus doses and infusions) that have entered the system before time `t`, taking into account any lag times.
+    /// Consistent with [Self::solve], a dose given at exactly `t` is not counted.
+    pub fn dose_count(&self, t: T) -> usize {
+        self.dose_times().iter().take_while(|&td| *td < t).count()
+    }
+
+    /// The time since the last dose (bolus or infusion start) entered the system before time `t`, taking into account any lag times,
+    /// or `None` if no dose has been given before `t`. Consistent with [Self::solve], a dose given at exactly `t` is not the last dose.
+    pub fn time_after_dose(&self, t: T) -> Option<T> {
+        self.dose_times()
+            .into_iter()
+            .take_while(|td| *td < t)
+            .last()
+            .map(|td| t - td)
+    }
+
+    /// The total infusion rate into each of the `nstates` states over the interval starting at time `t`.
+    pub fn infusion_rate<V: Vector<T = T>>(&self, t: T, nstates: usize) -> V {
+        let mut rate = V::zeros(nstates);
+        for infusion in self.infusions.iter().filter(|i| i.is_active(t.clone())) {
+            rate[infusion.state_index] += infusion.rate.clone();
+        }
+        rate
+    }
+
+    fn check(&self, nstates: usize) -> Result<(), PSError> {
+        let indices = self
+            .doses
+            .iter()
+            .map(|d| d.state_index)
+            .chain(self.infusions.iter().map(|i| i.state_index));
+        for state_index in indices {
+            if state_index >= nstates {
+                return Err(PSError::InvalidStateIndex {
+                    index: state_index,
+                    nstates,
+                });
+            }
+        }
+        // the doses and infusions are public, so they are checked again in case they were not added with the constructors
+        for dose in self.doses.iter() {
+            Self::check_dose(dose)?;
+        }
+        for infusion in self.infusions.iter() {
+            Self::check_infusion(infusion)?;
+        }
+        Ok(())
+    }
+
+    fn check_dose(dose: &Dose<T>) -> Result<(), PSError> {
+        if !dose.t.is_finite() || !dose.lag.is_finite() {
+            return Err(PSError::Other {
+                e: "dose time and lag time must be finite".to_string(),
+            });
+        }
+        if dose.lag < T::zero() {
+            return Err(PSError::Other {
+                e: "dose lag time must not be negative".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn check_infusion(infusion: &Infusion<T>) -> Result<(), PSError> {
+        if !infusion.t.is_finite() || !infusion.duration.is_finite() {
+            return Err(PSError::Other {
+                e: "infusion start time and duration must be finite".to_string(),
+            });
+        }
+        if infusion.duration < T::zero() {
+            return Err(PSError::Other {
+                e: "infusion duration must not be negative".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Solve `problem` with this dosing schedule applied, returning the solution at each of the output times `t_eval` (which must be sorted).
+    /// The solver is stopped at each discontinuity of the schedule, where any doses are applied and the infusion rate is updated,
+    /// before restarting the solver from the new state. A solution requested at the same time as a dose is the value just before the dose is given.
+    /// Doses and infusions before the initial time of the problem are ignored. The time after dose and dose count at each output time are given by
+    /// [Self::time_after_dose] and [Self::dose_count].
+    pub fn solve<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+        t_eval: &[T],
+    ) -> Result<Vec<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        self.solve_with_occasions(solver, problem, t_eval, &[])
+    }
+
+    /// As [Self::solve], but the parameters of the equations are changed at the start of each of the `occasions` (which must be sorted by time),
+    /// e.g. to simulate inter-occasion variability. The parameters at the initial time are those of the last occasion starting at or before it
+    /// (or the current parameters of `problem` if there is none). At each later occasion the solver is stopped, and set up again from the current state
+    /// with the new parameters, so any jacobians are recalculated. On return the parameters of `problem` are those of the last occasion.
+    pub fn solve_with_occasions<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+        t_eval: &[T],
+        occasions: &[Occasion<Eqn::V>],
+    ) -> Result<Vec<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        self.solve_impl(solver, problem, t_eval, occasions)
+    }
+
+    fn solve_impl<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+        t_eval: &[T],
+        occasions: &[Occasion<Eqn::V>],
+    ) -> Result<Vec<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        let nstates = problem.eqn.rhs().nstates();
+        self.check(nstates)?;
+        if t_eval
+            .iter()
+            .chain(occasions.iter().map(|o| &o.t))
+            .any(|t| !t.is_finite())
+        {
+            return Err(PSError::Other {
+                e: "output and occasion times must be finite".to_string(),
+            });
+        }
+        if t_eval.windows(2).any(|w| w[0] > w[1]) || occasions.windows(2).any(|w| w[0].t > w[1].t) {
+            return Err(PSError::Other {
+                e: "output and occasion times must be sorted".to_string(),
+            });
+        }
+        let t0 = problem.t0.clone();
+        if t_eval.first().is_some_and(|t| *t < t0) {
+            return Err(PSError::Other {
+                e: "output times must not be before the initial time".to_string(),
+            });
+        }
+
+        // release any previous problem so the parameters can be updated in place
+        solver.take_state();
+        if let Some(occasion) = occasions.iter().filter(|o| o.t <= t0).last() {
+            problem.set_params(occasion.params.clone())?;
+        }
+        // the control input, and the number of samples of the controller so far
+        let mut u = Eqn::V::zeros(nstates);
+        let mut nsamples = 0;
+        let period = problem.eqn.controller().map(|c| c.period());
+        if let Some(root) = problem.eqn.root() {
+            root.set_next_sample(t0.clone());
+        }
+        problem
+            .eqn
+            .rhs()
+            .set_rate(&(self.infusion_rate::<Eqn::V>(t0.clone(), nstates) + &u));
+        let state = OdeSolverState::new(problem, solver)?;
+        solver.set_problem(state, problem);
+
+        // the first sample is at the initial time, after any doses given at that time
+        let mut discontinuities = self
+            .discontinuities()
+            .into_iter()
+            .filter(|t| *t >= t0)
+            .chain(occasions.iter().map(|o| o.t.clone()).filter(|t| *t > t0))
+            .chain(period.clone().map(|_| t0.clone()))
+            .collect::<Vec<_>>();
+        discontinuities.sort_by(|a, b| a.total_cmp(b));
+        discontinuities.dedup();
+        let mut ret = Vec::with_capacity(t_eval.len());
+        let mut next_discontinuity = discontinuities.iter().peekable();
+        let mut next_output = t_eval.iter().peekable();
+        loop {
+            // the next time to stop at is the next output or discontinuity, whichever comes first
+            let t_stop = match (next_output.peek(), next_discontinuity.peek()) {
+                (Some(&to), Some(&td)) => {
+                    if to <= td {
+                        to.clone()
+                    } else {
+                        td.clone()
+                    }
+                }
+                (Some(&to), None) => to.clone(),
+                (None, _) => break,
+            };
+            while t_stop > solver.state().unwrap().t {
+                solver.set_stop_time(t_stop.clone())?;
+                let clock_index = problem.eqn.root().and_then(|root| root.clock_index());
+                loop {
+                    match solver.step()? {
+                        OdeSolverStopReason::TstopReached => break,
+                        OdeSolverStopReason::RootFound { index, t }
+                            if Some(index) == clock_index =>
+                        {
+                            // restart the solver from the state at the sample time, with the new control input
+                            let y = solver.interpolate(t.clone())?;
+                            let order = solver.order();
+                            let s = if solver.state().unwrap().s.is_empty() {
+                                Vec::new()
+                            } else {
+                                solver.interpolate_sens(t.clone())?
+                            };
+                            let mut state = solver.take_state().ok_or(PSError::StateNotSet)?;
+                            state.t = t.clone();
+                            state.y = y;
+                            state.s = s;
+                            nsamples += 1;
+                            self.sample(problem, &state, t.clone(), t0.clone(), nsamples, &mut u);
+                            problem
+                                .eqn
+                                .rhs()
+                                .set_rate(&(self.infusion_rate::<Eqn::V>(t, nstates) + &u));
+                            state.restart(problem, order)?;
+                            solver.set_problem(state, problem);
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            while next_output.peek().is_some_and(|&to| *to == t_stop) {
+                next_output.next();
+                ret.push(solver.state().unwrap().y.clone());
+            }
+            // a sample that falls on a stop time is taken here, as the clock crosses zero at the end of the step
+            let sample_due = period.is_some()
+                && problem
+                    .eqn
+                    .root()
+                    .is_some_and(|root| root.next_sample() <= t_stop);
+            let discontinuity = next_discontinuity.peek().is_some_and(|&td| *td == t_stop);
+
+            if discontinuity {
+                next_discontinuity.next();
+            }
+            if discontinuity || sample_due {
+                let order = solver.order();
+                let mut state = solver.take_state().ok_or(PSError::StateNotSet)?;
+                if let Some(occasion) = occasions.iter().filter(|o| o.t == t_stop).last() {
+                    problem.set_params(occasion.params.clone())?;
+                }
+                for dose in self.doses.iter().filter(|d| d.t_effective() == t_stop) {
+                    state.y[dose.state_index] += dose.amount.clone();
+                }
+                if sample_due {
+                    nsamples += 1;
+                    self.sample(
+                        problem,
+                        &state,
+                        t_stop.clone(),
+                        t0.clone(),
+                        nsamples,
+                        &mut u,
+                    );
+                }
+                problem
+                    .eqn
+                    .rhs()
+                    .set_rate(&(self.infusion_rate::<Eqn::V>(t_stop, nstates) + &u));
+                // restart the solver from the new state
+                state.restart(problem, order)?;
+                solver.set_problem(state, problem);
+            }
+        }
+        Ok(ret)
+    }
+
+    // update the control input `u` from the state at the `nsamples`-th sample time `t` of the controller, and set the next sample
+    // time of the sampling clock (counting from the initial time `t0`, so the sample times do not drift)
+    fn sample<Eqn: OdeEquations<T = T>>(
+        &self,
+        problem: &OdeSolverProblem<InfusionEquations<Eqn>>,
+        state: &OdeSolverState<Eqn::V>,
+        t: T,
+        t0: T,
+        nsamples: usize,
+        u: &mut Eqn::V,
+    ) {
+        let mut controller = problem.eqn.controller().unwrap();
+        controller.update(t, &state.y, u);
+        if let Some(root) = problem.eqn.root() {
+            root.set_next_sample(t0 + controller.period() * T::cast(nsamples as f64));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::PSError,
+        ode_solver::{
+            dosing::{Dose, DosingRegimen, DosingSchedule, Occasion, SteadyStateOptions},
+            test_models::exponential_decay::exponential_decay_problem,
+        },
+        Bdf, NalgebraLU, Sdirk, Tableau, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // for dy/dt = -ky with a dose D every tau, the steady-state trough is D e^{-k tau} / (1 - e^{-k tau}) added to
+    // the decayed initial condition, which is negligible once steady state is reached
+    fn expected_trough(amount: f64, k: f64, tau: f64) -> f64 {
+        let r = f64::exp(-k * tau);
+        amount * r / (1.0 - r)
+    }
+
+    #[test]
+    fn test_steady_state_bdf() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let regimen = DosingRegimen::new(2.0, 12.0, 0);
+        let options = SteadyStateOptions::default();
+        let ss = regimen
+            .steady_state(&mut solver, &problem, &options)
+            .unwrap();
+        let trough = expected_trough(2.0, 0.1, 12.0);
+        assert!(ss.ncycles > 1);
+        assert_eq!(ss.t.len(), options.npoints + 1);
+        assert!((ss.trough()[0] - trough).abs() < 1e-3 * trough);
+        assert!((ss.y[0][0] - (trough + 2.0)).abs() < 1e-3 * trough);
+        // the undosed state decays to zero
+        assert!(ss.trough()[1].abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_steady_state_sdirk() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut solver = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        let regimen = DosingRegimen::new(2.0, 12.0, 0);
+        let ss = regimen
+            .steady_state(&mut solver, &problem, &SteadyStateOptions::default())
+            .unwrap();
+        let trough = expected_trough(2.0, 0.1, 12.0);
+        assert!((ss.trough()[0] - trough).abs() < 1e-3 * trough);
+    }
+
+    #[test]
+    fn test_steady_state_not_reached() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let regimen = DosingRegimen::new(2.0, 12.0, 0);
+        let options = SteadyStateOptions {
+            max_cycles: 3,
+            ..Default::default()
+        };
+        assert!(regimen
+            .steady_state(&mut solver, &problem, &options)
+            .is_err());
+    }
+
+    #[test]
+    fn test_invalid_state_index() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let regimen = DosingRegimen::new(2.0, 12.0, 2);
+        let options = SteadyStateOptions::default();
+        assert!(matches!(
+            regimen.steady_state(&mut solver, &problem, &options),
+            Err(PSError::InvalidStateIndex {
+                index: 2,
+                nstates: 2
+            })
+        ));
+
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose(1.0, 1.0, 2).unwrap();
+        assert!(matches!(
+            schedule.solve(&mut solver, &mut problem, &[0.0, 2.0]),
+            Err(PSError::InvalidStateIndex {
+                index: 2,
+                nstates: 2
+            })
+        ));
+    }
+
+    // infusion of 0.5 into the first state between t = 2 and t = 5, and a bolus dose of 1.0 into the second state at t = 3
+    fn schedule() -> DosingSchedule<f64> {
+        let mut schedule = DosingSchedule::new();
+        schedule.add_infusion(2.0, 3.0, 0.5, 0).unwrap();
+        schedule.add_dose(3.0, 1.0, 1).unwrap();
+        schedule
+    }
+
+    fn schedule_solution(t: f64) -> (f64, f64) {
+        let k = 0.1;
+        let rate = 0.5;
+        let decay = |y: f64, dt: f64| y * f64::exp(-k * dt);
+        let infuse = |y: f64, dt: f64| decay(y, dt) + rate / k * (1.0 - f64::exp(-k * dt));
+        let y0 = if t < 2.0 {
+            decay(1.0, t)
+        } else if t < 5.0 {
+            infuse(decay(1.0, 2.0), t - 2.0)
+        } else {
+            decay(infuse(decay(1.0, 2.0), 3.0), t - 5.0)
+        };
+        // the solution at the dose time is the value just before the dose
+        let y1 = if t <= 3.0 {
+            decay(1.0, t)
+        } else {
+            decay(1.0, t) + decay(1.0, t - 3.0)
+        };
+        (y0, y1)
+    }
+
+    fn check_schedule_solution(t_eval: &[f64], soln: &[V]) {
+        assert_eq!(t_eval.len(), soln.len());
+        for (&t, y) in t_eval.iter().zip(soln.iter()) {
+            let (y0, y1) = schedule_solution(t);
+            y.assert_eq_st(&V::from_vec(vec![y0, y1]), 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_dosing_schedule_bdf() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let t_eval = [0.0, 1.0, 2.0, 2.5, 3.0, 4.0, 5.0, 6.0, 10.0];
+        let soln = schedule()
+            .solve(&mut solver, &mut problem, &t_eval)
+            .unwrap();
+        check_schedule_solution(&t_eval, &soln);
+    }
+
+    #[test]
+    fn test_dosing_schedule_sdirk() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut solver = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        let t_eval = [0.0, 1.0, 2.0, 2.5, 3.0, 4.0, 5.0, 6.0, 10.0];
+        let soln = schedule()
+            .solve(&mut solver, &mut problem, &t_eval)
+            .unwrap();
+        check_schedule_solution(&t_eval, &soln);
+    }
+
+    #[test]
+    fn test_dosing_schedule_discontinuities() {
+        let mut schedule = schedule();
+        schedule.add_dose(5.0, 1.0, 0).unwrap();
+        assert_eq!(schedule.discontinuities(), vec![2.0, 3.0, 5.0]);
+        let rate: V = schedule.infusion_rate(2.0, 2);
+        rate.assert_eq_st(&V::from_vec(vec![0.5, 0.0]), 1e-10);
+        let rate: V = schedule.infusion_rate(5.0, 2);
+        rate.assert_eq_st(&V::from_vec(vec![0.0, 0.0]), 1e-10);
+    }
+
+    #[test]
+    fn test_dosing_schedule_occasions() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose(4.0, 1.0, 0).unwrap();
+        // the elimination rate is 0.2 until the second occasion at t = 4 (when the dose is given), then 0.05
+        let occasions = [
+            Occasion::new(0.0, V::from_vec(vec![0.2])),
+            Occasion::new(4.0, V::from_vec(vec![0.05])),
+        ];
+        let t_eval = [2.0, 4.0, 8.0];
+        let soln = schedule
+            .solve_with_occasions(&mut solver, &mut problem, &t_eval, &occasions)
+            .unwrap();
+        let y4 = f64::exp(-0.2 * 4.0);
+        let expect = [
+            [f64::exp(-0.4), f64::exp(-0.4)],
+            [y4, y4],
+            [(y4 + 1.0) * f64::exp(-0.2), y4 * f64::exp(-0.2)],
+        ];
+        for (y, e) in soln.iter().zip(expect.iter()) {
+            y.assert_eq_st(&V::from_vec(e.to_vec()), 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_dosing_schedule_lag() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        // doses given at t = 0 and t = 4 both enter the first state after a lag time of 1
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose_with_lag(0.0, 1.0, 0, 1.0).unwrap();
+        schedule.add_dose_with_lag(4.0, 1.0, 0, 1.0).unwrap();
+        assert_eq!(schedule.discontinuities(), vec![1.0, 5.0]);
+        let t_eval = [0.5, 1.0, 3.0, 5.0, 6.0];
+        let soln = schedule.solve(&mut solver, &mut problem, &t_eval).unwrap();
+        let k: f64 = 0.1;
+        for (&t, y) in t_eval.iter().zip(soln.iter()) {
+            let mut y0 = f64::exp(-k * t);
+            for td in [1.0, 5.0] {
+                if t > td {
+                    y0 += f64::exp(-k * (t - td));
+                }
+            }
+            assert!(
+                (y[0] - y0).abs() < 1e-4,
+                "t = {} y = {} expected {}",
+                t,
+                y[0],
+                y0
+            );
+        }
+
+        let tad = t_eval
+            .iter()
+            .map(|&t| schedule.time_after_dose(t))
+            .collect::<Vec<_>>();
+        assert_eq!(tad, vec![None, None, Some(2.0), Some(4.0), Some(1.0)]);
+        let count = t_eval
+            .iter()
+            .map(|&t| schedule.dose_count(t))
+            .collect::<Vec<_>>();
+        assert_eq!(count, vec![0, 0, 1, 1, 2]);
+
+        assert!(schedule.add_dose_with_lag(6.0, 1.0, 0, -1.0).is_err());
+        schedule.doses.push(Dose {
+            t: 6.0,
+            amount: 1.0,
+            state_index: 0,
+            lag: -1.0,
+        });
+        assert!(schedule.solve(&mut solver, &mut problem, &t_eval).is_err());
+    }
+
+    #[test]
+    fn test_dosing_schedule_non_finite() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut schedule = schedule();
+        assert!(schedule.add_dose(f64::NAN, 1.0, 0).is_err());
+        assert!(schedule
+            .add_dose_with_lag(1.0, 1.0, 0, f64::INFINITY)
+            .is_err());
+        assert!(schedule.add_infusion(1.0, f64::NAN, 0.5, 0).is_err());
+        assert_eq!(schedule.doses.len(), 1);
+        assert_eq!(schedule.infusions.len(), 1);
+
+        // occasion and output times are checked before solving
+        let t_eval = [1.0, f64::NAN];
+        assert!(schedule.solve(&mut solver, &mut problem, &t_eval).is_err());
+        let occasions = [Occasion::new(f64::NAN, V::from_vec(vec![0.1]))];
+        assert!(schedule
+            .solve_with_occasions(&mut solver, &mut problem, &[1.0], &occasions)
+            .is_err());
+
+        // a NaN dose pushed directly is sorted last rather than panicking, and rejected by solve
+        schedule.doses.push(Dose {
+            t: f64::NAN,
+            amount: 1.0,
+            state_index: 0,
+            lag: 0.0,
+        });
+        let discontinuities = schedule.discontinuities();
+        assert_eq!(discontinuities[..3], [2.0, 3.0, 5.0]);
+        assert!(discontinuities[3].is_nan());
+        assert!(schedule.dose_times()[2].is_nan());
+        assert!(schedule.solve(&mut solver, &mut problem, &[1.0]).is_err());
+    }
+}
@@ -0,0 +1,456 @@
+use std::ops::Index;
+
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+use crate::{errors::PSError, Scalar, Vector};
+
+/// Streaming summary statistics of an ensemble of trajectories (e.g. from [crate::ParameterDistribution::monte_carlo], a [crate::Population]
+/// or repeated stochastic simulations), evaluated on a common grid of output times.
+///
+/// Trajectories are added one at a time using [Self::add_trajectory], which linearly interpolates each trajectory onto the grid, so they
+/// can be saved at different times. Only the summary statistics are kept, not the trajectories: the mean and variance are updated using
+/// Welford's algorithm, and each of the requested quantiles is estimated using the P² algorithm of Jain and Chlamtac (1985),
+/// which keeps five markers per quantile. The resulting mean, quantile bands and prediction intervals at each output time are the data
+/// needed for e.g. a visual predictive check.
+#[derive(Clone, Debug)]
+pub struct EnsembleStatistics<V: Vector> {
+    times: Vec<V::T>,
+    nstates: usize,
+    probabilities: Vec<V::T>,
+    count: usize,
+    non_finite: usize,
+    mean: Vec<V>,
+    m2: Vec<V>,
+    // quantile estimators, indexed by [time][state][probability]
+    quantiles: Vec<Vec<Vec<P2Quantile>>>,
+}
+
+impl<V: Vector> EnsembleStatistics<V> {
+    /// Create a new set of statistics on the output grid `times` (which must be sorted), for trajectories with `nstates` states.
+    /// The quantiles at each of the `probabilities` (which must be between 0 and 1) are estimated.
+    pub fn new(times: Vec<V::T>, nstates: usize, probabilities: &[V::T]) -> Result<Self, PSError> {
+        if times.windows(2).any(|w| w[0] > w[1]) {
+            return Err(PSError::Other {
+                e: "output times must be sorted".to_string(),
+            });
+        }
+        if probabilities
+            .iter()
+            .any(|p| *p <= V::T::zero() || *p >= V::T::one())
+        {
+            return Err(PSError::Other {
+                e: "quantile probabilities must be between 0 and 1".to_string(),
+            });
+        }
+        let quantiles = times
+            .iter()
+            .map(|_| {
+                (0..nstates)
+                    .map(|_| {
+                        probabilities
+                            .iter()
+                            .map(|p| P2Quantile::new(p.to_f64()))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(Self {
+            mean: vec![V::zeros(nstates); times.len()],
+            m2: vec![V::zeros(nstates); times.len()],
+            times,
+            nstates,
+            probabilities: probabilities.to_vec(),
+            count: 0,
+            non_finite: 0,
+            quantiles,
+        })
+    }
+
+    pub fn times(&self) -> &[V::T] {
+        &self.times
+    }
+
+    pub fn probabilities(&self) -> &[V::T] {
+        &self.probabilities
+    }
+
+    /// The number of trajectories added so far
+    pub fn ntrajectories(&self) -> usize {
+        self.count
+    }
+
+    /// The number of non-finite values (e.g. from a diverged trajectory) added so far. These are included in the mean and variance,
+    /// but are skipped by the quantile estimates.
+    pub fn nnon_finite(&self) -> usize {
+        self.non_finite
+    }
+
+    /// Add a trajectory with states `y` at times `t`, which is linearly interpolated onto the output grid.
+    /// The trajectory must cover all the output times.
+    pub fn add_trajectory<Y>(&mut self, t: &[V::T], y: &[Y]) -> Result<(), PSError>
+    where
+        Y: Index<usize, Output = V::T>,
+    {
+        if t.len() != y.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "trajectory".to_string(),
+                expected: t.len(),
+                found: y.len(),
+            });
+        }
+        if t.windows(2).any(|w| w[0] > w[1]) {
+            return Err(PSError::Other {
+                e: "trajectory times must be sorted".to_string(),
+            });
+        }
+        let covered = match (t.first(), t.last(), self.times.first(), self.times.last()) {
+            (Some(t0), Some(t1), Some(g0), Some(g1)) => t0 <= g0 && g1 <= t1,
+            (_, _, None, _) => true,
+            _ => false,
+        };
+        if !covered {
+            return Err(PSError::Other {
+                e: "trajectory does not cover the output times".to_string(),
+            });
+        }
+        let mut values = Vec::with_capacity(self.times.len());
+        let mut segment = 0;
+        for ti in self.times.iter() {
+            // find the segment [t[segment], t[segment + 1]] containing ti
+            while segment + 1 < t.len() - 1 && t[segment + 1] < *ti {
+                segment += 1;
+            }
+            let mut yi = V::zeros(self.nstates);
+            if segment + 1 == t.len() || t[segment + 1] == t[segment] {
+                for j in 0..self.nstates {
+                    yi[j] = y[segment][j].clone();
+                }
+            } else {
+                let theta = (ti.clone() - t[segment].clone())
+                    / (t[segment + 1].clone() - t[segment].clone());
+                for j in 0..self.nstates {
+                    yi[j] = y[segment][j].clone()
+                        + theta.clone() * (y[segment + 1][j].clone() - y[segment][j].clone());
+                }
+            }
+            values.push(yi);
+        }
+        self.add_values(&values)
+    }
+
+    /// Add a trajectory that is already evaluated at each of the output times (e.g. each trajectory of a [crate::MonteCarloSolution])
+    pub fn add_values<Y>(&mut self, y: &[Y]) -> Result<(), PSError>
+    where
+        Y: Index<usize, Output = V::T>,
+    {
+        if y.len() != self.times.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "trajectory".to_string(),
+                expected: self.times.len(),
+                found: y.len(),
+            });
+        }
+        self.count += 1;
+        let n = V::T::cast(self.count as f64);
+        for (i, yi) in y.iter().enumerate() {
+            for j in 0..self.nstates {
+                let x = yi[j].clone();
+                let delta = x.clone() - self.mean[i][j].clone();
+                self.mean[i][j] += delta.clone() / n.clone();
+                let delta2 = x.clone() - self.mean[i][j].clone();
+                self.m2[i][j] += delta * delta2;
+                let x = x.to_f64();
+                if !x.is_finite() {
+                    self.non_finite += 1;
+                    continue;
+                }
+                for q in self.quantiles[i][j].iter_mut() {
+                    q.add(x);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The mean of the trajectories at each output time
+    pub fn mean(&self) -> &[V] {
+        &self.mean
+    }
+
+    /// The (unbiased) sample variance of the trajectories at each output time
+    pub fn variance(&self) -> Vec<V> {
+        let n = V::T::cast(self.count.saturating_sub(1).max(1) as f64);
+        self.m2
+            .iter()
+            .map(|m2| {
+                let mut v = m2.clone();
+                for j in 0..self.nstates {
+                    v[j] /= n.clone();
+                }
+                v
+            })
+            .collect()
+    }
+
+    /// The estimated quantile of the trajectories at each output time, for the probability at index `k` of [Self::probabilities]
+    pub fn quantile(&self, k: usize) -> Vec<V> {
+        self.quantiles
+            .iter()
+            .map(|qi| {
+                let mut v = V::zeros(self.nstates);
+                for (j, qij) in qi.iter().enumerate() {
+                    v[j] = V::T::cast(qij[k].value());
+                }
+                v
+            })
+            .collect()
+    }
+
+    /// The lower and upper bounds of the quantile band between the probabilities at indices `lower` and `upper` of [Self::probabilities],
+    /// at each output time. For probabilities `(1 - level) / 2` and `(1 + level) / 2`, this is the empirical prediction interval containing a
+    /// proportion `level` of the trajectories.
+    pub fn quantile_band(&self, lower: usize, upper: usize) -> (Vec<V>, Vec<V>) {
+        (self.quantile(lower), self.quantile(upper))
+    }
+
+    /// The normal-theory prediction interval for a new trajectory, at each output time, with coverage `level` (e.g. 0.9).
+    /// This is `mean ± z sd sqrt(1 + 1/n)`, where `z` is the `(1 + level) / 2` quantile of the standard normal distribution.
+    pub fn prediction_interval(&self, level: V::T) -> Result<(Vec<V>, Vec<V>), PSError> {
+        if level <= V::T::zero() || level >= V::T::one() {
+            return Err(PSError::Other {
+                e: "prediction interval level must be between 0 and 1".to_string(),
+            });
+        }
+        if self.count < 2 {
+            return Err(PSError::Other {
+                e: "at least two trajectories are needed for a prediction interval".to_string(),
+            });
+        }
+        let z = V::T::cast(inverse_normal_cdf((1.0 + level.to_f64()) / 2.0));
+        let scale = (V::T::one() + V::T::one() / V::T::cast(self.count as f64)).sqrt();
+        let mut lower = Vec::with_capacity(self.times.len());
+        let mut upper = Vec::with_capacity(self.times.len());
+        for (mean, var) in self.mean.iter().zip(self.variance()) {
+            let mut l = mean.clone();
+            let mut u = mean.clone();
+            for j in 0..self.nstates {
+                let half_width = z.clone() * var[j].clone().sqrt() * scale.clone();
+                l[j] -= half_width.clone();
+                u[j] += half_width;
+            }
+            lower.push(l);
+            upper.push(u);
+        }
+        Ok((lower, upper))
+    }
+}
+
+/// Streaming estimate of a single quantile using the P² algorithm, which keeps the heights and positions of five markers:
+/// the minimum, the maximum, the quantile itself and the quantiles at half and at (1 + p) / 2 of the way between them.
+#[derive(Clone, Debug)]
+struct P2Quantile {
+    p: f64,
+    count: usize,
+    heights: [f64; 5],
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * p, 1.0 + 4.0 * p, 3.0 + 2.0 * p, 5.0],
+            increments: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+        }
+    }
+
+    fn add(&mut self, x: f64) {
+        // the first five observations initialise the markers
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+        let h = &mut self.heights;
+        let k = if x < h[0] {
+            h[0] = x;
+            0
+        } else if x >= h[4] {
+            h[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| h[i] <= x && x < h[i + 1]).unwrap_or(3)
+        };
+        for n in self.positions[k + 1..].iter_mut() {
+            *n += 1.0;
+        }
+        for (d, dn) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *d += dn;
+        }
+        // adjust the heights of the middle markers if they are off their desired positions
+        for i in 1..4 {
+            let n = &mut self.positions;
+            let d = self.desired[i] - n[i];
+            if (d >= 1.0 && n[i + 1] - n[i] > 1.0) || (d <= -1.0 && n[i - 1] - n[i] < -1.0) {
+                let d = d.signum();
+                let parabolic = h[i]
+                    + d / (n[i + 1] - n[i - 1])
+                        * ((n[i] - n[i - 1] + d) * (h[i + 1] - h[i]) / (n[i + 1] - n[i])
+                            + (n[i + 1] - n[i] - d) * (h[i] - h[i - 1]) / (n[i] - n[i - 1]));
+                h[i] = if h[i - 1] < parabolic && parabolic < h[i + 1] {
+                    parabolic
+                } else {
+                    let j = if d > 0.0 { i + 1 } else { i - 1 };
+                    h[i] + d * (h[j] - h[i]) / (n[j] - n[i])
+                };
+                n[i] += d;
+            }
+        }
+    }
+
+    fn value(&self) -> f64 {
+        if self.count >= 5 {
+            return self.heights[2];
+        }
+        if self.count == 0 {
+            return f64::NAN;
+        }
+        // too few observations for the markers, so use the empirical quantile
+        let mut sorted = self.heights[..self.count].to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let index = (self.p * (self.count - 1) as f64).round() as usize;
+        sorted[index]
+    }
+}
+
+// the quantile function of the standard normal distribution, using the rational approximation of Acklam (relative error < 1.2e-9)
+fn inverse_normal_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    let p_low = 0.02425;
+    if p < p_low {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - p_low {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    use rand_distr::{Distribution, StandardNormal};
+
+    use crate::ode_solver::ensemble::{inverse_normal_cdf, EnsembleStatistics};
+
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_ensemble_quantiles() {
+        // trajectories y = a t, with a uniformly spaced in [0, 1] and added in a random order
+        let mut a = (0..=1000).map(|i| i as f64 / 1000.0).collect::<Vec<_>>();
+        a.shuffle(&mut StdRng::seed_from_u64(0));
+        let mut stats =
+            EnsembleStatistics::<V>::new(vec![1.0, 2.0, 3.0], 1, &[0.05, 0.5, 0.95]).unwrap();
+        for &ai in a.iter() {
+            // saved at different times to the output grid
+            let t = [0.0, 1.5, 4.0];
+            let y = t.iter().map(|&t| vec![ai * t]).collect::<Vec<_>>();
+            stats.add_trajectory(&t, &y).unwrap();
+        }
+        assert_eq!(stats.ntrajectories(), 1001);
+        let (lower, upper) = stats.quantile_band(0, 2);
+        let median = stats.quantile(1);
+        for (i, &t) in stats.times().iter().enumerate() {
+            assert!((stats.mean()[i][0] - 0.5 * t).abs() < 1e-10);
+            assert!((stats.variance()[i][0] - t * t * 1001.0 * 1002.0 / 12e6).abs() < 1e-10);
+            assert!((lower[i][0] - 0.05 * t).abs() < 0.01 * t);
+            assert!((median[i][0] - 0.5 * t).abs() < 0.01 * t);
+            assert!((upper[i][0] - 0.95 * t).abs() < 0.01 * t);
+        }
+
+        // the trajectory must cover the output times
+        assert!(stats.add_trajectory(&[1.5, 4.0], &[[0.0], [0.0]]).is_err());
+        assert!(stats.add_values(&[[0.0], [0.0]]).is_err());
+        assert!(EnsembleStatistics::<V>::new(vec![1.0], 1, &[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_ensemble_quantiles_skip_non_finite() {
+        let mut stats = EnsembleStatistics::<V>::new(vec![0.0], 1, &[0.5]).unwrap();
+        for x in [1.0, f64::NAN, 3.0, 2.0, f64::INFINITY, 5.0, 4.0] {
+            stats.add_values(&[[x]]).unwrap();
+        }
+        assert_eq!(stats.ntrajectories(), 7);
+        assert_eq!(stats.nnon_finite(), 2);
+        assert_eq!(stats.quantile(0)[0][0], 3.0);
+    }
+
+    #[test]
+    fn test_ensemble_prediction_interval() {
+        assert!((inverse_normal_cdf(0.975) - 1.959963984540054).abs() < 1e-8);
+        assert!((inverse_normal_cdf(0.001) + 3.090232306167813).abs() < 1e-8);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut stats = EnsembleStatistics::<V>::new(vec![0.0], 2, &[0.05, 0.95]).unwrap();
+        assert!(stats.prediction_interval(0.9).is_err());
+        for _ in 0..5000 {
+            let x: f64 = StandardNormal.sample(&mut rng);
+            stats.add_values(&[[1.0 + 2.0 * x, 1.0]]).unwrap();
+        }
+        let (lower, upper) = stats.prediction_interval(0.9).unwrap();
+        assert!((lower[0][0] - (1.0 - 2.0 * 1.645)).abs() < 0.1);
+        assert!((upper[0][0] - (1.0 + 2.0 * 1.645)).abs() < 0.1);
+        assert_eq!(lower[0][1], 1.0);
+        // the empirical band agrees with the normal-theory interval
+        let (qlower, qupper) = stats.quantile_band(0, 1);
+        assert!((qlower[0][0] - lower[0][0]).abs() < 0.15);
+        assert!((qupper[0][0] - upper[0][0]).abs() < 0.15);
+    }
+}
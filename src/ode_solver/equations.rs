@@ -1,10 +1,13 @@
 use std::rc::Rc;
 
 use crate::{
-    op::{unit::UnitCallable, ConstantOp},
+    errors::PSError,
+    matrix::default_solver::DefaultSolver,
+    op::{linear_rhs::LinearRhs, matrix::MatrixOp, unit::UnitCallable, ConstantOp},
     scalar::Scalar,
-    LinearOp, Matrix, NonLinearOp, Vector,
+    LinearOp, LinearSolver, Matrix, NonLinearOp, SolverProblem, Vector,
 };
+use num_traits::{One, Zero};
 use serde::Serialize;
 
 #[derive(Clone, Debug, Serialize)]
@@ -68,8 +71,46 @@ pub trait OdeEquations {
         None
     }
 
+    /// Optionally splits the right-hand side into a stiff and a non-stiff part, `F(t, y) = F_I(t, y) + F_E(t, y)`, for
+    /// implicit-explicit methods such as [crate::Imex]. This function evaluates the non-stiff part `F_E(t, y)` in-place,
+    /// and the stiff part is the remainder `F_I = F - F_E`, so [Self::rhs] must still return the full right-hand side.
+    /// Solvers that do not use the splitting ignore this function.
+    ///
+    /// The default implementation sets `y` to zero, i.e. the whole right-hand side is treated implicitly.
+    fn rhs_explicit_inplace(&self, _x: &Self::V, _t: Self::T, y: &mut Self::V) {
+        y.fill(Self::T::zero());
+    }
+
     /// returns the initial condition, i.e. `y(t)`, where `t` is the initial time
     fn init(&self) -> &Rc<Self::Init>;
+
+    /// Returns true if the jacobian of the right-hand side and the mass matrix are symmetric, so that the iteration matrices
+    /// `M - c J` of the implicit solvers are symmetric (and positive definite if the jacobian is negative definite, e.g. for
+    /// gradient flows or diffusion). If no linear solver is chosen, the Cholesky decomposition is then used for `DMatrix` (falling
+    /// back to LU, see [crate::Cholesky::lu_fallback]), and the equilibration of [crate::NalgebraLU] uses a symmetric scaling.
+    /// The default implementation returns false.
+    fn is_symmetric(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the equations have invariants (conserved quantities) `g(y, t) = 0`, in which case solvers with the
+    /// [crate::SolverCapabilities::invariants] capability call [Self::project_inplace] after each step (see [InvariantEquations]).
+    /// The default implementation returns false.
+    fn has_invariants(&self) -> bool {
+        false
+    }
+
+    /// Projects the state `y` at time `t` back onto the manifold `g(y, t) = 0` defined by the invariants of the equations, using
+    /// `atol` and `rtol` as the tolerances of the projection. The default implementation does nothing.
+    fn project_inplace(
+        &self,
+        _y: &mut Self::V,
+        _t: Self::T,
+        _atol: &Self::V,
+        _rtol: Self::T,
+    ) -> Result<(), PSError> {
+        Ok(())
+    }
 }
 
 /// This struct implements the ODE equation trait [OdeEquations] for a given right-hand side op, mass op, optional root op, and initial condition function.
@@ -154,6 +195,7 @@ where
     root: Option<Rc<Root>>,
     init: Rc<Init>,
     p: Rc<M::V>,
+    symmetric: bool,
 }
 
 impl<M, Rhs, Init, Mass, Root> OdeSolverEquations<M, Rhs, Init, Mass, Root>
@@ -178,8 +220,14 @@ where
             root,
             init,
             p,
+            symmetric: false,
         }
     }
+
+    /// Declare whether the jacobian of the right-hand side and the mass matrix are symmetric (see [OdeEquations::is_symmetric])
+    pub fn set_symmetric(&mut self, symmetric: bool) {
+        self.symmetric = symmetric;
+    }
 }
 
 impl<M, Rhs, Init, Mass, Root> OdeEquations for OdeSolverEquations<M, Rhs, Init, Mass, Root>
@@ -210,6 +258,9 @@ where
     fn init(&self) -> &Rc<Self::Init> {
         &self.init
     }
+    fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
 
     fn set_params(&mut self, p: Self::V) {
         self.p = Rc::new(p);
@@ -222,26 +273,270 @@ where
         if let Some(r) = self.root.as_mut() {
             Rc::<Root>::get_mut(r).unwrap().set_params(self.p.clone())
         }
+        Rc::<Init>::get_mut(&mut self.init)
+            .unwrap()
+            .set_params(self.p.clone());
+    }
+}
+
+/// Wraps the equations `eqn` so that they use the root function `root` (see [OdeEquations::root]), replacing any root function of `eqn`.
+/// All the other equations (right-hand side, mass, initial condition) are unchanged. This is normally created using [crate::OdeSolverProblem::with_root_fn].
+pub struct RootEquations<Eqn, Root>
+where
+    Eqn: OdeEquations,
+    Root: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    eqn: Eqn,
+    root: Rc<Root>,
+}
+
+impl<Eqn, Root> RootEquations<Eqn, Root>
+where
+    Eqn: OdeEquations,
+    Root: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    pub fn new(eqn: Eqn, root: Root) -> Self {
+        Self {
+            eqn,
+            root: Rc::new(root),
+        }
+    }
+
+    /// The wrapped equations
+    pub fn eqn(&self) -> &Eqn {
+        &self.eqn
+    }
+}
+
+impl<Eqn, Root> OdeEquations for RootEquations<Eqn, Root>
+where
+    Eqn: OdeEquations,
+    Root: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = Eqn::Rhs;
+    type Mass = Eqn::Mass;
+    type Root = Root;
+    type Init = Eqn::Init;
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.eqn.rhs()
+    }
+    fn mass(&self) -> Option<&Rc<Self::Mass>> {
+        self.eqn.mass()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        Some(&self.root)
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        self.eqn.init()
+    }
+    fn rhs_explicit_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.eqn.rhs_explicit_inplace(x, t, y)
+    }
+    fn is_symmetric(&self) -> bool {
+        self.eqn.is_symmetric()
+    }
+    fn has_invariants(&self) -> bool {
+        self.eqn.has_invariants()
+    }
+    fn project_inplace(
+        &self,
+        y: &mut Self::V,
+        t: Self::T,
+        atol: &Self::V,
+        rtol: Self::T,
+    ) -> Result<(), PSError> {
+        self.eqn.project_inplace(y, t, atol, rtol)
+    }
+    fn set_params(&mut self, p: Self::V) {
+        let p_root = Rc::new(p.clone());
+        self.eqn.set_params(p);
+        Rc::<Root>::get_mut(&mut self.root)
+            .unwrap()
+            .set_params(p_root);
+    }
+}
+
+/// Wraps the equations `eqn` so that the solution is kept on the manifold `g(y, t) = 0` given by the invariant function `g`, for example
+/// to conserve the total mass of a chemical reaction network or the energy of a mechanical system. The invariant function is given as a
+/// [NonLinearOp] with one output for each invariant, and its jacobian (given by [NonLinearOp::jacobian]) must have full row rank.
+/// All the other equations are unchanged. This is normally created using [crate::OdeSolverProblem::with_invariant].
+///
+/// After each step, solvers with the [crate::SolverCapabilities::invariants] capability replace the solution `y` by the nearest point
+/// (in the least-squares sense) that satisfies the invariants, i.e. `y + G^T lambda` where `G` is the jacobian of `g` at `y` and
+/// `lambda` is found using a simplified Newton iteration, solving with `G G^T` using the default linear solver of the matrix type.
+/// The sensitivities and the interpolated solution between the steps are not projected.
+pub struct InvariantEquations<Eqn, Inv>
+where
+    Eqn: OdeEquations,
+    Inv: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    eqn: Eqn,
+    invariant: Rc<Inv>,
+}
+
+impl<Eqn, Inv> InvariantEquations<Eqn, Inv>
+where
+    Eqn: OdeEquations,
+    Inv: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    /// Maximum number of Newton iterations used to project the solution onto the invariants
+    const MAX_ITER: usize = 10;
+
+    pub fn new(eqn: Eqn, invariant: Inv) -> Self {
+        Self {
+            eqn,
+            invariant: Rc::new(invariant),
+        }
+    }
+
+    /// The wrapped equations
+    pub fn eqn(&self) -> &Eqn {
+        &self.eqn
+    }
+
+    /// The invariant function `g`
+    pub fn invariant(&self) -> &Rc<Inv> {
+        &self.invariant
+    }
+}
+
+impl<Eqn, Inv> OdeEquations for InvariantEquations<Eqn, Inv>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    Inv: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+{
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = Eqn::Rhs;
+    type Mass = Eqn::Mass;
+    type Root = Eqn::Root;
+    type Init = Eqn::Init;
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.eqn.rhs()
+    }
+    fn mass(&self) -> Option<&Rc<Self::Mass>> {
+        self.eqn.mass()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.eqn.root()
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        self.eqn.init()
+    }
+    fn rhs_explicit_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.eqn.rhs_explicit_inplace(x, t, y)
+    }
+    fn is_symmetric(&self) -> bool {
+        self.eqn.is_symmetric()
+    }
+    fn has_invariants(&self) -> bool {
+        true
+    }
+    fn project_inplace(
+        &self,
+        y: &mut Self::V,
+        t: Self::T,
+        atol: &Self::V,
+        rtol: Self::T,
+    ) -> Result<(), PSError> {
+        // any invariants of the wrapped equations are projected first
+        self.eqn.project_inplace(y, t.clone(), atol, rtol.clone())?;
+
+        // the jacobian G is only evaluated at the unprojected solution (simplified Newton iteration)
+        let ninvariants = self.invariant.nout();
+        let jac = self.invariant.jacobian(y, t.clone());
+        let mut rows = vec![Vec::new(); ninvariants];
+        for (i, j, v) in jac.triplet_iter() {
+            rows[i].push((j, v.clone()));
+        }
+        let mut triplets = Vec::new();
+        for (i, row_i) in rows.iter().enumerate() {
+            for (j, row_j) in rows.iter().enumerate() {
+                let mut ggt = Self::T::zero();
+                for (k, v) in row_i.iter() {
+                    if let Some((_, w)) = row_j.iter().find(|(l, _)| l == k) {
+                        ggt += v.clone() * w.clone();
+                    }
+                }
+                if ggt != Self::T::zero() {
+                    triplets.push((i, j, ggt));
+                }
+            }
+        }
+        let ggt = Rc::new(LinearRhs::new(MatrixOp::new(Self::M::try_from_triplets(
+            ninvariants,
+            ninvariants,
+            triplets,
+        )?)));
+        let mut linear_solver = <Self::M as DefaultSolver>::default_solver();
+        linear_solver.set_problem(&SolverProblem::new(
+            ggt,
+            Rc::new(Self::V::from_element(ninvariants, Self::T::one())),
+            rtol.clone(),
+        ));
+        linear_solver.set_linearisation(&Self::V::zeros(ninvariants), t.clone());
+
+        let mut g = Self::V::zeros(ninvariants);
+        let mut dy = Self::V::zeros(y.len());
+        for _ in 0..Self::MAX_ITER {
+            // solve G G^T lambda = g(y) and update y -= G^T lambda
+            self.invariant.call_inplace(y, t.clone(), &mut g);
+            linear_solver.solve_in_place(&mut g)?;
+            dy.fill(Self::T::zero());
+            for (i, j, v) in jac.triplet_iter() {
+                dy[j] += v.clone() * g[i].clone();
+            }
+            y.axpy(-Self::T::one(), &dy, Self::T::one());
+            if (0..y.len()).any(|i| y[i].is_nan()) {
+                break;
+            }
+            // converged if the update is well below the tolerances
+            if dy.squared_norm(y, atol, rtol.clone()) < Self::T::cast(1e-6) {
+                return Ok(());
+            }
+        }
+        Err(PSError::InvariantProjectionFailed { t: t.to_string() })
+    }
+    fn set_params(&mut self, p: Self::V) {
+        let p_invariant = Rc::new(p.clone());
+        self.eqn.set_params(p);
+        Rc::<Inv>::get_mut(&mut self.invariant)
+            .unwrap()
+            .set_params(p_invariant);
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::rc::Rc;
+
     use nalgebra::DVector;
 
     use crate::ode_solver::equations::OdeEquations;
     use crate::ode_solver::test_models::exponential_decay::exponential_decay_problem;
     use crate::ode_solver::test_models::exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem;
+    use crate::ode_solver::test_models::harmonic_oscillator::{
+        harmonic_oscillator_energy, harmonic_oscillator_energy_jacobian,
+        harmonic_oscillator_problem,
+    };
     use crate::vector::Vector;
     use crate::LinearOp;
     use crate::NonLinearOp;
+    use crate::{Adams, Closure, OdeSolverState};
 
     type Mcpu = nalgebra::DMatrix<f64>;
     type Vcpu = nalgebra::DVector<f64>;
 
     #[test]
     fn ode_equation_test() {
-        let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_problem::<Mcpu>();
         let y = DVector::from_vec(vec![1.0, 1.0]);
         let rhs_y = problem.eqn.rhs().call(&y, 0.0);
         let expect_rhs_y = DVector::from_vec(vec![-0.1, -0.1]);
@@ -259,7 +554,7 @@ mod tests {
 
     #[test]
     fn ode_with_mass_test() {
-        let (problem, _soln) = exponential_decay_with_algebraic_problem::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_with_algebraic_problem::<Mcpu>();
         let y = DVector::from_vec(vec![1.0, 1.0, 1.0]);
         let rhs_y = problem.eqn.rhs().call(&y, 0.0);
         let expect_rhs_y = DVector::from_vec(vec![-0.1, -0.1, 0.0]);
@@ -288,4 +583,26 @@ mod tests {
         assert_eq!(jac[(1, 2)], 0.0);
         assert_eq!(jac[(2, 1)], -1.0);
     }
+
+    #[test]
+    fn invariant_projection_test() {
+        let (problem, _soln) = harmonic_oscillator_problem::<Mcpu>();
+        let invariant = Closure::new(
+            harmonic_oscillator_energy::<Mcpu>,
+            harmonic_oscillator_energy_jacobian::<Mcpu>,
+            2,
+            1,
+            Rc::new(Vcpu::zeros(0)),
+        );
+        let problem = problem.with_invariant(invariant).unwrap();
+        assert!(problem.eqn.has_invariants());
+
+        // a point just outside the unit circle is projected back onto it along the gradient of the energy
+        let mut y = Vcpu::from_vec(vec![0.6 * 1.001, 0.8 * 1.001]);
+        problem.project(&mut y, 0.0).unwrap();
+        y.assert_eq_st(&Vcpu::from_vec(vec![0.6, 0.8]), 1e-8);
+
+        // solvers that cannot project the solution reject the problem
+        assert!(OdeSolverState::new(&problem, &Adams::default()).is_err());
+    }
 }
@@ -0,0 +1,605 @@
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+
+use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::Tableau;
+use crate::{
+    scale, DenseMatrix, MatrixView, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, Op, Scalar, SolverCapabilities, Vector, VectorView, VectorViewMut,
+};
+
+use super::bdf::BdfStatistics;
+
+/// An explicit Runge-Kutta method with an embedded pair for error control, for non-stiff problems.
+/// The particular method is defined by the [Tableau] used to create the solver, e.g. [Tableau::dopri5] or [Tableau::tsit5].
+/// If the `beta` matrix of the [Tableau] is present this is used for interpolation, otherwise hermite interpolation is used.
+///
+/// If the last row of the `a` matrix is the same as the `b` vector and the last element of the `c` vector is 1 (the first-same-as-last property),
+/// the last stage is reused as the first stage of the next step.
+///
+/// Restrictions:
+/// - The diagonal and upper triangular part of the `a` matrix must be zero (i.e. explicit).
+/// - The problem must not have a mass matrix or sensitivities.
+pub struct Erk<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    tableau: Tableau<M>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    diff: M,
+    a_rows: Vec<Eqn::V>,
+    is_fsal: bool,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    stage_y: Eqn::V,
+    stage_f: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<M, Eqn> Default for Erk<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn default() -> Self {
+        Self::new(Tableau::<M>::tsit5())
+    }
+}
+
+impl<M, Eqn> Erk<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    pub fn new(tableau: Tableau<M>) -> Self {
+        // check that the diagonal and upper triangular part of a is zero
+        let s = tableau.s();
+        for i in 0..s {
+            for j in i..s {
+                assert_eq!(
+                    tableau.a()[(i, j)],
+                    Eqn::T::zero(),
+                    "Invalid tableau, expected a(i, j) = 0 for i >= j"
+                );
+            }
+        }
+
+        let mut a_rows = Vec::with_capacity(s);
+        for i in 0..s {
+            let mut row = Vec::with_capacity(i);
+            for j in 0..i {
+                row.push(tableau.a()[(i, j)].clone());
+            }
+            a_rows.push(Eqn::V::from_vec(row));
+        }
+
+        // check if the last stage is the solution (first same as last)
+        let is_fsal = tableau.c()[s - 1] == Eqn::T::one()
+            && (0..s).all(|i| tableau.a()[(s - 1, i)] == tableau.b()[i]);
+
+        let n = 1;
+        Self {
+            diff: M::zeros(n, s),
+            tableau,
+            problem: None,
+            state: None,
+            a_rows,
+            is_fsal,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            stage_y: <Eqn::V as Vector>::zeros(n),
+            stage_f: <Eqn::V as Vector>::zeros(n),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    fn interpolate_beta_function(theta: Eqn::T, beta: &M) -> Eqn::V {
+        let poly_order = beta.ncols();
+        let s_star = beta.nrows();
+        let mut thetav = Vec::with_capacity(poly_order);
+        thetav.push(theta.clone());
+        for i in 1..poly_order {
+            thetav.push(theta.clone() * thetav[i - 1].clone());
+        }
+        // beta_poly = beta * thetav
+        let thetav = Eqn::V::from_vec(thetav);
+        let mut beta_f = <Eqn::V as Vector>::zeros(s_star);
+        beta.gemv(Eqn::T::one(), &thetav, Eqn::T::zero(), &mut beta_f);
+        beta_f
+    }
+
+    fn interpolate_hermite(
+        theta: Eqn::T,
+        u0: &Eqn::V,
+        u1: &Eqn::V,
+        hf0: &Eqn::V,
+        hf1: &Eqn::V,
+    ) -> Eqn::V {
+        u0 * scale(Eqn::T::cast(1.0) - theta.clone())
+            + u1 * scale(theta.clone())
+            + ((u1 - u0) * scale(Eqn::T::cast(1.0) - Eqn::T::cast(2.0) * theta.clone())
+                + hf0 * scale(theta.clone() - Eqn::T::cast(1.0))
+                + hf1 * scale(theta.clone()))
+                * scale(theta.clone() * (theta - Eqn::T::cast(1.0)))
+    }
+}
+
+impl<M, Eqn> OdeSolverMethod<Eqn> for Erk<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        self.tableau.order()
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: false,
+            max_order: self.tableau.order(),
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: true,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.root_finder = None;
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        let nstates = state.y.len();
+        self.diff = M::zeros(nstates, self.tableau.s());
+        self.stage_y = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_f = <Eqn::V as Vector>::zeros(nstates);
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs().clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let s = self.tableau.s();
+        let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the derivative and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            let state = self.state.as_mut().unwrap();
+            rhs.call_inplace(&state.y, state.t.clone(), &mut state.dy);
+            self.t_compensation = Eqn::T::zero();
+        }
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // loop until step is accepted
+        loop {
+            let state = self.state.as_ref().unwrap();
+            let t0 = state.t.clone();
+            let h = state.h.clone();
+
+            // the first stage is the derivative at the start of the step
+            {
+                let mut hf = self.diff.column_mut(0);
+                hf.copy_from(&state.dy);
+                hf *= scale(h.clone());
+            }
+            for i in 1..s {
+                let t = t0.clone() + self.tableau.c()[i].clone() * h.clone();
+                self.stage_y.copy_from(&state.y);
+                self.diff.columns(0, i).gemv_o(
+                    Eqn::T::one(),
+                    &self.a_rows[i],
+                    Eqn::T::one(),
+                    &mut self.stage_y,
+                );
+                rhs.call_inplace(&self.stage_y, t, &mut self.stage_f);
+                let mut hf = self.diff.column_mut(i);
+                hf.copy_from(&self.stage_f);
+                hf *= scale(h.clone());
+            }
+
+            // the solution is the last stage for fsal methods
+            if self.is_fsal {
+                y1.copy_from(&self.stage_y);
+            } else {
+                y1.copy_from(&state.y);
+                self.diff
+                    .gemv(Eqn::T::one(), self.tableau.b(), Eqn::T::one(), &mut y1);
+            }
+
+            // compute error norm
+            self.diff
+                .gemv(Eqn::T::one(), self.tableau.d(), Eqn::T::zero(), &mut error);
+            let atol = self.problem.as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem.as_ref().unwrap().rtol.clone();
+            let error_norm = error.squared_norm(&y1, atol, rtol);
+
+            // adjust step size based on error, the error estimate is of the same order as the method
+            let safety = self.problem.as_ref().unwrap().options.safety_factor.clone();
+            let order = self.tableau.order() as f64;
+            let mut factor = safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / order));
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+
+        // project the solution onto the invariants of the equations (if any)
+        let problem = self.problem.as_ref().unwrap();
+        problem.project(&mut state.y, state.t.clone())?;
+
+        // the derivative at the end of the step is the last stage for fsal methods, unless the solution was projected
+        if self.is_fsal && !problem.eqn.has_invariants() {
+            std::mem::swap(&mut self.stage_f, &mut state.dy);
+        } else {
+            rhs.call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        }
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt.clone();
+
+        if let Some(beta) = self.tableau.beta() {
+            // ret = old_y + sum_{i=0}^{s_star-1} beta[i] * diff[:, i]
+            let beta_f = Self::interpolate_beta_function(theta, beta);
+            let mut ret = self.old_y.clone();
+            self.diff
+                .gemv(Eqn::T::one(), &beta_f, Eqn::T::one(), &mut ret);
+            Ok(ret)
+        } else {
+            let hf0 = self.diff.column(0).into_owned();
+            let hf1 = state.dy.clone() * scale(dt);
+            let ret = Self::interpolate_hermite(theta, &self.old_y, &state.y, &hf0, &hf1);
+            Ok(ret)
+        }
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<M, Eqn> StepChecks<Eqn> for Erk<M, Eqn>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_sens,
+                    exponential_decay_problem_with_root,
+                },
+                gaussian_decay::gaussian_decay_problem,
+                harmonic_oscillator::harmonic_oscillator_problem,
+                robertson::robertson,
+            },
+            tests::{
+                test_capabilities, test_global_error_exponential_decay, test_interpolate,
+                test_invariant_harmonic_oscillator, test_local_error_order_exponential_decay,
+                test_no_set_problem, test_ode_solver, test_state_mut,
+            },
+        },
+        Erk, OdeEquations, OdeSolverState, Op, StepControl, Tableau,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn erk_no_set_problem() {
+        test_no_set_problem::<M, _>(Erk::<M, _>::default());
+    }
+    #[test]
+    fn erk_state_mut() {
+        test_state_mut::<M, _>(Erk::<M, _>::default());
+    }
+    #[test]
+    fn erk_test_interpolate() {
+        test_interpolate::<M, _>(Erk::<M, _>::new(Tableau::<M>::dopri5()));
+        test_interpolate::<M, _>(Erk::<M, _>::new(Tableau::<M>::tsit5()));
+    }
+
+    #[test]
+    fn erk_test_global_error_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_global_error_exponential_decay(Erk::<M, _>::default(), p);
+    }
+
+    #[test]
+    fn erk_test_invariant_harmonic_oscillator() {
+        let (p, soln) = harmonic_oscillator_problem::<M>();
+        test_invariant_harmonic_oscillator(Erk::<M, _>::default(), p, soln);
+    }
+
+    #[test]
+    fn erk_test_capabilities_exponential_decay() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        test_capabilities(Erk::<M, _>::default(), problem);
+    }
+
+    #[test]
+    fn erk_test_unsupported_problems() {
+        let (problem, _soln) = robertson::<M>();
+        let s = Erk::<M, _>::default();
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+        let (problem, _soln) = exponential_decay_problem_sens::<M>();
+        let s = Erk::<M, _>::default();
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.options.step_control = StepControl::Fixed(0.1);
+        let s = Erk::<M, _>::default();
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.backward = true;
+        let s = Erk::<M, _>::default();
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+    }
+
+    #[test]
+    fn erk_test_local_error_order() {
+        // dopri5 and tsit5 are both 5th order, so the local error is O(h^6)
+        for tableau in [
+            Tableau::<M>::dopri5 as fn() -> Tableau<M>,
+            Tableau::<M>::tsit5,
+        ] {
+            let (problem, _soln) = exponential_decay_problem::<M>();
+            test_local_error_order_exponential_decay(|| Erk::new(tableau()), problem, 1.0, 5);
+        }
+    }
+
+    #[test]
+    fn test_tableau_continuous_extension() {
+        // the continuous extension must match the solution at the end of the step
+        for tableau in [Tableau::<M>::dopri5(), Tableau::<M>::tsit5()] {
+            let beta = tableau.beta().unwrap();
+            for i in 0..tableau.s() {
+                let sum = (0..beta.ncols()).map(|j| beta[(i, j)]).sum::<f64>();
+                assert!(abs(sum - tableau.b()[i]) < 1e-12);
+            }
+            // both the method and the embedded method are consistent
+            let b_sum = (0..tableau.s()).map(|i| tableau.b()[i]).sum::<f64>();
+            let d_sum = (0..tableau.s()).map(|i| tableau.d()[i]).sum::<f64>();
+            assert!(abs(b_sum - 1.0) < 1e-12);
+            assert!(abs(d_sum) < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_dopri5_nalgebra_exponential_decay() {
+        let mut s = Erk::new(Tableau::<M>::dopri5());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 6
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.07647244913317301
+        final_step_size: 2.9073391071341117
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 38
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_tsit5_nalgebra_exponential_decay() {
+        let mut s = Erk::new(Tableau::<M>::tsit5());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 5
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.07647244913317301
+        final_step_size: 3.2021852686240466
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 32
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_tsit5_nalgebra_gaussian_decay() {
+        let mut s = Erk::new(Tableau::<M>::tsit5());
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[test]
+    fn test_tstop_dopri5() {
+        let mut s = Erk::new(Tableau::<M>::dopri5());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_tsit5() {
+        let mut s = Erk::new(Tableau::<M>::tsit5());
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,254 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::{errors::PSError, scalar::Scalar, PopulationSolution, SubjectSolution, Vector};
+
+/// A residual error model, describing the distribution of an observation `y` around the model prediction `f`, where `eps` is a standard normal random variable.
+///
+/// - `Additive`: `y = f + sigma * eps`
+/// - `Proportional`: `y = f + sigma * f * eps`
+/// - `Combined`: `y = f + sqrt(additive^2 + (proportional * f)^2) * eps`
+/// - `LogNormal`: `y = f * exp(sigma * eps)`
+#[derive(Clone, Debug)]
+pub enum ErrorModel<T: Scalar> {
+    Additive { sigma: T },
+    Proportional { sigma: T },
+    Combined { additive: T, proportional: T },
+    LogNormal { sigma: T },
+}
+
+impl<T: Scalar> ErrorModel<T> {
+    /// The standard deviation of the observation error for a prediction `f` (for [Self::LogNormal] this is the standard deviation of `log(y)`)
+    pub fn std_dev(&self, f: T) -> T {
+        match self.clone() {
+            Self::Additive { sigma } => sigma,
+            Self::Proportional { sigma } => sigma * f.abs(),
+            Self::Combined {
+                additive,
+                proportional,
+            } => (additive.clone() * additive
+                + proportional.clone() * proportional * f.clone() * f)
+                .sqrt(),
+            Self::LogNormal { sigma } => sigma,
+        }
+    }
+
+    /// Sample a simulated observation given the model prediction `f`
+    pub fn sample<R: Rng + ?Sized>(&self, f: T, rng: &mut R) -> T {
+        let eps = T::cast(rng.sample::<f64, _>(StandardNormal));
+        match self {
+            Self::LogNormal { sigma } => f * (sigma.clone() * eps).exp(),
+            _ => f.clone() + self.std_dev(f) * eps,
+        }
+    }
+
+    /// The log-likelihood of an observation `y` given the model prediction `f`
+    pub fn log_likelihood(&self, f: T, y: T) -> T {
+        let half = T::cast(0.5);
+        let log_2pi = T::cast((2.0 * std::f64::consts::PI).ln());
+        match self {
+            Self::LogNormal { sigma } => {
+                if y <= T::zero() || f <= T::zero() {
+                    return T::cast(f64::NEG_INFINITY);
+                }
+                let r = (y.clone().ln() - f.ln()) / sigma.clone();
+                -half * (log_2pi + r.clone() * r) - sigma.clone().ln() - y.ln()
+            }
+            _ => {
+                let sd = self.std_dev(f.clone());
+                let r = (y - f) / sd.clone();
+                -half * (log_2pi + r.clone() * r) - sd.ln()
+            }
+        }
+    }
+
+    /// The derivative of [Self::log_likelihood] with respect to the model prediction `f`, used to calculate the gradient of the log-likelihood
+    /// with respect to the parameters from the sensitivities of the solution
+    pub fn log_likelihood_derivative(&self, f: T, y: T) -> T {
+        match self {
+            Self::LogNormal { sigma } => {
+                if y <= T::zero() || f <= T::zero() {
+                    return T::zero();
+                }
+                (y.ln() - f.clone().ln()) / (sigma.clone() * sigma.clone() * f)
+            }
+            _ => {
+                // for log L = -log(2 pi) / 2 - r^2 / 2 - log(sd) with r = (y - f) / sd, where sd depends on f
+                let sd = self.std_dev(f.clone());
+                let r = (y - f.clone()) / sd.clone();
+                r.clone() / sd.clone()
+                    + (r.clone() * r - T::one()) * self.std_dev_derivative(f) / sd
+            }
+        }
+    }
+
+    fn std_dev_derivative(&self, f: T) -> T {
+        match self.clone() {
+            Self::Additive { .. } | Self::LogNormal { .. } => T::zero(),
+            Self::Proportional { sigma } => {
+                if f < T::zero() {
+                    -sigma
+                } else {
+                    sigma
+                }
+            }
+            Self::Combined { proportional, .. } => {
+                proportional.clone() * proportional * f.clone() / self.std_dev(f)
+            }
+        }
+    }
+
+    /// Sample a simulated observation for each component of the prediction `f`
+    pub fn sample_vector<V: Vector<T = T>, R: Rng + ?Sized>(&self, f: &V, rng: &mut R) -> V {
+        let mut y = f.clone();
+        for i in 0..y.len() {
+            y[i] = self.sample(f[i].clone(), rng);
+        }
+        y
+    }
+}
+
+impl<V: Vector> PopulationSolution<V> {
+    /// Generate a simulated dataset by applying a residual error model to each output, `models` must contain one error model for each
+    /// output (i.e. state) of the solution. The returned solution has the same structure, with each value replaced by a simulated observation.
+    pub fn simulate_observations<R: Rng + ?Sized>(
+        &self,
+        models: &[ErrorModel<V::T>],
+        rng: &mut R,
+    ) -> Result<Self, PSError> {
+        let subjects = self
+            .subjects
+            .iter()
+            .map(|s| {
+                let y =
+                    s.y.iter()
+                        .map(|f| {
+                            if f.len() != models.len() {
+                                return Err(PSError::DimensionMismatch {
+                                    name: "error models".to_string(),
+                                    expected: f.len(),
+                                    found: models.len(),
+                                });
+                            }
+                            let mut y = f.clone();
+                            for (i, model) in models.iter().enumerate() {
+                                y[i] = model.sample(f[i].clone(), rng);
+                            }
+                            Ok(y)
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                Ok(SubjectSolution {
+                    id: s.id.clone(),
+                    t: s.t.clone(),
+                    y,
+                    time_after_dose: s.time_after_dose.clone(),
+                    dose_count: s.dose_count.clone(),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { subjects })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{ode_solver::error_model::ErrorModel, PopulationSolution, SubjectSolution};
+
+    type V = nalgebra::DVector<f64>;
+
+    fn sample_moments(model: &ErrorModel<f64>, f: f64, n: usize) -> (f64, f64) {
+        let mut rng = StdRng::seed_from_u64(0);
+        let samples = (0..n)
+            .map(|_| model.sample(f, &mut rng))
+            .collect::<Vec<_>>();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let var = samples.iter().map(|y| (y - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+        (mean, var.sqrt())
+    }
+
+    #[test]
+    fn test_error_model_moments() {
+        let n = 20000;
+        let models = [
+            ErrorModel::Additive { sigma: 0.5 },
+            ErrorModel::Proportional { sigma: 0.1 },
+            ErrorModel::Combined {
+                additive: 0.5,
+                proportional: 0.1,
+            },
+        ];
+        for model in models.iter() {
+            let (mean, sd) = sample_moments(model, 10.0, n);
+            assert!((mean - 10.0).abs() < 0.05, "{:?} mean {}", model, mean);
+            let expect = model.std_dev(10.0);
+            assert!((sd - expect).abs() < 0.05 * expect, "{:?} sd {}", model, sd);
+        }
+
+        // log-normal observations are always positive, with a median equal to the prediction
+        let model = ErrorModel::LogNormal { sigma: 0.2 };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut samples = (0..n)
+            .map(|_| model.sample(10.0, &mut rng))
+            .collect::<Vec<f64>>();
+        assert!(samples.iter().all(|&y| y > 0.0));
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((samples[n / 2] - 10.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_error_model_log_likelihood() {
+        let model = ErrorModel::Additive { sigma: 2.0 };
+        let expect = -0.5 * (2.0 * std::f64::consts::PI).ln() - 2.0f64.ln() - 0.5 * 0.25;
+        assert!((model.log_likelihood(1.0, 2.0) - expect).abs() < 1e-12);
+        let model = ErrorModel::LogNormal { sigma: 0.5 };
+        assert_eq!(model.log_likelihood(1.0, -1.0), f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn test_error_model_log_likelihood_derivative() {
+        let models = [
+            ErrorModel::Additive { sigma: 0.5 },
+            ErrorModel::Proportional { sigma: 0.1 },
+            ErrorModel::Combined {
+                additive: 0.5,
+                proportional: 0.1,
+            },
+            ErrorModel::LogNormal { sigma: 0.2 },
+        ];
+        let eps = 1e-6;
+        for model in models.iter() {
+            let (f, y) = (3.0_f64, 3.5);
+            let fd =
+                (model.log_likelihood(f + eps, y) - model.log_likelihood(f - eps, y)) / (2.0 * eps);
+            let d = model.log_likelihood_derivative(f, y);
+            assert!((d - fd).abs() < 1e-6, "{:?} {} {}", model, d, fd);
+        }
+    }
+
+    #[test]
+    fn test_simulate_observations() {
+        let soln = PopulationSolution {
+            subjects: vec![SubjectSolution {
+                id: "0".to_string(),
+                t: vec![0.0, 1.0],
+                y: vec![V::from_vec(vec![1.0, 2.0]), V::from_vec(vec![3.0, 4.0])],
+                time_after_dose: vec![None, Some(1.0)],
+                dose_count: vec![0, 1],
+            }],
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let models = [
+            ErrorModel::Additive { sigma: 0.0 },
+            ErrorModel::Proportional { sigma: 0.1 },
+        ];
+        let obs = soln.simulate_observations(&models, &mut rng).unwrap();
+        assert_eq!(obs.subjects[0].t, vec![0.0, 1.0]);
+        assert_eq!(obs.subjects[0].time_after_dose, vec![None, Some(1.0)]);
+        assert_eq!(obs.subjects[0].y[0][0], 1.0);
+        assert_eq!(obs.subjects[0].y[1][0], 3.0);
+        assert_ne!(obs.subjects[0].y[1][1], 4.0);
+        assert!(soln.simulate_observations(&models[..1], &mut rng).is_err());
+    }
+}
@@ -0,0 +1,729 @@
+use nalgebra::{ComplexField, DMatrix};
+use num_traits::abs;
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+
+use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::{
+    scale, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op,
+    Scalar, SolverCapabilities, Vector,
+};
+
+use super::bdf::BdfStatistics;
+
+/// Returns `φ_k(A) e_1`, where `φ_0(z) = exp(z)` and `φ_{k+1}(z) = (φ_k(z) - 1/k!) / z`.
+/// For `k > 0` this is the last column of the top `m` rows of the exponential of the augmented matrix `[[A, e_1, 0], [0, 0, I_{k-1}], [0, 0, 0]]`
+/// (see Sidje, "Expokit: a software package for computing matrix exponentials", ACM TOMS 24 (1998)).
+fn phi_e1(k: usize, a: &DMatrix<f64>) -> Vec<f64> {
+    let m = a.nrows();
+    let mut aug = DMatrix::<f64>::zeros(m + k, m + k);
+    aug.view_mut((0, 0), (m, m)).copy_from(a);
+    if k > 0 {
+        aug[(0, m)] = 1.0;
+        for i in 0..k - 1 {
+            aug[(m + i, m + i + 1)] = 1.0;
+        }
+    }
+    let exp = aug.exp();
+    let col = if k == 0 { 0 } else { m + k - 1 };
+    (0..m).map(|i| exp[(i, col)]).collect()
+}
+
+/// The Krylov approximation `beta V φ_k(sH) e_1` of `φ_k(sJ) b` for any `s`, where `V` is the orthonormal basis and `H` the upper hessenberg matrix
+/// of the arnoldi process for the jacobian `J` and the vector `b`, and `beta = |b|`.
+struct KrylovApproximation<V: Vector> {
+    k: usize,
+    beta: f64,
+    basis: Vec<V>,
+    hess: DMatrix<f64>,
+}
+
+impl<V: Vector> KrylovApproximation<V> {
+    fn zeros(k: usize) -> Self {
+        Self {
+            k,
+            beta: 0.0,
+            basis: Vec::new(),
+            hess: DMatrix::zeros(0, 0),
+        }
+    }
+
+    fn eval(&self, s: V::T, n: usize) -> V {
+        let mut ret = V::zeros(n);
+        if self.basis.is_empty() {
+            return ret;
+        }
+        let s: f64 = s.to_f64();
+        let phi = phi_e1(self.k, &(&self.hess * s));
+        for (v, &phi_i) in self.basis.iter().zip(phi.iter()) {
+            ret.axpy(V::T::cast(self.beta * phi_i), v, V::T::one());
+        }
+        ret
+    }
+}
+
+/// An exponential Rosenbrock method, suitable for stiff semilinear problems (e.g. the semi-discretisation of reaction-diffusion PDEs)
+/// where the stiffness comes from a dominant linear part of the right-hand side.
+///
+/// The method is `exprb32` from Hochbruck, Ostermann and Schweitzer, "Exponential Rosenbrock-type methods", SIAM J. Numer. Anal. 47 (2009).
+/// With `J` the jacobian of the rhs and `v` its time derivative at the start of the step, a step of size `h` is
+///
+/// ```text
+/// U = y_0 + h φ_1(hJ) f(t_0, y_0) + h^2 φ_2(hJ) v
+/// D = f(t_0 + h, U) - f(t_0, y_0) - J (U - y_0) - h v
+/// y_1 = U + 2h φ_3(hJ) D
+/// ```
+///
+/// The first stage is the second order exponential Rosenbrock-Euler method, and the final correction `2h φ_3(hJ) D` is used as the error estimate
+/// of the third order solution. The method is exact for linear constant-coefficient problems, so the step size is only limited by the nonlinear part
+/// of the rhs, not by its stiffness.
+///
+/// The products of the φ-functions with a vector are approximated in a Krylov subspace of the jacobian, built by an Arnoldi process using only
+/// the jacobian-vector product of the rhs ([NonLinearOp::jac_mul_inplace]), so neither the jacobian matrix nor any linear solves are required.
+/// The Krylov subspace is extended until the estimated error of the approximation is a fraction of the tolerance of the step, up to a dimension of
+/// [ExponentialIntegrator::MAX_KRYLOV_DIM], and if this is not sufficient the step is rejected and the step size reduced.
+/// The time derivative of the rhs (for non-autonomous problems) is approximated using a finite difference. Dense output replaces `h` by `s = t - t_0`
+/// in the φ-functions, `y(t_0 + s) = y_0 + s φ_1(sJ) f(t_0, y_0) + s^2 φ_2(sJ) v + 2 s^3 / h^2 φ_3(sJ) D`, reusing the Krylov subspaces of the step.
+///
+/// Restrictions:
+/// - The problem must not have a mass matrix or sensitivities.
+pub struct ExponentialIntegrator<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_h: Eqn::T,
+    dense: Option<[KrylovApproximation<Eqn::V>; 3]>,
+    ft: Eqn::V,
+    krylov_dim: usize,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn> Default for ExponentialIntegrator<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Eqn> ExponentialIntegrator<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+    const ORDER: usize = 3;
+
+    /// The maximum dimension of the Krylov subspace used to approximate each φ-function product
+    pub const MAX_KRYLOV_DIM: usize = 30;
+
+    // the Krylov approximation error must be less than this fraction of the tolerance of the step
+    const KRYLOV_TOL: f64 = 0.1;
+
+    pub fn new() -> Self {
+        let n = 1;
+        Self {
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            old_h: Eqn::T::zero(),
+            dense: None,
+            ft: <Eqn::V as Vector>::zeros(n),
+            krylov_dim: 0,
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    /// The largest Krylov subspace dimension used in the last step
+    pub fn krylov_dim(&self) -> usize {
+        self.krylov_dim
+    }
+
+    /// Approximate `φ_k(hJ) b` in the Krylov subspace of the jacobian `J` of the rhs at `(y, t)`, spanned by `b, Jb, J^2b, ...`.
+    /// The error of the approximation is scaled by `coeff` (its coefficient in the step) and measured in the same weighted norm as the
+    /// error test of the step. Returns `None` if it has not converged within [Self::MAX_KRYLOV_DIM] iterations.
+    fn phi_krylov(
+        &mut self,
+        k: usize,
+        h: Eqn::T,
+        b: &Eqn::V,
+        y: &Eqn::V,
+        t: Eqn::T,
+        coeff: Eqn::T,
+    ) -> Option<KrylovApproximation<Eqn::V>> {
+        let problem = self.problem.as_ref().unwrap();
+        let rhs = problem.eqn.rhs().clone();
+        let atol = problem.atol.clone();
+        let rtol = problem.rtol.clone();
+        let n = b.len();
+        let beta = b.norm();
+        if beta == Eqn::T::zero() {
+            return Some(KrylovApproximation::zeros(k));
+        }
+        let max_dim = std::cmp::min(n, Self::MAX_KRYLOV_DIM);
+        let h_f64: f64 = h.to_f64();
+        let beta_f64: f64 = beta.to_f64();
+        let coeff_f64: f64 = abs(coeff.to_f64());
+
+        // arnoldi process, with the upper hessenberg matrix hess = V^T J V
+        let mut basis = vec![b.clone() * scale(Eqn::T::one() / beta.clone())];
+        let mut hess = DMatrix::<f64>::zeros(max_dim + 1, max_dim);
+        let mut w = <Eqn::V as Vector>::zeros(n);
+        for j in 0..max_dim {
+            rhs.jac_mul_inplace(y, t.clone(), &basis[j], &mut w);
+            for (i, v) in basis.iter().enumerate() {
+                let hij = w.dot(v);
+                hess[(i, j)] = hij.to_f64();
+                w.axpy(-hij, v, Eqn::T::one());
+            }
+            let w_norm = w.norm();
+            hess[(j + 1, j)] = w_norm.to_f64();
+
+            // a breakdown means that the subspace is invariant and the approximation is exact,
+            // otherwise estimate the error using the next term of the arnoldi relation, `beta h phi_j w`
+            let m = j + 1;
+            let phi = phi_e1(k, &(hess.view((0, 0), (m, m)) * h_f64));
+            let breakdown = w_norm <= Eqn::T::EPSILON * beta.clone();
+            let w_norm_weighted: f64 = w
+                .squared_norm(y, atol.as_ref(), rtol.clone())
+                .sqrt()
+                .to_f64();
+            let error = coeff_f64 * beta_f64 * abs(h_f64) * abs(phi[j]) * w_norm_weighted;
+            if breakdown || error <= Self::KRYLOV_TOL {
+                self.krylov_dim = std::cmp::max(self.krylov_dim, m);
+                return Some(KrylovApproximation {
+                    k,
+                    beta: beta_f64,
+                    basis,
+                    hess: hess.view((0, 0), (m, m)).into_owned(),
+                });
+            }
+            basis.push(w.clone() * scale(Eqn::T::one() / w_norm));
+        }
+        self.krylov_dim = max_dim;
+        None
+    }
+}
+
+impl<Eqn> OdeSolverMethod<Eqn> for ExponentialIntegrator<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        Self::ORDER
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: true,
+            max_order: Self::ORDER,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.dense = None;
+        self.root_finder = None;
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        let nstates = state.y.len();
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.old_h = Eqn::T::zero();
+        self.dense = None;
+        self.ft = <Eqn::V as Vector>::zeros(nstates);
+        self.krylov_dim = 0;
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let eqn = self.problem.as_ref().unwrap().eqn.clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+        let mut f_stage = <Eqn::V as Vector>::zeros(n);
+        let mut jac_diff = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the accumulated roundoff in t is no longer valid,
+        // and the derivative at the start of the step must be recalculated
+        if self.is_state_mutated {
+            self.t_compensation = Eqn::T::zero();
+            let state = self.state.as_mut().unwrap();
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        }
+
+        // the time derivative of the rhs at the start of the step (using a finite difference)
+        let (y0, f0, t0) = {
+            let state = self.state.as_ref().unwrap();
+            let dt = Eqn::T::EPSILON.sqrt() * (Eqn::T::one() + abs(state.t.clone()));
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone() + dt.clone(), &mut self.ft);
+            self.ft
+                .axpy(-Eqn::T::one() / dt.clone(), &state.dy, Eqn::T::one() / dt);
+            (state.y.clone(), state.dy.clone(), state.t.clone())
+        };
+        let atol = self.problem.as_ref().unwrap().atol.clone();
+        let rtol = self.problem.as_ref().unwrap().rtol.clone();
+        let ft = self.ft.clone();
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+        let mut h: Eqn::T;
+        let mut dense = None;
+
+        // loop until step is accepted
+        loop {
+            h = self.state.as_ref().unwrap().h.clone();
+            self.krylov_dim = 0;
+
+            // U = y0 + h phi_1(hJ) f0 + h^2 phi_2(hJ) ft
+            let phi1 = self.phi_krylov(1, h.clone(), &f0, &y0, t0.clone(), h.clone());
+            let phi2 = self.phi_krylov(2, h.clone(), &ft, &y0, t0.clone(), h.clone() * h.clone());
+            let error = if let (Some(phi1), Some(phi2)) = (phi1, phi2) {
+                let mut u = y0.clone();
+                u.axpy(h.clone(), &phi1.eval(h.clone(), n), Eqn::T::one());
+                u.axpy(
+                    h.clone() * h.clone(),
+                    &phi2.eval(h.clone(), n),
+                    Eqn::T::one(),
+                );
+
+                // D = f(t0 + h, U) - f0 - J (U - y0) - h ft
+                eqn.rhs()
+                    .call_inplace(&u, t0.clone() + h.clone(), &mut f_stage);
+                y1.copy_from(&u);
+                y1 -= &y0;
+                eqn.rhs()
+                    .jac_mul_inplace(&y0, t0.clone(), &y1, &mut jac_diff);
+                f_stage -= &f0;
+                f_stage -= &jac_diff;
+                f_stage.axpy(-h.clone(), &ft, Eqn::T::one());
+
+                // y1 = U + 2h phi_3(hJ) D, the correction is the error estimate
+                self.phi_krylov(
+                    3,
+                    h.clone(),
+                    &f_stage,
+                    &y0,
+                    t0.clone(),
+                    Eqn::T::cast(2.0) * h.clone(),
+                )
+                .map(|phi3| {
+                    let error = phi3.eval(h.clone(), n) * scale(Eqn::T::cast(2.0) * h.clone());
+                    y1.copy_from(&u);
+                    y1 += &error;
+                    dense = Some([phi1, phi2, phi3]);
+                    error
+                })
+            } else {
+                None
+            };
+
+            // adjust step size based on error, if the krylov approximation did not converge the step is rejected
+            let (error_norm, mut factor) = match error {
+                Some(error) => {
+                    let error_norm = error.squared_norm(&y1, atol.as_ref(), rtol.clone());
+                    let safety = self.problem.as_ref().unwrap().options.safety_factor.clone();
+                    let factor = safety
+                        * error_norm
+                            .clone()
+                            .pow(Eqn::T::cast(-0.5 / Self::ORDER as f64));
+                    (error_norm, factor)
+                }
+                None => (Eqn::T::cast(f64::INFINITY), Eqn::T::cast(0.5)),
+            };
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+        eqn.rhs()
+            .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        self.old_h = h;
+        self.dense = dense;
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let Some([phi1, phi2, phi3]) = self.dense.as_ref() else {
+            return Ok(state.y.clone());
+        };
+
+        // y0 + s phi_1(sJ) f0 + s^2 phi_2(sJ) ft + 2 s^3 / h^2 phi_3(sJ) D
+        let n = state.y.len();
+        let s = t - self.old_t.clone();
+        let mut ret = self.old_y.clone();
+        ret.axpy(s.clone(), &phi1.eval(s.clone(), n), Eqn::T::one());
+        ret.axpy(
+            s.clone() * s.clone(),
+            &phi2.eval(s.clone(), n),
+            Eqn::T::one(),
+        );
+        let c3 = Eqn::T::cast(2.0) * s.clone() * s.clone() * s.clone()
+            / (self.old_h.clone() * self.old_h.clone());
+        ret.axpy(c3, &phi3.eval(s, n), Eqn::T::one());
+        Ok(ret)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<Eqn> StepChecks<Eqn> for ExponentialIntegrator<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{
+        errors::PSError,
+        ode_solver::{
+            test_models::{
+                dydt_y2::dydt_y2_problem,
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                heat1d::heat1d_problem,
+                robertson_ode::robertson_ode,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        ExponentialIntegrator, OdeEquations, OdeSolverMethod, OdeSolverState, Op, Vector,
+    };
+
+    use super::phi_e1;
+
+    use nalgebra::DMatrix;
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+    type Eqn = crate::ode_solver::tests::TestEqn<M>;
+
+    #[test]
+    fn exponential_no_set_problem() {
+        test_no_set_problem::<M, _>(ExponentialIntegrator::default());
+    }
+    #[test]
+    fn exponential_state_mut() {
+        test_state_mut::<M, _>(ExponentialIntegrator::default());
+    }
+    #[test]
+    fn exponential_test_interpolate() {
+        test_interpolate::<M, _>(ExponentialIntegrator::default());
+    }
+
+    // local error of a single step of size h for dy/dt = y^2, y(0) = -200, with solution y = y0 / (1 - y0 t)
+    fn exprb32_local_error(h: f64) -> f64 {
+        let (mut problem, _soln) = dydt_y2_problem::<M>(1);
+        problem.rtol = 1e-2;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(1, 1e-2));
+        let mut s = ExponentialIntegrator::default();
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = h;
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        let state = s.state().unwrap();
+        assert_eq!(state.t, h);
+        abs(state.y[0] - (-200.0 / (1.0 + 200.0 * h)))
+    }
+
+    #[test]
+    fn exponential_test_exactness_and_order() {
+        // the method is exact for linear constant-coefficient problems, so a single large step of dy/dt = -0.1 y is accurate
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut s = ExponentialIntegrator::default();
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = 5.0;
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        let state = s.state().unwrap();
+        assert_eq!(state.t, 5.0);
+        state
+            .y
+            .assert_eq_st(&nalgebra::DVector::from_element(2, (-0.5f64).exp()), 1e-10);
+
+        // exprb32 is 3rd order for nonlinear problems, so the local error is O(h^4)
+        let ratio = exprb32_local_error(2e-4) / exprb32_local_error(1e-4);
+        assert!(ratio > 12.0 && ratio < 20.0, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn exponential_test_solution_bound_exponential_decay() {
+        // the method is exact for linear problems so the steps are too large for the shared test's bound on the
+        // failure time, only check that the bound is detected at the end of the step which exceeds it
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem
+            .set_params(nalgebra::DVector::from_element(1, -1.0))
+            .unwrap();
+        problem.max_abs_state = Some(Rc::new(nalgebra::DVector::from_element(2, 10.0)));
+        let mut s = ExponentialIntegrator::default();
+        match s.solve(&problem, 10.0) {
+            Err(PSError::SolutionBoundExceeded { t, indices }) => {
+                let t: f64 = t.parse().unwrap();
+                assert!(t > 10.0f64.ln() && t <= 10.0, "t = {}", t);
+                assert_eq!(indices, vec![0, 1]);
+            }
+            _ => panic!("expected SolutionBoundExceeded error"),
+        }
+    }
+
+    #[test]
+    fn test_phi_functions() {
+        // for a scalar z, phi_1(z) = (e^z - 1) / z, phi_2(z) = (e^z - 1 - z) / z^2, phi_3(z) = (e^z - 1 - z - z^2/2) / z^3
+        let z: f64 = -2.5;
+        let a = DMatrix::from_element(1, 1, z);
+        let expect = [
+            z.exp(),
+            (z.exp() - 1.0) / z,
+            (z.exp() - 1.0 - z) / (z * z),
+            (z.exp() - 1.0 - z - z * z / 2.0) / (z * z * z),
+        ];
+        for (k, expect) in expect.iter().enumerate() {
+            let phi = phi_e1(k, &a);
+            assert!(abs(phi[0] - expect) < 1e-12, "phi_{} = {}", k, phi[0]);
+        }
+    }
+
+    #[test]
+    fn test_exponential_nalgebra_exponential_decay() {
+        let mut s = ExponentialIntegrator::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 4
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.02114742526881128
+        final_step_size: 211.47425268811276
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 14
+        number_of_jac_muls: 10
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_exponential_nalgebra_heat1d() {
+        let mut s = ExponentialIntegrator::default();
+        let (problem, soln) = heat1d_problem::<M>(50);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        // the problem is linear, so the method is exact up to the error in the krylov approximation
+        assert!(s.krylov_dim() <= ExponentialIntegrator::<Eqn>::MAX_KRYLOV_DIM);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 3
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0040216967447244956
+        final_step_size: 4.021696744724496
+        "###);
+    }
+
+    #[test]
+    fn test_exponential_nalgebra_robertson_ode() {
+        let mut s = ExponentialIntegrator::default();
+        let (problem, soln) = robertson_ode::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 235
+        number_of_error_test_failures: 2
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0031785690922085303
+        final_step_size: 36306733527.4172
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_exponential() {
+        let mut s = ExponentialIntegrator::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_exponential() {
+        let mut s = ExponentialIntegrator::default();
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,728 @@
+use nalgebra::Matrix4;
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+
+use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::{
+    scale, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op,
+    Scalar, SolverCapabilities, Vector,
+};
+
+use super::bdf::BdfStatistics;
+
+/// An explicit extrapolation method (Gragg-Bulirsch-Stoer) for smooth non-stiff problems where high accuracy is required.
+///
+/// Each step of size `H` is computed using Gragg's modified midpoint rule with `n_j = 4j + 2` substeps, for `j = 0, ..., k`.
+/// These are combined using Aitken-Neville extrapolation in `(H/n_j)^2`, giving a solution of order `2k + 2`.
+/// The difference between the last two entries of the extrapolation tableau is used as the error estimate, and the
+/// depth `k` of the tableau (and hence the order) is adapted each step to minimise the work per unit step,
+/// following the ODEX code of Hairer, Norsett and Wanner (Solving Ordinary Differential Equations I, Section II.9).
+///
+/// For dense output, the midpoint rule also provides approximations to the solution at the middle of the step and (using
+/// central differences of the right-hand side evaluations) to its derivatives there. These are extrapolated in the same way,
+/// and together with the solution and its derivative at both ends of the step define a Hermite interpolation polynomial
+/// of degree `2k + 3` over the step (HNW I, Section II.9, "Dense Output for Extrapolation Methods").
+///
+/// Restrictions:
+/// - The problem must not have a mass matrix or sensitivities.
+pub struct Extrapolation<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    dense: Vec<Eqn::V>,
+    table: Vec<Eqn::V>,
+    mid_derivatives: Vec<Vec<Eqn::V>>,
+    f_history: Vec<Eqn::V>,
+    z: Eqn::V,
+    z_prev: Eqn::V,
+    k: usize,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn> Default for Extrapolation<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Eqn> Extrapolation<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    /// The maximum depth of the extrapolation tableau, the maximum order of the method is `2 * MAX_K + 2`
+    pub const MAX_K: usize = 8;
+
+    // the depth is always at least 2, so that the work for depth k - 1 can be estimated
+    const MIN_K: usize = 2;
+
+    pub fn new() -> Self {
+        let n = 1;
+        Self {
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            old_dy: <Eqn::V as Vector>::zeros(n),
+            dense: Vec::new(),
+            table: Vec::new(),
+            mid_derivatives: Vec::new(),
+            f_history: Vec::new(),
+            z: <Eqn::V as Vector>::zeros(n),
+            z_prev: <Eqn::V as Vector>::zeros(n),
+            k: 4,
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    /// The number of substeps of the midpoint rule used for row `j` of the extrapolation tableau
+    fn substeps(j: usize) -> usize {
+        4 * j + 2
+    }
+
+    /// The number of right-hand side evaluations required to compute rows `0..=j` of the extrapolation tableau
+    fn work(j: usize) -> usize {
+        1 + (0..=j).map(Self::substeps).sum::<usize>()
+    }
+
+    /// Compute row `j` of the extrapolation tableau, using the modified midpoint rule with `n_j` substeps for the
+    /// first entry and Aitken-Neville extrapolation for the rest. On exit `table[j]` contains `T_{j,j}` and
+    /// `table[j - 1]` contains `T_{j,j-1}`, and `mid_derivatives[j]` contains the (unextrapolated) approximations
+    /// to the solution and its first `2j + 1` derivatives at the middle of the step.
+    fn tableau_row(&mut self, j: usize) {
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let state = self.state.as_ref().unwrap();
+        let n = Self::substeps(j);
+        let h = state.h.clone() / Eqn::T::cast(n as f64);
+        let two_h = Eqn::T::cast(2.0) * h.clone();
+
+        // modified midpoint rule, z_{m+1} = z_{m-1} + 2h f(z_m)
+        self.z_prev.copy_from(&state.y);
+        self.z.copy_from(&state.y);
+        self.z.axpy(h.clone(), &state.dy, Eqn::T::one());
+        let mid = n / 2;
+        if mid == 1 {
+            self.mid_derivatives[j][0].copy_from(&self.z);
+        }
+        for m in 1..n {
+            let t = state.t.clone() + Eqn::T::cast(m as f64) * h.clone();
+            rhs.call_inplace(&self.z, t, &mut self.f_history[m]);
+            self.z_prev
+                .axpy(two_h.clone(), &self.f_history[m], Eqn::T::one());
+            std::mem::swap(&mut self.z_prev, &mut self.z);
+            if m + 1 == mid {
+                self.mid_derivatives[j][0].copy_from(&self.z);
+            }
+        }
+
+        // derivatives at the middle of the step, y^(q+1) ~ delta^q f_mid / (2h)^q using central differences with
+        // step 2h, so that only values with the same parity as the midpoint (which is odd) are used
+        let mut inv_step = Eqn::T::one();
+        for q in 0..=2 * j {
+            let derivative = &mut self.mid_derivatives[j][q + 1];
+            derivative.copy_from(&self.f_history[mid + q]);
+            let mut binomial = 1;
+            for i in 1..=q {
+                binomial = binomial * (q + 1 - i) / i;
+                let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+                derivative.axpy(
+                    Eqn::T::cast(sign * binomial as f64),
+                    &self.f_history[mid + q - 2 * i],
+                    Eqn::T::one(),
+                );
+            }
+            *derivative *= scale(inv_step.clone());
+            inv_step /= two_h.clone();
+        }
+
+        // extrapolate, T_{j,l} = T_{j,l-1} + (T_{j,l-1} - T_{j-1,l-1}) / ((n_j / n_{j-l})^2 - 1)
+        for l in 1..=j {
+            // the ratio is computed in T so that the coefficients are exact to the precision of T
+            let ratio = Eqn::T::cast(n as f64) / Eqn::T::cast(Self::substeps(j - l) as f64);
+            let c = Eqn::T::one() / (ratio.clone() * ratio - Eqn::T::one());
+            // on exit table[l - 1] holds T_{j,l-1} and z holds T_{j,l}
+            std::mem::swap(&mut self.table[l - 1], &mut self.z);
+            self.z *= scale(-c.clone());
+            self.z
+                .axpy(Eqn::T::one() + c, &self.table[l - 1], Eqn::T::one());
+        }
+        self.table[j].copy_from(&self.z);
+    }
+
+    /// The error estimate for row `j` of the tableau, `T_{j,j} - T_{j,j-1}`, and the corresponding optimal step size factor
+    fn error_and_factor(&self, j: usize) -> (Eqn::T, Eqn::T) {
+        let problem = self.problem.as_ref().unwrap();
+        let mut error = self.table[j].clone();
+        error.axpy(-Eqn::T::one(), &self.table[j - 1], Eqn::T::one());
+        let error_norm =
+            error.squared_norm(&self.table[j], problem.atol.as_ref(), problem.rtol.clone());
+
+        // the error estimate is O(h^(2j + 1))
+        let safety = problem.options.safety_factor.clone();
+        let mut factor = safety
+            * error_norm
+                .clone()
+                .pow(Eqn::T::cast(-0.5 / (2.0 * j as f64 + 1.0)));
+        if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+            factor = Eqn::T::cast(Self::MIN_FACTOR);
+        }
+        if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+            factor = Eqn::T::cast(Self::MAX_FACTOR);
+        }
+        (error_norm, factor)
+    }
+
+    /// Compute the dense output polynomial for the step just taken with a tableau of depth `k`. This is stored as the
+    /// coefficients of `P(s)`, with `s = theta - 1/2` and `theta` the fraction of the step.
+    ///
+    /// The first `mu + 1 = 2k` coefficients are the extrapolated derivatives at the middle of the step (scaled by
+    /// `dt^i / i!`), the remaining four are chosen so that `P` interpolates the solution and its derivative at both
+    /// ends of the step.
+    fn dense_output(&mut self, k: usize) {
+        let state = self.state.as_ref().unwrap();
+        let dt = state.t.clone() - self.old_t.clone();
+        let mu = 2 * k - 1;
+        let mut coefficients = Vec::with_capacity(mu + 5);
+        let mut factor = Eqn::T::one();
+        for kappa in 0..=mu {
+            // only rows kappa / 2..=k of the tableau have enough substeps to approximate this derivative
+            let lambda = kappa / 2;
+            let mut column = (lambda..=k)
+                .map(|j| self.mid_derivatives[j][kappa].clone())
+                .collect::<Vec<_>>();
+            for l in 1..column.len() {
+                for i in (l..column.len()).rev() {
+                    let ratio = Eqn::T::cast(Self::substeps(lambda + i) as f64)
+                        / Eqn::T::cast(Self::substeps(lambda + i - l) as f64);
+                    let c = Eqn::T::one() / (ratio.clone() * ratio - Eqn::T::one());
+                    let (lower, upper) = column.split_at_mut(i);
+                    upper[0] *= scale(Eqn::T::one() + c.clone());
+                    upper[0].axpy(-c, &lower[i - 1], Eqn::T::one());
+                }
+            }
+            if kappa > 0 {
+                factor *= dt.clone() / Eqn::T::cast(kappa as f64);
+            }
+            let mut coefficient = column.pop().unwrap();
+            coefficient *= scale(factor.clone());
+            coefficients.push(coefficient);
+        }
+
+        // residuals of the end conditions P(-1/2) = y0, P(1/2) = y1, P'(-1/2) = dt f0 and P'(1/2) = dt f1
+        let mut residuals = [
+            self.old_y.clone(),
+            state.y.clone(),
+            self.old_dy.clone() * scale(dt.clone()),
+            state.dy.clone() * scale(dt),
+        ];
+        for (i, coefficient) in coefficients.iter().enumerate() {
+            let s_i = 0.5f64.powi(i as i32);
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            residuals[0].axpy(Eqn::T::cast(-sign * s_i), coefficient, Eqn::T::one());
+            residuals[1].axpy(Eqn::T::cast(-s_i), coefficient, Eqn::T::one());
+            if i > 0 {
+                let ds_i = i as f64 * 0.5f64.powi(i as i32 - 1);
+                residuals[2].axpy(Eqn::T::cast(sign * ds_i), coefficient, Eqn::T::one());
+                residuals[3].axpy(Eqn::T::cast(-ds_i), coefficient, Eqn::T::one());
+            }
+        }
+        let inverse = Self::end_conditions_inverse(mu);
+        for l in 0..4 {
+            let mut coefficient = <Eqn::V as Vector>::zeros(state.y.len());
+            for (i, residual) in residuals.iter().enumerate() {
+                coefficient.axpy(inverse[(l, i)].clone(), residual, Eqn::T::one());
+            }
+            coefficients.push(coefficient);
+        }
+        self.dense = coefficients;
+    }
+
+    /// The inverse of the matrix relating the coefficients of `s^(mu + 1), ..., s^(mu + 4)` in the dense output
+    /// polynomial to its values and derivatives at `s = -1/2` and `s = 1/2`
+    fn end_conditions_inverse(mu: usize) -> Matrix4<Eqn::T> {
+        let half = Eqn::T::cast(0.5);
+        Matrix4::from_fn(|i, l| {
+            let p = (mu + 1 + l) as i32;
+            match i {
+                0 => (-half.clone()).pow(p),
+                1 => half.clone().pow(p),
+                2 => Eqn::T::cast(f64::from(p)) * (-half.clone()).pow(p - 1),
+                _ => Eqn::T::cast(f64::from(p)) * half.clone().pow(p - 1),
+            }
+        })
+        .try_inverse()
+        .unwrap()
+    }
+}
+
+impl<Eqn> OdeSolverMethod<Eqn> for Extrapolation<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        2 * self.k + 2
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: false,
+            max_order: 2 * Self::MAX_K + 2,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.root_finder = None;
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        // initial depth of the tableau based on the requested tolerance (as in ODEX)
+        let rtol: f64 = problem.rtol.to_f64();
+        let k = (-(rtol + 1e-40).log10() * 0.6 + 0.5).floor() as usize;
+        self.k = k.clamp(Self::MIN_K, Self::MAX_K - 1);
+
+        let nstates = state.y.len();
+        self.table = vec![<Eqn::V as Vector>::zeros(nstates); Self::MAX_K + 1];
+        self.mid_derivatives = (0..=Self::MAX_K)
+            .map(|j| vec![<Eqn::V as Vector>::zeros(nstates); 2 * j + 2])
+            .collect();
+        self.f_history = vec![<Eqn::V as Vector>::zeros(nstates); Self::substeps(Self::MAX_K)];
+        self.z = <Eqn::V as Vector>::zeros(nstates);
+        self.z_prev = <Eqn::V as Vector>::zeros(nstates);
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.dense = Vec::new();
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs().clone();
+
+        // state has been mutated by the user, so the derivative and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            let state = self.state.as_mut().unwrap();
+            rhs.call_inplace(&state.y, state.t.clone(), &mut state.dy);
+            self.t_compensation = Eqn::T::zero();
+        }
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+        let mut k: usize;
+
+        // loop until step is accepted
+        loop {
+            k = self.k;
+            // the error estimate for depth k - 1 is needed for the order selection, this must be computed before
+            // the next row of the tableau overwrites it
+            let mut factor_m = Eqn::T::one();
+            for j in 0..=k {
+                self.tableau_row(j);
+                if j == k - 1 {
+                    factor_m = self.error_and_factor(j).1;
+                }
+            }
+            let (error_norm, factor) = self.error_and_factor(k);
+
+            // choose the depth of the tableau (and step size) for the next step to minimise the work per unit step
+            let work_m = Eqn::T::cast(Self::work(k - 1) as f64) / factor_m.clone();
+            let work = Eqn::T::cast(Self::work(k) as f64) / factor.clone();
+            let mut new_factor = factor.clone();
+            if k > Self::MIN_K && work_m < Eqn::T::cast(0.8) * work.clone() {
+                self.k = k - 1;
+                new_factor = factor_m;
+            } else if error_norm <= Eqn::T::one()
+                && k < Self::MAX_K
+                && work < Eqn::T::cast(0.9) * work_m
+            {
+                // only increase the depth after an accepted step, with the step size scaled by the relative work
+                self.k = k + 1;
+                new_factor = factor * Eqn::T::cast(Self::work(k + 1) as f64)
+                    / Eqn::T::cast(Self::work(k) as f64);
+                if new_factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                    new_factor = Eqn::T::cast(Self::MAX_FACTOR);
+                }
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= new_factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::one() {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        // take the step, the solution is the last entry of the tableau
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut self.table[k], &mut state.y);
+        std::mem::swap(&mut self.old_dy, &mut state.dy);
+        rhs.call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        self.dense_output(k);
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        // evaluate the dense output polynomial using horner's method
+        let s = (t - self.old_t.clone()) / dt - Eqn::T::cast(0.5);
+        let mut coefficients = self.dense.iter().rev();
+        let mut ret = coefficients.next().unwrap().clone();
+        for coefficient in coefficients {
+            ret.axpy(Eqn::T::one(), coefficient, s.clone());
+        }
+        Ok(ret)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<Eqn> StepChecks<Eqn> for Extrapolation<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{
+        errors::PSError,
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                gaussian_decay::gaussian_decay_problem,
+                lotka_volterra::lotka_volterra_problem,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        scale, ConstantOp, Extrapolation, OdeEquations, OdeSolverMethod, OdeSolverState, Op,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn extrapolation_no_set_problem() {
+        test_no_set_problem::<M, _>(Extrapolation::default());
+    }
+    #[test]
+    fn extrapolation_state_mut() {
+        test_state_mut::<M, _>(Extrapolation::default());
+    }
+    #[test]
+    fn extrapolation_test_interpolate() {
+        test_interpolate::<M, _>(Extrapolation::default());
+    }
+
+    // local error of a single step of size h for dy/dt = -0.1 y, with the depth of the tableau fixed at k for the step
+    fn extrapolation_local_error(k: usize, h: f64) -> f64 {
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.rtol = 1e-2;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(2, 1e-2));
+        let mut s = Extrapolation::default();
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = h;
+        s.set_problem(state, &problem);
+        s.k = k;
+        s.step().unwrap();
+        let state = s.state().unwrap();
+        assert_eq!(state.t, h);
+        let y = problem.eqn.init().call(0.0) * scale((-0.1 * h).exp());
+        (state.y.clone() - y).norm()
+    }
+
+    // the depth of the tableau used for each step when solving dy/dt = -0.1 y up to t = 10 with the relative tolerance rtol
+    fn extrapolation_depths(rtol: f64) -> Vec<usize> {
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.rtol = rtol;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(2, 1e-2 * rtol));
+        let mut s = Extrapolation::default();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        let mut depths = vec![s.k];
+        while s.state().unwrap().t < 10.0 {
+            s.step().unwrap();
+            depths.push(s.k);
+        }
+        depths
+    }
+
+    #[test]
+    fn extrapolation_test_order_control() {
+        // a tableau of depth k gives a method of order 2k + 2, so the local error is O(h^(2k + 3))
+        for (k, h) in [(2, 2.0), (3, 4.0)] {
+            let ratio = extrapolation_local_error(k, h) / extrapolation_local_error(k, h / 2.0);
+            let expect = 2.0f64.powi(2 * k as i32 + 3);
+            assert!(
+                ratio > 0.75 * expect && ratio < 1.25 * expect,
+                "k = {}, ratio = {}",
+                k,
+                ratio
+            );
+        }
+
+        // the initial depth is chosen from the tolerance, and then changes by at most one each step to minimise the work
+        let loose = extrapolation_depths(1e-4);
+        let tight = extrapolation_depths(1e-10);
+        assert_eq!(loose[0], 2);
+        assert_eq!(tight[0], 6);
+        for depths in [&loose, &tight] {
+            for w in depths.windows(2) {
+                assert!(w[0].abs_diff(w[1]) <= 1, "depths = {:?}", depths);
+            }
+        }
+        assert!(tight.iter().max() > loose.iter().max());
+    }
+
+    #[test]
+    fn extrapolation_test_solution_bound_exponential_decay() {
+        // the steps are too large for the shared test's bound on the failure time, so only check that the bound is
+        // detected at the end of the step which exceeds it
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem
+            .set_params(nalgebra::DVector::from_element(1, -1.0))
+            .unwrap();
+        problem.max_abs_state = Some(Rc::new(nalgebra::DVector::from_element(2, 10.0)));
+        let mut s = Extrapolation::default();
+        match s.solve(&problem, 10.0) {
+            Err(PSError::SolutionBoundExceeded { t, indices }) => {
+                let t: f64 = t.parse().unwrap();
+                assert!(t > 10.0f64.ln() && t < 10.0, "t = {}", t);
+                assert_eq!(indices, vec![0, 1]);
+            }
+            _ => panic!("expected SolutionBoundExceeded error"),
+        }
+    }
+
+    #[test]
+    fn test_extrapolation_nalgebra_exponential_decay() {
+        let mut s = Extrapolation::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 3
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.24603830525241827
+        final_step_size: 27.295431113519193
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 123
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_extrapolation_nalgebra_gaussian_decay() {
+        let mut s = Extrapolation::default();
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[test]
+    fn test_extrapolation_nalgebra_gaussian_decay_high_accuracy() {
+        // tighter tolerances result in a deeper tableau (i.e. a higher order method)
+        let mut s = Extrapolation::default();
+        let (mut problem, soln) = gaussian_decay_problem::<M>(10);
+        problem.rtol = 1e-12;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(10, 1e-12));
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(s.order() >= 12, "order = {}", s.order());
+    }
+
+    #[test]
+    fn test_extrapolation_nalgebra_lotka_volterra() {
+        let mut s = Extrapolation::default();
+        let (problem, soln) = lotka_volterra_problem::<M>(1e-12, 1e-12);
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[cfg(feature = "dashu")]
+    #[test]
+    fn test_extrapolation_big_float_lotka_volterra() {
+        // the reference solution is only reproduced to 30 digits with a scalar type more precise than f64
+        let mut s = Extrapolation::default();
+        let (problem, soln) =
+            lotka_volterra_problem::<nalgebra::DMatrix<crate::scalar::BigFloat>>(1e-30, 1e-30);
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_tstop_extrapolation() {
+        let mut s = Extrapolation::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_extrapolation() {
+        let mut s = Extrapolation::default();
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,318 @@
+use std::rc::Rc;
+
+use num_traits::{One, Zero};
+
+use super::bdf::BdfStatistics;
+use crate::{
+    errors::PSError, matrix::MatrixRef, op::sdirk::SdirkCallable, scalar::Scalar,
+    vector::VectorRef, ConstantOp, LinearSolver, NonLinearOp, OdeEquations, OdeSolverProblem, Op,
+    SolverProblem, Vector,
+};
+
+/// The method used by a [FixedLowOrder] stepper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FixedLowOrderMethod {
+    /// The first order implicit (backward) Euler method, `M (y_{n+1} - y_n) = h F(y_{n+1}, t_{n+1})`
+    ImplicitEuler,
+    /// The second order BDF method with a constant step size, `M (y_{n+1} - 4/3 y_n + 1/3 y_{n-1}) = 2/3 h F(y_{n+1}, t_{n+1})`.
+    /// The first step is taken using the implicit Euler method.
+    Bdf2,
+}
+
+/// A fixed step, low order implicit stepper (implicit Euler or BDF2, see [FixedLowOrderMethod]) for real-time use,
+/// e.g. for a model running inside a control loop, where each step must finish in a bounded time.
+///
+/// All the storage, including the factorisation of the iteration matrix `M - c h J` (where `J` is the jacobian of the right-hand side),
+/// is allocated in [Self::set_problem], so that [Self::step] does not allocate (as long as the equations and the linear solver
+/// do not allocate when evaluating the right-hand side or solving with an existing factorisation). The jacobian is only evaluated
+/// at the initial state, and each step uses a simplified Newton iteration with this frozen jacobian. It can be updated at the
+/// current state using [Self::update_jacobian], which does allocate so should be called outside the time-critical part of the loop.
+///
+/// The Newton iteration is limited to [crate::OdeSolverOptions::max_nonlinear_solver_iterations] iterations (default 10).
+/// If it does not converge within this limit, or the solution becomes NaN, [Self::step] returns
+/// [PSError::FixedStepNonlinearSolverFailure] and the state is left unchanged, so the caller can decide what to do
+/// (e.g. update the jacobian and retry the step). There is no error control and the step size is never changed.
+/// Sensitivities and root finding are not supported, and for DAEs the initial condition must be consistent.
+pub struct FixedLowOrder<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+{
+    method: FixedLowOrderMethod,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    op: Option<Rc<SdirkCallable<Eqn>>>,
+    // factorisation of M - h J, used by the implicit Euler method (and the first step of BDF2)
+    linear_solver: LS,
+    // factorisation of M - 2/3 h J, used by BDF2
+    linear_solver_bdf2: LS,
+    max_iter: usize,
+    h: Eqn::T,
+    t0: Eqn::T,
+    nsteps: usize,
+    y: Eqn::V,
+    y_prev: Eqn::V,
+    y_new: Eqn::V,
+    phi: Eqn::V,
+    x: Eqn::V,
+    dx: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+}
+
+impl<Eqn, LS> FixedLowOrder<Eqn, LS>
+where
+    Eqn: OdeEquations,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    const DEFAULT_MAX_ITER: usize = 10;
+    const NEWTON_TOL: f64 = 1e-2;
+
+    pub fn new(method: FixedLowOrderMethod) -> Self
+    where
+        LS: Default,
+    {
+        Self {
+            method,
+            problem: None,
+            op: None,
+            linear_solver: LS::default(),
+            linear_solver_bdf2: LS::default(),
+            max_iter: Self::DEFAULT_MAX_ITER,
+            h: Eqn::T::zero(),
+            t0: Eqn::T::zero(),
+            nsteps: 0,
+            y: Eqn::V::zeros(0),
+            y_prev: Eqn::V::zeros(0),
+            y_new: Eqn::V::zeros(0),
+            phi: Eqn::V::zeros(0),
+            x: Eqn::V::zeros(0),
+            dx: Eqn::V::zeros(0),
+            statistics: BdfStatistics::default(),
+        }
+    }
+
+    pub fn method(&self) -> FixedLowOrderMethod {
+        self.method
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    /// Set the problem to solve, starting from the initial condition at `problem.t0` and taking steps of size `h > 0`.
+    /// This allocates all the storage used by the stepper and factorises the iteration matrix at the initial condition.
+    pub fn set_problem(
+        &mut self,
+        problem: &OdeSolverProblem<Eqn>,
+        h: Eqn::T,
+    ) -> Result<(), PSError> {
+        if h <= Eqn::T::zero() {
+            return Err(PSError::UnsupportedProblem {
+                e: "fixed step size must be positive".to_string(),
+            });
+        }
+        if problem.eqn_sens.is_some() || problem.eqn.root().is_some() {
+            return Err(PSError::UnsupportedProblem {
+                e: "fixed low order stepper does not support sensitivities or root finding"
+                    .to_string(),
+            });
+        }
+        let n = problem.eqn.rhs().nstates();
+        self.y = problem.eqn.init().call(problem.t0.clone());
+        self.y_prev = self.y.clone();
+        self.y_new = Eqn::V::zeros(n);
+        self.phi = Eqn::V::zeros(n);
+        self.x = Eqn::V::zeros(n);
+        self.dx = Eqn::V::zeros(n);
+        self.h = h.clone();
+        self.t0 = problem.t0.clone();
+        self.nsteps = 0;
+        self.max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::DEFAULT_MAX_ITER);
+
+        let op = Rc::new(SdirkCallable::new(problem, Eqn::T::one()));
+        let solver_problem = SolverProblem::new_from_ode_problem(op.clone(), problem);
+        self.linear_solver.set_problem(&solver_problem);
+        if self.method == FixedLowOrderMethod::Bdf2 {
+            self.linear_solver_bdf2.set_problem(&solver_problem);
+        }
+        self.op = Some(op);
+        self.problem = Some(problem.clone());
+
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = h.clone();
+        self.statistics.final_step_size = h;
+        self.update_jacobian()
+    }
+
+    /// Evaluate the jacobian at the current state and factorise the iteration matrix again.
+    /// Unlike [Self::step], this allocates.
+    pub fn update_jacobian(&mut self) -> Result<(), PSError> {
+        let op = self.op.as_ref().ok_or(PSError::StateNotSet)?;
+        let t = self.t();
+        op.set_jacobian_is_stale();
+        op.set_phi_direct(&self.y);
+        self.x.fill(Eqn::T::zero());
+        op.set_h(self.h.clone());
+        self.linear_solver.set_linearisation(&self.x, t.clone());
+        self.statistics.number_of_linear_solver_setups += 1;
+        if self.method == FixedLowOrderMethod::Bdf2 {
+            op.set_h(Eqn::T::cast(2.0 / 3.0) * self.h.clone());
+            self.linear_solver_bdf2.set_linearisation(&self.x, t);
+            self.statistics.number_of_linear_solver_setups += 1;
+        }
+        Ok(())
+    }
+
+    /// The current time, i.e. `t0 + n h` after `n` steps (so there is no accumulated roundoff)
+    pub fn t(&self) -> Eqn::T {
+        self.t0.clone() + self.h.clone() * Eqn::T::cast(self.nsteps as f64)
+    }
+
+    /// The solution at the current time
+    pub fn y(&self) -> &Eqn::V {
+        &self.y
+    }
+
+    /// The (fixed) step size
+    pub fn h(&self) -> Eqn::T {
+        self.h.clone()
+    }
+
+    /// Take a single step of size `h`, see [FixedLowOrder] for the possible failures.
+    pub fn step(&mut self) -> Result<(), PSError> {
+        let problem = self.problem.as_ref().ok_or(PSError::StateNotSet)?;
+        let op = self.op.as_ref().unwrap();
+        let one = Eqn::T::one();
+        let t1 = self.t0.clone() + self.h.clone() * Eqn::T::cast((self.nsteps + 1) as f64);
+
+        // solve M x = c h F(phi + x, t_{n+1}) for the update x = y_{n+1} - phi, where phi is known from the previous steps,
+        // starting from the extrapolation of the previous steps
+        let bdf2 = self.method == FixedLowOrderMethod::Bdf2 && self.nsteps > 0;
+        self.phi.copy_from(&self.y);
+        let linear_solver = if bdf2 {
+            // phi = 4/3 y_n - 1/3 y_{n-1}, x = 2 y_n - y_{n-1} - phi = 2/3 (y_n - y_{n-1})
+            let third = Eqn::T::cast(1.0 / 3.0);
+            self.phi
+                .axpy(-third.clone(), &self.y_prev, one.clone() + third.clone());
+            self.x.copy_from(&self.y);
+            self.x.axpy(
+                -Eqn::T::cast(2.0) * third.clone(),
+                &self.y_prev,
+                Eqn::T::cast(2.0) * third.clone(),
+            );
+            op.set_h(Eqn::T::cast(2.0) * third * self.h.clone());
+            &self.linear_solver_bdf2
+        } else {
+            self.x.fill(Eqn::T::zero());
+            op.set_h(self.h.clone());
+            &self.linear_solver
+        };
+        op.set_phi_direct(&self.phi);
+
+        let tol = Eqn::T::cast(Self::NEWTON_TOL * Self::NEWTON_TOL);
+        let mut converged = false;
+        for _ in 0..self.max_iter {
+            self.statistics.number_of_nonlinear_solver_iterations += 1;
+            op.call_inplace(&self.x, t1.clone(), &mut self.dx);
+            linear_solver.solve_in_place(&mut self.dx)?;
+            self.x.axpy(-one.clone(), &self.dx, one.clone());
+            self.y_new.copy_from(&self.phi);
+            self.y_new.axpy(one.clone(), &self.x, one.clone());
+            let norm = self
+                .dx
+                .squared_norm(&self.y_new, &problem.atol, problem.rtol.clone());
+            if norm.is_nan() {
+                break;
+            }
+            if norm < tol {
+                converged = true;
+                break;
+            }
+        }
+        if !converged {
+            self.statistics.number_of_nonlinear_solver_fails += 1;
+            return Err(PSError::FixedStepNonlinearSolverFailure { t: t1.to_string() });
+        }
+
+        // take the step
+        std::mem::swap(&mut self.y_prev, &mut self.y);
+        std::mem::swap(&mut self.y, &mut self.y_new);
+        self.nsteps += 1;
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver =
+            self.linear_solver.statistics() + self.linear_solver_bdf2.statistics();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::PSError, ode_solver::test_models::exponential_decay::exponential_decay_problem,
+        FixedLowOrder, FixedLowOrderMethod, NalgebraLU, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn test_fixed_low_order_convergence() {
+        // dy/dt = -a y, y(0) = 1, the error at t = 1 should reduce by 2^order when halving the step size
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let a = 0.1;
+        for (method, order) in [
+            (FixedLowOrderMethod::ImplicitEuler, 1),
+            (FixedLowOrderMethod::Bdf2, 2),
+        ] {
+            let error = |h: f64| {
+                let mut s = FixedLowOrder::<_, NalgebraLU<f64, _>>::new(method);
+                s.set_problem(&problem, h).unwrap();
+                while s.t() < 1.0 - 1e-12 {
+                    s.step().unwrap();
+                }
+                assert_eq!(s.t(), 1.0);
+                (s.y()[0] - f64::exp(-a)).abs()
+            };
+            let ratio = error(0.1) / error(0.05);
+            let expect = 2.0f64.powi(order);
+            assert!(
+                (ratio - expect).abs() < 0.1 * expect,
+                "{:?}: ratio = {}",
+                method,
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn test_fixed_low_order_failure() {
+        // the simplified Newton iteration needs at least two iterations to detect convergence
+        let (mut problem, soln) = exponential_decay_problem::<M>();
+        problem.options.max_nonlinear_solver_iterations = Some(1);
+        let mut s = FixedLowOrder::<_, NalgebraLU<f64, _>>::new(FixedLowOrderMethod::Bdf2);
+        s.set_problem(&problem, 0.1).unwrap();
+        let y0 = s.y().clone();
+        match s.step() {
+            Err(PSError::FixedStepNonlinearSolverFailure { t }) => assert_eq!(t, "0.1"),
+            _ => panic!("expected FixedStepNonlinearSolverFailure"),
+        }
+        // the state is unchanged by the failed step
+        assert_eq!(s.t(), 0.0);
+        s.y().assert_eq_st(&y0, 1e-15);
+        assert_eq!(s.get_statistics().number_of_nonlinear_solver_fails, 1);
+
+        // with the default number of iterations the steps succeed
+        problem.options.max_nonlinear_solver_iterations = None;
+        s.set_problem(&problem, 0.01).unwrap();
+        for point in soln.solution_points.iter().take(2) {
+            while s.t() < point.t - 1e-12 {
+                s.step().unwrap();
+            }
+            s.y().assert_eq_st(&point.state, 1e-4);
+        }
+        assert_eq!(s.get_statistics().number_of_nonlinear_solver_fails, 0);
+    }
+}
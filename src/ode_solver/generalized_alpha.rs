@@ -0,0 +1,555 @@
+use num_traits::{One, Zero};
+use std::rc::Rc;
+
+use crate::{
+    errors::PSError, op::generalized_alpha::GeneralizedAlphaCallable, scalar::compensated_add,
+    scale, ConstantOp, LinearOp, LinearSolver, Op, Scalar, SecondOrderOde, SolverProblem, Vector,
+};
+
+use super::bdf::BdfStatistics;
+
+/// The state of the [GeneralizedAlpha] solver, containing the displacement `y`, velocity `v` and acceleration `a`
+/// at time `t`, and the step size `h` used for the next step.
+#[derive(Clone, Debug)]
+pub struct GeneralizedAlphaState<V: Vector> {
+    pub y: V,
+    pub v: V,
+    pub a: V,
+    pub t: V::T,
+    pub h: V::T,
+}
+
+/// The generalized-alpha method of Chung & Hulbert (1993) for linear second-order systems given by the [SecondOrderOde] trait,
+/// i.e. `M y'' + C y' + K y = f(t)`. The system is integrated directly, so the size and sparsity of the matrices is preserved.
+///
+/// The method is second order accurate and unconditionally stable, and the amount of numerical dissipation of the high frequency modes
+/// is controlled by the spectral radius at infinite step size `rho_inf` (see [Self::new]). The classical Newmark method is also available
+/// using [Self::newmark].
+///
+/// The step size is fixed, and is given when setting the problem using [Self::set_problem]. It can be changed between steps using
+/// [Self::state_mut]. Each step requires a single linear solve with the matrix `M + c_c C + c_k K`, which is only factorised again
+/// when the step size changes.
+///
+/// Chung, J., & Hulbert, G. M. (1993). A time integration algorithm for structural dynamics with improved numerical dissipation:
+/// the generalized-α method. Journal of Applied Mechanics, 60(2), 371-375.
+pub struct GeneralizedAlpha<Eqn, LS>
+where
+    Eqn: SecondOrderOde,
+    LS: LinearSolver<GeneralizedAlphaCallable<Eqn>>,
+{
+    alpha_m: Eqn::T,
+    alpha_f: Eqn::T,
+    beta: Eqn::T,
+    gamma: Eqn::T,
+    linear_solver: LS,
+    callable: Option<Rc<GeneralizedAlphaCallable<Eqn>>>,
+    state: Option<GeneralizedAlphaState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_v: Eqn::V,
+    h_linearised: Option<Eqn::T>,
+    statistics: BdfStatistics<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn, LS> GeneralizedAlpha<Eqn, LS>
+where
+    Eqn: SecondOrderOde,
+    LS: LinearSolver<GeneralizedAlphaCallable<Eqn>>,
+{
+    /// Create a generalized-alpha solver with the given spectral radius at infinite step size `0 <= rho_inf <= 1`.
+    /// `rho_inf = 1` gives no numerical dissipation, while `rho_inf = 0` annihilates the highest frequency modes in a single step.
+    pub fn new(rho_inf: Eqn::T, linear_solver: LS) -> Self {
+        assert!(
+            rho_inf >= Eqn::T::zero() && rho_inf <= Eqn::T::one(),
+            "rho_inf must be in the range [0, 1]"
+        );
+        let one = Eqn::T::one();
+        let alpha_m =
+            (Eqn::T::cast(2.0) * rho_inf.clone() - one.clone()) / (rho_inf.clone() + one.clone());
+        let alpha_f = rho_inf.clone() / (rho_inf + one.clone());
+        let gamma = Eqn::T::cast(0.5) - alpha_m.clone() + alpha_f.clone();
+        let beta = Eqn::T::cast(0.25)
+            * (one.clone() - alpha_m.clone() + alpha_f.clone())
+            * (one - alpha_m.clone() + alpha_f.clone());
+        Self::from_parameters(alpha_m, alpha_f, beta, gamma, linear_solver)
+    }
+
+    /// Create a Newmark solver with the given parameters `beta` and `gamma`, e.g. `beta = 1/4` and `gamma = 1/2` gives the
+    /// (second order, energy conserving) average acceleration method.
+    pub fn newmark(beta: Eqn::T, gamma: Eqn::T, linear_solver: LS) -> Self {
+        Self::from_parameters(Eqn::T::zero(), Eqn::T::zero(), beta, gamma, linear_solver)
+    }
+
+    /// Create a solver with the given parameters of the generalized-alpha method, the balance equation is evaluated at
+    /// `M a_{n+1-alpha_m} + C v_{n+1-alpha_f} + K y_{n+1-alpha_f} = f(t_{n+1-alpha_f})`, and the Newmark parameters
+    /// `beta` and `gamma` are used to update the displacement and velocity.
+    pub fn from_parameters(
+        alpha_m: Eqn::T,
+        alpha_f: Eqn::T,
+        beta: Eqn::T,
+        gamma: Eqn::T,
+        linear_solver: LS,
+    ) -> Self {
+        assert!(alpha_m < Eqn::T::one(), "alpha_m must be less than 1");
+        assert!(alpha_f < Eqn::T::one(), "alpha_f must be less than 1");
+        assert!(beta > Eqn::T::zero(), "beta must be greater than 0");
+        let n = 1;
+        Self {
+            alpha_m,
+            alpha_f,
+            beta,
+            gamma,
+            linear_solver,
+            callable: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            old_v: <Eqn::V as Vector>::zeros(n),
+            h_linearised: None,
+            statistics: BdfStatistics::default(),
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    /// Set the problem to solve, starting from the initial conditions at `t0` and using a step size `h`.
+    /// The initial acceleration is calculated from the initial displacement and velocity, which requires the mass matrix to be non-singular.
+    pub fn set_problem(&mut self, eqn: Rc<Eqn>, t0: Eqn::T, h: Eqn::T) -> Result<(), PSError> {
+        let callable = Rc::new(GeneralizedAlphaCallable::new(&eqn, t0.clone()));
+        let n = callable.nstates();
+
+        // the linear solver does not use the tolerances
+        let atol = Rc::new(<Eqn::V as Vector>::from_element(n, Eqn::T::zero()));
+        let problem = SolverProblem::new(callable.clone(), atol, Eqn::T::zero());
+        self.linear_solver.set_problem(&problem);
+
+        let y = eqn.init().call(t0.clone());
+        let v = eqn.init_velocity().call(t0.clone());
+        let a = <Eqn::V as Vector>::zeros(n);
+        self.old_t = t0.clone();
+        self.old_y = y.clone();
+        self.old_v = v.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.state = Some(GeneralizedAlphaState {
+            y,
+            v,
+            a,
+            t: t0,
+            h: h.clone(),
+        });
+        self.callable = Some(callable);
+
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = h;
+
+        self.set_consistent_acceleration()?;
+        self.is_state_mutated = false;
+        Ok(())
+    }
+
+    /// Take the current state of the solver (if it exists), the problem is cleared
+    pub fn take_state(&mut self) -> Option<GeneralizedAlphaState<Eqn::V>> {
+        self.callable = None;
+        self.h_linearised = None;
+        self.linear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    /// Get the current state of the solver, if it exists
+    pub fn state(&self) -> Option<&GeneralizedAlphaState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    /// Get a mutable reference to the current state of the solver, if it exists.
+    /// The acceleration is recalculated from the displacement and velocity at the start of the next step.
+    pub fn state_mut(&mut self) -> Option<&mut GeneralizedAlphaState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+
+    // solve M a = f(t) - C v - K y for the acceleration at the current state
+    fn set_consistent_acceleration(&mut self) -> Result<(), PSError> {
+        let callable = self.callable.as_ref().unwrap();
+        let eqn = callable.eqn();
+        let state = self.state.as_mut().unwrap();
+        eqn.stiffness()
+            .gemv_inplace(&state.y, state.t.clone(), Eqn::T::zero(), &mut state.a);
+        if let Some(damping) = eqn.damping() {
+            damping.gemv_inplace(&state.v, state.t.clone(), Eqn::T::one(), &mut state.a);
+        }
+        let f = eqn.forcing().call(state.t.clone());
+        state.a.axpy(Eqn::T::one(), &f, -Eqn::T::one());
+
+        callable.set_coefficients(Eqn::T::zero(), Eqn::T::zero());
+        self.linear_solver
+            .set_linearisation(&state.y, state.t.clone());
+        self.h_linearised = None;
+        self.linear_solver.solve_in_place(&mut state.a)
+    }
+
+    /// Take a single step of size `h` (given by the current state), returning an error if the linear solve fails.
+    pub fn step(&mut self) -> Result<(), PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+
+        // state has been mutated by the user, so the acceleration and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            self.set_consistent_acceleration()?;
+            self.t_compensation = Eqn::T::zero();
+            self.is_state_mutated = false;
+        }
+
+        let one = Eqn::T::one();
+        let alpha_m = self.alpha_m.clone();
+        let alpha_f = self.alpha_f.clone();
+        let beta = self.beta.clone();
+        let gamma = self.gamma.clone();
+        let callable = self.callable.as_ref().unwrap();
+        let eqn = callable.eqn();
+        let state = self.state.as_mut().unwrap();
+        let h = state.h.clone();
+
+        // the effective matrix (1 - alpha_m) (M + c_c C + c_k K) only needs to be factorised if h has changed
+        if self.h_linearised != Some(h.clone()) {
+            let c_damping = (one.clone() - alpha_f.clone()) * gamma.clone() * h.clone()
+                / (one.clone() - alpha_m.clone());
+            let c_stiffness =
+                (one.clone() - alpha_f.clone()) * beta.clone() * h.clone() * h.clone()
+                    / (one.clone() - alpha_m.clone());
+            callable.set_coefficients(c_damping, c_stiffness);
+            self.linear_solver
+                .set_linearisation(&state.y, state.t.clone());
+            self.h_linearised = Some(h.clone());
+            self.statistics.number_of_linear_solver_setups += 1;
+        }
+
+        // predict the displacement and velocity using the old acceleration
+        // y* = y + h v + h^2 (1/2 - beta) a
+        // v* = v + h (1 - gamma) a
+        let mut y1 = state.y.clone();
+        y1.axpy(h.clone(), &state.v, one.clone());
+        y1.axpy(
+            h.clone() * h.clone() * (Eqn::T::cast(0.5) - beta.clone()),
+            &state.a,
+            one.clone(),
+        );
+        let mut v1 = state.v.clone();
+        v1.axpy(
+            h.clone() * (one.clone() - gamma.clone()),
+            &state.a,
+            one.clone(),
+        );
+
+        // the balance equation is evaluated at the intermediate time t_{n + 1 - alpha_f}, so the right-hand side is
+        // f(t_{n + 1 - alpha_f}) - alpha_m M a - C ((1 - alpha_f) v* + alpha_f v) - K ((1 - alpha_f) y* + alpha_f y)
+        let t_f = state.t.clone() + (one.clone() - alpha_f.clone()) * h.clone();
+        let mut y_f = y1.clone();
+        y_f.axpy(alpha_f.clone(), &state.y, one.clone() - alpha_f.clone());
+        let mut v_f = v1.clone();
+        v_f.axpy(alpha_f.clone(), &state.v, one.clone() - alpha_f);
+        let mut a_m = state.a.clone();
+        a_m *= scale(alpha_m.clone());
+        let mut r = <Eqn::V as Vector>::zeros(y1.len());
+        eqn.stiffness()
+            .gemv_inplace(&y_f, t_f.clone(), Eqn::T::zero(), &mut r);
+        if let Some(damping) = eqn.damping() {
+            damping.gemv_inplace(&v_f, t_f.clone(), one.clone(), &mut r);
+        }
+        eqn.mass()
+            .gemv_inplace(&a_m, t_f.clone(), one.clone(), &mut r);
+        let mut a1 = eqn.forcing().call(t_f);
+        a1.axpy(-one.clone(), &r, one.clone());
+
+        // solve (1 - alpha_m) (M + c_c C + c_k K) a_{n+1} = rhs
+        a1 *= scale(one.clone() / (one.clone() - alpha_m));
+        self.linear_solver.solve_in_place(&mut a1)?;
+
+        // correct the displacement and velocity using the new acceleration
+        y1.axpy(beta * h.clone() * h.clone(), &a1, one.clone());
+        v1.axpy(gamma * h.clone(), &a1, one);
+
+        // take the step
+        // accumulate time using compensated summation to avoid drift over many small steps
+        self.old_t = state.t.clone();
+        state.t = compensated_add(state.t.clone(), h.clone(), &mut self.t_compensation);
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut self.old_v, &mut state.v);
+        state.y = y1;
+        state.v = v1;
+        state.a = a1;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.linear_solver.statistics();
+        self.statistics.final_step_size = h;
+        Ok(())
+    }
+
+    /// Interpolate the displacement at time `t` within the last step, using Hermite interpolation of the displacement and velocity.
+    pub fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt.clone();
+        let one = Eqn::T::one();
+
+        // y = (1 - theta) y0 + theta y1 + theta (theta - 1) ((1 - 2 theta) (y1 - y0) + (theta - 1) h v0 + theta h v1)
+        let mut poly = state.y.clone();
+        poly.axpy(-one.clone(), &self.old_y, one.clone());
+        poly *= scale(one.clone() - Eqn::T::cast(2.0) * theta.clone());
+        poly.axpy(
+            (theta.clone() - one.clone()) * dt.clone(),
+            &self.old_v,
+            one.clone(),
+        );
+        poly.axpy(theta.clone() * dt, &state.v, one.clone());
+        let mut ret = self.old_y.clone();
+        ret.axpy(theta.clone(), &state.y, one.clone() - theta.clone());
+        ret.axpy(theta.clone() * (theta - one.clone()), &poly, one);
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+
+    use nalgebra::ComplexField;
+
+    use crate::{
+        op::{generalized_alpha::GeneralizedAlphaCallable, matrix::MatrixOp},
+        ConstantClosure, GeneralizedAlpha, LinearSolver, NalgebraLU, SecondOrderOde,
+        SecondOrderOdeEquations, UnitCallable, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // undamped oscillator y'' + omega^2 y = 0, y(0) = 1, y'(0) = 0, with solution y = cos(omega t)
+    fn oscillator(omega: f64) -> Rc<impl SecondOrderOde<M = M, V = V, T = f64>> {
+        let p = Rc::new(V::zeros(0));
+        let damping: Option<Rc<UnitCallable<M>>> = None;
+        Rc::new(SecondOrderOdeEquations::new(
+            Rc::new(MatrixOp::new(M::from_diagonal(&V::from_vec(vec![1.0])))),
+            damping,
+            Rc::new(MatrixOp::new(M::from_diagonal(&V::from_vec(vec![
+                omega * omega,
+            ])))),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, _t: f64| V::from_vec(vec![0.0]),
+                p.clone(),
+            )),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, _t: f64| V::from_vec(vec![1.0]),
+                p.clone(),
+            )),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, _t: f64| V::from_vec(vec![0.0]),
+                p,
+            )),
+        ))
+    }
+
+    // two degree of freedom system with rayleigh damping C = 0.1 K, forced by f = [sin(t), 0]
+    fn forced_damped_two_dof() -> Rc<impl SecondOrderOde<M = M, V = V, T = f64>> {
+        let p = Rc::new(V::zeros(0));
+        let stiffness = M::from_vec(2, 2, vec![6.0, -2.0, -2.0, 4.0]);
+        Rc::new(SecondOrderOdeEquations::new(
+            Rc::new(MatrixOp::new(M::from_diagonal(&V::from_vec(vec![
+                1.0, 2.0,
+            ])))),
+            Some(Rc::new(MatrixOp::new(stiffness.clone() * 0.1))),
+            Rc::new(MatrixOp::new(stiffness)),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, t: f64| V::from_vec(vec![t.sin(), 0.0]),
+                p.clone(),
+            )),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, _t: f64| V::from_vec(vec![1.0, 0.0]),
+                p.clone(),
+            )),
+            Rc::new(ConstantClosure::new(
+                |_p: &V, _t: f64| V::from_vec(vec![0.0, 1.0]),
+                p,
+            )),
+        ))
+    }
+
+    fn solve<Eqn, LS>(
+        mut s: GeneralizedAlpha<Eqn, LS>,
+        eqn: Rc<Eqn>,
+        h: f64,
+        final_time: f64,
+    ) -> GeneralizedAlpha<Eqn, LS>
+    where
+        Eqn: SecondOrderOde<M = M, V = V, T = f64>,
+        LS: LinearSolver<GeneralizedAlphaCallable<Eqn>>,
+    {
+        s.set_problem(eqn, 0.0, h).unwrap();
+        let nsteps = (final_time / h).round() as usize;
+        for _ in 0..nsteps {
+            s.step().unwrap();
+        }
+        assert!((s.state().unwrap().t - final_time).abs() < 1e-12);
+        s
+    }
+
+    #[test]
+    fn generalized_alpha_no_set_problem() {
+        let mut s = GeneralizedAlpha::new(0.5, NalgebraLU::default());
+        let eqn = oscillator(2.0);
+        assert!(s.state().is_none());
+        assert!(s.step().is_err());
+        assert!(s.interpolate(1.0).is_err());
+        s.set_problem(eqn, 0.0, 0.1).unwrap();
+        assert!(s.take_state().is_some());
+        assert!(s.state().is_none());
+    }
+
+    #[test]
+    fn generalized_alpha_initial_acceleration() {
+        let s = GeneralizedAlpha::new(0.5, NalgebraLU::default());
+        let s = solve(s, forced_damped_two_dof(), 0.1, 0.0);
+        // M a = f - C v - K y = [-5.8, 1.6] at t = 0
+        let a = &s.state().unwrap().a;
+        a.assert_eq_st(&V::from_vec(vec![-5.8, 0.8]), 1e-12);
+    }
+
+    #[test]
+    fn generalized_alpha_second_order_convergence() {
+        let omega = 2.0;
+        let final_time = 1.0;
+        let exact = (omega * final_time).cos();
+        for rho_inf in [0.0, 0.5, 1.0] {
+            let err = [0.01, 0.005].map(|h| {
+                let s = GeneralizedAlpha::new(rho_inf, NalgebraLU::default());
+                let s = solve(s, oscillator(omega), h, final_time);
+                (s.state().unwrap().y[0] - exact).abs()
+            });
+            assert!(err[0] < 1e-3, "rho_inf = {}, err = {}", rho_inf, err[0]);
+            let ratio = err[0] / err[1];
+            assert!(
+                ratio > 3.5 && ratio < 4.5,
+                "rho_inf = {}, ratio = {}",
+                rho_inf,
+                ratio
+            );
+        }
+    }
+
+    #[test]
+    fn newmark_average_acceleration_conserves_energy() {
+        let omega = 2.0;
+        let s = GeneralizedAlpha::newmark(0.25, 0.5, NalgebraLU::default());
+        let s = solve(s, oscillator(omega), 0.3, 30.0);
+        let state = s.state().unwrap();
+        let energy = state.v[0] * state.v[0] + omega * omega * state.y[0] * state.y[0];
+        assert!(
+            (energy - omega * omega).abs() < 1e-10,
+            "energy = {}",
+            energy
+        );
+    }
+
+    #[test]
+    fn generalized_alpha_high_frequency_dissipation() {
+        // the step size is much larger than the period of the oscillation
+        let omega = 1000.0;
+        let h = 0.1;
+        let amplitude = |rho_inf: f64| {
+            let s = GeneralizedAlpha::new(rho_inf, NalgebraLU::default());
+            let s = solve(s, oscillator(omega), h, 10.0 * h);
+            let state = s.state().unwrap();
+            (state.y[0] * state.y[0] + state.v[0] * state.v[0] / (omega * omega)).sqrt()
+        };
+        // the spectral radius is rho_inf, but the amplification matrix is not diagonalisable, so the decay is slower than rho_inf^n
+        assert!(amplitude(0.0) < 1e-6);
+        assert!(amplitude(0.5) < 0.2);
+        assert!((amplitude(1.0) - 1.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn generalized_alpha_forced_damped_two_dof() {
+        // reference solution using a small step size
+        let reference = {
+            let s = GeneralizedAlpha::new(0.8, NalgebraLU::default());
+            let s = solve(s, forced_damped_two_dof(), 1e-4, 2.0);
+            s.state().unwrap().y.clone()
+        };
+        let s = GeneralizedAlpha::new(0.8, NalgebraLU::default());
+        let s = solve(s, forced_damped_two_dof(), 1e-2, 2.0);
+        s.state().unwrap().y.assert_eq_st(&reference, 1e-3);
+        assert_eq!(s.get_statistics().number_of_steps, 200);
+        assert_eq!(s.get_statistics().number_of_linear_solver_setups, 1);
+    }
+
+    #[test]
+    fn generalized_alpha_state_mut() {
+        let omega = 2.0;
+        let mut s = GeneralizedAlpha::new(1.0, NalgebraLU::default());
+        s.set_problem(oscillator(omega), 0.0, 0.01).unwrap();
+        for _ in 0..10 {
+            s.step().unwrap();
+        }
+        // restart from y = 0, v = omega, which has solution y = sin(omega (t - t0))
+        let t0 = {
+            let state = s.state_mut().unwrap();
+            state.y[0] = 0.0;
+            state.v[0] = omega;
+            state.t
+        };
+        assert!(s.interpolate(t0).is_ok());
+        assert!(s.interpolate(t0 - 0.005).is_err());
+        for _ in 0..100 {
+            s.step().unwrap();
+        }
+        let state = s.state().unwrap();
+        assert!(state.a[0].abs() > 0.0);
+        let exact = (omega * (state.t - t0)).sin();
+        assert!((state.y[0] - exact).abs() < 1e-3);
+        assert_eq!(s.get_statistics().number_of_linear_solver_setups, 2);
+    }
+
+    #[test]
+    fn generalized_alpha_interpolate() {
+        let omega = 2.0;
+        let h = 0.01;
+        let s = GeneralizedAlpha::new(0.5, NalgebraLU::default());
+        let s = solve(s, oscillator(omega), h, 1.0);
+        let state = s.state().unwrap();
+        s.interpolate(state.t)
+            .unwrap()
+            .assert_eq_st(&state.y, 1e-12);
+        let t = state.t - 0.5 * h;
+        let y = s.interpolate(t).unwrap();
+        assert!((y[0] - (omega * t).cos()).abs() < 1e-3);
+        assert!(s.interpolate(state.t + h).is_err());
+        assert!(s.interpolate(state.t - 2.0 * h).is_err());
+    }
+}
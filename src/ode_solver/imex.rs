@@ -0,0 +1,827 @@
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+use std::rc::Rc;
+
+use crate::errors::PSError;
+use crate::matrix::MatrixRef;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::vector::VectorRef;
+use crate::ImexTableau;
+use crate::LinearSolver;
+use crate::NewtonNonlinearSolver;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::Tableau;
+use crate::{
+    nonlinear_solver::NonLinearSolver, op::sdirk::SdirkCallable, scalar::compensated_add, scale,
+    solver::SolverProblem, DenseMatrix, MatrixView, NonLinearOp, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, Op, Scalar, SolverCapabilities, Vector, VectorViewMut,
+};
+
+use super::bdf::BdfStatistics;
+
+/// An additive implicit-explicit (IMEX) Runge-Kutta method, for problems where the right-hand side can be split into a stiff part,
+/// which is integrated implicitly, and a non-stiff part, which is integrated explicitly (e.g. the diffusion and reaction terms of a
+/// method-of-lines discretisation of a PDE). The non-stiff part is given by [OdeEquations::rhs_explicit_inplace], if this is not
+/// implemented by the equations then the whole right-hand side is integrated implicitly.
+///
+/// The particular method is defined by the [ImexTableau] used to create the solver, e.g. [ImexTableau::ark436l2sa].
+/// Hermite interpolation is used for dense output.
+///
+/// Each implicit stage is solved using a Newton iteration with the jacobian of the full right-hand side, so the jacobian of
+/// the non-stiff part does not need to be provided separately.
+///
+/// Restrictions:
+/// - The diagonal and upper triangular part of the explicit `a` matrix must be zero.
+/// - The implicit method must be an ESDIRK method, i.e. the upper triangular part of the `a` matrix must be zero, the first row must be zero
+///   and the rest of the diagonal must be the same non-zero value.
+/// - The problem must not have a mass matrix or sensitivities.
+pub struct Imex<M, Eqn, LS>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    tableau: ImexTableau<M>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    nonlinear_solver: NewtonNonlinearSolver<SdirkCallable<Eqn>, LS>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    diff_explicit: M,
+    diff_implicit: M,
+    gamma: Eqn::T,
+    a_rows_explicit: Vec<Eqn::V>,
+    a_rows_implicit: Vec<Eqn::V>,
+    b_minus_last_row_explicit: Eqn::V,
+    b_minus_last_row_implicit: Eqn::V,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    f_explicit0: Eqn::V,
+    phi: Eqn::V,
+    stage_x: Eqn::V,
+    stage_y: Eqn::V,
+    stage_f: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<M, Eqn, LS> Imex<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    const NEWTON_MAXITER: usize = 10;
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    pub fn new(tableau: ImexTableau<M>, linear_solver: LS) -> Self {
+        let mut nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
+        // set max iterations for nonlinear solver
+        nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
+
+        let s = tableau.s();
+        let ae = tableau.explicit().a();
+        let ai = tableau.implicit().a();
+
+        // check that the diagonal and upper triangular part of the explicit a is zero
+        for i in 0..s {
+            for j in i..s {
+                assert_eq!(
+                    ae[(i, j)],
+                    Eqn::T::zero(),
+                    "Invalid explicit tableau, expected a(i, j) = 0 for i >= j"
+                );
+            }
+        }
+
+        // check that the implicit method is an esdirk method
+        for i in 0..s {
+            for j in (i + 1)..s {
+                assert_eq!(
+                    ai[(i, j)],
+                    Eqn::T::zero(),
+                    "Invalid implicit tableau, expected a(i, j) = 0 for i > j"
+                );
+            }
+        }
+        assert_eq!(
+            ai[(0, 0)],
+            Eqn::T::zero(),
+            "Invalid implicit tableau, expected a(0, 0) = 0"
+        );
+        assert_eq!(
+            tableau.implicit().c()[0],
+            Eqn::T::zero(),
+            "Invalid tableau, expected c(0) = 0"
+        );
+        let gamma = ai[(1, 1)].clone();
+        assert_ne!(
+            gamma,
+            Eqn::T::zero(),
+            "Invalid implicit tableau, expected a(1, 1) != 0"
+        );
+        for i in 1..s {
+            assert_eq!(
+                ai[(i, i)],
+                gamma,
+                "Invalid implicit tableau, expected a(i, i) = gamma = {} for i = 1..s-1",
+                gamma
+            );
+        }
+
+        let a_rows = |a: &M| {
+            let mut a_rows = Vec::with_capacity(s);
+            for i in 0..s {
+                let mut row = Vec::with_capacity(i);
+                for j in 0..i {
+                    row.push(a[(i, j)].clone());
+                }
+                a_rows.push(Eqn::V::from_vec(row));
+            }
+            a_rows
+        };
+        let a_rows_explicit = a_rows(ae);
+        let a_rows_implicit = a_rows(ai);
+
+        // y1 - Y_s = sum_j (b_j - a_sj) k_j, used to get the derivative at the end of the step from the last stage
+        let b_minus_last_row = |tableau: &Tableau<M>| {
+            let mut d = tableau.b().clone();
+            for j in 0..s {
+                d[j] -= tableau.a()[(s - 1, j)].clone();
+            }
+            d
+        };
+        let b_minus_last_row_explicit = b_minus_last_row(tableau.explicit());
+        let b_minus_last_row_implicit = b_minus_last_row(tableau.implicit());
+
+        let n = 1;
+        Self {
+            diff_explicit: M::zeros(n, s),
+            diff_implicit: M::zeros(n, s),
+            tableau,
+            nonlinear_solver,
+            problem: None,
+            state: None,
+            gamma,
+            a_rows_explicit,
+            a_rows_implicit,
+            b_minus_last_row_explicit,
+            b_minus_last_row_implicit,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            old_dy: <Eqn::V as Vector>::zeros(n),
+            f_explicit0: <Eqn::V as Vector>::zeros(n),
+            phi: <Eqn::V as Vector>::zeros(n),
+            stage_x: <Eqn::V as Vector>::zeros(n),
+            stage_y: <Eqn::V as Vector>::zeros(n),
+            stage_f: <Eqn::V as Vector>::zeros(n),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    fn predict_stage(i: usize, diff: &M, dy: &mut Eqn::V, tableau: &Tableau<M>) {
+        if i == 0 {
+            dy.fill(Eqn::T::zero());
+        } else if i == 1 {
+            dy.copy_from_view(&diff.column(i - 1));
+        } else {
+            let c = (tableau.c()[i].clone() - tableau.c()[i - 2].clone())
+                / (tableau.c()[i - 1].clone() - tableau.c()[i - 2].clone());
+            // dy = c1  + c * (c1 - c2)
+            dy.copy_from_view(&diff.column(i - 1));
+            dy.axpy_v(-c.clone(), &diff.column(i - 2), Eqn::T::one() + c);
+        }
+    }
+
+    fn interpolate_hermite(
+        theta: Eqn::T,
+        u0: &Eqn::V,
+        u1: &Eqn::V,
+        hf0: &Eqn::V,
+        hf1: &Eqn::V,
+    ) -> Eqn::V {
+        u0 * scale(Eqn::T::cast(1.0) - theta.clone())
+            + u1 * scale(theta.clone())
+            + ((u1 - u0) * scale(Eqn::T::cast(1.0) - Eqn::T::cast(2.0) * theta.clone())
+                + hf0 * scale(theta.clone() - Eqn::T::cast(1.0))
+                + hf1 * scale(theta.clone()))
+                * scale(theta.clone() * (theta - Eqn::T::cast(1.0)))
+    }
+}
+
+impl<M, Eqn, LS> OdeSolverMethod<Eqn> for Imex<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        self.tableau.order()
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: true,
+            max_order: self.tableau.order(),
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.root_finder = None;
+        self.nonlinear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // setup linear solver for first step, only the stiff part of the rhs is solved for implicitly
+        let mut callable = SdirkCallable::new(problem, self.gamma.clone());
+        callable.set_split_rhs(true);
+        callable.set_h(state.h.clone());
+        let callable = Rc::new(callable);
+        let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
+        let max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::NEWTON_MAXITER);
+        self.nonlinear_solver.set_max_iter(max_iter);
+        self.nonlinear_solver
+            .set_max_rate(problem.options.max_convergence_rate.clone());
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        let nstates = state.y.len();
+        self.diff_explicit = M::zeros(nstates, self.tableau.s());
+        self.diff_implicit = M::zeros(nstates, self.tableau.s());
+        self.f_explicit0 = <Eqn::V as Vector>::zeros(nstates);
+        self.phi = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_x = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_y = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_f = <Eqn::V as Vector>::zeros(nstates);
+        self.old_dy = state.dy.clone();
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let eqn = self.problem.as_ref().unwrap().eqn.clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let s = self.tableau.s();
+        let mut updated_jacobian = false;
+
+        // dont' reset jacobian for the first attempt at the step
+        let mut second_step_attempt = false;
+        let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // state has been mutated by the user, so the derivative and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            let state = self.state.as_mut().unwrap();
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+            self.t_compensation = Eqn::T::zero();
+        }
+
+        // the non-stiff part of the derivative at the start of the step
+        {
+            let state = self.state.as_ref().unwrap();
+            eqn.rhs_explicit_inplace(&state.y, state.t.clone(), &mut self.f_explicit0);
+        }
+
+        // loop until step is accepted
+        'step: loop {
+            let t0 = self.state.as_ref().unwrap().t.clone();
+            let h = self.state.as_ref().unwrap().h.clone();
+
+            // the first stage is explicit for both methods, so split the derivative at the start of the step into its two parts
+            {
+                let state = self.state.as_ref().unwrap();
+                self.stage_f.copy_from(&state.dy);
+                self.stage_f
+                    .axpy(-Eqn::T::one(), &self.f_explicit0, Eqn::T::one());
+                let mut hf = self.diff_implicit.column_mut(0);
+                hf.copy_from(&self.stage_f);
+                hf *= scale(h.clone());
+                let mut hf = self.diff_explicit.column_mut(0);
+                hf.copy_from(&self.f_explicit0);
+                hf *= scale(h.clone());
+            }
+
+            for i in 1..s {
+                let t = t0.clone() + self.tableau.implicit().c()[i].clone() * h.clone();
+
+                // phi = y + sum_j ae_ij h F_E(Y_j), the implicit stages are added by the callable
+                self.phi.copy_from(&self.state.as_ref().unwrap().y);
+                self.diff_explicit.columns(0, i).gemv_o(
+                    Eqn::T::one(),
+                    &self.a_rows_explicit[i],
+                    Eqn::T::one(),
+                    &mut self.phi,
+                );
+                self.nonlinear_solver.problem().f.set_phi(
+                    &self.diff_implicit.columns(0, i),
+                    &self.phi,
+                    &self.a_rows_implicit[i],
+                );
+
+                Self::predict_stage(
+                    i,
+                    &self.diff_implicit,
+                    &mut self.stage_x,
+                    self.tableau.implicit(),
+                );
+
+                // if we're attempting the step again, then we need to reset the jacobian
+                // as h has changed or jacobian needs to be recalculated
+                if i == 1 && second_step_attempt {
+                    // have to do it here cause phi needs to be set first
+                    self.nonlinear_solver
+                        .reset_jacobian(&self.stage_x, t.clone());
+                }
+
+                // always reset jacobian if step is attempted again
+                second_step_attempt = true;
+
+                let solve_result = self
+                    .nonlinear_solver
+                    .solve_in_place(&mut self.stage_x, t.clone());
+                self.statistics.number_of_nonlinear_solver_iterations +=
+                    self.nonlinear_solver.niter();
+
+                // handle solve failure
+                if solve_result.is_err() {
+                    if !updated_jacobian {
+                        // newton iteration did not converge, so update jacobian and try again
+                        self.nonlinear_solver.problem().f.set_jacobian_is_stale();
+                        updated_jacobian = true;
+                        self.statistics.number_of_nonlinear_solver_fails += 1;
+                    } else {
+                        // newton iteration did not converge and jacobian has been updated, so we reduce step size and try again
+                        let state = self.state.as_mut().unwrap();
+                        self.statistics.number_of_nonlinear_solver_fails += 1;
+                        state.h *= Eqn::T::cast(0.3);
+
+                        // if step size too small, then fail
+                        if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                            return Err(PSError::StepSizeTooSmall {
+                                t: state.t.to_string(),
+                            });
+                        }
+
+                        // update h for new step size
+                        self.nonlinear_solver.problem().f.set_h(state.h.clone());
+                    }
+                    // try again....
+                    continue 'step;
+                };
+
+                // update diff with the solved stiff part
+                self.diff_implicit.column_mut(i).copy_from(&self.stage_x);
+
+                // evaluate the non-stiff part at the stage value
+                self.stage_y
+                    .copy_from(&self.nonlinear_solver.problem().f.get_last_f_eval());
+                eqn.rhs_explicit_inplace(&self.stage_y, t, &mut self.stage_f);
+                let mut hf = self.diff_explicit.column_mut(i);
+                hf.copy_from(&self.stage_f);
+                hf *= scale(h.clone());
+            }
+
+            // successfully solved for all stages, now compute the solution and error
+            y1.copy_from(&self.state.as_ref().unwrap().y);
+            self.diff_explicit.gemv(
+                Eqn::T::one(),
+                self.tableau.explicit().b(),
+                Eqn::T::one(),
+                &mut y1,
+            );
+            self.diff_implicit.gemv(
+                Eqn::T::one(),
+                self.tableau.implicit().b(),
+                Eqn::T::one(),
+                &mut y1,
+            );
+            self.diff_explicit.gemv(
+                Eqn::T::one(),
+                self.tableau.explicit().d(),
+                Eqn::T::zero(),
+                &mut error,
+            );
+            self.diff_implicit.gemv(
+                Eqn::T::one(),
+                self.tableau.implicit().d(),
+                Eqn::T::one(),
+                &mut error,
+            );
+
+            // solve for  (M - h * c * J) * error = error_est as by Hosea, M. E., & Shampine, L. F. (1996). Analysis and implementation of TR-BDF2. Applied Numerical Mathematics, 20(1-2), 21-37.
+            self.nonlinear_solver
+                .solve_linearised_in_place(&mut error)?;
+
+            // compute error norm
+            let atol = self.problem().as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem().as_ref().unwrap().rtol.clone();
+            let error_norm = error.squared_norm(&y1, atol, rtol);
+
+            // adjust step size based on error, the error estimate is of the same order as the method
+            let maxiter = self.nonlinear_solver.max_iter() as f64;
+            let niter = self.nonlinear_solver.niter() as f64;
+            let safety_factor: f64 = self.problem().unwrap().options.safety_factor.to_f64();
+            let safety =
+                Eqn::T::cast(safety_factor * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
+            let order = self.tableau.order() as f64;
+            let mut factor = safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / order));
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // update c for new step size
+            self.nonlinear_solver.problem().f.set_h(state.h.clone());
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break 'step;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        //setup jacobian for next step (h was changed so jacobian needs to be recalculated)
+        self.nonlinear_solver
+            .reset_jacobian(&self.stage_x, t1.clone());
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        let dt = t1.clone() - state.t.clone();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+        std::mem::swap(&mut self.old_dy, &mut state.dy);
+
+        // the derivative at the end of the step is taken from the last stage, dy = x_s / h + f_e(Y_s) + f(y1) - f(Y_s),
+        // rather than evaluating f(y1) directly, which would amplify the error left by the newton iteration
+        // by the stiff part of the jacobian. y1 - Y_s is computed from the stages so that the correction is
+        // exactly zero if y1 = Y_s (e.g. a stiffly accurate implicit method with no explicit part)
+        let ts = self.old_t.clone()
+            + self.tableau.implicit().c()[self.tableau.s() - 1].clone() * dt.clone();
+        state.dy.copy_from(&self.stage_f);
+        state
+            .dy
+            .axpy(Eqn::T::one() / dt, &self.stage_x, Eqn::T::one());
+        self.diff_explicit.gemv(
+            Eqn::T::one(),
+            &self.b_minus_last_row_explicit,
+            Eqn::T::zero(),
+            &mut self.phi,
+        );
+        self.diff_implicit.gemv(
+            Eqn::T::one(),
+            &self.b_minus_last_row_implicit,
+            Eqn::T::one(),
+            &mut self.phi,
+        );
+        if self.phi.norm() > Eqn::T::zero() {
+            self.phi.axpy(Eqn::T::one(), &self.stage_y, Eqn::T::one());
+            eqn.rhs()
+                .call_inplace(&self.phi, state.t.clone(), &mut self.stage_f);
+            state.dy.axpy(Eqn::T::one(), &self.stage_f, Eqn::T::one());
+            eqn.rhs().call_inplace(&self.stage_y, ts, &mut self.stage_f);
+            state.dy.axpy(-Eqn::T::one(), &self.stage_f, Eqn::T::one());
+        }
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_linear_solver_setups =
+            self.nonlinear_solver.problem().f.number_of_jac_evals();
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.nonlinear_solver.linear_solver_statistics();
+        self.statistics.nonlinear_solver = self.nonlinear_solver.get_statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt.clone();
+        let hf0 = self.old_dy.clone() * scale(dt.clone());
+        let hf1 = state.dy.clone() * scale(dt);
+        let ret = Self::interpolate_hermite(theta, &self.old_y, &state.y, &hf0, &hf1);
+        Ok(ret)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<M, Eqn, LS> StepChecks<Eqn> for Imex<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        let state = self.state.as_mut().unwrap();
+        state.h *= factor;
+        self.nonlinear_solver.problem().f.set_h(state.h.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                robertson_ode::robertson_ode,
+                stiff_forced_decay::stiff_forced_decay_problem,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        Imex, ImexTableau, NalgebraLU, OdeEquations, OdeSolverMethod, OdeSolverState, Op,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn imex_no_set_problem() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        test_no_set_problem::<M, _>(Imex::new(tableau, NalgebraLU::default()));
+    }
+    #[test]
+    fn imex_state_mut() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        test_state_mut::<M, _>(Imex::new(tableau, NalgebraLU::default()));
+    }
+    #[test]
+    fn imex_test_interpolate() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        test_interpolate::<M, _>(Imex::new(tableau, NalgebraLU::default()));
+    }
+
+    // local error of a single step of size h for the stiff forced decay problem with a = 1, so that the explicit forcing
+    // cos(t) and the implicit decay -(y - sin(t)) are of similar size, the solution is y = sin(t) + exp(-t)
+    fn ark436l2sa_local_error(h: f64) -> f64 {
+        let (mut problem, _soln) = stiff_forced_decay_problem::<M>();
+        problem
+            .set_params(nalgebra::DVector::from_vec(vec![1.0]))
+            .unwrap();
+        let mut s = Imex::new(ImexTableau::<M>::ark436l2sa(), NalgebraLU::default());
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = h;
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        let state = s.state().unwrap();
+        assert_eq!(state.t, h);
+        let y = h.sin() + (-h).exp();
+        (state.y.clone() - nalgebra::DVector::from_element(2, y)).norm()
+    }
+
+    #[test]
+    fn imex_test_local_error_order() {
+        // ark436l2sa is 4th order for the combined explicit and implicit parts, so the local error is O(h^5)
+        let ratio = ark436l2sa_local_error(0.2) / ark436l2sa_local_error(0.1);
+        assert!(ratio > 24.0 && ratio < 40.0, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn test_ark436l2sa_tableau() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        for t in [tableau.explicit(), tableau.implicit()] {
+            // the rows of a sum to c
+            for i in 0..t.s() {
+                let sum = (0..t.s()).map(|j| t.a()[(i, j)]).sum::<f64>();
+                assert!(abs(sum - t.c()[i]) < 1e-12);
+            }
+            // both the method and the embedded method are consistent
+            let b_sum = (0..t.s()).map(|i| t.b()[i]).sum::<f64>();
+            let d_sum = (0..t.s()).map(|i| t.d()[i]).sum::<f64>();
+            assert!(abs(b_sum - 1.0) < 1e-12);
+            assert!(abs(d_sum) < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_ark436l2sa_nalgebra_exponential_decay() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        let mut s = Imex::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 7
+        number_of_steps: 6
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 60
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.045730505192732626
+        final_step_size: 3.1516023264175845
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 62
+        number_of_jac_muls: 2
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_ark436l2sa_nalgebra_stiff_forced_decay() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        let mut s = Imex::new(tableau, NalgebraLU::default());
+        let (problem, soln) = stiff_forced_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 389
+        number_of_steps: 383
+        number_of_error_test_failures: 5
+        number_of_nonlinear_solver_iterations: 3880
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.001001001001001001
+        final_step_size: 0.02686001130247205
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 4648
+        number_of_jac_muls: 2
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_ark436l2sa_nalgebra_robertson_ode() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        let mut s = Imex::new(tableau, NalgebraLU::default());
+        let (problem, soln) = robertson_ode::<M>();
+        // hermite interpolation is not accurate enough for the stiff components, so stop at each output time
+        test_ode_solver(&mut s, &problem, soln, None, true);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 90
+        number_of_steps: 61
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 1622
+        number_of_nonlinear_solver_fails: 28
+        initial_step_size: 0.010041193236044976
+        final_step_size: 83946133436.80542
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_ark436l2sa() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        let mut s = Imex::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_ark436l2sa() {
+        let tableau = ImexTableau::<M>::ark436l2sa();
+        let mut s = Imex::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,222 @@
+use std::rc::Rc;
+
+use num_traits::abs;
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver,
+    nonlinear_solver::kind::AnyNonLinearSolver, scalar::Scalar, InitOp, NonLinearOp,
+    NonLinearSolver, OdeEquations, OdeSolverProblem, OdeSolverState, Op, SolverProblem,
+    StateInitOp, Vector,
+};
+
+/// Which components of the initial state are calculated by [InitialConditionSolver], following the options of Sundials IDA.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InitialConditionMode {
+    /// The differential states are fixed, and the algebraic states and the time derivatives of the differential states are calculated
+    /// (as for `IDA_YA_YDP_INIT` in IDA). This is what [OdeSolverState::new] does, and is the default.
+    AlgebraicStates,
+    /// The time derivative of the state is fixed, and the full state vector is calculated (as for `IDA_Y_INIT` in IDA),
+    /// i.e. the state is projected onto the solution of `M dy = f(t, y)`.
+    FullState,
+}
+
+/// Diagnostic output of [InitialConditionSolver::solve].
+#[derive(Clone, Debug)]
+pub struct InitialConditionReport {
+    /// The components of the state vector that were changed by more than the tolerances used by the solver
+    pub modified: Vec<usize>,
+    /// Number of Newton iterations taken
+    pub niter: usize,
+}
+
+/// Calculates consistent initial conditions for an index-1 DAE, using a Newton iteration with configurable tolerances
+/// (or the nonlinear solver set by [crate::OdeBuilder::initialisation_solver]).
+///
+/// [OdeSolverState::new] (or [OdeSolverState::set_consistent]) makes the initial state consistent using the tolerances of the problem
+/// and the [InitialConditionMode::AlgebraicStates] mode. This solver gives more control over the calculation, and reports which
+/// components of the state were modified.
+#[derive(Clone, Debug)]
+pub struct InitialConditionSolver<T: Scalar> {
+    /// Which components of the state are calculated (default [InitialConditionMode::AlgebraicStates])
+    pub mode: InitialConditionMode,
+    /// Relative tolerance of the Newton iteration, if `None` the relative tolerance of the problem is used (default `None`).
+    pub rtol: Option<T>,
+    /// Absolute tolerance of the Newton iteration for all the states, if `None` the absolute tolerance of the problem is used (default `None`).
+    pub atol: Option<T>,
+    /// Maximum number of Newton iterations (default 100)
+    pub max_iter: usize,
+}
+
+impl<T: Scalar> Default for InitialConditionSolver<T> {
+    fn default() -> Self {
+        Self::new(InitialConditionMode::AlgebraicStates)
+    }
+}
+
+impl<T: Scalar> InitialConditionSolver<T> {
+    pub fn new(mode: InitialConditionMode) -> Self {
+        Self {
+            mode,
+            rtol: None,
+            atol: None,
+            max_iter: 100,
+        }
+    }
+
+    /// Make `state` consistent with the equations of `problem` at the time of the state, using the given [Self::mode].
+    /// In [InitialConditionMode::FullState] mode, the time derivative is taken from `state.dy`.
+    pub fn solve<Eqn>(
+        &self,
+        problem: &OdeSolverProblem<Eqn>,
+        state: &mut OdeSolverState<Eqn::V>,
+    ) -> Result<InitialConditionReport, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+    {
+        let n = problem.eqn.rhs().nstates();
+        let rtol = self.rtol.clone().unwrap_or(problem.rtol.clone());
+        let atol = match self.atol.clone() {
+            Some(atol) => Rc::new(Eqn::V::from_element(n, atol)),
+            None => problem.atol.clone(),
+        };
+        let y_old = state.y.clone();
+        let niter = match self.mode {
+            InitialConditionMode::AlgebraicStates => {
+                problem
+                    .eqn
+                    .rhs()
+                    .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+                if problem.eqn.mass().is_none() {
+                    0
+                } else {
+                    let f = Rc::new(InitOp::new(
+                        &problem.eqn,
+                        state.t.clone(),
+                        &state.y,
+                        &state.dy,
+                    ));
+                    let mut newton = AnyNonLinearSolver::new(
+                        problem.options.initialisation_solver,
+                        <Eqn::M as DefaultSolver>::default_solver(),
+                    );
+                    newton.set_max_iter(self.max_iter);
+                    newton.set_problem(&SolverProblem::new(f.clone(), atol.clone(), rtol.clone()));
+                    let mut y = f.y0.borrow().clone();
+                    newton.solve_in_place(&mut y, state.t.clone())?;
+                    f.scatter_soln(&y, &mut state.y, &mut state.dy);
+                    newton.niter()
+                }
+            }
+            InitialConditionMode::FullState => {
+                let f = Rc::new(StateInitOp::new(&problem.eqn, state.t.clone(), &state.dy));
+                let mut newton = AnyNonLinearSolver::new(
+                    problem.options.initialisation_solver,
+                    <Eqn::M as DefaultSolver>::default_solver(),
+                );
+                newton.set_max_iter(self.max_iter);
+                newton.set_problem(&SolverProblem::new(f, atol.clone(), rtol.clone()));
+                newton.solve_in_place(&mut state.y, state.t.clone())?;
+                newton.niter()
+            }
+        };
+        let modified = (0..n)
+            .filter(|&i| {
+                abs(state.y[i].clone() - y_old[i].clone())
+                    > atol[i].clone() + rtol.clone() * abs(y_old[i].clone())
+            })
+            .collect();
+        Ok(InitialConditionReport { modified, niter })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::test_models::exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem,
+        Bdf, InitialConditionMode, InitialConditionSolver, NonLinearSolverKind, OdeBuilder,
+        OdeSolverState, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_initial_condition_algebraic_states() {
+        // dy/dt = -0.1 y, 0 = z - y, with inconsistent initial state (1, 1, 0)
+        let (problem, _soln) = exponential_decay_with_algebraic_problem::<M>();
+        let mut state = OdeSolverState::new_without_initialise(&problem);
+        let report = InitialConditionSolver::default()
+            .solve(&problem, &mut state)
+            .unwrap();
+        assert_eq!(report.modified, vec![2]);
+        assert!(report.niter > 0);
+        state
+            .y
+            .assert_eq_st(&V::from_vec(vec![1.0, 1.0, 1.0]), 1e-6);
+        assert!((state.dy[0] + 0.1).abs() < 1e-6);
+        assert!((state.dy[1] + 0.1).abs() < 1e-6);
+
+        // a consistent state is not modified
+        let report = InitialConditionSolver::default()
+            .solve(&problem, &mut state)
+            .unwrap();
+        assert!(report.modified.is_empty());
+    }
+
+    #[test]
+    fn test_initial_condition_full_state() {
+        // fix dy/dt = -0.2 so that y = 2 and z = y
+        let (problem, _soln) = exponential_decay_with_algebraic_problem::<M>();
+        let mut state = OdeSolverState::new_without_initialise(&problem);
+        state.dy = V::from_vec(vec![-0.2, -0.2, 0.0]);
+        let mut solver = InitialConditionSolver::new(InitialConditionMode::FullState);
+        solver.rtol = Some(1e-10);
+        solver.atol = Some(1e-10);
+        let report = solver.solve(&problem, &mut state).unwrap();
+        assert_eq!(report.modified, vec![0, 1, 2]);
+        state
+            .y
+            .assert_eq_st(&V::from_vec(vec![2.0, 2.0, 2.0]), 1e-8);
+
+        // not enough iterations to converge
+        let mut state = OdeSolverState::new_without_initialise(&problem);
+        state.dy = V::from_vec(vec![-0.2, -0.2, 0.0]);
+        solver.max_iter = 0;
+        assert!(solver.solve(&problem, &mut state).is_err());
+    }
+
+    #[test]
+    fn test_initial_condition_dogleg() {
+        // dy/dt = -y, 0 = exp(z) + z / 2 - y, with y = 1 so that z = 0, from a poor initial guess z = -2
+        let build = |kind: NonLinearSolverKind| {
+            OdeBuilder::new()
+                .initialisation_solver(kind)
+                .build_ode_with_mass::<M, _, _, _, _>(
+                    |x, _p, _t, y| {
+                        y[0] = -x[0];
+                        y[1] = x[1].exp() + 0.5 * x[1] - x[0];
+                    },
+                    |x, _p, _t, v, y| {
+                        y[0] = -v[0];
+                        y[1] = (x[1].exp() + 0.5) * v[1] - v[0];
+                    },
+                    |v, _p, _t, beta, y| {
+                        y[0] = v[0] + beta * y[0];
+                        y[1] *= beta;
+                    },
+                    |_p, _t| V::from_vec(vec![1.0, -2.0]),
+                )
+                .unwrap()
+        };
+
+        // the newton step overshoots and the iteration diverges
+        let problem = build(NonLinearSolverKind::Newton);
+        assert!(OdeSolverState::new(&problem, &Bdf::default()).is_err());
+
+        let problem = build(NonLinearSolverKind::Dogleg);
+        let state = OdeSolverState::new(&problem, &Bdf::default()).unwrap();
+        state.y.assert_eq_st(&V::from_vec(vec![1.0, 0.0]), 1e-6);
+        assert!((state.dy[0] + 1.0).abs() < 1e-6);
+    }
+}
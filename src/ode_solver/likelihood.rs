@@ -0,0 +1,289 @@
+use super::output::solve_dense_sens;
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, ErrorModel,
+    ObservationData, OdeEquations, OdeSolverMethod, OdeSolverProblem, Op, ParameterTransforms,
+    Vector,
+};
+
+/// The log-likelihood of a set of observations given the solution of a problem, with a residual error model for each output.
+///
+/// The gradient of the log-likelihood with respect to the parameters is calculated using the forward sensitivities of the solution,
+/// so the problem must be created with sensitivities to use [Self::evaluate_with_gradient] or [Self::logp]. [Self::logp] takes the
+/// parameters and returns the gradient in the form expected by gradient-based samplers such as HMC or NUTS, so that the problem can be used
+/// as the forward model for Bayesian parameter inference. The weights of the observations are not used.
+///
+/// If [ParameterTransforms] are set using [Self::with_transforms], [Self::logp] takes the unconstrained values of the parameters, which are transformed
+/// to the natural scale before being passed to the equations.
+#[derive(Clone, Debug)]
+pub struct LogLikelihood<T: Scalar> {
+    data: ObservationData<T>,
+    models: Vec<ErrorModel<T>>,
+    transforms: Option<ParameterTransforms<T>>,
+}
+
+impl<T: Scalar> LogLikelihood<T> {
+    /// Create a new log-likelihood, `models` must contain an error model for each output (i.e. state) that is observed in `data`
+    pub fn new(data: ObservationData<T>, models: Vec<ErrorModel<T>>) -> Result<Self, PSError> {
+        if let Some(o) = data
+            .observations()
+            .iter()
+            .find(|o| o.output >= models.len())
+        {
+            return Err(PSError::DimensionMismatch {
+                name: "error models".to_string(),
+                expected: o.output + 1,
+                found: models.len(),
+            });
+        }
+        Ok(Self {
+            data,
+            models,
+            transforms: None,
+        })
+    }
+
+    /// Set the transformations applied to the parameters passed to [Self::logp]
+    pub fn with_transforms(mut self, transforms: ParameterTransforms<T>) -> Self {
+        self.transforms = Some(transforms);
+        self
+    }
+
+    pub fn transforms(&self) -> Option<&ParameterTransforms<T>> {
+        self.transforms.as_ref()
+    }
+
+    pub fn data(&self) -> &ObservationData<T> {
+        &self.data
+    }
+
+    pub fn models(&self) -> &[ErrorModel<T>] {
+        &self.models
+    }
+
+    /// Solve `problem` and return the log-likelihood of the observations
+    pub fn evaluate<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<T, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let y = solver.solve_dense(problem, self.data.times())?.y;
+        self.check_outputs(&y)?;
+        let mut ll = T::zero();
+        for (o, &i) in self
+            .data
+            .observations()
+            .iter()
+            .zip(self.data.time_index().iter())
+        {
+            ll += self.models[o.output].log_likelihood(y[i][o.output].clone(), o.value.clone());
+        }
+        Ok(ll)
+    }
+
+    /// Solve `problem` with sensitivities and return the log-likelihood of the observations, along with its gradient with respect to the parameters
+    pub fn evaluate_with_gradient<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<(T, Vec<T>), PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let (y, s) = solve_dense_sens(solver, problem, self.data.times())?;
+        self.check_outputs(&y)?;
+        let nparams = problem.eqn.rhs().nparams();
+        let mut ll = T::zero();
+        let mut grad = vec![T::zero(); nparams];
+        for (o, &i) in self
+            .data
+            .observations()
+            .iter()
+            .zip(self.data.time_index().iter())
+        {
+            let model = &self.models[o.output];
+            let f = y[i][o.output].clone();
+            ll += model.log_likelihood(f.clone(), o.value.clone());
+            let dll_df = model.log_likelihood_derivative(f, o.value.clone());
+            for (g, sj) in grad.iter_mut().zip(s[i].iter()) {
+                *g += dll_df.clone() * sj[o.output].clone();
+            }
+        }
+        Ok((ll, grad))
+    }
+
+    /// Set the parameters of `problem` to `position` and return the log-likelihood, writing its gradient with respect to the parameters into `grad`.
+    /// The solver is left without a problem on return, so the parameters of `problem` can be changed by the next call.
+    ///
+    /// If transforms are set, `position` holds the unconstrained values of the parameters, and the gradient is with respect to these values.
+    /// The log-likelihood then includes the log of the jacobian determinant of the transformation (see [ParameterTransforms::log_abs_det_jacobian]),
+    /// so that it is the correct log-density for sampling in the unconstrained space.
+    pub fn logp<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<Eqn>,
+        position: &[T],
+        grad: &mut [T],
+    ) -> Result<T, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        if grad.len() != position.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "gradient".to_string(),
+                expected: position.len(),
+                found: grad.len(),
+            });
+        }
+        let params = match self.transforms.as_ref() {
+            Some(transforms) => {
+                if transforms.nparams() != position.len() {
+                    return Err(PSError::DimensionMismatch {
+                        name: "parameter transforms".to_string(),
+                        expected: position.len(),
+                        found: transforms.nparams(),
+                    });
+                }
+                transforms.to_natural(position)
+            }
+            None => position.to_vec(),
+        };
+        // release the previous problem so the parameters can be updated in place
+        solver.take_state();
+        problem.set_params(Eqn::V::from_vec(params))?;
+        let (mut ll, g) = self.evaluate_with_gradient(solver, problem)?;
+        solver.take_state();
+        grad.clone_from_slice(&g);
+        if let Some(transforms) = self.transforms.as_ref() {
+            transforms.chain_gradient(position, grad);
+            ll += transforms.log_abs_det_jacobian(position);
+            for (g, dj) in grad
+                .iter_mut()
+                .zip(transforms.log_abs_det_jacobian_gradient(position))
+            {
+                *g += dj;
+            }
+        }
+        Ok(ll)
+    }
+
+    fn check_outputs<V: Vector<T = T>>(&self, y: &[V]) -> Result<(), PSError> {
+        let nstates = y.first().map(|y| y.len()).unwrap_or(0);
+        if let Some(o) = self
+            .data
+            .observations()
+            .iter()
+            .find(|o| o.output >= nstates)
+        {
+            return Err(PSError::DimensionMismatch {
+                name: "observation output index".to_string(),
+                expected: nstates,
+                found: o.output,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::{
+            likelihood::LogLikelihood,
+            test_models::exponential_decay::{
+                exponential_decay_problem, exponential_decay_problem_sens,
+            },
+        },
+        Bdf, ErrorModel, Observation, ObservationData, ParameterTransform, ParameterTransforms,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    fn log_likelihood() -> LogLikelihood<f64> {
+        let data = ObservationData::new(vec![
+            Observation::new(1.0, 0, 0.95, 1.0),
+            Observation::new(2.0, 0, 0.8, 1.0),
+            Observation::new(4.0, 1, 0.7, 1.0),
+        ])
+        .unwrap();
+        let models = vec![
+            ErrorModel::Additive { sigma: 0.1 },
+            ErrorModel::Proportional { sigma: 0.2 },
+        ];
+        LogLikelihood::new(data, models).unwrap()
+    }
+
+    #[test]
+    fn test_log_likelihood_gradient() {
+        let (mut problem, _soln) = exponential_decay_problem_sens::<M>();
+        let mut solver = Bdf::default();
+        let ll = log_likelihood();
+        let mut grad = [0.0];
+        let value = ll
+            .logp(&mut solver, &mut problem, &[0.1], &mut grad)
+            .unwrap();
+
+        // y = exp(-k t) for both states
+        let analytic = |k: f64| {
+            let models = ll.models();
+            models[0].log_likelihood(f64::exp(-k), 0.95)
+                + models[0].log_likelihood(f64::exp(-2.0 * k), 0.8)
+                + models[1].log_likelihood(f64::exp(-4.0 * k), 0.7)
+        };
+        assert!((value - analytic(0.1)).abs() < 1e-3);
+        let eps = 1e-6;
+        let fd = (analytic(0.1 + eps) - analytic(0.1 - eps)) / (2.0 * eps);
+        assert!(
+            (grad[0] - fd).abs() < 1e-2 * fd.abs(),
+            "grad {} fd {}",
+            grad[0],
+            fd
+        );
+
+        // the value without sensitivities is the same
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let value2 = ll.evaluate(&mut solver, &problem).unwrap();
+        assert!((value - value2).abs() < 1e-3);
+        // but the gradient requires sensitivities
+        assert!(ll.evaluate_with_gradient(&mut solver, &problem).is_err());
+    }
+
+    #[test]
+    fn test_log_likelihood_transforms() {
+        let (mut problem, _soln) = exponential_decay_problem_sens::<M>();
+        let mut solver = Bdf::default();
+        let transforms = ParameterTransforms::new(vec![ParameterTransform::Log]).unwrap();
+        let ll = log_likelihood().with_transforms(transforms);
+        let x = f64::ln(0.1);
+        let mut grad = [0.0];
+        let value = ll.logp(&mut solver, &mut problem, &[x], &mut grad).unwrap();
+        let mut grad_natural = [0.0];
+        let value_natural = log_likelihood()
+            .logp(&mut solver, &mut problem, &[0.1], &mut grad_natural)
+            .unwrap();
+        // log p(x) = log p(k) + log(dk/dx) = log p(k) + x, and d/dx = k d/dk + 1 (up to the solver tolerance)
+        assert!((value - (value_natural + x)).abs() < 1e-4);
+        assert!((grad[0] - (0.1 * grad_natural[0] + 1.0)).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_log_likelihood_errors() {
+        let data = ObservationData::new(vec![Observation::new(1.0, 1, 1.0, 1.0)]).unwrap();
+        assert!(LogLikelihood::new(data, vec![ErrorModel::Additive { sigma: 1.0 }]).is_err());
+        let (mut problem, _soln) = exponential_decay_problem_sens::<M>();
+        let mut solver = Bdf::default();
+        let mut grad = [0.0, 0.0];
+        assert!(log_likelihood()
+            .logp(&mut solver, &mut problem, &[0.1], &mut grad)
+            .is_err());
+    }
+}
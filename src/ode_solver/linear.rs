@@ -0,0 +1,500 @@
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+use std::rc::Rc;
+
+use crate::errors::PSError;
+use crate::matrix::MatrixRef;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::LinearSolver;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::{
+    op::sdirk::SdirkCallable, scale, solver::SolverProblem, NonLinearOp, OdeEquations,
+    OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op, Scalar, SolverCapabilities, Vector,
+};
+
+use super::bdf::BdfStatistics;
+
+/// A Crank-Nicolson (trapezoidal rule) solver for linear constant-coefficient problems `M y' = A y`, where the rhs is linear with a
+/// constant jacobian `A` (see [crate::NonLinearOp::is_linear] and [crate::OdeBuilder::build_ode_linear]).
+///
+/// Each step of size `h` is taken as two trapezoidal rule sub-steps of size `h/2`, each of which is a single linear solve with the matrix
+/// `M - h/4 A`, so there are no nonlinear iterations. The matrix `A` is assembled once when the problem is set using [SdirkCallable], and
+/// `M - h/4 A` is only re-factorised when the step size changes. To reuse the factorisation for as many steps as possible, the step size is
+/// only increased if it can grow by at least a factor of [Self::MIN_INCREASE_FACTOR].
+///
+/// The local error is estimated from the second difference of the rhs over the two sub-steps, `h/12 (f_0 - 2 f_1/2 + f_1) ~ h^3/48 M y'''`,
+/// filtered using the same factorisation, so that the estimate remains bounded for the stiff components of the solution.
+/// Dense output is given by the quadratic through the solution at the start, middle and end of the step.
+///
+/// Restrictions:
+/// - The rhs must be linear with a constant jacobian, checked using [crate::NonLinearOp::is_linear].
+/// - Problems with a singular mass matrix are not supported.
+/// - Sensitivities are not supported.
+pub struct LinearOdeSolver<Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    problem: Option<OdeSolverProblem<Eqn>>,
+    linear_solver: LS,
+    op: Option<Rc<SdirkCallable<Eqn>>>,
+    factorised_h: Option<Eqn::T>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    mid_y: Eqn::V,
+    f0: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn, LS> LinearOdeSolver<Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    /// An accepted step only changes the step size (and therefore the factorisation) if it can increase by at least this factor
+    pub const MIN_INCREASE_FACTOR: f64 = 1.5;
+
+    pub fn new(linear_solver: LS) -> Self {
+        let n = 1;
+        Self {
+            problem: None,
+            linear_solver,
+            op: None,
+            factorised_h: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            mid_y: <Eqn::V as Vector>::zeros(n),
+            f0: <Eqn::V as Vector>::zeros(n),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    // factorise M - h/4 A, unless the current factorisation is already for this step size
+    fn factorise(&mut self, h: Eqn::T, t: Eqn::T) {
+        if self.factorised_h == Some(h.clone()) {
+            return;
+        }
+        let op = self.op.as_ref().unwrap();
+        op.set_h(h.clone());
+        let x0 = <Eqn::V as Vector>::zeros(op.nstates());
+        self.linear_solver.set_linearisation(&x0, t);
+        self.factorised_h = Some(h);
+        self.statistics.number_of_linear_solver_setups += 1;
+    }
+}
+
+impl<Eqn, LS> OdeSolverMethod<Eqn> for LinearOdeSolver<Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        2
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: true,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: true,
+            max_order: 2,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: false,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.op = None;
+        self.factorised_h = None;
+        self.root_finder = None;
+        self.linear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // each sub-step of size h/2 solves (M - h/4 A) dy = h/2 A y0, the jacobian A is only evaluated on the first factorisation
+        let callable = Rc::new(SdirkCallable::new(problem, Eqn::T::cast(0.25)));
+        callable.set_h(state.h.clone());
+        let linear_problem = SolverProblem::new_from_ode_problem(callable.clone(), problem);
+        self.linear_solver.set_problem(&linear_problem);
+        self.op = Some(callable);
+        self.factorised_h = None;
+
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.mid_y = state.y.clone();
+        self.f0 = problem.eqn.rhs().call(&state.y, state.t.clone());
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let eqn = self.problem.as_ref().unwrap().eqn.clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let mut y_mid = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+        let mut f_mid = <Eqn::V as Vector>::zeros(n);
+        let mut f1 = <Eqn::V as Vector>::zeros(n);
+        let mut dy = <Eqn::V as Vector>::zeros(n);
+        let mut error = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the accumulated roundoff in t is no longer valid,
+        // and the rhs at the start of the step must be recalculated
+        if self.is_state_mutated {
+            self.t_compensation = Eqn::T::zero();
+            let state = self.state.as_ref().unwrap();
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut self.f0);
+        }
+        let f0 = self.f0.clone();
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // loop until step is accepted
+        loop {
+            let t0 = self.state.as_ref().unwrap().t.clone();
+            let h = self.state.as_ref().unwrap().h.clone();
+            let half_h = h.clone() / Eqn::T::cast(2.0);
+            self.factorise(h.clone(), t0.clone());
+            let state = self.state.as_ref().unwrap();
+
+            // first sub-step, (M - h/4 A) dy = h/2 A y0
+            dy.axpy(half_h.clone(), &f0, Eqn::T::zero());
+            self.linear_solver.solve_in_place(&mut dy)?;
+            y_mid.copy_from(&state.y);
+            y_mid += &dy;
+            eqn.rhs()
+                .call_inplace(&y_mid, t0.clone() + half_h.clone(), &mut f_mid);
+
+            // second sub-step, (M - h/4 A) dy = h/2 A y_mid
+            dy.axpy(half_h, &f_mid, Eqn::T::zero());
+            self.linear_solver.solve_in_place(&mut dy)?;
+            y1.copy_from(&y_mid);
+            y1 += &dy;
+            eqn.rhs().call_inplace(&y1, t0 + h.clone(), &mut f1);
+
+            // error estimate, (M - h/4 A) error = -h/12 (f0 - 2 f_mid + f1)
+            error.copy_from(&f0);
+            error.axpy(Eqn::T::cast(-2.0), &f_mid, Eqn::T::one());
+            error.axpy(Eqn::T::one(), &f1, Eqn::T::one());
+            error *= scale(-h / Eqn::T::cast(12.0));
+            self.linear_solver.solve_in_place(&mut error)?;
+            let atol = self.problem.as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem.as_ref().unwrap().rtol.clone();
+            let error_norm = error.squared_norm(&y1, atol, rtol);
+
+            // adjust step size based on error, the local error is O(h^3)
+            let safety = self.problem.as_ref().unwrap().options.safety_factor.clone();
+            let mut factor = safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / 3.0));
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+            let accepted = error_norm <= Eqn::T::cast(1.0);
+
+            // keep the current step size (and factorisation) unless it must decrease or can grow significantly
+            if accepted && factor < Eqn::T::cast(Self::MIN_INCREASE_FACTOR) {
+                factor = Eqn::T::one();
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if accepted {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+        std::mem::swap(&mut self.mid_y, &mut y_mid);
+
+        // the derivative at the end of the step, if there is a mass matrix this uses M y'_1 = A y_1, which (applying the
+        // trapezoidal rule to each sub-step) gives y'_1 = 4/h (y_1 - 2 y_1/2 + y_0) + y'_0
+        if eqn.mass().is_none() {
+            state.dy.copy_from(&f1);
+        } else {
+            let h = state.t.clone() - self.old_t.clone();
+            state
+                .dy
+                .axpy(Eqn::T::cast(4.0) / h.clone(), &state.y, Eqn::T::one());
+            state
+                .dy
+                .axpy(Eqn::T::cast(-8.0) / h.clone(), &self.mid_y, Eqn::T::one());
+            state
+                .dy
+                .axpy(Eqn::T::cast(4.0) / h, &self.old_y, Eqn::T::one());
+        }
+        std::mem::swap(&mut f1, &mut self.f0);
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.linear_solver.statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt;
+
+        // quadratic lagrange polynomial through theta = 0, 1/2 and 1
+        let half = Eqn::T::cast(0.5);
+        let l0 =
+            Eqn::T::cast(2.0) * (theta.clone() - half.clone()) * (theta.clone() - Eqn::T::one());
+        let l_mid = Eqn::T::cast(-4.0) * theta.clone() * (theta.clone() - Eqn::T::one());
+        let l1 = Eqn::T::cast(2.0) * theta.clone() * (theta - half);
+        let mut ret = self.old_y.clone() * scale(l0);
+        ret.axpy(l_mid, &self.mid_y, Eqn::T::one());
+        ret.axpy(l1, &state.y, Eqn::T::one());
+        Ok(ret)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<Eqn, LS> StepChecks<Eqn> for LinearOdeSolver<Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{exponential_decay_problem, exponential_decay_problem_linear},
+                heat1d::heat1d_linear_problem,
+            },
+            tests::{test_capabilities, test_interpolate, test_no_set_problem, test_ode_solver},
+        },
+        LinearOdeSolver, NalgebraLU, OdeEquations, OdeSolverState, Op,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn linear_no_set_problem() {
+        test_no_set_problem::<M, _>(LinearOdeSolver::new(NalgebraLU::default()));
+    }
+
+    #[test]
+    fn linear_test_interpolate() {
+        test_interpolate::<M, _>(LinearOdeSolver::new(NalgebraLU::default()));
+    }
+
+    #[test]
+    fn linear_test_capabilities_exponential_decay() {
+        let (problem, _soln) = exponential_decay_problem_linear::<M>();
+        test_capabilities(LinearOdeSolver::new(NalgebraLU::default()), problem);
+    }
+
+    #[test]
+    fn linear_test_unsupported_problems() {
+        // the rhs is not declared as linear
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let s = LinearOdeSolver::new(NalgebraLU::default());
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+    }
+
+    #[test]
+    fn test_linear_nalgebra_exponential_decay() {
+        let mut s = LinearOdeSolver::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem_linear::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 3
+        number_of_steps: 24
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.005848035476425734
+        final_step_size: 0.4123384640882837
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 53
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_linear_nalgebra_heat1d() {
+        let mut s = LinearOdeSolver::new(NalgebraLU::default());
+        let (problem, soln) = heat1d_linear_problem::<M>(20);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 3
+        number_of_steps: 41
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0006371355765860152
+        final_step_size: 0.006800496400272658
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 105
+        number_of_jac_muls: 0
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_linear() {
+        let mut s = LinearOdeSolver::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem_linear::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+}
@@ -1,21 +1,49 @@
 use nalgebra::ComplexField;
-use num_traits::{One, Pow};
+use num_traits::{abs, One, Pow, Zero};
 use std::rc::Rc;
 
 use crate::{
-    matrix::default_solver::DefaultSolver, scalar::Scalar, scale, ConstantOp, InitOp,
-    NewtonNonlinearSolver, NonLinearOp, NonLinearSolver, OdeEquations, OdeSolverProblem, Op,
-    SensEquations, SolverProblem, Vector,
+    matrix::default_solver::DefaultSolver, nonlinear_solver::kind::AnyNonLinearSolver,
+    scalar::Scalar, scale, ConstantOp, IndexType, InitOp, LinearOp, Matrix, NewtonNonlinearSolver,
+    NonLinearOp, NonLinearSolver, OdeEquations, OdeSolverProblem, OdeSolverTrajectory, Op,
+    RootFinder, SensEquations, SolverProblem, StepControl, Vector, VectorIndex,
 };
 
 use crate::errors::PSError;
 
 pub enum OdeSolverStopReason<T: Scalar> {
     InternalTimestep,
-    RootFound(T),
+    RootFound { index: IndexType, t: T },
     TstopReached,
 }
 
+/// Describes the features of an ODE solver method, so that a problem can be checked for compatibility with the method before solving.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolverCapabilities {
+    /// The method can solve problems with a (non-identity) mass matrix
+    pub mass_matrix: bool,
+    /// The method can solve problems with a singular mass matrix (i.e. DAEs with algebraic states)
+    pub singular_mass_matrix: bool,
+    /// The method can find roots of the root function during the solve
+    pub roots: bool,
+    /// The method can solve the forward sensitivity equations alongside the problem
+    pub sensitivities: bool,
+    /// The method is implicit and suitable for stiff problems
+    pub stiff: bool,
+    /// The maximum order of accuracy of the method
+    pub max_order: usize,
+    /// The method can interpolate the solution within the last step (see [OdeSolverMethod::interpolate])
+    pub dense_output: bool,
+    /// The method can take steps of a constant size without error control (see [crate::StepControl::Fixed])
+    pub fixed_step: bool,
+    /// The method can solve problems with a nonlinear right-hand side, otherwise the right-hand side must be linear (see [crate::NonLinearOp::is_linear])
+    pub nonlinear_rhs: bool,
+    /// The method can integrate backward in time, i.e. with a negative step size (see [OdeSolverProblem::backward])
+    pub backward: bool,
+    /// The method projects the solution onto the invariants of the equations after each step (see [OdeEquations::has_invariants])
+    pub invariants: bool,
+}
+
 /// Trait for ODE solver methods. This is the main user interface for the ODE solvers.
 /// The solver is responsible for stepping the solution (given in the `OdeSolverState`), and interpolating the solution at a given time.
 /// However, the solver does not own the state, so the user is responsible for creating and managing the state. If the user
@@ -51,15 +79,17 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     /// Step the solution forward by one step, altering the internal state of the solver.
     /// The return value is a `Result` containing the reason for stopping the solver, possible reasons are:
     /// - `InternalTimestep`: The solver has taken a step forward in time, the internal state of the solver is at time self.state().t
-    /// - `RootFound(t_root)`: The solver has found a root at time `t_root`. Note that the internal state of the solver is at the internal time step `self.state().t`, *not* at time `t_root`.
+    /// - `RootFound { index, t }`: The solver has found a root of the root function with index `index` (i.e. the `index`-th output of [OdeEquations::root]) at time `t`. Note that the internal state of the solver is at the internal time step `self.state().t`, *not* at time `t`.
     /// - `TstopReached`: The solver has reached the stop time set by [Self::set_stop_time], the internal state of the solver is at time `tstop`, which is the same as `self.state().t`
     fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError>;
 
     /// Set a stop time for the solver. The solver will stop when the internal time reaches this time.
-    /// Once it stops, the stop time is unset. If `tstop` is at or before the current internal time, an error is returned.
+    /// Once it stops, the stop time is unset. If `tstop` is at or before the current internal time (or at or after it when
+    /// integrating backward in time), an error is returned.
     fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<(), PSError>;
 
     /// Interpolate the solution at a given time. This time should be between the current time and the last solver time step
+    /// (note that when integrating backward in time the last time step is after the current time)
     fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError>;
 
     /// Interpolate the sensitivity vectors at a given time. This time should be between the current time and the last solver time step
@@ -76,34 +106,417 @@ pub trait OdeSolverMethod<Eqn: OdeEquations> {
     /// Get the current order of accuracy of the solver (e.g. explict euler method is first-order)
     fn order(&self) -> usize;
 
+    /// Get the capabilities of the solver method, see [SolverCapabilities]
+    fn capabilities(&self) -> SolverCapabilities;
+
+    /// Check that the solver method supports all the features required to solve `problem`, returning an error if not.
+    /// This is called when creating the initial state using [OdeSolverState::new].
+    fn check_problem(&self, problem: &OdeSolverProblem<Eqn>) -> Result<(), PSError> {
+        let capabilities = self.capabilities();
+        if let Some(mass) = problem.eqn.mass() {
+            if !capabilities.mass_matrix {
+                return Err(PSError::UnsupportedProblem {
+                    e: "solver does not support problems with a mass matrix".to_string(),
+                });
+            }
+            if !capabilities.singular_mass_matrix {
+                let diagonal = mass.matrix(problem.t0.clone()).diagonal();
+                if diagonal.filter_indices(|x| x == Eqn::T::zero()).len() > 0 {
+                    return Err(PSError::UnsupportedProblem {
+                        e: "solver does not support problems with algebraic states".to_string(),
+                    });
+                }
+            }
+        }
+        if let Some(kind) = problem.options.linear_solver {
+            if !Eqn::M::supports_linear_solver(kind) {
+                return Err(PSError::UnsupportedLinearSolver {
+                    kind: format!("{:?}", kind),
+                });
+            }
+        }
+        if problem.eqn.root().is_some() && !capabilities.roots {
+            return Err(PSError::UnsupportedProblem {
+                e: "solver does not support root finding".to_string(),
+            });
+        }
+        if problem.eqn_sens.is_some() && !capabilities.sensitivities {
+            return Err(PSError::UnsupportedProblem {
+                e: "solver does not support sensitivities".to_string(),
+            });
+        }
+        if !capabilities.nonlinear_rhs && !problem.eqn.rhs().is_linear() {
+            return Err(PSError::UnsupportedProblem {
+                e: "solver only supports problems with a linear right-hand side".to_string(),
+            });
+        }
+        if problem.eqn.has_invariants() && !capabilities.invariants {
+            return Err(PSError::UnsupportedProblem {
+                e: "solver does not support projection onto invariants".to_string(),
+            });
+        }
+        if problem.backward && !capabilities.backward {
+            return Err(PSError::UnsupportedProblem {
+                e: "solver does not support integrating backward in time".to_string(),
+            });
+        }
+        if let StepControl::Fixed(h) = problem.options.step_control.clone() {
+            if !capabilities.fixed_step {
+                return Err(PSError::UnsupportedProblem {
+                    e: "solver does not support a fixed step size".to_string(),
+                });
+            }
+            if h <= Eqn::T::zero() {
+                return Err(PSError::UnsupportedProblem {
+                    e: "fixed step size must be positive".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
     /// Take the current state of the solver, if it exists, returning it to the user. This is useful if you want to use this
     /// state in another solver or problem. Note that this will unset the current problem and solver state, so you will need to call
     /// `set_problem` again before calling `step` or `solve`.
     fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>>;
 
-    /// Reinitialise the solver state and solve the problem up to time `t`
-    fn solve(&mut self, problem: &OdeSolverProblem<Eqn>, t: Eqn::T) -> Result<Eqn::V, PSError>
+    /// Reinitialise the solver state and solve the problem up to time `t`, returning the solution at the initial time and at each
+    /// internal time step of the solver. The last step is shortened to stop exactly at `t` (see [Self::set_stop_time]).
+    /// The solver is also stopped and restarted at each of the [OdeSolverProblem::breakpoints] before `t`.
+    fn solve(
+        &mut self,
+        problem: &OdeSolverProblem<Eqn>,
+        t: Eqn::T,
+    ) -> Result<OdeSolverTrajectory<Eqn::V>, PSError>
     where
         Eqn::M: DefaultSolver,
         Self: Sized,
     {
         let state = OdeSolverState::new(problem, self)?;
+        let mut ret = OdeSolverTrajectory::new();
+        ret.push(state.t.clone(), state.y.clone());
         self.set_problem(state, problem);
-        self.set_stop_time(t)?;
-        loop {
-            if let OdeSolverStopReason::TstopReached = self.step()? {
-                break;
+        let breakpoints = problem.breakpoints_between(problem.t0.clone(), t.clone());
+        for (i, tstop) in breakpoints.iter().chain(std::iter::once(&t)).enumerate() {
+            if i > 0 {
+                self.restart(problem, |_| ())?;
+            }
+            self.set_stop_time(tstop.clone())?;
+            loop {
+                let reason = self.step()?;
+                let state = self.state().unwrap();
+                ret.push(state.t.clone(), state.y.clone());
+                if let OdeSolverStopReason::TstopReached = reason {
+                    break;
+                }
             }
         }
-        Ok(self.state().unwrap().y.clone())
+        if let Some(factor) = problem.options.global_error_factor.clone() {
+            ret.global_error = Some(self.estimate_global_error(problem, &ret, factor)?);
+        }
+        Ok(ret)
     }
+
+    /// Reinitialise the solver state and solve the problem, returning the solution at each of the times `t_eval`, which must be sorted
+    /// in the direction of integration and not before the initial time. The solution between the internal time steps of the solver
+    /// is found using [Self::interpolate]. The solver is also stopped and restarted at each of the [OdeSolverProblem::breakpoints].
+    fn solve_dense(
+        &mut self,
+        problem: &OdeSolverProblem<Eqn>,
+        t_eval: &[Eqn::T],
+    ) -> Result<OdeSolverTrajectory<Eqn::V>, PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Self: Sized,
+    {
+        let mut ret = self.solve_dense_with_breakpoints(problem, t_eval, |_, _| ())?;
+        if let Some(factor) = problem.options.global_error_factor.clone() {
+            ret.global_error = Some(self.estimate_global_error(problem, &ret, factor)?);
+        }
+        Ok(ret)
+    }
+
+    /// Estimate the global error of the solution `soln` of `problem` (see [crate::OdeSolverOptions::global_error_factor]), by solving
+    /// the problem again with the tolerances divided by `factor` and returning the difference between `soln` and this reference
+    /// solution at each time of `soln`. The error of the reference solution itself is neglected, so the estimate is most reliable
+    /// for factors that are large compared to one (e.g. 100). This is called by [Self::solve] and [Self::solve_dense].
+    fn estimate_global_error(
+        &mut self,
+        problem: &OdeSolverProblem<Eqn>,
+        soln: &OdeSolverTrajectory<Eqn::V>,
+        factor: Eqn::T,
+    ) -> Result<Vec<Eqn::V>, PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Self: Sized,
+    {
+        let mut reference = problem.clone();
+        reference.rtol /= factor.clone();
+        reference.atol = Rc::new(problem.atol.as_ref().clone() * scale(Eqn::T::one() / factor));
+        reference.options.global_error_factor = None;
+        let reference = self.solve_dense(&reference, &soln.t)?;
+        Ok(soln
+            .y
+            .iter()
+            .zip(reference.y.iter())
+            .map(|(y, y_ref)| y.clone() - y_ref)
+            .collect())
+    }
+
+    /// As [Self::solve_dense], but at each of the [OdeSolverProblem::breakpoints] the solver is stopped exactly at the breakpoint and
+    /// `callback` is called with the breakpoint time and the state vector, which it may modify (e.g. to add an impulse or bolus dose).
+    /// The solver is then restarted from the modified state (see [Self::restart]). An output time equal to a breakpoint gives the
+    /// solution before the callback is applied. The global error is not estimated by this function.
+    fn solve_dense_with_breakpoints<F>(
+        &mut self,
+        problem: &OdeSolverProblem<Eqn>,
+        t_eval: &[Eqn::T],
+        mut callback: F,
+    ) -> Result<OdeSolverTrajectory<Eqn::V>, PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Self: Sized,
+        F: FnMut(Eqn::T, &mut Eqn::V),
+    {
+        let direction = problem.direction();
+        if t_eval
+            .first()
+            .is_some_and(|t| (t.clone() - problem.t0.clone()) * direction.clone() < Eqn::T::zero())
+        {
+            return Err(PSError::Other {
+                e: "output times must not be before the initial time".to_string(),
+            });
+        }
+        let state = OdeSolverState::new(problem, self)?;
+        self.set_problem(state, problem);
+        let t_final = t_eval.last().cloned().unwrap_or(problem.t0.clone());
+        let mut breakpoints = problem
+            .breakpoints_between(problem.t0.clone(), t_final)
+            .into_iter();
+        let mut next_breakpoint = breakpoints.next();
+        if let Some(tb) = next_breakpoint.clone() {
+            self.set_stop_time(tb)?;
+        }
+        let mut ret = OdeSolverTrajectory::new();
+        for t in t_eval.iter().cloned() {
+            while (t.clone() - self.state().unwrap().t.clone()) * direction.clone() > Eqn::T::zero()
+            {
+                if let Some(tb) = next_breakpoint
+                    .clone()
+                    .filter(|tb| *tb == self.state().unwrap().t)
+                {
+                    // the solver has stopped at the breakpoint, apply the callback and restart
+                    self.restart(problem, |state| callback(tb, &mut state.y))?;
+                    next_breakpoint = breakpoints.next();
+                    if let Some(tb) = next_breakpoint.clone() {
+                        self.set_stop_time(tb)?;
+                    }
+                }
+                self.step()?;
+            }
+            let state = self.state().unwrap();
+            if state.t == t {
+                ret.push(t, state.y.clone());
+            } else {
+                let y = self.interpolate(t.clone())?;
+                ret.push(t, y);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Restart the solver at the current time, after applying `update` to the current state (e.g. to add an impulse to the state vector).
+    /// The solver history (e.g. the past solutions used by multistep methods) is discarded and the solver is set up again from the
+    /// updated state using [OdeSolverState::restart], as if solving a new problem starting at the current time.
+    fn restart<F>(&mut self, problem: &OdeSolverProblem<Eqn>, update: F) -> Result<(), PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Self: Sized,
+        F: FnOnce(&mut OdeSolverState<Eqn::V>),
+    {
+        let order = self.order();
+        let mut state = self.take_state().ok_or(PSError::StateNotSet)?;
+        update(&mut state);
+        state.restart(problem, order)?;
+        self.set_problem(state, problem);
+        Ok(())
+    }
+
+    /// Solve the problem up to time `t` for each of the parameter vectors in `params`, returning the solution at time `t` for each.
+    /// The solver is reused between solves, and each solve after the first is warm-started using the initial step size
+    /// found for the previous parameter vector, and the previous consistent initial state as the initial guess for the
+    /// algebraic states. Any sparsity patterns already calculated for the equations, and the symbolic analysis of the sparse
+    /// linear solver, are kept.
+    /// On return the parameters of `problem` are set to the last vector in `params`.
+    fn solve_sweep(
+        &mut self,
+        problem: &mut OdeSolverProblem<Eqn>,
+        params: &[Eqn::V],
+        t: Eqn::T,
+    ) -> Result<Vec<Eqn::V>, PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Self: Sized,
+    {
+        let mut ret = Vec::with_capacity(params.len());
+        let mut h0 = None;
+        let mut y0: Option<Eqn::V> = None;
+        for p in params {
+            // release the previous problem so the parameters can be updated in place, the linear solver keeps its symbolic
+            // analysis so this is reused if the sparsity pattern is unchanged
+            self.take_state();
+            problem.set_params(p.clone())?;
+            let state = match (h0, y0.as_ref()) {
+                (Some(h), Some(y0)) => {
+                    let mut state = OdeSolverState::new_without_initialise(problem);
+                    // the algebraic states of the previous consistent initial state are the initial guess for the new ones
+                    if let Some(mass) = problem.eqn.mass() {
+                        let algebraic_indices = mass
+                            .matrix(problem.t0.clone())
+                            .diagonal()
+                            .filter_indices(|x| x == Eqn::T::zero());
+                        state.y.copy_from_indices(y0, &algebraic_indices);
+                    }
+                    let mut root_solver = AnyNonLinearSolver::new(
+                        problem.options.initialisation_solver,
+                        <Eqn::M as DefaultSolver>::default_solver(),
+                    );
+                    state.set_consistent(problem, &mut root_solver)?;
+                    let mut root_solver_sens =
+                        NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+                    state.set_consistent_sens(problem, &mut root_solver_sens)?;
+                    state.h = h;
+                    state
+                }
+                _ => OdeSolverState::new(problem, self)?,
+            };
+            h0 = Some(state.h.clone());
+            y0 = Some(state.y.clone());
+            self.set_problem(state, problem);
+            self.set_stop_time(t.clone())?;
+            loop {
+                if let OdeSolverStopReason::TstopReached = self.step()? {
+                    break;
+                }
+            }
+            ret.push(self.state().unwrap().y.clone());
+        }
+        self.take_state();
+        Ok(ret)
+    }
+}
+
+/// The parts of a solver used by the checks shared by all the solvers at the end of each step (see [end_step]) and when setting
+/// the stop time (see [set_stop_time]).
+pub(crate) trait StepChecks<Eqn: OdeEquations>: OdeSolverMethod<Eqn> {
+    /// The root finder of the solver, if the problem has a root function
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>>;
+
+    /// The current state, the stop time and the roundoff error accumulated in the current time of the solver
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    );
+
+    /// Scale the step size of the solver by `factor`
+    fn scale_step(&mut self, factor: Eqn::T);
+}
+
+/// Check if the solver is at its stop time (if set), in which case the current time is snapped to the stop time and the stop
+/// time is unset. Otherwise the step size is reduced if the next step would go past the stop time, in the direction of integration.
+/// If the solver is already past the stop time an error is returned, and the stop time is unset.
+pub(crate) fn handle_tstop<Eqn, S>(
+    solver: &mut S,
+) -> Result<Option<OdeSolverStopReason<Eqn::T>>, PSError>
+where
+    Eqn: OdeEquations,
+    S: StepChecks<Eqn>,
+{
+    let direction = solver.problem().unwrap().direction();
+    let (state, tstop, t_compensation) = solver.tstop_mut();
+    let Some(t_stop) = tstop.clone() else {
+        return Ok(None);
+    };
+    let troundoff =
+        Eqn::T::cast(100.0) * Eqn::T::EPSILON * (abs(state.t.clone()) + abs(state.h.clone()));
+    if abs(state.t.clone() - t_stop.clone()) <= troundoff {
+        // snap to tstop so the output time is exact
+        state.t = t_stop;
+        *t_compensation = Eqn::T::zero();
+        *tstop = None;
+        return Ok(Some(OdeSolverStopReason::TstopReached));
+    } else if (t_stop.clone() - state.t.clone()) * direction.clone() < -troundoff.clone() {
+        *tstop = None;
+        return Err(PSError::StopBeforeCurrentTime {
+            tstop: t_stop.to_string(),
+            t: state.t.to_string(),
+        });
+    }
+
+    // check if the next step will be beyond tstop, if so adjust the step size
+    // (taking into account the roundoff error accumulated in t)
+    if (state.t.clone() + state.h.clone() - t_stop.clone()) * direction > troundoff {
+        let factor = (t_stop - state.t.clone() + t_compensation.clone()) / state.h.clone();
+        solver.scale_step(factor);
+    }
+    Ok(None)
+}
+
+/// Set the stop time of the solver, see [OdeSolverMethod::set_stop_time]
+pub(crate) fn set_stop_time<Eqn, S>(solver: &mut S, tstop: Eqn::T) -> Result<(), PSError>
+where
+    Eqn: OdeEquations,
+    S: StepChecks<Eqn>,
+{
+    if solver.state().is_none() {
+        return Err(PSError::StateNotSet);
+    }
+    *solver.tstop_mut().1 = Some(tstop.clone());
+    if let Some(OdeSolverStopReason::TstopReached) = handle_tstop(solver)? {
+        return Err(PSError::StopBeforeCurrentTime {
+            tstop: tstop.to_string(),
+            t: solver.state().unwrap().t.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// The checks at the end of each accepted step of a solver: that the solution has not blown up (see
+/// [OdeSolverProblem::check_state_bound]), for a root of the root function within the step, and if the stop time has been reached.
+pub(crate) fn end_step<Eqn, S>(solver: &mut S) -> Result<OdeSolverStopReason<Eqn::T>, PSError>
+where
+    Eqn: OdeEquations,
+    S: StepChecks<Eqn>,
+{
+    let problem = solver.problem().unwrap();
+    let state = solver.state().unwrap();
+    problem.check_state_bound(&state.y, state.t.clone())?;
+
+    // check for root within accepted step
+    if let (Some(root_fn), Some(root_finder)) = (problem.eqn.root(), solver.root_finder()) {
+        let ret = root_finder.check_root(
+            &|t| solver.interpolate(t),
+            root_fn.as_ref(),
+            &state.y,
+            state.t.clone(),
+        )?;
+        if let Some((index, t)) = ret {
+            return Ok(OdeSolverStopReason::RootFound { index, t });
+        }
+    }
+
+    // check if the we are at tstop, otherwise this is just a normal step
+    Ok(handle_tstop(solver)?.unwrap_or(OdeSolverStopReason::InternalTimestep))
 }
 
 /// State for the ODE solver, containing:
 /// - the current solution `y`
 /// - the derivative of the solution wrt time `dy`
 /// - the current time `t`
-/// - the current step size `h`,
+/// - the current step size `h`, this is negative if integrating backward in time (see [Self::is_backward])
 /// - the sensitivity vectors `s`
 /// - the derivative of the sensitivity vectors wrt time `ds`
 ///
@@ -129,9 +542,12 @@ impl<V: Vector> OdeSolverState<V> {
         Eqn::M: DefaultSolver,
         S: OdeSolverMethod<Eqn>,
     {
+        solver.check_problem(ode_problem)?;
         let mut ret = Self::new_without_initialise(ode_problem);
-        let mut root_solver =
-            NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+        let mut root_solver = AnyNonLinearSolver::new(
+            ode_problem.options.initialisation_solver,
+            <Eqn::M as DefaultSolver>::default_solver(),
+        );
         ret.set_consistent(ode_problem, &mut root_solver)?;
         let mut root_solver_sens =
             NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
@@ -148,30 +564,34 @@ impl<V: Vector> OdeSolverState<V> {
     where
         Eqn: OdeEquations<T = V::T, V = V>,
     {
-        let t = ode_problem.t0;
-        let h = ode_problem.h0;
-        let y = ode_problem.eqn.init().call(t);
+        let t = ode_problem.t0.clone();
+        let h = abs(ode_problem.h0.clone()) * ode_problem.direction();
+        let y = ode_problem.eqn.init().call(t.clone());
         let dy = V::zeros(y.len());
         let nparams = ode_problem.eqn.rhs().nparams();
-        let (s, ds) = if ode_problem.eqn_sens.is_none() {
-            (vec![], vec![])
-        } else {
-            let eqn_sens = ode_problem.eqn_sens.as_ref().unwrap();
-            eqn_sens.init().update_state(t);
+        let (s, ds) = if let Some(eqn_sens) = ode_problem.eqn_sens.as_ref() {
+            eqn_sens.init().update_state(t.clone());
             let mut s = Vec::with_capacity(nparams);
             let mut ds = Vec::with_capacity(nparams);
             for i in 0..nparams {
                 eqn_sens.init().set_param_index(i);
-                let si = eqn_sens.init().call(t);
+                let si = eqn_sens.init().call(t.clone());
                 let dsi = V::zeros(y.len());
                 s.push(si);
                 ds.push(dsi);
             }
             (s, ds)
+        } else {
+            (vec![], vec![])
         };
         Self { y, t, h, dy, s, ds }
     }
 
+    /// Returns true if the state is integrating backward in time, i.e. the step size `h` is negative
+    pub fn is_backward(&self) -> bool {
+        self.h < V::T::zero()
+    }
+
     /// Calculate a consistent state and time derivative of the state, based on the equations of the problem.
     pub fn set_consistent<Eqn, S>(
         &mut self,
@@ -185,22 +605,22 @@ impl<V: Vector> OdeSolverState<V> {
         ode_problem
             .eqn
             .rhs()
-            .call_inplace(&self.y, self.t, &mut self.dy);
+            .call_inplace(&self.y, self.t.clone(), &mut self.dy);
         if ode_problem.eqn.mass().is_none() {
             return Ok(());
         }
         let f = Rc::new(InitOp::new(
             &ode_problem.eqn,
-            ode_problem.t0,
+            ode_problem.t0.clone(),
             &self.y,
             &self.dy,
         ));
-        let rtol = ode_problem.rtol;
+        let rtol = ode_problem.rtol.clone();
         let atol = ode_problem.atol.clone();
         let init_problem = SolverProblem::new(f.clone(), atol, rtol);
         root_solver.set_problem(&init_problem);
         let mut y = f.y0.borrow().clone();
-        root_solver.solve_in_place(&mut y, self.t)?;
+        root_solver.solve_in_place(&mut y, self.t.clone())?;
         f.scatter_soln(&y, &mut self.y, &mut self.dy);
         Ok(())
     }
@@ -222,13 +642,15 @@ impl<V: Vector> OdeSolverState<V> {
         }
 
         let eqn_sens = ode_problem.eqn_sens.as_ref().unwrap();
-        eqn_sens.rhs().update_state(&self.y, &self.dy, self.t);
+        eqn_sens
+            .rhs()
+            .update_state(&self.y, &self.dy, self.t.clone());
         for i in 0..ode_problem.eqn.rhs().nparams() {
             eqn_sens.init().set_param_index(i);
             eqn_sens.rhs().set_param_index(i);
             eqn_sens
                 .rhs()
-                .call_inplace(&self.s[i], self.t, &mut self.ds[i]);
+                .call_inplace(&self.s[i], self.t.clone(), &mut self.ds[i]);
         }
 
         if ode_problem.eqn.mass().is_none() {
@@ -240,73 +662,100 @@ impl<V: Vector> OdeSolverState<V> {
             eqn_sens.rhs().set_param_index(i);
             let f = Rc::new(InitOp::new(
                 eqn_sens,
-                ode_problem.t0,
+                ode_problem.t0.clone(),
                 &self.s[i],
                 &self.ds[i],
             ));
             root_solver.set_problem(&SolverProblem::new(
                 f.clone(),
                 ode_problem.atol.clone(),
-                ode_problem.rtol,
+                ode_problem.rtol.clone(),
             ));
             let mut y = f.y0.borrow().clone();
-            root_solver.solve_in_place(&mut y, self.t)?;
+            root_solver.solve_in_place(&mut y, self.t.clone())?;
             f.scatter_soln(&y, &mut self.s[i], &mut self.ds[i]);
         }
         Ok(())
     }
 
+    /// Set up the state again after a discontinuity at the current time (e.g. after the state vector has been modified), making it consistent
+    /// with any algebraic constraints using a default nonlinear solver. The previous step size is not valid across the discontinuity,
+    /// so a new initial step size is calculated based on `solver_order` (see [Self::set_step_size]).
+    pub fn restart<Eqn>(
+        &mut self,
+        ode_problem: &OdeSolverProblem<Eqn>,
+        solver_order: usize,
+    ) -> Result<(), PSError>
+    where
+        Eqn: OdeEquations<T = V::T, V = V>,
+        Eqn::M: DefaultSolver,
+    {
+        let mut root_solver = AnyNonLinearSolver::new(
+            ode_problem.options.initialisation_solver,
+            <Eqn::M as DefaultSolver>::default_solver(),
+        );
+        self.set_consistent(ode_problem, &mut root_solver)?;
+        let mut root_solver_sens =
+            NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+        self.set_consistent_sens(ode_problem, &mut root_solver_sens)?;
+        self.set_step_size(ode_problem, solver_order);
+        Ok(())
+    }
+
     /// compute size of first step based on alg in Hairer, Norsett, Wanner
     /// Solving Ordinary Differential Equations I, Nonstiff Problems
     /// Section II.4.2
     /// Note: this assumes that the state is already consistent with the algebraic constraints
-    /// and y and dy are already set appropriately
+    /// and y and dy are already set appropriately. The sign of the step size is set by [OdeSolverProblem::direction].
     pub fn set_step_size<Eqn>(&mut self, ode_problem: &OdeSolverProblem<Eqn>, solver_order: usize)
     where
         Eqn: OdeEquations<T = V::T, V = V>,
     {
         let y0 = &self.y;
-        let t0 = self.t;
+        let t0 = self.t.clone();
         let f0 = &self.dy;
 
-        let rtol = ode_problem.rtol;
+        let rtol = ode_problem.rtol.clone();
         let atol = ode_problem.atol.as_ref();
 
-        let d0 = y0.squared_norm(y0, atol, rtol).sqrt();
-        let d1 = f0.squared_norm(y0, atol, rtol).sqrt();
+        let d0 = y0.squared_norm(y0, atol, rtol.clone()).sqrt();
+        let d1 = f0.squared_norm(y0, atol, rtol.clone()).sqrt();
 
-        let h0 = if d0 < Eqn::T::from(1e-5) || d1 < Eqn::T::from(1e-5) {
-            Eqn::T::from(1e-6)
+        let h0 = if d0 < Eqn::T::cast(1e-5) || d1 < Eqn::T::cast(1e-5) {
+            Eqn::T::cast(1e-6)
         } else {
-            Eqn::T::from(0.01) * (d0 / d1)
+            Eqn::T::cast(0.01) * (d0 / d1.clone())
         };
 
-        let y1 = f0.clone() * scale(h0) + y0;
-        let t1 = t0 + h0;
+        // take the trial explicit euler step in the direction of integration
+        let direction = ode_problem.direction();
+        let y1 = f0.clone() * scale(direction.clone() * h0.clone()) + y0;
+        let t1 = t0 + direction.clone() * h0.clone();
         let f1 = ode_problem.eqn.rhs().call(&y1, t1);
 
         let df = f1 - f0;
-        let d2 = df.squared_norm(y0, atol, rtol).sqrt() / h0;
+        let d2 = df.squared_norm(y0, atol, rtol).sqrt() / h0.clone();
 
         let mut max_d = d2;
         if max_d < d1 {
             max_d = d1;
         }
-        let h1 = if max_d < Eqn::T::from(1e-15) {
-            let h1 = h0 * Eqn::T::from(1e-3);
-            if h1 < Eqn::T::from(1e-6) {
-                Eqn::T::from(1e-6)
+        let h1 = if max_d < Eqn::T::cast(1e-15) {
+            let h1 = h0.clone() * Eqn::T::cast(1e-3);
+            if h1 < Eqn::T::cast(1e-6) {
+                Eqn::T::cast(1e-6)
             } else {
                 h1
             }
         } else {
-            (Eqn::T::from(0.01) / max_d)
-                .pow(Eqn::T::one() / Eqn::T::from(1.0 + solver_order as f64))
+            (Eqn::T::cast(0.01) / max_d)
+                .pow(Eqn::T::one() / Eqn::T::cast(1.0 + solver_order as f64))
         };
 
-        self.h = Eqn::T::from(100.0) * h0;
+        self.h = Eqn::T::cast(100.0) * h0;
         if self.h > h1 {
             self.h = h1;
         }
+        self.h *= direction;
     }
 }
@@ -1,12 +1,45 @@
+pub mod adams;
+pub mod analytic;
+pub mod auto_linear;
 pub mod bdf;
 pub mod builder;
+pub mod complex;
+pub mod control;
+pub mod dae_index;
+pub mod dosing;
+pub mod ensemble;
 pub mod equations;
+pub mod erk;
+pub mod error_model;
+pub mod exponential;
+pub mod extrapolation;
+pub mod fixed_low_order;
+pub mod generalized_alpha;
+pub mod imex;
+pub mod initial_condition;
+pub mod likelihood;
+pub mod linear;
 pub mod method;
+pub mod objective;
+pub mod output;
+pub mod population;
 pub mod problem;
+pub mod radau;
+pub mod reaction;
+pub mod rkc;
+pub mod rosenbrock;
+pub mod sampling;
 pub mod sdirk;
+pub mod second_order;
 pub mod sens_equations;
+pub mod sobol;
+pub mod steady_state;
+pub mod switching;
 pub mod tableau;
 pub mod test_models;
+pub mod transform;
+pub mod transit;
+pub mod uncertainty;
 
 #[cfg(feature = "diffsl")]
 pub mod diffsl;
@@ -22,10 +55,12 @@ mod tests {
     use nalgebra::ComplexField;
 
     use super::*;
+    use crate::errors::PSError;
     use crate::matrix::Matrix;
     use crate::op::unit::UnitCallable;
     use crate::op::{NonLinearOp, Op};
-    use crate::{ConstantOp, DefaultSolver, Vector};
+    use crate::{scale, ConstantOp, DefaultSolver, Scalar, Vector};
+    use crate::{Closure, ClosureNoJac, InvariantEquations, RootEquations};
     use crate::{
         OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason,
     };
@@ -49,14 +84,15 @@ mod tests {
         let have_root = problem.eqn.as_ref().root().is_some();
         for (i, point) in solution.solution_points.iter().enumerate() {
             let (soln, sens_soln) = if use_tstop {
-                match method.set_stop_time(point.t) {
+                match method.set_stop_time(point.t.clone()) {
                     Ok(_) => loop {
                         match method.step() {
-                            Ok(OdeSolverStopReason::RootFound(_)) => {
+                            Ok(OdeSolverStopReason::RootFound { .. }) => {
                                 assert!(have_root);
                                 return method.state().unwrap().y.clone();
                             }
                             Ok(OdeSolverStopReason::TstopReached) => {
+                                assert_eq!(method.state().unwrap().t, point.t);
                                 break (
                                     method.state().unwrap().y.clone(),
                                     method.state().unwrap().s.clone(),
@@ -72,25 +108,25 @@ mod tests {
                 }
             } else {
                 while method.state().unwrap().t < point.t {
-                    if let OdeSolverStopReason::RootFound(t) = method.step().unwrap() {
+                    if let OdeSolverStopReason::RootFound { t, .. } = method.step().unwrap() {
                         assert!(have_root);
                         return method.interpolate(t).unwrap();
                     }
                 }
-                let soln = method.interpolate(point.t).unwrap();
-                let sens_soln = method.interpolate_sens(point.t).unwrap();
+                let soln = method.interpolate(point.t.clone()).unwrap();
+                let sens_soln = method.interpolate_sens(point.t.clone()).unwrap();
                 (soln, sens_soln)
             };
 
-            if let Some(override_tol) = override_tol {
+            if let Some(override_tol) = override_tol.clone() {
                 soln.assert_eq_st(&point.state, override_tol);
             } else {
                 let error = soln.clone() - &point.state;
                 let error_norm = error
-                    .squared_norm(&point.state, &problem.atol, problem.rtol)
+                    .squared_norm(&point.state, &problem.atol, problem.rtol.clone())
                     .sqrt();
                 assert!(
-                    error_norm < M::T::from(15.0),
+                    error_norm < M::T::cast(15.0),
                     "error_norm: {} at t = {}",
                     error_norm,
                     point.t
@@ -101,10 +137,10 @@ mod tests {
                         let sens_soln = &sens_soln[j];
                         let error = sens_soln.clone() - &sens_point.state;
                         let error_norm = error
-                            .squared_norm(&sens_point.state, &problem.atol, problem.rtol)
+                            .squared_norm(&sens_point.state, &problem.atol, problem.rtol.clone())
                             .sqrt();
                         assert!(
-                            error_norm < M::T::from(20.0),
+                            error_norm < M::T::cast(20.0),
                             "error_norm: {} at t = {}",
                             error_norm,
                             point.t
@@ -221,8 +257,8 @@ mod tests {
     pub fn test_interpolate<M: Matrix, Method: OdeSolverMethod<TestEqn<M>>>(mut s: Method) {
         let problem = OdeSolverProblem::new(
             TestEqn::new(),
-            M::T::from(1e-6),
-            M::V::from_element(1, M::T::from(1e-6)),
+            M::T::cast(1e-6),
+            M::V::from_element(1, M::T::cast(1e-6)),
             M::T::zero(),
             M::T::one(),
             false,
@@ -233,13 +269,20 @@ mod tests {
         s.set_problem(state.clone(), &problem);
         let t0 = M::T::zero();
         let t1 = M::T::one();
-        s.interpolate(t0)
+        s.interpolate(t0.clone())
             .unwrap()
-            .assert_eq_st(&state.y, M::T::from(1e-9));
-        assert!(s.interpolate(t1).is_err());
+            .assert_eq_st(&state.y, M::T::cast(1e-9));
+        assert!(s.interpolate(t1.clone()).is_err());
         s.step().unwrap();
-        assert!(s.interpolate(s.state().unwrap().t).is_ok());
-        assert!(s.interpolate(s.state().unwrap().t + t1).is_err());
+        assert!(s.interpolate(s.state().unwrap().t.clone()).is_ok());
+        assert!(s.interpolate(s.state().unwrap().t.clone() + t1).is_err());
+
+        // a stop time behind the current time is an error, and is not kept as the stop time
+        assert!(s.set_stop_time(t0).is_err());
+        assert!(matches!(
+            s.step().unwrap(),
+            OdeSolverStopReason::InternalTimestep
+        ));
     }
 
     pub fn test_no_set_problem<M: Matrix, Method: OdeSolverMethod<TestEqn<M>>>(mut s: Method) {
@@ -247,14 +290,15 @@ mod tests {
         assert!(s.problem().is_none());
         assert!(s.state().is_none());
         assert!(s.step().is_err());
+        assert!(s.set_stop_time(M::T::one()).is_err());
         assert!(s.interpolate(M::T::one()).is_err());
     }
 
     pub fn test_state_mut<M: Matrix, Method: OdeSolverMethod<TestEqn<M>>>(mut s: Method) {
         let problem = OdeSolverProblem::new(
             TestEqn::new(),
-            M::T::from(1e-6),
-            M::V::from_element(1, M::T::from(1e-6)),
+            M::T::cast(1e-6),
+            M::V::from_element(1, M::T::cast(1e-6)),
             M::T::zero(),
             M::T::one(),
             false,
@@ -264,9 +308,346 @@ mod tests {
         let state = OdeSolverState::new_without_initialise(&problem);
         s.set_problem(state.clone(), &problem);
         let state2 = s.state().unwrap();
-        state2.y.assert_eq_st(&state.y, M::T::from(1e-9));
-        s.state_mut().unwrap().y[0] = M::T::from(std::f64::consts::PI);
-        assert_eq!(s.state().unwrap().y[0], M::T::from(std::f64::consts::PI));
+        state2.y.assert_eq_st(&state.y, M::T::cast(1e-9));
+        s.state_mut().unwrap().y[0] = M::T::cast(std::f64::consts::PI);
+        assert_eq!(s.state().unwrap().y[0], M::T::cast(std::f64::consts::PI));
+    }
+
+    pub fn test_capabilities<Eqn, Method>(mut s: Method, problem: OdeSolverProblem<Eqn>)
+    where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        let capabilities = s.capabilities();
+        s.check_problem(&problem).unwrap();
+        assert!(capabilities.mass_matrix || problem.eqn.mass().is_none());
+        assert!(capabilities.sensitivities || problem.eqn_sens.is_none());
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        for _ in 0..10 {
+            s.step().unwrap();
+            assert!(s.order() <= capabilities.max_order);
+        }
+    }
+
+    pub fn test_solve_sweep_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        mut problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = -a y, y(0) = 1
+        let t = Eqn::T::cast(1.0);
+        let params = [0.1, 0.2, 0.5]
+            .iter()
+            .map(|&a| Eqn::V::from_vec(vec![Eqn::T::cast(a)]))
+            .collect::<Vec<_>>();
+        let solns = s.solve_sweep(&mut problem, &params, t.clone()).unwrap();
+        assert_eq!(solns.len(), params.len());
+        for (p, soln) in params.iter().zip(solns.iter()) {
+            let expect = Eqn::V::from_element(soln.len(), (-p[0].clone() * t.clone()).exp());
+            soln.assert_eq_st(&expect, Eqn::T::cast(1e-4));
+        }
+        // problem is released by the solver, so parameters can be set again
+        problem
+            .set_params(Eqn::V::from_vec(vec![Eqn::T::cast(0.1)]))
+            .unwrap();
+    }
+
+    pub fn test_solution_bound_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        mut problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = y, y(0) = 1 grows past the bound of 10 at t = ln(10)
+        problem
+            .set_params(Eqn::V::from_vec(vec![Eqn::T::cast(-1.0)]))
+            .unwrap();
+        let nstates = problem.eqn.rhs().nstates();
+        problem.max_abs_state = Some(Rc::new(Eqn::V::from_element(nstates, Eqn::T::cast(10.0))));
+        match s.solve(&problem, Eqn::T::cast(10.0)) {
+            Err(PSError::SolutionBoundExceeded { t, indices }) => {
+                let t: f64 = t.parse().unwrap();
+                assert!(t > 10.0f64.ln() && t < 3.0, "t = {}", t);
+                assert_eq!(indices, (0..nstates).collect::<Vec<_>>());
+            }
+            _ => panic!("expected SolutionBoundExceeded error"),
+        }
+    }
+
+    pub fn test_backward_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        mut problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = -a y, y(0) = 1 integrated backward in time, so y(t) = e^{-a t} for t < 0
+        problem.backward = true;
+        let a = 0.1;
+        let nstates = problem.eqn.rhs().nstates();
+        let expect = |t: f64| Eqn::V::from_element(nstates, Eqn::T::cast((-a * t).exp()));
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        assert!(state.is_backward());
+        s.set_problem(state, &problem);
+
+        // the stop time must be ahead of the current time in the direction of integration
+        assert!(s.set_stop_time(Eqn::T::cast(1.0)).is_err());
+        s.set_stop_time(Eqn::T::cast(-1.0)).unwrap();
+
+        // interpolate at t = -0.5 within the step past it, the step is shortened to stop exactly at the stop time
+        let mut interpolated = false;
+        loop {
+            let reason = s.step().unwrap();
+            let t = s.state().unwrap().t.clone();
+            if !interpolated && t <= Eqn::T::cast(-0.5) {
+                let y = s.interpolate(Eqn::T::cast(-0.5)).unwrap();
+                y.assert_eq_st(&expect(-0.5), Eqn::T::cast(1e-4));
+                assert!(s.interpolate(t - Eqn::T::cast(1.0)).is_err());
+                interpolated = true;
+            }
+            if let OdeSolverStopReason::TstopReached = reason {
+                break;
+            }
+        }
+        assert_eq!(s.state().unwrap().t, Eqn::T::cast(-1.0));
+        s.state()
+            .unwrap()
+            .y
+            .assert_eq_st(&expect(-1.0), Eqn::T::cast(1e-4));
+    }
+
+    /// Root function of [test_root_fn_exponential_decay], with two outputs crossing zero at `y = 0.6` and `y = 0.8`.
+    pub type ExponentialDecayRoot<M, V, T> = ClosureNoJac<M, fn(&V, &V, T, &mut V)>;
+
+    #[allow(clippy::type_complexity)]
+    pub fn test_root_fn_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<RootEquations<Eqn, ExponentialDecayRoot<Eqn::M, Eqn::V, Eqn::T>>>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = -a y, y(0) = 1, so y(t) = e^{-a t} crosses 0.8 first and then 0.6
+        let a = 0.1;
+        let nstates = problem.eqn.rhs().nstates();
+        let root_fn: fn(&Eqn::V, &Eqn::V, Eqn::T, &mut Eqn::V) = |x, _p, _t, g| {
+            g[0] = x[0].clone() - Eqn::T::cast(0.6);
+            g[1] = x[0].clone() - Eqn::T::cast(0.8);
+        };
+        let p = Rc::new(Eqn::V::zeros(problem.eqn.rhs().nparams()));
+        let root = ClosureNoJac::new(root_fn, nstates, 2, p);
+        let problem = problem.with_root_fn(root).unwrap();
+        assert_eq!(problem.eqn.root().unwrap().nout(), 2);
+
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        for (expect_index, y_root) in [(1, 0.8), (0, 0.6)] {
+            let (index, t) = loop {
+                if let OdeSolverStopReason::RootFound { index, t } = s.step().unwrap() {
+                    break (index, t);
+                }
+            };
+            assert_eq!(index, expect_index);
+            let t_root = -f64::ln(y_root) / a;
+            assert!(
+                (t.clone() - Eqn::T::cast(t_root)).abs() < Eqn::T::cast(1e-3),
+                "t = {}",
+                t
+            );
+            let y = s.interpolate(t).unwrap();
+            y.assert_eq_st(
+                &Eqn::V::from_element(nstates, Eqn::T::cast(y_root)),
+                Eqn::T::cast(1e-4),
+            );
+        }
+    }
+
+    pub fn test_global_error_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        mut problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = -a y, y(0) = 1, so the true global error is y - e^{-a t}
+        let a = 0.1;
+        let t_eval = [1.0, 5.0, 10.0].map(Eqn::T::cast);
+        problem.rtol = Eqn::T::cast(1e-3);
+        problem.atol = Rc::new(Eqn::V::from_element(
+            problem.eqn.rhs().nstates(),
+            Eqn::T::cast(1e-6),
+        ));
+        let soln = s.solve_dense(&problem, &t_eval).unwrap();
+        assert!(soln.global_error.is_none());
+
+        problem.options.global_error_factor = Some(Eqn::T::cast(100.0));
+        let soln = s.solve_dense(&problem, &t_eval).unwrap();
+        let global_error = soln.global_error.unwrap();
+        assert_eq!(global_error.len(), t_eval.len());
+        let true_error = t_eval
+            .iter()
+            .zip(soln.y.iter())
+            .map(|(t, y)| y[0].clone() - (Eqn::T::cast(-a) * t.clone()).exp())
+            .collect::<Vec<_>>();
+        let max_error = true_error.iter().fold(Eqn::T::zero(), |m, e| {
+            if e.clone().abs() > m {
+                e.clone().abs()
+            } else {
+                m
+            }
+        });
+        for ((t, err), true_err) in t_eval.iter().zip(global_error.iter()).zip(true_error) {
+            // the estimate should agree with the true error to within a fraction of the largest error along the solution
+            assert!(
+                (err[0].clone() - true_err.clone()).abs() <= Eqn::T::cast(0.5) * max_error.clone(),
+                "t = {}, estimated error = {}, true error = {}",
+                t,
+                err[0],
+                true_err
+            );
+        }
+
+        let soln = s.solve(&problem, Eqn::T::cast(10.0)).unwrap();
+        assert_eq!(soln.global_error.unwrap().len(), soln.t.len());
+    }
+
+    /// Checks the order of accuracy of a one-step method on the exponential decay problem dy/dt = -a y (a = 0.1), using the local
+    /// error of a single step from the initial state: halving the step size from `h` should reduce the error by a factor of
+    /// 2^(order + 1). `h` must be small enough that the step is accepted by the error control of the method.
+    pub fn test_local_error_order_exponential_decay<Eqn, Method>(
+        new_solver: impl Fn() -> Method,
+        problem: OdeSolverProblem<Eqn>,
+        h: Eqn::T,
+        order: usize,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        let local_error = |h: Eqn::T| {
+            let mut s = new_solver();
+            let mut state = OdeSolverState::new(&problem, &s).unwrap();
+            state.h = h.clone();
+            s.set_problem(state, &problem);
+            s.step().unwrap();
+            let state = s.state().unwrap();
+            assert_eq!(state.t, h, "the step of size {} was not accepted", h);
+            let y0 = problem.eqn.init().call(problem.t0.clone());
+            let y_exact = y0 * scale((Eqn::T::cast(-0.1) * h).exp());
+            (state.y.clone() - y_exact).norm()
+        };
+        let ratio = local_error(h.clone()) / local_error(h / Eqn::T::cast(2.0));
+        let expect = Eqn::T::cast(2.0f64.powi(order as i32 + 1));
+        assert!(
+            ratio > Eqn::T::cast(0.75) * expect.clone()
+                && ratio < Eqn::T::cast(1.25) * expect.clone(),
+            "ratio = {}, expected {}",
+            ratio,
+            expect
+        );
+    }
+
+    /// Invariant function of [test_invariant_harmonic_oscillator], the energy of the oscillator minus its initial value.
+    pub type HarmonicOscillatorInvariant<M, V, T> =
+        Closure<M, fn(&V, &V, T, &mut V), fn(&V, &V, T, &V, &mut V)>;
+
+    #[allow(clippy::type_complexity)]
+    pub fn test_invariant_harmonic_oscillator<Eqn, Method>(
+        mut s: Method,
+        problem: OdeSolverProblem<Eqn>,
+        solution: OdeSolverSolution<Eqn::V>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<
+            InvariantEquations<Eqn, HarmonicOscillatorInvariant<Eqn::M, Eqn::V, Eqn::T>>,
+        >,
+        Eqn::M: DefaultSolver,
+    {
+        // dx/dt = v, dv/dt = -x, with the energy x^2 + v^2 = 1 kept constant by projecting after each step
+        let energy: fn(&Eqn::V, &Eqn::V, Eqn::T, &mut Eqn::V) =
+            test_models::harmonic_oscillator::harmonic_oscillator_energy::<Eqn::M>;
+        let energy_jacobian: fn(&Eqn::V, &Eqn::V, Eqn::T, &Eqn::V, &mut Eqn::V) =
+            test_models::harmonic_oscillator::harmonic_oscillator_energy_jacobian::<Eqn::M>;
+        let p = Rc::new(Eqn::V::zeros(problem.eqn.rhs().nparams()));
+        let invariant = Closure::new(energy, energy_jacobian, 2, 1, p);
+        let problem = problem.with_invariant(invariant).unwrap();
+        assert!(problem.eqn.has_invariants());
+
+        let t_final = solution.solution_points.last().unwrap().t.clone();
+        let soln = s.solve(&problem, t_final).unwrap();
+        for y in soln.y.iter() {
+            let energy = y[0].clone() * y[0].clone() + y[1].clone() * y[1].clone();
+            assert!(
+                (energy.clone() - Eqn::T::one()).abs() < Eqn::T::cast(1e-10),
+                "energy = {}",
+                energy
+            );
+        }
+        let t_eval = solution
+            .solution_points
+            .iter()
+            .map(|point| point.t.clone())
+            .collect::<Vec<_>>();
+        let soln = s.solve_dense(&problem, &t_eval).unwrap();
+        for (y, point) in soln.y.iter().zip(solution.solution_points.iter()) {
+            y.assert_eq_st(&point.state, Eqn::T::cast(1e-2));
+        }
+    }
+
+    pub fn test_breakpoints_exponential_decay<Eqn, Method>(
+        mut s: Method,
+        mut problem: OdeSolverProblem<Eqn>,
+    ) where
+        Eqn: OdeEquations,
+        Method: OdeSolverMethod<Eqn>,
+        Eqn::M: DefaultSolver,
+    {
+        // dy/dt = -a y, y(0) = 1, with an impulse of 1 added to the state at t = 1 and t = 2
+        let a = 0.1;
+        let nstates = problem.eqn.rhs().nstates();
+        let expect = |t: f64| {
+            let y = (-a * t).exp()
+                + if t > 1.0 { (-a * (t - 1.0)).exp() } else { 0.0 }
+                + if t > 2.0 { (-a * (t - 2.0)).exp() } else { 0.0 };
+            Eqn::V::from_element(nstates, Eqn::T::cast(y))
+        };
+        problem.breakpoints = vec![Eqn::T::cast(2.0), Eqn::T::cast(1.0), Eqn::T::cast(5.0)];
+        assert_eq!(
+            problem.breakpoints_between(Eqn::T::zero(), Eqn::T::cast(3.0)),
+            vec![Eqn::T::cast(1.0), Eqn::T::cast(2.0)]
+        );
+
+        // the solver stops exactly at each breakpoint before the final time
+        let soln = s.solve(&problem, Eqn::T::cast(3.0)).unwrap();
+        assert!(soln.t.contains(&Eqn::T::cast(1.0)));
+        assert!(soln.t.contains(&Eqn::T::cast(2.0)));
+        soln.y.last().unwrap().assert_eq_st(
+            &Eqn::V::from_element(nstates, Eqn::T::cast((-a * 3.0).exp())),
+            Eqn::T::cast(1e-4),
+        );
+
+        let t_eval = [0.5, 1.0, 1.5, 2.0, 3.0].map(Eqn::T::cast);
+        let mut called = Vec::new();
+        let soln = s
+            .solve_dense_with_breakpoints(&problem, &t_eval, |t, y| {
+                called.push(t);
+                y.add_scalar_mut(Eqn::T::one());
+            })
+            .unwrap();
+        assert_eq!(called, vec![Eqn::T::cast(1.0), Eqn::T::cast(2.0)]);
+        for (t, y) in soln.t.iter().zip(soln.y.iter()) {
+            y.assert_eq_st(&expect((*t).to_f64()), Eqn::T::cast(1e-4));
+        }
     }
 
     pub fn test_state_mut_on_problem<Eqn, Method>(
@@ -279,7 +660,7 @@ mod tests {
         Eqn::M: DefaultSolver,
     {
         // solve for a little bit
-        s.solve(&problem, Eqn::T::from(1.0)).unwrap();
+        s.solve(&problem, Eqn::T::cast(1.0)).unwrap();
 
         // reinit using state_mut
         let state = OdeSolverState::new_without_initialise(&problem);
@@ -291,13 +672,13 @@ mod tests {
             while s.state().unwrap().t < point.t {
                 s.step().unwrap();
             }
-            let soln = s.interpolate(point.t).unwrap();
+            let soln = s.interpolate(point.t.clone()).unwrap();
             let error = soln.clone() - &point.state;
             let error_norm = error
-                .squared_norm(&error, &problem.atol, problem.rtol)
+                .squared_norm(&error, &problem.atol, problem.rtol.clone())
                 .sqrt();
             assert!(
-                error_norm < Eqn::T::from(16.0),
+                error_norm < Eqn::T::cast(16.0),
                 "error_norm: {} at t = {}",
                 error_norm,
                 point.t
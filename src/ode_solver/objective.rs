@@ -0,0 +1,359 @@
+use num_traits::Zero;
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, InfusionEquations,
+    OdeEquations, OdeSolverMethod, OdeSolverProblem, Population, Vector,
+};
+
+/// A single observation of the state at index `output` at time `t`, with the given `weight` in the objective (e.g. `1 / sigma^2`)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Observation<T: Scalar> {
+    pub t: T,
+    pub output: usize,
+    pub value: T,
+    pub weight: T,
+}
+
+impl<T: Scalar> Observation<T> {
+    pub fn new(t: T, output: usize, value: T, weight: T) -> Self {
+        Self {
+            t,
+            output,
+            value,
+            weight,
+        }
+    }
+}
+
+/// The weighted residuals `r_i = sqrt(w_i) (y_i - f_i)` for each observation `y_i` with weight `w_i` and model prediction `f_i`,
+/// in the same order as the observations, along with the weighted residual sum of squares `wrss = sum_i r_i^2`.
+#[derive(Clone, Debug)]
+pub struct WeightedResiduals<T: Scalar> {
+    pub residuals: Vec<T>,
+    pub wrss: T,
+}
+
+/// A set of observations of the solution of a problem, used to evaluate a weighted least-squares objective.
+///
+/// The model predictions are needed at each of the (unique) observation times given by [Self::times]. These can be calculated by
+/// [Self::weighted_least_squares], which solves the problem and interpolates the solution at each time, or by any other method
+/// (e.g. [crate::DosingSchedule::solve]) and then passed to [Self::residuals].
+#[derive(Clone, Debug)]
+pub struct ObservationData<T: Scalar> {
+    observations: Vec<Observation<T>>,
+    times: Vec<T>,
+    time_index: Vec<usize>,
+}
+
+impl<T: Scalar> ObservationData<T> {
+    /// Create a new set of observations, which can be in any order. All the weights must be non-negative.
+    pub fn new(observations: Vec<Observation<T>>) -> Result<Self, PSError> {
+        if observations.iter().any(|o| o.weight < T::zero()) {
+            return Err(PSError::Other {
+                e: "observation weights must not be negative".to_string(),
+            });
+        }
+        let mut times = observations.iter().map(|o| o.t.clone()).collect::<Vec<_>>();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup();
+        let time_index = observations
+            .iter()
+            .map(|o| {
+                times
+                    .binary_search_by(|t| t.partial_cmp(&o.t).unwrap())
+                    .unwrap()
+            })
+            .collect();
+        Ok(Self {
+            observations,
+            times,
+            time_index,
+        })
+    }
+
+    pub fn observations(&self) -> &[Observation<T>] {
+        &self.observations
+    }
+
+    /// The unique observation times, sorted
+    pub fn times(&self) -> &[T] {
+        &self.times
+    }
+
+    /// The index into [Self::times] of the time of each observation
+    pub(crate) fn time_index(&self) -> &[usize] {
+        &self.time_index
+    }
+
+    /// Calculate the weighted residuals given the model predictions `y`, which must contain the solution at each of the times in [Self::times]
+    pub fn residuals<V: Vector<T = T>>(&self, y: &[V]) -> Result<WeightedResiduals<T>, PSError> {
+        if y.len() != self.times.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "model predictions".to_string(),
+                expected: self.times.len(),
+                found: y.len(),
+            });
+        }
+        let mut wrss = T::zero();
+        let mut residuals = Vec::with_capacity(self.observations.len());
+        for (o, &i) in self.observations.iter().zip(self.time_index.iter()) {
+            if o.output >= y[i].len() {
+                return Err(PSError::DimensionMismatch {
+                    name: "observation output index".to_string(),
+                    expected: y[i].len(),
+                    found: o.output,
+                });
+            }
+            let r = o.weight.clone().sqrt() * (o.value.clone() - y[i][o.output].clone());
+            wrss += r.clone() * r.clone();
+            residuals.push(r);
+        }
+        Ok(WeightedResiduals { residuals, wrss })
+    }
+
+    /// Solve `problem` up to the last observation time, interpolating the solution at each observation time, and return the weighted residuals.
+    /// The observation times must not be before the initial time of the problem.
+    pub fn weighted_least_squares<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<WeightedResiduals<T>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let y = solver.solve_dense(problem, &self.times)?.y;
+        self.residuals(&y)
+    }
+}
+
+/// The weighted residuals for each subject of a [PopulationObjective], along with the total weighted residual sum of squares over all subjects
+#[derive(Clone, Debug)]
+pub struct PopulationResiduals<T: Scalar> {
+    pub subjects: Vec<WeightedResiduals<T>>,
+    pub wrss: T,
+}
+
+/// A weighted least-squares objective over a [Population], with a set of observations for each subject.
+///
+/// The residuals for each subject are cached along with the parameters used to calculate them, so when the objective is evaluated
+/// repeatedly (e.g. by an optimiser that only changes some of the subjects' parameters), subjects whose parameters are unchanged are not solved again.
+/// If anything else about a subject changes (e.g. their dosing schedule), use [Self::invalidate] to remove the cached residuals.
+pub struct PopulationObjective<V: Vector> {
+    population: Population<V>,
+    data: Vec<ObservationData<V::T>>,
+    cache: Vec<Option<(V, WeightedResiduals<V::T>)>>,
+    nsolves: usize,
+}
+
+impl<V: Vector> PopulationObjective<V> {
+    /// Create a new objective, `data` must contain the observations for each subject of `population`, in the same order as the subjects.
+    /// The observation times of each subject are used in place of their `t_eval`.
+    pub fn new(
+        population: Population<V>,
+        data: Vec<ObservationData<V::T>>,
+    ) -> Result<Self, PSError> {
+        if population.subjects.len() != data.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "subject observations".to_string(),
+                expected: population.subjects.len(),
+                found: data.len(),
+            });
+        }
+        let cache = vec![None; data.len()];
+        Ok(Self {
+            population,
+            data,
+            cache,
+            nsolves: 0,
+        })
+    }
+
+    pub fn population(&self) -> &Population<V> {
+        &self.population
+    }
+
+    /// The total number of subject solves performed, not including evaluations using cached residuals
+    pub fn nsolves(&self) -> usize {
+        self.nsolves
+    }
+
+    /// Remove the cached residuals for the subject at index `i`, so it is solved at the next evaluation
+    pub fn invalidate(&mut self, i: usize) {
+        self.cache[i] = None;
+    }
+
+    /// Remove the cached residuals for all the subjects
+    pub fn invalidate_all(&mut self) {
+        self.cache.iter_mut().for_each(|c| *c = None);
+    }
+
+    /// Evaluate the objective with the parameters of each subject given by `params` (in the same order as the subjects).
+    /// Only the subjects whose parameters differ from those of the last evaluation (or whose cached residuals have been invalidated) are solved,
+    /// using `solver` and the equations in `problem` as in [Population::simulate].
+    pub fn evaluate<Eqn, S>(
+        &mut self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+        params: &[V],
+    ) -> Result<PopulationResiduals<V::T>, PSError>
+    where
+        Eqn: OdeEquations<T = V::T, V = V>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        if params.len() != self.population.subjects.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "subject parameters".to_string(),
+                expected: self.population.subjects.len(),
+                found: params.len(),
+            });
+        }
+        let mut wrss = V::T::zero();
+        let mut subjects = Vec::with_capacity(params.len());
+        for (i, p) in params.iter().enumerate() {
+            let is_cached = self.cache[i]
+                .as_ref()
+                .is_some_and(|(cached, _)| Self::params_equal(cached, p));
+            if !is_cached {
+                let mut subject = self.population.subjects[i].clone();
+                subject.params = p.clone();
+                subject.t_eval = self.data[i].times().to_vec();
+                let soln = self
+                    .population
+                    .simulate_subject(&subject, solver, problem)?;
+                self.nsolves += 1;
+                self.cache[i] = Some((p.clone(), self.data[i].residuals(&soln.y)?));
+            }
+            let residuals = &self.cache[i].as_ref().unwrap().1;
+            wrss += residuals.wrss.clone();
+            subjects.push(residuals.clone());
+        }
+        solver.take_state();
+        Ok(PopulationResiduals { subjects, wrss })
+    }
+
+    fn params_equal(a: &V, b: &V) -> bool {
+        a.len() == b.len() && (0..a.len()).all(|i| a[i] == b[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::{
+            objective::{Observation, ObservationData},
+            test_models::exponential_decay::exponential_decay_problem,
+        },
+        Bdf, DosingSchedule, Population, PopulationObjective, Subject,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_weighted_least_squares() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        // the solution is y = exp(-0.1 t) for both states, observations are given out of order
+        let observations = vec![
+            Observation::new(2.0, 1, f64::exp(-0.2) + 0.1, 4.0),
+            Observation::new(0.5, 0, f64::exp(-0.05), 1.0),
+            Observation::new(2.0, 0, f64::exp(-0.2) - 0.2, 1.0),
+            Observation::new(7.3, 0, f64::exp(-0.73), 0.0),
+        ];
+        let data = ObservationData::new(observations).unwrap();
+        assert_eq!(data.times(), &[0.5, 2.0, 7.3]);
+        let res = data.weighted_least_squares(&mut solver, &problem).unwrap();
+        let expect = [0.2, 0.0, -0.2, 0.0];
+        assert_eq!(res.residuals.len(), 4);
+        for (r, e) in res.residuals.iter().zip(expect.iter()) {
+            assert!((r - e).abs() < 1e-4, "residual {} expected {}", r, e);
+        }
+        assert!((res.wrss - 0.08).abs() < 1e-4);
+
+        let bad = ObservationData::new(vec![Observation::new(1.0, 2, 1.0, 1.0)]).unwrap();
+        assert!(bad.weighted_least_squares(&mut solver, &problem).is_err());
+        assert!(ObservationData::new(vec![Observation::new(1.0, 0, 1.0, -1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_residuals_with_dosing() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut schedule = DosingSchedule::new();
+        schedule.add_dose(1.0, 1.0, 0).unwrap();
+        let data = ObservationData::new(vec![
+            Observation::new(3.0, 0, f64::exp(-0.3) + f64::exp(-0.2), 1.0),
+            Observation::new(3.0, 1, f64::exp(-0.3), 1.0),
+        ])
+        .unwrap();
+        let y = schedule
+            .solve(&mut solver, &mut problem, data.times())
+            .unwrap();
+        let res = data.residuals(&y).unwrap();
+        assert!(res.wrss < 1e-8);
+        assert!(data.residuals::<V>(&[]).is_err());
+    }
+
+    #[test]
+    fn test_population_objective_cache() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let mut population = Population::new();
+        let mut data = Vec::new();
+        for i in 0..3 {
+            let subject = Subject::new(
+                &format!("{}", i),
+                V::from_vec(vec![0.1]),
+                DosingSchedule::new(),
+                vec![],
+            );
+            population.add_subject(subject);
+            // observations generated with k = 0.1
+            data.push(
+                ObservationData::new(vec![
+                    Observation::new(1.0, 0, f64::exp(-0.1), 1.0),
+                    Observation::new(2.0, 0, f64::exp(-0.2), 1.0),
+                ])
+                .unwrap(),
+            );
+        }
+        let mut objective = PopulationObjective::new(population, data).unwrap();
+        let mut params = vec![V::from_vec(vec![0.1]); 3];
+        let res = objective
+            .evaluate(&mut solver, &mut problem, &params)
+            .unwrap();
+        assert_eq!(objective.nsolves(), 3);
+        assert!(res.wrss < 1e-8);
+
+        // only the subject whose parameters changed is solved again
+        params[1] = V::from_vec(vec![0.2]);
+        let res = objective
+            .evaluate(&mut solver, &mut problem, &params)
+            .unwrap();
+        assert_eq!(objective.nsolves(), 4);
+        assert!(res.subjects[0].wrss < 1e-8);
+        let r = f64::exp(-0.1) - f64::exp(-0.2);
+        let r2 = f64::exp(-0.2) - f64::exp(-0.4);
+        assert!((res.subjects[1].wrss - (r * r + r2 * r2)).abs() < 1e-6);
+        assert!((res.wrss - res.subjects[1].wrss).abs() < 1e-8);
+
+        objective.invalidate(0);
+        objective
+            .evaluate(&mut solver, &mut problem, &params)
+            .unwrap();
+        assert_eq!(objective.nsolves(), 5);
+        objective.invalidate_all();
+        objective
+            .evaluate(&mut solver, &mut problem, &params)
+            .unwrap();
+        assert_eq!(objective.nsolves(), 8);
+        assert!(objective
+            .evaluate(&mut solver, &mut problem, &params[..2])
+            .is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, OdeEquations,
+    OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op, Vector,
+};
+
+/// The solution of an ODE problem at a sequence of times, where `y[i]` is the state at time `t[i]`.
+/// This is returned by [OdeSolverMethod::solve] (at the internal time steps of the solver) and [OdeSolverMethod::solve_dense] (at the requested times).
+#[derive(Clone, Debug)]
+pub struct OdeSolverTrajectory<V: Vector> {
+    pub t: Vec<V::T>,
+    pub y: Vec<V>,
+    /// The estimated global error of each state `y[i]`, i.e. the accumulated error and not just the local error of each step.
+    /// This is only calculated if [crate::OdeSolverOptions::global_error_factor] is set, and is `None` otherwise.
+    pub global_error: Option<Vec<V>>,
+}
+
+impl<V: Vector> Default for OdeSolverTrajectory<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Vector> OdeSolverTrajectory<V> {
+    pub fn new() -> Self {
+        Self {
+            t: Vec::new(),
+            y: Vec::new(),
+            global_error: None,
+        }
+    }
+
+    pub fn push(&mut self, t: V::T, y: V) {
+        self.t.push(t);
+        self.y.push(y);
+    }
+
+    pub fn len(&self) -> usize {
+        self.t.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.t.is_empty()
+    }
+}
+
+/// The solution for a single output (i.e. the state at index `output`) at each of its observation times
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputSolution<T: Scalar> {
+    pub output: usize,
+    pub t: Vec<T>,
+    pub values: Vec<T>,
+}
+
+/// A set of observation time grids, one for each output of the model (e.g. plasma concentrations sampled densely, and urine amounts sampled sparsely).
+///
+/// [Self::solve] steps the solver once over the union of all the grids, using dense output to interpolate the solution at each time,
+/// and returns the solution of each output only at its own times.
+#[derive(Clone, Debug)]
+pub struct ObservationSchedule<T: Scalar> {
+    outputs: Vec<usize>,
+    times: Vec<Vec<T>>,
+}
+
+impl<T: Scalar> Default for ObservationSchedule<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> ObservationSchedule<T> {
+    pub fn new() -> Self {
+        Self {
+            outputs: Vec::new(),
+            times: Vec::new(),
+        }
+    }
+
+    /// Add the observation times `t` (which must be sorted) for the state at index `output`
+    pub fn add_output(&mut self, output: usize, t: Vec<T>) -> Result<(), PSError> {
+        if t.windows(2).any(|w| w[0] > w[1]) {
+            return Err(PSError::Other {
+                e: format!("observation times for output {} must be sorted", output),
+            });
+        }
+        self.outputs.push(output);
+        self.times.push(t);
+        Ok(())
+    }
+
+    pub fn outputs(&self) -> &[usize] {
+        &self.outputs
+    }
+
+    /// The union of the observation times of all the outputs, sorted and without duplicates
+    pub fn all_times(&self) -> Vec<T> {
+        let mut times = self.times.iter().flatten().cloned().collect::<Vec<_>>();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times.dedup();
+        times
+    }
+
+    /// Solve `problem` up to the last observation time of any output, returning the solution of each output at its own observation times,
+    /// in the order the outputs were added. The observation times must not be before the initial time of the problem.
+    pub fn solve<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<Vec<OutputSolution<T>>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let nstates = problem.eqn.rhs().nstates();
+        if let Some(&output) = self.outputs.iter().find(|&&o| o >= nstates) {
+            return Err(PSError::DimensionMismatch {
+                name: "output index".to_string(),
+                expected: nstates,
+                found: output,
+            });
+        }
+        let times = self.all_times();
+        let y = solver.solve_dense(problem, &times)?.y;
+        let ret = self
+            .outputs
+            .iter()
+            .zip(self.times.iter())
+            .map(|(&output, t)| {
+                // both sets of times are sorted, so a single pass over the union finds the index of each time
+                let mut j = 0;
+                let values = t
+                    .iter()
+                    .map(|ti| {
+                        while times[j] != *ti {
+                            j += 1;
+                        }
+                        y[j][output].clone()
+                    })
+                    .collect();
+                OutputSolution {
+                    output,
+                    t: t.clone(),
+                    values,
+                }
+            })
+            .collect();
+        Ok(ret)
+    }
+}
+
+/// As [OdeSolverMethod::solve_dense], but also returns the sensitivities of the solution with respect to the parameters at each time (which requires a problem with sensitivities)
+#[allow(clippy::type_complexity)]
+pub(crate) fn solve_dense_sens<Eqn, S>(
+    solver: &mut S,
+    problem: &OdeSolverProblem<Eqn>,
+    t_eval: &[Eqn::T],
+) -> Result<(Vec<Eqn::V>, Vec<Vec<Eqn::V>>), PSError>
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    S: OdeSolverMethod<Eqn>,
+{
+    if problem.eqn_sens.is_none() {
+        return Err(PSError::Other {
+            e: "problem must be created with sensitivities".to_string(),
+        });
+    }
+    if t_eval.first().is_some_and(|t| *t < problem.t0) {
+        return Err(PSError::Other {
+            e: "output times must not be before the initial time".to_string(),
+        });
+    }
+    let state = OdeSolverState::new(problem, solver)?;
+    solver.set_problem(state, problem);
+    let mut y = Vec::with_capacity(t_eval.len());
+    let mut s = Vec::with_capacity(t_eval.len());
+    for t in t_eval.iter().cloned() {
+        while solver.state().unwrap().t < t {
+            solver.step()?;
+        }
+        let state = solver.state().unwrap();
+        if state.t == t {
+            y.push(state.y.clone());
+            s.push(state.s.clone());
+        } else {
+            y.push(solver.interpolate(t.clone())?);
+            s.push(solver.interpolate_sens(t)?);
+        }
+    }
+    Ok((y, s))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::{
+            output::ObservationSchedule, test_models::exponential_decay::exponential_decay_problem,
+        },
+        Bdf, OdeSolverMethod,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn test_observation_schedule() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let mut schedule = ObservationSchedule::new();
+        let dense = (0..=20).map(|i| 0.5 * i as f64).collect::<Vec<_>>();
+        schedule.add_output(0, dense.clone()).unwrap();
+        schedule.add_output(1, vec![2.0, 8.0, 12.0]).unwrap();
+        assert_eq!(schedule.all_times().len(), 22);
+        let soln = schedule.solve(&mut solver, &problem).unwrap();
+        assert_eq!(soln.len(), 2);
+        assert_eq!(soln[0].output, 0);
+        assert_eq!(soln[0].t, dense);
+        assert_eq!(soln[1].t, vec![2.0, 8.0, 12.0]);
+        for s in soln.iter() {
+            assert_eq!(s.t.len(), s.values.len());
+            for (&t, &y) in s.t.iter().zip(s.values.iter()) {
+                let expect = f64::exp(-0.1 * t);
+                assert!((y - expect).abs() < 1e-4, "t = {} y = {}", t, y);
+            }
+        }
+
+        assert!(schedule.add_output(0, vec![1.0, 0.0]).is_err());
+        schedule.add_output(2, vec![1.0]).unwrap();
+        assert!(schedule.solve(&mut solver, &problem).is_err());
+    }
+
+    #[test]
+    fn test_solve_trajectory() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let soln = solver.solve(&problem, 2.0).unwrap();
+        assert_eq!(soln.len(), solver.get_statistics().number_of_steps + 1);
+        assert_eq!(soln.t[0], 0.0);
+        assert_eq!(*soln.t.last().unwrap(), 2.0);
+        assert!(soln.t.windows(2).all(|w| w[0] < w[1]));
+        for (&t, y) in soln.t.iter().zip(soln.y.iter()) {
+            assert!(
+                (y[0] - f64::exp(-0.1 * t)).abs() < 1e-4,
+                "t = {} y = {}",
+                t,
+                y
+            );
+        }
+
+        let t_eval = [0.0, 0.5, 1.0, 4.0];
+        let soln = solver.solve_dense(&problem, &t_eval).unwrap();
+        assert_eq!(soln.t, t_eval.to_vec());
+        for (&t, y) in soln.t.iter().zip(soln.y.iter()) {
+            assert!(
+                (y[0] - f64::exp(-0.1 * t)).abs() < 1e-4,
+                "t = {} y = {}",
+                t,
+                y
+            );
+        }
+        assert!(solver.solve_dense(&problem, &[-1.0, 1.0]).is_err());
+
+        // the output times are in the direction of integration when integrating backward in time
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem.backward = true;
+        let t_eval = [-0.5, -1.0, -4.0];
+        let soln = solver.solve_dense(&problem, &t_eval).unwrap();
+        for (&t, y) in soln.t.iter().zip(soln.y.iter()) {
+            assert!(
+                (y[0] - f64::exp(-0.1 * t)).abs() < 1e-4,
+                "t = {} y = {}",
+                t,
+                y
+            );
+        }
+        assert!(solver.solve_dense(&problem, &[1.0]).is_err());
+    }
+}
@@ -0,0 +1,480 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, vector::VectorCommon,
+    DosingSchedule, InfusionEquations, Occasion, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    Vector,
+};
+
+/// A time-varying covariate (e.g. body weight), given by its value at a set of record times.
+/// The covariate is piecewise constant, with each value carried forward until the next record.
+#[derive(Clone, Debug)]
+pub struct Covariate<T: Scalar> {
+    pub name: String,
+    pub t: Vec<T>,
+    pub values: Vec<T>,
+}
+
+impl<T: Scalar> Covariate<T> {
+    /// Create a new covariate from its record times (which must be sorted) and values.
+    pub fn new(name: &str, t: Vec<T>, values: Vec<T>) -> Result<Self, PSError> {
+        if t.len() != values.len() || t.is_empty() {
+            return Err(PSError::Other {
+                e: format!(
+                    "covariate {} must have one value for each record time",
+                    name
+                ),
+            });
+        }
+        if t.windows(2).any(|w| w[0] > w[1]) {
+            return Err(PSError::Other {
+                e: format!("record times for covariate {} must be sorted", name),
+            });
+        }
+        Ok(Self {
+            name: name.to_string(),
+            t,
+            values,
+        })
+    }
+
+    /// A covariate that is constant for all time
+    pub fn constant(name: &str, value: T) -> Self {
+        Self {
+            name: name.to_string(),
+            t: vec![T::zero()],
+            values: vec![value],
+        }
+    }
+
+    /// The value of the covariate at time `t`, this is the value of the last record at or before `t`
+    /// (or the first record if `t` is before all the records).
+    pub fn value(&self, t: T) -> T {
+        let i = self.t.iter().take_while(|&ti| *ti <= t).count();
+
+        self.values[i.saturating_sub(1)].clone()
+    }
+}
+
+/// A single subject of a population, with their own parameters, dosing schedule, covariates and observation times.
+/// The subject's parameters can also vary between `occasions` (see [DosingSchedule::solve_with_occasions]), in which case `params`
+/// are used until the first occasion.
+#[derive(Clone, Debug)]
+pub struct Subject<V: Vector> {
+    pub id: String,
+    pub params: V,
+    pub schedule: DosingSchedule<V::T>,
+    pub covariates: Vec<Covariate<V::T>>,
+    pub occasions: Vec<Occasion<V>>,
+    pub t_eval: Vec<V::T>,
+}
+
+impl<V: Vector> Subject<V> {
+    pub fn new(id: &str, params: V, schedule: DosingSchedule<V::T>, t_eval: Vec<V::T>) -> Self {
+        Self {
+            id: id.to_string(),
+            params,
+            schedule,
+            covariates: Vec::new(),
+            occasions: Vec::new(),
+            t_eval,
+        }
+    }
+
+    pub fn add_covariate(&mut self, covariate: Covariate<V::T>) {
+        self.covariates.push(covariate);
+    }
+
+    /// Add an occasion starting at time `t`, from which the subject's parameters are `params`. Occasions must be added in time order.
+    pub fn add_occasion(&mut self, t: V::T, params: V) {
+        self.occasions.push(Occasion::new(t, params));
+    }
+
+    /// The value of the covariate with the given `name` at time `t`, if the subject has this covariate
+    pub fn covariate(&self, name: &str, t: V::T) -> Option<V::T> {
+        self.covariates
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.value(t))
+    }
+}
+
+/// The solution for a single subject at each of their observation times, along with the time after dose and number of doses
+/// given before each observation (see [DosingSchedule::time_after_dose] and [DosingSchedule::dose_count]).
+#[derive(Clone, Debug)]
+pub struct SubjectSolution<V: Vector> {
+    pub id: String,
+    pub t: Vec<V::T>,
+    pub y: Vec<V>,
+    pub time_after_dose: Vec<Option<V::T>>,
+    pub dose_count: Vec<usize>,
+}
+
+/// A single row of a [PopulationSolution] in long format, giving the value of the state at index `output` for subject `id` at time `t`
+#[derive(Clone, Debug, PartialEq)]
+pub struct PopulationRecord<T: Scalar> {
+    pub id: String,
+    pub t: T,
+    pub output: usize,
+    pub value: T,
+    pub time_after_dose: Option<T>,
+    pub dose_count: usize,
+}
+
+/// The solutions for each subject in a [Population], in the same order as the subjects
+#[derive(Clone, Debug)]
+pub struct PopulationSolution<V: Vector> {
+    pub subjects: Vec<SubjectSolution<V>>,
+}
+
+impl<V: Vector> PopulationSolution<V> {
+    /// Convert the solution into a tidy (long) format, with one record per subject, observation time and state
+    pub fn records(&self) -> Vec<PopulationRecord<V::T>> {
+        self.subjects
+            .iter()
+            .flat_map(|s| {
+                s.t.iter()
+                    .zip(s.y.iter())
+                    .enumerate()
+                    .flat_map(move |(i, (t, y))| {
+                        (0..y.len()).map(move |output| PopulationRecord {
+                            id: s.id.clone(),
+                            t: t.clone(),
+                            output,
+                            value: y[output].clone(),
+                            time_after_dose: s.time_after_dose[i].clone(),
+
+                            dose_count: s.dose_count[i],
+                        })
+                    })
+            })
+            .collect()
+    }
+}
+
+/// A covariate model, giving the parameters of a subject at a time `t` from their own parameters at that time (i.e. [Subject::params],
+/// or those of their current [Occasion]) and their covariates (see [Subject::covariate]).
+type CovariateModel<V> = Arc<dyn Fn(&Subject<V>, &V, <V as VectorCommon>::T) -> V + Send + Sync>;
+
+/// A population of subjects that share the same model equations, but each have their own parameters, dosing schedule, covariates and observation times.
+///
+/// If a covariate model is set using [Self::set_covariate_model], the parameters passed to the equations are calculated from each subject's
+/// parameters and covariates, and are updated at each of the covariate record times.
+#[derive(Clone)]
+pub struct Population<V: Vector> {
+    pub subjects: Vec<Subject<V>>,
+    covariate_model: Option<CovariateModel<V>>,
+}
+
+impl<V: Vector> fmt::Debug for Population<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Population")
+            .field("subjects", &self.subjects)
+            .field("covariate_model", &self.covariate_model.is_some())
+            .finish()
+    }
+}
+
+impl<V: Vector> Default for Population<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: Vector> Population<V> {
+    pub fn new() -> Self {
+        Self {
+            subjects: Vec::new(),
+            covariate_model: None,
+        }
+    }
+
+    pub fn add_subject(&mut self, subject: Subject<V>) {
+        self.subjects.push(subject);
+    }
+
+    /// Set the covariate model `f(subject, params, t)`, which returns the parameters of the equations for `subject` at time `t`, where `params`
+    /// are the subject's own parameters at that time (e.g. the typical values of the parameters, or their individual values), and their covariates
+    /// at `t` are given by [Subject::covariate]. The model is evaluated at the initial time, and at each covariate record and occasion time after it,
+    /// where the solver is stopped and restarted with the new parameters (see [DosingSchedule::solve_with_occasions]).
+    pub fn set_covariate_model<F>(&mut self, f: F)
+    where
+        F: Fn(&Subject<V>, &V, V::T) -> V + Send + Sync + 'static,
+    {
+        self.covariate_model = Some(Arc::new(f));
+    }
+
+    /// The parameters of `subject` at the initial time `t0`, and the occasions at which they change after it
+    fn subject_params(&self, subject: &Subject<V>, t0: V::T) -> (V, Vec<Occasion<V>>) {
+        // the subject's own parameters at time t
+        let own_params = |t: V::T| {
+            subject
+                .occasions
+                .iter()
+                .take_while(|o| o.t <= t)
+                .last()
+                .map_or(&subject.params, |o| &o.params)
+        };
+        let Some(model) = self.covariate_model.as_ref() else {
+            return (own_params(t0).clone(), subject.occasions.clone());
+        };
+        let mut times = subject
+            .occasions
+            .iter()
+            .map(|o| o.t.clone())
+            .chain(subject.covariates.iter().flat_map(|c| c.t.iter().cloned()))
+            .filter(|t| *t > t0)
+            .collect::<Vec<_>>();
+        times.sort_by(|a, b| a.total_cmp(b));
+        times.dedup();
+        let occasions = times
+            .into_iter()
+            .map(|t| Occasion::new(t.clone(), model(subject, own_params(t.clone()), t)))
+            .collect();
+        (model(subject, own_params(t0.clone()), t0), occasions)
+    }
+
+    /// Simulate each subject in turn using the equations in `problem`, by setting the parameters of the problem to the subject's
+    /// parameters and solving with their dosing schedule (see [DosingSchedule::solve]). The solver is reused between subjects.
+    /// On return the parameters of `problem` are set to those of the last subject.
+    pub fn simulate<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+    ) -> Result<PopulationSolution<V>, PSError>
+    where
+        Eqn: OdeEquations<T = V::T, V = V>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        let subjects = self
+            .subjects
+            .iter()
+            .map(|subject| self.simulate_subject(subject, solver, problem))
+            .collect::<Result<Vec<_>, _>>()?;
+        solver.take_state();
+        Ok(PopulationSolution { subjects })
+    }
+
+    /// Simulate the subjects in parallel using `nthreads` threads. As problems and solvers cannot be shared between threads,
+    /// `setup` is called once on each thread to create the solver and problem used to simulate the subjects on that thread.
+    /// The result is the same as [Self::simulate].
+    pub fn simulate_parallel<Eqn, S, F>(
+        &self,
+        nthreads: usize,
+        setup: F,
+    ) -> Result<PopulationSolution<V>, PSError>
+    where
+        Eqn: OdeEquations<T = V::T, V = V>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+        F: Fn() -> Result<(S, OdeSolverProblem<InfusionEquations<Eqn>>), PSError> + Sync,
+        V: Send + Sync,
+        V::T: Send + Sync,
+    {
+        let nthreads = nthreads.max(1);
+        let chunk_size = self.subjects.len().div_ceil(nthreads).max(1);
+        let setup = &setup;
+        let chunks = std::thread::scope(|scope| {
+            let handles = self
+                .subjects
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let (mut solver, mut problem) = setup()?;
+                        chunk
+                            .iter()
+                            .map(|subject| {
+                                self.simulate_subject(subject, &mut solver, &mut problem)
+                            })
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("population simulation thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        let mut subjects = Vec::with_capacity(self.subjects.len());
+        for chunk in chunks {
+            subjects.extend(chunk?);
+        }
+        Ok(PopulationSolution { subjects })
+    }
+
+    pub(crate) fn simulate_subject<Eqn, S>(
+        &self,
+        subject: &Subject<V>,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<InfusionEquations<Eqn>>,
+    ) -> Result<SubjectSolution<V>, PSError>
+    where
+        Eqn: OdeEquations<T = V::T, V = V>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<InfusionEquations<Eqn>>,
+    {
+        // release the previous problem so the parameters can be updated in place
+        solver.take_state();
+        let (params, occasions) = self.subject_params(subject, problem.t0.clone());
+        problem.set_params(params)?;
+        let y =
+            subject
+                .schedule
+                .solve_with_occasions(solver, problem, &subject.t_eval, &occasions)?;
+        let schedule = &subject.schedule;
+        Ok(SubjectSolution {
+            id: subject.id.clone(),
+            t: subject.t_eval.clone(),
+            y,
+            time_after_dose: subject
+                .t_eval
+                .iter()
+                .map(|t| schedule.time_after_dose(t.clone()))
+                .collect(),
+            dose_count: subject
+                .t_eval
+                .iter()
+                .map(|t| schedule.dose_count(t.clone()))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::{
+            population::{Covariate, Population, PopulationSolution, Subject},
+            test_models::exponential_decay::exponential_decay_problem,
+        },
+        Bdf, DosingSchedule, OdeEquations, Op, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // the elimination rate scales with body weight
+    fn weight_model(subject: &Subject<V>, params: &V, t: f64) -> V {
+        V::from_vec(vec![
+            params[0] * subject.covariate("weight", t).unwrap() / 70.0,
+        ])
+    }
+
+    // subjects with a typical elimination rate of 0.1, and a bolus dose at t = 1
+    fn population() -> Population<V> {
+        let mut population = Population::new();
+        for (i, weight) in [50.0, 70.0, 90.0].into_iter().enumerate() {
+            let mut schedule = DosingSchedule::new();
+            schedule.add_dose(1.0, 1.0 + i as f64, 0).unwrap();
+            let mut subject = Subject::new(
+                &format!("{}", i),
+                V::from_vec(vec![0.1]),
+                schedule,
+                vec![0.5, 1.0, 2.0, 4.0],
+            );
+            subject.add_covariate(Covariate::constant("weight", weight));
+            population.add_subject(subject);
+        }
+        population.set_covariate_model(weight_model);
+        population
+    }
+
+    fn expected(k: f64, dose: f64, t: f64) -> [f64; 2] {
+        let y0 = f64::exp(-k * t)
+            + if t > 1.0 {
+                dose * f64::exp(-k * (t - 1.0))
+            } else {
+                0.0
+            };
+        [y0, f64::exp(-k * t)]
+    }
+
+    fn check(soln: &PopulationSolution<V>) {
+        let population = population();
+        assert_eq!(soln.subjects.len(), 3);
+        for (i, (s, subject)) in soln
+            .subjects
+            .iter()
+            .zip(population.subjects.iter())
+            .enumerate()
+        {
+            assert_eq!(s.id, subject.id);
+            let k = 0.1 * subject.covariate("weight", 0.0).unwrap() / 70.0;
+            for (&t, y) in s.t.iter().zip(s.y.iter()) {
+                let expect = expected(k, 1.0 + i as f64, t);
+                y.assert_eq_st(&V::from_vec(expect.to_vec()), 1e-4);
+            }
+        }
+        let records = soln.records();
+        assert_eq!(records.len(), 3 * 4 * 2);
+        assert_eq!(records[2].id, "0");
+        assert_eq!(records[2].t, 1.0);
+        assert_eq!(records[2].output, 0);
+        // the dose at t = 1 is given after the observation at t = 1
+        assert_eq!(records[2].dose_count, 0);
+        assert_eq!(records[2].time_after_dose, None);
+        assert_eq!(records[4].t, 2.0);
+        assert_eq!(records[4].dose_count, 1);
+        assert_eq!(records[4].time_after_dose, Some(1.0));
+    }
+
+    #[test]
+    fn test_population_simulate() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let soln = population().simulate(&mut solver, &mut problem).unwrap();
+        check(&soln);
+        assert_eq!(problem.eqn.rhs().nparams(), 1);
+    }
+
+    #[test]
+    fn test_population_simulate_parallel() {
+        let soln = population()
+            .simulate_parallel(2, || {
+                let (problem, _soln) = exponential_decay_problem::<M>();
+                Ok((Bdf::default(), problem.with_infusions()?))
+            })
+            .unwrap();
+        check(&soln);
+    }
+
+    #[test]
+    fn test_population_time_varying_covariate() {
+        // the weight changes from 70 to 140 at t = 2, doubling the elimination rate
+        let mut subject = Subject::new(
+            "0",
+            V::from_vec(vec![0.1]),
+            DosingSchedule::new(),
+            vec![1.0, 2.0, 4.0],
+        );
+        subject.add_covariate(Covariate::new("weight", vec![0.0, 2.0], vec![70.0, 140.0]).unwrap());
+        let mut population = Population::new();
+        population.add_subject(subject);
+        population.set_covariate_model(weight_model);
+
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut problem = problem.with_infusions().unwrap();
+        let mut solver = Bdf::default();
+        let soln = population.simulate(&mut solver, &mut problem).unwrap();
+        let y = &soln.subjects[0].y;
+        for (yi, expect) in
+            y.iter()
+                .zip([f64::exp(-0.1), f64::exp(-0.2), f64::exp(-0.2 - 0.2 * 2.0)])
+        {
+            yi.assert_eq_st(&V::from_vec(vec![expect, expect]), 1e-4);
+        }
+        // the parameters of the last occasion are left on the problem
+        assert_eq!(problem.eqn.rhs().nparams(), 1);
+    }
+
+    #[test]
+    fn test_covariate() {
+        let c = Covariate::new("weight", vec![0.0, 10.0], vec![70.0, 75.0]).unwrap();
+        assert_eq!(c.value(-1.0), 70.0);
+        assert_eq!(c.value(5.0), 70.0);
+        assert_eq!(c.value(10.0), 75.0);
+        assert!(Covariate::new("weight", vec![10.0, 0.0], vec![70.0, 75.0]).is_err());
+    }
+}
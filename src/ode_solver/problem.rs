@@ -1,7 +1,104 @@
+use num_traits::{abs, One, Zero};
 use std::rc::Rc;
 
 use crate::errors::PSError;
-use crate::{vector::Vector, ConstantOp, LinearOp, NonLinearOp, OdeEquations, SensEquations};
+use crate::linear_solver::kind::LinearSolverKind;
+use crate::matrix::default_solver::DefaultSolver;
+use crate::nonlinear_solver::convergence::ConvergenceCriterion;
+use crate::nonlinear_solver::kind::NonLinearSolverKind;
+use crate::ode_solver::equations::{InvariantEquations, RootEquations};
+use crate::{
+    vector::Vector, ConstantOp, Constraints, IndexType, LinearOp, NonLinearOp, OdeEquations, Op,
+    Scalar, SensEquations,
+};
+
+/// How the step size is chosen by the ODE solvers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepControl<T: Scalar> {
+    /// The step size is adapted to keep the estimated local error within the given tolerances (the default).
+    Adaptive,
+    /// Every step is taken with the given constant step size and no error control is done, the step is only
+    /// shortened to stop exactly at a stop time. If the nonlinear solver fails to converge the step is not retried
+    /// with a smaller step size, instead [PSError::FixedStepNonlinearSolverFailure] is returned.
+    /// Only supported by solvers with the [crate::SolverCapabilities::fixed_step] capability.
+    Fixed(T),
+}
+
+/// When the jacobian of the right-hand side is re-evaluated by [crate::Bdf] (similar to `CVodeSetJacEvalFrequency` and
+/// `CVodeSetDeltaGammaMaxLSetup` in CVODE). Between evaluations the nonlinear solver uses a modified Newton iteration, i.e.
+/// the iteration matrix `M - c J` is refactorised with the stored jacobian `J` whenever `c = h alpha` changes, but `J` itself
+/// is only recalculated according to this policy. The jacobian is always re-evaluated after the nonlinear solver fails to
+/// converge, before the step size is reduced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JacobianUpdatePolicy<T: Scalar> {
+    /// Re-evaluate and refactorise at every Newton iteration (i.e. a full Newton iteration)
+    EveryIteration,
+    /// Re-evaluate at the start of every step
+    EveryStep,
+    /// Only re-evaluate when the nonlinear solver fails to converge (the default)
+    OnConvergenceFailure,
+    /// Re-evaluate once the given number of steps have been taken since the last evaluation
+    AfterSteps(usize),
+    /// Re-evaluate when `c` has changed by more than the given fraction (e.g. 0.3) since the last evaluation
+    OnStepSizeChange(T),
+}
+
+/// Options for the nonlinear solve and step size control used by the implicit ODE solvers.
+#[derive(Clone, Debug)]
+pub struct OdeSolverOptions<T: Scalar> {
+    /// Maximum number of Newton iterations per step. If `None`, the default for the solver is used
+    /// (4 for [crate::Bdf], 10 for [crate::Sdirk] and [crate::FixedLowOrder]).
+    pub max_nonlinear_solver_iterations: Option<IndexType>,
+    /// Safety factor applied to the optimal step size factor calculated from the error estimate (default 0.9).
+    pub safety_factor: T,
+    /// The Newton iteration is considered to be diverging if the ratio of successive update norms
+    /// (i.e. the convergence rate) is larger than this value (default 1.0).
+    pub max_convergence_rate: T,
+    /// How the step size is chosen (default [StepControl::Adaptive]).
+    pub step_control: StepControl<T>,
+    /// If set, [crate::OdeSolverMethod::solve] and [crate::OdeSolverMethod::solve_dense] also estimate the global error of the
+    /// solution at each output time, by solving the problem a second time with both tolerances divided by this factor and taking
+    /// the difference between the two solutions (see [crate::OdeSolverTrajectory::global_error]). Default `None`.
+    pub global_error_factor: Option<T>,
+    /// Size of the diagonal blocks of block preconditioners (e.g. [crate::BlockJacobiPreconditioner]) used by an iterative linear solver.
+    /// If `None`, the block size given to the preconditioner is used. Default `None`.
+    pub preconditioner_block_size: Option<IndexType>,
+    /// The linear solver used by the default linear solver of the matrix type to solve the linear systems of the implicit solvers
+    /// (see [LinearSolverKind]). If `None`, the default for the matrix type is used. Default `None`.
+    pub linear_solver: Option<LinearSolverKind>,
+    /// The nonlinear solver used to calculate consistent initial conditions when the problem has a mass matrix
+    /// (see [NonLinearSolverKind]). Default [NonLinearSolverKind::Newton].
+    pub initialisation_solver: NonLinearSolverKind,
+    /// When the jacobian is re-evaluated (see [JacobianUpdatePolicy]). Default [JacobianUpdatePolicy::OnConvergenceFailure].
+    pub jacobian_update: JacobianUpdatePolicy<T>,
+    /// If set, [crate::Bdf] uses the convergence rate of the Newton iteration on the last step to control the step size: assuming that
+    /// the rate is proportional to the step size, an increase in step size is limited so that the predicted rate stays below this
+    /// fraction of [Self::max_convergence_rate], and after a convergence failure the step size is reduced by more than the usual
+    /// factor of 0.3 if needed to bring the predicted rate below it. Default `None`.
+    pub convergence_rate_step_limit: Option<T>,
+    /// The test used by the nonlinear solvers to decide whether the iteration has converged (see [ConvergenceCriterion]).
+    /// Default [ConvergenceCriterion::UpdateNorm].
+    pub convergence_criterion: ConvergenceCriterion,
+}
+
+impl<T: Scalar> Default for OdeSolverOptions<T> {
+    fn default() -> Self {
+        Self {
+            max_nonlinear_solver_iterations: None,
+            safety_factor: T::cast(0.9),
+            max_convergence_rate: T::cast(1.0),
+            step_control: StepControl::Adaptive,
+            global_error_factor: None,
+            preconditioner_block_size: None,
+            linear_solver: None,
+            initialisation_solver: NonLinearSolverKind::Newton,
+            jacobian_update: JacobianUpdatePolicy::OnConvergenceFailure,
+            convergence_rate_step_limit: None,
+            convergence_criterion: ConvergenceCriterion::UpdateNorm,
+        }
+    }
+}
+
 pub struct OdeSolverProblem<Eqn: OdeEquations> {
     pub eqn: Rc<Eqn>,
     pub rtol: Eqn::T,
@@ -10,6 +107,24 @@ pub struct OdeSolverProblem<Eqn: OdeEquations> {
     pub h0: Eqn::T,
     pub eqn_sens: Option<Rc<SensEquations<Eqn>>>,
     pub sens_error_control: bool,
+    pub options: OdeSolverOptions<Eqn::T>,
+    /// Optional bound on the absolute value of each component of the solution, the solve is stopped with
+    /// [PSError::SolutionBoundExceeded] if this is exceeded (or the solution becomes NaN).
+    pub max_abs_state: Option<Rc<Eqn::V>>,
+    /// Integrate backward in time from `t0` (i.e. with a negative step size), for example to solve a terminal-value or adjoint
+    /// problem. Only supported by solvers with the [crate::SolverCapabilities::backward] capability.
+    pub backward: bool,
+    /// Known times of discontinuities in the equations or the solution (e.g. dosing events or switching inputs), in any order.
+    /// [crate::OdeSolverMethod::solve], [crate::OdeSolverMethod::solve_dense] and [crate::OdeSolverMethod::solve_dense_with_breakpoints]
+    /// stop the solver exactly at each breakpoint and restart it from there, so the solver never steps over a discontinuity.
+    pub breakpoints: Vec<Eqn::T>,
+    /// Sizes of the diagonal blocks of the jacobian, in order, if the equations are made up of uncoupled blocks of states
+    /// (e.g. independent compartments or subjects). The default linear solver for nalgebra matrices then factorises each block
+    /// independently (see [crate::BlockDiagonalLU]).
+    pub block_sizes: Option<Vec<IndexType>>,
+    /// Optional lower and upper bounds on each component of the solution, which [crate::Bdf] enforces during the nonlinear solve
+    /// at each step (see [Self::with_constraints]).
+    pub constraints: Option<Rc<Constraints<Eqn::V>>>,
 }
 
 // impl clone
@@ -17,22 +132,28 @@ impl<Eqn: OdeEquations> Clone for OdeSolverProblem<Eqn> {
     fn clone(&self) -> Self {
         Self {
             eqn: self.eqn.clone(),
-            rtol: self.rtol,
+            rtol: self.rtol.clone(),
             atol: self.atol.clone(),
-            t0: self.t0,
-            h0: self.h0,
+            t0: self.t0.clone(),
+            h0: self.h0.clone(),
             eqn_sens: self.eqn_sens.clone(),
             sens_error_control: self.sens_error_control,
+            options: self.options.clone(),
+            max_abs_state: self.max_abs_state.clone(),
+            backward: self.backward,
+            breakpoints: self.breakpoints.clone(),
+            block_sizes: self.block_sizes.clone(),
+            constraints: self.constraints.clone(),
         }
     }
 }
 
 impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
     pub fn default_rtol() -> Eqn::T {
-        Eqn::T::from(1e-6)
+        Eqn::T::cast(1e-6)
     }
     pub fn default_atol(nstates: usize) -> Eqn::V {
-        Eqn::V::from_element(nstates, Eqn::T::from(1e-6))
+        Eqn::V::from_element(nstates, Eqn::T::cast(1e-6))
     }
     pub fn new(
         eqn: Eqn,
@@ -43,6 +164,7 @@ impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
         with_sensitivity: bool,
         sens_error_control: bool,
     ) -> Result<Self, PSError> {
+        Self::check_dimensions(&eqn, &atol)?;
         let eqn = Rc::new(eqn);
         let atol = Rc::new(atol);
         let mass_has_sens = if let Some(mass) = eqn.mass() {
@@ -67,14 +189,255 @@ impl<Eqn: OdeEquations> OdeSolverProblem<Eqn> {
             h0,
             eqn_sens,
             sens_error_control,
+            options: OdeSolverOptions::default(),
+            max_abs_state: None,
+            backward: false,
+            breakpoints: Vec::new(),
+            block_sizes: None,
+            constraints: None,
         })
     }
 
+    /// The direction of integration in time, `-1` if integrating backward (see [Self::backward]) and `1` otherwise.
+    /// The step size of the solver state always has this sign.
+    pub fn direction(&self) -> Eqn::T {
+        if self.backward {
+            -Eqn::T::one()
+        } else {
+            Eqn::T::one()
+        }
+    }
+
+    /// The breakpoints (see [Self::breakpoints]) strictly between `t0` and `t1`, sorted in the direction of integration.
+    pub fn breakpoints_between(&self, t0: Eqn::T, t1: Eqn::T) -> Vec<Eqn::T> {
+        let direction = self.direction();
+        let mut ret = self
+            .breakpoints
+            .iter()
+            .filter(|&t| {
+                (t.clone() - t0.clone()) * direction.clone() > Eqn::T::zero()
+                    && (t1.clone() - t.clone()) * direction.clone() > Eqn::T::zero()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        ret.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        ret.dedup();
+        if self.backward {
+            ret.reverse();
+        }
+        ret
+    }
+
+    /// Check that the dimensions of the equations and absolute tolerance are consistent with the number of states
+    /// given by the right-hand side, and that all the equations agree on the number of parameters.
+    fn check_dimensions(eqn: &Eqn, atol: &Eqn::V) -> Result<(), PSError> {
+        let check = |name: &str, expected: usize, found: usize| {
+            if expected != found {
+                Err(PSError::DimensionMismatch {
+                    name: name.to_string(),
+                    expected,
+                    found,
+                })
+            } else {
+                Ok(())
+            }
+        };
+        let nstates = eqn.rhs().nstates();
+        let nparams = eqn.rhs().nparams();
+        check("right-hand side output", nstates, eqn.rhs().nout())?;
+        check("initial condition", nstates, eqn.init().nout())?;
+        check(
+            "initial condition parameters",
+            nparams,
+            eqn.init().nparams(),
+        )?;
+        if let Some(mass) = eqn.mass() {
+            check("mass matrix input", nstates, mass.nstates())?;
+            check("mass matrix output", nstates, mass.nout())?;
+            check("mass matrix parameters", nparams, mass.nparams())?;
+        }
+        if let Some(root) = eqn.root() {
+            check("root function input", nstates, root.nstates())?;
+            check("root function parameters", nparams, root.nparams())?;
+        }
+        if atol.len() != nstates {
+            return Err(PSError::AbsoluteToleranceLengthMismatch);
+        }
+        Ok(())
+    }
+
+    /// Check that the solution `y` at time `t` is within the bound given by [Self::max_abs_state] (if set),
+    /// returning an error containing the offending components if not.
+    pub fn check_state_bound(&self, y: &Eqn::V, t: Eqn::T) -> Result<(), PSError> {
+        let bound = match self.max_abs_state.as_ref() {
+            Some(bound) => bound,
+            None => return Ok(()),
+        };
+        let indices = (0..y.len())
+            .filter(|&i| y[i].is_nan() || abs(y[i].clone()) > bound[i])
+            .collect::<Vec<_>>();
+        if indices.is_empty() {
+            Ok(())
+        } else {
+            Err(PSError::SolutionBoundExceeded {
+                t: t.to_string(),
+                indices,
+            })
+        }
+    }
+
+    /// Constrain each component of the solution to lie between `lower` and `upper` (use infinite bounds for unconstrained components),
+    /// for example a lower bound of zero to keep concentrations non-negative. During the nonlinear solve at each step, the Newton
+    /// iterates are clipped and damped to stay within the bounds, and the step is rejected (and retried with a smaller step size) if the
+    /// constraints cannot be satisfied (see [Constraints]). Only used by [crate::Bdf] with the [crate::NewtonNonlinearSolver].
+    pub fn with_constraints(mut self, lower: Eqn::V, upper: Eqn::V) -> Result<Self, PSError> {
+        let constraints = Constraints::new(lower, upper)?;
+        if constraints.len() != self.eqn.rhs().nstates() {
+            return Err(PSError::InvalidConstraints);
+        }
+        self.constraints = Some(Rc::new(constraints));
+        Ok(self)
+    }
+
+    /// Project the solution `y` at time `t` onto the invariants of the equations (see [crate::InvariantEquations]), using the
+    /// tolerances of the problem. This does nothing if the equations have no invariants.
+    pub fn project(&self, y: &mut Eqn::V, t: Eqn::T) -> Result<(), PSError> {
+        self.eqn
+            .project_inplace(y, t, &self.atol, self.rtol.clone())
+    }
+
+    /// Set the parameters of the equations. This requires that no other references to the equations exist,
+    /// so any solver using this problem must release it first (see [crate::OdeSolverMethod::take_state]).
     pub fn set_params(&mut self, p: Eqn::V) -> Result<(), PSError> {
-        let eqn = Rc::get_mut(&mut self.eqn).ok_or(PSError::MutableReferenceError)?;
-        eqn.set_params(p);
+        let nparams = self.eqn.rhs().nparams();
+        if p.len() != nparams {
+            return Err(PSError::ParameterLengthMismatch {
+                expected: nparams,
+                found: p.len(),
+            });
+        }
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        let with_sensitivity = self.eqn_sens.take().is_some();
+        if let Some(eqn) = Rc::get_mut(&mut self.eqn) {
+            eqn.set_params(p);
+        } else {
+            if with_sensitivity {
+                self.eqn_sens = Some(Rc::new(SensEquations::new(&self.eqn)));
+            }
+            return Err(PSError::MutableReferenceError);
+        }
+        if with_sensitivity {
+            self.eqn_sens = Some(Rc::new(SensEquations::new(&self.eqn)));
+        }
         Ok(())
     }
+
+    /// Convert this problem into one that uses the root function `root` (see [RootEquations]) for event detection, replacing any
+    /// existing root function. The solver then stops with [crate::OdeSolverStopReason::RootFound] when any output of `root`
+    /// crosses zero, giving the index of that output and the time of the crossing. All the other settings of the problem are kept.
+    /// This requires that no other references to the equations exist (e.g. held by a solver).
+    pub fn with_root_fn<Root>(
+        self,
+        root: Root,
+    ) -> Result<OdeSolverProblem<RootEquations<Eqn, Root>>, PSError>
+    where
+        Root: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    {
+        let with_sensitivity = self.eqn_sens.is_some();
+        let Self {
+            eqn,
+            rtol,
+            atol,
+            t0,
+            h0,
+            eqn_sens,
+            sens_error_control,
+            options,
+            max_abs_state,
+            backward,
+            breakpoints,
+            block_sizes,
+            constraints,
+        } = self;
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        drop(eqn_sens);
+        let eqn = Rc::try_unwrap(eqn).map_err(|_| PSError::MutableReferenceError)?;
+        let atol = Rc::try_unwrap(atol).unwrap_or_else(|atol| atol.as_ref().clone());
+        let mut problem = OdeSolverProblem::new(
+            RootEquations::new(eqn, root),
+            rtol,
+            atol,
+            t0,
+            h0,
+            with_sensitivity,
+            sens_error_control,
+        )?;
+        problem.options = options;
+        problem.max_abs_state = max_abs_state;
+        problem.backward = backward;
+        problem.breakpoints = breakpoints;
+        problem.block_sizes = block_sizes;
+        problem.constraints = constraints;
+        Ok(problem)
+    }
+
+    /// Convert this problem into one whose solution is kept on the manifold `g(y, t) = 0`, where `g` is the invariant function `invariant`
+    /// (see [InvariantEquations]). `g` and its jacobian are given by the [NonLinearOp] `invariant`, e.g. a [crate::Closure] built from the
+    /// function and its jacobian action. The solution is projected onto the invariants after each step, so the problem can only be solved
+    /// by solvers with the [crate::SolverCapabilities::invariants] capability. All the other settings of the problem are kept.
+    /// This requires that no other references to the equations exist (e.g. held by a solver).
+    pub fn with_invariant<Inv>(
+        self,
+        invariant: Inv,
+    ) -> Result<OdeSolverProblem<InvariantEquations<Eqn, Inv>>, PSError>
+    where
+        Eqn::M: DefaultSolver,
+        Inv: NonLinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>,
+    {
+        let with_sensitivity = self.eqn_sens.is_some();
+        let Self {
+            eqn,
+            rtol,
+            atol,
+            t0,
+            h0,
+            eqn_sens,
+            sens_error_control,
+            options,
+            max_abs_state,
+            backward,
+            breakpoints,
+            block_sizes,
+            constraints,
+        } = self;
+        // the sensitivity equations hold a reference to the equations, so are rebuilt afterwards
+        drop(eqn_sens);
+        let eqn = Rc::try_unwrap(eqn).map_err(|_| PSError::MutableReferenceError)?;
+        let atol = Rc::try_unwrap(atol).unwrap_or_else(|atol| atol.as_ref().clone());
+        if invariant.nstates() != eqn.rhs().nstates() {
+            return Err(PSError::DimensionMismatch {
+                name: "invariant function input".to_string(),
+                expected: eqn.rhs().nstates(),
+                found: invariant.nstates(),
+            });
+        }
+        let mut problem = OdeSolverProblem::new(
+            InvariantEquations::new(eqn, invariant),
+            rtol,
+            atol,
+            t0,
+            h0,
+            with_sensitivity,
+            sens_error_control,
+        )?;
+        problem.options = options;
+        problem.max_abs_state = max_abs_state;
+        problem.backward = backward;
+        problem.breakpoints = breakpoints;
+        problem.block_sizes = block_sizes;
+        problem.constraints = constraints;
+        Ok(problem)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -91,7 +454,7 @@ pub struct OdeSolverSolution<V: Vector> {
 impl<V: Vector> OdeSolverSolution<V> {
     pub fn push(&mut self, state: V, t: V::T) {
         // find the index to insert the new point keeping the times sorted
-        let index = self.get_index(t);
+        let index = self.get_index(t.clone());
         // insert the new point at that index
         self.solution_points
             .insert(index, OdeSolverSolutionPoint { state, t });
@@ -104,10 +467,15 @@ impl<V: Vector> OdeSolverSolution<V> {
     }
     pub fn push_sens(&mut self, state: V, t: V::T, sens: &[V]) {
         // find the index to insert the new point keeping the times sorted
-        let index = self.get_index(t);
+        let index = self.get_index(t.clone());
         // insert the new point at that index
-        self.solution_points
-            .insert(index, OdeSolverSolutionPoint { state, t });
+        self.solution_points.insert(
+            index,
+            OdeSolverSolutionPoint {
+                state,
+                t: t.clone(),
+            },
+        );
         // if the sensitivity solution is not initialized, initialize it
         if self.sens_solution_points.is_none() {
             self.sens_solution_points = Some(vec![vec![]; sens.len()]);
@@ -118,7 +486,7 @@ impl<V: Vector> OdeSolverSolution<V> {
                 index,
                 OdeSolverSolutionPoint {
                     state: s.clone(),
-                    t,
+                    t: t.clone(),
                 },
             );
         }
@@ -133,3 +501,64 @@ impl<V: Vector> Default for OdeSolverSolution<V> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        errors::PSError,
+        ode_solver::{
+            equations::OdeSolverEquations,
+            test_models::exponential_decay::exponential_decay_problem,
+        },
+        Closure, ConstantClosure, OdeSolverProblem, UnitCallable,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_problem_dimension_mismatch() {
+        let p = Rc::new(V::from_vec(vec![0.1]));
+        let rhs = Closure::<M, _, _>::new(
+            |x: &V, p: &V, _t, y: &mut V| y.copy_from(&(x * -p[0])),
+            |_x: &V, p: &V, _t, v: &V, y: &mut V| y.copy_from(&(v * -p[0])),
+            2,
+            2,
+            p.clone(),
+        );
+        let init = ConstantClosure::<M, _>::new(|_p: &V, _t| V::from_element(3, 1.0), p.clone());
+        let eqn = OdeSolverEquations::<M, _, _, UnitCallable<M>, UnitCallable<M>>::new(
+            Rc::new(rhs),
+            None,
+            None,
+            Rc::new(init),
+            p,
+        );
+        let ret =
+            OdeSolverProblem::new(eqn, 1e-6, V::from_element(2, 1e-6), 0.0, 1.0, false, false);
+        match ret {
+            Err(PSError::DimensionMismatch {
+                expected, found, ..
+            }) => {
+                assert_eq!(expected, 2);
+                assert_eq!(found, 3);
+            }
+            _ => panic!("expected a dimension mismatch error"),
+        }
+    }
+
+    #[test]
+    fn test_problem_set_params_length_mismatch() {
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        let ret = problem.set_params(V::from_vec(vec![0.1, 0.2]));
+        assert!(matches!(
+            ret,
+            Err(PSError::ParameterLengthMismatch {
+                expected: 1,
+                found: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,720 @@
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+use std::rc::Rc;
+
+use crate::errors::PSError;
+use crate::matrix::MatrixRef;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::vector::VectorRef;
+use crate::LinearSolver;
+use crate::NewtonNonlinearSolver;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::{
+    nonlinear_solver::NonLinearSolver, op::radau::RadauCallable, scalar::compensated_add,
+    solver::SolverProblem, DenseMatrix, LinearOp, NonLinearOp, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, Op, Scalar, SolverCapabilities, Vector, VectorViewMut,
+};
+
+use super::bdf::BdfStatistics;
+
+/// The 3-stage, order 5, Radau IIA fully implicit Runge-Kutta method (RADAU5), suitable for very stiff problems and DAEs (i.e. with a singular mass matrix).
+///
+/// The coupled stage equations are solved using a simplified Newton iteration ([NewtonNonlinearSolver]) on the stage increments transformed by the
+/// eigenvectors of the Runge-Kutta matrix, so that the jacobian of the nonlinear system decouples into a real `n x n` block and the real form of
+/// a complex `n x n` block (see [RadauCallable]). The error estimate and step size control follow
+/// Hairer, E., & Wanner, G. (1996). Solving Ordinary Differential Equations II: Stiff and Differential-Algebraic Problems.
+///
+/// Dense output is provided by the collocation polynomial of the last step, which is also used to predict the stages of the next step.
+///
+/// Restrictions:
+/// - Sensitivities are not supported.
+pub struct Radau<M, Eqn, LS>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    LS: LinearSolver<RadauCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    problem: Option<OdeSolverProblem<Eqn>>,
+    nonlinear_solver: NewtonNonlinearSolver<RadauCallable<Eqn>, LS>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    diff: M,
+    beta: M,
+    dd: Eqn::V,
+    z: Vec<Eqn::V>,
+    w: Eqn::V,
+    f0: Eqn::V,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<M, Eqn, LS> Radau<M, Eqn, LS>
+where
+    LS: LinearSolver<RadauCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    const NEWTON_MAXITER: usize = 7;
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+    const ORDER: usize = 5;
+
+    pub fn new(linear_solver: LS) -> Self {
+        let mut nonlinear_solver = NewtonNonlinearSolver::new(linear_solver);
+        // set max iterations for nonlinear solver
+        nonlinear_solver.set_max_iter(Self::NEWTON_MAXITER);
+
+        // the collocation polynomial of each stage increment, u(theta) = y0 + sum_i L_i(theta) Z_i,
+        // where L_i is the lagrange polynomial with L_i(0) = 0 and L_i(c_j) = delta_ij
+        let sqrt6 = 6.0_f64.sqrt();
+        let c = [(4.0 - sqrt6) / 10.0, (4.0 + sqrt6) / 10.0, 1.0];
+        let mut beta = M::zeros(3, 3);
+        for i in 0..3 {
+            let (cj, ck) = (c[(i + 1) % 3], c[(i + 2) % 3]);
+            let den = c[i] * (c[i] - cj) * (c[i] - ck);
+            beta[(i, 0)] = Eqn::T::cast(cj * ck / den);
+            beta[(i, 1)] = Eqn::T::cast(-(cj + ck) / den);
+            beta[(i, 2)] = Eqn::T::cast(1.0 / den);
+        }
+
+        // coefficients of the embedded error estimate
+        let dd = Eqn::V::from_vec(vec![
+            Eqn::T::cast(-(13.0 + 7.0 * sqrt6) / 3.0),
+            Eqn::T::cast((-13.0 + 7.0 * sqrt6) / 3.0),
+            Eqn::T::cast(-1.0 / 3.0),
+        ]);
+
+        let n = 1;
+        Self {
+            problem: None,
+            nonlinear_solver,
+            state: None,
+            diff: M::zeros(n, 3),
+            beta,
+            dd,
+            z: vec![<Eqn::V as Vector>::zeros(n); 3],
+            w: <Eqn::V as Vector>::zeros(3 * n),
+            f0: <Eqn::V as Vector>::zeros(n),
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    fn interpolate_beta_function(theta: Eqn::T, beta: &M) -> Eqn::V {
+        let poly_order = beta.ncols();
+        let s_star = beta.nrows();
+        let mut thetav = Vec::with_capacity(poly_order);
+        thetav.push(theta.clone());
+        for i in 1..poly_order {
+            thetav.push(theta.clone() * thetav[i - 1].clone());
+        }
+        // beta_poly = beta * thetav
+        let thetav = Eqn::V::from_vec(thetav);
+        let mut beta_f = <Eqn::V as Vector>::zeros(s_star);
+        beta.gemv(Eqn::T::one(), &thetav, Eqn::T::zero(), &mut beta_f);
+        beta_f
+    }
+
+    // predict the transformed stage increments for a step of size h by extrapolating the
+    // collocation polynomial of the last step (this is zero if there is no previous step)
+    fn predict_stages(&mut self, h: Eqn::T) {
+        let state = self.state.as_ref().unwrap();
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            self.w.fill(Eqn::T::zero());
+            return;
+        }
+        let op = &self.nonlinear_solver.problem().f;
+        let beta_1 = Self::interpolate_beta_function(Eqn::T::one(), &self.beta);
+        for i in 0..3 {
+            let theta = Eqn::T::one() + op.c()[i].clone() * h.clone() / dt.clone();
+            let beta_f = Self::interpolate_beta_function(theta, &self.beta) - &beta_1;
+            self.diff
+                .gemv(Eqn::T::one(), &beta_f, Eqn::T::zero(), &mut self.z[i]);
+        }
+        op.transformed_variables(&self.z, &mut self.w);
+    }
+
+    // solve (gamma * M - h * J) x = b, using the first diagonal block of the factorised jacobian
+    fn solve_error_estimate(&self, b: &mut Eqn::V) -> Result<(), PSError> {
+        let n = b.len();
+        let mut x = <Eqn::V as Vector>::zeros(3 * n);
+        for i in 0..n {
+            x[i] = b[i].clone();
+        }
+        self.nonlinear_solver.solve_linearised_in_place(&mut x)?;
+        for i in 0..n {
+            b[i] = x[i].clone();
+        }
+        Ok(())
+    }
+
+    // error = (gamma * M - h * J)^-1 (h * f(y) + M * (dd * Z))
+    fn error_estimate(
+        &self,
+        y: &Eqn::V,
+        t: Eqn::T,
+        h: Eqn::T,
+        mdz: &Eqn::V,
+        error: &mut Eqn::V,
+    ) -> Result<(), PSError> {
+        let eqn = &self.problem.as_ref().unwrap().eqn;
+        eqn.rhs().call_inplace(y, t, error);
+        error.axpy(Eqn::T::one(), mdz, h);
+        self.solve_error_estimate(error)
+    }
+}
+
+impl<M, Eqn, LS> OdeSolverMethod<Eqn> for Radau<M, Eqn, LS>
+where
+    LS: LinearSolver<RadauCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        Self::ORDER
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: true,
+            singular_mass_matrix: true,
+            roots: true,
+            sensitivities: false,
+            stiff: true,
+            max_order: Self::ORDER,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.root_finder = None;
+        self.nonlinear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // the nonlinear system is three times the size of the ode, so stack the absolute tolerances
+        let nstates = state.y.len();
+        let callable = Rc::new(RadauCallable::new(problem));
+        callable.set_h(state.h.clone());
+        callable.set_phi(&state.y);
+        let mut atol = <Eqn::V as Vector>::zeros(3 * nstates);
+        for i in 0..3 * nstates {
+            atol[i] = problem.atol[i % nstates].clone();
+        }
+        let nonlinear_problem = SolverProblem::new(callable, Rc::new(atol), problem.rtol.clone());
+        let max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::NEWTON_MAXITER);
+        self.nonlinear_solver.set_max_iter(max_iter);
+        self.nonlinear_solver
+            .set_max_rate(problem.options.max_convergence_rate.clone());
+        self.nonlinear_solver.set_problem(&nonlinear_problem);
+
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        self.diff = M::zeros(nstates, 3);
+        self.z = vec![<Eqn::V as Vector>::zeros(nstates); 3];
+        self.w = <Eqn::V as Vector>::zeros(3 * nstates);
+        self.f0 = <Eqn::V as Vector>::zeros(nstates);
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let n = self.state.as_ref().unwrap().y.len();
+        let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut mdz = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the last collocation polynomial and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            self.old_t = self.state.as_ref().unwrap().t.clone();
+            self.t_compensation = Eqn::T::zero();
+        }
+
+        // the stages are all relative to the start of the step
+        {
+            let state = self.state.as_ref().unwrap();
+            self.nonlinear_solver.problem().f.set_phi(&state.y);
+            self.problem.as_ref().unwrap().eqn.rhs().call_inplace(
+                &state.y,
+                state.t.clone(),
+                &mut self.f0,
+            );
+        }
+
+        let mut updated_jacobian = false;
+        let mut step_rejected = false;
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // loop until step is accepted
+        loop {
+            let t0 = self.state.as_ref().unwrap().t.clone();
+            let h = self.state.as_ref().unwrap().h.clone();
+            self.predict_stages(h.clone());
+
+            // if we're attempting the step again, then we need to reset the jacobian
+            // as h has changed or jacobian needs to be recalculated
+            if step_rejected || updated_jacobian {
+                self.nonlinear_solver.reset_jacobian(&self.w, t0.clone());
+            }
+
+            let solve_result = self
+                .nonlinear_solver
+                .solve_in_place(&mut self.w, t0.clone());
+            self.statistics.number_of_nonlinear_solver_iterations += self.nonlinear_solver.niter();
+
+            // handle solve failure
+            if solve_result.is_err() {
+                self.statistics.number_of_nonlinear_solver_fails += 1;
+                if !updated_jacobian {
+                    // newton iteration did not converge, so update jacobian and try again
+                    self.nonlinear_solver.problem().f.set_jacobian_is_stale();
+                    updated_jacobian = true;
+                } else {
+                    // newton iteration did not converge and jacobian has been updated, so we reduce step size and try again
+                    let state = self.state.as_mut().unwrap();
+                    state.h *= Eqn::T::cast(0.3);
+
+                    // if step size too small, then fail
+                    if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                        return Err(PSError::StepSizeTooSmall {
+                            t: state.t.to_string(),
+                        });
+                    }
+
+                    // update h for new step size
+                    self.nonlinear_solver.problem().f.set_h(state.h.clone());
+                    step_rejected = true;
+                }
+                // try again....
+                continue;
+            }
+
+            // recover the stage increments, the solution is the last stage
+            self.nonlinear_solver
+                .problem()
+                .f
+                .stage_increments(&self.w, &mut self.z);
+            for i in 0..3 {
+                self.diff.column_mut(i).copy_from(&self.z[i]);
+            }
+            let state = self.state.as_ref().unwrap();
+            y1.copy_from(&state.y);
+            y1.axpy(Eqn::T::one(), &self.z[2], Eqn::T::one());
+
+            // compute error estimate, M * (dd * Z)
+            self.diff
+                .gemv(Eqn::T::one(), &self.dd, Eqn::T::zero(), &mut error);
+            match self.problem.as_ref().unwrap().eqn.mass() {
+                Some(mass) => mass.call_inplace(&error, t0.clone(), &mut mdz),
+                None => mdz.copy_from(&error),
+            }
+            error.copy_from(&self.f0);
+            error.axpy(Eqn::T::one(), &mdz, h.clone());
+            self.solve_error_estimate(&mut error)?;
+            let atol = self.problem.as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem.as_ref().unwrap().rtol.clone();
+            let mut error_norm = error.squared_norm(&y1, atol, rtol.clone());
+
+            // for the first step or after a rejected step, the error estimate is improved by an additional evaluation
+            // to filter out the stiff components
+            if error_norm > Eqn::T::one() && (self.statistics.number_of_steps == 0 || step_rejected)
+            {
+                let mut y_err = self.state.as_ref().unwrap().y.clone();
+                y_err.axpy(Eqn::T::one(), &error, Eqn::T::one());
+                self.error_estimate(&y_err, t0, h, &mdz, &mut error)?;
+                error_norm = error.squared_norm(&y1, atol, rtol);
+            }
+
+            // adjust step size based on error, the error estimate is of order 4
+            let maxiter = self.nonlinear_solver.max_iter() as f64;
+            let niter = self.nonlinear_solver.niter() as f64;
+            let safety_factor: f64 = self.problem().unwrap().options.safety_factor.to_f64();
+            let safety =
+                Eqn::T::cast(safety_factor * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
+            let mut factor = safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / 4.0));
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // update h for new step size
+            self.nonlinear_solver.problem().f.set_h(state.h.clone());
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+            step_rejected = true;
+        }
+
+        //setup jacobian for next step (h was changed so jacobian needs to be recalculated)
+        self.nonlinear_solver.reset_jacobian(&self.w, t1.clone());
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        let dt = t1.clone() - state.t.clone();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+
+        // the derivative at the end of the step is the derivative of the collocation polynomial
+        let three = Eqn::T::cast(3.0);
+        let two = Eqn::T::cast(2.0);
+        let dbeta = Eqn::V::from_vec(
+            (0..3)
+                .map(|i| {
+                    self.beta[(i, 0)].clone()
+                        + two.clone() * self.beta[(i, 1)].clone()
+                        + three.clone() * self.beta[(i, 2)].clone()
+                })
+                .collect(),
+        );
+        self.diff
+            .gemv(Eqn::T::one() / dt, &dbeta, Eqn::T::zero(), &mut state.dy);
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_linear_solver_setups =
+            self.nonlinear_solver.problem().f.number_of_jac_evals();
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.nonlinear_solver.linear_solver_statistics();
+        self.statistics.nonlinear_solver = self.nonlinear_solver.get_statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt;
+
+        // ret = old_y + sum_{i=0}^{2} beta[i] * diff[:, i]
+        let beta_f = Self::interpolate_beta_function(theta, &self.beta);
+        let mut ret = self.old_y.clone();
+        self.diff
+            .gemv(Eqn::T::one(), &beta_f, Eqn::T::one(), &mut ret);
+        Ok(ret)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<M, Eqn, LS> StepChecks<Eqn> for Radau<M, Eqn, LS>
+where
+    LS: LinearSolver<RadauCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        let state = self.state.as_mut().unwrap();
+        state.h *= factor;
+        self.nonlinear_solver.problem().f.set_h(state.h.clone());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        nonlinear_solver::NonLinearSolver,
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem,
+                robertson::robertson,
+                robertson_ode::robertson_ode,
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        scale, NalgebraLU, OdeEquations, OdeSolverMethod, OdeSolverState, Op, Radau, Vector,
+    };
+
+    use num_traits::abs;
+    use std::rc::Rc;
+
+    type M = nalgebra::DMatrix<f64>;
+    #[test]
+    fn radau_no_set_problem() {
+        test_no_set_problem::<M, _>(Radau::<M, _, _>::new(NalgebraLU::default()));
+    }
+    #[test]
+    fn radau_state_mut() {
+        test_state_mut::<M, _>(Radau::<M, _, _>::new(NalgebraLU::default()));
+    }
+    #[test]
+    fn radau_test_interpolate() {
+        test_interpolate::<M, _>(Radau::<M, _, _>::new(NalgebraLU::default()));
+    }
+
+    // error in the stage increments of a second step of size h, predicted from the collocation polynomial of a first step of
+    // size h, for dy/dt = -0.1 y
+    fn stage_prediction_error(h: f64) -> f64 {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = h;
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        assert_eq!(s.state().unwrap().t, h);
+        let y1 = s.state().unwrap().y.clone();
+        s.predict_stages(h);
+        let c = *s.nonlinear_solver.problem().f.c();
+        (0..3)
+            .map(|i| {
+                let expect = &y1 * scale((-0.1 * c[i] * h).exp() - 1.0);
+                (&s.z[i] - expect).norm()
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_radau_error_estimate_and_stage_prediction() {
+        // dy/dt = -a y with a = 1e6 is so stiff that a first step of h = 0.1 is far outside the stability region of an
+        // explicit method, the error estimate is filtered by (gamma M - h J)^-1 so that the step is accepted
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        problem
+            .set_params(nalgebra::DVector::from_vec(vec![1e6]))
+            .unwrap();
+        problem.rtol = 1e-4;
+        problem.atol = Rc::new(nalgebra::DVector::from_element(2, 1e-4));
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let mut state = OdeSolverState::new(&problem, &s).unwrap();
+        state.h = 0.1;
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        assert_eq!(s.get_statistics().number_of_error_test_failures, 0);
+        assert_eq!(s.state().unwrap().t, 0.1);
+        s.state()
+            .unwrap()
+            .y
+            .assert_eq_st(&nalgebra::DVector::zeros(2), 1e-4);
+
+        // the stages predicted by extrapolating the collocation polynomial (of stage order 3) have an error of O(h^4)
+        let ratio = stage_prediction_error(1.0) / stage_prediction_error(0.5);
+        assert!(ratio > 12.0 && ratio < 20.0, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn test_radau_nalgebra_exponential_decay() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 10
+        number_of_steps: 9
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 18
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.07647244913317301
+        final_step_size: 1.415884276293923
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 65
+        number_of_jac_muls: 2
+        number_of_matrix_evals: 1
+        "###);
+    }
+
+    #[test]
+    fn test_radau_nalgebra_exponential_decay_algebraic() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_with_algebraic_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[test]
+    fn test_radau_nalgebra_robertson() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = robertson::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 158
+        number_of_steps: 129
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 650
+        number_of_nonlinear_solver_fails: 28
+        initial_step_size: 0.022903742606203592
+        final_step_size: 40311670277.085
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 1915
+        number_of_jac_muls: 69
+        number_of_matrix_evals: 23
+        "###);
+    }
+
+    #[test]
+    fn test_radau_nalgebra_robertson_ode() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = robertson_ode::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 158
+        number_of_steps: 129
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 647
+        number_of_nonlinear_solver_fails: 28
+        initial_step_size: 0.021618278333847298
+        final_step_size: 38883390480.22723
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_radau() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_radau() {
+        let mut s = Radau::<M, _, _>::new(NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
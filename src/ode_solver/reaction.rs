@@ -0,0 +1,710 @@
+use std::{cell::RefCell, ops::Index, rc::Rc};
+
+use num_traits::Zero;
+use rand::Rng;
+use rand_distr::{Distribution, Poisson};
+
+use crate::{
+    errors::PSError, scalar::Scalar, Matrix, NonLinearOp, OdeBuilder, OdeEquations,
+    OdeSolverMethod, OdeSolverProblem, OdeSolverState, OdeSolverStopReason, Op, Vector,
+};
+
+/// A single reaction of a [ReactionNetwork], given by the species consumed and produced (as `(species index, stoichiometric coefficient)` pairs),
+/// with a mass-action rate constant given by the parameter at index `rate_index`.
+#[derive(Clone, Debug)]
+pub struct Reaction {
+    pub reactants: Vec<(usize, u32)>,
+    pub products: Vec<(usize, u32)>,
+    pub rate_index: usize,
+}
+
+impl Reaction {
+    /// The net change in the number of each species when the reaction fires, as `(species index, change)` pairs
+    pub fn net_change(&self) -> Vec<(usize, i64)> {
+        let mut change: Vec<(usize, i64)> = Vec::new();
+        let terms = self
+            .reactants
+            .iter()
+            .map(|&(i, n)| (i, -(n as i64)))
+            .chain(self.products.iter().map(|&(i, n)| (i, n as i64)));
+        for (i, n) in terms {
+            match change.iter_mut().find(|(j, _)| *j == i) {
+                Some((_, m)) => *m += n,
+                None => change.push((i, n)),
+            }
+        }
+        change.retain(|&(_, n)| n != 0);
+        change
+    }
+
+    /// The deterministic mass-action rate `k prod_i x_i^n_i` for the concentrations `x` and parameters `p`
+    pub fn rate<T, X, P>(&self, x: &X, p: &P) -> T
+    where
+        T: Scalar,
+        X: Index<usize, Output = T> + ?Sized,
+        P: Index<usize, Output = T> + ?Sized,
+    {
+        self.reactants
+            .iter()
+            .fold(p[self.rate_index].clone(), |acc, &(i, n)| {
+                acc * x[i].clone().powi(n as i32)
+            })
+    }
+
+    /// The derivative of [Self::rate] with respect to the concentration of `species`
+    pub fn rate_derivative<T, X, P>(&self, x: &X, p: &P, species: usize) -> T
+    where
+        T: Scalar,
+        X: Index<usize, Output = T> + ?Sized,
+        P: Index<usize, Output = T> + ?Sized,
+    {
+        self.reactants
+            .iter()
+            .fold(p[self.rate_index].clone(), |acc, &(i, n)| {
+                if i == species {
+                    acc * T::cast(n as f64) * x[i].clone().powi(n as i32 - 1)
+                } else {
+                    acc * x[i].clone().powi(n as i32)
+                }
+            })
+    }
+
+    /// The stochastic propensity `k prod_i x_i (x_i - 1) ... (x_i - n_i + 1)` for the numbers of molecules `x` and parameters `p`
+    pub fn propensity<T, X, P>(&self, x: &X, p: &P) -> T
+    where
+        T: Scalar,
+        X: Index<usize, Output = T> + ?Sized,
+        P: Index<usize, Output = T> + ?Sized,
+    {
+        let mut a = p[self.rate_index].clone();
+        for &(i, n) in self.reactants.iter() {
+            for j in 0..n {
+                let f = x[i].clone() - T::cast(j as f64);
+                if f <= T::zero() {
+                    return T::zero();
+                }
+                a *= f;
+            }
+        }
+        a
+    }
+}
+
+/// A network of chemical reactions between a set of named species, with mass-action kinetics.
+///
+/// The same network (species, reactions and rate parameters) can be simulated deterministically, by building an ODE problem for the
+/// concentrations of the species using [Self::build_ode], or stochastically, by simulating the number of molecules of each species
+/// exactly using the Gillespie stochastic simulation algorithm in [Self::simulate_ssa].
+///
+/// # Example
+///
+/// ```rust
+/// use diffsol::{OdeBuilder, ReactionNetwork, Bdf, OdeSolverMethod};
+/// use rand::{rngs::StdRng, SeedableRng};
+/// type M = nalgebra::DMatrix<f64>;
+///
+/// // A -> B with rate constant p[0]
+/// let mut network = ReactionNetwork::new(&["A", "B"]);
+/// network.add_reaction(&[("A", 1)], &[("B", 1)], 0).unwrap();
+///
+/// let problem = network.build_ode::<M>(OdeBuilder::new().p([0.1]), &[100.0, 0.0]).unwrap();
+/// let mut solver = Bdf::default();
+/// let soln = solver.solve(&problem, 1.0).unwrap();
+/// let y = soln.y.last().unwrap();
+/// assert!((y[0] - 100.0 * f64::exp(-0.1)).abs() < 1e-2);
+///
+/// let mut rng = StdRng::seed_from_u64(0);
+/// let x = network.simulate_ssa(&[100.0, 0.0], &[0.1], 0.0, &[1.0], &mut rng).unwrap();
+/// assert_eq!(x[0][0] + x[0][1], 100.0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReactionNetwork {
+    species: Vec<String>,
+    reactions: Vec<Reaction>,
+}
+
+impl ReactionNetwork {
+    pub fn new(species: &[&str]) -> Self {
+        Self {
+            species: species.iter().map(|s| s.to_string()).collect(),
+            reactions: Vec::new(),
+        }
+    }
+
+    pub fn nspecies(&self) -> usize {
+        self.species.len()
+    }
+
+    pub fn species(&self) -> &[String] {
+        &self.species
+    }
+
+    pub fn reactions(&self) -> &[Reaction] {
+        &self.reactions
+    }
+
+    pub fn species_index(&self, name: &str) -> Option<usize> {
+        self.species.iter().position(|s| s == name)
+    }
+
+    /// The number of parameters needed for the rate constants of the reactions
+    pub fn nparams(&self) -> usize {
+        self.reactions
+            .iter()
+            .map(|r| r.rate_index + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Add a reaction consuming the `reactants` and creating the `products`, given as `(species name, stoichiometric coefficient)` pairs,
+    /// with a rate constant given by the parameter at index `rate_index`
+    pub fn add_reaction(
+        &mut self,
+        reactants: &[(&str, u32)],
+        products: &[(&str, u32)],
+        rate_index: usize,
+    ) -> Result<(), PSError> {
+        let lookup = |terms: &[(&str, u32)]| {
+            terms
+                .iter()
+                .map(|&(name, n)| {
+                    self.species_index(name)
+                        .map(|i| (i, n))
+                        .ok_or_else(|| PSError::Other {
+                            e: format!("unknown species {}", name),
+                        })
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let reaction = Reaction {
+            reactants: lookup(reactants)?,
+            products: lookup(products)?,
+            rate_index,
+        };
+        self.reactions.push(reaction);
+        Ok(())
+    }
+
+    /// The rate of change of the concentrations `x` for the parameters `p`
+    pub fn rhs<V: Vector>(&self, x: &V, p: &V, y: &mut V) {
+        self.rhs_subset(x, p, y, None);
+    }
+
+    /// The product of the jacobian of [Self::rhs] with the vector `v`
+    pub fn jac_mul<V: Vector>(&self, x: &V, p: &V, v: &V, y: &mut V) {
+        self.jac_mul_subset(x, p, v, y, None);
+    }
+
+    // the rate of change due to the reactions in `subset` (or all reactions if `None`)
+    fn rhs_subset<V: Vector>(&self, x: &V, p: &V, y: &mut V, subset: Option<&[bool]>) {
+        y.fill(V::T::zero());
+        for (j, reaction) in self.reactions.iter().enumerate() {
+            if subset.is_some_and(|s| !s[j]) {
+                continue;
+            }
+            let rate = reaction.rate(x, p);
+            for (i, n) in reaction.net_change() {
+                y[i] += V::T::cast(n as f64) * rate.clone();
+            }
+        }
+    }
+
+    fn jac_mul_subset<V: Vector>(&self, x: &V, p: &V, v: &V, y: &mut V, subset: Option<&[bool]>) {
+        y.fill(V::T::zero());
+        for (j, reaction) in self.reactions.iter().enumerate() {
+            if subset.is_some_and(|s| !s[j]) {
+                continue;
+            }
+            let mut drate = V::T::zero();
+            for &(k, _) in reaction.reactants.iter() {
+                drate += reaction.rate_derivative(x, p, k) * v[k].clone();
+            }
+            for (i, n) in reaction.net_change() {
+                y[i] += V::T::cast(n as f64) * drate.clone();
+            }
+        }
+    }
+
+    /// Build an ODE problem for the concentrations of the species, with initial concentrations `x0`. The parameters (i.e. the rate constants)
+    /// and any other settings are taken from `builder`.
+    pub fn build_ode<M: Matrix + 'static>(
+        &self,
+        builder: OdeBuilder,
+        x0: &[f64],
+    ) -> Result<OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>, PSError> {
+        if x0.len() != self.nspecies() {
+            return Err(PSError::DimensionMismatch {
+                name: "initial concentrations".to_string(),
+                expected: self.nspecies(),
+                found: x0.len(),
+            });
+        }
+        let rhs_network = self.clone();
+        let jac_network = self.clone();
+        let x0 = x0.iter().map(|&x| M::T::cast(x)).collect::<Vec<_>>();
+        let problem = builder.build_ode::<M, _, _, _>(
+            move |x: &M::V, p: &M::V, _t, y: &mut M::V| rhs_network.rhs(x, p, y),
+            move |x: &M::V, p: &M::V, _t, v: &M::V, y: &mut M::V| jac_network.jac_mul(x, p, v, y),
+            move |_p: &M::V, _t| M::V::from_vec(x0.clone()),
+        )?;
+        self.check_nparams(problem.eqn.rhs().nparams())?;
+        Ok(problem)
+    }
+
+    /// Build a [HybridProblem] for simulating the numbers of molecules of each species, partitioning the reactions into fast reactions, which are
+    /// integrated deterministically, and slow reactions, which are simulated stochastically. The rate constants and any other settings of the
+    /// ODE solver are taken from `builder`.
+    pub fn build_hybrid<M: Matrix + 'static>(
+        &self,
+        builder: OdeBuilder,
+    ) -> Result<HybridProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>, PSError> {
+        self.check_nparams(builder.params().len())?;
+        let params = builder
+            .params()
+            .iter()
+            .map(|&p| M::T::cast(p))
+            .collect::<Vec<_>>();
+        let fast = Rc::new(RefCell::new(vec![true; self.reactions.len()]));
+        let rhs_network = self.clone();
+        let jac_network = self.clone();
+        let rhs_fast = fast.clone();
+        let jac_fast = fast.clone();
+        let nspecies = self.nspecies();
+        // only the fast reactions contribute to the equations, the initial state is set by the simulation
+        let problem = builder.build_ode::<M, _, _, _>(
+            move |x: &M::V, p: &M::V, _t, y: &mut M::V| {
+                rhs_network.rhs_subset(x, p, y, Some(&rhs_fast.borrow()))
+            },
+            move |x: &M::V, p: &M::V, _t, v: &M::V, y: &mut M::V| {
+                jac_network.jac_mul_subset(x, p, v, y, Some(&jac_fast.borrow()))
+            },
+            move |_p: &M::V, _t| M::V::zeros(nspecies),
+        )?;
+        Ok(HybridProblem {
+            network: self.clone(),
+            problem,
+            params,
+            fast,
+        })
+    }
+
+    fn check_nparams(&self, nparams: usize) -> Result<(), PSError> {
+        if nparams < self.nparams() {
+            return Err(PSError::ParameterLengthMismatch {
+                expected: self.nparams(),
+                found: nparams,
+            });
+        }
+        Ok(())
+    }
+
+    /// Simulate the numbers of molecules of each species using the (exact) Gillespie stochastic simulation algorithm, starting from `x0` at time `t0`,
+    /// with rate constants `p`. Returns the number of molecules at each of the output times `t_eval` (which must be sorted and not before `t0`).
+    pub fn simulate_ssa<T: Scalar, R: Rng + ?Sized>(
+        &self,
+        x0: &[T],
+        p: &[T],
+        t0: T,
+        t_eval: &[T],
+        rng: &mut R,
+    ) -> Result<Vec<Vec<T>>, PSError> {
+        self.check_ssa(x0, p, t0.clone(), t_eval)?;
+        let changes = self
+            .reactions
+            .iter()
+            .map(|r| r.net_change())
+            .collect::<Vec<_>>();
+        let mut x = x0.to_vec();
+        let mut t = t0;
+        let mut propensities = vec![T::zero(); self.reactions.len()];
+        let mut ret = Vec::with_capacity(t_eval.len());
+        for t_out in t_eval.iter().cloned() {
+            loop {
+                for (a, r) in propensities.iter_mut().zip(self.reactions.iter()) {
+                    *a = r.propensity(x.as_slice(), p);
+                }
+                let a0 = propensities
+                    .iter()
+                    .fold(T::zero(), |acc, a| acc + a.clone());
+                if a0 <= T::zero() {
+                    // no more reactions can fire
+                    t = t_out.clone();
+                    break;
+                }
+                let u: f64 = rng.gen();
+                let tau = -T::cast((1.0 - u).ln()) / a0.clone();
+                if t.clone() + tau.clone() > t_out {
+                    // the exponential waiting time is memoryless, so the next reaction time can be resampled from t_out
+                    t = t_out.clone();
+                    break;
+                }
+                t += tau;
+                let target = a0 * T::cast(rng.gen::<f64>());
+                let mut sum = T::zero();
+                let mut fired = propensities.len() - 1;
+                for (j, a) in propensities.iter().enumerate() {
+                    sum += a.clone();
+                    if target < sum {
+                        fired = j;
+                        break;
+                    }
+                }
+                for &(i, n) in changes[fired].iter() {
+                    x[i] += T::cast(n as f64);
+                }
+            }
+            debug_assert!(t == t_out);
+            ret.push(x.clone());
+        }
+        Ok(ret)
+    }
+
+    pub(crate) fn check_ssa<T: Scalar>(
+        &self,
+        x0: &[T],
+        p: &[T],
+        t0: T,
+        t_eval: &[T],
+    ) -> Result<(), PSError> {
+        if x0.len() != self.nspecies() {
+            return Err(PSError::DimensionMismatch {
+                name: "initial numbers of molecules".to_string(),
+                expected: self.nspecies(),
+                found: x0.len(),
+            });
+        }
+        self.check_nparams(p.len())?;
+        if t_eval.windows(2).any(|w| w[0] > w[1]) || t_eval.first().is_some_and(|t| *t < t0) {
+            return Err(PSError::Other {
+                e: "output times must be sorted and not before the initial time".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Options for the hybrid simulation in [HybridProblem::simulate].
+#[derive(Clone, Debug)]
+pub struct HybridOptions<T: Scalar> {
+    /// The (maximum) length of each tau-leap of the slow reactions, the reactions are repartitioned at the start of each leap (default 0.01).
+    pub leap_interval: T,
+    /// A reaction is fast if it is expected to fire at least this many times during a leap (default 10).
+    pub min_firings: T,
+    /// A reaction is fast only if all of its reactants have at least this many molecules (default 100).
+    pub min_population: T,
+    /// The maximum number of times a leap is halved to avoid negative numbers of molecules before giving up (default 20).
+    pub max_halvings: usize,
+}
+
+impl<T: Scalar> Default for HybridOptions<T> {
+    fn default() -> Self {
+        Self {
+            leap_interval: T::cast(0.01),
+            min_firings: T::cast(10.0),
+            min_population: T::cast(100.0),
+            max_halvings: 20,
+        }
+    }
+}
+
+/// A hybrid deterministic / stochastic simulation of a [ReactionNetwork], built using [ReactionNetwork::build_hybrid].
+///
+/// The simulation proceeds in leaps of at most [HybridOptions::leap_interval]. At the start of each leap, the reactions are partitioned
+/// using the current numbers of molecules: a reaction is fast if it is expected to fire many times during the leap and all of its
+/// reactants are abundant, otherwise it is slow. Over the leap, the fast reactions are integrated as an ODE (using the mass-action rates) with
+/// the given solver, and the slow reactions fire a Poisson-distributed number of times (tau-leaping). Species that are not involved in any
+/// fast reaction are kept as whole numbers of molecules. If a leap would make the number of molecules of any species negative,
+/// the leap is halved and repeated.
+pub struct HybridProblem<Eqn: OdeEquations> {
+    network: ReactionNetwork,
+    problem: OdeSolverProblem<Eqn>,
+    params: Vec<Eqn::T>,
+    fast: Rc<RefCell<Vec<bool>>>,
+}
+
+impl<Eqn: OdeEquations> HybridProblem<Eqn> {
+    pub fn network(&self) -> &ReactionNetwork {
+        &self.network
+    }
+
+    pub fn problem(&self) -> &OdeSolverProblem<Eqn> {
+        &self.problem
+    }
+
+    /// The partition of the reactions used for the last leap, `true` for the fast reactions
+    pub fn fast_reactions(&self) -> Vec<bool> {
+        self.fast.borrow().clone()
+    }
+
+    /// Simulate the numbers of molecules of each species starting from `x0` at time `t0`, returning the numbers of molecules at each of the
+    /// output times `t_eval` (which must be sorted and not before `t0`).
+    pub fn simulate<S: OdeSolverMethod<Eqn>, R: Rng + ?Sized>(
+        &self,
+        solver: &mut S,
+        x0: &[Eqn::T],
+        t0: Eqn::T,
+        t_eval: &[Eqn::T],
+        options: &HybridOptions<Eqn::T>,
+        rng: &mut R,
+    ) -> Result<Vec<Vec<Eqn::T>>, PSError> {
+        self.network
+            .check_ssa(x0, &self.params, t0.clone(), t_eval)?;
+        if options.leap_interval <= Eqn::T::zero() {
+            return Err(PSError::Other {
+                e: "leap interval must be positive".to_string(),
+            });
+        }
+        let reactions = self.network.reactions();
+        let changes = reactions.iter().map(|r| r.net_change()).collect::<Vec<_>>();
+        let mut x = x0.to_vec();
+        let mut t = t0;
+        let mut propensities = vec![Eqn::T::zero(); reactions.len()];
+        let mut ret = Vec::with_capacity(t_eval.len());
+        for t_out in t_eval.iter().cloned() {
+            while t < t_out {
+                let mut t_next = if t.clone() + options.leap_interval.clone() < t_out {
+                    t.clone() + options.leap_interval.clone()
+                } else {
+                    t_out.clone()
+                };
+                for (a, r) in propensities.iter_mut().zip(reactions.iter()) {
+                    *a = r.propensity(x.as_slice(), &self.params);
+                }
+                let fast = reactions
+                    .iter()
+                    .zip(propensities.iter())
+                    .map(|(r, a)| {
+                        a.clone() * (t_next.clone() - t.clone()) >= options.min_firings
+                            && r.reactants
+                                .iter()
+                                .all(|&(i, _)| x[i] >= options.min_population)
+                    })
+                    .collect::<Vec<_>>();
+                // species that are only changed by slow reactions are discrete
+                for (i, xi) in x.iter_mut().enumerate() {
+                    let continuous = changes
+                        .iter()
+                        .zip(fast.iter())
+                        .any(|(c, &f)| f && c.iter().any(|&(k, _)| k == i));
+                    if !continuous {
+                        *xi = Eqn::T::cast(xi.to_f64().round());
+                    }
+                }
+                for (a, r) in propensities.iter_mut().zip(reactions.iter()) {
+                    *a = r.propensity(x.as_slice(), &self.params);
+                }
+                let any_fast = fast.iter().any(|&f| f);
+                *self.fast.borrow_mut() = fast;
+                let mut nhalvings = 0;
+                x = loop {
+                    let mut x_new = if any_fast {
+                        self.integrate(solver, &x, t.clone(), t_next.clone())?
+                    } else {
+                        x.clone()
+                    };
+                    let tau = t_next.clone() - t.clone();
+                    for (j, a) in propensities.iter().enumerate() {
+                        if self.fast.borrow()[j] || *a <= Eqn::T::zero() {
+                            continue;
+                        }
+                        let lambda: f64 = (a.clone() * tau.clone()).to_f64();
+                        let nfirings = Poisson::new(lambda)
+                            .map_err(|e| PSError::Other { e: e.to_string() })?
+                            .sample(rng);
+                        for &(i, n) in changes[j].iter() {
+                            x_new[i] += Eqn::T::cast(n as f64 * nfirings);
+                        }
+                    }
+                    if x_new.iter().all(|xi| *xi >= Eqn::T::zero()) {
+                        break x_new;
+                    }
+                    nhalvings += 1;
+                    if nhalvings > options.max_halvings {
+                        return Err(PSError::Other {
+                            e: format!(
+                                "number of molecules became negative after {} halvings of the leap at t = {}",
+                                options.max_halvings, t
+                            ),
+                        });
+                    }
+                    t_next = t.clone() + tau / Eqn::T::cast(2.0);
+                };
+                t = t_next;
+            }
+            ret.push(x.clone());
+        }
+        Ok(ret)
+    }
+
+    // integrate the fast reactions from `x` at time `t0` to time `t1`
+    fn integrate<S: OdeSolverMethod<Eqn>>(
+        &self,
+        solver: &mut S,
+        x: &[Eqn::T],
+        t0: Eqn::T,
+        t1: Eqn::T,
+    ) -> Result<Vec<Eqn::T>, PSError> {
+        let mut state = OdeSolverState::new_without_initialise(&self.problem);
+        state.y = Eqn::V::from_vec(x.to_vec());
+        state.t = t0.clone();
+        // there is no mass matrix, so the state is consistent once its time derivative is set
+        self.problem
+            .eqn
+            .rhs()
+            .call_inplace(&state.y, t0, &mut state.dy);
+        state.set_step_size(&self.problem, solver.order());
+        solver.set_problem(state, &self.problem);
+        solver.set_stop_time(t1)?;
+        while !matches!(solver.step()?, OdeSolverStopReason::TstopReached) {}
+        let state = solver.take_state().ok_or(PSError::StateNotSet)?;
+        Ok((0..state.y.len()).map(|i| state.y[i].clone()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        ode_solver::reaction::{HybridOptions, ReactionNetwork},
+        Bdf, OdeBuilder, OdeSolverMethod,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+
+    // reversible dimerisation 2A <-> B
+    fn dimerisation() -> ReactionNetwork {
+        let mut network = ReactionNetwork::new(&["A", "B"]);
+        network.add_reaction(&[("A", 2)], &[("B", 1)], 0).unwrap();
+        network.add_reaction(&[("B", 1)], &[("A", 2)], 1).unwrap();
+        network
+    }
+
+    #[test]
+    fn test_reaction_network_ode() {
+        let network = dimerisation();
+        assert_eq!(network.nparams(), 2);
+        assert_eq!(network.reactions()[0].net_change(), vec![(0, -2), (1, 1)]);
+        let problem = network
+            .build_ode::<M>(
+                OdeBuilder::new().p([0.5, 1.0]).rtol(1e-8).atol([1e-8]),
+                &[2.0, 0.0],
+            )
+            .unwrap();
+        let mut solver = Bdf::default();
+        let y = solver.solve(&problem, 50.0).unwrap().y.pop().unwrap();
+        // mass is conserved and at equilibrium k1 A^2 = k2 B
+        assert!((y[0] + 2.0 * y[1] - 2.0).abs() < 1e-6);
+        assert!((0.5 * y[0] * y[0] - y[1]).abs() < 1e-6);
+
+        assert!(network
+            .build_ode::<M>(OdeBuilder::new().p([0.5]), &[2.0, 0.0])
+            .is_err());
+        assert!(network
+            .build_ode::<M>(OdeBuilder::new().p([0.5, 1.0]), &[2.0])
+            .is_err());
+        let mut network = dimerisation();
+        assert!(network.add_reaction(&[("C", 1)], &[], 0).is_err());
+    }
+
+    #[test]
+    fn test_ssa_decay() {
+        // A -> 0 with rate k, the mean number of molecules is x0 exp(-k t)
+        let mut network = ReactionNetwork::new(&["A"]);
+        network.add_reaction(&[("A", 1)], &[], 0).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let t_eval = [0.5_f64, 1.0, 2.0];
+        let nruns = 500;
+        let mut mean = [0.0; 3];
+        for _ in 0..nruns {
+            let x = network
+                .simulate_ssa(&[100.0], &[0.5], 0.0, &t_eval, &mut rng)
+                .unwrap();
+            for (m, xi) in mean.iter_mut().zip(x.iter()) {
+                assert_eq!(xi[0], xi[0].round());
+                *m += xi[0] / nruns as f64;
+            }
+        }
+        for (&t, m) in t_eval.iter().zip(mean.iter()) {
+            let expect = 100.0 * f64::exp(-0.5 * t);
+            assert!((m - expect).abs() < 0.05 * expect, "t = {} mean = {}", t, m);
+        }
+
+        // molecules are conserved by the dimerisation reactions
+        let x = dimerisation()
+            .simulate_ssa(&[50.0, 0.0], &[0.01, 1.0], 0.0, &[10.0], &mut rng)
+            .unwrap();
+        assert_eq!(x[0][0] + 2.0 * x[0][1], 50.0);
+        assert!(network
+            .simulate_ssa(&[100.0], &[], 0.0, &t_eval, &mut rng)
+            .is_err());
+    }
+
+    #[test]
+    fn test_hybrid_simulation() {
+        // A -> B is fast, B -> C is slow
+        let mut network = ReactionNetwork::new(&["A", "B", "C"]);
+        network.add_reaction(&[("A", 1)], &[("B", 1)], 0).unwrap();
+        network.add_reaction(&[("B", 1)], &[("C", 1)], 1).unwrap();
+        let hybrid = network
+            .build_hybrid::<M>(OdeBuilder::new().p([1.0, 0.001]).rtol(1e-8).atol([1e-6]))
+            .unwrap();
+        let mut solver = Bdf::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let options = HybridOptions {
+            leap_interval: 0.05,
+            ..Default::default()
+        };
+        let nruns = 50;
+        let mut mean_c = 0.0;
+        for _ in 0..nruns {
+            let x = hybrid
+                .simulate(
+                    &mut solver,
+                    &[1e4, 0.0, 0.0],
+                    0.0,
+                    &[1.0],
+                    &options,
+                    &mut rng,
+                )
+                .unwrap();
+            let x = &x[0];
+            assert!((x[0] - 1e4 * f64::exp(-1.0)).abs() < 1.0);
+            assert!((x[0] + x[1] + x[2] - 1e4).abs() < 1e-2);
+            assert_eq!(x[2], x[2].round());
+            mean_c += x[2] / nruns as f64;
+        }
+        assert_eq!(hybrid.fast_reactions(), vec![true, false]);
+        // dC/dt = 0.001 B with B ~ 1e4 (1 - exp(-t)), so E[C(1)] ~ 10 exp(-1)
+        assert!(
+            (mean_c - 10.0 * f64::exp(-1.0)).abs() < 1.0,
+            "mean = {}",
+            mean_c
+        );
+    }
+
+    #[test]
+    fn test_hybrid_repartitioning() {
+        // A -> 0 starts fast, but becomes slow as A is depleted
+        let mut network = ReactionNetwork::new(&["A"]);
+        network.add_reaction(&[("A", 1)], &[], 0).unwrap();
+        let hybrid = network
+            .build_hybrid::<M>(OdeBuilder::new().p([1.0]))
+            .unwrap();
+        let mut solver = Bdf::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        let options = HybridOptions {
+            leap_interval: 0.1,
+            ..Default::default()
+        };
+        let x = hybrid
+            .simulate(&mut solver, &[1e4], 0.0, &[1.0, 10.0], &options, &mut rng)
+            .unwrap();
+        assert!((x[0][0] - 1e4 * f64::exp(-1.0)).abs() < 1.0);
+        assert_eq!(x[1][0], x[1][0].round());
+        assert!(x[1][0] >= 0.0 && x[1][0] < 10.0);
+        assert_eq!(hybrid.fast_reactions(), vec![false]);
+
+        assert!(network.build_hybrid::<M>(OdeBuilder::new()).is_err());
+    }
+}
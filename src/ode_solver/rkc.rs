@@ -0,0 +1,746 @@
+use num_traits::abs;
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+
+use crate::errors::PSError;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::{
+    scale, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op,
+    Scalar, SolverCapabilities, Vector,
+};
+
+use super::bdf::BdfStatistics;
+
+/// A second order Runge-Kutta-Chebyshev (RKC) method, a stabilized explicit method for mildly stiff problems
+/// whose jacobian has eigenvalues close to the negative real axis, such as the semi-discretisation of parabolic PDEs.
+///
+/// The number of stages `s` is chosen at each step so that the real stability interval of the method, which grows
+/// as `0.65 s^2`, covers `h` times the spectral radius of the jacobian. No linear solves are required;
+/// the spectral radius is estimated by a power iteration using the jacobian-vector product of the right-hand side,
+/// which is repeated every few steps and after each rejected step. If more than [Rkc::MAX_STAGES] stages would be required
+/// the step size is reduced instead.
+///
+/// The method and error estimate follow Sommeijer, Shampine and Verwer, "RKC: An explicit solver for parabolic PDEs",
+/// J. Comput. Appl. Math. 88 (1997), with the damping parameter `eps = 2/13`.
+///
+/// Restrictions:
+/// - The problem must not have a mass matrix or sensitivities.
+/// - The eigenvalues of the jacobian should lie close to the negative real axis, for problems with
+///   strongly oscillatory components an implicit method such as [crate::Bdf] or [crate::Sdirk] should be used instead.
+pub struct Rkc<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    problem: Option<OdeSolverProblem<Eqn>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    old_dy: Eqn::V,
+    stage_y: Eqn::V,
+    stage_y_prev: Eqn::V,
+    stage_y_prev2: Eqn::V,
+    stage_f: Eqn::V,
+    eigenvector: Option<Eqn::V>,
+    spectral_radius: Eqn::T,
+    steps_since_spectral_radius: usize,
+    number_of_stages: usize,
+    error_norm_old: Option<Eqn::T>,
+    h_old: Eqn::T,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<Eqn> Default for Rkc<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Eqn> Rkc<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    /// The maximum number of stages used in a single step
+    pub const MAX_STAGES: usize = 250;
+
+    // damping parameter of the chebyshev polynomials
+    const EPSILON: f64 = 2.0 / 13.0;
+
+    // the spectral radius estimate is multiplied by this factor to allow for the error in the power iteration
+    const SPECTRAL_RADIUS_SAFETY: f64 = 1.2;
+    const SPECTRAL_RADIUS_TOL: f64 = 0.01;
+    const MAX_POWER_ITERATIONS: usize = 50;
+    const STEPS_BETWEEN_SPECTRAL_RADIUS: usize = 25;
+
+    pub fn new() -> Self {
+        let n = 1;
+        Self {
+            problem: None,
+            state: None,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            old_dy: <Eqn::V as Vector>::zeros(n),
+            stage_y: <Eqn::V as Vector>::zeros(n),
+            stage_y_prev: <Eqn::V as Vector>::zeros(n),
+            stage_y_prev2: <Eqn::V as Vector>::zeros(n),
+            stage_f: <Eqn::V as Vector>::zeros(n),
+            eigenvector: None,
+            spectral_radius: Eqn::T::zero(),
+            steps_since_spectral_radius: 0,
+            number_of_stages: 0,
+            error_norm_old: None,
+            h_old: Eqn::T::zero(),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    /// The number of stages used in the last step
+    pub fn number_of_stages(&self) -> usize {
+        self.number_of_stages
+    }
+
+    /// The current estimate of the spectral radius of the jacobian (including a safety factor)
+    pub fn spectral_radius(&self) -> Eqn::T {
+        self.spectral_radius.clone()
+    }
+
+    /// Estimate the spectral radius of the jacobian at the current state using a power iteration,
+    /// warm-started from the dominant eigenvector found by the previous estimate
+    fn estimate_spectral_radius(&mut self) {
+        let state = self.state.as_ref().unwrap();
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs();
+        let n = state.y.len();
+
+        // the initial vector is an oscillating vector plus the (normalised) derivative, since the derivative is often
+        // dominated by the smooth (slowly decaying) modes. The derivative is scaled by 0.5 so the sum can never be zero
+        let mut v = match self.eigenvector.take() {
+            Some(v) => v,
+            None => {
+                let mut v = Eqn::V::from_vec(
+                    (0..n)
+                        .map(|i| {
+                            if i % 2 == 0 {
+                                Eqn::T::one()
+                            } else {
+                                -Eqn::T::one()
+                            }
+                        })
+                        .collect(),
+                );
+                let dy_norm = state.dy.norm();
+                if dy_norm > Eqn::T::zero() {
+                    v.axpy(Eqn::T::cast(0.5) / dy_norm, &state.dy, Eqn::T::one());
+                }
+                v
+            }
+        };
+
+        let mut jv = <Eqn::V as Vector>::zeros(n);
+        let mut rho = Eqn::T::zero();
+        for i in 0..Self::MAX_POWER_ITERATIONS {
+            let v_norm = v.norm();
+            v *= scale(Eqn::T::one() / v_norm);
+            rhs.jac_mul_inplace(&state.y, state.t.clone(), &v, &mut jv);
+            let new_rho = jv.norm();
+
+            // the jacobian is zero (in the direction of v), so there are no stiff components
+            if new_rho == Eqn::T::zero() {
+                rho = new_rho;
+                break;
+            }
+            let converged = i > 0
+                && abs(new_rho.clone() - rho)
+                    <= Eqn::T::cast(Self::SPECTRAL_RADIUS_TOL) * new_rho.clone();
+            rho = new_rho;
+            std::mem::swap(&mut v, &mut jv);
+            if converged {
+                break;
+            }
+        }
+        self.eigenvector = Some(v);
+        self.spectral_radius = Eqn::T::cast(Self::SPECTRAL_RADIUS_SAFETY) * rho;
+        self.steps_since_spectral_radius = 0;
+    }
+
+    /// The number of stages required for the step size `h` to be stable, the real stability interval
+    /// of the s-stage method is approximately `0.65 s^2`
+    fn stages_for_step(&self, h: Eqn::T) -> usize {
+        let h_rho: f64 = (h * self.spectral_radius.clone()).to_f64();
+        let s = 1.0 + (1.0 + 1.54 * h_rho).sqrt();
+        (s.floor() as usize).max(2)
+    }
+
+    /// Compute the coefficients `(mu, nu, mu_tilde, gamma_tilde, c)` of each stage `j = 0..=s`
+    /// of the s-stage RKC method from the (shifted) chebyshev polynomials `T_j(w0)` and their derivatives
+    #[allow(clippy::type_complexity)]
+    fn stage_coefficients(
+        s: usize,
+    ) -> (
+        Vec<Eqn::T>,
+        Vec<Eqn::T>,
+        Vec<Eqn::T>,
+        Vec<Eqn::T>,
+        Vec<Eqn::T>,
+    ) {
+        let one = Eqn::T::one();
+        let two = Eqn::T::cast(2.0);
+        let w0 = one.clone() + Eqn::T::cast(Self::EPSILON) / Eqn::T::cast((s * s) as f64);
+
+        // chebyshev polynomials and their first and second derivatives at w0
+        let mut t = vec![one.clone(), w0.clone()];
+        let mut dt = vec![Eqn::T::zero(), one.clone()];
+        let mut ddt = vec![Eqn::T::zero(), Eqn::T::zero()];
+        for j in 2..=s {
+            t.push(two.clone() * w0.clone() * t[j - 1].clone() - t[j - 2].clone());
+            dt.push(
+                two.clone() * t[j - 1].clone() + two.clone() * w0.clone() * dt[j - 1].clone()
+                    - dt[j - 2].clone(),
+            );
+            ddt.push(
+                Eqn::T::cast(4.0) * dt[j - 1].clone()
+                    + two.clone() * w0.clone() * ddt[j - 1].clone()
+                    - ddt[j - 2].clone(),
+            );
+        }
+        let w1 = dt[s].clone() / ddt[s].clone();
+
+        let mut b = vec![Eqn::T::zero(); s + 1];
+        for j in 2..=s {
+            b[j] = ddt[j].clone() / (dt[j].clone() * dt[j].clone());
+        }
+        b[0] = b[2].clone();
+        b[1] = b[2].clone();
+
+        let mut c = vec![Eqn::T::zero(); s + 1];
+        for j in 2..=s {
+            c[j] = w1.clone() * ddt[j].clone() / dt[j].clone();
+        }
+        c[1] = c[2].clone() / (Eqn::T::cast(4.0) * w0.clone());
+
+        let mut mu = vec![Eqn::T::zero(); s + 1];
+        let mut nu = vec![Eqn::T::zero(); s + 1];
+        let mut mu_tilde = vec![Eqn::T::zero(); s + 1];
+        let mut gamma_tilde = vec![Eqn::T::zero(); s + 1];
+        mu_tilde[1] = b[1].clone() * w1.clone();
+        for j in 2..=s {
+            mu[j] = two.clone() * b[j].clone() * w0.clone() / b[j - 1].clone();
+            nu[j] = -b[j].clone() / b[j - 2].clone();
+            mu_tilde[j] = two.clone() * b[j].clone() * w1.clone() / b[j - 1].clone();
+            gamma_tilde[j] =
+                -(one.clone() - b[j - 1].clone() * t[j - 1].clone()) * mu_tilde[j].clone();
+        }
+        (mu, nu, mu_tilde, gamma_tilde, c)
+    }
+
+    fn interpolate_hermite(
+        theta: Eqn::T,
+        u0: &Eqn::V,
+        u1: &Eqn::V,
+        hf0: &Eqn::V,
+        hf1: &Eqn::V,
+    ) -> Eqn::V {
+        u0 * scale(Eqn::T::cast(1.0) - theta.clone())
+            + u1 * scale(theta.clone())
+            + ((u1 - u0) * scale(Eqn::T::cast(1.0) - Eqn::T::cast(2.0) * theta.clone())
+                + hf0 * scale(theta.clone() - Eqn::T::cast(1.0))
+                + hf1 * scale(theta.clone()))
+                * scale(theta.clone() * (theta - Eqn::T::cast(1.0)))
+    }
+}
+
+impl<Eqn> OdeSolverMethod<Eqn> for Rkc<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        2
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: false,
+            singular_mass_matrix: false,
+            roots: true,
+            sensitivities: false,
+            stiff: false,
+            max_order: 2,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.root_finder = None;
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        let nstates = state.y.len();
+        self.stage_y = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_y_prev = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_y_prev2 = <Eqn::V as Vector>::zeros(nstates);
+        self.stage_f = <Eqn::V as Vector>::zeros(nstates);
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.old_dy = state.dy.clone();
+        self.eigenvector = None;
+        self.spectral_radius = Eqn::T::zero();
+        self.number_of_stages = 0;
+        self.error_norm_old = None;
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        self.estimate_spectral_radius();
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let rhs = self.problem.as_ref().unwrap().eqn.rhs().clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the derivative, the spectral radius and the accumulated roundoff in t are no longer valid
+        if self.is_state_mutated {
+            let state = self.state.as_mut().unwrap();
+            rhs.call_inplace(&state.y, state.t.clone(), &mut state.dy);
+            self.t_compensation = Eqn::T::zero();
+            self.error_norm_old = None;
+            self.estimate_spectral_radius();
+        } else if self.steps_since_spectral_radius >= Self::STEPS_BETWEEN_SPECTRAL_RADIUS {
+            self.estimate_spectral_radius();
+        }
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+        let mut h: Eqn::T;
+        let mut error_norm: Eqn::T;
+
+        // loop until step is accepted
+        loop {
+            // choose the number of stages for stability, reducing the step size if too many stages are required
+            let mut s = self.stages_for_step(self.state.as_ref().unwrap().h.clone());
+            if s > Self::MAX_STAGES {
+                s = Self::MAX_STAGES;
+                let max_h = Eqn::T::cast(((s - 1) * (s - 1) - 1) as f64)
+                    / (Eqn::T::cast(1.54) * self.spectral_radius.clone());
+                self.state.as_mut().unwrap().h = max_h;
+            }
+            self.number_of_stages = s;
+            let (mu, nu, mu_tilde, gamma_tilde, c) = Self::stage_coefficients(s);
+
+            let state = self.state.as_ref().unwrap();
+            let t0 = state.t.clone();
+            h = state.h.clone();
+
+            // first stage, Y_1 = y_n + mu_tilde_1 h F_0
+            self.stage_y_prev2.copy_from(&state.y);
+            self.stage_y_prev.copy_from(&state.y);
+            self.stage_y_prev
+                .axpy(mu_tilde[1].clone() * h.clone(), &state.dy, Eqn::T::one());
+
+            // Y_j = (1 - mu_j - nu_j) y_n + mu_j Y_{j-1} + nu_j Y_{j-2} + mu_tilde_j h F(Y_{j-1}) + gamma_tilde_j h F_0
+            for j in 2..=s {
+                rhs.call_inplace(
+                    &self.stage_y_prev,
+                    t0.clone() + c[j - 1].clone() * h.clone(),
+                    &mut self.stage_f,
+                );
+                self.stage_y.copy_from(&state.y);
+                self.stage_y.axpy(
+                    mu[j].clone(),
+                    &self.stage_y_prev,
+                    Eqn::T::one() - mu[j].clone() - nu[j].clone(),
+                );
+                self.stage_y
+                    .axpy(nu[j].clone(), &self.stage_y_prev2, Eqn::T::one());
+                self.stage_y.axpy(
+                    mu_tilde[j].clone() * h.clone(),
+                    &self.stage_f,
+                    Eqn::T::one(),
+                );
+                self.stage_y
+                    .axpy(gamma_tilde[j].clone() * h.clone(), &state.dy, Eqn::T::one());
+                std::mem::swap(&mut self.stage_y_prev2, &mut self.stage_y_prev);
+                std::mem::swap(&mut self.stage_y_prev, &mut self.stage_y);
+            }
+            y1.copy_from(&self.stage_y_prev);
+
+            // the derivative at the end of the step is needed for the error estimate, and is reused for the next step
+            rhs.call_inplace(&y1, t0 + h.clone(), &mut self.stage_f);
+
+            // error estimate, err = 0.8 (y_n - y_{n+1}) + 0.4 h (F_n + F_{n+1})
+            error.copy_from(&y1);
+            error.axpy(Eqn::T::cast(0.8), &state.y, Eqn::T::cast(-0.8));
+            error.axpy(Eqn::T::cast(0.4) * h.clone(), &state.dy, Eqn::T::one());
+            error.axpy(Eqn::T::cast(0.4) * h.clone(), &self.stage_f, Eqn::T::one());
+            let atol = self.problem.as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem.as_ref().unwrap().rtol.clone();
+            error_norm = error.squared_norm(&y1, atol, rtol);
+
+            // adjust step size based on error, the local error is O(h^3). After an accepted step that followed
+            // another accepted step the predictive controller of Gustafsson (as used by RKC) is used instead
+            let safety = self.problem.as_ref().unwrap().options.safety_factor.clone();
+            let mut factor = match self.error_norm_old.clone() {
+                Some(error_norm_old)
+                    if error_norm <= Eqn::T::one() && error_norm_old > Eqn::T::zero() =>
+                {
+                    safety
+                        * (h.clone() / self.h_old.clone())
+                        * error_norm_old.pow(Eqn::T::cast(0.5 / 3.0))
+                        * error_norm.clone().pow(Eqn::T::cast(-1.0 / 3.0))
+                }
+                _ => safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / 3.0)),
+            };
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break;
+            }
+            // step is rejected, the failure might be due to an underestimate of the spectral radius
+            // so this is recomputed before trying again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+            self.error_norm_old = None;
+            self.estimate_spectral_radius();
+        }
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+        std::mem::swap(&mut self.old_dy, &mut state.dy);
+        std::mem::swap(&mut self.stage_f, &mut state.dy);
+
+        self.is_state_mutated = false;
+        self.steps_since_spectral_radius += 1;
+        self.error_norm_old = Some(error_norm);
+        self.h_old = h;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt.clone();
+        let hf0 = self.old_dy.clone() * scale(dt.clone());
+        let hf1 = state.dy.clone() * scale(dt);
+        Ok(Self::interpolate_hermite(
+            theta,
+            &self.old_y,
+            &state.y,
+            &hf0,
+            &hf1,
+        ))
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<Eqn> StepChecks<Eqn> for Rkc<Eqn>
+where
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                heat1d::{heat1d_problem, heat1d_spectral_radius},
+            },
+            tests::{test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut},
+        },
+        vector::VectorRef,
+        OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op, Rkc,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn rkc_no_set_problem() {
+        test_no_set_problem::<M, _>(Rkc::default());
+    }
+    #[test]
+    fn rkc_state_mut() {
+        test_state_mut::<M, _>(Rkc::default());
+    }
+    #[test]
+    fn rkc_test_interpolate() {
+        test_interpolate::<M, _>(Rkc::default());
+    }
+
+    #[test]
+    fn rkc_test_spectral_radius_estimate() {
+        // the jacobian of dy/dt = -0.1 y is -0.1 I, so the power iteration is exact
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let mut s = Rkc::default();
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        assert!(abs(s.spectral_radius() - 1.2 * 0.1) < 1e-12);
+
+        // the estimate for the heat equation is an upper bound on the largest eigenvalue of the discrete laplacian, and the
+        // number of stages makes the step stable, i.e. h times the spectral radius is within the stability interval 0.65 s^2
+        for n in [10, 50, 100] {
+            let (problem, _soln) = heat1d_problem::<M>(n);
+            let mut s = Rkc::default();
+            let state = OdeSolverState::new(&problem, &s).unwrap();
+            s.set_problem(state, &problem);
+            let rho = heat1d_spectral_radius::<f64>(n);
+            assert!(
+                s.spectral_radius() > rho && s.spectral_radius() < 1.3 * rho,
+                "n = {}, spectral radius estimate {} vs {}",
+                n,
+                s.spectral_radius(),
+                rho
+            );
+            for h in [1e-4, 1e-3, 1e-2] {
+                let stages = s.stages_for_step(h) as f64;
+                assert!(0.65 * stages * stages >= h * s.spectral_radius());
+            }
+        }
+    }
+
+    fn check_stage_coefficients<Eqn: OdeEquations<T = f64>>(_problem: &OdeSolverProblem<Eqn>)
+    where
+        for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    {
+        // for dy/dt = 1 the stages are exact, Y_j = y_n + c_j h, and the last stage is the end of the step
+        for s in [2, 3, 10, 50, Rkc::<Eqn>::MAX_STAGES] {
+            let (mu, nu, mu_tilde, gamma_tilde, c) = Rkc::<Eqn>::stage_coefficients(s);
+            assert!(abs(c[s] - 1.0) < 1e-12, "c[s] = {}", c[s]);
+            assert!(abs(mu_tilde[1] - c[1]) < 1e-12);
+            for j in 2..=s {
+                let c_j = mu[j] * c[j - 1] + nu[j] * c[j - 2] + mu_tilde[j] + gamma_tilde[j];
+                assert!(abs(c_j - c[j]) < 1e-10, "s = {}, j = {}", s, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stage_coefficients_consistent() {
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        check_stage_coefficients(&problem);
+    }
+
+    #[test]
+    fn test_rkc_nalgebra_exponential_decay() {
+        let mut s = Rkc::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        // the method is only second order, so the global error accumulated over the (long) steps taken
+        // for this problem is larger than the default test tolerance based on rtol and atol
+        test_ode_solver(&mut s, &problem, soln, Some(1e-4), false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 45
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.005848035476425734
+        final_step_size: 0.23282853737631615
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 92
+        number_of_jac_muls: 4
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_rkc_nalgebra_heat1d() {
+        let n = 50;
+        let mut s = Rkc::default();
+        let (problem, soln) = heat1d_problem::<M>(n);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+
+        // the stages are increased to keep the step stable, rather than limiting the step size
+        assert!(s.number_of_stages() > 2);
+        let rho = heat1d_spectral_radius::<f64>(n);
+        assert!(
+            s.spectral_radius() > rho && s.spectral_radius() < 1.3 * rho,
+            "spectral radius estimate {} vs {}",
+            s.spectral_radius(),
+            rho
+        );
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 0
+        number_of_steps: 61
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0006395567586646338
+        final_step_size: 0.0039337931044644715
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 502
+        number_of_jac_muls: 6
+        number_of_matrix_evals: 0
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_rkc() {
+        let mut s = Rkc::default();
+        let (problem, soln) = heat1d_problem::<M>(20);
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_rkc() {
+        let mut s = Rkc::default();
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
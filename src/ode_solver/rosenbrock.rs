@@ -0,0 +1,669 @@
+use nalgebra::ComplexField;
+use num_traits::abs;
+use num_traits::One;
+use num_traits::Pow;
+use num_traits::Zero;
+use std::rc::Rc;
+
+use crate::errors::PSError;
+use crate::matrix::MatrixRef;
+use crate::ode_solver::method::{self, end_step, StepChecks};
+use crate::scalar::compensated_add;
+use crate::vector::VectorRef;
+use crate::LinearSolver;
+use crate::OdeSolverStopReason;
+use crate::RootFinder;
+use crate::RosenbrockTableau;
+use crate::{
+    op::sdirk::SdirkCallable, scale, solver::SolverProblem, DenseMatrix, LinearOp, MatrixView,
+    NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op, Scalar,
+    SolverCapabilities, Vector, VectorViewMut,
+};
+
+use super::bdf::BdfStatistics;
+
+/// A Rosenbrock (linearly implicit Runge-Kutta) method with an embedded pair for error control, for mildly stiff problems.
+/// The particular method is defined by the [RosenbrockTableau] used to create the solver, e.g. [RosenbrockTableau::rodas3] or [RosenbrockTableau::rodas4].
+///
+/// Instead of solving a nonlinear system for each stage, each stage requires a single linear solve with the matrix `M - h * gamma * J`,
+/// where `J` is the jacobian of the rhs at the start of the step. The jacobian and its factorisation are cached using [SdirkCallable],
+/// so the jacobian is evaluated once per step, and a rejected step only requires a new factorisation.
+/// The time derivative of the rhs (for non-autonomous problems) is approximated using a finite difference.
+///
+/// If the `beta` matrix of the [RosenbrockTableau] is present this is used for interpolation, otherwise hermite interpolation is used
+/// (which can be inaccurate for the stiff components of a solution over large time steps, so prefer stopping at the output times in this case).
+///
+/// Restrictions:
+/// - Problems with a mass matrix require a tableau with a continuous extension (e.g. [RosenbrockTableau::rodas4]).
+/// - Sensitivities are not supported.
+pub struct Rosenbrock<M, Eqn, LS>
+where
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    tableau: RosenbrockTableau<M>,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    linear_solver: LS,
+    op: Option<Rc<SdirkCallable<Eqn>>>,
+    state: Option<OdeSolverState<Eqn::V>>,
+    diff: M,
+    a_rows: Vec<Eqn::V>,
+    gamma_rows: Vec<Eqn::V>,
+    old_t: Eqn::T,
+    old_y: Eqn::V,
+    f0: Eqn::V,
+    ft: Eqn::V,
+    statistics: BdfStatistics<Eqn::T>,
+    root_finder: Option<RootFinder<Eqn::V>>,
+    tstop: Option<Eqn::T>,
+    is_state_mutated: bool,
+    t_compensation: Eqn::T,
+}
+
+impl<M, Eqn, LS> Rosenbrock<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    const MIN_FACTOR: f64 = 0.2;
+    const MAX_FACTOR: f64 = 10.0;
+    const MIN_TIMESTEP: f64 = 1e-13;
+
+    pub fn new(tableau: RosenbrockTableau<M>, linear_solver: LS) -> Self {
+        // check that a and gamma_mat are strictly lower triangular
+        let s = tableau.s();
+        for i in 0..s {
+            for j in i..s {
+                assert_eq!(
+                    tableau.a()[(i, j)],
+                    Eqn::T::zero(),
+                    "Invalid tableau, expected a(i, j) = 0 for i >= j"
+                );
+                assert_eq!(
+                    tableau.gamma_mat()[(i, j)],
+                    Eqn::T::zero(),
+                    "Invalid tableau, expected gamma_mat(i, j) = 0 for i >= j"
+                );
+            }
+        }
+        assert!(
+            tableau.gamma() > Eqn::T::zero(),
+            "Invalid tableau, expected gamma > 0"
+        );
+
+        let mut a_rows = Vec::with_capacity(s);
+        let mut gamma_rows = Vec::with_capacity(s);
+        for i in 0..s {
+            let mut a_row = Vec::with_capacity(i);
+            let mut gamma_row = Vec::with_capacity(i);
+            for j in 0..i {
+                a_row.push(tableau.a()[(i, j)].clone());
+                gamma_row.push(tableau.gamma_mat()[(i, j)].clone());
+            }
+            a_rows.push(Eqn::V::from_vec(a_row));
+            gamma_rows.push(Eqn::V::from_vec(gamma_row));
+        }
+
+        let n = 1;
+        Self {
+            diff: M::zeros(n, s),
+            tableau,
+            problem: None,
+            linear_solver,
+            op: None,
+            state: None,
+            a_rows,
+            gamma_rows,
+            old_t: Eqn::T::zero(),
+            old_y: <Eqn::V as Vector>::zeros(n),
+            f0: <Eqn::V as Vector>::zeros(n),
+            ft: <Eqn::V as Vector>::zeros(n),
+            statistics: BdfStatistics::default(),
+            root_finder: None,
+            tstop: None,
+            is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
+        }
+    }
+
+    pub fn get_statistics(&self) -> &BdfStatistics<Eqn::T> {
+        &self.statistics
+    }
+
+    fn interpolate_beta_function(theta: Eqn::T, beta: &M) -> Eqn::V {
+        let poly_order = beta.ncols();
+        let s_star = beta.nrows();
+        let mut thetav = Vec::with_capacity(poly_order);
+        thetav.push(theta.clone());
+        for i in 1..poly_order {
+            thetav.push(theta.clone() * thetav[i - 1].clone());
+        }
+        // beta_poly = beta * thetav
+        let thetav = Eqn::V::from_vec(thetav);
+        let mut beta_f = <Eqn::V as Vector>::zeros(s_star);
+        beta.gemv(Eqn::T::one(), &thetav, Eqn::T::zero(), &mut beta_f);
+        beta_f
+    }
+
+    fn interpolate_hermite(
+        theta: Eqn::T,
+        u0: &Eqn::V,
+        u1: &Eqn::V,
+        hf0: &Eqn::V,
+        hf1: &Eqn::V,
+    ) -> Eqn::V {
+        u0 * scale(Eqn::T::cast(1.0) - theta.clone())
+            + u1 * scale(theta.clone())
+            + ((u1 - u0) * scale(Eqn::T::cast(1.0) - Eqn::T::cast(2.0) * theta.clone())
+                + hf0 * scale(theta.clone() - Eqn::T::cast(1.0))
+                + hf1 * scale(theta.clone()))
+                * scale(theta.clone() * (theta - Eqn::T::cast(1.0)))
+    }
+}
+
+impl<M, Eqn, LS> OdeSolverMethod<Eqn> for Rosenbrock<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        self.tableau.order()
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        let has_dense_output = self.tableau.beta().is_some();
+        SolverCapabilities {
+            mass_matrix: has_dense_output,
+            singular_mass_matrix: has_dense_output,
+            roots: true,
+            sensitivities: false,
+            stiff: true,
+            max_order: self.tableau.order(),
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.op = None;
+        self.root_finder = None;
+        self.linear_solver.clear_problem();
+        Option::take(&mut self.state)
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+        // the linear system for each stage is M - h * gamma * J, with J evaluated at the start of the step
+        let callable = Rc::new(SdirkCallable::new(problem, self.tableau.gamma()));
+        callable.set_h(state.h.clone());
+        let linear_problem = SolverProblem::new_from_ode_problem(callable.clone(), problem);
+        self.linear_solver.set_problem(&linear_problem);
+        self.op = Some(callable);
+
+        // update statistics
+        self.statistics = BdfStatistics::default();
+        self.statistics.initial_step_size = state.h.clone();
+
+        let nstates = state.y.len();
+        self.diff = M::zeros(nstates, self.tableau.s());
+        self.f0 = <Eqn::V as Vector>::zeros(nstates);
+        self.ft = <Eqn::V as Vector>::zeros(nstates);
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
+        self.old_y = state.y.clone();
+        self.is_state_mutated = false;
+        self.state = Some(state);
+        self.problem = Some(problem.clone());
+        if let Some(root_fn) = problem.eqn.root() {
+            let state = self.state.as_ref().unwrap();
+            self.root_finder = Some(RootFinder::new(root_fn.nout()));
+            self.root_finder
+                .as_ref()
+                .unwrap()
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
+        }
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let eqn = self.problem.as_ref().unwrap().eqn.clone();
+        let op = self.op.as_ref().unwrap().clone();
+        let n = self.state.as_ref().unwrap().y.len();
+        let s = self.tableau.s();
+        let gamma = self.tableau.gamma();
+        let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut y1 = <Eqn::V as Vector>::zeros(n);
+        let mut stage_y = <Eqn::V as Vector>::zeros(n);
+        let mut stage_f = <Eqn::V as Vector>::zeros(n);
+        let mut tmp = <Eqn::V as Vector>::zeros(n);
+        let x0 = <Eqn::V as Vector>::zeros(n);
+
+        // state has been mutated by the user, so the accumulated roundoff in t is no longer valid
+        if self.is_state_mutated {
+            self.t_compensation = Eqn::T::zero();
+        }
+
+        // evaluate the rhs, its time derivative (using a finite difference) and the jacobian at the start of the step
+        {
+            let state = self.state.as_ref().unwrap();
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut self.f0);
+            let dt = Eqn::T::EPSILON.sqrt() * (Eqn::T::one() + abs(state.t.clone()));
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone() + dt.clone(), &mut self.ft);
+            self.ft
+                .axpy(-Eqn::T::one() / dt.clone(), &self.f0, Eqn::T::one() / dt);
+            op.set_phi_direct(&state.y);
+            op.set_jacobian_is_stale();
+        }
+
+        let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // loop until step is accepted
+        loop {
+            let state = self.state.as_ref().unwrap();
+            let t0 = state.t.clone();
+            let h = state.h.clone();
+
+            // factorise M - h * gamma * J (the jacobian is only re-evaluated on the first attempt)
+            op.set_h(h.clone());
+            self.linear_solver.set_linearisation(&x0, t0.clone());
+            self.statistics.number_of_linear_solver_setups += 1;
+
+            for i in 0..s {
+                // f(t + c_i h, y + sum_j a_ij k_j)
+                if i == 0 {
+                    stage_f.copy_from(&self.f0);
+                } else {
+                    let t = t0.clone() + self.tableau.c()[i].clone() * h.clone();
+                    stage_y.copy_from(&state.y);
+                    self.diff.columns(0, i).gemv_o(
+                        Eqn::T::one(),
+                        &self.a_rows[i],
+                        Eqn::T::one(),
+                        &mut stage_y,
+                    );
+                    eqn.rhs().call_inplace(&stage_y, t, &mut stage_f);
+
+                    // + M sum_j (gamma_ij / h) k_j
+                    tmp.fill(Eqn::T::zero());
+                    self.diff.columns(0, i).gemv_o(
+                        Eqn::T::one() / h.clone(),
+                        &self.gamma_rows[i],
+                        Eqn::T::zero(),
+                        &mut tmp,
+                    );
+                    match eqn.mass() {
+                        Some(mass) => {
+                            mass.gemv_inplace(&tmp, t0.clone(), Eqn::T::one(), &mut stage_f)
+                        }
+                        None => stage_f.axpy(Eqn::T::one(), &tmp, Eqn::T::one()),
+                    }
+                }
+
+                // + gamma_i h df/dt
+                stage_f.axpy(
+                    self.tableau.gamma_t()[i].clone() * h.clone(),
+                    &self.ft,
+                    Eqn::T::one(),
+                );
+
+                // solve (M / (h gamma) - J) k_i = stage_f, i.e. (M - h gamma J) k_i = h gamma stage_f
+                stage_f *= scale(h.clone() * gamma.clone());
+                self.linear_solver.solve_in_place(&mut stage_f)?;
+                self.diff.column_mut(i).copy_from(&stage_f);
+            }
+
+            // compute the solution and the error norm
+            y1.copy_from(&state.y);
+            self.diff
+                .gemv(Eqn::T::one(), self.tableau.b(), Eqn::T::one(), &mut y1);
+            self.diff
+                .gemv(Eqn::T::one(), self.tableau.d(), Eqn::T::zero(), &mut error);
+            let atol = self.problem.as_ref().unwrap().atol.as_ref();
+            let rtol = self.problem.as_ref().unwrap().rtol.clone();
+            let error_norm = error.squared_norm(&y1, atol, rtol);
+
+            // adjust step size based on error, the error estimate is of the same order as the method
+            let safety = self.problem.as_ref().unwrap().options.safety_factor.clone();
+            let order = self.tableau.order() as f64;
+            let mut factor = safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / order));
+            if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                factor = Eqn::T::cast(Self::MIN_FACTOR);
+            }
+            if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                factor = Eqn::T::cast(Self::MAX_FACTOR);
+            }
+
+            // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
+            let state = self.state.as_mut().unwrap();
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
+            state.h *= factor;
+
+            // if step size too small, then fail
+            if state.h < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
+            }
+
+            // test error is within tolerance
+            if error_norm <= Eqn::T::cast(1.0) {
+                break;
+            }
+            // step is rejected, factor reduces step size, so we try again with the smaller step size
+            self.statistics.number_of_error_test_failures += 1;
+        }
+
+        // take the step
+        let state = self.state.as_mut().unwrap();
+        self.old_t = state.t.clone();
+        state.t = t1;
+        self.t_compensation = t1_compensation;
+        std::mem::swap(&mut self.old_y, &mut state.y);
+        std::mem::swap(&mut y1, &mut state.y);
+
+        // the derivative at the end of the step (only used for hermite interpolation, so there is no mass matrix)
+        if self.tableau.beta().is_none() {
+            eqn.rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        }
+
+        self.is_state_mutated = false;
+
+        // update statistics
+        self.statistics.number_of_steps += 1;
+        self.statistics.linear_solver = self.linear_solver.statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
+
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
+    }
+
+    fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
+        method::set_stop_time(self, tstop)
+    }
+
+    fn interpolate_sens(
+        &self,
+        _t: <Eqn as OdeEquations>::T,
+    ) -> Result<Vec<<Eqn as OdeEquations>::V>, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        // sensitivities are not supported, so there are no sensitivity vectors
+        Ok(Vec::new())
+    }
+
+    fn interpolate(&self, t: <Eqn>::T) -> Result<<Eqn>::V, PSError> {
+        if self.state.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        let state = self.state.as_ref().unwrap();
+
+        if self.is_state_mutated {
+            if t == state.t {
+                return Ok(state.y.clone());
+            } else {
+                return Err(PSError::InterpolationOutsideCurrentStep);
+            }
+        }
+
+        // check that t is within the current step
+        if t > state.t || t < self.old_t {
+            return Err(PSError::InterpolationOutsideCurrentStep);
+        }
+        let dt = state.t.clone() - self.old_t.clone();
+        if dt == Eqn::T::zero() {
+            return Ok(state.y.clone());
+        }
+        let theta = (t - self.old_t.clone()) / dt.clone();
+
+        if let Some(beta) = self.tableau.beta() {
+            // ret = old_y + sum_{i=0}^{s_star-1} beta[i] * diff[:, i]
+            let beta_f = Self::interpolate_beta_function(theta, beta);
+            let mut ret = self.old_y.clone();
+            self.diff
+                .gemv(Eqn::T::one(), &beta_f, Eqn::T::one(), &mut ret);
+            Ok(ret)
+        } else {
+            let hf0 = self.f0.clone() * scale(dt.clone());
+            let hf1 = state.dy.clone() * scale(dt);
+            let ret = Self::interpolate_hermite(theta, &self.old_y, &state.y, &hf0, &hf1);
+            Ok(ret)
+        }
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.state.as_ref()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.is_state_mutated = true;
+        self.state.as_mut()
+    }
+}
+
+impl<M, Eqn, LS> StepChecks<Eqn> for Rosenbrock<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        self.state.as_mut().unwrap().h *= factor;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                gaussian_decay::gaussian_decay_problem,
+                robertson::robertson,
+                robertson_ode::robertson_ode,
+            },
+            tests::{
+                test_interpolate, test_local_error_order_exponential_decay, test_no_set_problem,
+                test_ode_solver, test_state_mut,
+            },
+        },
+        NalgebraLU, OdeEquations, Op, Rosenbrock, RosenbrockTableau,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn rosenbrock_no_set_problem() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        test_no_set_problem::<M, _>(Rosenbrock::new(tableau, NalgebraLU::default()));
+    }
+    #[test]
+    fn rosenbrock_state_mut() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        test_state_mut::<M, _>(Rosenbrock::new(tableau, NalgebraLU::default()));
+    }
+    #[test]
+    fn rosenbrock_test_interpolate() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        test_interpolate::<M, _>(Rosenbrock::new(tableau, NalgebraLU::default()));
+        let tableau = RosenbrockTableau::<M>::rodas3();
+        test_interpolate::<M, _>(Rosenbrock::new(tableau, NalgebraLU::default()));
+    }
+
+    #[test]
+    fn rosenbrock_test_local_error_order() {
+        // rodas3 is 3rd order and rodas4 is 4th order
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let new_solver =
+            || Rosenbrock::new(RosenbrockTableau::<M>::rodas3(), NalgebraLU::default());
+        test_local_error_order_exponential_decay(new_solver, problem, 0.2, 3);
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let new_solver =
+            || Rosenbrock::new(RosenbrockTableau::<M>::rodas4(), NalgebraLU::default());
+        test_local_error_order_exponential_decay(new_solver, problem, 0.5, 4);
+    }
+
+    #[test]
+    fn test_rodas3_nalgebra_exponential_decay() {
+        let tableau = RosenbrockTableau::<M>::rodas3();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 27
+        number_of_steps: 27
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.02114742526881128
+        final_step_size: 0.400086448641377
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 164
+        number_of_jac_muls: 54
+        number_of_matrix_evals: 27
+        "###);
+    }
+
+    #[test]
+    fn test_rodas4_nalgebra_exponential_decay() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 9
+        number_of_steps: 9
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.045730505192732626
+        final_step_size: 1.5173031488162805
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 65
+        number_of_jac_muls: 18
+        number_of_matrix_evals: 9
+        "###);
+    }
+
+    #[test]
+    fn test_rodas4_nalgebra_gaussian_decay() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = gaussian_decay_problem::<M>(10);
+        test_ode_solver(&mut s, &problem, soln, None, false);
+    }
+
+    #[test]
+    fn test_rodas4_nalgebra_robertson() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = robertson::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 108
+        number_of_steps: 105
+        number_of_error_test_failures: 3
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.010761870091461323
+        final_step_size: 67431580043.95112
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 754
+        number_of_jac_muls: 318
+        number_of_matrix_evals: 106
+        "###);
+    }
+
+    #[test]
+    fn test_rodas3_nalgebra_robertson_ode() {
+        let tableau = RosenbrockTableau::<M>::rodas3();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = robertson_ode::<M>();
+        // hermite interpolation is not accurate enough for the stiff components, so stop at each output time
+        test_ode_solver(&mut s, &problem, soln, None, true);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 103
+        number_of_steps: 101
+        number_of_error_test_failures: 2
+        number_of_nonlinear_solver_iterations: 0
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0031785690922085303
+        final_step_size: 206729447778.1576
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_rodas4() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_root_finder_rodas4() {
+        let tableau = RosenbrockTableau::<M>::rodas4();
+        let mut s = Rosenbrock::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
@@ -0,0 +1,261 @@
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{errors::PSError, scalar::Scalar, Vector};
+
+/// The method used to sample points in a parameter hypercube (see [ParameterBounds::sample])
+///
+/// - `Uniform`: independent uniform random samples
+/// - `LatinHypercube`: each parameter range is split into `n` equal strata, with exactly one sample in each stratum of each parameter
+/// - `Sobol`: the first `n` points of a Sobol low-discrepancy sequence (see [SobolSequence]), which does not use the random number generator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplingMethod {
+    Uniform,
+    LatinHypercube,
+    Sobol,
+}
+
+/// Lower and upper bounds for each parameter, defining a hypercube of parameter space to sample from.
+/// The samples are returned as a list of parameter vectors, which can be passed directly to e.g. [crate::OdeSolverMethod::solve_sweep].
+#[derive(Clone, Debug)]
+pub struct ParameterBounds<T: Scalar> {
+    pub lower: Vec<T>,
+    pub upper: Vec<T>,
+}
+
+impl<T: Scalar> ParameterBounds<T> {
+    pub fn new(lower: Vec<T>, upper: Vec<T>) -> Result<Self, PSError> {
+        if lower.len() != upper.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "parameter bounds".to_string(),
+                expected: lower.len(),
+                found: upper.len(),
+            });
+        }
+        if lower.iter().zip(upper.iter()).any(|(l, u)| l > u) {
+            return Err(PSError::Other {
+                e: "lower parameter bounds must not be greater than the upper bounds".to_string(),
+            });
+        }
+        Ok(Self { lower, upper })
+    }
+
+    pub fn nparams(&self) -> usize {
+        self.lower.len()
+    }
+
+    /// Sample `n` parameter vectors from the hypercube using the given `method`
+    pub fn sample<V: Vector<T = T>, R: Rng + ?Sized>(
+        &self,
+        method: SamplingMethod,
+        n: usize,
+        rng: &mut R,
+    ) -> Result<Vec<V>, PSError> {
+        let k = self.nparams();
+        // points in the unit hypercube, stored as n rows of k columns
+        let unit = match method {
+            SamplingMethod::Uniform => (0..n * k).map(|_| rng.gen::<f64>()).collect::<Vec<_>>(),
+            SamplingMethod::LatinHypercube => {
+                let mut unit = vec![0.0; n * k];
+                let mut strata = (0..n).collect::<Vec<_>>();
+                for j in 0..k {
+                    strata.shuffle(rng);
+                    for (i, &s) in strata.iter().enumerate() {
+                        unit[i * k + j] = (s as f64 + rng.gen::<f64>()) / n as f64;
+                    }
+                }
+                unit
+            }
+            SamplingMethod::Sobol => {
+                let mut sequence = SobolSequence::new(k)?;
+                (0..n).flat_map(|_| sequence.next_point()).collect()
+            }
+        };
+        let ret = unit
+            .chunks(k.max(1))
+            .take(n)
+            .map(|u| {
+                let mut p = V::zeros(k);
+                for (j, &uj) in u.iter().enumerate().take(k) {
+                    p[j] = self.lower[j].clone()
+                        + (self.upper[j].clone() - self.lower[j].clone()) * T::cast(uj);
+                }
+                p
+            })
+            .collect();
+        Ok(ret)
+    }
+}
+
+// primitive polynomials and initial direction numbers for dimensions 2 to 21, from the new-joe-kuo-6.21201 table of Joe and Kuo (2008),
+// as (degree s, coefficients a, initial direction numbers m_1..m_s)
+const SOBOL_DIRECTIONS: [(u32, u32, &[u32]); 20] = [
+    (1, 0, &[1]),
+    (2, 1, &[1, 3]),
+    (3, 1, &[1, 3, 1]),
+    (3, 2, &[1, 1, 1]),
+    (4, 1, &[1, 1, 3, 3]),
+    (4, 4, &[1, 3, 5, 13]),
+    (5, 2, &[1, 1, 5, 5, 17]),
+    (5, 4, &[1, 1, 5, 5, 5]),
+    (5, 7, &[1, 1, 7, 11, 19]),
+    (5, 11, &[1, 1, 5, 1, 1]),
+    (5, 13, &[1, 1, 1, 3, 11]),
+    (5, 14, &[1, 3, 5, 5, 31]),
+    (6, 1, &[1, 3, 3, 9, 7, 49]),
+    (6, 13, &[1, 1, 1, 15, 21, 21]),
+    (6, 16, &[1, 3, 1, 13, 27, 49]),
+    (6, 19, &[1, 1, 1, 15, 7, 5]),
+    (6, 22, &[1, 3, 1, 15, 13, 25]),
+    (6, 25, &[1, 1, 5, 5, 19, 61]),
+    (7, 1, &[1, 3, 7, 11, 23, 15, 103]),
+    (7, 4, &[1, 3, 7, 13, 13, 15, 69]),
+];
+
+const SOBOL_BITS: usize = 32;
+
+/// A Sobol low-discrepancy sequence in the unit hypercube of dimension `dim` (up to [Self::MAX_DIM]), generated using the direction numbers
+/// of Joe and Kuo (2008) and the Gray code ordering of Antonov and Saleev. The first point of the sequence is the origin.
+/// The first `2^m` points of the sequence have exactly one point in each of the `2^m` equal intervals of each dimension.
+#[derive(Clone, Debug)]
+pub struct SobolSequence {
+    index: u32,
+    x: Vec<u32>,
+    directions: Vec<[u32; SOBOL_BITS]>,
+}
+
+impl SobolSequence {
+    pub const MAX_DIM: usize = SOBOL_DIRECTIONS.len() + 1;
+
+    pub fn new(dim: usize) -> Result<Self, PSError> {
+        if dim > Self::MAX_DIM {
+            return Err(PSError::Other {
+                e: format!(
+                    "Sobol sequences are only available for up to {} dimensions",
+                    Self::MAX_DIM
+                ),
+            });
+        }
+        let mut directions = Vec::with_capacity(dim);
+        if dim > 0 {
+            // the first dimension is the van der Corput sequence in base 2
+            let mut v = [0u32; SOBOL_BITS];
+            for (k, vk) in v.iter_mut().enumerate() {
+                *vk = 1 << (SOBOL_BITS - 1 - k);
+            }
+            directions.push(v);
+        }
+        for &(s, a, m) in SOBOL_DIRECTIONS.iter().take(dim.saturating_sub(1)) {
+            let s = s as usize;
+            let mut v = [0u32; SOBOL_BITS];
+            for k in 0..s {
+                v[k] = m[k] << (SOBOL_BITS - 1 - k);
+            }
+            for k in s..SOBOL_BITS {
+                v[k] = v[k - s] ^ (v[k - s] >> s);
+                for i in 1..s {
+                    if (a >> (s - 1 - i)) & 1 == 1 {
+                        v[k] ^= v[k - i];
+                    }
+                }
+            }
+            directions.push(v);
+        }
+        Ok(Self {
+            index: 0,
+            x: vec![0; dim],
+            directions,
+        })
+    }
+
+    pub fn dim(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Return the next point of the sequence
+    pub fn next_point(&mut self) -> Vec<f64> {
+        let scale = 1.0 / (1u64 << SOBOL_BITS) as f64;
+        let ret = self.x.iter().map(|&x| x as f64 * scale).collect();
+        // the next point differs from this one in the direction number given by the lowest zero bit of the index
+        let c = self.index.trailing_ones() as usize;
+        for (x, v) in self.x.iter_mut().zip(self.directions.iter()) {
+            *x ^= v[c.min(SOBOL_BITS - 1)];
+        }
+        self.index = self.index.wrapping_add(1);
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::ode_solver::sampling::{ParameterBounds, SamplingMethod, SobolSequence};
+
+    type V = nalgebra::DVector<f64>;
+
+    // checks that each of the n equal intervals of each dimension of the unit hypercube contains exactly one point
+    fn check_stratified(points: &[Vec<f64>]) {
+        let n = points.len();
+        for j in 0..points[0].len() {
+            let mut strata = points
+                .iter()
+                .map(|p| (p[j] * n as f64).floor() as usize)
+                .collect::<Vec<_>>();
+            strata.sort();
+            assert_eq!(strata, (0..n).collect::<Vec<_>>(), "dimension {}", j);
+        }
+    }
+
+    #[test]
+    fn test_sobol_sequence() {
+        let mut sequence = SobolSequence::new(SobolSequence::MAX_DIM).unwrap();
+        let points = (0..64).map(|_| sequence.next_point()).collect::<Vec<_>>();
+        assert!(points[0].iter().all(|&x| x == 0.0));
+        assert_eq!(
+            points[..4].iter().map(|p| p[0]).collect::<Vec<_>>(),
+            vec![0.0, 0.5, 0.75, 0.25]
+        );
+        assert_eq!(
+            points[..4].iter().map(|p| p[1]).collect::<Vec<_>>(),
+            vec![0.0, 0.5, 0.25, 0.75]
+        );
+        check_stratified(&points);
+        check_stratified(&points[..16]);
+
+        // the first two dimensions form a (0, m, 2)-net, so each 1/4 x 1/4 square has exactly one of the first 16 points
+        let mut squares = points[..16]
+            .iter()
+            .map(|p| ((p[0] * 4.0) as usize, (p[1] * 4.0) as usize))
+            .collect::<Vec<_>>();
+        squares.sort();
+        squares.dedup();
+        assert_eq!(squares.len(), 16);
+
+        assert!(SobolSequence::new(SobolSequence::MAX_DIM + 1).is_err());
+    }
+
+    #[test]
+    fn test_parameter_bounds_sample() {
+        let bounds = ParameterBounds::new(vec![0.0, 10.0], vec![1.0, 20.0]).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        for method in [
+            SamplingMethod::Uniform,
+            SamplingMethod::LatinHypercube,
+            SamplingMethod::Sobol,
+        ] {
+            let samples = bounds.sample::<V, _>(method, 32, &mut rng).unwrap();
+            assert_eq!(samples.len(), 32);
+            assert!(samples.iter().all(|p| p.len() == 2
+                && (0.0..1.0).contains(&p[0])
+                && (10.0..20.0).contains(&p[1])));
+            if method != SamplingMethod::Uniform {
+                let unit = samples
+                    .iter()
+                    .map(|p| vec![p[0], (p[1] - 10.0) / 10.0])
+                    .collect::<Vec<_>>();
+                check_stratified(&unit);
+            }
+        }
+        assert!(ParameterBounds::new(vec![1.0], vec![0.0]).is_err());
+    }
+}
@@ -9,17 +9,19 @@ use crate::errors::PSError;
 use crate::matrix::MatrixRef;
 use crate::nonlinear_solver::convergence::Convergence;
 use crate::nonlinear_solver::newton::newton_iteration;
+use crate::ode_solver::method::{self, end_step, StepChecks};
 use crate::vector::VectorRef;
 use crate::LinearSolver;
 use crate::NewtonNonlinearSolver;
 use crate::OdeSolverStopReason;
 use crate::RootFinder;
 use crate::SensEquations;
+use crate::StepControl;
 use crate::Tableau;
 use crate::{
-    nonlinear_solver::NonLinearSolver, op::sdirk::SdirkCallable, scale, solver::SolverProblem,
-    DenseMatrix, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState, Op,
-    Scalar, Vector, VectorViewMut,
+    nonlinear_solver::NonLinearSolver, op::sdirk::SdirkCallable, scalar::compensated_add, scale,
+    solver::SolverProblem, DenseMatrix, LinearOp, NonLinearOp, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, Op, Scalar, SolverCapabilities, Vector, VectorViewMut,
 };
 
 use super::bdf::BdfStatistics;
@@ -59,6 +61,7 @@ where
     root_finder: Option<RootFinder<Eqn::V>>,
     tstop: Option<Eqn::T>,
     is_state_mutated: bool,
+    t_compensation: Eqn::T,
 }
 
 impl<M, Eqn, LS> Sdirk<M, Eqn, LS>
@@ -90,7 +93,7 @@ where
                 );
             }
         }
-        let gamma = tableau.a()[(1, 1)];
+        let gamma = tableau.a()[(1, 1)].clone();
         //check that for i = 1..s-1, a(i, i) = gamma
         for i in 1..tableau.s() {
             assert_eq!(
@@ -113,7 +116,7 @@ where
         for i in 0..s {
             let mut row = Vec::with_capacity(i);
             for j in 0..i {
-                row.push(tableau.a()[(i, j)]);
+                row.push(tableau.a()[(i, j)].clone());
             }
             a_rows.push(Eqn::V::from_vec(row));
         }
@@ -173,6 +176,7 @@ where
             root_finder: None,
             tstop: None,
             is_state_mutated: false,
+            t_compensation: Eqn::T::zero(),
         }
     }
 
@@ -180,44 +184,25 @@ where
         &self.statistics
     }
 
-    fn handle_tstop(
-        &mut self,
-        tstop: Eqn::T,
-    ) -> Result<Option<OdeSolverStopReason<Eqn::T>>, PSError> {
-        let state = self.state.as_mut().unwrap();
-
-        // check if the we are at tstop
-        let troundoff = Eqn::T::from(100.0) * Eqn::T::EPSILON * (abs(state.t) + abs(state.h));
-        if abs(state.t - tstop) <= troundoff {
-            self.tstop = None;
-            return Ok(Some(OdeSolverStopReason::TstopReached));
-        } else if tstop < state.t - troundoff {
-            return Err(PSError::StopBeforeCurrentTime {
-                tstop: tstop.into(),
-                t: state.t.into(),
-            });
-        }
-
-        // check if the next step will be beyond tstop, if so adjust the step size
-        if state.t + state.h > tstop + troundoff {
-            let factor = (tstop - state.t) / state.h;
-            state.h *= factor;
-            self.nonlinear_solver.problem().f.set_h(state.h);
-        }
-        Ok(None)
-    }
-
     fn predict_stage(i: usize, diff: &M, dy: &mut Eqn::V, tableau: &Tableau<M>) {
         if i == 0 {
             dy.fill(Eqn::T::zero());
         } else if i == 1 {
             dy.copy_from_view(&diff.column(i - 1));
         } else {
-            let c =
-                (tableau.c()[i] - tableau.c()[i - 2]) / (tableau.c()[i - 1] - tableau.c()[i - 2]);
+            let c = (tableau.c()[i].clone() - tableau.c()[i - 2].clone())
+                / (tableau.c()[i - 1].clone() - tableau.c()[i - 2].clone());
             // dy = c1  + c * (c1 - c2)
             dy.copy_from_view(&diff.column(i - 1));
-            dy.axpy_v(-c, &diff.column(i - 2), Eqn::T::one() + c);
+            dy.axpy_v(-c.clone(), &diff.column(i - 2), Eqn::T::one() + c);
+        }
+    }
+
+    /// Multiply `x` by the mass matrix in place (using `tmp` as workspace), does nothing if the mass matrix is the identity
+    fn mass_mul(&self, t: Eqn::T, x: &mut Eqn::V, tmp: &mut Eqn::V) {
+        if let Some(mass) = self.problem.as_ref().unwrap().eqn.mass() {
+            mass.call_inplace(x, t, tmp);
+            std::mem::swap(x, tmp);
         }
     }
 
@@ -231,7 +216,7 @@ where
                 .as_ref()
                 .unwrap()
                 .rhs()
-                .update_state(&self.old_y, &self.old_f, t);
+                .update_state(&self.old_y, &self.old_f, t.clone());
         }
 
         // reuse linear solver from nonlinear solver
@@ -241,14 +226,16 @@ where
 
         // construct bdf discretisation of sensitivity equations
         let op = self.s_op.as_ref().unwrap();
-        op.set_h(self.state.as_ref().unwrap().h);
+        op.set_h(self.state.as_ref().unwrap().h.clone());
 
         // solve for sensitivities equations discretised using sdirk equation
-        let fun = |x: &Eqn::V, y: &mut Eqn::V| op.call_inplace(x, t, y);
-        let rtol = self.problem().as_ref().unwrap().rtol;
+        let fun = |x: &Eqn::V, y: &mut Eqn::V| op.call_inplace(x, t.clone(), y);
+        let rtol = self.problem().as_ref().unwrap().rtol.clone();
         let atol = self.problem().as_ref().unwrap().atol.clone();
         let maxiter = self.nonlinear_solver.max_iter();
         let mut convergence = Convergence::new(rtol, atol, maxiter);
+        convergence.set_max_rate(self.nonlinear_solver.max_rate());
+        convergence.set_criterion(self.nonlinear_solver.problem().convergence_criterion);
         let nparams = self.problem().as_ref().unwrap().eqn.rhs().nparams();
         for j in 0..nparams {
             let s0 = &self.state.as_ref().unwrap().s[j];
@@ -278,9 +265,9 @@ where
         let poly_order = beta.ncols();
         let s_star = beta.nrows();
         let mut thetav = Vec::with_capacity(poly_order);
-        thetav.push(theta);
+        thetav.push(theta.clone());
         for i in 1..poly_order {
-            thetav.push(theta * thetav[i - 1]);
+            thetav.push(theta.clone() * thetav[i - 1].clone());
         }
         // beta_poly = beta * thetav
         let thetav = Eqn::V::from_vec(thetav);
@@ -292,12 +279,12 @@ where
     fn interpolate_hermite(theta: Eqn::T, u0: &Eqn::V, u1: &Eqn::V, diff: &M) -> Eqn::V {
         let hf0 = diff.column(0);
         let hf1 = diff.column(diff.ncols() - 1);
-        u0 * scale(Eqn::T::from(1.0) - theta)
-            + u1 * scale(theta)
-            + ((u1 - u0) * scale(Eqn::T::from(1.0) - Eqn::T::from(2.0) * theta)
-                + hf0 * scale(theta - Eqn::T::from(1.0))
-                + hf1 * scale(theta))
-                * scale(theta * (theta - Eqn::T::from(1.0)))
+        u0 * scale(Eqn::T::cast(1.0) - theta.clone())
+            + u1 * scale(theta.clone())
+            + ((u1 - u0) * scale(Eqn::T::cast(1.0) - Eqn::T::cast(2.0) * theta.clone())
+                + hf0 * scale(theta.clone() - Eqn::T::cast(1.0))
+                + hf1 * scale(theta.clone()))
+                * scale(theta.clone() * (theta - Eqn::T::cast(1.0)))
     }
 }
 
@@ -317,36 +304,73 @@ where
         self.tableau.order()
     }
 
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: true,
+            singular_mass_matrix: true,
+            roots: true,
+            sensitivities: true,
+            stiff: true,
+            max_order: self.tableau.order(),
+            dense_output: true,
+            fixed_step: true,
+            nonlinear_rhs: true,
+            backward: true,
+            invariants: true,
+        }
+    }
+
     fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.s_op = None;
+        self.root_finder = None;
+        self.nonlinear_solver.clear_problem();
         Option::take(&mut self.state)
     }
 
-    fn set_problem(&mut self, state: OdeSolverState<<Eqn>::V>, problem: &OdeSolverProblem<Eqn>) {
+    fn set_problem(
+        &mut self,
+        mut state: OdeSolverState<<Eqn>::V>,
+        problem: &OdeSolverProblem<Eqn>,
+    ) {
+        // with a fixed step size the initial step size is ignored
+        if let StepControl::Fixed(h) = problem.options.step_control.clone() {
+            state.h = h * problem.direction();
+        }
+
         // setup linear solver for first step
-        let callable = Rc::new(SdirkCallable::new(problem, self.gamma));
-        callable.set_h(state.h);
+        let callable = Rc::new(SdirkCallable::new(problem, self.gamma.clone()));
+        callable.set_h(state.h.clone());
         let nonlinear_problem = SolverProblem::new_from_ode_problem(callable, problem);
+        let max_iter = problem
+            .options
+            .max_nonlinear_solver_iterations
+            .unwrap_or(Self::NEWTON_MAXITER);
+        self.nonlinear_solver.set_max_iter(max_iter);
+        self.nonlinear_solver
+            .set_max_rate(problem.options.max_convergence_rate.clone());
         self.nonlinear_solver.set_problem(&nonlinear_problem);
 
         // update statistics
         self.statistics = BdfStatistics::default();
-        self.statistics.initial_step_size = state.h;
+        self.statistics.initial_step_size = state.h.clone();
 
         let nstates = state.y.len();
         let nparams = problem.eqn.rhs().nparams();
-        if problem.eqn_sens.is_some() {
+        if let Some(eqn_sens) = problem.eqn_sens.as_ref() {
             self.sdiff = vec![M::zeros(nstates, self.tableau.s()); nparams];
             self.old_f_sens = vec![<Eqn::V as Vector>::zeros(nstates); nparams];
             self.old_y_sens = vec![<Eqn::V as Vector>::zeros(nstates); nparams];
             self.s_op = Some(SdirkCallable::from_eqn(
-                problem.eqn_sens.as_ref().unwrap().clone(),
-                self.gamma,
+                eqn_sens.clone(),
+                self.gamma.clone(),
             ));
         }
 
         self.diff = M::zeros(nstates, self.tableau.s());
         self.old_f = state.dy.clone();
-        self.old_t = state.t;
+        self.old_t = state.t.clone();
+        self.t_compensation = Eqn::T::zero();
         self.old_y = state.y.clone();
         self.state = Some(state);
         self.problem = Some(problem.clone());
@@ -356,7 +380,7 @@ where
             self.root_finder
                 .as_ref()
                 .unwrap()
-                .init(root_fn.as_ref(), &state.y, state.t);
+                .init(root_fn.as_ref(), &state.y, state.t.clone());
         }
     }
 
@@ -366,6 +390,7 @@ where
             return Err(PSError::StateNotSet);
         }
         let n = self.state.as_ref().unwrap().y.len();
+        let step_control = self.problem().unwrap().options.step_control.clone();
 
         let start = if self.is_sdirk { 0 } else { 1 };
         let mut updated_jacobian = false;
@@ -373,19 +398,26 @@ where
         // dont' reset jacobian for the first attempt at the step
         let mut second_step_attempt = false;
         let mut error = <Eqn::V as Vector>::zeros(n);
+        let mut mass_error = <Eqn::V as Vector>::zeros(n);
 
         let mut t1: Eqn::T;
+        let mut t1_compensation: Eqn::T;
+
+        // state has been mutated by the user, so the accumulated roundoff in t is no longer valid
+        if self.is_state_mutated {
+            self.t_compensation = Eqn::T::zero();
+        }
 
         // loop until step is accepted
         'step: loop {
-            let t0 = self.state.as_ref().unwrap().t;
-            let h = self.state.as_ref().unwrap().h;
+            let t0 = self.state.as_ref().unwrap().t.clone();
+            let h = self.state.as_ref().unwrap().h.clone();
             // if start == 1, then we need to compute the first stage
             if start == 1 {
                 {
                     let mut hf = self.diff.column_mut(0);
                     hf.copy_from(&self.state.as_ref().unwrap().dy);
-                    hf *= scale(h);
+                    hf *= scale(h.clone());
                 }
 
                 // sensitivities too
@@ -397,13 +429,13 @@ where
                     {
                         let mut hf = diff.column_mut(0);
                         hf.copy_from(dy);
-                        hf *= scale(h);
+                        hf *= scale(h.clone());
                     }
                 }
             }
 
             for i in start..self.tableau.s() {
-                let t = t0 + self.tableau.c()[i] * h;
+                let t = t0.clone() + self.tableau.c()[i].clone() * h.clone();
                 self.nonlinear_solver.problem().f.set_phi(
                     &self.diff.columns(0, i),
                     &self.state.as_ref().unwrap().y,
@@ -416,13 +448,15 @@ where
                 // as h has changed or jacobian needs to be recalculated
                 if i == start && second_step_attempt {
                     // have to do it here cause phi needs to be set first
-                    self.nonlinear_solver.reset_jacobian(&self.old_f, t);
+                    self.nonlinear_solver.reset_jacobian(&self.old_f, t.clone());
                 }
 
                 // always reset jacobian if step is attempted again
                 second_step_attempt = true;
 
-                let mut solve_result = self.nonlinear_solver.solve_in_place(&mut self.old_f, t);
+                let mut solve_result = self
+                    .nonlinear_solver
+                    .solve_in_place(&mut self.old_f, t.clone());
                 self.statistics.number_of_nonlinear_solver_iterations +=
                     self.nonlinear_solver.niter();
 
@@ -443,19 +477,26 @@ where
                         self.nonlinear_solver.problem().f.set_jacobian_is_stale();
                         updated_jacobian = true;
                         self.statistics.number_of_nonlinear_solver_fails += 1;
+                    } else if let StepControl::Fixed(_) = step_control {
+                        // the step size cannot be reduced if it is fixed, so fail
+                        return Err(PSError::FixedStepNonlinearSolverFailure {
+                            t: self.state.as_ref().unwrap().t.to_string(),
+                        });
                     } else {
                         // newton iteration did not converge and jacobian has been updated, so we reduce step size and try again
                         let state = self.state.as_mut().unwrap();
                         self.statistics.number_of_nonlinear_solver_fails += 1;
-                        state.h *= Eqn::T::from(0.3);
+                        state.h *= Eqn::T::cast(0.3);
 
                         // if step size too small, then fail
-                        if state.h < Eqn::T::from(Self::MIN_TIMESTEP) {
-                            return Err(PSError::StepSizeTooSmall { t: state.t.into() });
+                        if abs(state.h.clone()) < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                            return Err(PSError::StepSizeTooSmall {
+                                t: state.t.to_string(),
+                            });
                         }
 
                         // update h for new step size
-                        self.nonlinear_solver.problem().f.set_h(state.h);
+                        self.nonlinear_solver.problem().f.set_h(state.h.clone());
                     }
                     // try again....
                     continue 'step;
@@ -470,62 +511,86 @@ where
                     }
                 }
             }
-            // successfully solved for all stages, now compute error
-            self.diff
-                .gemv(Eqn::T::one(), self.tableau.d(), Eqn::T::zero(), &mut error);
-
-            // solve for  (M - h * c * J) * error = error_est as by Hosea, M. E., & Shampine, L. F. (1996). Analysis and implementation of TR-BDF2. Applied Numerical Mathematics, 20(1-2), 21-37.
-            self.nonlinear_solver
-                .solve_linearised_in_place(&mut error)?;
-
-            // compute error norm
-            let atol = self.problem().as_ref().unwrap().atol.as_ref();
-            let rtol = self.problem().as_ref().unwrap().rtol;
-            let mut error_norm = error.squared_norm(&self.old_y, atol, rtol);
-
-            // sensitivity errors
-            if self.problem().as_ref().unwrap().eqn_sens.is_some()
-                && self.problem().as_ref().unwrap().sens_error_control
-            {
-                for i in 0..self.sdiff.len() {
-                    self.sdiff[i].gemv(Eqn::T::one(), self.tableau.d(), Eqn::T::zero(), &mut error);
-                    self.nonlinear_solver
-                        .solve_linearised_in_place(&mut error)?;
-                    let sens_error_norm = error.squared_norm(&self.old_y_sens[i], atol, rtol);
-                    error_norm += sens_error_norm;
+            // successfully solved for all stages, now compute error and the factor for the next step size.
+            // With a fixed step size there is no error control, so the error is not computed and the step size
+            // is restored if the step was shortened to stop at tstop
+            let (error_norm, factor) = if let StepControl::Fixed(h) = &step_control {
+                let h = h.clone() * self.problem().unwrap().direction();
+
+                (Eqn::T::zero(), h / self.state.as_ref().unwrap().h.clone())
+            } else {
+                self.diff
+                    .gemv(Eqn::T::one(), self.tableau.d(), Eqn::T::zero(), &mut error);
+                self.mass_mul(t0.clone(), &mut error, &mut mass_error);
+
+                // solve for  (M - h * c * J) * error = M * error_est as by Hosea, M. E., & Shampine, L. F. (1996). Analysis and implementation of TR-BDF2. Applied Numerical Mathematics, 20(1-2), 21-37.
+                self.nonlinear_solver
+                    .solve_linearised_in_place(&mut error)?;
+
+                // compute error norm
+                let atol = self.problem().as_ref().unwrap().atol.as_ref();
+                let rtol = self.problem().as_ref().unwrap().rtol.clone();
+                let mut error_norm = error.squared_norm(&self.old_y, atol, rtol.clone());
+
+                // sensitivity errors
+                if self.problem().as_ref().unwrap().eqn_sens.is_some()
+                    && self.problem().as_ref().unwrap().sens_error_control
+                {
+                    for i in 0..self.sdiff.len() {
+                        self.sdiff[i].gemv(
+                            Eqn::T::one(),
+                            self.tableau.d(),
+                            Eqn::T::zero(),
+                            &mut error,
+                        );
+                        self.mass_mul(t0.clone(), &mut error, &mut mass_error);
+                        self.nonlinear_solver
+                            .solve_linearised_in_place(&mut error)?;
+                        let sens_error_norm =
+                            error.squared_norm(&self.old_y_sens[i], atol, rtol.clone());
+                        error_norm += sens_error_norm;
+                    }
+                    error_norm /= Eqn::T::cast((self.sdiff.len() + 1) as f64);
                 }
-                error_norm /= Eqn::T::from((self.sdiff.len() + 1) as f64);
-            }
 
-            // adjust step size based on error
-            // TODO: if factor close to 1 we shouldn't do this, think there is an alg in the textbook...
-            let maxiter = self.nonlinear_solver.max_iter() as f64;
-            let niter = self.nonlinear_solver.niter() as f64;
-            let safety = Eqn::T::from(0.9 * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
-            let order = self.tableau.order() as f64;
-            let mut factor = safety * error_norm.pow(Eqn::T::from(-0.5 / (order + 1.0)));
-            if factor < Eqn::T::from(Self::MIN_FACTOR) {
-                factor = Eqn::T::from(Self::MIN_FACTOR);
-            }
-            if factor > Eqn::T::from(Self::MAX_FACTOR) {
-                factor = Eqn::T::from(Self::MAX_FACTOR);
-            }
+                // adjust step size based on error
+                // TODO: if factor close to 1 we shouldn't do this, think there is an alg in the textbook...
+                let maxiter = self.nonlinear_solver.max_iter() as f64;
+                let niter = self.nonlinear_solver.niter() as f64;
+                let safety_factor: f64 = self.problem().unwrap().options.safety_factor.to_f64();
+                let safety =
+                    Eqn::T::cast(safety_factor * (2.0 * maxiter + 1.0) / (2.0 * maxiter + niter));
+                let order = self.tableau.order() as f64;
+                let mut factor =
+                    safety * error_norm.clone().pow(Eqn::T::cast(-0.5 / (order + 1.0)));
+                if factor < Eqn::T::cast(Self::MIN_FACTOR) {
+                    factor = Eqn::T::cast(Self::MIN_FACTOR);
+                }
+                if factor > Eqn::T::cast(Self::MAX_FACTOR) {
+                    factor = Eqn::T::cast(Self::MAX_FACTOR);
+                }
+                (error_norm, factor)
+            };
 
             // adjust step size for next step
+            // accumulate time using compensated summation to avoid drift over many small steps
             let state = self.state.as_mut().unwrap();
-            t1 = state.t + state.h;
+            t1_compensation = self.t_compensation.clone();
+            t1 = compensated_add(state.t.clone(), state.h.clone(), &mut t1_compensation);
             state.h *= factor;
 
             // if step size too small, then fail
-            if state.h < Eqn::T::from(Self::MIN_TIMESTEP) {
-                return Err(PSError::StepSizeTooSmall { t: state.t.into() });
+            if abs(state.h.clone()) < Eqn::T::cast(Self::MIN_TIMESTEP) {
+                return Err(PSError::StepSizeTooSmall {
+                    t: state.t.to_string(),
+                });
             }
 
             // update c for new step size
-            self.nonlinear_solver.problem().f.set_h(state.h);
+            self.nonlinear_solver.problem().f.set_h(state.h.clone());
 
             // test error is within tolerance
-            if error_norm <= Eqn::T::from(1.0) {
+            if error_norm <= Eqn::T::cast(1.0) {
                 break 'step;
             }
             // step is rejected, factor reduces step size, so we try again with the smaller step size
@@ -533,72 +598,52 @@ where
         }
 
         //setup jacobian for next step (h was changed so jacobian needs to be recalculated)
-        self.nonlinear_solver.reset_jacobian(&self.old_f, t1);
+        self.nonlinear_solver
+            .reset_jacobian(&self.old_f, t1.clone());
 
         // take the step
         let state = self.state.as_mut().unwrap();
-        let dt = t1 - state.t;
-        self.old_t = state.t;
+        let dt = t1.clone() - state.t.clone();
+        self.old_t = state.t.clone();
         state.t = t1;
+        self.t_compensation = t1_compensation;
 
         // last stage is the solution and is the same as old_f
         // todo: can we get rid of old_f and just use diff?
-        self.old_f.mul_assign(scale(Eqn::T::one() / dt));
+        self.old_f.mul_assign(scale(Eqn::T::one() / dt.clone()));
         std::mem::swap(&mut self.old_f, &mut state.dy);
 
         // old_y already has the new y soln
         std::mem::swap(&mut self.old_y, &mut state.y);
 
         for i in 0..self.sdiff.len() {
-            self.old_f_sens[i].mul_assign(scale(Eqn::T::one() / dt));
+            self.old_f_sens[i].mul_assign(scale(Eqn::T::one() / dt.clone()));
             std::mem::swap(&mut self.old_f_sens[i], &mut state.ds[i]);
             std::mem::swap(&mut self.old_y_sens[i], &mut state.s[i]);
         }
 
+        // project the solution onto the invariants of the equations (if any)
+        self.problem
+            .as_ref()
+            .unwrap()
+            .project(&mut state.y, state.t.clone())?;
+
         self.is_state_mutated = false;
 
         // update statistics
         self.statistics.number_of_linear_solver_setups =
             self.nonlinear_solver.problem().f.number_of_jac_evals();
         self.statistics.number_of_steps += 1;
-        self.statistics.final_step_size = self.state.as_ref().unwrap().h;
-
-        // check for root within accepted step
-        if let Some(root_fn) = self.problem.as_ref().unwrap().eqn.root() {
-            let ret = self.root_finder.as_ref().unwrap().check_root(
-                &|t| self.interpolate(t),
-                root_fn.as_ref(),
-                &self.state.as_ref().unwrap().y,
-                self.state.as_ref().unwrap().t,
-            );
-            if let Some(root) = ret {
-                return Ok(OdeSolverStopReason::RootFound(root));
-            }
-        }
+        self.statistics.linear_solver = self.nonlinear_solver.linear_solver_statistics();
+        self.statistics.nonlinear_solver = self.nonlinear_solver.get_statistics();
+        self.statistics.final_step_size = self.state.as_ref().unwrap().h.clone();
 
-        // check if the we are at tstop
-        if let Some(tstop) = self.tstop {
-            if let Some(reason) = self.handle_tstop(tstop).unwrap() {
-                return Ok(reason);
-            }
-        }
-
-        // just a normal step, no roots or tstop reached
-        Ok(OdeSolverStopReason::InternalTimestep)
+        // check that the solution has not blown up, for a root within the accepted step, and if we are at tstop
+        end_step(self)
     }
 
     fn set_stop_time(&mut self, tstop: <Eqn as OdeEquations>::T) -> Result<(), PSError> {
-        self.tstop = Some(tstop);
-        if let Some(OdeSolverStopReason::TstopReached) = self.handle_tstop(tstop)? {
-            return {
-                self.tstop = None;
-                Err(PSError::StopBeforeCurrentTime {
-                    tstop: tstop.into(),
-                    t: self.state.as_ref().unwrap().t.into(),
-                })
-            };
-        }
-        Ok(())
+        method::set_stop_time(self, tstop)
     }
 
     fn interpolate_sens(
@@ -618,15 +663,17 @@ where
             }
         }
 
-        // check that t is within the current step
-        if t > state.t || t < self.old_t {
+        // check that t is within the current step (which is reversed when integrating backward in time)
+        if (t.clone() - state.t.clone()) * state.h.clone() > Eqn::T::zero()
+            || (t.clone() - self.old_t.clone()) * state.h.clone() < Eqn::T::zero()
+        {
             return Err(PSError::InterpolationOutsideCurrentStep);
         }
-        let dt = state.t - self.old_t;
+        let dt = state.t.clone() - self.old_t.clone();
         let theta = if dt == Eqn::T::zero() {
             Eqn::T::one()
         } else {
-            (t - self.old_t) / dt
+            (t - self.old_t.clone()) / dt
         };
 
         if let Some(beta) = self.tableau.beta() {
@@ -644,7 +691,7 @@ where
                 .iter()
                 .zip(state.s.iter())
                 .zip(self.sdiff.iter())
-                .map(|((s0, s1), diff)| Self::interpolate_hermite(theta, s0, s1, diff))
+                .map(|((s0, s1), diff)| Self::interpolate_hermite(theta.clone(), s0, s1, diff))
                 .collect();
             Ok(ret)
         }
@@ -664,15 +711,17 @@ where
             }
         }
 
-        // check that t is within the current step
-        if t > state.t || t < self.old_t {
+        // check that t is within the current step (which is reversed when integrating backward in time)
+        if (t.clone() - state.t.clone()) * state.h.clone() > Eqn::T::zero()
+            || (t.clone() - self.old_t.clone()) * state.h.clone() < Eqn::T::zero()
+        {
             return Err(PSError::InterpolationOutsideCurrentStep);
         }
-        let dt = state.t - self.old_t;
+        let dt = state.t.clone() - self.old_t.clone();
         let theta = if dt == Eqn::T::zero() {
             Eqn::T::one()
         } else {
-            (t - self.old_t) / dt
+            (t - self.old_t.clone()) / dt
         };
 
         if let Some(beta) = self.tableau.beta() {
@@ -695,6 +744,39 @@ where
     }
 }
 
+impl<M, Eqn, LS> StepChecks<Eqn> for Sdirk<M, Eqn, LS>
+where
+    LS: LinearSolver<SdirkCallable<Eqn>>,
+    M: DenseMatrix<T = Eqn::T, V = Eqn::V>,
+    Eqn: OdeEquations,
+    for<'a> &'a Eqn::V: VectorRef<Eqn::V>,
+    for<'a> &'a Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn root_finder(&self) -> Option<&RootFinder<Eqn::V>> {
+        self.root_finder.as_ref()
+    }
+
+    fn tstop_mut(
+        &mut self,
+    ) -> (
+        &mut OdeSolverState<Eqn::V>,
+        &mut Option<Eqn::T>,
+        &mut Eqn::T,
+    ) {
+        (
+            self.state.as_mut().unwrap(),
+            &mut self.tstop,
+            &mut self.t_compensation,
+        )
+    }
+
+    fn scale_step(&mut self, factor: Eqn::T) {
+        let state = self.state.as_mut().unwrap();
+        state.h *= factor;
+        self.nonlinear_solver.problem().f.set_h(state.h.clone());
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
@@ -704,17 +786,24 @@ mod test {
                     exponential_decay_problem, exponential_decay_problem_sens,
                     exponential_decay_problem_with_root,
                 },
+                exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem,
+                harmonic_oscillator::harmonic_oscillator_problem,
                 robertson::robertson,
                 robertson_ode::robertson_ode,
                 robertson_sens::robertson_sens,
             },
             tests::{
-                test_interpolate, test_no_set_problem, test_ode_solver, test_state_mut,
-                test_state_mut_on_problem,
+                test_backward_exponential_decay, test_breakpoints_exponential_decay,
+                test_capabilities, test_interpolate, test_invariant_harmonic_oscillator,
+                test_no_set_problem, test_ode_solver, test_root_fn_exponential_decay,
+                test_solution_bound_exponential_decay, test_solve_sweep_exponential_decay,
+                test_state_mut, test_state_mut_on_problem,
             },
         },
-        NalgebraLU, OdeEquations, Op, Sdirk, Tableau,
+        NalgebraLU, OdeBuilder, OdeEquations, OdeSolverMethod, OdeSolverState, Op, Sdirk,
+        StepControl, Tableau,
     };
+    use nalgebra::DVector;
 
     use num_traits::abs;
 
@@ -735,19 +824,103 @@ mod test {
         test_interpolate::<M, _>(Sdirk::<M, _, _>::new(tableau, NalgebraLU::default()));
     }
 
+    #[test]
+    fn sdirk_test_capabilities_robertson() {
+        let (problem, _soln) = robertson::<M>();
+        let tableau = Tableau::<M>::esdirk34();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_capabilities(s, problem);
+    }
+
     #[test]
     fn sdirk_test_state_mut_exponential_decay() {
-        let (p, soln) = exponential_decay_problem::<M>(false);
+        let (p, soln) = exponential_decay_problem::<M>();
         let tableau = Tableau::<M>::tr_bdf2();
         let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
         test_state_mut_on_problem(s, p, soln);
     }
 
+    #[test]
+    fn sdirk_test_solve_sweep_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_solve_sweep_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_solution_bound_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_solution_bound_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_backward_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::esdirk34();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_backward_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_backward_fixed_step_exponential_decay() {
+        let (mut p, _soln) = exponential_decay_problem::<M>();
+        p.options.step_control = StepControl::Fixed(0.1);
+        let tableau = Tableau::<M>::tr_bdf2();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_backward_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_breakpoints_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_breakpoints_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_root_fn_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        let tableau = Tableau::<M>::esdirk34();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_root_fn_exponential_decay(s, p);
+    }
+
+    #[test]
+    fn sdirk_test_invariant_harmonic_oscillator() {
+        let (p, soln) = harmonic_oscillator_problem::<M>();
+        let tableau = Tableau::<M>::tr_bdf2();
+        let s = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        test_invariant_harmonic_oscillator(s, p, soln);
+    }
+
+    #[test]
+    fn sdirk_nonlinear_solver_statistics() {
+        let tableau = Tableau::<M>::esdirk34();
+        let mut s = Sdirk::new(tableau, NalgebraLU::default());
+        let (problem, soln) = robertson_ode::<M>();
+        let t = soln.solution_points.last().unwrap().t;
+        s.solve(&problem, t).unwrap();
+        let stats = s.get_statistics();
+        let nls = stats.nonlinear_solver;
+        // there is a nonlinear solve for each implicit stage of each attempted step
+        assert!(nls.number_of_solves >= 3 * stats.number_of_steps);
+        assert!(nls.number_of_iterations >= nls.number_of_solves);
+        assert_eq!(
+            nls.number_of_failures,
+            stats.number_of_nonlinear_solver_fails
+        );
+        assert!(nls.average_convergence_rate().is_some());
+    }
+
     #[test]
     fn test_tr_bdf2_nalgebra_exponential_decay() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -757,7 +930,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 116
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.005848035476425734
-        final_step_size: 0.3808530346209797
+        final_step_size: 0.38085303462013687
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -771,7 +944,7 @@ mod test {
     fn test_tr_bdf2_nalgebra_exponential_decay_sens() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem_sens::<M>(false);
+        let (problem, soln) = exponential_decay_problem_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -781,7 +954,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 464
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.005848035476425734
-        final_step_size: 0.22851673033949357
+        final_step_size: 0.22851673033960335
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -795,7 +968,7 @@ mod test {
     fn test_esdirk34_nalgebra_exponential_decay() {
         let tableau = Tableau::<M>::esdirk34();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -805,7 +978,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 78
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.02114742526881128
-        final_step_size: 0.9531112013867072
+        final_step_size: 0.953111201385571
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -819,7 +992,7 @@ mod test {
     fn test_esdirk34_nalgebra_exponential_decay_sens() {
         let tableau = Tableau::<M>::esdirk34();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem_sens::<M>(false);
+        let (problem, soln) = exponential_decay_problem_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -829,7 +1002,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 264
         number_of_nonlinear_solver_fails: 0
         initial_step_size: 0.02114742526881128
-        final_step_size: 0.5893196907333161
+        final_step_size: 0.5893196907330434
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -839,25 +1012,73 @@ mod test {
         "###);
     }
 
+    #[test]
+    fn test_tr_bdf2_nalgebra_exponential_decay_algebraic() {
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut s = Sdirk::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_with_algebraic_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 7
+        number_of_steps: 6
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 24
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.0008450237215972955
+        final_step_size: 0.3206008007430424
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 28
+        number_of_jac_muls: 6
+        number_of_matrix_evals: 2
+        "###);
+    }
+
+    #[test]
+    fn test_esdirk34_nalgebra_exponential_decay_algebraic() {
+        let tableau = Tableau::<M>::esdirk34();
+        let mut s = Sdirk::new(tableau, NalgebraLU::default());
+        let (problem, soln) = exponential_decay_with_algebraic_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 5
+        number_of_steps: 4
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 24
+        number_of_nonlinear_solver_fails: 0
+        initial_step_size: 0.004956232549086462
+        final_step_size: 0.8317679377186903
+        "###);
+        insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
+        ---
+        number_of_calls: 28
+        number_of_jac_muls: 6
+        number_of_matrix_evals: 2
+        "###);
+    }
+
     #[test]
     fn test_tr_bdf2_nalgebra_robertson() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = robertson::<M>(false);
+        let (problem, soln) = robertson::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
-        number_of_linear_solver_setups: 429
-        number_of_steps: 410
-        number_of_error_test_failures: 6
-        number_of_nonlinear_solver_iterations: 3032
+        number_of_linear_solver_setups: 243
+        number_of_steps: 230
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 2381
         number_of_nonlinear_solver_fails: 12
         initial_step_size: 0.0005245814253712257
-        final_step_size: 38234484245.73098
+        final_step_size: 56982937055.220314
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 2993
+        number_of_calls: 2323
         number_of_jac_muls: 42
         number_of_matrix_evals: 14
         "###);
@@ -867,22 +1088,22 @@ mod test {
     fn test_tr_bdf2_nalgebra_robertson_sens() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = robertson_sens::<M>(false);
+        let (problem, soln) = robertson_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
-        number_of_linear_solver_setups: 914
-        number_of_steps: 891
-        number_of_error_test_failures: 7
-        number_of_nonlinear_solver_iterations: 17062
+        number_of_linear_solver_setups: 266
+        number_of_steps: 247
+        number_of_error_test_failures: 3
+        number_of_nonlinear_solver_iterations: 6770
         number_of_nonlinear_solver_fails: 15
         initial_step_size: 0.0005245814253712257
-        final_step_size: 16695887030.215992
+        final_step_size: 12288632830.758913
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 4809
-        number_of_jac_muls: 12347
+        number_of_calls: 2230
+        number_of_jac_muls: 4635
         number_of_matrix_evals: 20
         "###);
     }
@@ -891,23 +1112,23 @@ mod test {
     fn test_esdirk34_nalgebra_robertson() {
         let tableau = Tableau::<M>::esdirk34();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = robertson::<M>(false);
+        let (problem, soln) = robertson::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
-        number_of_linear_solver_setups: 289
-        number_of_steps: 266
-        number_of_error_test_failures: 3
-        number_of_nonlinear_solver_iterations: 2889
-        number_of_nonlinear_solver_fails: 19
+        number_of_linear_solver_setups: 157
+        number_of_steps: 136
+        number_of_error_test_failures: 0
+        number_of_nonlinear_solver_iterations: 2136
+        number_of_nonlinear_solver_fails: 20
         initial_step_size: 0.0034662483959892352
-        final_step_size: 47734821046.576515
+        final_step_size: 48955347443.50819
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 2848
-        number_of_jac_muls: 57
-        number_of_matrix_evals: 19
+        number_of_calls: 2086
+        number_of_jac_muls: 60
+        number_of_matrix_evals: 20
         "###);
     }
 
@@ -915,22 +1136,22 @@ mod test {
     fn test_esdirk34_nalgebra_robertson_sens() {
         let tableau = Tableau::<M>::esdirk34();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = robertson_sens::<M>(false);
+        let (problem, soln) = robertson_sens::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
-        number_of_linear_solver_setups: 489
-        number_of_steps: 461
-        number_of_error_test_failures: 3
-        number_of_nonlinear_solver_iterations: 13777
+        number_of_linear_solver_setups: 175
+        number_of_steps: 149
+        number_of_error_test_failures: 1
+        number_of_nonlinear_solver_iterations: 6354
         number_of_nonlinear_solver_fails: 24
         initial_step_size: 0.0034662483959892352
-        final_step_size: 23926664695.166664
+        final_step_size: 18100377856.390316
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
-        number_of_calls: 3989
-        number_of_jac_muls: 9909
+        number_of_calls: 2147
+        number_of_jac_muls: 4315
         number_of_matrix_evals: 26
         "###);
     }
@@ -939,7 +1160,7 @@ mod test {
     fn test_tr_bdf2_nalgebra_robertson_ode() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = robertson_ode::<M>(false);
+        let (problem, soln) = robertson_ode::<M>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -949,7 +1170,7 @@ mod test {
         number_of_nonlinear_solver_iterations: 2383
         number_of_nonlinear_solver_fails: 12
         initial_step_size: 0.00046734995811969143
-        final_step_size: 59513072650.62326
+        final_step_size: 59513072733.737236
         "###);
         insta::assert_yaml_snapshot!(problem.eqn.as_ref().rhs().statistics(), @r###"
         ---
@@ -963,15 +1184,89 @@ mod test {
     fn test_tstop_tr_bdf2() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem::<M>(false);
+        let (problem, soln) = exponential_decay_problem::<M>();
         test_ode_solver(&mut s, &problem, soln, None, true);
     }
 
+    #[test]
+    fn test_tr_bdf2_fixed_step_exponential_decay() {
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut s = Sdirk::new(tableau, NalgebraLU::default());
+        let (mut problem, soln) = exponential_decay_problem::<M>();
+        problem.options.step_control = StepControl::Fixed(0.3);
+        test_ode_solver(&mut s, &problem, soln, Some(1e-4), true);
+
+        // each unit interval between the stop times is covered by three steps of 0.3 and one shortened step of 0.1,
+        // after which the fixed step size is restored
+        let statistics = s.get_statistics();
+        assert_eq!(statistics.number_of_steps, 36);
+        assert_eq!(statistics.number_of_error_test_failures, 0);
+        assert_eq!(statistics.initial_step_size, 0.3);
+        assert!(abs(s.state().unwrap().h - 0.3) < 1e-12);
+    }
+
+    #[test]
+    fn test_tr_bdf2_fixed_step_convergence() {
+        // without error control the global error of the second order method is reduced by a factor of 4
+        // when the step size is halved
+        let errors = [0.2, 0.1].map(|h| {
+            let tableau = Tableau::<M>::tr_bdf2();
+            let mut s = Sdirk::new(tableau, NalgebraLU::default());
+            let (mut problem, soln) = exponential_decay_problem::<M>();
+            problem.options.step_control = StepControl::Fixed(h);
+            let point = soln.solution_points.last().unwrap();
+            let y = s.solve(&problem, point.t).unwrap().y.pop().unwrap();
+            (y - &point.state).norm()
+        });
+        let ratio = errors[0] / errors[1];
+        assert!(ratio > 3.5 && ratio < 4.5, "ratio = {}", ratio);
+    }
+
+    // error of the dense output at the middle of a single step of size h for dy/dt = -y^2, y(0) = 1, with solution
+    // y = 1 / (1 + t). Also checks that the dense output is continuous at the start and end of the step.
+    fn dense_output_error(tableau: Tableau<M>, h: f64) -> f64 {
+        let problem = OdeBuilder::new()
+            .rtol(1e-8)
+            .atol([1e-8])
+            .fixed_step(h)
+            .build_ode::<M, _, _, _>(
+                |x, _p, _t, y| y[0] = -x[0] * x[0],
+                |x, _p, _t, v, y| y[0] = -2.0 * x[0] * v[0],
+                |_p, _t| DVector::from_element(1, 1.0),
+            )
+            .unwrap();
+        let mut s = Sdirk::new(tableau, NalgebraLU::default());
+        let state = OdeSolverState::new(&problem, &s).unwrap();
+        s.set_problem(state, &problem);
+        s.step().unwrap();
+        assert!((s.interpolate(0.0).unwrap()[0] - 1.0).abs() < 1e-14);
+        let y1 = s.state().unwrap().y[0];
+        assert!((s.interpolate(h).unwrap()[0] - y1).abs() < 1e-8);
+        let t = 0.5 * h;
+        (s.interpolate(t).unwrap()[0] - 1.0 / (1.0 + t)).abs()
+    }
+
+    #[test]
+    fn test_tr_bdf2_dense_output_order() {
+        // the local error of the second order continuous extension is O(h^3)
+        let ratio = dense_output_error(Tableau::<M>::tr_bdf2(), 0.2)
+            / dense_output_error(Tableau::<M>::tr_bdf2(), 0.1);
+        assert!(ratio > 6.5 && ratio < 9.0, "ratio = {}", ratio);
+    }
+
+    #[test]
+    fn test_esdirk34_dense_output_order() {
+        // the local error of the third order continuous extension is O(h^4)
+        let ratio = dense_output_error(Tableau::<M>::esdirk34(), 0.1)
+            / dense_output_error(Tableau::<M>::esdirk34(), 0.05);
+        assert!(ratio > 13.0 && ratio < 18.0, "ratio = {}", ratio);
+    }
+
     #[test]
     fn test_root_finder_tr_bdf2() {
         let tableau = Tableau::<M>::tr_bdf2();
         let mut s = Sdirk::new(tableau, NalgebraLU::default());
-        let (problem, soln) = exponential_decay_problem_with_root::<M>(false);
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
         let y = test_ode_solver(&mut s, &problem, soln, None, false);
         assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
     }
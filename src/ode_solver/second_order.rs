@@ -0,0 +1,162 @@
+use std::rc::Rc;
+
+use crate::{op::ConstantOp, scalar::Scalar, LinearOp, Matrix, UnitCallable, Vector};
+
+/// this is the trait that defines a linear second-order ODE system of the form
+///
+/// $$
+///  M \frac{d^2y}{dt^2} + C \frac{dy}{dt} + K y = f(t)
+///  y(t_0) = y_0(t_0)
+///  \frac{dy}{dt}(t_0) = v_0(t_0)
+/// $$
+///
+/// as found in structural dynamics (e.g. the semi-discretisation of a finite element model). Solvers such as [crate::GeneralizedAlpha]
+/// integrate this system directly, rather than rewriting it as a first-order system of twice the size.
+///
+/// The equations are defined by:
+/// - the mass matrix `M`, which is given as a [LinearOp] using the `Mass` associated type and the [Self::mass] function,
+/// - the optional damping matrix `C`, which is given as a [LinearOp] using the `Damping` associated type and the [Self::damping] function,
+/// - the stiffness matrix `K`, which is given as a [LinearOp] using the `Stiffness` associated type and the [Self::stiffness] function,
+/// - the forcing `f(t)`, which is given as a [ConstantOp] using the `Forcing` associated type and the [Self::forcing] function,
+/// - the initial displacement `y_0(t_0)` and velocity `v_0(t_0)`, which are given using the [Self::init] and [Self::init_velocity] functions.
+///
+/// The matrices `M`, `C` and `K` are assumed to be constant in time.
+pub trait SecondOrderOde {
+    type T: Scalar;
+    type V: Vector<T = Self::T>;
+    type M: Matrix<T = Self::T, V = Self::V>;
+    type Mass: LinearOp<M = Self::M, V = Self::V, T = Self::T>;
+    type Damping: LinearOp<M = Self::M, V = Self::V, T = Self::T>;
+    type Stiffness: LinearOp<M = Self::M, V = Self::V, T = Self::T>;
+    type Forcing: ConstantOp<M = Self::M, V = Self::V, T = Self::T>;
+    type Init: ConstantOp<M = Self::M, V = Self::V, T = Self::T>;
+    type InitVelocity: ConstantOp<M = Self::M, V = Self::V, T = Self::T>;
+
+    /// returns the mass matrix `M` as a [LinearOp]
+    fn mass(&self) -> &Rc<Self::Mass>;
+
+    /// returns the damping matrix `C` as a [LinearOp], or `None` if the system is undamped
+    fn damping(&self) -> Option<&Rc<Self::Damping>>;
+
+    /// returns the stiffness matrix `K` as a [LinearOp]
+    fn stiffness(&self) -> &Rc<Self::Stiffness>;
+
+    /// returns the forcing `f(t)` as a [ConstantOp]
+    fn forcing(&self) -> &Rc<Self::Forcing>;
+
+    /// returns the initial displacement, i.e. `y(t)`, where `t` is the initial time
+    fn init(&self) -> &Rc<Self::Init>;
+
+    /// returns the initial velocity, i.e. `dy/dt(t)`, where `t` is the initial time
+    fn init_velocity(&self) -> &Rc<Self::InitVelocity>;
+}
+
+/// This struct implements the [SecondOrderOde] trait for a given mass, optional damping, stiffness, forcing and initial condition ops.
+/// The [crate::op::matrix::MatrixOp] struct can be used to give any of the matrices directly.
+pub struct SecondOrderOdeEquations<
+    M,
+    Mass,
+    Stiffness,
+    Forcing,
+    Init,
+    InitVelocity,
+    Damping = UnitCallable<M>,
+> where
+    M: Matrix,
+    Mass: LinearOp<M = M, V = M::V, T = M::T>,
+    Damping: LinearOp<M = M, V = M::V, T = M::T>,
+    Stiffness: LinearOp<M = M, V = M::V, T = M::T>,
+    Forcing: ConstantOp<M = M, V = M::V, T = M::T>,
+    Init: ConstantOp<M = M, V = M::V, T = M::T>,
+    InitVelocity: ConstantOp<M = M, V = M::V, T = M::T>,
+{
+    mass: Rc<Mass>,
+    damping: Option<Rc<Damping>>,
+    stiffness: Rc<Stiffness>,
+    forcing: Rc<Forcing>,
+    init: Rc<Init>,
+    init_velocity: Rc<InitVelocity>,
+}
+
+impl<M, Mass, Stiffness, Forcing, Init, InitVelocity, Damping>
+    SecondOrderOdeEquations<M, Mass, Stiffness, Forcing, Init, InitVelocity, Damping>
+where
+    M: Matrix,
+    Mass: LinearOp<M = M, V = M::V, T = M::T>,
+    Damping: LinearOp<M = M, V = M::V, T = M::T>,
+    Stiffness: LinearOp<M = M, V = M::V, T = M::T>,
+    Forcing: ConstantOp<M = M, V = M::V, T = M::T>,
+    Init: ConstantOp<M = M, V = M::V, T = M::T>,
+    InitVelocity: ConstantOp<M = M, V = M::V, T = M::T>,
+{
+    pub fn new(
+        mass: Rc<Mass>,
+        damping: Option<Rc<Damping>>,
+        stiffness: Rc<Stiffness>,
+        forcing: Rc<Forcing>,
+        init: Rc<Init>,
+        init_velocity: Rc<InitVelocity>,
+    ) -> Self {
+        let n = mass.nstates();
+        assert_eq!(stiffness.nstates(), n, "stiffness has the wrong size");
+        assert_eq!(forcing.nout(), n, "forcing has the wrong size");
+        assert_eq!(init.nout(), n, "initial displacement has the wrong size");
+        assert_eq!(
+            init_velocity.nout(),
+            n,
+            "initial velocity has the wrong size"
+        );
+        if let Some(damping) = &damping {
+            assert_eq!(damping.nstates(), n, "damping has the wrong size");
+        }
+        Self {
+            mass,
+            damping,
+            stiffness,
+            forcing,
+            init,
+            init_velocity,
+        }
+    }
+}
+
+impl<M, Mass, Stiffness, Forcing, Init, InitVelocity, Damping> SecondOrderOde
+    for SecondOrderOdeEquations<M, Mass, Stiffness, Forcing, Init, InitVelocity, Damping>
+where
+    M: Matrix,
+    Mass: LinearOp<M = M, V = M::V, T = M::T>,
+    Damping: LinearOp<M = M, V = M::V, T = M::T>,
+    Stiffness: LinearOp<M = M, V = M::V, T = M::T>,
+    Forcing: ConstantOp<M = M, V = M::V, T = M::T>,
+    Init: ConstantOp<M = M, V = M::V, T = M::T>,
+    InitVelocity: ConstantOp<M = M, V = M::V, T = M::T>,
+{
+    type T = M::T;
+    type V = M::V;
+    type M = M;
+    type Mass = Mass;
+    type Damping = Damping;
+    type Stiffness = Stiffness;
+    type Forcing = Forcing;
+    type Init = Init;
+    type InitVelocity = InitVelocity;
+
+    fn mass(&self) -> &Rc<Self::Mass> {
+        &self.mass
+    }
+    fn damping(&self) -> Option<&Rc<Self::Damping>> {
+        self.damping.as_ref()
+    }
+    fn stiffness(&self) -> &Rc<Self::Stiffness> {
+        &self.stiffness
+    }
+    fn forcing(&self) -> &Rc<Self::Forcing> {
+        &self.forcing
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        &self.init
+    }
+    fn init_velocity(&self) -> &Rc<Self::InitVelocity> {
+        &self.init_velocity
+    }
+}
@@ -154,11 +154,11 @@ where
 
     /// pre-compute S = f_p - M_p * dy/dt from the state
     pub fn update_state(&self, y: &Eqn::V, dy: &Eqn::V, t: Eqn::T) {
-        if self.rhs_sens.is_some() {
-            let mut rhs_sens = self.rhs_sens.as_ref().unwrap().borrow_mut();
+        if let Some(rhs_sens) = self.rhs_sens.as_ref() {
+            let mut rhs_sens = rhs_sens.borrow_mut();
             let mut mass_sens = self.mass_sens.as_ref().unwrap().borrow_mut();
             let mut sens = self.sens.borrow_mut();
-            self.eqn.rhs().sens_inplace(y, t, &mut rhs_sens);
+            self.eqn.rhs().sens_inplace(y, t.clone(), &mut rhs_sens);
             self.eqn.mass().unwrap().sens_inplace(dy, t, &mut mass_sens);
             sens.scale_add_and_assign(&rhs_sens, -Eqn::T::one(), &mass_sens);
         } else {
@@ -292,6 +292,9 @@ where
     fn init(&self) -> &Rc<Self::Init> {
         &self.init
     }
+    fn is_symmetric(&self) -> bool {
+        self.eqn.is_symmetric()
+    }
     fn set_params(&mut self, _p: Self::V) {
         panic!("Not implemented for SensEquations");
     }
@@ -313,7 +316,7 @@ mod tests {
     #[test]
     fn test_rhs_exponential() {
         // dy/dt = -ay (p = [a])
-        let (problem, _soln) = exponential_decay_problem_sens::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_problem_sens::<Mcpu>();
         let sens_eqn = SensEquations::new(&problem.eqn);
         let state = OdeSolverState {
             t: 0.0,
@@ -349,7 +352,7 @@ mod tests {
 
     #[test]
     fn test_rhs_exponential_algebraic() {
-        let (problem, _soln) = exponential_decay_with_algebraic_problem_sens::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_with_algebraic_problem_sens::<Mcpu>();
         let sens_eqn = SensEquations::new(&problem.eqn);
         let state = OdeSolverState {
             t: 0.0,
@@ -394,7 +397,7 @@ mod tests {
 
     #[test]
     fn test_rhs_robertson() {
-        let (problem, _soln) = robertson_sens::<Mcpu>(false);
+        let (problem, _soln) = robertson_sens::<Mcpu>();
         let sens_eqn = SensEquations::new(&problem.eqn);
         let state = OdeSolverState {
             t: 0.0,
@@ -0,0 +1,213 @@
+use rand::Rng;
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, OdeEquations,
+    OdeSolverMethod, OdeSolverProblem, Op, Vector,
+};
+
+/// First-order and total Sobol sensitivity indices for each parameter, along with the total variance of the output
+#[derive(Clone, Debug)]
+pub struct SobolIndices<T: Scalar> {
+    pub first_order: Vec<T>,
+    pub total: Vec<T>,
+    pub variance: T,
+}
+
+/// Variance-based (Sobol) global sensitivity analysis of a scalar output of the model, with the parameters varying uniformly over the
+/// hypercube given by `lower` and `upper` bounds.
+///
+/// The indices are estimated using the sampling scheme of Saltelli et al. (2010), with `nsamples` base samples. This requires
+/// `nsamples * (nparams + 2)` solves of the problem. The first-order index of a parameter is the fraction of the output variance due
+/// to that parameter alone, and the total index the fraction due to that parameter including all its interactions with the other parameters.
+#[derive(Clone, Debug)]
+pub struct SobolAnalysis<T: Scalar> {
+    pub lower: Vec<T>,
+    pub upper: Vec<T>,
+    pub nsamples: usize,
+}
+
+impl<T: Scalar> SobolAnalysis<T> {
+    pub fn new(lower: Vec<T>, upper: Vec<T>, nsamples: usize) -> Result<Self, PSError> {
+        if lower.len() != upper.len() {
+            return Err(PSError::DimensionMismatch {
+                name: "parameter bounds".to_string(),
+                expected: lower.len(),
+                found: upper.len(),
+            });
+        }
+        if lower.iter().zip(upper.iter()).any(|(l, u)| l > u) {
+            return Err(PSError::Other {
+                e: "lower parameter bounds must not be greater than the upper bounds".to_string(),
+            });
+        }
+        if nsamples < 2 {
+            return Err(PSError::Other {
+                e: "at least two samples are required".to_string(),
+            });
+        }
+        Ok(Self {
+            lower,
+            upper,
+            nsamples,
+        })
+    }
+
+    pub fn nparams(&self) -> usize {
+        self.lower.len()
+    }
+
+    /// Calculate the Sobol indices of the scalar output `output(y)`, where `y` is the solution of `problem` at time `t`.
+    /// The problem is solved for each sample using [OdeSolverMethod::solve_sweep]. On return the parameters of `problem` are set to the last sample.
+    pub fn analyse<Eqn, S, F, R>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<Eqn>,
+        t: T,
+        output: F,
+        rng: &mut R,
+    ) -> Result<SobolIndices<T>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+        F: Fn(&Eqn::V) -> T,
+        R: Rng + ?Sized,
+    {
+        let k = self.nparams();
+        if k != problem.eqn.rhs().nparams() {
+            return Err(PSError::DimensionMismatch {
+                name: "parameter bounds".to_string(),
+                expected: problem.eqn.rhs().nparams(),
+                found: k,
+            });
+        }
+        let n = self.nsamples;
+        let a = self.sample_matrix::<Eqn::V, R>(rng);
+        let b = self.sample_matrix::<Eqn::V, R>(rng);
+
+        // the samples are A, B and then each A_B^i (A with the ith column taken from B)
+        let mut params = Vec::with_capacity(n * (k + 2));
+        params.extend(a.iter().cloned());
+        params.extend(b.iter().cloned());
+        for i in 0..k {
+            params.extend(a.iter().zip(b.iter()).map(|(ai, bi)| {
+                let mut p = ai.clone();
+                p[i] = bi[i].clone();
+                p
+            }));
+        }
+        let f = solver
+            .solve_sweep(problem, &params, t)?
+            .iter()
+            .map(output)
+            .collect::<Vec<_>>();
+        let (fa, rest) = f.split_at(n);
+        let (fb, fab) = rest.split_at(n);
+
+        let nt = T::cast(n as f64);
+        let mean = fa
+            .iter()
+            .chain(fb.iter())
+            .fold(T::zero(), |acc, x| acc + x.clone())
+            / (nt.clone() + nt.clone());
+        let variance = fa.iter().chain(fb.iter()).fold(T::zero(), |acc, x| {
+            acc + (x.clone() - mean.clone()) * (x.clone() - mean.clone())
+        }) / (nt.clone() + nt.clone() - T::one());
+        let mut first_order = Vec::with_capacity(k);
+        let mut total = Vec::with_capacity(k);
+        for i in 0..k {
+            let fabi = &fab[i * n..(i + 1) * n];
+            let mut vi = T::zero();
+            let mut vti = T::zero();
+            for j in 0..n {
+                vi += fb[j].clone() * (fabi[j].clone() - fa[j].clone());
+                vti += (fa[j].clone() - fabi[j].clone()) * (fa[j].clone() - fabi[j].clone());
+            }
+            // a constant output has no variance to apportion between the parameters
+            if variance > T::zero() {
+                first_order.push(vi / nt.clone() / variance.clone());
+                total.push(vti / (nt.clone() + nt.clone()) / variance.clone());
+            } else {
+                first_order.push(T::zero());
+                total.push(T::zero());
+            }
+        }
+        Ok(SobolIndices {
+            first_order,
+            total,
+            variance,
+        })
+    }
+
+    fn sample_matrix<V: Vector<T = T>, R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<V> {
+        (0..self.nsamples)
+            .map(|_| {
+                let mut p = V::zeros(self.nparams());
+                for (i, (l, u)) in self.lower.iter().zip(self.upper.iter()).enumerate() {
+                    p[i] = l.clone() + (u.clone() - l.clone()) * T::cast(rng.gen::<f64>());
+                }
+                p
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{ode_solver::sobol::SobolAnalysis, Bdf, OdeBuilder};
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_sobol_additive() {
+        // y0 = exp(-p0 t) and y1 = p1, the third parameter has no effect
+        let mut problem = OdeBuilder::new()
+            .p([0.5, 0.5, 0.5])
+            .build_ode::<M, _, _, _>(
+                |x: &V, p: &V, _t, y: &mut V| {
+                    y[0] = -p[0] * x[0];
+                    y[1] = 0.0;
+                },
+                |_x: &V, p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = -p[0] * v[0];
+                    y[1] = 0.0;
+                },
+                |p: &V, _t| V::from_vec(vec![1.0, p[1]]),
+            )
+            .unwrap();
+        let mut solver = Bdf::default();
+        let analysis = SobolAnalysis::new(vec![0.0; 3], vec![1.0; 3], 2000).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let indices = analysis
+            .analyse(&mut solver, &mut problem, 1.0, |y| y[0] + y[1], &mut rng)
+            .unwrap();
+
+        // for an additive output the first-order and total indices are equal, V_i / V
+        let v0 = (1.0 - f64::exp(-2.0)) / 2.0 - (1.0 - f64::exp(-1.0)).powi(2);
+        let v1 = 1.0 / 12.0;
+        let expect = [v0 / (v0 + v1), v1 / (v0 + v1), 0.0];
+        assert!((indices.variance - (v0 + v1)).abs() < 0.01);
+        for (i, e) in expect.iter().enumerate() {
+            assert!(
+                (indices.first_order[i] - e).abs() < 0.05,
+                "first order {:?}",
+                indices.first_order
+            );
+            assert!(
+                (indices.total[i] - e).abs() < 0.05,
+                "total {:?}",
+                indices.total
+            );
+        }
+
+        assert!(SobolAnalysis::new(vec![0.0], vec![1.0, 1.0], 10).is_err());
+        assert!(SobolAnalysis::new(vec![1.0], vec![0.0], 10).is_err());
+        let analysis = SobolAnalysis::new(vec![0.0], vec![1.0], 10).unwrap();
+        assert!(analysis
+            .analyse(&mut solver, &mut problem, 1.0, |y| y[0], &mut rng)
+            .is_err());
+    }
+}
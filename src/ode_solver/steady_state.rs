@@ -0,0 +1,197 @@
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, ConstantOp,
+    NewtonNonlinearSolver, NonLinearOp, NonLinearSolver, OdeEquations, OdeSolverMethod,
+    OdeSolverProblem, OdeSolverState, Op, SolverProblem, Vector,
+};
+
+/// A steady state found by [SteadyStateSolver::solve].
+#[derive(Clone, Debug)]
+pub struct SteadyState<V: Vector> {
+    /// The steady state, i.e. the solution of `F(y, t_0) = 0`
+    pub y: V,
+    /// Number of time steps taken by the pseudo-transient continuation before the Newton iteration converged,
+    /// zero if the Newton iteration converged from the initial condition
+    pub nsteps: usize,
+}
+
+/// Finds a steady state (equilibrium) `y` of the equations, i.e. a solution of `F(y, t_0) = 0`, for example before running
+/// the dynamics from equilibrium. The right-hand side is evaluated at the initial time of the problem, so the equations
+/// should not depend on time.
+///
+/// A Newton iteration is first tried starting from the initial condition of the problem. If this diverges, the steady
+/// state is found by pseudo-transient continuation: the equations are integrated in time using the given ODE solver
+/// (e.g. [crate::Bdf]), whose step size grows as the solution approaches the steady state, until the weighted norm of the
+/// right-hand side is below [Self::rhs_tol]. The Newton iteration is then retried from the current state, and if it fails again
+/// the integration is continued.
+///
+/// For DAEs, the integration is done with the mass matrix so the solution stays on the algebraic constraints, and the
+/// Newton iteration solves the full set of equations (including the algebraic equations). In both cases the steady state
+/// must be isolated, i.e. the jacobian of the right-hand side must be non-singular at the steady state.
+#[derive(Clone, Debug)]
+pub struct SteadyStateSolver<T: Scalar> {
+    /// The pseudo-transient continuation switches back to the Newton iteration once the norm of the right-hand side,
+    /// weighted by the tolerances of the problem, is below this value (default 1e-2).
+    pub rhs_tol: T,
+    /// Maximum number of time steps of the pseudo-transient continuation before giving up (default 10000).
+    pub max_steps: usize,
+}
+
+impl<T: Scalar> Default for SteadyStateSolver<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Scalar> SteadyStateSolver<T> {
+    pub fn new() -> Self {
+        Self {
+            rhs_tol: T::cast(1e-2),
+            max_steps: 10000,
+        }
+    }
+
+    /// Find a steady state of the equations of `problem`, using `solver` for the pseudo-transient continuation if the Newton
+    /// iteration from the initial condition diverges. Any previous problem of `solver` is replaced.
+    /// Returns [PSError::SteadyStateNotFound] if no steady state is found within [Self::max_steps] time steps.
+    pub fn solve<Eqn, S>(
+        &self,
+        solver: &mut S,
+        problem: &OdeSolverProblem<Eqn>,
+    ) -> Result<SteadyState<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+    {
+        let t0 = problem.t0.clone();
+        if let Ok(y) = Self::newton(problem, problem.eqn.init().call(t0.clone())) {
+            return Ok(SteadyState { y, nsteps: 0 });
+        }
+
+        // pseudo-transient continuation
+        let state = OdeSolverState::new(problem, solver)?;
+        solver.set_problem(state, problem);
+        let mut f = Eqn::V::zeros(problem.eqn.rhs().nstates());
+        for nsteps in 1..=self.max_steps {
+            solver.step()?;
+            let y = &solver.state().unwrap().y;
+            problem.eqn.rhs().call_inplace(y, t0.clone(), &mut f);
+            let norm = f
+                .squared_norm(y, &problem.atol, problem.rtol.clone())
+                .sqrt();
+            if norm < self.rhs_tol {
+                if let Ok(y) = Self::newton(problem, y.clone()) {
+                    return Ok(SteadyState { y, nsteps });
+                }
+            }
+        }
+        Err(PSError::SteadyStateNotFound {
+            nsteps: self.max_steps,
+        })
+    }
+
+    /// Solve `F(y, t_0) = 0` using a Newton iteration starting from `y`
+    fn newton<Eqn>(problem: &OdeSolverProblem<Eqn>, mut y: Eqn::V) -> Result<Eqn::V, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+    {
+        let mut newton = NewtonNonlinearSolver::new(<Eqn::M as DefaultSolver>::default_solver());
+        newton.set_problem(&SolverProblem::new(
+            problem.eqn.rhs().clone(),
+            problem.atol.clone(),
+            problem.rtol.clone(),
+        ));
+        newton.solve_in_place(&mut y, problem.t0.clone())?;
+        if (0..y.len()).any(|i| y[i].is_nan()) {
+            return Err(PSError::MaxIterReached);
+        }
+        Ok(y)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::DVector;
+
+    use crate::{
+        errors::PSError, ode_solver::test_models::exponential_decay::exponential_decay_problem,
+        Bdf, NalgebraLU, OdeBuilder, Sdirk, SteadyStateSolver, Tableau, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_steady_state_newton() {
+        // the steady state of dy/dt = -ay is y = 0, found directly by the Newton iteration
+        let (problem, _soln) = exponential_decay_problem::<M>();
+        let ss = SteadyStateSolver::default()
+            .solve(&mut Bdf::default(), &problem)
+            .unwrap();
+        assert_eq!(ss.nsteps, 0);
+        ss.y.assert_eq_st(&V::zeros(2), 1e-8);
+    }
+
+    #[test]
+    fn test_steady_state_pseudo_transient() {
+        // dy/dt = -atan(y - 2), the Newton iteration diverges from y = 0 but the solution converges to y = 2
+        let problem = OdeBuilder::new()
+            .build_ode::<M, _, _, _>(
+                |x, _p, _t, y| y[0] = -(x[0] - 2.0).atan(),
+                |x, _p, _t, v, y| y[0] = -v[0] / (1.0 + (x[0] - 2.0).powi(2)),
+                |_p, _t| DVector::from_element(1, 0.0),
+            )
+            .unwrap();
+        let ss = SteadyStateSolver::default()
+            .solve(&mut Bdf::default(), &problem)
+            .unwrap();
+        assert!(ss.nsteps > 0);
+        ss.y.assert_eq_st(&V::from_element(1, 2.0), 1e-8);
+
+        let tableau = Tableau::<M>::tr_bdf2();
+        let mut sdirk = Sdirk::<M, _, _>::new(tableau, NalgebraLU::default());
+        let ss = SteadyStateSolver::default()
+            .solve(&mut sdirk, &problem)
+            .unwrap();
+        assert!(ss.nsteps > 0);
+        ss.y.assert_eq_st(&V::from_element(1, 2.0), 1e-8);
+
+        let solver = SteadyStateSolver {
+            max_steps: 2,
+            ..Default::default()
+        };
+        assert!(matches!(
+            solver.solve(&mut Bdf::default(), &problem),
+            Err(PSError::SteadyStateNotFound { nsteps: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_steady_state_dae() {
+        // dy/dt = -atan(y - 2)
+        // 0 = z - y
+        let problem = OdeBuilder::new()
+            .build_ode_with_mass::<M, _, _, _, _>(
+                |x, _p, _t, y| {
+                    y[0] = -(x[0] - 2.0).atan();
+                    y[1] = x[1] - x[0];
+                },
+                |x, _p, _t, v, y| {
+                    y[0] = -v[0] / (1.0 + (x[0] - 2.0).powi(2));
+                    y[1] = v[1] - v[0];
+                },
+                |v, _p, _t, beta, y| {
+                    y[0] = v[0] + beta * y[0];
+                    y[1] *= beta;
+                },
+                |_p, _t| DVector::from_element(2, 0.0),
+            )
+            .unwrap();
+        let ss = SteadyStateSolver::default()
+            .solve(&mut Bdf::default(), &problem)
+            .unwrap();
+        assert!(ss.nsteps > 0);
+        ss.y.assert_eq_st(&V::from_element(2, 2.0), 1e-8);
+    }
+}
@@ -19,7 +19,7 @@ use sundials_sys::{
 use crate::{
     matrix::sparsity::MatrixSparsityRef, scale, vector::sundials::get_suncontext, LinearOp, Matrix,
     NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem, OdeSolverState,
-    OdeSolverStopReason, Op, SundialsMatrix, SundialsVector, Vector,
+    OdeSolverStopReason, Op, SolverCapabilities, SundialsMatrix, SundialsVector, Vector,
 };
 
 pub fn sundials_check(retval: c_int) -> Result<()> {
@@ -293,6 +293,22 @@ where
         1
     }
 
+    fn capabilities(&self) -> SolverCapabilities {
+        SolverCapabilities {
+            mass_matrix: true,
+            singular_mass_matrix: true,
+            roots: false,
+            sensitivities: false,
+            stiff: true,
+            max_order: 5,
+            dense_output: true,
+            fixed_step: false,
+            nonlinear_rhs: true,
+            backward: false,
+            invariants: false,
+        }
+    }
+
     fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
         self.is_state_modified = true;
         self.state.as_mut()
@@ -397,7 +413,10 @@ where
         match retval {
             IDA_SUCCESS => Ok(OdeSolverStopReason::InternalTimestep),
             IDA_TSTOP_RETURN => Ok(OdeSolverStopReason::TstopReached),
-            IDA_ROOT_RETURN => Ok(OdeSolverStopReason::RootFound(state.t)),
+            IDA_ROOT_RETURN => Ok(OdeSolverStopReason::RootFound {
+                index: 0,
+                t: state.t,
+            }),
             IDA_MEM_NULL => Err(anyhow!("The ida_mem argument was NULL.")),
             IDA_ILL_INPUT => Err(anyhow!("One of the inputs to IDASolve() was illegal, or some other input to the solver was either illegal or missing.")),
             IDA_TOO_MUCH_WORK => Err(anyhow!("The solver took mxstep internal steps but could not reach tout.")),
@@ -463,7 +482,7 @@ mod test {
     #[test]
     fn test_sundials_exponential_decay() {
         let mut s = crate::SundialsIda::default();
-        let (problem, soln) = exponential_decay_problem::<crate::SundialsMatrix>(false);
+        let (problem, soln) = exponential_decay_problem::<crate::SundialsMatrix>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -486,7 +505,7 @@ mod test {
     #[test]
     fn test_sundials_robertson() {
         let mut s = crate::SundialsIda::default();
-        let (problem, soln) = robertson::<crate::SundialsMatrix>(false);
+        let (problem, soln) = robertson::<crate::SundialsMatrix>();
         test_ode_solver(&mut s, &problem, soln, None, false);
         insta::assert_yaml_snapshot!(s.get_statistics(), @r###"
         ---
@@ -0,0 +1,453 @@
+use num_traits::{abs, One, Zero};
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, matrix::MatrixRef,
+    op::bdf::BdfCallable, scale, vector::DefaultDenseMatrix, Adams, Bdf, FixedPointNonlinearSolver,
+    NewtonNonlinearSolver, NonLinearOp, OdeEquations, OdeSolverMethod, OdeSolverProblem,
+    OdeSolverState, OdeSolverStopReason, Scalar, SolverCapabilities, Vector, VectorRef,
+};
+
+/// The method currently used by a [SwitchingSolver].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActiveMethod {
+    NonStiff,
+    Stiff,
+}
+
+/// An ODE solver that automatically switches between a non-stiff and a stiff method, in the spirit of LSODA \[1\].
+///
+/// The integration starts with the non-stiff method (by default [Adams]). After each accepted step the spectral radius
+/// `rho` of the jacobian of the rhs is estimated using a few iterations of the power method (warm-started from the
+/// previous estimate), and the stiffness indicator `h * rho` is compared with the stability boundary of the non-stiff method
+/// (see [Self::set_stability_size]):
+///
+/// - if the non-stiff method has been running with a step size close to its stability boundary for several consecutive
+///   steps, its step size is limited by stability rather than accuracy and the problem is considered stiff,
+/// - if the stiff method (by default [Bdf]) has been taking steps well within the stability boundary of the non-stiff
+///   method for several consecutive steps, the problem is considered non-stiff.
+///
+/// When switching, the current state (`t`, `y`, `dy` and the step size `h`) is transferred to the other method,
+/// which restarts its history (e.g. the difference array) at first order from this state.
+/// The switch is done at the start of the following step, so the solution can still be interpolated within the last step.
+///
+/// Restrictions:
+/// - The problem must be supported by both methods (e.g. no mass matrix or sensitivities for [Adams]).
+///
+/// # References
+///
+/// \[1\] Petzold, L. (1983). Automatic selection of methods for solving stiff and nonstiff systems of ordinary differential equations. SIAM journal on scientific and statistical computing, 4(1), 136-148.
+pub struct SwitchingSolver<Eqn, NonStiff, Stiff>
+where
+    Eqn: OdeEquations,
+    NonStiff: OdeSolverMethod<Eqn>,
+    Stiff: OdeSolverMethod<Eqn>,
+{
+    nonstiff: NonStiff,
+    stiff: Stiff,
+    active: ActiveMethod,
+    problem: Option<OdeSolverProblem<Eqn>>,
+    eigenvector: Eqn::V,
+    spectral_radius: Eqn::T,
+    stability_size: Eqn::T,
+    stiff_count: usize,
+    nonstiff_count: usize,
+    switch_pending: bool,
+    number_of_switches: usize,
+    tstop: Option<Eqn::T>,
+}
+
+impl<Eqn> Default
+    for SwitchingSolver<
+        Eqn,
+        Adams<<Eqn::V as DefaultDenseMatrix>::M, Eqn, FixedPointNonlinearSolver<BdfCallable<Eqn>>>,
+        Bdf<
+            <Eqn::V as DefaultDenseMatrix>::M,
+            Eqn,
+            NewtonNonlinearSolver<
+                BdfCallable<Eqn>,
+                <Eqn::M as DefaultSolver>::LS<BdfCallable<Eqn>>,
+            >,
+        >,
+    >
+where
+    Eqn: OdeEquations,
+    Eqn::M: DefaultSolver,
+    Eqn::V: DefaultDenseMatrix,
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    fn default() -> Self {
+        Self::new(Adams::default(), Bdf::default())
+    }
+}
+
+impl<Eqn, NonStiff, Stiff> SwitchingSolver<Eqn, NonStiff, Stiff>
+where
+    Eqn: OdeEquations,
+    NonStiff: OdeSolverMethod<Eqn>,
+    Stiff: OdeSolverMethod<Eqn>,
+{
+    /// number of power iterations used to update the spectral radius estimate after each step
+    const POWER_ITERATIONS: usize = 3;
+    /// switch to the stiff method if `h * rho > STIFF_TOL * stability_size` for more than this many consecutive steps
+    const MAX_STIFF_STEPS: usize = 10;
+    /// switch to the non-stiff method if `h * rho < NONSTIFF_TOL * stability_size` for more than this many consecutive steps
+    const MAX_NONSTIFF_STEPS: usize = 3;
+    const STIFF_TOL: f64 = 0.9;
+    const NONSTIFF_TOL: f64 = 0.5;
+
+    pub fn new(nonstiff: NonStiff, stiff: Stiff) -> Self {
+        let n = 1;
+        Self {
+            nonstiff,
+            stiff,
+            active: ActiveMethod::NonStiff,
+            problem: None,
+            eigenvector: <Eqn::V as Vector>::zeros(n),
+            spectral_radius: Eqn::T::zero(),
+            stability_size: Eqn::T::one(),
+            stiff_count: 0,
+            nonstiff_count: 0,
+            switch_pending: false,
+            number_of_switches: 0,
+            tstop: None,
+        }
+    }
+
+    /// Set the size of the stability region of the non-stiff method along the negative real axis (default 1.0),
+    /// i.e. the largest `h * rho` for which the non-stiff method is expected to be stable.
+    pub fn set_stability_size(&mut self, stability_size: Eqn::T) {
+        self.stability_size = stability_size;
+    }
+
+    /// The method that is currently being used to take steps.
+    pub fn active_method(&self) -> ActiveMethod {
+        self.active
+    }
+
+    /// The number of times the solver has switched between the non-stiff and stiff methods.
+    pub fn number_of_switches(&self) -> usize {
+        self.number_of_switches
+    }
+
+    /// The latest estimate of the spectral radius of the jacobian of the rhs.
+    pub fn spectral_radius(&self) -> Eqn::T {
+        self.spectral_radius.clone()
+    }
+
+    pub fn nonstiff_solver(&self) -> &NonStiff {
+        &self.nonstiff
+    }
+
+    pub fn stiff_solver(&self) -> &Stiff {
+        &self.stiff
+    }
+
+    fn active_solver(&self) -> &dyn OdeSolverMethod<Eqn> {
+        match self.active {
+            ActiveMethod::NonStiff => &self.nonstiff,
+            ActiveMethod::Stiff => &self.stiff,
+        }
+    }
+
+    fn active_solver_mut(&mut self) -> &mut dyn OdeSolverMethod<Eqn> {
+        match self.active {
+            ActiveMethod::NonStiff => &mut self.nonstiff,
+            ActiveMethod::Stiff => &mut self.stiff,
+        }
+    }
+
+    // update the spectral radius estimate using the power method on the jacobian of the rhs at the current state
+    fn update_spectral_radius(&mut self) {
+        let eqn = self.problem.as_ref().unwrap().eqn.clone();
+        let (y, t) = {
+            let state = self.active_solver().state().unwrap();
+            (state.y.clone(), state.t.clone())
+        };
+        let mut tmp = <Eqn::V as Vector>::zeros(y.len());
+        for _ in 0..Self::POWER_ITERATIONS {
+            eqn.rhs()
+                .jac_mul_inplace(&y, t.clone(), &self.eigenvector, &mut tmp);
+            let norm = tmp.norm();
+            if norm == Eqn::T::zero() {
+                // the jacobian is zero along this direction, restart the iteration next time
+                self.spectral_radius = Eqn::T::zero();
+                self.eigenvector.fill(Eqn::T::one());
+                self.eigenvector *= scale(Eqn::T::one() / self.eigenvector.norm());
+                return;
+            }
+            self.spectral_radius = norm.clone();
+            self.eigenvector.copy_from(&tmp);
+            self.eigenvector *= scale(Eqn::T::one() / norm);
+        }
+    }
+
+    // check the stiffness indicator h * rho, and schedule a switch if required
+    fn check_stiffness(&mut self) {
+        self.update_spectral_radius();
+        let h = self.active_solver().state().unwrap().h.clone();
+        let indicator = abs(h) * self.spectral_radius.clone();
+        match self.active {
+            ActiveMethod::NonStiff => {
+                if indicator > Eqn::T::cast(Self::STIFF_TOL) * self.stability_size.clone() {
+                    self.stiff_count += 1;
+                } else {
+                    self.stiff_count = 0;
+                }
+                if self.stiff_count > Self::MAX_STIFF_STEPS {
+                    self.switch_pending = true;
+                }
+            }
+            ActiveMethod::Stiff => {
+                if indicator < Eqn::T::cast(Self::NONSTIFF_TOL) * self.stability_size.clone() {
+                    self.nonstiff_count += 1;
+                } else {
+                    self.nonstiff_count = 0;
+                }
+                if self.nonstiff_count > Self::MAX_NONSTIFF_STEPS {
+                    self.switch_pending = true;
+                }
+            }
+        }
+    }
+
+    // transfer the current state to the other method and make it the active method
+    fn switch(&mut self) -> Result<(), PSError> {
+        let problem = self.problem.clone().unwrap();
+        let mut state = self.active_solver_mut().take_state().unwrap();
+
+        // the stored derivative may only be an approximation (e.g. from a difference array), so recompute it
+        if problem.eqn.mass().is_none() {
+            problem
+                .eqn
+                .rhs()
+                .call_inplace(&state.y, state.t.clone(), &mut state.dy);
+        }
+
+        self.active = match self.active {
+            ActiveMethod::NonStiff => ActiveMethod::Stiff,
+            ActiveMethod::Stiff => ActiveMethod::NonStiff,
+        };
+        self.active_solver_mut().set_problem(state, &problem);
+        if let Some(tstop) = self.tstop.clone() {
+            self.active_solver_mut().set_stop_time(tstop)?;
+        }
+        self.switch_pending = false;
+        self.stiff_count = 0;
+        self.nonstiff_count = 0;
+        self.number_of_switches += 1;
+        Ok(())
+    }
+}
+
+impl<Eqn, NonStiff, Stiff> OdeSolverMethod<Eqn> for SwitchingSolver<Eqn, NonStiff, Stiff>
+where
+    Eqn: OdeEquations,
+    NonStiff: OdeSolverMethod<Eqn>,
+    Stiff: OdeSolverMethod<Eqn>,
+{
+    fn problem(&self) -> Option<&OdeSolverProblem<Eqn>> {
+        self.problem.as_ref()
+    }
+
+    fn order(&self) -> usize {
+        self.active_solver().order()
+    }
+
+    fn capabilities(&self) -> SolverCapabilities {
+        let nonstiff = self.nonstiff.capabilities();
+        let stiff = self.stiff.capabilities();
+        SolverCapabilities {
+            mass_matrix: nonstiff.mass_matrix && stiff.mass_matrix,
+            singular_mass_matrix: nonstiff.singular_mass_matrix && stiff.singular_mass_matrix,
+            roots: nonstiff.roots && stiff.roots,
+            sensitivities: nonstiff.sensitivities && stiff.sensitivities,
+            stiff: stiff.stiff,
+            max_order: nonstiff.max_order.max(stiff.max_order),
+            dense_output: nonstiff.dense_output && stiff.dense_output,
+            fixed_step: nonstiff.fixed_step && stiff.fixed_step,
+            nonlinear_rhs: nonstiff.nonlinear_rhs && stiff.nonlinear_rhs,
+            backward: nonstiff.backward && stiff.backward,
+            invariants: nonstiff.invariants && stiff.invariants,
+        }
+    }
+
+    fn set_problem(&mut self, state: OdeSolverState<Eqn::V>, problem: &OdeSolverProblem<Eqn>) {
+        // always start with the non-stiff method, so clear any previous problem from the stiff method
+        self.stiff.take_state();
+
+        let nstates = state.y.len();
+        self.eigenvector = <Eqn::V as Vector>::zeros(nstates);
+        self.eigenvector.fill(Eqn::T::one());
+        self.eigenvector *= scale(Eqn::T::one() / self.eigenvector.norm());
+        self.spectral_radius = Eqn::T::zero();
+        self.stiff_count = 0;
+        self.nonstiff_count = 0;
+        self.switch_pending = false;
+        self.number_of_switches = 0;
+        self.tstop = None;
+        self.active = ActiveMethod::NonStiff;
+        self.problem = Some(problem.clone());
+        self.nonstiff.set_problem(state, problem);
+    }
+
+    fn step(&mut self) -> Result<OdeSolverStopReason<Eqn::T>, PSError> {
+        if self.problem.is_none() {
+            return Err(PSError::StateNotSet);
+        }
+        if self.switch_pending {
+            self.switch()?;
+        }
+        let reason = self.active_solver_mut().step()?;
+        if let OdeSolverStopReason::TstopReached = reason {
+            self.tstop = None;
+        }
+        self.check_stiffness();
+        Ok(reason)
+    }
+
+    fn set_stop_time(&mut self, tstop: Eqn::T) -> Result<(), PSError> {
+        self.active_solver_mut().set_stop_time(tstop.clone())?;
+        self.tstop = Some(tstop);
+        Ok(())
+    }
+
+    fn interpolate(&self, t: Eqn::T) -> Result<Eqn::V, PSError> {
+        self.active_solver().interpolate(t)
+    }
+
+    fn interpolate_sens(&self, t: Eqn::T) -> Result<Vec<Eqn::V>, PSError> {
+        self.active_solver().interpolate_sens(t)
+    }
+
+    fn state(&self) -> Option<&OdeSolverState<Eqn::V>> {
+        self.active_solver().state()
+    }
+
+    fn state_mut(&mut self) -> Option<&mut OdeSolverState<Eqn::V>> {
+        self.active_solver_mut().state_mut()
+    }
+
+    fn take_state(&mut self) -> Option<OdeSolverState<Eqn::V>> {
+        self.problem = None;
+        self.tstop = None;
+        self.switch_pending = false;
+        self.active_solver_mut().take_state()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ode_solver::{
+            test_models::{
+                exponential_decay::{
+                    exponential_decay_problem, exponential_decay_problem_with_root,
+                },
+                robertson::robertson,
+                robertson_ode::robertson_ode,
+            },
+            tests::{
+                test_interpolate, test_no_set_problem, test_ode_solver,
+                test_solution_bound_exponential_decay, test_solve_sweep_exponential_decay,
+                test_state_mut, test_state_mut_on_problem,
+            },
+        },
+        ActiveMethod, OdeSolverState, SwitchingSolver,
+    };
+
+    use num_traits::abs;
+
+    type M = nalgebra::DMatrix<f64>;
+
+    #[test]
+    fn switching_no_set_problem() {
+        test_no_set_problem::<M, _>(SwitchingSolver::default())
+    }
+    #[test]
+    fn switching_state_mut() {
+        test_state_mut::<M, _>(SwitchingSolver::default())
+    }
+    #[test]
+    fn switching_test_interpolate() {
+        test_interpolate::<M, _>(SwitchingSolver::default())
+    }
+
+    #[test]
+    fn switching_test_unsupported_problems() {
+        let (problem, _soln) = robertson::<M>();
+        let s = SwitchingSolver::default();
+        assert!(OdeSolverState::new(&problem, &s).is_err());
+    }
+
+    #[test]
+    fn switching_test_state_mut_exponential_decay() {
+        let (p, soln) = exponential_decay_problem::<M>();
+        test_state_mut_on_problem(SwitchingSolver::default(), p, soln);
+    }
+
+    #[test]
+    fn switching_test_solve_sweep_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_solve_sweep_exponential_decay(SwitchingSolver::default(), p);
+    }
+
+    #[test]
+    fn switching_test_solution_bound_exponential_decay() {
+        let (p, _soln) = exponential_decay_problem::<M>();
+        test_solution_bound_exponential_decay(SwitchingSolver::default(), p);
+    }
+
+    #[test]
+    fn switching_test_nalgebra_exponential_decay() {
+        let mut s = SwitchingSolver::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        // the problem is not stiff, so the non-stiff method is used throughout
+        assert_eq!(s.number_of_switches(), 0);
+        assert_eq!(s.active_method(), ActiveMethod::NonStiff);
+    }
+
+    #[test]
+    fn switching_test_nalgebra_robertson_ode() {
+        let mut s = SwitchingSolver::default();
+        let (problem, soln) = robertson_ode::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, false);
+        // the problem becomes stiff after the initial transient
+        assert!(s.number_of_switches() > 0);
+        assert_eq!(s.active_method(), ActiveMethod::Stiff);
+        insta::assert_yaml_snapshot!(s.stiff_solver().get_statistics(), @r###"
+        ---
+        number_of_linear_solver_setups: 80
+        number_of_steps: 295
+        number_of_error_test_failures: 1
+        number_of_nonlinear_solver_iterations: 832
+        number_of_nonlinear_solver_fails: 14
+        initial_step_size: 0.0005823008914929852
+        final_step_size: 15882284521.074724
+        "###);
+    }
+
+    #[test]
+    fn test_tstop_switching() {
+        let mut s = SwitchingSolver::default();
+        let (problem, soln) = exponential_decay_problem::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+    }
+
+    #[test]
+    fn test_tstop_switching_robertson_ode() {
+        let mut s = SwitchingSolver::default();
+        let (problem, soln) = robertson_ode::<M>();
+        test_ode_solver(&mut s, &problem, soln, None, true);
+        assert!(s.number_of_switches() > 0);
+    }
+
+    #[test]
+    fn test_root_finder_switching() {
+        let mut s = SwitchingSolver::default();
+        let (problem, soln) = exponential_decay_problem_with_root::<M>();
+        let y = test_ode_solver(&mut s, &problem, soln, None, false);
+        assert!(abs(y[0] - 0.6) < 1e-6, "y[0] = {}", y[0]);
+    }
+}
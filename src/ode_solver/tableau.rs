@@ -1,4 +1,4 @@
-use crate::{DenseMatrix, Vector};
+use crate::{DenseMatrix, Scalar, Vector};
 use num_traits::{One, Zero};
 
 /// A butcher tableau for a Runge-Kutta method.
@@ -19,7 +19,9 @@ use num_traits::{One, Zero};
 ///
 /// where `be` is the embedded method for error control and `d` is the difference between the main and embedded method.
 ///
-/// For continous extension methods, the beta matrix is also included.
+/// For continous extension methods, the beta matrix is also included. This gives the interpolation (dense output) weights
+/// `b_i(theta) = sum_j beta_ij theta^(j + 1)` for `theta` in `[0, 1]`, so that `y(t0 + theta h) = y0 + h sum_i b_i(theta) k_i`.
+/// The weights must reduce to `b` at `theta = 1`.
 ///
 pub struct Tableau<M: DenseMatrix> {
     a: M,
@@ -38,36 +40,36 @@ impl<M: DenseMatrix> Tableau<M> {
     /// continuous extension from :
     /// from Jørgensen, J. B., Kristensen, M. R., & Thomsen, P. G. (2018). A family of ESDIRK integration methods. arXiv preprint arXiv:1803.01613.
     pub fn tr_bdf2() -> Self {
-        let gamma = M::T::from(2.0 - 2.0_f64.sqrt());
-        let d = gamma / M::T::from(2.0);
-        let w = M::T::from(2.0_f64.sqrt() / 4.0);
+        let gamma = M::T::cast(2.0 - 2.0_f64.sqrt());
+        let d = gamma.clone() / M::T::cast(2.0);
+        let w = M::T::cast(2.0_f64.sqrt() / 4.0);
 
         let mut a = M::zeros(3, 3);
-        a[(1, 0)] = d;
-        a[(1, 1)] = d;
+        a[(1, 0)] = d.clone();
+        a[(1, 1)] = d.clone();
 
-        a[(2, 0)] = w;
-        a[(2, 1)] = w;
-        a[(2, 2)] = d;
+        a[(2, 0)] = w.clone();
+        a[(2, 1)] = w.clone();
+        a[(2, 2)] = d.clone();
 
-        let b = M::V::from_vec(vec![w, w, d]);
+        let b = M::V::from_vec(vec![w.clone(), w.clone(), d.clone()]);
         let b_hat = M::V::from_vec(vec![
-            (M::T::from(1.0) - w) / M::T::from(3.0),
-            (M::T::from(3.0) * w + M::T::from(1.0)) / M::T::from(3.0),
-            d / M::T::from(3.0),
+            (M::T::cast(1.0) - w.clone()) / M::T::cast(3.0),
+            (M::T::cast(3.0) * w.clone() + M::T::cast(1.0)) / M::T::cast(3.0),
+            d / M::T::cast(3.0),
         ]);
         let mut d = M::V::zeros(3);
         for i in 0..3 {
-            d[i] = b[i] - b_hat[i];
+            d[i] = b[i].clone() - b_hat[i].clone();
         }
 
         let mut beta = M::zeros(3, 2);
-        beta[(0, 0)] = M::T::from(2.0) * w;
-        beta[(0, 1)] = -w;
-        beta[(1, 0)] = M::T::from(2.0) * w;
-        beta[(1, 1)] = -w;
-        beta[(2, 0)] = gamma - M::T::from(1.0);
-        beta[(2, 1)] = M::T::from(2.0) * w;
+        beta[(0, 0)] = M::T::cast(2.0) * w.clone();
+        beta[(0, 1)] = -w.clone();
+        beta[(1, 0)] = M::T::cast(2.0) * w.clone();
+        beta[(1, 1)] = -w.clone();
+        beta[(2, 0)] = gamma.clone() - M::T::cast(1.0);
+        beta[(2, 1)] = M::T::cast(2.0) * w;
 
         let c = M::V::from_vec(vec![M::T::zero(), gamma, M::T::one()]);
 
@@ -78,38 +80,269 @@ impl<M: DenseMatrix> Tableau<M> {
 
     /// A third order ESDIRK method
     /// from Jørgensen, J. B., Kristensen, M. R., & Thomsen, P. G. (2018). A family of ESDIRK integration methods. arXiv preprint arXiv:1803.01613.
+    ///
+    /// continuous extension of order 3 that interpolates the stage derivatives, see below.
     pub fn esdirk34() -> Self {
         let mut a = M::zeros(4, 4);
-        let gamma = M::T::from(0.435_866_521_508_459);
-        a[(1, 0)] = gamma;
-        a[(1, 1)] = gamma;
+        let gamma = M::T::cast(0.435_866_521_508_459);
+        a[(1, 0)] = gamma.clone();
+        a[(1, 1)] = gamma.clone();
 
-        a[(2, 0)] = M::T::from(0.140_737_774_724_706_2);
-        a[(2, 1)] = M::T::from(-0.108_365_551_381_320_8);
-        a[(2, 2)] = gamma;
+        a[(2, 0)] = M::T::cast(0.140_737_774_724_706_2);
+        a[(2, 1)] = M::T::cast(-0.108_365_551_381_320_8);
+        a[(2, 2)] = gamma.clone();
 
-        a[(3, 0)] = M::T::from(0.102_399_400_619_911);
-        a[(3, 1)] = M::T::from(-0.376_878_452_255_556_1);
-        a[(3, 2)] = M::T::from(0.838_612_530_127_186_1);
+        a[(3, 0)] = M::T::cast(0.102_399_400_619_911);
+        a[(3, 1)] = M::T::cast(-0.376_878_452_255_556_1);
+        a[(3, 2)] = M::T::cast(0.838_612_530_127_186_1);
         a[(3, 3)] = gamma;
 
-        let b = M::V::from_vec(vec![a[(3, 0)], a[(3, 1)], a[(3, 2)], a[(3, 3)]]);
+        let b = M::V::from_vec(vec![
+            a[(3, 0)].clone(),
+            a[(3, 1)].clone(),
+            a[(3, 2)].clone(),
+            a[(3, 3)].clone(),
+        ]);
+
+        let c = M::V::from_vec(vec![
+            M::T::zero(),
+            M::T::cast(0.871_733_043_016_918),
+            M::T::cast(0.468_238_744_851_844_4),
+            M::T::one(),
+        ]);
+
+        let d = M::V::from_vec(vec![
+            M::T::cast(-0.054_625_497_240_413_94),
+            M::T::cast(-0.494_208_893_625_994_96),
+            M::T::cast(0.221_934_499_735_064_66),
+            M::T::cast(0.326_899_891_131_344_27),
+        ]);
+
+        // the derivative of the interpolant interpolates the stage derivatives at the times c_i, and the interpolant passes
+        // through the end of the step, which gives weights of degree 5 in theta. As the method has stage order 2
+        // (i.e. sum_j a_ij c_j = c_i^2 / 2) and b satisfies the quadrature conditions up to order 3, the weights satisfy the
+        // order conditions sum_i b_i(theta) c_i^m = theta^(m + 1) / (m + 1) for m <= 2, and so the extension is of order 3
+        let beta_coeffs: [[f64; 5]; 4] = [
+            [
+                1.0,
+                -3.892_781_554_827_741_4,
+                6.911_446_303_164_239,
+                -5.632_550_938_745_699,
+                1.716_285_591_029_112_4,
+            ],
+            [
+                0.0,
+                -21.034_336_311_047_69,
+                56.088_772_215_943_97,
+                -50.958_927_760_022_654,
+                15.527_613_402_870_818,
+            ],
+            [
+                0.0,
+                11.453_997_083_536_294,
+                -26.526_532_912_388_57,
+                22.884_137_224_804_19,
+                -6.972_988_865_824_726,
+            ],
+            [
+                0.0,
+                13.473_120_782_339_134,
+                -36.473_685_606_719_634,
+                33.707_341_473_964_16,
+                -10.270_910_128_075_203,
+            ],
+        ];
+        let mut beta = M::zeros(4, 5);
+        for (i, row) in beta_coeffs.iter().enumerate() {
+            for (j, &x) in row.iter().enumerate() {
+                beta[(i, j)] = M::T::cast(x);
+            }
+        }
+
+        Self::new(a, b, c, d, 3, Some(beta))
+    }
+
+    /// Dormand-Prince 5(4) explicit method, with the first-same-as-last property
+    /// from Dormand, J. R., & Prince, P. J. (1980). A family of embedded Runge-Kutta formulae. Journal of computational and applied mathematics, 6(1), 19-26.
+    ///
+    /// continuous extension of order 4 from :
+    /// Hairer, E., Nørsett, S. P., & Wanner, G. (1993). Solving Ordinary Differential Equations I, Nonstiff Problems. Section II.6.
+    pub fn dopri5() -> Self {
+        let f = |num: f64, den: f64| M::T::cast(num / den);
+        let mut a = M::zeros(7, 7);
+        a[(1, 0)] = f(1.0, 5.0);
+
+        a[(2, 0)] = f(3.0, 40.0);
+        a[(2, 1)] = f(9.0, 40.0);
+
+        a[(3, 0)] = f(44.0, 45.0);
+        a[(3, 1)] = f(-56.0, 15.0);
+        a[(3, 2)] = f(32.0, 9.0);
+
+        a[(4, 0)] = f(19372.0, 6561.0);
+        a[(4, 1)] = f(-25360.0, 2187.0);
+        a[(4, 2)] = f(64448.0, 6561.0);
+        a[(4, 3)] = f(-212.0, 729.0);
+
+        a[(5, 0)] = f(9017.0, 3168.0);
+        a[(5, 1)] = f(-355.0, 33.0);
+        a[(5, 2)] = f(46732.0, 5247.0);
+        a[(5, 3)] = f(49.0, 176.0);
+        a[(5, 4)] = f(-5103.0, 18656.0);
+
+        a[(6, 0)] = f(35.0, 384.0);
+        a[(6, 2)] = f(500.0, 1113.0);
+        a[(6, 3)] = f(125.0, 192.0);
+        a[(6, 4)] = f(-2187.0, 6784.0);
+        a[(6, 5)] = f(11.0, 84.0);
+
+        let b = M::V::from_vec((0..7).map(|j| a[(6, j)].clone()).collect());
+
+        let c = M::V::from_vec(vec![
+            M::T::zero(),
+            f(1.0, 5.0),
+            f(3.0, 10.0),
+            f(4.0, 5.0),
+            f(8.0, 9.0),
+            M::T::one(),
+            M::T::one(),
+        ]);
+
+        let d = M::V::from_vec(vec![
+            f(71.0, 57600.0),
+            M::T::zero(),
+            f(-71.0, 16695.0),
+            f(71.0, 1920.0),
+            f(-17253.0, 339200.0),
+            f(22.0, 525.0),
+            f(-1.0, 40.0),
+        ]);
+
+        // the dense output is y(theta) = y0 + theta (r2 + (1 - theta) (r3 + theta (r4 + (1 - theta) r5))),
+        // where r2 = sum(b_i k_i), r3 = k_1 - r2, r4 = r2 - k_7 - r3 and r5 = sum(e_i k_i),
+        // which is expanded into the coefficients of theta, theta^2, theta^3 and theta^4 for each stage
+        let e = [
+            f(-12715105075.0, 11282082432.0),
+            M::T::zero(),
+            f(87487479700.0, 32700410799.0),
+            f(-10690763975.0, 1880347072.0),
+            f(701980252875.0, 199316789632.0),
+            f(-1453857185.0, 822651844.0),
+            f(69997945.0, 29380423.0),
+        ];
+        let mut beta = M::zeros(7, 4);
+        for i in 0..7 {
+            let delta = |j: usize| if i == j { M::T::one() } else { M::T::zero() };
+            let r2 = b[i].clone();
+            let r3 = delta(0) - r2.clone();
+            let r4 = r2.clone() - delta(6) - r3.clone();
+            let r5 = e[i].clone();
+            beta[(i, 0)] = r2 + r3.clone();
+            beta[(i, 1)] = r4.clone() + r5.clone() - r3;
+            beta[(i, 2)] = -r4 - M::T::cast(2.0) * r5.clone();
+            beta[(i, 3)] = r5;
+        }
+
+        Self::new(a, b, c, d, 5, Some(beta))
+    }
+
+    /// Tsitouras 5(4) explicit method, with the first-same-as-last property
+    /// from Tsitouras, C. (2011). Runge–Kutta pairs of order 5(4) satisfying only the first column simplifying assumption. Computers & Mathematics with Applications, 62(2), 770-775.
+    ///
+    /// continuous extension of order 4 from the same paper.
+    pub fn tsit5() -> Self {
+        let mut a = M::zeros(7, 7);
+        a[(1, 0)] = M::T::cast(0.161);
+
+        a[(2, 0)] = M::T::cast(-0.008_480_655_492_356_989);
+        a[(2, 1)] = M::T::cast(0.335_480_655_492_357);
+
+        a[(3, 0)] = M::T::cast(2.897_153_057_105_493);
+        a[(3, 1)] = M::T::cast(-6.359_448_489_975_075);
+        a[(3, 2)] = M::T::cast(4.362_295_432_869_581_5);
+
+        a[(4, 0)] = M::T::cast(5.325_864_828_439_257);
+        a[(4, 1)] = M::T::cast(-11.748_883_564_062_828);
+        a[(4, 2)] = M::T::cast(7.495_539_342_889_836_5);
+        a[(4, 3)] = M::T::cast(-0.092_495_066_361_755_25);
+
+        a[(5, 0)] = M::T::cast(5.861_455_442_946_42);
+        a[(5, 1)] = M::T::cast(-12.920_969_317_847_11);
+        a[(5, 2)] = M::T::cast(8.159_367_898_576_159);
+        a[(5, 3)] = M::T::cast(-0.071_584_973_281_401);
+        a[(5, 4)] = M::T::cast(-0.028_269_050_394_068_383);
+
+        a[(6, 0)] = M::T::cast(0.096_460_766_818_065_23);
+        a[(6, 1)] = M::T::cast(0.01);
+        a[(6, 2)] = M::T::cast(0.479_889_650_414_499_6);
+        a[(6, 3)] = M::T::cast(1.379_008_574_103_742);
+        a[(6, 4)] = M::T::cast(-3.290_069_515_436_081);
+        a[(6, 5)] = M::T::cast(2.324_710_524_099_774);
+
+        let b = M::V::from_vec((0..7).map(|j| a[(6, j)].clone()).collect());
 
         let c = M::V::from_vec(vec![
             M::T::zero(),
-            M::T::from(0.871_733_043_016_918),
-            M::T::from(0.468_238_744_851_844_4),
+            M::T::cast(0.161),
+            M::T::cast(0.327),
+            M::T::cast(0.9),
+            M::T::cast(0.980_025_540_904_509_7),
+            M::T::one(),
             M::T::one(),
         ]);
 
         let d = M::V::from_vec(vec![
-            M::T::from(-0.054_625_497_240_413_94),
-            M::T::from(-0.494_208_893_625_994_96),
-            M::T::from(0.221_934_499_735_064_66),
-            M::T::from(0.326_899_891_131_344_27),
+            M::T::cast(-0.001_780_011_052_225_777),
+            M::T::cast(-0.000_816_434_459_656_746_9),
+            M::T::cast(0.007_880_878_010_261_995),
+            M::T::cast(-0.144_711_007_173_262_9),
+            M::T::cast(0.582_357_165_452_555_2),
+            M::T::cast(-0.458_082_105_929_186_97),
+            M::T::cast(1.0 / 66.0),
         ]);
 
-        Self::new(a, b, c, d, 3, None)
+        let beta_coeffs: [[f64; 4]; 7] = [
+            [
+                1.0,
+                -2.763_706_197_274_826,
+                2.913_255_461_821_912_6,
+                -1.053_088_497_729_021_6,
+            ],
+            [0.0, 0.131_7, -0.223_4, 0.101_7],
+            [
+                0.0,
+                3.930_296_236_894_751_6,
+                -5.941_033_872_131_505,
+                2.490_627_285_651_253,
+            ],
+            [
+                0.0,
+                -12.411_077_166_933_676,
+                30.338_188_630_282_32,
+                -16.548_102_889_244_902,
+            ],
+            [
+                0.0,
+                37.509_313_416_511_04,
+                -88.178_904_894_766_4,
+                47.379_521_962_819_28,
+            ],
+            [
+                0.0,
+                -27.896_526_289_197_286,
+                65.091_894_674_793_66,
+                -34.870_657_861_496_61,
+            ],
+            [0.0, 1.5, -4.0, 2.5],
+        ];
+        let mut beta = M::zeros(7, 4);
+        for (i, row) in beta_coeffs.iter().enumerate() {
+            for (j, &x) in row.iter().enumerate() {
+                beta[(i, j)] = M::T::cast(x);
+            }
+        }
+
+        Self::new(a, b, c, d, 5, Some(beta))
     }
 
     pub fn new(a: M, b: M::V, c: M::V, d: M::V, order: usize, beta: Option<M>) -> Self {
@@ -179,3 +412,414 @@ impl<M: DenseMatrix> Tableau<M> {
         self.beta.as_ref()
     }
 }
+
+/// A tableau for a Rosenbrock (linearly implicit Runge-Kutta) method, written in the transformed form of Hairer & Wanner,
+/// where each stage requires the solution of a linear system with the matrix `M / (h * gamma) - J`:
+///
+/// ```text
+/// (M / (h gamma) - J) k_i = f(t + c_i h, y + sum_j a_ij k_j) + M sum_j (gamma_ij / h) k_j + gamma_i h df/dt
+/// y_new = y + sum_i b_i k_i
+/// err = sum_i d_i k_i
+/// ```
+///
+/// where `J` is the jacobian of `f` at `(t, y)`, `a` and `gamma_ij` are strictly lower triangular, and `d` is the difference between the main and embedded method.
+///
+/// For continous extension methods, the beta matrix is also included.
+pub struct RosenbrockTableau<M: DenseMatrix> {
+    a: M,
+    gamma_mat: M,
+    b: M::V,
+    c: M::V,
+    d: M::V,
+    gamma_t: M::V,
+    gamma: M::T,
+    order: usize,
+    beta: Option<M>,
+}
+
+impl<M: DenseMatrix> RosenbrockTableau<M> {
+    /// RODAS3, a stiffly accurate third order method with a second order embedded method, suitable for DAEs.
+    /// from Sandu, A., Verwer, J. G., Blom, J. G., Spee, E. J., Carmichael, G. R., & Potra, F. A. (1997). Benchmarking stiff ODE solvers for atmospheric chemistry problems II: Rosenbrock solvers. Atmospheric Environment, 31(20), 3459-3472.
+    pub fn rodas3() -> Self {
+        let gamma = M::T::cast(0.5);
+
+        let mut a = M::zeros(4, 4);
+        a[(2, 0)] = M::T::cast(2.0);
+        a[(3, 0)] = M::T::cast(2.0);
+        a[(3, 2)] = M::T::one();
+
+        let mut gamma_mat = M::zeros(4, 4);
+        gamma_mat[(1, 0)] = M::T::cast(4.0);
+        gamma_mat[(2, 0)] = M::T::one();
+        gamma_mat[(2, 1)] = M::T::cast(-1.0);
+        gamma_mat[(3, 0)] = M::T::one();
+        gamma_mat[(3, 1)] = M::T::cast(-1.0);
+        gamma_mat[(3, 2)] = M::T::cast(-8.0 / 3.0);
+
+        // stiffly accurate, the solution is the last stage plus its increment, and the embedded solution is the last stage
+        let b = M::V::from_vec(vec![
+            M::T::cast(2.0),
+            M::T::zero(),
+            M::T::one(),
+            M::T::one(),
+        ]);
+        let mut d = M::V::zeros(4);
+        d[3] = M::T::one();
+
+        let c = M::V::from_vec(vec![M::T::zero(), M::T::zero(), M::T::one(), M::T::one()]);
+        let gamma_t = M::V::from_vec(vec![
+            M::T::cast(0.5),
+            M::T::cast(1.5),
+            M::T::zero(),
+            M::T::zero(),
+        ]);
+
+        let order = 3;
+
+        Self::new(a, gamma_mat, b, c, d, gamma_t, gamma, order, None)
+    }
+
+    /// RODAS4, a stiffly accurate fourth order method with a third order embedded method, suitable for DAEs.
+    /// from Hairer, E., & Wanner, G. (1996). Solving Ordinary Differential Equations II: Stiff and Differential-Algebraic Problems.
+    ///
+    /// continuous extension (third order) from the same reference.
+    pub fn rodas4() -> Self {
+        let gamma = M::T::cast(0.25);
+
+        let mut a = M::zeros(6, 6);
+        a[(1, 0)] = M::T::cast(1.544);
+        a[(2, 0)] = M::T::cast(0.946_678_528_081_582_6);
+        a[(2, 1)] = M::T::cast(0.255_701_169_898_328_4);
+        a[(3, 0)] = M::T::cast(3.314_825_187_068_521);
+        a[(3, 1)] = M::T::cast(2.896_124_015_972_201);
+        a[(3, 2)] = M::T::cast(0.998_641_913_997_781_7);
+        a[(4, 0)] = M::T::cast(1.221_224_509_226_641);
+        a[(4, 1)] = M::T::cast(6.019_134_481_288_629);
+        a[(4, 2)] = M::T::cast(12.537_083_329_320_87);
+        a[(4, 3)] = M::T::cast(-0.687_886_036_105_895);
+        for j in 0..4 {
+            a[(5, j)] = a[(4, j)].clone();
+        }
+        a[(5, 4)] = M::T::one();
+
+        let mut gamma_mat = M::zeros(6, 6);
+        gamma_mat[(1, 0)] = M::T::cast(-5.6688);
+        gamma_mat[(2, 0)] = M::T::cast(-2.430_093_356_833_875);
+        gamma_mat[(2, 1)] = M::T::cast(-0.206_359_915_709_191_5);
+        gamma_mat[(3, 0)] = M::T::cast(-0.107_352_905_815_137_5);
+        gamma_mat[(3, 1)] = M::T::cast(-9.594_562_251_023_355);
+        gamma_mat[(3, 2)] = M::T::cast(-20.470_286_148_096_16);
+        gamma_mat[(4, 0)] = M::T::cast(7.496_443_313_967_647);
+        gamma_mat[(4, 1)] = M::T::cast(-10.246_804_314_643_52);
+        gamma_mat[(4, 2)] = M::T::cast(-33.999_903_528_199_05);
+        gamma_mat[(4, 3)] = M::T::cast(11.708_908_932_061_6);
+        gamma_mat[(5, 0)] = M::T::cast(8.083_246_795_921_522);
+        gamma_mat[(5, 1)] = M::T::cast(-7.981_132_988_064_893);
+        gamma_mat[(5, 2)] = M::T::cast(-31.521_594_328_743_71);
+        gamma_mat[(5, 3)] = M::T::cast(16.319_305_431_231_36);
+        gamma_mat[(5, 4)] = M::T::cast(-6.058_818_238_834_054);
+
+        // stiffly accurate, the solution is the last stage plus its increment, and the embedded solution is the last stage
+        let mut b = M::V::zeros(6);
+        for j in 0..5 {
+            b[j] = a[(5, j)].clone();
+        }
+        b[5] = M::T::one();
+        let mut d = M::V::zeros(6);
+        d[5] = M::T::one();
+
+        let c = M::V::from_vec(vec![
+            M::T::zero(),
+            M::T::cast(0.386),
+            M::T::cast(0.21),
+            M::T::cast(0.63),
+            M::T::one(),
+            M::T::one(),
+        ]);
+        let gamma_t = M::V::from_vec(vec![
+            M::T::cast(0.25),
+            M::T::cast(-0.1043),
+            M::T::cast(0.1035),
+            M::T::cast(-0.0362),
+            M::T::zero(),
+            M::T::zero(),
+        ]);
+
+        // the continuous extension is y(theta) = (1 - theta) y0 + theta (y1 + (1 - theta) (h2 k + theta h3 k))
+        let h2 = [
+            10.126_235_083_445_86,
+            -7.487_995_877_610_167,
+            -34.800_918_615_557_47,
+            -7.992_771_707_568_823,
+            1.025_137_723_295_662,
+            0.0,
+        ];
+        let h3 = [
+            -0.676_280_339_280_125_3,
+            6.087_714_651_680_015,
+            16.430_843_208_924_78,
+            24.767_225_114_183_86,
+            -6.594_389_125_716_872,
+            0.0,
+        ];
+        let mut beta = M::zeros(6, 3);
+        for i in 0..6 {
+            let (h2, h3) = (M::T::cast(h2[i]), M::T::cast(h3[i]));
+            beta[(i, 0)] = b[i].clone() + h2.clone();
+            beta[(i, 1)] = h3.clone() - h2;
+            beta[(i, 2)] = -h3;
+        }
+
+        let order = 4;
+
+        Self::new(a, gamma_mat, b, c, d, gamma_t, gamma, order, Some(beta))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        a: M,
+        gamma_mat: M,
+        b: M::V,
+        c: M::V,
+        d: M::V,
+        gamma_t: M::V,
+        gamma: M::T,
+        order: usize,
+        beta: Option<M>,
+    ) -> Self {
+        let s = c.len();
+        assert_eq!(a.nrows(), s, "Invalid number of rows in a, expected {}", s);
+        assert_eq!(
+            a.ncols(),
+            s,
+            "Invalid number of columns in a, expected {}",
+            s
+        );
+        assert_eq!(
+            gamma_mat.nrows(),
+            s,
+            "Invalid number of rows in gamma_mat, expected {}",
+            s
+        );
+        assert_eq!(
+            gamma_mat.ncols(),
+            s,
+            "Invalid number of columns in gamma_mat, expected {}",
+            s
+        );
+        assert_eq!(
+            b.len(),
+            s,
+            "Invalid number of elements in b, expected {}",
+            s
+        );
+        assert_eq!(
+            d.len(),
+            s,
+            "Invalid number of elements in d, expected {}",
+            s
+        );
+        assert_eq!(
+            gamma_t.len(),
+            s,
+            "Invalid number of elements in gamma_t, expected {}",
+            s
+        );
+        if let Some(beta) = &beta {
+            assert_eq!(
+                beta.nrows(),
+                s,
+                "Invalid number of rows in beta, expected {}",
+                s
+            );
+        }
+        Self {
+            a,
+            gamma_mat,
+            b,
+            c,
+            d,
+            gamma_t,
+            gamma,
+            order,
+            beta,
+        }
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn s(&self) -> usize {
+        self.c.len()
+    }
+
+    pub fn a(&self) -> &M {
+        &self.a
+    }
+
+    pub fn gamma_mat(&self) -> &M {
+        &self.gamma_mat
+    }
+
+    pub fn b(&self) -> &M::V {
+        &self.b
+    }
+
+    pub fn c(&self) -> &M::V {
+        &self.c
+    }
+
+    pub fn d(&self) -> &M::V {
+        &self.d
+    }
+
+    pub fn gamma_t(&self) -> &M::V {
+        &self.gamma_t
+    }
+
+    pub fn gamma(&self) -> M::T {
+        self.gamma.clone()
+    }
+
+    pub fn beta(&self) -> Option<&M> {
+        self.beta.as_ref()
+    }
+}
+
+/// A pair of tableaus for an additive (implicit-explicit) Runge-Kutta method, for problems with a right-hand side that is
+/// split into a non-stiff part `F_E`, which is integrated using the `explicit` tableau, and a stiff part `F_I`, which is integrated
+/// using the `implicit` tableau:
+///
+/// ```text
+/// Y_i = y + h sum_j ae_ij F_E(t + c_j h, Y_j) + h sum_j ai_ij F_I(t + c_j h, Y_j)
+/// y_new = y + h sum_i be_i F_E(t + c_i h, Y_i) + h sum_i bi_i F_I(t + c_i h, Y_i)
+/// ```
+///
+/// Both tableaus must have the same number of stages and the same `c` vector.
+pub struct ImexTableau<M: DenseMatrix> {
+    explicit: Tableau<M>,
+    implicit: Tableau<M>,
+}
+
+impl<M: DenseMatrix> ImexTableau<M> {
+    /// ARK4(3)6L\[2\]SA, a fourth order method with a third order embedded method. The implicit method is a stiffly accurate
+    /// L-stable ESDIRK method.
+    /// from Kennedy, C. A., & Carpenter, M. H. (2003). Additive Runge–Kutta schemes for convection–diffusion–reaction equations. Applied numerical mathematics, 44(1-2), 139-181.
+    pub fn ark436l2sa() -> Self {
+        let s = 6;
+        let b = M::V::from_vec(vec![
+            M::T::cast(82889.0 / 524892.0),
+            M::T::zero(),
+            M::T::cast(15625.0 / 83664.0),
+            M::T::cast(69875.0 / 102672.0),
+            M::T::cast(-2260.0 / 8211.0),
+            M::T::cast(0.25),
+        ]);
+        let b_hat = M::V::from_vec(vec![
+            M::T::cast(4586570599.0 / 29645900160.0),
+            M::T::zero(),
+            M::T::cast(178811875.0 / 945068544.0),
+            M::T::cast(814220225.0 / 1159782912.0),
+            M::T::cast(-3700637.0 / 11593932.0),
+            M::T::cast(61727.0 / 225920.0),
+        ]);
+        let mut d = M::V::zeros(s);
+        for i in 0..s {
+            d[i] = b[i].clone() - b_hat[i].clone();
+        }
+        let c = M::V::from_vec(vec![
+            M::T::zero(),
+            M::T::cast(0.5),
+            M::T::cast(83.0 / 250.0),
+            M::T::cast(31.0 / 50.0),
+            M::T::cast(17.0 / 20.0),
+            M::T::one(),
+        ]);
+
+        let mut ae = M::zeros(s, s);
+        ae[(1, 0)] = M::T::cast(0.5);
+
+        ae[(2, 0)] = M::T::cast(13861.0 / 62500.0);
+        ae[(2, 1)] = M::T::cast(6889.0 / 62500.0);
+
+        ae[(3, 0)] = M::T::cast(-116923316275.0 / 2393684061468.0);
+        ae[(3, 1)] = M::T::cast(-2731218467317.0 / 15368042101831.0);
+        ae[(3, 2)] = M::T::cast(9408046702089.0 / 11113171139209.0);
+
+        ae[(4, 0)] = M::T::cast(-451086348788.0 / 2902428689909.0);
+        ae[(4, 1)] = M::T::cast(-2682348792572.0 / 7519795681897.0);
+        ae[(4, 2)] = M::T::cast(12662868775082.0 / 11960479115383.0);
+        ae[(4, 3)] = M::T::cast(3355817975965.0 / 11060851509271.0);
+
+        ae[(5, 0)] = M::T::cast(647845179188.0 / 3216320057751.0);
+        ae[(5, 1)] = M::T::cast(73281519250.0 / 8382639484533.0);
+        ae[(5, 2)] = M::T::cast(552539513391.0 / 3454668386233.0);
+        ae[(5, 3)] = M::T::cast(3354512671639.0 / 8306763924573.0);
+        ae[(5, 4)] = M::T::cast(4040.0 / 17871.0);
+
+        let gamma = M::T::cast(0.25);
+        let mut ai = M::zeros(s, s);
+        ai[(1, 0)] = gamma.clone();
+        ai[(1, 1)] = gamma.clone();
+
+        ai[(2, 0)] = M::T::cast(8611.0 / 62500.0);
+        ai[(2, 1)] = M::T::cast(-1743.0 / 31250.0);
+        ai[(2, 2)] = gamma.clone();
+
+        ai[(3, 0)] = M::T::cast(5012029.0 / 34652500.0);
+        ai[(3, 1)] = M::T::cast(-654441.0 / 2922500.0);
+        ai[(3, 2)] = M::T::cast(174375.0 / 388108.0);
+        ai[(3, 3)] = gamma.clone();
+
+        ai[(4, 0)] = M::T::cast(15267082809.0 / 155376265600.0);
+        ai[(4, 1)] = M::T::cast(-71443401.0 / 120774400.0);
+        ai[(4, 2)] = M::T::cast(730878875.0 / 902184768.0);
+        ai[(4, 3)] = M::T::cast(2285395.0 / 8070912.0);
+        ai[(4, 4)] = gamma;
+
+        // stiffly accurate, so the last row is b
+        for i in 0..s {
+            ai[(5, i)] = b[i].clone();
+        }
+
+        let order = 4;
+        let explicit = Tableau::new(ae, b.clone(), c.clone(), d.clone(), order, None);
+        let implicit = Tableau::new(ai, b, c, d, order, None);
+        Self::new(explicit, implicit)
+    }
+
+    pub fn new(explicit: Tableau<M>, implicit: Tableau<M>) -> Self {
+        let s = implicit.s();
+        assert_eq!(
+            explicit.s(),
+            s,
+            "Invalid number of stages in explicit tableau, expected {}",
+            s
+        );
+        for i in 0..s {
+            assert_eq!(
+                explicit.c()[i],
+                implicit.c()[i],
+                "Invalid tableaus, expected the same c vector for the explicit and implicit methods"
+            );
+        }
+        Self { explicit, implicit }
+    }
+
+    pub fn order(&self) -> usize {
+        self.implicit.order()
+    }
+
+    pub fn s(&self) -> usize {
+        self.implicit.s()
+    }
+
+    pub fn explicit(&self) -> &Tableau<M> {
+        &self.explicit
+    }
+
+    pub fn implicit(&self) -> &Tableau<M> {
+        &self.implicit
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{
     ode_solver::problem::OdeSolverSolution, scalar::scale, DenseMatrix, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    OdeSolverProblem, Scalar, Vector,
 };
 use num_traits::One;
 use std::ops::MulAssign;
@@ -15,11 +15,36 @@ fn rhs<M: DenseMatrix>(x: &M::V, _p: &M::V, _t: M::T, y: &mut M::V) {
 fn rhs_jac<M: DenseMatrix>(x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
     y.copy_from(v);
     y.component_mul_assign(x);
-    y.mul_assign(scale(M::T::from(2.)));
+    y.mul_assign(scale(M::T::cast(2.)));
 }
 
+#[allow(clippy::type_complexity)]
 pub fn dydt_y2_problem<M: DenseMatrix + 'static>(
-    use_coloring: bool,
+    size: usize,
+) -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    dydt_y2_problem_with_builder(OdeBuilder::new(), size)
+}
+
+/// The dydt_y2 problem with the (diagonal) sparsity of the jacobian declared, so it is assembled using coloring
+#[allow(clippy::type_complexity)]
+pub fn dydt_y2_problem_colored<M: DenseMatrix + 'static>(
+    size: usize,
+) -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    dydt_y2_problem_with_builder(
+        OdeBuilder::new().jacobian_sparsity((0..size).map(|i| (i, i))),
+        size,
+    )
+}
+
+#[allow(clippy::type_complexity)]
+fn dydt_y2_problem_with_builder<M: DenseMatrix + 'static>(
+    builder: OdeBuilder,
     size: usize,
 ) -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
@@ -28,21 +53,20 @@ pub fn dydt_y2_problem<M: DenseMatrix + 'static>(
     let size2 = size;
     let y0 = -200.;
     let tlast = 20.0;
-    let problem = OdeBuilder::new()
-        .use_coloring(use_coloring)
+    let problem = builder
         .rtol(1e-4)
         .build_ode(rhs::<M>, rhs_jac::<M>, move |_p, _t| {
-            M::V::from_vec([y0.into()].repeat(size2))
+            M::V::from_vec(vec![M::T::cast(y0); size2])
         })
         .unwrap();
     let mut soln = OdeSolverSolution::default();
-    let y0 = M::V::from_vec([y0.into()].repeat(size));
+    let y0 = M::V::from_vec(vec![M::T::cast(y0); size]);
     let n = 10;
     let dt = tlast / n as f64;
     for i in 0..=n {
-        let t = M::T::from(i as f64 * dt);
+        let t = M::T::cast(i as f64 * dt);
         // y = y0 / (1 - y0 * t)
-        let mut denom = y0.clone() * (scale(-t));
+        let mut denom = y0.clone() * (scale(-t.clone()));
         denom.add_scalar_mut(M::T::one());
         let mut y = y0.clone();
         y.component_div_assign(&denom);
@@ -1,6 +1,6 @@
 use crate::{
     matrix::Matrix, ode_solver::problem::OdeSolverSolution, scalar::scale, ConstantOp, OdeBuilder,
-    OdeEquations, OdeSolverProblem, Vector,
+    OdeEquations, OdeSolverProblem, Scalar, Vector,
 };
 use nalgebra::ComplexField;
 use num_traits::Zero;
@@ -10,23 +10,28 @@ use std::ops::MulAssign;
 // dy/dt = -ay (p = [a])
 fn exponential_decay<M: Matrix>(x: &M::V, p: &M::V, _t: M::T, y: &mut M::V) {
     y.copy_from(x);
-    y.mul_assign(scale(-p[0]));
+    y.mul_assign(scale(-p[0].clone()));
 }
 
 // df/dp v = -yv (p = [a])
 fn exponential_decay_sens<M: Matrix>(x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
     y.copy_from(x);
-    y.mul_assign(scale(-v[0]));
+    y.mul_assign(scale(-v[0].clone()));
 }
 
 // Jv = -av
 fn exponential_decay_jacobian<M: Matrix>(_x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
     y.copy_from(v);
-    y.mul_assign(scale(-p[0]));
+    y.mul_assign(scale(-p[0].clone()));
+}
+
+// y = -a x + beta y, i.e. the rhs written as a linear op
+fn exponential_decay_linear<M: Matrix>(x: &M::V, p: &M::V, _t: M::T, beta: M::T, y: &mut M::V) {
+    y.axpy(-p[0].clone(), x, beta);
 }
 
 fn exponential_decay_init<M: Matrix>(_p: &M::V, _t: M::T) -> M::V {
-    M::V::from_vec(vec![1.0.into(), 1.0.into()])
+    M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0)])
 }
 
 fn exponential_decay_init_sens<M: Matrix>(_p: &M::V, _t: M::T, _v: &M::V, y: &mut M::V) {
@@ -34,44 +39,74 @@ fn exponential_decay_init_sens<M: Matrix>(_p: &M::V, _t: M::T, _v: &M::V, y: &mu
 }
 
 fn exponential_decay_root<M: Matrix>(x: &M::V, _p: &M::V, _t: M::T, y: &mut M::V) {
-    y[0] = x[0] - M::T::from(0.6);
+    y[0] = x[0].clone() - M::T::cast(0.6);
+}
+
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_problem<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    exponential_decay_problem_with_tol::<M>(1e-6, 1e-6)
 }
 
-pub fn exponential_decay_problem<M: Matrix + 'static>(
-    use_coloring: bool,
+// the same problem with the given tolerances, e.g. for scalar types less precise than f64
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_problem_with_tol<M: Matrix + 'static>(
+    rtol: f64,
+    atol: f64,
 ) -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
     let problem = OdeBuilder::new()
+        .rtol(rtol)
+        .atol([atol])
         .p([0.1])
-        .use_coloring(use_coloring)
         .build_ode(
             exponential_decay::<M>,
             exponential_decay_jacobian::<M>,
             exponential_decay_init::<M>,
         )
         .unwrap();
-    let p = [M::T::from(0.1)];
+    let p = [M::T::cast(0.1)];
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64);
+        let t = M::T::cast(i as f64);
         let y0: M::V = problem.eqn.init().call(M::T::zero());
-        let y = y0 * scale(M::T::exp(-p[0] * t));
+        let y = y0 * scale(M::T::exp(-p[0].clone() * t.clone()));
         soln.push(y, t);
     }
     (problem, soln)
 }
 
-pub fn exponential_decay_problem_with_root<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_problem_linear<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let problem = OdeBuilder::new()
+        .p([0.1])
+        .build_ode_linear(exponential_decay_linear::<M>, exponential_decay_init::<M>)
+        .unwrap();
+    let p = [M::T::cast(0.1)];
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::cast(i as f64);
+        let y0: M::V = problem.eqn.init().call(M::T::zero());
+        let y = y0 * scale(M::T::exp(-p[0].clone() * t.clone()));
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
+
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_problem_with_root<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
     let problem = OdeBuilder::new()
         .p([0.1])
-        .use_coloring(use_coloring)
         .build_ode_with_root(
             exponential_decay::<M>,
             exponential_decay_jacobian::<M>,
@@ -80,26 +115,24 @@ pub fn exponential_decay_problem_with_root<M: Matrix + 'static>(
             1,
         )
         .unwrap();
-    let p = [M::T::from(0.1)];
+    let p = [M::T::cast(0.1)];
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64);
+        let t = M::T::cast(i as f64);
         let y0: M::V = problem.eqn.init().call(M::T::zero());
-        let y = y0 * scale(M::T::exp(-p[0] * t));
+        let y = y0 * scale(M::T::exp(-p[0].clone() * t.clone()));
         soln.push(y, t);
     }
     (problem, soln)
 }
 
-pub fn exponential_decay_problem_sens<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_problem_sens<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
     let problem = OdeBuilder::new()
         .p([0.1])
-        .use_coloring(use_coloring)
         .sensitivities_error_control(true)
         .build_ode_with_sens(
             exponential_decay::<M>,
@@ -109,13 +142,13 @@ pub fn exponential_decay_problem_sens<M: Matrix + 'static>(
             exponential_decay_init_sens::<M>,
         )
         .unwrap();
-    let p = [M::T::from(0.1)];
+    let p = [M::T::cast(0.1)];
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64);
+        let t = M::T::cast(i as f64);
         let y0: M::V = problem.eqn.init().call(M::T::zero());
-        let y = y0.clone() * scale(M::T::exp(-p[0] * t));
-        let yp = y0 * scale(-t * M::T::exp(-p[0] * t));
+        let y = y0.clone() * scale(M::T::exp(-p[0].clone() * t.clone()));
+        let yp = y0 * scale(-t.clone() * M::T::exp(-p[0].clone() * t.clone()));
         soln.push_sens(y, t, &[yp]);
     }
     (problem, soln)
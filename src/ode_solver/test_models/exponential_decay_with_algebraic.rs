@@ -1,6 +1,6 @@
 use crate::{
     matrix::Matrix, ode_solver::problem::OdeSolverSolution, scalar::scale, OdeBuilder,
-    OdeEquations, OdeSolverProblem, Vector,
+    OdeEquations, OdeSolverProblem, Scalar, Vector,
 };
 use nalgebra::ComplexField;
 use num_traits::{One, Zero};
@@ -13,9 +13,9 @@ use std::ops::MulAssign;
 #[allow(unused_mut)]
 fn exponential_decay_with_algebraic<M: Matrix>(x: &M::V, p: &M::V, _t: M::T, mut y: &mut M::V) {
     y.copy_from(x);
-    y.mul_assign(scale(-p[0]));
+    y.mul_assign(scale(-p[0].clone()));
     let nstates = y.len();
-    y[nstates - 1] = x[nstates - 1] - x[nstates - 2];
+    y[nstates - 1] = x[nstates - 1].clone() - x[nstates - 2].clone();
 }
 
 #[allow(unused_mut)]
@@ -27,7 +27,7 @@ fn exponential_decay_with_algebraic_sens<M: Matrix>(
     mut y: &mut M::V,
 ) {
     y.copy_from(x);
-    y.mul_assign(scale(-v[0]));
+    y.mul_assign(scale(-v[0].clone()));
     let nstates = y.len();
     y[nstates - 1] = M::T::zero();
 }
@@ -42,9 +42,9 @@ fn exponential_decay_with_algebraic_jacobian<M: Matrix>(
     mut y: &mut M::V,
 ) {
     y.copy_from(v);
-    y.mul_assign(scale(-p[0]));
+    y.mul_assign(scale(-p[0].clone()));
     let nstates = y.len();
-    y[nstates - 1] = v[nstates - 1] - v[nstates - 2];
+    y[nstates - 1] = v[nstates - 1].clone() - v[nstates - 2].clone();
 }
 
 // y = Mx + beta * y = | 1 0 | | x[0] | + beta | y[0] |
@@ -57,7 +57,7 @@ fn exponential_decay_with_algebraic_mass<M: Matrix>(
     y: &mut M::V,
 ) {
     let nstates = y.len();
-    let yn = beta * y[nstates - 1];
+    let yn = beta.clone() * y[nstates - 1].clone();
     y.axpy(M::T::one(), x, beta);
     y[nstates - 1] = yn;
 }
@@ -73,7 +73,7 @@ fn exponential_decay_with_algebraic_mass_sens<M: Matrix>(
 }
 
 fn exponential_decay_with_algebraic_init<M: Matrix>(_p: &M::V, _t: M::T) -> M::V {
-    M::V::from_vec(vec![1.0.into(), 1.0.into(), 0.0.into()])
+    M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0), M::T::cast(0.0)])
 }
 
 fn exponential_decay_with_algebraic_init_sens<M: Matrix>(
@@ -85,16 +85,14 @@ fn exponential_decay_with_algebraic_init_sens<M: Matrix>(
     y.fill(M::T::zero());
 }
 
-pub fn exponential_decay_with_algebraic_problem<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_with_algebraic_problem<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
-    let p = M::V::from_vec(vec![0.1.into()]);
+    let p = M::V::from_vec(vec![M::T::cast(0.1)]);
     let problem = OdeBuilder::new()
         .p([0.1])
-        .use_coloring(use_coloring)
         .build_ode_with_mass(
             exponential_decay_with_algebraic::<M>,
             exponential_decay_with_algebraic_jacobian::<M>,
@@ -105,24 +103,22 @@ pub fn exponential_decay_with_algebraic_problem<M: Matrix + 'static>(
 
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64 / 10.0);
-        let y0 = M::V::from_vec(vec![1.0.into(), 1.0.into(), 1.0.into()]);
-        let y: M::V = y0 * scale(M::T::exp(-p[0] * t));
+        let t = M::T::cast(i as f64 / 10.0);
+        let y0 = M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0), M::T::cast(1.0)]);
+        let y: M::V = y0 * scale(M::T::exp(-p[0].clone() * t.clone()));
         soln.push(y, t);
     }
     (problem, soln)
 }
 
-pub fn exponential_decay_with_algebraic_problem_sens<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn exponential_decay_with_algebraic_problem_sens<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
-    let p = M::V::from_vec(vec![0.1.into()]);
+    let p = M::V::from_vec(vec![M::T::cast(0.1)]);
     let problem = OdeBuilder::new()
         .p([0.1])
-        .use_coloring(use_coloring)
         .sensitivities_error_control(true)
         .build_ode_with_mass_and_sens(
             exponential_decay_with_algebraic::<M>,
@@ -137,10 +133,10 @@ pub fn exponential_decay_with_algebraic_problem_sens<M: Matrix + 'static>(
 
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64 / 10.0);
-        let y0 = M::V::from_vec(vec![1.0.into(), 1.0.into(), 1.0.into()]);
-        let y: M::V = y0.clone() * scale(M::T::exp(-p[0] * t));
-        let yp = y0 * scale(-t * M::T::exp(-p[0] * t));
+        let t = M::T::cast(i as f64 / 10.0);
+        let y0 = M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0), M::T::cast(1.0)]);
+        let y: M::V = y0.clone() * scale(M::T::exp(-p[0].clone() * t.clone()));
+        let yp = y0 * scale(-t.clone() * M::T::exp(-p[0].clone() * t.clone()));
         soln.push_sens(y, t, &[yp]);
     }
     (problem, soln)
@@ -1,6 +1,6 @@
 use crate::ode_solver::problem::OdeSolverSolution;
 use crate::OdeSolverProblem;
-use crate::{scalar::scale, ConstantOp, DenseMatrix, OdeBuilder, OdeEquations, Vector};
+use crate::{scalar::scale, ConstantOp, DenseMatrix, OdeBuilder, OdeEquations, Scalar, Vector};
 use num_traits::Pow;
 use num_traits::Zero;
 use std::ops::MulAssign;
@@ -19,8 +19,8 @@ fn gaussian_decay_jacobian<M: DenseMatrix>(_x: &M::V, p: &M::V, t: M::T, v: &M::
     y.mul_assign(scale(-t));
 }
 
+#[allow(clippy::type_complexity)]
 pub fn gaussian_decay_problem<M: DenseMatrix + 'static>(
-    use_coloring: bool,
     size: usize,
 ) -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
@@ -29,18 +29,17 @@ pub fn gaussian_decay_problem<M: DenseMatrix + 'static>(
     let size2 = size;
     let problem = OdeBuilder::new()
         .p([0.1].repeat(size))
-        .use_coloring(use_coloring)
         .build_ode(
             gaussian_decay::<M>,
             gaussian_decay_jacobian::<M>,
-            move |_p, _t| M::V::from_vec([1.0.into()].repeat(size2)),
+            move |_p, _t| M::V::from_vec(vec![M::T::cast(1.0); size2]),
         )
         .unwrap();
-    let p = [M::T::from(0.1)].repeat(size);
+    let p = vec![M::T::cast(0.1); size];
     let mut soln = OdeSolverSolution::default();
     for i in 0..10 {
-        let t = M::T::from(i as f64 / 1.0);
-        let px = M::V::from_vec(p.clone()) * scale(t.pow(2)) / scale(M::T::from(-2.0));
+        let t = M::T::cast(i as f64 / 1.0);
+        let px = M::V::from_vec(p.clone()) * scale(t.clone().pow(2)) / scale(M::T::cast(-2.0));
         let mut y: M::V = problem.eqn.init().call(M::T::zero());
         y.component_mul_assign(&px.exp());
         soln.push(y, t);
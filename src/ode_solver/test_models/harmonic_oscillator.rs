@@ -0,0 +1,59 @@
+use crate::{
+    matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations,
+    OdeSolverProblem, Scalar, Vector,
+};
+use nalgebra::ComplexField;
+
+// dx/dt = v
+// dv/dt = -x
+fn harmonic_oscillator<M: Matrix>(x: &M::V, _p: &M::V, _t: M::T, y: &mut M::V) {
+    y[0] = x[1].clone();
+    y[1] = -x[0].clone();
+}
+
+fn harmonic_oscillator_jacobian<M: Matrix>(_x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
+    y[0] = v[1].clone();
+    y[1] = -v[0].clone();
+}
+
+fn harmonic_oscillator_init<M: Matrix>(_p: &M::V, _t: M::T) -> M::V {
+    M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(0.0)])
+}
+
+/// The energy `x^2 + v^2` of the harmonic oscillator is conserved, so this is an invariant `g(y) = x^2 + v^2 - 1 = 0` of the solution
+pub fn harmonic_oscillator_energy<M: Matrix>(x: &M::V, _p: &M::V, _t: M::T, y: &mut M::V) {
+    y[0] = x[0].clone() * x[0].clone() + x[1].clone() * x[1].clone() - M::T::cast(1.0);
+}
+
+pub fn harmonic_oscillator_energy_jacobian<M: Matrix>(
+    x: &M::V,
+    _p: &M::V,
+    _t: M::T,
+    v: &M::V,
+    y: &mut M::V,
+) {
+    y[0] = M::T::cast(2.0) * (x[0].clone() * v[0].clone() + x[1].clone() * v[1].clone());
+}
+
+#[allow(clippy::type_complexity)]
+pub fn harmonic_oscillator_problem<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let problem = OdeBuilder::new()
+        .rtol(1e-4)
+        .atol([1e-6])
+        .build_ode(
+            harmonic_oscillator::<M>,
+            harmonic_oscillator_jacobian::<M>,
+            harmonic_oscillator_init::<M>,
+        )
+        .unwrap();
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::cast(i as f64);
+        let y = M::V::from_vec(vec![t.clone().cos(), -t.clone().sin()]);
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
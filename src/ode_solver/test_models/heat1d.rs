@@ -0,0 +1,115 @@
+use crate::{
+    matrix::Matrix, ode_solver::problem::OdeSolverSolution, scalar::scale, ConstantOp, OdeBuilder,
+    OdeEquations, OdeSolverProblem, Scalar, Vector,
+};
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+
+// 1D heat equation du/dt = D d^2u/dx^2 on x in [0, 1] with u(0) = u(1) = 0 (p = [D]),
+// discretised with second-order central differences on n equally spaced interior points
+fn heat1d<M: Matrix>(x: &M::V, p: &M::V, _t: M::T, y: &mut M::V) {
+    let n = x.len();
+    let dx = M::T::one() / M::T::cast((n + 1) as f64);
+    let c = p[0].clone() / (dx.clone() * dx);
+    for i in 0..n {
+        let left = if i > 0 {
+            x[i - 1].clone()
+        } else {
+            M::T::zero()
+        };
+        let right = if i + 1 < n {
+            x[i + 1].clone()
+        } else {
+            M::T::zero()
+        };
+        y[i] = c.clone() * (left - M::T::cast(2.0) * x[i].clone() + right);
+    }
+}
+
+// Jv = D/dx^2 (v_{i-1} - 2 v_i + v_{i+1})
+fn heat1d_jacobian<M: Matrix>(_x: &M::V, p: &M::V, t: M::T, v: &M::V, y: &mut M::V) {
+    heat1d::<M>(v, p, t, y);
+}
+
+// y = A x + beta y, i.e. the rhs written as a linear op
+fn heat1d_linear<M: Matrix>(x: &M::V, p: &M::V, t: M::T, beta: M::T, y: &mut M::V) {
+    let mut ax = M::V::zeros(x.len());
+    heat1d::<M>(x, p, t, &mut ax);
+    y.axpy(M::T::one(), &ax, beta);
+}
+
+/// The semi-discrete heat equation is a typical mildly stiff parabolic problem, the eigenvalues of the jacobian
+/// are real and negative, with the spectral radius growing as `4D/dx^2`.
+/// For the initial condition `u_i(0) = sin(pi x_i)` the semi-discrete solution is `u_i(t) = exp(lambda t) sin(pi x_i)`,
+/// where `lambda = -4D/dx^2 sin^2(pi dx / 2)`.
+#[allow(clippy::type_complexity)]
+pub fn heat1d_problem<M: Matrix + 'static>(
+    n: usize,
+) -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let d = M::T::cast(1.0);
+    let dx = M::T::one() / M::T::cast((n + 1) as f64);
+    let pi_dx = M::T::cast(std::f64::consts::PI) * dx.clone();
+    let sin_half = (pi_dx.clone() / M::T::cast(2.0)).sin();
+    let lambda = -M::T::cast(4.0) * d / (dx.clone() * dx) * sin_half.clone() * sin_half;
+    let problem = OdeBuilder::new()
+        .p([1.0])
+        .build_ode(heat1d::<M>, heat1d_jacobian::<M>, move |_p, _t| {
+            M::V::from_vec(
+                (0..n)
+                    .map(|i| (pi_dx.clone() * M::T::cast((i + 1) as f64)).sin())
+                    .collect(),
+            )
+        })
+        .unwrap();
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::cast(i as f64 * 0.02);
+        let y0: M::V = problem.eqn.init().call(M::T::zero());
+        let y = y0 * scale((lambda.clone() * t.clone()).exp());
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
+
+/// The same problem as [heat1d_problem], but with the rhs given as a linear op using [OdeBuilder::build_ode_linear].
+#[allow(clippy::type_complexity)]
+pub fn heat1d_linear_problem<M: Matrix + 'static>(
+    n: usize,
+) -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let d = M::T::cast(1.0);
+    let dx = M::T::one() / M::T::cast((n + 1) as f64);
+    let pi_dx = M::T::cast(std::f64::consts::PI) * dx.clone();
+    let sin_half = (pi_dx.clone() / M::T::cast(2.0)).sin();
+    let lambda = -M::T::cast(4.0) * d / (dx.clone() * dx) * sin_half.clone() * sin_half;
+    let problem = OdeBuilder::new()
+        .p([1.0])
+        .build_ode_linear(heat1d_linear::<M>, move |_p, _t| {
+            M::V::from_vec(
+                (0..n)
+                    .map(|i| (pi_dx.clone() * M::T::cast((i + 1) as f64)).sin())
+                    .collect(),
+            )
+        })
+        .unwrap();
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::cast(i as f64 * 0.02);
+        let y0: M::V = problem.eqn.init().call(M::T::zero());
+        let y = y0 * scale((lambda.clone() * t.clone()).exp());
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
+
+/// The spectral radius of the jacobian of [heat1d_problem], i.e. `4D/dx^2 sin^2(n pi dx / 2)`
+pub fn heat1d_spectral_radius<T: Scalar>(n: usize) -> T {
+    let dx = T::one() / T::cast((n + 1) as f64);
+    let sin_half = (T::cast(n as f64 * std::f64::consts::PI) * dx.clone() / T::cast(2.0)).sin();
+    T::cast(4.0) / (dx.clone() * dx) * sin_half.clone() * sin_half
+}
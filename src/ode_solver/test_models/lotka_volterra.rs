@@ -0,0 +1,125 @@
+use crate::{
+    ode_solver::problem::OdeSolverSolution, DenseMatrix, OdeBuilder, OdeEquations,
+    OdeSolverProblem, Scalar, Vector,
+};
+use num_traits::Num;
+
+// lotka-volterra predator-prey problem (p = [a, b, c, d])
+// dx/dt = a x - b x y
+// dy/dt = c x y - d y
+fn rhs<M: DenseMatrix>(x: &M::V, p: &M::V, _t: M::T, y: &mut M::V) {
+    let xy = x[0].clone() * x[1].clone();
+    y[0] = p[0].clone() * x[0].clone() - p[1].clone() * xy.clone();
+    y[1] = p[2].clone() * xy - p[3].clone() * x[1].clone();
+}
+
+// J = [[a - b y, -b x], [c y, c x - d]]
+fn rhs_jac<M: DenseMatrix>(x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
+    y[0] = (p[0].clone() - p[1].clone() * x[1].clone()) * v[0].clone()
+        - p[1].clone() * x[0].clone() * v[1].clone();
+    y[1] = p[2].clone() * x[1].clone() * v[0].clone()
+        + (p[2].clone() * x[0].clone() - p[3].clone()) * v[1].clone();
+}
+
+/// Reference solution `(t, [x, y])` to 34 significant digits (i.e. quad precision), computed with
+/// [crate::Extrapolation] using [crate::scalar::BigFloat] and `rtol = atol = 1e-40`.
+pub const LOTKA_VOLTERRA_REFERENCE: [(f64, [&str; 2]); 10] = [
+    (
+        1.0,
+        [
+            "3.100286961225392034275772859857559e-1",
+            "3.965132342173033803853386029963234e+0",
+        ],
+    ),
+    (
+        2.0,
+        [
+            "5.986241978735400764986115273098763e-2",
+            "2.159570258929883473166828241973052e+0",
+        ],
+    ),
+    (
+        3.0,
+        [
+            "6.207358960716272292158929344583591e-2",
+            "9.374387039448469414824370726458342e-1",
+        ],
+    ),
+    (
+        4.0,
+        [
+            "1.443946254152693554060398490666494e-1",
+            "4.569986775112061278134785681907186e-1",
+        ],
+    ),
+    (
+        5.0,
+        [
+            "4.408198330251869348151793094433941e-1",
+            "3.709698854087419161154930617855706e-1",
+        ],
+    ),
+    (
+        6.0,
+        [
+            "1.046635537731854939170686844653268e+0",
+            "1.305142487159719634102446694409414e+0",
+        ],
+    ),
+    (
+        7.0,
+        [
+            "2.272652272360727151006533162000074e-1",
+            "3.864198020548417067419369447625326e+0",
+        ],
+    ),
+    (
+        8.0,
+        [
+            "5.579511799934842814926381688777856e-2",
+            "1.942541466673243971843271699612232e+0",
+        ],
+    ),
+    (
+        9.0,
+        [
+            "6.711304680645582817319076244975966e-2",
+            "8.454439674885898800857937475277218e-1",
+        ],
+    ),
+    (
+        10.0,
+        [
+            "1.653607610809357625307272878320671e-1",
+            "4.266678699739042967110644809530740e-1",
+        ],
+    ),
+];
+
+/// The lotka-volterra problem, with the [LOTKA_VOLTERRA_REFERENCE] solution
+#[allow(clippy::type_complexity)]
+pub fn lotka_volterra_problem<M: DenseMatrix + 'static>(
+    rtol: f64,
+    atol: f64,
+) -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let problem = OdeBuilder::new()
+        .rtol(rtol)
+        .atol([atol])
+        .p([1.5, 1.0, 3.0, 1.0])
+        .build_ode(rhs::<M>, rhs_jac::<M>, |_p, _t| {
+            M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0)])
+        })
+        .unwrap();
+    let mut soln = OdeSolverSolution::default();
+    for (t, y) in LOTKA_VOLTERRA_REFERENCE {
+        // parsed directly to M::T, so that no digits are lost for types more precise than f64
+        let y = y.map(|y| {
+            M::T::from_str_radix(y, 10).unwrap_or_else(|_| panic!("invalid reference {}", y))
+        });
+        soln.push(M::V::from_vec(y.to_vec()), M::T::cast(t));
+    }
+    (problem, soln)
+}
@@ -2,7 +2,11 @@ pub mod dydt_y2;
 pub mod exponential_decay;
 pub mod exponential_decay_with_algebraic;
 pub mod gaussian_decay;
+pub mod harmonic_oscillator;
+pub mod heat1d;
+pub mod lotka_volterra;
 pub mod robertson;
 pub mod robertson_ode;
 pub mod robertson_ode_with_sens;
 pub mod robertson_sens;
+pub mod stiff_forced_decay;
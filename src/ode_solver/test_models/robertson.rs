@@ -1,42 +1,71 @@
 use crate::{
     matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    OdeSolverProblem, Scalar, Vector,
 };
 
-pub fn robertson<M: Matrix + 'static>(
-    use_coloring: bool,
+#[allow(clippy::type_complexity)]
+pub fn robertson<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    robertson_with_builder(OdeBuilder::new())
+}
+
+/// The robertson problem with the sparsity of the jacobian and mass declared, so they are assembled using coloring
+#[allow(clippy::type_complexity)]
+pub fn robertson_colored<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let jacobian = (0..3).flat_map(|j| (0..3).map(move |i| (i, j)));
+    robertson_with_builder(
+        OdeBuilder::new()
+            .jacobian_sparsity(jacobian)
+            .mass_sparsity([(0, 0), (1, 1)]),
+    )
+}
+
+/// The robertson problem, built using the given builder (e.g. to change the solver options)
+#[allow(clippy::type_complexity)]
+pub fn robertson_with_builder<M: Matrix + 'static>(
+    builder: OdeBuilder,
 ) -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
-    let problem = OdeBuilder::new()
+    let problem = builder
         .p([0.04, 1.0e4, 3.0e7])
         .rtol(1e-4)
         .atol([1.0e-8, 1.0e-6, 1.0e-6])
-        .use_coloring(use_coloring)
         .build_ode_with_mass(
             //*      dy1/dt = -.04*y1 + 1.e4*y2*y3
             //*      dy2/dt = .04*y1 - 1.e4*y2*y3 - 3.e7*y2**2
             //*         0   = y1 + y2 + y3 - 1
             |x: &M::V, p: &M::V, _t: M::T, y: &mut M::V| {
-                y[0] = -p[0] * x[0] + p[1] * x[1] * x[2];
-                y[1] = p[0] * x[0] - p[1] * x[1] * x[2] - p[2] * x[1] * x[1];
-                y[2] = x[0] + x[1] + x[2] - M::T::from(1.0);
+                y[0] = -p[0].clone() * x[0].clone() + p[1].clone() * x[1].clone() * x[2].clone();
+                y[1] = p[0].clone() * x[0].clone()
+                    - p[1].clone() * x[1].clone() * x[2].clone()
+                    - p[2].clone() * x[1].clone() * x[1].clone();
+                y[2] = x[0].clone() + x[1].clone() + x[2].clone() - M::T::cast(1.0);
             },
             |x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V| {
-                y[0] = -p[0] * v[0] + p[1] * v[1] * x[2] + p[1] * x[1] * v[2];
-                y[1] = p[0] * v[0]
-                    - p[1] * v[1] * x[2]
-                    - p[1] * x[1] * v[2]
-                    - M::T::from(2.0) * p[2] * x[1] * v[1];
-                y[2] = v[0] + v[1] + v[2];
+                y[0] = -p[0].clone() * v[0].clone()
+                    + p[1].clone() * v[1].clone() * x[2].clone()
+                    + p[1].clone() * x[1].clone() * v[2].clone();
+                y[1] = p[0].clone() * v[0].clone()
+                    - p[1].clone() * v[1].clone() * x[2].clone()
+                    - p[1].clone() * x[1].clone() * v[2].clone()
+                    - M::T::cast(2.0) * p[2].clone() * x[1].clone() * v[1].clone();
+                y[2] = v[0].clone() + v[1].clone() + v[2].clone();
             },
             |x: &M::V, _p: &M::V, _t: M::T, beta: M::T, y: &mut M::V| {
-                y[0] = x[0] + beta * y[0];
-                y[1] = x[1] + beta * y[1];
-                y[2] = beta * y[2];
+                y[0] = x[0].clone() + beta.clone() * y[0].clone();
+                y[1] = x[1].clone() + beta.clone() * y[1].clone();
+                y[2] = beta * y[2].clone();
+            },
+            |_p: &M::V, _t: M::T| {
+                M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(0.0), M::T::cast(0.0)])
             },
-            |_p: &M::V, _t: M::T| M::V::from_vec(vec![1.0.into(), 0.0.into(), 0.0.into()]),
         )
         .unwrap();
 
@@ -59,8 +88,8 @@ pub fn robertson<M: Matrix + 'static>(
 
     for (values, time) in data {
         soln.push(
-            M::V::from_vec(values.into_iter().map(|v| v.into()).collect()),
-            time.into(),
+            M::V::from_vec(values.into_iter().map(M::T::cast).collect()),
+            M::T::cast(time),
         );
     }
 
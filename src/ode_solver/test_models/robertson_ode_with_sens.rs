@@ -1,12 +1,11 @@
 use crate::{
     ode_solver::problem::OdeSolverSolution, Matrix, OdeBuilder, OdeEquations, OdeSolverProblem,
-    Vector,
+    Scalar, Vector,
 };
 use num_traits::Zero;
 
-pub fn robertson_ode_with_sens<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn robertson_ode_with_sens<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
@@ -14,30 +13,37 @@ pub fn robertson_ode_with_sens<M: Matrix + 'static>(
         .p([0.04, 1.0e4, 3.0e7])
         .rtol(1e-4)
         .atol([1.0e-8, 1.0e-6, 1.0e-6])
-        .use_coloring(use_coloring)
         .build_ode_with_sens(
             //     dy1/dt = -.04*y1 + 1.e4*y2*y3
             //*    dy2/dt = .04*y1 - 1.e4*y2*y3 - 3.e7*(y2)^2
             //*    dy3/dt = 3.e7*(y2)^2
             |x: &M::V, p: &M::V, _t: M::T, y: &mut M::V| {
-                y[0] = -p[0] * x[0] + p[1] * x[1] * x[2];
-                y[1] = p[0] * x[0] - p[1] * x[1] * x[2] - p[2] * x[1] * x[1];
-                y[2] = p[2] * x[1] * x[1];
+                y[0] = -p[0].clone() * x[0].clone() + p[1].clone() * x[1].clone() * x[2].clone();
+                y[1] = p[0].clone() * x[0].clone()
+                    - p[1].clone() * x[1].clone() * x[2].clone()
+                    - p[2].clone() * x[1].clone() * x[1].clone();
+                y[2] = p[2].clone() * x[1].clone() * x[1].clone();
             },
             |x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V| {
-                y[0] = -p[0] * v[0] + p[1] * v[1] * x[2] + p[1] * x[1] * v[2];
-                y[1] = p[0] * v[0]
-                    - p[1] * v[1] * x[2]
-                    - p[1] * x[1] * v[2]
-                    - M::T::from(2.0) * p[2] * x[1] * v[1];
-                y[2] = M::T::from(2.0) * p[2] * x[1] * v[1];
+                y[0] = -p[0].clone() * v[0].clone()
+                    + p[1].clone() * v[1].clone() * x[2].clone()
+                    + p[1].clone() * x[1].clone() * v[2].clone();
+                y[1] = p[0].clone() * v[0].clone()
+                    - p[1].clone() * v[1].clone() * x[2].clone()
+                    - p[1].clone() * x[1].clone() * v[2].clone()
+                    - M::T::cast(2.0) * p[2].clone() * x[1].clone() * v[1].clone();
+                y[2] = M::T::cast(2.0) * p[2].clone() * x[1].clone() * v[1].clone();
             },
             |x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V| {
-                y[0] = -v[0] * x[0] + v[1] * x[1] * x[2];
-                y[1] = v[0] * x[0] - v[1] * x[1] * x[2] - v[2] * x[1] * x[1];
-                y[2] = v[2] * x[1] * x[1];
+                y[0] = -v[0].clone() * x[0].clone() + v[1].clone() * x[1].clone() * x[2].clone();
+                y[1] = v[0].clone() * x[0].clone()
+                    - v[1].clone() * x[1].clone() * x[2].clone()
+                    - v[2].clone() * x[1].clone() * x[1].clone();
+                y[2] = v[2].clone() * x[1].clone() * x[1].clone();
+            },
+            |_p: &M::V, _t: M::T| {
+                M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(0.0), M::T::cast(0.0)])
             },
-            |_p: &M::V, _t: M::T| M::V::from_vec(vec![1.0.into(), 0.0.into(), 0.0.into()]),
             |_p: &M::V, _t: M::T, _v: &M::V, y: &mut M::V| y.fill(M::T::zero()),
         )
         .unwrap();
@@ -61,8 +67,8 @@ pub fn robertson_ode_with_sens<M: Matrix + 'static>(
 
     for (values, time) in data {
         soln.push(
-            M::V::from_vec(values.into_iter().map(|v| v.into()).collect()),
-            time.into(),
+            M::V::from_vec(values.into_iter().map(M::T::cast).collect()),
+            M::T::cast(time),
         );
     }
     (problem, soln)
@@ -1,12 +1,11 @@
 use crate::{
     matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations,
-    OdeSolverProblem, Vector,
+    OdeSolverProblem, Scalar, Vector,
 };
 use num_traits::Zero;
 
-pub fn robertson_sens<M: Matrix + 'static>(
-    use_coloring: bool,
-) -> (
+#[allow(clippy::type_complexity)]
+pub fn robertson_sens<M: Matrix + 'static>() -> (
     OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
     OdeSolverSolution<M::V>,
 ) {
@@ -14,39 +13,46 @@ pub fn robertson_sens<M: Matrix + 'static>(
         .p([0.04, 1.0e4, 3.0e7])
         .rtol(1e-4)
         .atol([1.0e-8, 1.0e-6, 1.0e-6])
-        .use_coloring(use_coloring)
         .sensitivities_error_control(true)
         .build_ode_with_mass_and_sens(
             //*      dy1/dt = -.04*y1 + 1.e4*y2*y3
             //*      dy2/dt = .04*y1 - 1.e4*y2*y3 - 3.e7*y2**2
             //*         0   = y1 + y2 + y3 - 1
             |x: &M::V, p: &M::V, _t: M::T, y: &mut M::V| {
-                y[0] = -p[0] * x[0] + p[1] * x[1] * x[2];
-                y[1] = p[0] * x[0] - p[1] * x[1] * x[2] - p[2] * x[1] * x[1];
-                y[2] = x[0] + x[1] + x[2] - M::T::from(1.0);
+                y[0] = -p[0].clone() * x[0].clone() + p[1].clone() * x[1].clone() * x[2].clone();
+                y[1] = p[0].clone() * x[0].clone()
+                    - p[1].clone() * x[1].clone() * x[2].clone()
+                    - p[2].clone() * x[1].clone() * x[1].clone();
+                y[2] = x[0].clone() + x[1].clone() + x[2].clone() - M::T::cast(1.0);
             },
             |x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V| {
-                y[0] = -p[0] * v[0] + p[1] * v[1] * x[2] + p[1] * x[1] * v[2];
-                y[1] = p[0] * v[0]
-                    - p[1] * v[1] * x[2]
-                    - p[1] * x[1] * v[2]
-                    - M::T::from(2.0) * p[2] * x[1] * v[1];
-                y[2] = v[0] + v[1] + v[2];
+                y[0] = -p[0].clone() * v[0].clone()
+                    + p[1].clone() * v[1].clone() * x[2].clone()
+                    + p[1].clone() * x[1].clone() * v[2].clone();
+                y[1] = p[0].clone() * v[0].clone()
+                    - p[1].clone() * v[1].clone() * x[2].clone()
+                    - p[1].clone() * x[1].clone() * v[2].clone()
+                    - M::T::cast(2.0) * p[2].clone() * x[1].clone() * v[1].clone();
+                y[2] = v[0].clone() + v[1].clone() + v[2].clone();
             },
             |x: &M::V, _p: &M::V, _t: M::T, v: &M::V, y: &mut M::V| {
-                y[0] = -v[0] * x[0] + v[1] * x[1] * x[2];
-                y[1] = v[0] * x[0] - v[1] * x[1] * x[2] - v[2] * x[1] * x[1];
+                y[0] = -v[0].clone() * x[0].clone() + v[1].clone() * x[1].clone() * x[2].clone();
+                y[1] = v[0].clone() * x[0].clone()
+                    - v[1].clone() * x[1].clone() * x[2].clone()
+                    - v[2].clone() * x[1].clone() * x[1].clone();
                 y[2] = M::T::zero();
             },
             |x: &M::V, _p: &M::V, _t: M::T, beta: M::T, y: &mut M::V| {
-                y[0] = x[0] + beta * y[0];
-                y[1] = x[1] + beta * y[1];
-                y[2] = beta * y[2];
+                y[0] = x[0].clone() + beta.clone() * y[0].clone();
+                y[1] = x[1].clone() + beta.clone() * y[1].clone();
+                y[2] = beta * y[2].clone();
             },
             |_x: &M::V, _p: &M::V, _t: M::T, _v: &M::V, y: &mut M::V| {
                 y.fill(M::T::zero());
             },
-            |_p: &M::V, _t: M::T| M::V::from_vec(vec![1.0.into(), 0.0.into(), 0.0.into()]),
+            |_p: &M::V, _t: M::T| {
+                M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(0.0), M::T::cast(0.0)])
+            },
             |_p: &M::V, _t: M::T, _v: &M::V, y: &mut M::V| {
                 y.fill(M::T::zero());
             },
@@ -72,8 +78,8 @@ pub fn robertson_sens<M: Matrix + 'static>(
 
     for (values, time) in data {
         soln.push(
-            M::V::from_vec(values.into_iter().map(|v| v.into()).collect()),
-            time.into(),
+            M::V::from_vec(values.into_iter().map(M::T::cast).collect()),
+            M::T::cast(time),
         );
     }
 
@@ -0,0 +1,105 @@
+use crate::{
+    matrix::Matrix, ode_solver::problem::OdeSolverSolution, OdeBuilder, OdeEquations,
+    OdeSolverProblem, Scalar, Vector,
+};
+use nalgebra::ComplexField;
+use std::rc::Rc;
+
+// stiff forced decay problem
+// dy/dt = -a (y - sin(t)) + cos(t) (p = [a])
+// with solution y = sin(t) + exp(-a t) for y(0) = 1
+fn stiff_forced_decay<M: Matrix>(x: &M::V, p: &M::V, t: M::T, y: &mut M::V) {
+    for i in 0..x.len() {
+        y[i] = -p[0].clone() * (x[i].clone() - t.clone().sin()) + t.clone().cos();
+    }
+}
+
+// Jv = -av
+fn stiff_forced_decay_jacobian<M: Matrix>(_x: &M::V, p: &M::V, _t: M::T, v: &M::V, y: &mut M::V) {
+    for i in 0..v.len() {
+        y[i] = -p[0].clone() * v[i].clone();
+    }
+}
+
+fn stiff_forced_decay_init<M: Matrix>(_p: &M::V, _t: M::T) -> M::V {
+    M::V::from_vec(vec![M::T::cast(1.0), M::T::cast(1.0)])
+}
+
+/// The stiff forced decay equations, with the right-hand side split into the stiff decay `-a (y - sin(t))`
+/// and the non-stiff forcing `cos(t)`, which is given by [OdeEquations::rhs_explicit_inplace].
+pub struct StiffForcedDecayEquations<Eqn: OdeEquations> {
+    eqn: Eqn,
+}
+
+impl<Eqn: OdeEquations> OdeEquations for StiffForcedDecayEquations<Eqn> {
+    type T = Eqn::T;
+    type V = Eqn::V;
+    type M = Eqn::M;
+    type Rhs = Eqn::Rhs;
+    type Mass = Eqn::Mass;
+    type Root = Eqn::Root;
+    type Init = Eqn::Init;
+
+    fn rhs(&self) -> &Rc<Self::Rhs> {
+        self.eqn.rhs()
+    }
+    fn mass(&self) -> Option<&Rc<Self::Mass>> {
+        self.eqn.mass()
+    }
+    fn root(&self) -> Option<&Rc<Self::Root>> {
+        self.eqn.root()
+    }
+    fn init(&self) -> &Rc<Self::Init> {
+        self.eqn.init()
+    }
+    fn rhs_explicit_inplace(&self, _x: &Self::V, t: Self::T, y: &mut Self::V) {
+        y.fill(t.cos());
+    }
+    fn set_params(&mut self, p: Self::V) {
+        self.eqn.set_params(p);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn stiff_forced_decay_problem<M: Matrix + 'static>() -> (
+    OdeSolverProblem<impl OdeEquations<M = M, V = M::V, T = M::T>>,
+    OdeSolverSolution<M::V>,
+) {
+    let problem = OdeBuilder::new()
+        .p([1000.0])
+        .build_ode::<M, _, _, _>(
+            stiff_forced_decay::<M>,
+            stiff_forced_decay_jacobian::<M>,
+            stiff_forced_decay_init::<M>,
+        )
+        .unwrap();
+    let OdeSolverProblem {
+        eqn,
+        rtol,
+        atol,
+        t0,
+        h0,
+        ..
+    } = problem;
+    let eqn = Rc::into_inner(eqn).unwrap();
+    let atol = Rc::try_unwrap(atol).unwrap_or_else(|atol| atol.as_ref().clone());
+    let problem = OdeSolverProblem::new(
+        StiffForcedDecayEquations { eqn },
+        rtol,
+        atol,
+        t0,
+        h0,
+        false,
+        false,
+    )
+    .unwrap();
+
+    let a = M::T::cast(1000.0);
+    let mut soln = OdeSolverSolution::default();
+    for i in 0..10 {
+        let t = M::T::cast(i as f64);
+        let y = M::V::from_element(2, t.clone().sin() + M::T::exp(-a.clone() * t.clone()));
+        soln.push(y, t);
+    }
+    (problem, soln)
+}
@@ -0,0 +1,201 @@
+use crate::{errors::PSError, scalar::Scalar};
+
+/// A transformation between the natural scale of a parameter `p` (the value passed to the equations) and an unconstrained value `x`
+/// (the value seen by an optimiser or sampler).
+///
+/// - `Identity`: `p = x`
+/// - `Log`: `p = exp(x)`, for a positive parameter
+/// - `Logit`: `p = lower + (upper - lower) / (1 + exp(-x))`, for a parameter bounded between `lower` and `upper`
+/// - `Affine`: `p = offset + scale * x`, e.g. to rescale parameters of very different magnitudes
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterTransform<T: Scalar> {
+    Identity,
+    Log,
+    Logit { lower: T, upper: T },
+    Affine { scale: T, offset: T },
+}
+
+impl<T: Scalar> ParameterTransform<T> {
+    /// The natural-scale value `p` of the unconstrained value `x`
+    pub fn to_natural(&self, x: T) -> T {
+        match self.clone() {
+            Self::Identity => x,
+            Self::Log => x.exp(),
+            Self::Logit { lower, upper } => {
+                lower.clone() + (upper - lower) / (T::one() + (-x).exp())
+            }
+            Self::Affine { scale, offset } => offset + scale * x,
+        }
+    }
+
+    /// The unconstrained value `x` of the natural-scale value `p`
+    pub fn to_unconstrained(&self, p: T) -> T {
+        match self.clone() {
+            Self::Identity => p,
+            Self::Log => p.ln(),
+            Self::Logit { lower, upper } => ((p.clone() - lower) / (upper - p)).ln(),
+            Self::Affine { scale, offset } => (p - offset) / scale,
+        }
+    }
+
+    /// The derivative `dp/dx` of the natural-scale value with respect to the unconstrained value at `x`
+    pub fn derivative(&self, x: T) -> T {
+        match self.clone() {
+            Self::Identity => T::one(),
+            Self::Log => x.exp(),
+            Self::Logit { lower, upper } => {
+                let s = T::one() / (T::one() + (-x).exp());
+                (upper - lower) * s.clone() * (T::one() - s)
+            }
+            Self::Affine { scale, .. } => scale,
+        }
+    }
+
+    fn check(&self) -> Result<(), PSError> {
+        match self.clone() {
+            Self::Logit { lower, upper } if lower >= upper => Err(PSError::Other {
+                e: "lower bound of a logit transform must be less than the upper bound".to_string(),
+            }),
+            Self::Affine { scale, .. } if scale == T::zero() => Err(PSError::Other {
+                e: "scale of an affine transform must not be zero".to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A [ParameterTransform] for each parameter of a problem.
+///
+/// Optimisers and samplers work with the unconstrained values, which are mapped to the natural scale using [Self::to_natural] before being
+/// passed to the equations. Gradients with respect to the natural-scale parameters (e.g. calculated from the sensitivities) are mapped back
+/// to gradients with respect to the unconstrained values using the chain rule in [Self::chain_gradient].
+#[derive(Clone, Debug)]
+pub struct ParameterTransforms<T: Scalar> {
+    transforms: Vec<ParameterTransform<T>>,
+}
+
+impl<T: Scalar> ParameterTransforms<T> {
+    pub fn new(transforms: Vec<ParameterTransform<T>>) -> Result<Self, PSError> {
+        for transform in transforms.iter() {
+            transform.check()?;
+        }
+        Ok(Self { transforms })
+    }
+
+    pub fn transforms(&self) -> &[ParameterTransform<T>] {
+        &self.transforms
+    }
+
+    pub fn nparams(&self) -> usize {
+        self.transforms.len()
+    }
+
+    pub fn to_natural(&self, x: &[T]) -> Vec<T> {
+        self.transforms
+            .iter()
+            .zip(x.iter())
+            .map(|(tr, xi)| tr.to_natural(xi.clone()))
+            .collect()
+    }
+
+    pub fn to_unconstrained(&self, p: &[T]) -> Vec<T> {
+        self.transforms
+            .iter()
+            .zip(p.iter())
+            .map(|(tr, pi)| tr.to_unconstrained(pi.clone()))
+            .collect()
+    }
+
+    /// Convert the gradient `grad` with respect to the natural-scale parameters into the gradient with respect to the unconstrained values `x`, in place
+    pub fn chain_gradient(&self, x: &[T], grad: &mut [T]) {
+        for ((tr, xi), g) in self.transforms.iter().zip(x.iter()).zip(grad.iter_mut()) {
+            *g *= tr.derivative(xi.clone());
+        }
+    }
+
+    /// The log of the absolute value of the determinant of the jacobian `dp/dx` at `x`. This must be added to a log-density on the natural scale
+    /// to give the log-density of the unconstrained values (e.g. when sampling in the unconstrained space).
+    pub fn log_abs_det_jacobian(&self, x: &[T]) -> T {
+        self.transforms
+            .iter()
+            .zip(x.iter())
+            .fold(T::zero(), |acc, (tr, xi)| {
+                acc + tr.derivative(xi.clone()).abs().ln()
+            })
+    }
+
+    /// The gradient of [Self::log_abs_det_jacobian] with respect to each of the unconstrained values `x`
+    pub fn log_abs_det_jacobian_gradient(&self, x: &[T]) -> Vec<T> {
+        self.transforms
+            .iter()
+            .zip(x.iter())
+            .map(|(tr, xi)| match tr {
+                ParameterTransform::Identity | ParameterTransform::Affine { .. } => T::zero(),
+                ParameterTransform::Log => T::one(),
+                ParameterTransform::Logit { .. } => {
+                    // d/dx log(s (1 - s)) = 1 - 2 s
+                    let s = T::one() / (T::one() + (-xi.clone()).exp());
+
+                    T::one() - T::cast(2.0) * s
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ode_solver::transform::{ParameterTransform, ParameterTransforms};
+
+    #[test]
+    fn test_parameter_transforms() {
+        let transforms = ParameterTransforms::new(vec![
+            ParameterTransform::Identity,
+            ParameterTransform::Log,
+            ParameterTransform::Logit {
+                lower: 1.0,
+                upper: 3.0,
+            },
+            ParameterTransform::Affine {
+                scale: 100.0,
+                offset: 1.0,
+            },
+        ])
+        .unwrap();
+        let p = [-0.5_f64, 0.2, 2.5, 51.0];
+        let x = transforms.to_unconstrained(&p);
+        assert_eq!(x[3], 0.5);
+        let p2 = transforms.to_natural(&x);
+        for (a, b) in p.iter().zip(p2.iter()) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // check the derivatives and log determinant against finite differences
+        let eps = 1e-6;
+        let mut grad = vec![1.0; 4];
+        transforms.chain_gradient(&x, &mut grad);
+        let ljac_grad = transforms.log_abs_det_jacobian_gradient(&x);
+        for i in 0..4 {
+            let mut xp = x.clone();
+            let mut xm = x.clone();
+            xp[i] += eps;
+            xm[i] -= eps;
+            let fd = (transforms.to_natural(&xp)[i] - transforms.to_natural(&xm)[i]) / (2.0 * eps);
+            assert!((grad[i] - fd).abs() < 1e-6 * fd.abs().max(1.0));
+            let fd = (transforms.log_abs_det_jacobian(&xp) - transforms.log_abs_det_jacobian(&xm))
+                / (2.0 * eps);
+            assert!((ljac_grad[i] - fd).abs() < 1e-6);
+        }
+
+        assert!(ParameterTransforms::new(vec![ParameterTransform::Logit {
+            lower: 1.0,
+            upper: 1.0
+        }])
+        .is_err());
+        assert!(ParameterTransforms::new(vec![ParameterTransform::Affine {
+            scale: 0.0,
+            offset: 1.0
+        }])
+        .is_err());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{errors::PSError, scalar::Scalar, Vector};
+
+/// A chain of `ntransit` transit compartments used to model delayed absorption, appended to the states of an absorption model.
+///
+/// The chain occupies the states `offset..offset + ntransit`, and empties into the absorption compartment (e.g. the depot) at state `target`.
+/// A dose enters the first compartment of the chain, which is at [Self::dose_index]. For a mean transit time `mtt`, the transit rate constant is
+/// `ktr = (ntransit + 1) / mtt` and the equations for the chain are
+///
+/// - `dA_1/dt = -ktr A_1`
+/// - `dA_i/dt = ktr (A_{i-1} - A_i)` for `i = 2..ntransit`
+///
+/// with a flux of `ktr A_ntransit` added to the target state. With `ntransit = 0` the chain is empty and doses go directly into the target state,
+/// so the length of the chain can be changed without changing the rest of the model. The chain is linear in the states, so [Self::add_jac_mul]
+/// gives the jacobian-vector product, and [Self::jacobian_sparsity] the non-zeros of the jacobian (for use with [crate::OdeBuilder::jacobian_sparsity]).
+///
+/// # Example
+///
+/// ```rust
+/// use diffsol::{OdeBuilder, TransitChain};
+/// type M = nalgebra::DMatrix<f64>;
+/// type V = nalgebra::DVector<f64>;
+///
+/// // one compartment model with first-order absorption from the depot (state 0) into the central compartment (state 1),
+/// // with absorption delayed by a chain of 3 transit compartments, p = [ka, k, mtt]
+/// let chain = TransitChain::new(3, 2, 0);
+/// let nstates = 2 + chain.ntransit();
+/// let problem = OdeBuilder::new()
+///     .p([1.0, 0.1, 2.0])
+///     .build_ode::<M, _, _, _>(
+///         move |x: &V, p: &V, _t, y: &mut V| {
+///             y[0] = -p[0] * x[0];
+///             y[1] = p[0] * x[0] - p[1] * x[1];
+///             chain.add_rhs(x, p[2], y);
+///         },
+///         move |_x: &V, p: &V, _t, v: &V, y: &mut V| {
+///             y[0] = -p[0] * v[0];
+///             y[1] = p[0] * v[0] - p[1] * v[1];
+///             chain.add_jac_mul(v, p[2], y);
+///         },
+///         move |_p: &V, _t| V::zeros(nstates),
+///     )
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct TransitChain {
+    ntransit: usize,
+    offset: usize,
+    target: usize,
+}
+
+impl TransitChain {
+    /// Create a chain of `ntransit` compartments at states `offset..offset + ntransit`, emptying into the state at `target`
+    pub fn new(ntransit: usize, offset: usize, target: usize) -> Self {
+        Self {
+            ntransit,
+            offset,
+            target,
+        }
+    }
+
+    pub fn ntransit(&self) -> usize {
+        self.ntransit
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn target(&self) -> usize {
+        self.target
+    }
+
+    /// The index of the state that a dose should be added to, this is the first compartment of the chain (or the target state for an empty chain)
+    pub fn dose_index(&self) -> usize {
+        if self.ntransit == 0 {
+            self.target
+        } else {
+            self.offset
+        }
+    }
+
+    /// The index of the `i`th compartment of the chain (counting from zero)
+    pub fn state_index(&self, i: usize) -> usize {
+        assert!(i < self.ntransit, "transit compartment index out of range");
+        self.offset + i
+    }
+
+    /// The transit rate constant `ktr = (ntransit + 1) / mtt` for a mean transit time `mtt`
+    pub fn ktr<T: Scalar>(&self, mtt: T) -> T {
+        T::cast((self.ntransit + 1) as f64) / mtt
+    }
+
+    /// Check that the chain fits within a model with `nstates` states, and does not overlap with the target state
+    pub fn check(&self, nstates: usize) -> Result<(), PSError> {
+        if self.offset + self.ntransit > nstates || self.target >= nstates {
+            return Err(PSError::DimensionMismatch {
+                name: "transit chain states".to_string(),
+                expected: nstates,
+                found: (self.offset + self.ntransit).max(self.target + 1),
+            });
+        }
+        if self.ntransit > 0 && (self.offset..self.offset + self.ntransit).contains(&self.target) {
+            return Err(PSError::Other {
+                e: "the target state of a transit chain must not be part of the chain".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Set the derivatives of the chain states in `y` for the state `x` and mean transit time `mtt`, and add the flux out of the chain to the target state.
+    /// The other states of `y` are not modified, so this should be called after the rest of the right-hand side has been calculated.
+    pub fn add_rhs<V: Vector>(&self, x: &V, mtt: V::T, y: &mut V) {
+        self.add_linear(x, self.ktr(mtt), y);
+    }
+
+    /// Set the jacobian-vector product for the chain states in `y`, and add the contribution of the chain to the target state, as for [Self::add_rhs].
+    pub fn add_jac_mul<V: Vector>(&self, v: &V, mtt: V::T, y: &mut V) {
+        self.add_linear(v, self.ktr(mtt), y);
+    }
+
+    /// The non-zeros of the jacobian of the chain, as `(row, column)` pairs
+    pub fn jacobian_sparsity(&self) -> Vec<(usize, usize)> {
+        let mut non_zeros = Vec::with_capacity(2 * self.ntransit);
+        for i in 0..self.ntransit {
+            let j = self.offset + i;
+            non_zeros.push((j, j));
+            if i > 0 {
+                non_zeros.push((j, j - 1));
+            }
+        }
+        if self.ntransit > 0 {
+            non_zeros.push((self.target, self.offset + self.ntransit - 1));
+        }
+        non_zeros
+    }
+
+    fn add_linear<V: Vector>(&self, x: &V, ktr: V::T, y: &mut V) {
+        if self.ntransit == 0 {
+            return;
+        }
+        let first = self.offset;
+        y[first] = -ktr.clone() * x[first].clone();
+        for j in first + 1..first + self.ntransit {
+            y[j] = ktr.clone() * (x[j - 1].clone() - x[j].clone());
+        }
+        y[self.target] += ktr * x[first + self.ntransit - 1].clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ode_solver::transit::TransitChain, Bdf, DosingSchedule, OdeBuilder, OdeEquations,
+        OdeSolverProblem, Op,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    // a transit chain emptying into a target state with no elimination, p = [mtt]
+    fn chain_problem(
+        chain: TransitChain,
+    ) -> OdeSolverProblem<impl OdeEquations<M = M, V = V, T = f64>> {
+        let nstates = chain.ntransit() + 1;
+        OdeBuilder::new()
+            .p([2.0])
+            .rtol(1e-8)
+            .atol([1e-8])
+            .build_ode::<M, _, _, _>(
+                move |x: &V, p: &V, _t, y: &mut V| {
+                    y[0] = 0.0;
+                    chain.add_rhs(x, p[0], y);
+                },
+                move |_x: &V, p: &V, _t, v: &V, y: &mut V| {
+                    y[0] = 0.0;
+                    chain.add_jac_mul(v, p[0], y);
+                },
+                move |_p: &V, _t| V::zeros(nstates),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_transit_chain_erlang() {
+        // the amount that has reached the target after a unit dose is the cdf of an Erlang(n, ktr) distribution
+        for n in [0, 1, 3] {
+            let chain = TransitChain::new(n, 1, 0);
+            assert!(chain.check(n + 1).is_ok());
+            let problem = chain_problem(chain);
+            let mut problem = problem.with_infusions().unwrap();
+            assert_eq!(problem.eqn.rhs().nstates(), n + 1);
+            let mut schedule = DosingSchedule::new();
+            schedule.add_dose(0.0, 1.0, chain.dose_index()).unwrap();
+            let t_eval = [0.5, 1.0, 2.0, 5.0];
+            let mut solver = Bdf::default();
+            let soln = schedule.solve(&mut solver, &mut problem, &t_eval).unwrap();
+            let ktr = chain.ktr(2.0);
+            for (&t, y) in t_eval.iter().zip(soln.iter()) {
+                let mut term = 1.0;
+                let mut sum = 0.0;
+                for j in 0..n {
+                    if j > 0 {
+                        term *= ktr * t / j as f64;
+                    }
+                    sum += term;
+                }
+                // the dose is given just after t = 0, so with an empty chain it is all in the target
+                let expect = if n == 0 {
+                    1.0
+                } else {
+                    1.0 - f64::exp(-ktr * t) * sum
+                };
+                assert!(
+                    (y[0] - expect).abs() < 1e-5,
+                    "n = {} t = {} y = {} expected {}",
+                    n,
+                    t,
+                    y[0],
+                    expect
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_transit_chain_sparsity() {
+        let chain = TransitChain::new(3, 2, 0);
+        assert_eq!(chain.dose_index(), 2);
+        assert_eq!(chain.state_index(2), 4);
+        assert_eq!(
+            chain.jacobian_sparsity(),
+            vec![(2, 2), (3, 3), (3, 2), (4, 4), (4, 3), (0, 4)]
+        );
+        assert!(chain.check(5).is_ok());
+        assert!(chain.check(4).is_err());
+        assert!(TransitChain::new(2, 0, 1).check(3).is_err());
+        assert_eq!(TransitChain::new(0, 2, 0).jacobian_sparsity(), vec![]);
+    }
+}
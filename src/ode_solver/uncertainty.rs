@@ -0,0 +1,244 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use crate::{
+    errors::PSError, matrix::default_solver::DefaultSolver, scalar::Scalar, OdeEquations,
+    OdeSolverMethod, OdeSolverProblem, Op, Vector,
+};
+
+/// A multivariate normal or log-normal distribution of the parameter vector, used to propagate parameter uncertainty through the model.
+///
+/// For a normal distribution the parameters are sampled as `p = mean + L z`, where `L` is the lower Cholesky factor of the covariance
+/// matrix and `z` a vector of independent standard normal variables. For a log-normal distribution `mean` and the covariance are those of `log(p)`,
+/// so that `p = exp(mean + L z)` is always positive.
+#[derive(Clone, Debug)]
+pub struct ParameterDistribution<T: Scalar> {
+    mean: Vec<T>,
+    chol: Vec<T>,
+    log_normal: bool,
+}
+
+impl<T: Scalar> ParameterDistribution<T> {
+    /// A multivariate normal distribution, with covariance matrix `cov` stored row-major (which must be symmetric positive definite)
+    pub fn normal(mean: Vec<T>, cov: Vec<T>) -> Result<Self, PSError> {
+        let chol = Self::cholesky(mean.len(), cov)?;
+        Ok(Self {
+            mean,
+            chol,
+            log_normal: false,
+        })
+    }
+
+    /// A multivariate log-normal distribution, where `mean` and `cov` (stored row-major) are the mean and covariance of the log of the parameters
+    pub fn log_normal(mean: Vec<T>, cov: Vec<T>) -> Result<Self, PSError> {
+        let chol = Self::cholesky(mean.len(), cov)?;
+        Ok(Self {
+            mean,
+            chol,
+            log_normal: true,
+        })
+    }
+
+    /// Independent normal distributions for each parameter, with standard deviations `sd`
+    pub fn independent_normal(mean: Vec<T>, sd: Vec<T>) -> Result<Self, PSError> {
+        let cov = Self::diagonal(&sd);
+        Self::normal(mean, cov)
+    }
+
+    /// Independent log-normal distributions for each parameter, where `sd` is the standard deviation of the log of each parameter
+    pub fn independent_log_normal(mean: Vec<T>, sd: Vec<T>) -> Result<Self, PSError> {
+        let cov = Self::diagonal(&sd);
+        Self::log_normal(mean, cov)
+    }
+
+    pub fn nparams(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn is_log_normal(&self) -> bool {
+        self.log_normal
+    }
+
+    /// Sample a parameter vector from the distribution
+    pub fn sample<V: Vector<T = T>, R: Rng + ?Sized>(&self, rng: &mut R) -> V {
+        let n = self.mean.len();
+        let z = (0..n)
+            .map(|_| T::cast(rng.sample::<f64, _>(StandardNormal)))
+            .collect::<Vec<_>>();
+        let mut p = V::zeros(n);
+        for i in 0..n {
+            let mut pi = self.mean[i].clone();
+            for (j, zj) in z.iter().enumerate().take(i + 1) {
+                pi += self.chol[i * n + j].clone() * zj.clone();
+            }
+            p[i] = if self.log_normal { pi.exp() } else { pi };
+        }
+        p
+    }
+
+    /// Sample `nsamples` parameter vectors, solve `problem` with each, and return the solutions at the output times `t_eval` (which must be sorted).
+    /// The solver is reused between samples, and the solution is interpolated at each output time. On return the parameters of `problem` are set to the last sample.
+    pub fn monte_carlo<Eqn, S, R>(
+        &self,
+        solver: &mut S,
+        problem: &mut OdeSolverProblem<Eqn>,
+        nsamples: usize,
+        t_eval: &[T],
+        rng: &mut R,
+    ) -> Result<MonteCarloSolution<Eqn::V>, PSError>
+    where
+        Eqn: OdeEquations<T = T>,
+        Eqn::M: DefaultSolver,
+        S: OdeSolverMethod<Eqn>,
+        R: Rng + ?Sized,
+    {
+        if self.nparams() != problem.eqn.rhs().nparams() {
+            return Err(PSError::DimensionMismatch {
+                name: "parameter distribution".to_string(),
+                expected: problem.eqn.rhs().nparams(),
+                found: self.nparams(),
+            });
+        }
+        if t_eval.windows(2).any(|w| w[0] > w[1]) {
+            return Err(PSError::Other {
+                e: "output times must be sorted".to_string(),
+            });
+        }
+        let mut params = Vec::with_capacity(nsamples);
+        let mut y = Vec::with_capacity(nsamples);
+        for _ in 0..nsamples {
+            let p: Eqn::V = self.sample(rng);
+            // release the previous problem so the parameters can be updated in place
+            solver.take_state();
+            problem.set_params(p.clone())?;
+            y.push(solver.solve_dense(problem, t_eval)?.y);
+            params.push(p);
+        }
+        solver.take_state();
+        Ok(MonteCarloSolution {
+            t: t_eval.to_vec(),
+            params,
+            y,
+        })
+    }
+
+    fn diagonal(sd: &[T]) -> Vec<T> {
+        let n = sd.len();
+        let mut cov = vec![T::zero(); n * n];
+        for (i, s) in sd.iter().enumerate() {
+            cov[i * n + i] = s.clone() * s.clone();
+        }
+        cov
+    }
+
+    // lower Cholesky factor of a symmetric positive definite matrix, stored row-major
+    fn cholesky(n: usize, cov: Vec<T>) -> Result<Vec<T>, PSError> {
+        if cov.len() != n * n {
+            return Err(PSError::DimensionMismatch {
+                name: "covariance matrix".to_string(),
+                expected: n * n,
+                found: cov.len(),
+            });
+        }
+        let mut l = vec![T::zero(); n * n];
+        for i in 0..n {
+            for j in 0..=i {
+                let mut sum = cov[i * n + j].clone();
+                for k in 0..j {
+                    sum -= l[i * n + k].clone() * l[j * n + k].clone();
+                }
+                if i == j {
+                    if sum <= T::zero() {
+                        return Err(PSError::Other {
+                            e: "covariance matrix must be symmetric positive definite".to_string(),
+                        });
+                    }
+                    l[i * n + i] = sum.sqrt();
+                } else {
+                    l[i * n + j] = sum / l[j * n + j].clone();
+                }
+            }
+        }
+        Ok(l)
+    }
+}
+
+/// The solutions of a Monte Carlo simulation at each output time `t`, with `y[i][j]` the solution for the parameter sample `params[i]` at time `t[j]`
+#[derive(Clone, Debug)]
+pub struct MonteCarloSolution<V: Vector> {
+    pub t: Vec<V::T>,
+    pub params: Vec<V>,
+    pub y: Vec<Vec<V>>,
+}
+
+impl<V: Vector> MonteCarloSolution<V> {
+    pub fn nsamples(&self) -> usize {
+        self.params.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        ode_solver::{
+            test_models::exponential_decay::exponential_decay_problem,
+            uncertainty::ParameterDistribution,
+        },
+        Bdf,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_parameter_distribution_covariance() {
+        let dist = ParameterDistribution::normal(vec![1.0, 2.0], vec![4.0, 1.2, 1.2, 1.0]).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let n = 20000;
+        let samples = (0..n)
+            .map(|_| dist.sample::<V, _>(&mut rng))
+            .collect::<Vec<_>>();
+        let mean = samples.iter().fold(V::zeros(2), |acc, p| acc + p) / n as f64;
+        assert!((mean[0] - 1.0).abs() < 0.05);
+        assert!((mean[1] - 2.0).abs() < 0.05);
+        let cov01 = samples
+            .iter()
+            .map(|p| (p[0] - mean[0]) * (p[1] - mean[1]))
+            .sum::<f64>()
+            / (n - 1) as f64;
+        assert!((cov01 - 1.2).abs() < 0.1);
+
+        assert!(ParameterDistribution::normal(vec![1.0, 2.0], vec![1.0, 2.0, 2.0, 1.0]).is_err());
+        assert!(ParameterDistribution::normal(vec![1.0], vec![1.0, 0.0]).is_err());
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let (mut problem, _soln) = exponential_decay_problem::<M>();
+        let mut solver = Bdf::default();
+        let dist =
+            ParameterDistribution::independent_log_normal(vec![f64::ln(0.1)], vec![0.3]).unwrap();
+        assert!(dist.is_log_normal());
+        let mut rng = StdRng::seed_from_u64(0);
+        let t_eval = [0.0, 1.0, 5.0];
+        let soln = dist
+            .monte_carlo(&mut solver, &mut problem, 20, &t_eval, &mut rng)
+            .unwrap();
+        assert_eq!(soln.nsamples(), 20);
+        for (p, y) in soln.params.iter().zip(soln.y.iter()) {
+            assert!(p[0] > 0.0);
+            assert_eq!(y.len(), 3);
+            for (&t, yi) in t_eval.iter().zip(y.iter()) {
+                assert!((yi[0] - f64::exp(-p[0] * t)).abs() < 1e-4);
+            }
+        }
+
+        let dist =
+            ParameterDistribution::independent_normal(vec![0.1, 0.2], vec![0.01, 0.01]).unwrap();
+        assert!(dist
+            .monte_carlo(&mut solver, &mut problem, 1, &t_eval, &mut rng)
+            .is_err());
+    }
+}
@@ -1,15 +1,15 @@
 use crate::{
-    ode_solver::equations::OdeEquations, LinearOp, Matrix, MatrixRef, MatrixSparsity,
-    MatrixSparsityRef, OdeSolverProblem, Vector, VectorRef,
+    ode_solver::equations::OdeEquations, JacobianUpdatePolicy, LinearOp, Matrix, MatrixRef,
+    MatrixSparsity, OdeSolverProblem, Vector, VectorRef,
 };
-use num_traits::{One, Zero};
+use num_traits::{abs, One, Zero};
 use std::{
     cell::{Ref, RefCell},
     ops::{AddAssign, Deref, SubAssign},
     rc::Rc,
 };
 
-use super::{NonLinearOp, Op};
+use super::{implicit_jacobian_storage, NonLinearOp, Op};
 
 // callable to solve for F(y) = M (y' + psi) - c * f(y) = 0
 pub struct BdfCallable<Eqn: OdeEquations> {
@@ -20,6 +20,10 @@ pub struct BdfCallable<Eqn: OdeEquations> {
     rhs_jac: RefCell<Eqn::M>,
     mass_jac: RefCell<Eqn::M>,
     jacobian_is_stale: RefCell<bool>,
+    jacobian_update: JacobianUpdatePolicy<Eqn::T>,
+    // the number of steps and value of c since the rhs jacobian was last evaluated
+    steps_since_jacobian: RefCell<usize>,
+    c_at_jacobian: RefCell<Eqn::T>,
     number_of_jac_evals: RefCell<usize>,
     sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
 }
@@ -43,6 +47,9 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
             rhs_jac,
             mass_jac,
             jacobian_is_stale,
+            jacobian_update: JacobianUpdatePolicy::OnConvergenceFailure,
+            steps_since_jacobian: RefCell::new(0),
+            c_at_jacobian: RefCell::new(Eqn::T::zero()),
             number_of_jac_evals,
             tmp,
             sparsity,
@@ -60,40 +67,8 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
         let number_of_jac_evals = RefCell::new(0);
         let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
 
-        // create the mass and rhs jacobians according to the sparsity pattern
-        let rhs_jac_sparsity = eqn.rhs().sparsity();
-        let rhs_jac = RefCell::new(Eqn::M::new_from_sparsity(
-            n,
-            n,
-            rhs_jac_sparsity.map(|s| s.to_owned()),
-        ));
-        let sparsity = if let Some(rhs_jac_sparsity) = eqn.rhs().sparsity() {
-            if let Some(mass) = eqn.mass() {
-                // have mass, use the union of the mass and rhs jacobians sparse patterns
-                Some(
-                    mass.sparsity()
-                        .unwrap()
-                        .to_owned()
-                        .union(rhs_jac_sparsity)
-                        .unwrap(),
-                )
-            } else {
-                // no mass, use the identity
-                let mass_sparsity = <Eqn::M as Matrix>::Sparsity::new_diagonal(n);
-                Some(mass_sparsity.union(rhs_jac_sparsity).unwrap())
-            }
-        } else {
-            None
-        };
-
-        let mass_jac = if eqn.mass().is_none() {
-            // no mass matrix, so just use the identity
-            Eqn::M::from_diagonal(&Eqn::V::from_element(n, Eqn::T::one()))
-        } else {
-            // mass is not constant, so just create a matrix with the correct sparsity
-            Eqn::M::new_from_sparsity(n, n, eqn.mass().unwrap().sparsity().map(|s| s.to_owned()))
-        };
-
+        let (rhs_jac, mass_jac, sparsity) = implicit_jacobian_storage(eqn.as_ref());
+        let rhs_jac = RefCell::new(rhs_jac);
         let mass_jac = RefCell::new(mass_jac);
 
         Self {
@@ -103,6 +78,9 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
             rhs_jac,
             mass_jac,
             jacobian_is_stale,
+            jacobian_update: ode_problem.options.jacobian_update.clone(),
+            steps_since_jacobian: RefCell::new(0),
+            c_at_jacobian: RefCell::new(Eqn::T::zero()),
             number_of_jac_evals,
             tmp,
             sparsity,
@@ -119,7 +97,7 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
         self.psi_neg_y0.replace(psi_neg_y0);
     }
 
-    pub fn tmp(&self) -> Ref<Eqn::V> {
+    pub fn tmp(&self) -> Ref<'_, Eqn::V> {
         self.tmp.borrow()
     }
 
@@ -130,18 +108,38 @@ impl<Eqn: OdeEquations> BdfCallable<Eqn> {
     where
         for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
     {
-        self.c.replace(h * alpha);
+        let c = h * alpha;
+        self.c.replace(c.clone());
+        if let JacobianUpdatePolicy::OnStepSizeChange(max_change) = self.jacobian_update.clone() {
+            let c_at_jacobian = self.c_at_jacobian.borrow().clone();
+            if abs(c - c_at_jacobian.clone()) > max_change * abs(c_at_jacobian) {
+                self.set_jacobian_is_stale();
+            }
+        }
     }
-    pub fn set_psi_and_y0(&self, psi: Eqn::V, y0: &Eqn::V) {
-        let mut new_psi_neg_y0 = psi;
+    pub fn set_psi_and_y0(&self, psi: &Eqn::V, y0: &Eqn::V) {
+        let mut psi_neg_y0 = self.psi_neg_y0.borrow_mut();
+        psi_neg_y0.copy_from(psi);
 
         // now negate y0
-        new_psi_neg_y0.sub_assign(y0);
-        self.psi_neg_y0.replace(new_psi_neg_y0);
+        psi_neg_y0.sub_assign(y0);
     }
     pub fn set_jacobian_is_stale(&self) {
         self.jacobian_is_stale.replace(true);
     }
+
+    /// Called at the start of each step, marks the rhs jacobian as stale if required by the [JacobianUpdatePolicy] of the problem.
+    /// Returns true if the jacobian is stale, in which case the caller should reset the jacobian of the nonlinear solver.
+    pub fn new_step(&self) -> bool {
+        let steps = *self.steps_since_jacobian.borrow() + 1;
+        self.steps_since_jacobian.replace(steps);
+        match self.jacobian_update {
+            JacobianUpdatePolicy::EveryStep => self.set_jacobian_is_stale(),
+            JacobianUpdatePolicy::AfterSteps(n) if steps >= n => self.set_jacobian_is_stale(),
+            _ => return false,
+        }
+        true
+    }
 }
 
 impl<Eqn: OdeEquations> Op for BdfCallable<Eqn> {
@@ -175,12 +173,12 @@ where
         let psi_neg_y0_ref = self.psi_neg_y0.borrow();
         let psi_neg_y0 = psi_neg_y0_ref.deref();
 
-        self.eqn.rhs().call_inplace(x, t, y);
+        self.eqn.rhs().call_inplace(x, t.clone(), y);
 
         let mut tmp = self.tmp.borrow_mut();
         tmp.copy_from(x);
         tmp.add_assign(psi_neg_y0);
-        let c = *self.c.borrow().deref();
+        let c = self.c.borrow().deref().clone();
         // y = M tmp - c * y
         if let Some(mass) = self.eqn.mass() {
             mass.gemv_inplace(&tmp, t, -c, y);
@@ -190,8 +188,8 @@ where
     }
     // (M - c * f'(y)) v
     fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
-        self.eqn.rhs().jac_mul_inplace(x, t, v, y);
-        let c = *self.c.borrow().deref();
+        self.eqn.rhs().jac_mul_inplace(x, t.clone(), v, y);
+        let c = self.c.borrow().deref().clone();
         // y = Mv - c y
         if let Some(mass) = self.eqn.mass() {
             mass.gemv_inplace(v, t, -c, y);
@@ -202,25 +200,27 @@ where
 
     // M - c * f'(y)
     fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
-        if *self.jacobian_is_stale.borrow() {
+        let c = self.c.borrow().deref().clone();
+        let is_full_newton = matches!(self.jacobian_update, JacobianUpdatePolicy::EveryIteration);
+        if *self.jacobian_is_stale.borrow() || is_full_newton {
             // calculate the mass and rhs jacobians
             let mut rhs_jac = self.rhs_jac.borrow_mut();
-            self.eqn.rhs().jacobian_inplace(x, t, &mut rhs_jac);
-            let c = *self.c.borrow().deref();
+            self.eqn.rhs().jacobian_inplace(x, t.clone(), &mut rhs_jac);
             if self.eqn.mass().is_none() {
                 let mass_jac = self.mass_jac.borrow();
-                y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
+                y.scale_add_and_assign(mass_jac.deref(), -c.clone(), rhs_jac.deref());
             } else {
                 let mut mass_jac = self.mass_jac.borrow_mut();
                 self.eqn.mass().unwrap().matrix_inplace(t, &mut mass_jac);
-                y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
+                y.scale_add_and_assign(mass_jac.deref(), -c.clone(), rhs_jac.deref());
             }
             self.jacobian_is_stale.replace(false);
+            self.steps_since_jacobian.replace(0);
+            self.c_at_jacobian.replace(c);
         } else {
             // only c has changed, so just do the addition
             let rhs_jac = self.rhs_jac.borrow();
             let mass_jac = self.mass_jac.borrow();
-            let c = *self.c.borrow().deref();
             y.scale_add_and_assign(mass_jac.deref(), -c, rhs_jac.deref());
         }
         let number_of_jac_evals = *self.number_of_jac_evals.borrow() + 1;
@@ -240,7 +240,7 @@ mod tests {
 
     #[test]
     fn test_bdf_callable() {
-        let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_problem::<Mcpu>();
         let mut bdf_callable = BdfCallable::new(&problem);
         let c = 0.1;
         let phi_neg_y0 = Vcpu::from_vec(vec![1.1, 1.2]);
@@ -1,12 +1,17 @@
-// a callable that takes another callable and a mask vector
-// this callable, when called, will call the other callable with the mask applied
-
 use std::{cell::RefCell, rc::Rc};
 
+use num_traits::Zero;
+
 use crate::{Vector, VectorIndex};
 
 use super::{NonLinearOp, Op};
 
+/// A restriction of a [NonLinearOp] `F(x, t)` to a subset of its indices.
+///
+/// The filtered op acts on the subset `x_s` of the full state `x` given by `indices`, with the remaining states frozen at their current values.
+/// That is, the filtered op computes `F_s(x_s, t) = R F(P(x_s), t)`, where `P` is the prolongation that scatters `x_s` into the frozen full state,
+/// and `R` is the restriction that gathers the outputs at `indices`. This can be used to solve a nonlinear system on a subset of the states
+/// (e.g. only the algebraic states of a DAE) while the rest are held fixed.
 pub struct FilterCallable<C: NonLinearOp> {
     callable: Rc<C>,
     indices: <C::V as Vector>::Index,
@@ -16,6 +21,7 @@ pub struct FilterCallable<C: NonLinearOp> {
 }
 
 impl<C: NonLinearOp> FilterCallable<C> {
+    /// Create a new filtered op acting on the given `indices`, with the remaining states frozen at the values in `x`.
     pub fn new(callable: Rc<C>, x: &C::V, indices: <C::V as Vector>::Index) -> Self {
         let y_full = RefCell::new(C::V::zeros(callable.nout()));
         let x_full = RefCell::new(x.clone());
@@ -29,9 +35,43 @@ impl<C: NonLinearOp> FilterCallable<C> {
         }
     }
 
+    /// Create a new filtered op acting on the algebraic states given by the DAE id vector `id`
+    /// (i.e. the states where `id` is zero), with the differential states frozen at the values in `x`.
+    pub fn new_algebraic(callable: Rc<C>, x: &C::V, id: &C::V) -> Self {
+        let indices = id.filter_indices(|x| x == C::T::zero());
+        Self::new(callable, x, indices)
+    }
+
+    /// Create a new filtered op acting on the differential states given by the DAE id vector `id`
+    /// (i.e. the states where `id` is non-zero), with the algebraic states frozen at the values in `x`.
+    pub fn new_differential(callable: Rc<C>, x: &C::V, id: &C::V) -> Self {
+        let indices = id.filter_indices(|x| x != C::T::zero());
+        Self::new(callable, x, indices)
+    }
+
     pub fn indices(&self) -> &<C::V as Vector>::Index {
         &self.indices
     }
+
+    pub fn callable(&self) -> &Rc<C> {
+        &self.callable
+    }
+
+    /// Set the values of the frozen states from the full state vector `x` (the values at `indices` are ignored).
+    pub fn set_frozen(&self, x: &C::V) {
+        self.x_full.borrow_mut().copy_from(x);
+    }
+
+    /// Restrict the full vector `x_full` to the subset of states given by `indices`.
+    pub fn restrict(&self, x_full: &C::V) -> C::V {
+        x_full.filter(&self.indices)
+    }
+
+    /// Prolong the subset vector `x` to the full state, filling in the frozen states, and store the result in `x_full`.
+    pub fn prolong(&self, x: &C::V, x_full: &mut C::V) {
+        x_full.copy_from(&self.x_full.borrow());
+        x_full.scatter_from(x, &self.indices);
+    }
 }
 
 impl<C: NonLinearOp> Op for FilterCallable<C> {
@@ -67,4 +107,70 @@ impl<C: NonLinearOp> NonLinearOp for FilterCallable<C> {
             .jac_mul_inplace(&x_full, t, &v_full, &mut y_full);
         y.gather_from(&y_full, &self.indices);
     }
+    fn sens_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        let mut y_full = self.y_full.borrow_mut();
+        let mut x_full = self.x_full.borrow_mut();
+        x_full.scatter_from(x, &self.indices);
+        self.callable.sens_mul_inplace(&x_full, t, v, &mut y_full);
+        y.gather_from(&y_full, &self.indices);
+    }
+    fn has_sens(&self) -> bool {
+        self.callable.has_sens()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ode_solver::test_models::exponential_decay_with_algebraic::exponential_decay_with_algebraic_problem;
+    use crate::op::filter::FilterCallable;
+    use crate::op::{NonLinearOp, Op};
+    use crate::vector::Vector;
+    use crate::{LinearOp, OdeEquations};
+
+    type Mcpu = nalgebra::DMatrix<f64>;
+    type Vcpu = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_filter_callable() {
+        let (problem, _soln) = exponential_decay_with_algebraic_problem::<Mcpu>();
+        let t = 0.0;
+        let id = problem.eqn.mass().unwrap().matrix(t).diagonal();
+        let rhs = problem.eqn.rhs().clone();
+        let x = Vcpu::from_vec(vec![1.0, 2.0, 3.0]);
+
+        // f(y, z) = |-0.1 * y|
+        //           |z - y[1]|
+        // algebraic states are z, with y = |1| frozen
+        //                                  |2|
+        // i.e. F_s(z) = z - 2
+        let alg = FilterCallable::new_algebraic(rhs.clone(), &x, &id);
+        assert_eq!(alg.nstates(), 1);
+        let z = alg.restrict(&x);
+        z.assert_eq_st(&Vcpu::from_vec(vec![3.0]), 1e-10);
+        alg.call(&z, t)
+            .assert_eq_st(&Vcpu::from_vec(vec![1.0]), 1e-10);
+        let jac = alg.jacobian(&z, t);
+        assert_eq!(jac[(0, 0)], 1.0);
+
+        // differential states are y, with z = 3 frozen
+        // i.e. F_s(y) = -0.1 * y
+        let diff = FilterCallable::new_differential(rhs, &x, &id);
+        assert_eq!(diff.nstates(), 2);
+        let y = Vcpu::from_vec(vec![4.0, 5.0]);
+        diff.call(&y, t)
+            .assert_eq_st(&Vcpu::from_vec(vec![-0.4, -0.5]), 1e-10);
+        let mut x_full = Vcpu::zeros(3);
+        diff.prolong(&y, &mut x_full);
+        x_full.assert_eq_st(&Vcpu::from_vec(vec![4.0, 5.0, 3.0]), 1e-10);
+        let jac = diff.jacobian(&y, t);
+        assert_eq!(jac[(0, 0)], -0.1);
+        assert_eq!(jac[(0, 1)], 0.0);
+        assert_eq!(jac[(1, 0)], 0.0);
+        assert_eq!(jac[(1, 1)], -0.1);
+
+        // freezing a different state changes the result
+        diff.set_frozen(&Vcpu::from_vec(vec![0.0, 0.0, 7.0]));
+        diff.prolong(&y, &mut x_full);
+        x_full.assert_eq_st(&Vcpu::from_vec(vec![4.0, 5.0, 7.0]), 1e-10);
+    }
 }
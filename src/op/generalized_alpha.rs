@@ -0,0 +1,144 @@
+use crate::{
+    matrix::sparsity::MatrixSparsityRef, ode_solver::second_order::SecondOrderOde, LinearOp,
+    Matrix, MatrixSparsity, Vector,
+};
+use num_traits::{One, Zero};
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use super::{NonLinearOp, Op};
+
+// callable for the (linear) effective system of the generalized-alpha method, F(a) = (M + c_c * C + c_k * K) a
+pub struct GeneralizedAlphaCallable<Eqn: SecondOrderOde> {
+    eqn: Rc<Eqn>,
+    c_damping: RefCell<Eqn::T>,
+    c_stiffness: RefCell<Eqn::T>,
+    tmp: RefCell<Eqn::V>,
+    mass_jac: Eqn::M,
+    damping_jac: Option<Eqn::M>,
+    stiffness_jac: Eqn::M,
+    mass_damping_jac: RefCell<Eqn::M>,
+    sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+    number_of_jac_evals: RefCell<usize>,
+}
+
+impl<Eqn: SecondOrderOde> GeneralizedAlphaCallable<Eqn> {
+    /// Create the callable, the matrices `M`, `C` and `K` are assumed to be constant and are evaluated once at time `t0`
+    pub fn new(eqn: &Rc<Eqn>, t0: Eqn::T) -> Self {
+        let eqn = eqn.clone();
+        let n = eqn.mass().nstates();
+        let mass_jac = Self::matrix(eqn.mass().as_ref(), t0.clone());
+        let stiffness_jac = Self::matrix(eqn.stiffness().as_ref(), t0.clone());
+        let damping_jac = eqn
+            .damping()
+            .map(|damping| Self::matrix(damping.as_ref(), t0));
+
+        // the sparsity of the effective matrix is the union of the sparsity of M, C and K
+        let (mass_damping_sparsity, sparsity) = match mass_jac.sparsity() {
+            Some(mass_sparsity) => {
+                let mass_damping_sparsity = || match damping_jac.as_ref() {
+                    Some(damping_jac) => mass_sparsity
+                        .to_owned()
+                        .union(damping_jac.sparsity().unwrap())
+                        .unwrap(),
+                    None => mass_sparsity.to_owned(),
+                };
+                let sparsity = mass_damping_sparsity()
+                    .union(stiffness_jac.sparsity().unwrap())
+                    .unwrap();
+                (Some(mass_damping_sparsity()), Some(sparsity))
+            }
+            None => (None, None),
+        };
+        let mass_damping_jac = RefCell::new(Eqn::M::new_from_sparsity(n, n, mass_damping_sparsity));
+        let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
+
+        Self {
+            eqn,
+            c_damping: RefCell::new(Eqn::T::zero()),
+            c_stiffness: RefCell::new(Eqn::T::zero()),
+            tmp,
+            mass_jac,
+            damping_jac,
+            stiffness_jac,
+            mass_damping_jac,
+            sparsity,
+            number_of_jac_evals: RefCell::new(0),
+        }
+    }
+
+    fn matrix<Op2: LinearOp<M = Eqn::M, V = Eqn::V, T = Eqn::T>>(op: &Op2, t: Eqn::T) -> Eqn::M {
+        let n = op.nstates();
+        let mut m = Eqn::M::new_from_sparsity(n, n, op.sparsity().map(|s| s.to_owned()));
+        op.matrix_inplace(t, &mut m);
+        m
+    }
+
+    /// Set the coefficients `c_c` and `c_k` of the damping and stiffness matrices in the effective system
+    pub fn set_coefficients(&self, c_damping: Eqn::T, c_stiffness: Eqn::T) {
+        self.c_damping.replace(c_damping);
+        self.c_stiffness.replace(c_stiffness);
+    }
+
+    pub fn number_of_jac_evals(&self) -> usize {
+        *self.number_of_jac_evals.borrow()
+    }
+
+    pub fn eqn(&self) -> &Rc<Eqn> {
+        &self.eqn
+    }
+}
+
+impl<Eqn: SecondOrderOde> Op for GeneralizedAlphaCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.eqn.mass().nstates()
+    }
+    fn nout(&self) -> usize {
+        self.eqn.mass().nstates()
+    }
+    fn nparams(&self) -> usize {
+        0
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.sparsity.as_ref().map(|s| s.as_ref())
+    }
+}
+
+impl<Eqn: SecondOrderOde> NonLinearOp for GeneralizedAlphaCallable<Eqn> {
+    // F(a) = M a + c_c * C a + c_k * K a
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
+        let mut tmp = self.tmp.borrow_mut();
+        self.eqn
+            .mass()
+            .gemv_inplace(x, t.clone(), Eqn::T::zero(), y);
+        self.eqn
+            .stiffness()
+            .gemv_inplace(x, t.clone(), Eqn::T::zero(), &mut tmp);
+        y.axpy(self.c_stiffness.borrow().clone(), &tmp, Eqn::T::one());
+        if let Some(damping) = self.eqn.damping() {
+            damping.gemv_inplace(x, t, Eqn::T::zero(), &mut tmp);
+            y.axpy(self.c_damping.borrow().clone(), &tmp, Eqn::T::one());
+        }
+    }
+    // F is linear, so the jacobian is (M + c_c * C + c_k * K)
+    fn jac_mul_inplace(&self, _x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        self.call_inplace(v, t, y);
+    }
+
+    // M + c_c * C + c_k * K
+    fn jacobian_inplace(&self, _x: &Self::V, _t: Self::T, y: &mut Self::M) {
+        let c_damping = self.c_damping.borrow().clone();
+        let c_stiffness = self.c_stiffness.borrow().clone();
+        if let Some(damping_jac) = self.damping_jac.as_ref() {
+            let mut mass_damping_jac = self.mass_damping_jac.borrow_mut();
+            mass_damping_jac.scale_add_and_assign(&self.mass_jac, c_damping, damping_jac);
+            y.scale_add_and_assign(mass_damping_jac.deref(), c_stiffness, &self.stiffness_jac);
+        } else {
+            y.scale_add_and_assign(&self.mass_jac, c_stiffness, &self.stiffness_jac);
+        }
+        let number_of_jac_evals = *self.number_of_jac_evals.borrow() + 1;
+        self.number_of_jac_evals.replace(number_of_jac_evals);
+    }
+}
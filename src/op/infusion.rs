@@ -0,0 +1,172 @@
+use std::{cell::RefCell, rc::Rc};
+
+use num_traits::Zero;
+
+use crate::{Matrix, Scalar, Vector};
+
+use super::{NonLinearOp, Op};
+
+/// A [NonLinearOp] `F(x, t) + r` that adds a piecewise-constant infusion rate `r` to the output of another op `F`.
+///
+/// The rate is not a function of time, it is held constant until changed using [Self::set_rate]. This is intended to be
+/// used by a driver (see [crate::DosingSchedule]) that stops the solver at each time that the rate changes, so that the
+/// solver never steps over a discontinuity in the right-hand side.
+pub struct InfusionRhs<C: NonLinearOp> {
+    callable: Rc<C>,
+    rate: RefCell<C::V>,
+}
+
+impl<C: NonLinearOp> InfusionRhs<C> {
+    pub fn new(callable: Rc<C>) -> Self {
+        let rate = RefCell::new(C::V::zeros(callable.nout()));
+        Self { callable, rate }
+    }
+
+    pub fn callable(&self) -> &Rc<C> {
+        &self.callable
+    }
+
+    /// Set the current infusion rate into each of the states.
+    pub fn set_rate(&self, rate: &C::V) {
+        self.rate.borrow_mut().copy_from(rate);
+    }
+
+    /// Set the current infusion rate to zero.
+    pub fn clear_rate(&self) {
+        self.rate.borrow_mut().fill(C::T::zero());
+    }
+
+    pub fn rate(&self) -> C::V {
+        self.rate.borrow().clone()
+    }
+}
+
+impl<C: NonLinearOp> Op for InfusionRhs<C> {
+    type V = C::V;
+    type T = C::T;
+    type M = C::M;
+    fn nstates(&self) -> usize {
+        self.callable.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.callable.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.callable.nparams()
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.callable.sparsity()
+    }
+    fn sparsity_sens(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.callable.sparsity_sens()
+    }
+}
+
+// the rate is constant wrt the state and parameters, so only the call is modified
+impl<C: NonLinearOp> NonLinearOp for InfusionRhs<C> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.callable.call_inplace(x, t, y);
+        *y += &*self.rate.borrow();
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.callable.jac_mul_inplace(x, t, v, y);
+    }
+    fn sens_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.callable.sens_mul_inplace(x, t, v, y);
+    }
+    fn has_sens(&self) -> bool {
+        self.callable.has_sens()
+    }
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.callable.jacobian_inplace(x, t, y);
+    }
+}
+
+/// A [NonLinearOp] for the root function of [crate::InfusionEquations]: the outputs of the root function `G` of the wrapped equations (if any),
+/// followed by a sampling clock `t - t_s` (if there is a [crate::Controller]), which crosses zero at the next sample time `t_s` of the controller.
+///
+/// The sample time is not a function of the state, it is advanced using [Self::set_next_sample] by the driver (see [crate::DosingSchedule::solve])
+/// each time the solver stops at a sample, so each sample is located by the event system of the solver.
+pub struct InfusionRoot<C: NonLinearOp> {
+    callable: Option<Rc<C>>,
+    nstates: usize,
+    has_clock: bool,
+    next_sample: RefCell<C::T>,
+    tmp: RefCell<C::V>,
+}
+
+impl<C: NonLinearOp> InfusionRoot<C> {
+    /// Create the root function from the root function `callable` of the wrapped equations with `nstates` states, with a sampling clock if
+    /// `has_clock` is true. The clock does not cross zero until the first sample time is set.
+    pub fn new(callable: Option<Rc<C>>, nstates: usize, has_clock: bool) -> Self {
+        let nroots = callable.as_ref().map_or(0, |c| c.nout());
+        Self {
+            callable,
+            nstates,
+            has_clock,
+            next_sample: RefCell::new(C::T::INFINITY),
+            tmp: RefCell::new(C::V::zeros(nroots)),
+        }
+    }
+
+    pub fn callable(&self) -> Option<&Rc<C>> {
+        self.callable.as_ref()
+    }
+
+    /// The index of the output of the sampling clock, if there is one
+    pub fn clock_index(&self) -> Option<usize> {
+        self.has_clock.then(|| self.tmp.borrow().len())
+    }
+
+    /// Set the next sample time, at which the output of the sampling clock crosses zero.
+    pub fn set_next_sample(&self, t: C::T) {
+        self.next_sample.replace(t);
+    }
+
+    pub fn next_sample(&self) -> C::T {
+        self.next_sample.borrow().clone()
+    }
+}
+
+impl<C: NonLinearOp> Op for InfusionRoot<C> {
+    type V = C::V;
+    type T = C::T;
+    type M = C::M;
+    fn nstates(&self) -> usize {
+        self.nstates
+    }
+    fn nout(&self) -> usize {
+        self.tmp.borrow().len() + usize::from(self.has_clock)
+    }
+    fn nparams(&self) -> usize {
+        self.callable.as_ref().map_or(0, |c| c.nparams())
+    }
+}
+
+// the sampling clock is constant wrt the state and parameters, so its rows of the jacobian and sensitivities are zero
+impl<C: NonLinearOp> NonLinearOp for InfusionRoot<C> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        let tmp = &mut *self.tmp.borrow_mut();
+        if let Some(callable) = self.callable.as_ref() {
+            callable.call_inplace(x, t.clone(), tmp);
+        }
+        for i in 0..tmp.len() {
+            y[i] = tmp[i].clone();
+        }
+        if self.has_clock {
+            y[tmp.len()] = t.clone() - self.next_sample.borrow().clone();
+        }
+    }
+    fn jac_mul_inplace(&self, x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        let tmp = &mut *self.tmp.borrow_mut();
+        if let Some(callable) = self.callable.as_ref() {
+            callable.jac_mul_inplace(x, t, v, tmp);
+        }
+        for i in 0..tmp.len() {
+            y[i] = tmp[i].clone();
+        }
+        if self.has_clock {
+            y[tmp.len()] = C::T::zero();
+        }
+    }
+}
@@ -12,16 +12,19 @@ pub struct InitOp<Eqn: OdeEquations> {
     pub y0: RefCell<Eqn::V>,
     pub algebraic_indices: <Eqn::V as Vector>::Index,
     neg_mass: Eqn::M,
+    neg_mass_u: Eqn::M,
+    // the initial guess, at which `jac` is used
+    x0: Eqn::V,
 }
 
 impl<Eqn: OdeEquations> InitOp<Eqn> {
     pub fn new(eqn: &Rc<Eqn>, t0: Eqn::T, y0: &Eqn::V, dy0: &Eqn::V) -> Self {
         let eqn = eqn.clone();
         let n = eqn.rhs().nstates();
-        let mass_diagonal = eqn.mass().unwrap().matrix(t0).diagonal();
+        let mass_diagonal = eqn.mass().unwrap().matrix(t0.clone()).diagonal();
         let algebraic_indices = mass_diagonal.filter_indices(|x| x == Eqn::T::zero());
 
-        let rhs_jac = eqn.rhs().jacobian(y0, t0);
+        let rhs_jac = eqn.rhs().jacobian(y0, t0.clone());
         let mass = eqn.mass().unwrap().matrix(t0);
 
         // equations are:
@@ -46,16 +49,20 @@ impl<Eqn: OdeEquations> InitOp<Eqn> {
         let jac = Eqn::M::combine_at_indices(&m_u, &dfdv, &zero_ll, &dgdv, &algebraic_indices);
         let neg_mass =
             Eqn::M::combine_at_indices(&m_u, &zero_ur, &zero_ll, &zero_lr, &algebraic_indices);
+        let neg_mass_u = m_u;
 
         let mut y0 = y0.clone();
         y0.copy_from_indices(dy0, &algebraic_indices);
+        let x0 = y0.clone();
         let y0 = RefCell::new(y0);
         Self {
             eqn,
             jac,
             y0,
             neg_mass,
+            neg_mass_u,
             algebraic_indices,
+            x0,
         }
     }
 
@@ -101,14 +108,96 @@ impl<Eqn: OdeEquations> NonLinearOp for InitOp<Eqn> {
         self.neg_mass.gemv(Eqn::T::one(), x, Eqn::T::one(), y);
     }
 
-    // J v
-    fn jac_mul_inplace(&self, _x: &Eqn::V, _t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
-        self.jac.gemv(Eqn::T::one(), v, Eqn::T::one(), y);
+    // J v = (f; g)' (0, v_v) - M_u v_du
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        let mut y0 = self.y0.borrow_mut();
+        y0.copy_from_indices(x, &self.algebraic_indices);
+        let mut v_v = Eqn::V::zeros(v.len());
+        v_v.copy_from_indices(v, &self.algebraic_indices);
+        self.eqn.rhs().jac_mul_inplace(&y0, t, &v_v, y);
+        self.neg_mass.gemv(Eqn::T::one(), v, Eqn::T::one(), y);
+    }
+
+    // J = (-M_u, df/dv)
+    //     (0,    dg/dv)
+    // the jacobian calculated in new is used at the initial guess, otherwise it is evaluated at the current algebraic states
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        let alg = &self.algebraic_indices;
+        if (0..alg.len()).all(|i| x[alg[i]] == self.x0[alg[i]]) {
+            y.copy_from(&self.jac);
+            return;
+        }
+        let mut y0 = self.y0.borrow_mut();
+        y0.copy_from_indices(x, &self.algebraic_indices);
+        let rhs_jac = self.eqn.rhs().jacobian(&y0, t);
+        let (_, dfdv, _, dgdv) = rhs_jac.split_at_indices(&self.algebraic_indices);
+        let n = self.nstates();
+        let nalg = self.algebraic_indices.len();
+        let zero_ll = <Eqn::M as Matrix>::zeros(nalg, n - nalg);
+        y.copy_from(&Eqn::M::combine_at_indices(
+            &self.neg_mass_u,
+            &dfdv,
+            &zero_ll,
+            &dgdv,
+            &self.algebraic_indices,
+        ));
+    }
+}
+
+/// The nonlinear function used to calculate the full state vector `y` for a given time derivative `dy` (as for `IDA_Y_INIT` in Sundials IDA),
+/// i.e. `F(y) = f(t, y) - M dy`, so that the solution satisfies the equations `M dy = f(t, y)` with `dy` fixed.
+pub struct StateInitOp<Eqn: OdeEquations> {
+    eqn: Rc<Eqn>,
+    mass_dy: Eqn::V,
+}
+
+impl<Eqn: OdeEquations> StateInitOp<Eqn> {
+    pub fn new(eqn: &Rc<Eqn>, t0: Eqn::T, dy0: &Eqn::V) -> Self {
+        let eqn = eqn.clone();
+        let mass_dy = match eqn.mass() {
+            Some(mass) => {
+                let mut mass_dy = Eqn::V::zeros(dy0.len());
+                LinearOp::call_inplace(mass.as_ref(), dy0, t0, &mut mass_dy);
+                mass_dy
+            }
+            None => dy0.clone(),
+        };
+        Self { eqn, mass_dy }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for StateInitOp<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        self.eqn.rhs().nstates()
+    }
+    fn nout(&self) -> usize {
+        self.eqn.rhs().nstates()
+    }
+    fn nparams(&self) -> usize {
+        self.eqn.rhs().nparams()
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.eqn.rhs().sparsity()
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for StateInitOp<Eqn> {
+    // f(t, y) - M dy
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
+        self.eqn.rhs().call_inplace(x, t, y);
+        y.axpy(-Eqn::T::one(), &self.mass_dy, Eqn::T::one());
+    }
+
+    // f'(y) v
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        self.eqn.rhs().jac_mul_inplace(x, t, v, y);
     }
 
-    // M - c * f'(y)
-    fn jacobian_inplace(&self, _x: &Self::V, _t: Self::T, y: &mut Self::M) {
-        y.copy_from(&self.jac);
+    fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.eqn.rhs().jacobian_inplace(x, t, y);
     }
 }
 
@@ -125,7 +214,7 @@ mod tests {
 
     #[test]
     fn test_initop() {
-        let (problem, _soln) = exponential_decay_with_algebraic_problem::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_with_algebraic_problem::<Mcpu>();
         let y0 = Vcpu::from_vec(vec![1.0, 2.0, 3.0]);
         let dy0 = Vcpu::from_vec(vec![4.0, 5.0, 6.0]);
         let t = 0.0;
@@ -0,0 +1,320 @@
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+
+use nalgebra::{DMatrix, DVector};
+use num_traits::Zero;
+
+use crate::{
+    errors::PSError,
+    matrix::sparsity::{MatrixSparsity, MatrixSparsityRef},
+    op::{LinearOp, Op, OpStatistics},
+    scalar::{scale, IndexType},
+    Matrix, Scalar,
+};
+
+// eigendecompositions `A = Q_A diag(λ_A) Q_A^T` and `B = Q_B diag(λ_B) Q_B^T` of symmetric factors
+struct KroneckerEigen<T: Scalar> {
+    qa: DMatrix<T>,
+    la: DVector<T>,
+    qb: DMatrix<T>,
+    lb: DVector<T>,
+}
+
+/// The Kronecker sum `K = A ⊗ I + I ⊗ B` of a square `na x na` matrix `A` and a square `nb x nb` matrix `B`, which is the
+/// form of the jacobian of a linear operator (e.g. diffusion) discretised on a 2D tensor-product grid by the method of lines.
+/// The state is ordered with the index of `B` varying fastest, i.e. `x[i * nb + k]` is the value at grid point `(i, k)`.
+///
+/// As a [LinearOp] the product with a vector is calculated from the factors without assembling `K`, i.e. `y = A X + X B^T`
+/// where `X` is the `na x nb` matrix of the state, so only the (small) factors are stored. The matrix representation of `K`
+/// is only assembled when a solver asks for it, and if `M` is sparse it has the sparsity pattern of the Kronecker sum.
+/// Wrap the op in a [crate::LinearRhs] to use it as the right-hand side of an ODE.
+///
+/// If both factors are symmetric, the linear systems `(σ I + α K) x = b` that arise in the implicit solvers can be solved
+/// directly in `O(na nb (na + nb))` operations using the eigendecompositions of the factors (see [Self::solve_shifted_in_place]),
+/// rather than factorising the `na nb x na nb` matrix.
+pub struct KroneckerOp<M: Matrix> {
+    na: IndexType,
+    nb: IndexType,
+    a: Vec<(IndexType, IndexType, M::T)>,
+    b: Vec<(IndexType, IndexType, M::T)>,
+    eigen: Option<KroneckerEigen<M::T>>,
+    sparsity: Option<M::Sparsity>,
+    statistics: RefCell<OpStatistics>,
+}
+
+impl<M: Matrix> KroneckerOp<M> {
+    /// Create the Kronecker sum `A ⊗ I + I ⊗ B`, returning an error if either factor is not square.
+    pub fn new(a: &M, b: &M) -> Result<Self, PSError> {
+        for (name, m) in [("first factor", a), ("second factor", b)] {
+            if m.nrows() != m.ncols() {
+                return Err(PSError::DimensionMismatch {
+                    name: format!("number of columns of the {}", name),
+                    expected: m.nrows(),
+                    found: m.ncols(),
+                });
+            }
+        }
+        let triplets = |m: &M| {
+            m.triplet_iter()
+                .map(|(i, j, v)| (i, j, v.clone()))
+                .collect::<Vec<_>>()
+        };
+        let mut ret = Self {
+            na: a.nrows(),
+            nb: b.nrows(),
+            a: triplets(a),
+            b: triplets(b),
+            eigen: None,
+            sparsity: None,
+            statistics: RefCell::new(OpStatistics::default()),
+        };
+        let (a_dense, b_dense) = (
+            Self::dense_factor(&ret.a, ret.na),
+            Self::dense_factor(&ret.b, ret.nb),
+        );
+        if a_dense == a_dense.transpose() && b_dense == b_dense.transpose() {
+            let ea = a_dense.symmetric_eigen();
+            let eb = b_dense.symmetric_eigen();
+            ret.eigen = Some(KroneckerEigen {
+                qa: ea.eigenvectors,
+                la: ea.eigenvalues,
+                qb: eb.eigenvectors,
+                lb: eb.eigenvalues,
+            });
+        }
+        if M::is_sparse() {
+            let n = ret.nstates();
+            let k = M::try_from_triplets(n, n, ret.kronecker_triplets())?;
+            ret.sparsity = k.sparsity().map(|s| s.to_owned());
+        }
+        Ok(ret)
+    }
+
+    /// The sizes `(na, nb)` of the factors
+    pub fn factor_sizes(&self) -> (IndexType, IndexType) {
+        (self.na, self.nb)
+    }
+
+    /// Returns true if both factors are symmetric, so that [Self::solve_shifted_in_place] can be used
+    pub fn has_eigendecomposition(&self) -> bool {
+        self.eigen.is_some()
+    }
+
+    /// Solve `(σ I + α K) x = b` in place (i.e. `b` is overwritten with `x`) using the eigendecompositions of the factors,
+    /// `σ I + α K = (Q_A ⊗ Q_B) diag(σ + α (λ_A[i] + λ_B[k])) (Q_A ⊗ Q_B)^T`. For example, the BDF iteration matrix of the
+    /// ODE `dx/dt = K x` is `I - c K`, i.e. `σ = 1` and `α = -c`.
+    ///
+    /// Returns an error if either factor is not symmetric, or if the shifted matrix is singular.
+    pub fn solve_shifted_in_place(
+        &self,
+        sigma: M::T,
+        alpha: M::T,
+        b: &mut M::V,
+    ) -> Result<(), PSError> {
+        let eigen = self.eigen.as_ref().ok_or_else(|| PSError::UnsupportedProblem {
+            e: "the factors of the Kronecker operator must be symmetric to use the eigendecomposition".to_string(),
+        })?;
+        let (na, nb) = (self.na, self.nb);
+
+        // with the state stored as the nb x na matrix Z (column-major), K x = vec(B Z + Z A^T)
+        let z = DMatrix::from_fn(nb, na, |k, i| b[i * nb + k].clone());
+        let mut w = eigen.qb.tr_mul(&z) * &eigen.qa;
+        for i in 0..na {
+            for k in 0..nb {
+                let d = sigma.clone() + alpha.clone() * (eigen.la[i].clone() + eigen.lb[k].clone());
+                if d.is_zero() {
+                    return Err(PSError::LuFailed);
+                }
+                w[(k, i)] /= d;
+            }
+        }
+        let z = &eigen.qb * w * eigen.qa.transpose();
+        for i in 0..na {
+            for k in 0..nb {
+                b[i * nb + k] = z[(k, i)].clone();
+            }
+        }
+        Ok(())
+    }
+
+    fn dense_factor(triplets: &[(IndexType, IndexType, M::T)], n: IndexType) -> DMatrix<M::T> {
+        let mut m = DMatrix::zeros(n, n);
+        for &(i, j, ref v) in triplets {
+            m[(i, j)] += v.clone();
+        }
+        m
+    }
+
+    // the entries of the assembled Kronecker sum, with the contributions to the diagonal summed
+    fn kronecker_triplets(&self) -> Vec<(IndexType, IndexType, M::T)> {
+        let (na, nb) = (self.na, self.nb);
+        let mut entries = BTreeMap::new();
+        for &(i, j, ref v) in &self.a {
+            for k in 0..nb {
+                *entries
+                    .entry((j * nb + k, i * nb + k))
+                    .or_insert(M::T::zero()) += v.clone();
+            }
+        }
+        for &(k, l, ref v) in &self.b {
+            for i in 0..na {
+                *entries
+                    .entry((i * nb + l, i * nb + k))
+                    .or_insert(M::T::zero()) += v.clone();
+            }
+        }
+        // the map is sorted by column, then row
+        entries.into_iter().map(|((j, i), v)| (i, j, v)).collect()
+    }
+}
+
+impl<M: Matrix> Op for KroneckerOp<M> {
+    type V = M::V;
+    type T = M::T;
+    type M = M;
+    fn nstates(&self) -> usize {
+        self.na * self.nb
+    }
+    fn nout(&self) -> usize {
+        self.na * self.nb
+    }
+    // the factors are constant, so the parameters of the equations are ignored
+    fn set_params(&mut self, _p: Rc<M::V>) {}
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.sparsity.as_ref().map(|s| s.as_ref())
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.statistics.borrow().clone()
+    }
+}
+
+impl<M: Matrix> LinearOp for KroneckerOp<M> {
+    fn gemv_inplace(&self, x: &M::V, _t: M::T, beta: M::T, y: &mut M::V) {
+        self.statistics.borrow_mut().increment_call();
+        let (na, nb) = (self.na, self.nb);
+        *y *= scale(beta);
+        for &(i, j, ref v) in &self.a {
+            for k in 0..nb {
+                y[i * nb + k] += v.clone() * x[j * nb + k].clone();
+            }
+        }
+        for &(k, l, ref v) in &self.b {
+            for i in 0..na {
+                y[i * nb + k] += v.clone() * x[i * nb + l].clone();
+            }
+        }
+    }
+    fn matrix_inplace(&self, _t: M::T, y: &mut M) {
+        self.statistics.borrow_mut().increment_matrix();
+        let n = self.nstates();
+        let k = M::try_from_triplets(n, n, self.kronecker_triplets())
+            .expect("Kronecker sum entries are within the matrix");
+        y.copy_from(&k);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use nalgebra::{DMatrix, DVector};
+
+    use super::KroneckerOp;
+    use crate::{Bdf, LinearOp, OdeBuilder, OdeSolverMethod, Op, Vector};
+
+    // the second-difference matrix with zero boundary conditions, scaled by `d`
+    fn laplacian(n: usize, d: f64) -> DMatrix<f64> {
+        DMatrix::from_fn(n, n, |i, j| match i.abs_diff(j) {
+            0 => -2.0 * d,
+            1 => d,
+            _ => 0.0,
+        })
+    }
+
+    #[test]
+    fn test_kronecker_op() {
+        let (a, b) = (laplacian(3, 1.0), laplacian(4, 2.0));
+        let k = a.kronecker(&DMatrix::identity(4, 4)) + DMatrix::identity(3, 3).kronecker(&b);
+        let op = KroneckerOp::new(&a, &b).unwrap();
+        assert_eq!(op.nstates(), 12);
+        assert!(op.has_eigendecomposition());
+        assert_eq!(op.matrix(0.0), k);
+
+        // y = K x + 2 y
+        let x = DVector::from_fn(12, |i, _| (i as f64).sin());
+        let mut y = DVector::from_element(12, 1.0);
+        let expect = &k * &x + &y * 2.0;
+        op.gemv_inplace(&x, 0.0, 2.0, &mut y);
+        y.assert_eq_st(&expect, 1e-12);
+
+        // (I - 0.1 K) z = x
+        let mut z = x.clone();
+        op.solve_shifted_in_place(1.0, -0.1, &mut z).unwrap();
+        let lhs = (DMatrix::identity(12, 12) - &k * 0.1) * &z;
+        lhs.assert_eq_st(&x, 1e-12);
+
+        // the eigendecomposition is only used for symmetric factors
+        let mut c = a.clone();
+        c[(0, 1)] = 3.0;
+        let op = KroneckerOp::new(&c, &b).unwrap();
+        assert!(!op.has_eigendecomposition());
+        assert!(op.solve_shifted_in_place(1.0, -0.1, &mut z).is_err());
+        assert!(KroneckerOp::new(&DMatrix::zeros(2, 3), &b).is_err());
+    }
+
+    #[cfg(feature = "faer")]
+    #[test]
+    fn test_kronecker_op_sparse() {
+        use crate::Matrix;
+
+        let (a, b) = (laplacian(3, 1.0), laplacian(4, 2.0));
+
+        let sparse = |m: &DMatrix<f64>| {
+            let triplets = (0..m.ncols())
+                .flat_map(|j| (0..m.nrows()).map(move |i| (i, j)))
+                .filter(|&(i, j)| m[(i, j)] != 0.0)
+                .map(|(i, j)| (i, j, m[(i, j)]))
+                .collect::<Vec<_>>();
+            crate::SparseColMat::<f64>::try_from_triplets(m.nrows(), m.ncols(), triplets).unwrap()
+        };
+        let k = a.kronecker(&DMatrix::identity(4, 4)) + DMatrix::identity(3, 3).kronecker(&b);
+        let op = KroneckerOp::new(&sparse(&a), &sparse(&b)).unwrap();
+        assert!(op.sparsity().is_some());
+        let mat = op.matrix(0.0);
+        // each row couples a grid point with its neighbours in both directions
+        assert_eq!(
+            mat.triplet_iter().count(),
+            k.iter().filter(|&&v| v != 0.0).count()
+        );
+        for (i, j, &v) in mat.triplet_iter() {
+            assert_eq!(v, k[(i, j)]);
+        }
+    }
+
+    #[test]
+    fn test_kronecker_op_heat_equation() {
+        // the 2D heat equation on a 5 x 6 grid, starting from the slowest decaying eigenmode
+        let (na, nb) = (5, 6);
+        let op = Rc::new(KroneckerOp::new(&laplacian(na, 1.0), &laplacian(nb, 0.5)).unwrap());
+        let mode =
+            |n: usize, i: usize| (std::f64::consts::PI * (i + 1) as f64 / (n + 1) as f64).sin();
+        let lambda = |n: usize, d: f64| {
+            -4.0 * d * (std::f64::consts::PI / (2 * (n + 1)) as f64).sin().powi(2)
+        };
+        let x0 = DVector::from_fn(na * nb, |r, _| mode(na, r / nb) * mode(nb, r % nb));
+        let (op1, op2) = (op.clone(), op.clone());
+        let problem = OdeBuilder::new()
+            .rtol(1e-8)
+            .atol([1e-10])
+            .build_ode::<DMatrix<f64>, _, _, _>(
+                move |x, _p, t, y| op1.call_inplace(x, t, y),
+                move |_x, _p, t, v, y| op2.call_inplace(v, t, y),
+                move |_p, _t| x0.clone(),
+            )
+            .unwrap();
+        let t = 1.0;
+        let y = Bdf::default().solve(&problem, t).unwrap().y.pop().unwrap();
+        let decay = ((lambda(na, 1.0) + lambda(nb, 0.5)) * t).exp();
+        let expect = DVector::from_fn(na * nb, |r, _| decay * mode(na, r / nb) * mode(nb, r % nb));
+        y.assert_eq_st(&expect, 1e-6);
+    }
+}
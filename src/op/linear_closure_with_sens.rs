@@ -1,8 +1,10 @@
 use std::{cell::RefCell, rc::Rc};
 
 use crate::{
-    jacobian::{find_non_zeros_linear, JacobianColoring},
+    errors::PSError,
+    jacobian::{find_non_zeros_linear, sparsity_and_coloring, JacobianColoring},
     matrix::sparsity::MatrixSparsity,
+    scalar::IndexType,
     Matrix, Vector,
 };
 
@@ -46,13 +48,20 @@ where
         }
     }
 
+    /// Calculate the sparsity pattern of the matrix by evaluating the operator at `t0`, see [Self::set_sparsity].
     pub fn calculate_sparsity(&mut self, t0: M::T) {
         let non_zeros = find_non_zeros_linear(self, t0);
-        self.sparsity = Some(
-            MatrixSparsity::try_from_indices(self.nout(), self.nstates(), non_zeros.clone())
-                .expect("invalid sparsity pattern"),
-        );
-        self.coloring = Some(JacobianColoring::new_from_non_zeros(self, non_zeros));
+        self.set_sparsity(non_zeros)
+            .expect("invalid sparsity pattern");
+    }
+
+    /// Set the sparsity pattern of the matrix from a list of (row, column) indices of the non-zero entries.
+    /// The matrix is then assembled using graph coloring of this pattern.
+    pub fn set_sparsity(&mut self, non_zeros: Vec<(IndexType, IndexType)>) -> Result<(), PSError> {
+        let (sparsity, coloring) = sparsity_and_coloring(self.nout(), self.nstates(), non_zeros)?;
+        self.sparsity = Some(sparsity);
+        self.coloring = Some(coloring);
+        Ok(())
     }
 }
 
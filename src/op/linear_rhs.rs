@@ -0,0 +1,104 @@
+use std::rc::Rc;
+
+use crate::Matrix;
+
+use super::{LinearOp, NonLinearOp, Op, OpStatistics};
+
+/// A [NonLinearOp] `F(x, t) = A x` defined by a [LinearOp] `A` with a constant matrix (i.e. `A` must not depend on `t`).
+///
+/// The jacobian of the op is `A` itself, so it is assembled using [LinearOp::matrix_inplace] and is the same at every state and time.
+/// The op reports [NonLinearOp::is_linear], so that solvers specialised for linear problems (see [crate::LinearOdeSolver]) can
+/// be used with it.
+pub struct LinearRhs<L: LinearOp> {
+    op: L,
+}
+
+impl<L: LinearOp> LinearRhs<L> {
+    pub fn new(op: L) -> Self {
+        Self { op }
+    }
+
+    pub fn op(&self) -> &L {
+        &self.op
+    }
+}
+
+impl<L: LinearOp> Op for LinearRhs<L> {
+    type V = L::V;
+    type T = L::T;
+    type M = L::M;
+    fn nstates(&self) -> usize {
+        self.op.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.op.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.op.nparams()
+    }
+    fn set_params(&mut self, p: Rc<Self::V>) {
+        self.op.set_params(p);
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.op.sparsity()
+    }
+    fn statistics(&self) -> OpStatistics {
+        self.op.statistics()
+    }
+}
+
+impl<L: LinearOp> NonLinearOp for LinearRhs<L> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        self.op.call_inplace(x, t, y);
+    }
+    fn jac_mul_inplace(&self, _x: &Self::V, t: Self::T, v: &Self::V, y: &mut Self::V) {
+        self.op.call_inplace(v, t, y);
+    }
+    fn jacobian_inplace(&self, _x: &Self::V, t: Self::T, y: &mut Self::M) {
+        self.op.matrix_inplace(t, y);
+    }
+    fn is_linear(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        op::{linear_rhs::LinearRhs, NonLinearOp},
+        LinearClosure, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_linear_rhs() {
+        // A = [[-1, 2], [0, -3]]
+        let p = Rc::new(V::zeros(0));
+        let op = LinearClosure::<M, _>::new(
+            |x: &V, _p: &V, _t, beta, y: &mut V| {
+                y[0] = -x[0] + 2.0 * x[1] + beta * y[0];
+                y[1] = -3.0 * x[1] + beta * y[1];
+            },
+            2,
+            2,
+            p,
+        );
+        let rhs = LinearRhs::new(op);
+        assert!(rhs.is_linear());
+
+        let x = V::from_vec(vec![1.0, 2.0]);
+        let v = V::from_vec(vec![3.0, -1.0]);
+        rhs.call(&x, 0.0)
+            .assert_eq_st(&V::from_vec(vec![3.0, -6.0]), 1e-12);
+
+        // the jacobian does not depend on x
+        rhs.jac_mul(&x, 0.0, &v)
+            .assert_eq_st(&V::from_vec(vec![-5.0, 3.0]), 1e-12);
+        let jac = rhs.jacobian(&v, 1.0);
+        assert_eq!(jac, M::from_row_slice(2, 2, &[-1.0, 2.0, 0.0, -3.0]));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::matrix::Matrix;
+use num_traits::One;
 
 use super::{LinearOp, Op};
 
@@ -31,7 +32,10 @@ impl<M: Matrix> Op for MatrixOp<M> {
 }
 
 impl<M: Matrix> LinearOp for MatrixOp<M> {
-    fn gemv_inplace(&self, x: &Self::V, t: Self::T, beta: Self::T, y: &mut Self::V) {
-        self.m.gemv(t, x, beta, y);
+    fn gemv_inplace(&self, x: &Self::V, _t: Self::T, beta: Self::T, y: &mut Self::V) {
+        self.m.gemv(Self::T::one(), x, beta, y);
+    }
+    fn matrix_inplace(&self, _t: Self::T, y: &mut Self::M) {
+        y.copy_from(&self.m);
     }
 }
@@ -0,0 +1,161 @@
+use nalgebra::ComplexField;
+use num_traits::{One, Zero};
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{scalar::Scalar, Matrix, Vector};
+
+use super::{LinearOp, NonLinearOp, Op};
+
+/// The linearisation `J(x) v` of a nonlinear operator `F` at a point `x`, applied without ever assembling the jacobian `J(x)`.
+/// The product is computed using [NonLinearOp::jac_mul_inplace], or, if `finite_difference` is true, using the
+/// finite difference directional derivative
+///
+/// `J(x) v ≈ (F(x + σ v) - F(x)) / σ`, with `σ = sqrt(ε) (1 + |x|) / |v|`,
+///
+/// which only requires calls to `F` (the value of `F(x)` is cached between products at the same `x` and `t`).
+/// This is used by the iterative linear solvers (e.g. [crate::Gmres]) so that a Newton-Krylov iteration never calls
+/// [NonLinearOp::jacobian_inplace]. [LinearOp::matrix_inplace] still assembles the matrix from products with basis
+/// vectors, but the iterative solvers only call it if their preconditioner requires the matrix.
+pub struct MatrixFreeLinearisedOp<C: NonLinearOp> {
+    callable: Rc<C>,
+    x: C::V,
+    finite_difference: bool,
+    // F(x, t) for the finite difference approximation, and the time it was evaluated at
+    fx: RefCell<Option<(C::T, C::V)>>,
+    tmp: RefCell<C::V>,
+    gemv_tmp: RefCell<C::V>,
+    x_is_set: bool,
+}
+
+impl<C: NonLinearOp> MatrixFreeLinearisedOp<C> {
+    pub fn new(callable: Rc<C>, finite_difference: bool) -> Self {
+        let x = C::V::zeros(callable.nstates());
+        let tmp = RefCell::new(C::V::zeros(callable.nstates()));
+        let gemv_tmp = RefCell::new(C::V::zeros(callable.nout()));
+        Self {
+            callable,
+            x,
+            finite_difference,
+            fx: RefCell::new(None),
+            tmp,
+            gemv_tmp,
+            x_is_set: false,
+        }
+    }
+
+    pub fn set_x(&mut self, x: &C::V) {
+        self.x.copy_from(x);
+        self.fx.replace(None);
+        self.x_is_set = true;
+    }
+
+    pub fn x_is_set(&self) -> bool {
+        self.x_is_set
+    }
+
+    pub fn finite_difference(&self) -> bool {
+        self.finite_difference
+    }
+
+    fn finite_difference_jac_mul(&self, v: &C::V, t: C::T, y: &mut C::V) {
+        let vnorm = v.norm();
+        if vnorm == C::T::zero() {
+            y.fill(C::T::zero());
+            return;
+        }
+        let mut fx = self.fx.borrow_mut();
+        if !matches!(fx.as_ref(), Some((fx_t, _)) if *fx_t == t) {
+            let mut f = C::V::zeros(self.callable.nout());
+            self.callable.call_inplace(&self.x, t.clone(), &mut f);
+            *fx = Some((t.clone(), f));
+        }
+        let sigma = C::T::EPSILON.sqrt() * (C::T::one() + self.x.norm()) / vnorm;
+        let mut tmp = self.tmp.borrow_mut();
+        tmp.copy_from(&self.x);
+        tmp.axpy(sigma.clone(), v, C::T::one());
+        self.callable.call_inplace(&tmp, t, y);
+        y.axpy(
+            -C::T::one() / sigma.clone(),
+            &fx.as_ref().unwrap().1,
+            C::T::one() / sigma,
+        );
+    }
+}
+
+impl<C: NonLinearOp> Op for MatrixFreeLinearisedOp<C> {
+    type V = C::V;
+    type T = C::T;
+    type M = C::M;
+    fn nstates(&self) -> usize {
+        self.callable.nstates()
+    }
+    fn nout(&self) -> usize {
+        self.callable.nout()
+    }
+    fn nparams(&self) -> usize {
+        self.callable.nparams()
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.callable.sparsity()
+    }
+}
+
+impl<C: NonLinearOp> LinearOp for MatrixFreeLinearisedOp<C> {
+    fn call_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::V) {
+        if self.finite_difference {
+            self.finite_difference_jac_mul(x, t, y);
+        } else {
+            self.callable.jac_mul_inplace(&self.x, t, x, y);
+        }
+    }
+    fn gemv_inplace(&self, x: &Self::V, t: Self::T, beta: Self::T, y: &mut Self::V) {
+        let mut tmp = self.gemv_tmp.borrow_mut();
+        tmp.copy_from(y);
+        self.call_inplace(x, t, y);
+        y.axpy(beta, &tmp, Self::T::one());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use crate::{
+        op::{closure::Closure, matrix_free::MatrixFreeLinearisedOp},
+        LinearOp, NonLinearOp, Op, Vector,
+    };
+
+    type M = nalgebra::DMatrix<f64>;
+    type V = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_matrix_free_linearised_op() {
+        // F(x) = [x0^2 x1, sin(x1)]
+        let op = Rc::new(Closure::<M, _, _>::new(
+            |x: &V, _p: &V, _t, y: &mut V| {
+                y[0] = x[0] * x[0] * x[1];
+                y[1] = x[1].sin();
+            },
+            |x: &V, _p: &V, _t, v: &V, y: &mut V| {
+                y[0] = 2.0 * x[0] * x[1] * v[0] + x[0] * x[0] * v[1];
+                y[1] = x[1].cos() * v[1];
+            },
+            2,
+            2,
+            Rc::new(V::zeros(0)),
+        ));
+        let x = V::from_vec(vec![1.0, 2.0]);
+        let v = V::from_vec(vec![0.5, -1.0]);
+        let expect = op.jac_mul(&x, 0.0, &v);
+        for finite_difference in [false, true] {
+            let mut linearised = MatrixFreeLinearisedOp::new(op.clone(), finite_difference);
+            linearised.set_x(&x);
+            let mut y = V::zeros(2);
+            LinearOp::call_inplace(&linearised, &v, 0.0, &mut y);
+            y.assert_eq_st(&expect, 1e-6);
+        }
+        // the jacobian is never assembled, and finite differences only call F
+        assert_eq!(op.statistics().number_of_matrix_evals, 0);
+        assert_eq!(op.statistics().number_of_jac_muls, 2);
+    }
+}
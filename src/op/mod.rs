@@ -1,6 +1,9 @@
 use std::rc::Rc;
 
-use crate::{Matrix, MatrixSparsityRef, Scalar, Vector};
+use crate::{
+    jacobian::JacobianColoring, ode_solver::equations::OdeEquations, Matrix, MatrixSparsity,
+    MatrixSparsityRef, Scalar, Vector,
+};
 
 use num_traits::{One, Zero};
 use serde::Serialize;
@@ -12,11 +15,17 @@ pub mod closure_with_sens;
 pub mod constant_closure;
 pub mod constant_closure_with_sens;
 pub mod filter;
+pub mod generalized_alpha;
+pub mod infusion;
 pub mod init;
+pub mod kronecker;
 pub mod linear_closure;
 pub mod linear_closure_with_sens;
+pub mod linear_rhs;
 pub mod linearise;
 pub mod matrix;
+pub mod matrix_free;
+pub mod radau;
 pub mod sdirk;
 pub mod unit;
 
@@ -60,6 +69,45 @@ pub trait Op {
     }
 }
 
+/// Allocate the storage used by the implicit solver ops ([bdf::BdfCallable], [sdirk::SdirkCallable]) to assemble a jacobian of the form `M - c * df/dy`.
+/// Returns the rhs jacobian and mass matrix, both allocated with the correct sparsity pattern, along with the sparsity pattern of the combined jacobian (if the rhs is sparse).
+/// If the equations have no mass matrix then the identity is returned in its place.
+pub(crate) fn implicit_jacobian_storage<Eqn: OdeEquations>(
+    eqn: &Eqn,
+) -> (Eqn::M, Eqn::M, Option<<Eqn::M as Matrix>::Sparsity>) {
+    let n = eqn.rhs().nstates();
+
+    // create the mass and rhs jacobians according to the sparsity pattern
+    let rhs_jac = Eqn::M::new_from_sparsity(n, n, eqn.rhs().sparsity().map(|s| s.to_owned()));
+    let sparsity = if let Some(rhs_jac_sparsity) = eqn.rhs().sparsity() {
+        if let Some(mass) = eqn.mass() {
+            // have mass, use the union of the mass and rhs jacobians sparse patterns
+            Some(
+                mass.sparsity()
+                    .unwrap()
+                    .to_owned()
+                    .union(rhs_jac_sparsity)
+                    .unwrap(),
+            )
+        } else {
+            // no mass, use the identity
+            let mass_sparsity = <Eqn::M as Matrix>::Sparsity::new_diagonal(n);
+            Some(mass_sparsity.union(rhs_jac_sparsity).unwrap())
+        }
+    } else {
+        None
+    };
+
+    let mass_jac = if let Some(mass) = eqn.mass() {
+        // mass is not constant, so just create a matrix with the correct sparsity
+        Eqn::M::new_from_sparsity(n, n, mass.sparsity().map(|s| s.to_owned()))
+    } else {
+        // no mass matrix, so just use the identity
+        Eqn::M::from_diagonal(&Eqn::V::from_element(n, Eqn::T::one()))
+    };
+    (rhs_jac, mass_jac, sparsity)
+}
+
 #[derive(Default, Clone, Serialize)]
 pub struct OpStatistics {
     pub number_of_calls: usize,
@@ -115,6 +163,12 @@ pub trait NonLinearOp: Op {
         false
     }
 
+    /// Returns true if the operator is linear in `x` with a jacobian that does not depend on `x` or `t`, i.e. `F(x, t) = A x` for a constant matrix `A`
+    /// (see [linear_rhs::LinearRhs]). The default implementation returns false.
+    fn is_linear(&self) -> bool {
+        false
+    }
+
     /// Compute the operator `F(x, t)` at a given state and time, and return the result.
     /// Use `[Self::call_inplace]` to for a non-allocating version.
     fn call(&self, x: &Self::V, t: Self::T) -> Self::V {
@@ -126,7 +180,7 @@ pub trait NonLinearOp: Op {
     /// Compute the product of the Jacobian with a given vector `J(x, t) * v`, and return the result.
     /// Use `[Self::jac_mul_inplace]` to for a non-allocating version.
     fn jac_mul(&self, x: &Self::V, t: Self::T, v: &Self::V) -> Self::V {
-        let mut y = Self::V::zeros(self.nstates());
+        let mut y = Self::V::zeros(self.nout());
         self.jac_mul_inplace(x, t, v, &mut y);
         y
     }
@@ -148,12 +202,22 @@ pub trait NonLinearOp: Op {
     }
 
     /// Default implementation of the Jacobian computation (this is the default for [Self::jacobian_inplace]).
+    /// If the operator has a sparse [Op::sparsity] pattern then the Jacobian is assembled by multiplying with colored vectors
+    /// (see [JacobianColoring]), otherwise it is assembled column by column by multiplying with basis vectors.
+    /// Note that the coloring is recalculated on each call, ops that are assembled repeatedly should store a [JacobianColoring] instead.
     fn _default_jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
+        if let Some(sparsity) = self.sparsity() {
+            if <Self::M as Matrix>::Sparsity::is_sparse() {
+                let coloring = JacobianColoring::new_from_sparsity(&sparsity);
+                coloring.jacobian_inplace(self, x, t, y);
+                return;
+            }
+        }
         let mut v = Self::V::zeros(self.nstates());
         let mut col = Self::V::zeros(self.nout());
         for j in 0..self.nstates() {
             v[j] = Self::T::one();
-            self.jac_mul_inplace(x, t, &v, &mut col);
+            self.jac_mul_inplace(x, t.clone(), &v, &mut col);
             y.set_column(j, &col);
             v[j] = Self::T::zero();
         }
@@ -162,8 +226,11 @@ pub trait NonLinearOp: Op {
     /// Compute the Jacobian matrix `J(x, t)` of the operator and return it.
     /// See [Self::jacobian_inplace] for a non-allocating version.
     fn jacobian(&self, x: &Self::V, t: Self::T) -> Self::M {
-        let n = self.nstates();
-        let mut y = Self::M::new_from_sparsity(n, n, self.sparsity().map(|s| s.to_owned()));
+        let mut y = Self::M::new_from_sparsity(
+            self.nout(),
+            self.nstates(),
+            self.sparsity().map(|s| s.to_owned()),
+        );
         self.jacobian_inplace(x, t, &mut y);
         y
     }
@@ -182,7 +249,7 @@ pub trait NonLinearOp: Op {
         let mut col = Self::V::zeros(self.nout());
         for j in 0..self.nparams() {
             v[j] = Self::T::one();
-            self.sens_mul_inplace(x, t, &v, &mut col);
+            self.sens_mul_inplace(x, t.clone(), &v, &mut col);
             y.set_column(j, &col);
             v[j] = Self::T::zero();
         }
@@ -254,12 +321,21 @@ pub trait LinearOp: Op {
     }
 
     /// Default implementation of the matrix computation, see [Self::matrix_inplace].
+    /// As for [NonLinearOp::_default_jacobian_inplace], the matrix is assembled using colored vectors if the operator has a sparse [Op::sparsity] pattern,
+    /// or using basis vectors otherwise.
     fn _default_matrix_inplace(&self, t: Self::T, y: &mut Self::M) {
+        if let Some(sparsity) = self.sparsity() {
+            if <Self::M as Matrix>::Sparsity::is_sparse() {
+                let coloring = JacobianColoring::new_from_sparsity(&sparsity);
+                coloring.matrix_inplace(self, t, y);
+                return;
+            }
+        }
         let mut v = Self::V::zeros(self.nstates());
         let mut col = Self::V::zeros(self.nout());
         for j in 0..self.nstates() {
             v[j] = Self::T::one();
-            self.call_inplace(&v, t, &mut col);
+            self.call_inplace(&v, t.clone(), &mut col);
             y.set_column(j, &col);
             v[j] = Self::T::zero();
         }
@@ -279,7 +355,7 @@ pub trait LinearOp: Op {
         let mut col = Self::V::zeros(self.nout());
         for j in 0..self.nparams() {
             v[j] = Self::T::one();
-            self.sens_mul_inplace(x, t, &v, &mut col);
+            self.sens_mul_inplace(x, t.clone(), &v, &mut col);
             y.set_column(j, &col);
             v[j] = Self::T::zero();
         }
@@ -332,7 +408,7 @@ pub trait ConstantOp: Op {
         let mut col = Self::V::zeros(self.nout());
         for j in 0..self.nparams() {
             v[j] = Self::T::one();
-            self.sens_mul_inplace(t, &v, &mut col);
+            self.sens_mul_inplace(t.clone(), &v, &mut col);
             y.set_column(j, &col);
             v[j] = Self::T::zero();
         }
@@ -0,0 +1,396 @@
+use crate::{
+    matrix::MatrixRef, ode_solver::equations::OdeEquations, LinearOp, Matrix, MatrixSparsity,
+    MatrixSparsityRef, NonLinearOp, OdeSolverProblem, Op, Scalar, Vector, VectorRef,
+};
+use num_traits::{One, Zero};
+use std::{cell::RefCell, ops::Deref, rc::Rc};
+
+use super::implicit_jacobian_storage;
+
+// eigenvector matrix T of the Radau IIA (order 5) coefficient matrix A, and its inverse,
+// chosen so that T^-1 A^-1 T = [gamma 0 0; 0 alpha -beta; 0 beta alpha]
+// from Hairer, E., & Wanner, G. (1996). Solving Ordinary Differential Equations II.
+const T_MAT: [[f64; 3]; 3] = [
+    [
+        0.091_232_394_870_892_94,
+        -0.141_255_295_020_954_2,
+        -0.030_029_194_105_147_424,
+    ],
+    [
+        0.241_717_932_707_107,
+        0.204_129_352_293_799_93,
+        0.382_942_112_757_261_9,
+    ],
+    [0.966_048_182_615_093, 1.0, 0.0],
+];
+const T_INV: [[f64; 3]; 3] = [
+    [
+        4.325_579_890_063_155,
+        0.339_199_251_815_809_9,
+        0.541_770_539_935_874_9,
+    ],
+    [
+        -4.178_718_591_551_905,
+        -0.327_682_820_761_062_4,
+        0.476_623_554_500_550_4,
+    ],
+    [
+        -0.502_872_634_945_786_9,
+        2.571_926_949_855_605,
+        -0.596_039_204_828_225,
+    ],
+];
+
+// callable to solve for the transformed stage increments W = (T^-1 x I) Z of the 3-stage Radau IIA method,
+// F(W) = (L x M) W - h (T^-1 x I) f(phi + (T x I) W) = 0, where L = T^-1 A^-1 T
+// W and F(W) are stored as three consecutive blocks of length n.
+//
+// The jacobian (with df/dy evaluated at phi) is block diagonal, with a real block gamma * M - h * J
+// and the real form of the complex block (alpha + i beta) * M - h * J:
+// | gamma * M - h J  0                 0               |
+// | 0                alpha * M - h J   -beta * M       |
+// | 0                beta * M          alpha * M - h J |
+pub struct RadauCallable<Eqn: OdeEquations> {
+    eqn: Rc<Eqn>,
+    c: [Eqn::T; 3],
+    t_mat: [[Eqn::T; 3]; 3],
+    t_inv: [[Eqn::T; 3]; 3],
+    lambda: [[Eqn::T; 3]; 3],
+    h: RefCell<Eqn::T>,
+    phi: RefCell<Eqn::V>,
+    z: RefCell<Vec<Eqn::V>>,
+    f: RefCell<Vec<Eqn::V>>,
+    tmp: RefCell<Eqn::V>,
+    rhs_jac: RefCell<Eqn::M>,
+    mass_jac: RefCell<Eqn::M>,
+    block_jac: RefCell<Eqn::M>,
+    jacobian_is_stale: RefCell<bool>,
+    number_of_jac_evals: RefCell<usize>,
+    sparsity: Option<<Eqn::M as Matrix>::Sparsity>,
+}
+
+impl<Eqn: OdeEquations> RadauCallable<Eqn> {
+    pub fn new(ode_problem: &OdeSolverProblem<Eqn>) -> Self {
+        let eqn = ode_problem.eqn.clone();
+        let n = eqn.rhs().nstates();
+
+        let sqrt6 = 6.0_f64.sqrt();
+        let c = [(4.0 - sqrt6) / 10.0, (4.0 + sqrt6) / 10.0, 1.0].map(Eqn::T::cast);
+        let t_mat = T_MAT.map(|row| row.map(Eqn::T::cast));
+        let t_inv = T_INV.map(|row| row.map(Eqn::T::cast));
+
+        // eigenvalues of A^-1, one real (gamma) and a complex conjugate pair (alpha +- i beta)
+        let cbrt81 = 81.0_f64.cbrt();
+        let cbrt9 = 9.0_f64.cbrt();
+        let gamma = 30.0 / (6.0 + cbrt81 - cbrt9);
+        let alpha = (12.0 - cbrt81 + cbrt9) / 60.0;
+        let beta = (cbrt81 + cbrt9) * 3.0_f64.sqrt() / 60.0;
+        let norm = alpha * alpha + beta * beta;
+        let (alpha, beta) = (alpha / norm, beta / norm);
+        let lambda = [[gamma, 0.0, 0.0], [0.0, alpha, -beta], [0.0, beta, alpha]]
+            .map(|row| row.map(Eqn::T::cast));
+
+        let (rhs_jac, mass_jac, block_sparsity) = implicit_jacobian_storage(eqn.as_ref());
+        let block_jac =
+            Eqn::M::new_from_sparsity(n, n, block_sparsity.as_ref().map(|s| s.as_ref().to_owned()));
+
+        // the sparsity of the full jacobian is made up of the diagonal blocks (mass and rhs jacobian)
+        // and the off-diagonal blocks of the complex system (mass only)
+        let sparsity = block_sparsity.map(|block_sparsity| {
+            let mass_indices = match eqn.mass() {
+                Some(mass) => mass.sparsity().unwrap().indices(),
+                None => (0..n).map(|i| (i, i)).collect(),
+            };
+            let block_indices = block_sparsity.indices();
+            let mut indices = Vec::new();
+            for k in 0..3 {
+                indices.extend(block_indices.iter().map(|&(i, j)| (k * n + i, k * n + j)));
+            }
+            indices.extend(mass_indices.iter().map(|&(i, j)| (n + i, 2 * n + j)));
+            indices.extend(mass_indices.iter().map(|&(i, j)| (2 * n + i, n + j)));
+            <Eqn::M as Matrix>::Sparsity::try_from_indices(3 * n, 3 * n, indices).unwrap()
+        });
+
+        Self {
+            eqn,
+            c,
+            t_mat,
+            t_inv,
+            lambda,
+            h: RefCell::new(Eqn::T::zero()),
+            phi: RefCell::new(<Eqn::V as Vector>::zeros(n)),
+            z: RefCell::new(vec![<Eqn::V as Vector>::zeros(n); 3]),
+            f: RefCell::new(vec![<Eqn::V as Vector>::zeros(n); 3]),
+            tmp: RefCell::new(<Eqn::V as Vector>::zeros(n)),
+            rhs_jac: RefCell::new(rhs_jac),
+            mass_jac: RefCell::new(mass_jac),
+            block_jac: RefCell::new(block_jac),
+            jacobian_is_stale: RefCell::new(true),
+            number_of_jac_evals: RefCell::new(0),
+            sparsity,
+        }
+    }
+
+    pub fn number_of_jac_evals(&self) -> usize {
+        *self.number_of_jac_evals.borrow()
+    }
+    pub fn set_h(&self, h: Eqn::T) {
+        self.h.replace(h);
+    }
+    pub fn set_phi(&self, y0: &Eqn::V) {
+        self.phi.borrow_mut().copy_from(y0);
+    }
+    pub fn set_jacobian_is_stale(&self) {
+        self.jacobian_is_stale.replace(true);
+    }
+    pub fn eqn(&self) -> &Rc<Eqn> {
+        &self.eqn
+    }
+
+    /// The collocation points of the method
+    pub fn c(&self) -> &[Eqn::T; 3] {
+        &self.c
+    }
+
+    /// Recover the stage increments `Z = (T x I) W` from the transformed variables `W`
+    pub fn stage_increments(&self, w: &Eqn::V, z: &mut [Eqn::V]) {
+        Self::transform(&self.t_mat, w, z);
+    }
+
+    /// Compute the transformed variables `W = (T^-1 x I) Z` from the stage increments `Z`
+    pub fn transformed_variables(&self, z: &[Eqn::V], w: &mut Eqn::V) {
+        let n = z[0].len();
+        for i in 0..3 {
+            for k in 0..n {
+                w[i * n + k] = (0..3).fold(Eqn::T::zero(), |acc, j| {
+                    acc + self.t_inv[i][j].clone() * z[j][k].clone()
+                });
+            }
+        }
+    }
+
+    // out_i = sum_j m[i][j] x_j, where x_j is the j-th block of x
+    fn transform(m: &[[Eqn::T; 3]; 3], x: &Eqn::V, out: &mut [Eqn::V]) {
+        let n = out[0].len();
+        for (i, out_i) in out.iter_mut().enumerate() {
+            for k in 0..n {
+                out_i[k] = (0..3).fold(Eqn::T::zero(), |acc, j| {
+                    acc + m[i][j].clone() * x[j * n + k].clone()
+                });
+            }
+        }
+    }
+
+    // y_i = sum_j L[i][j] M x_j - h sum_j T^-1[i][j] f_j
+    fn combine(&self, x: &Eqn::V, t: Eqn::T, f: &[Eqn::V], y: &mut Eqn::V) {
+        let n = f[0].len();
+        let h = self.h.borrow().clone();
+        let mut tmp = self.tmp.borrow_mut();
+        let mut mx = vec![<Eqn::V as Vector>::zeros(n); 3];
+        for (j, mx_j) in mx.iter_mut().enumerate() {
+            for k in 0..n {
+                tmp[k] = x[j * n + k].clone();
+            }
+            match self.eqn.mass() {
+                Some(mass) => mass.call_inplace(&tmp, t.clone(), mx_j),
+                None => mx_j.copy_from(&tmp),
+            }
+        }
+        for i in 0..3 {
+            for k in 0..n {
+                y[i * n + k] = (0..3).fold(Eqn::T::zero(), |acc, j| {
+                    acc + self.lambda[i][j].clone() * mx[j][k].clone()
+                        - h.clone() * self.t_inv[i][j].clone() * f[j][k].clone()
+                });
+            }
+        }
+    }
+}
+
+impl<Eqn: OdeEquations> Op for RadauCallable<Eqn> {
+    type V = Eqn::V;
+    type T = Eqn::T;
+    type M = Eqn::M;
+    fn nstates(&self) -> usize {
+        3 * self.eqn.rhs().nstates()
+    }
+    fn nout(&self) -> usize {
+        3 * self.eqn.rhs().nstates()
+    }
+    fn nparams(&self) -> usize {
+        self.eqn.rhs().nparams()
+    }
+    fn sparsity(&self) -> Option<<Self::M as Matrix>::SparsityRef<'_>> {
+        self.sparsity.as_ref().map(|s| s.as_ref())
+    }
+}
+
+impl<Eqn: OdeEquations> NonLinearOp for RadauCallable<Eqn>
+where
+    for<'b> &'b Eqn::V: VectorRef<Eqn::V>,
+    for<'b> &'b Eqn::M: MatrixRef<Eqn::M>,
+{
+    // F(W) = (L x M) W - h (T^-1 x I) f(phi + (T x I) W)
+    fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
+        let h = self.h.borrow().clone();
+        let phi = self.phi.borrow();
+        let mut z = self.z.borrow_mut();
+        let mut f = self.f.borrow_mut();
+        Self::transform(&self.t_mat, x, &mut z);
+        for i in 0..3 {
+            let mut tmp = self.tmp.borrow_mut();
+            tmp.copy_from(&phi);
+            tmp.axpy(Eqn::T::one(), &z[i], Eqn::T::one());
+            self.eqn
+                .rhs()
+                .call_inplace(&tmp, t.clone() + self.c[i].clone() * h.clone(), &mut f[i]);
+        }
+        self.combine(x, t, &f, y);
+    }
+
+    // (L x M) v - h (T^-1 x I) diag(f'(phi + Z_i)) (T x I) v
+    fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
+        let h = self.h.borrow().clone();
+        let phi = self.phi.borrow();
+        let mut z = self.z.borrow_mut();
+        let mut f = self.f.borrow_mut();
+        Self::transform(&self.t_mat, x, &mut z);
+        let mut zv = vec![<Eqn::V as Vector>::zeros(phi.len()); 3];
+        Self::transform(&self.t_mat, v, &mut zv);
+        for i in 0..3 {
+            let mut tmp = self.tmp.borrow_mut();
+            tmp.copy_from(&phi);
+            tmp.axpy(Eqn::T::one(), &z[i], Eqn::T::one());
+            self.eqn.rhs().jac_mul_inplace(
+                &tmp,
+                t.clone() + self.c[i].clone() * h.clone(),
+                &zv[i],
+                &mut f[i],
+            );
+        }
+        self.combine(v, t, &f, y);
+    }
+
+    // block diagonal approximation of the jacobian, with f' evaluated at phi
+    fn jacobian_inplace(&self, _x: &Self::V, t: Self::T, y: &mut Self::M) {
+        let h = self.h.borrow().clone();
+        let n = self.phi.borrow().len();
+        if *self.jacobian_is_stale.borrow() {
+            // calculate the mass and rhs jacobians
+            let phi = self.phi.borrow();
+            let mut rhs_jac = self.rhs_jac.borrow_mut();
+            self.eqn
+                .rhs()
+                .jacobian_inplace(&phi, t.clone(), &mut rhs_jac);
+            if let Some(mass) = self.eqn.mass() {
+                let mut mass_jac = self.mass_jac.borrow_mut();
+                mass.matrix_inplace(t, &mut mass_jac);
+            }
+            self.jacobian_is_stale.replace(false);
+        }
+        let rhs_jac = self.rhs_jac.borrow();
+        let mass_jac = self.mass_jac.borrow();
+        let mut block_jac = self.block_jac.borrow_mut();
+        let gamma = self.lambda[0][0].clone();
+        let alpha = self.lambda[1][1].clone();
+        let beta = self.lambda[2][1].clone();
+        let mut triplets = Vec::new();
+
+        // gamma * M - h * J = gamma * (M - h / gamma * J)
+        block_jac.scale_add_and_assign(
+            mass_jac.deref(),
+            -h.clone() / gamma.clone(),
+            rhs_jac.deref(),
+        );
+        triplets.extend(
+            block_jac
+                .triplet_iter()
+                .map(|(i, j, v)| (i, j, gamma.clone() * v.clone())),
+        );
+
+        // alpha * M - h * J = alpha * (M - h / alpha * J)
+        block_jac.scale_add_and_assign(mass_jac.deref(), -h / alpha.clone(), rhs_jac.deref());
+        for k in 1..3 {
+            triplets.extend(
+                block_jac
+                    .triplet_iter()
+                    .map(|(i, j, v)| (k * n + i, k * n + j, alpha.clone() * v.clone())),
+            );
+        }
+
+        // -beta * M and beta * M
+        for (i, j, v) in mass_jac.triplet_iter() {
+            triplets.push((n + i, 2 * n + j, -beta.clone() * v.clone()));
+            triplets.push((2 * n + i, n + j, beta.clone() * v.clone()));
+        }
+        *y = Eqn::M::try_from_triplets(3 * n, 3 * n, triplets).unwrap();
+
+        let number_of_jac_evals = *self.number_of_jac_evals.borrow() + 1;
+        self.number_of_jac_evals.replace(number_of_jac_evals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ode_solver::test_models::robertson::{robertson, robertson_colored};
+    use crate::op::NonLinearOp;
+    use crate::vector::Vector;
+    use crate::{OdeEquations, OdeSolverProblem};
+
+    use super::{RadauCallable, T_INV, T_MAT};
+    type Mcpu = nalgebra::DMatrix<f64>;
+    type Vcpu = nalgebra::DVector<f64>;
+
+    #[test]
+    fn test_radau_transformation() {
+        let t = Mcpu::from_fn(3, 3, |i, j| T_MAT[i][j]);
+        let t_inv = Mcpu::from_fn(3, 3, |i, j| T_INV[i][j]);
+        assert!((&t * &t_inv - Mcpu::identity(3, 3)).abs().max() < 1e-14);
+
+        // T^-1 A^-1 T is block diagonal with the eigenvalues of A^-1
+        let (problem, _soln) = robertson::<Mcpu>();
+        let op = RadauCallable::new(&problem);
+        let sqrt6 = 6.0_f64.sqrt();
+        let a = Mcpu::from_row_slice(
+            3,
+            3,
+            &[
+                (88.0 - 7.0 * sqrt6) / 360.0,
+                (296.0 - 169.0 * sqrt6) / 1800.0,
+                (-2.0 + 3.0 * sqrt6) / 225.0,
+                (296.0 + 169.0 * sqrt6) / 1800.0,
+                (88.0 + 7.0 * sqrt6) / 360.0,
+                (-2.0 - 3.0 * sqrt6) / 225.0,
+                (16.0 - sqrt6) / 36.0,
+                (16.0 + sqrt6) / 36.0,
+                1.0 / 9.0,
+            ],
+        );
+        let lambda = &t_inv * a.try_inverse().unwrap() * &t;
+        let expect = Mcpu::from_fn(3, 3, |i, j| op.lambda[i][j]);
+        assert!((lambda - expect).abs().max() < 1e-12);
+    }
+
+    fn check_robertson_jacobian<Eqn: OdeEquations<M = Mcpu, V = Vcpu, T = f64>>(
+        problem: &OdeSolverProblem<Eqn>,
+    ) {
+        let op = RadauCallable::new(problem);
+        op.set_h(1.3);
+        op.set_phi(&Vcpu::from_vec(vec![1.1, 1.2, 1.3]));
+        let t = 0.9;
+
+        // at W = 0 all the stages are evaluated at phi, so the block jacobian is exact
+        let x = Vcpu::zeros(9);
+        let v = Vcpu::from_vec(vec![2.0, 3.0, 4.0, 1.0, -1.0, 0.5, 0.1, 0.2, 0.3]);
+        let jac = op.jacobian(&x, t);
+        let jac_mul_v = op.jac_mul(&x, t, &v);
+        let jac_mul_v2 = &jac * &v;
+        jac_mul_v.assert_eq_st(&jac_mul_v2, 1e-6);
+    }
+
+    #[test]
+    fn test_radau_robertson_jacobian() {
+        check_robertson_jacobian(&robertson::<Mcpu>().0);
+        check_robertson_jacobian(&robertson_colored::<Mcpu>().0);
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     matrix::{MatrixRef, MatrixView},
     ode_solver::equations::OdeEquations,
-    LinearOp, Matrix, MatrixSparsity, MatrixSparsityRef, OdeSolverProblem, Vector, VectorRef,
+    LinearOp, Matrix, MatrixSparsity, OdeSolverProblem, Vector, VectorRef,
 };
 use num_traits::{One, Zero};
 use std::{
@@ -10,15 +10,21 @@ use std::{
     rc::Rc,
 };
 
-use super::{NonLinearOp, Op};
+use super::{implicit_jacobian_storage, NonLinearOp, Op};
 
 // callable to solve for F(y) = M (y) - h f(phi + a * y) = 0
+// if the rhs is split, only the stiff part f - f_e is used (see OdeEquations::rhs_explicit_inplace)
 pub struct SdirkCallable<Eqn: OdeEquations> {
     eqn: Rc<Eqn>,
     c: Eqn::T,
     h: RefCell<Eqn::T>,
     phi: RefCell<Eqn::V>,
     tmp: RefCell<Eqn::V>,
+    // the point at which the jacobian-vector products are evaluated, kept separate from tmp so that
+    // the last evaluation of F is not overwritten by a matrix-free linear solver
+    jac_tmp: RefCell<Eqn::V>,
+    split_rhs: bool,
+    rhs_explicit: RefCell<Eqn::V>,
     rhs_jac: RefCell<Eqn::M>,
     mass_jac: RefCell<Eqn::M>,
     jacobian_is_stale: RefCell<bool>,
@@ -34,12 +40,16 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
         let jacobian_is_stale = RefCell::new(false);
         let number_of_jac_evals = RefCell::new(0);
         let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
+        let jac_tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
         let rhs_jac = RefCell::new(<Eqn::M as Matrix>::zeros(0, 0));
         let mass_jac = RefCell::new(<Eqn::M as Matrix>::zeros(0, 0));
         let sparsity = None;
+        let rhs_explicit = RefCell::new(<Eqn::V as Vector>::zeros(n));
         Self {
             eqn,
             phi,
+            split_rhs: false,
+            rhs_explicit,
             c,
             h,
             rhs_jac,
@@ -47,6 +57,7 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
             jacobian_is_stale,
             number_of_jac_evals,
             tmp,
+            jac_tmp,
             sparsity,
         }
     }
@@ -59,44 +70,18 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
         let jacobian_is_stale = RefCell::new(true);
         let number_of_jac_evals = RefCell::new(0);
         let tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
+        let jac_tmp = RefCell::new(<Eqn::V as Vector>::zeros(n));
 
-        // create the mass and rhs jacobians according to the sparsity pattern
-        let rhs_jac = RefCell::new(Eqn::M::new_from_sparsity(
-            n,
-            n,
-            eqn.rhs().sparsity().map(|s| s.to_owned()),
-        ));
-        let sparsity = if let Some(rhs_jac_sparsity) = eqn.rhs().sparsity() {
-            if let Some(mass) = eqn.mass() {
-                // have mass, use the union of the mass and rhs jacobians sparse patterns
-                Some(
-                    mass.sparsity()
-                        .unwrap()
-                        .to_owned()
-                        .union(rhs_jac_sparsity)
-                        .unwrap(),
-                )
-            } else {
-                // no mass, use the identity
-                let mass_sparsity = <Eqn::M as Matrix>::Sparsity::new_diagonal(n);
-                Some(mass_sparsity.union(rhs_jac_sparsity).unwrap())
-            }
-        } else {
-            None
-        };
-
-        let mass_jac = if eqn.mass().is_none() {
-            // no mass matrix, so just use the identity
-            Eqn::M::from_diagonal(&Eqn::V::from_element(n, Eqn::T::one()))
-        } else {
-            // mass is not constant, so just create a matrix with the correct sparsity
-            Eqn::M::new_from_sparsity(n, n, eqn.mass().unwrap().sparsity().map(|s| s.to_owned()))
-        };
+        let (rhs_jac, mass_jac, sparsity) = implicit_jacobian_storage(eqn.as_ref());
+        let rhs_jac = RefCell::new(rhs_jac);
         let mass_jac = RefCell::new(mass_jac);
+        let rhs_explicit = RefCell::new(<Eqn::V as Vector>::zeros(n));
 
         Self {
             eqn,
             phi,
+            split_rhs: false,
+            rhs_explicit,
             c,
             h,
             rhs_jac,
@@ -105,9 +90,16 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
             jacobian_is_stale,
             number_of_jac_evals,
             tmp,
+            jac_tmp,
         }
     }
 
+    /// Only treat the stiff part `F - F_E` of the rhs implicitly, where `F_E` is given by [OdeEquations::rhs_explicit_inplace].
+    /// The jacobian of the full rhs is still used, which is a good approximation as long as `F_E` is non-stiff.
+    pub fn set_split_rhs(&mut self, split_rhs: bool) {
+        self.split_rhs = split_rhs;
+    }
+
     pub fn number_of_jac_evals(&self) -> usize {
         *self.number_of_jac_evals.borrow()
     }
@@ -117,16 +109,15 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
     {
         self.h.replace(h);
     }
-    pub fn get_last_f_eval(&self) -> Ref<Eqn::V> {
+    pub fn get_last_f_eval(&self) -> Ref<'_, Eqn::V> {
         self.tmp.borrow()
     }
     pub fn eqn(&self) -> &Rc<Eqn> {
         &self.eqn
     }
-    #[allow(dead_code)]
-    fn set_phi_direct(&self, phi: Eqn::V) {
+    pub fn set_phi_direct(&self, phi: &Eqn::V) {
         let mut phi_ref = self.phi.borrow_mut();
-        phi_ref.copy_from(&phi);
+        phi_ref.copy_from(phi);
     }
     pub fn set_phi<'a, M: MatrixView<'a, T = Eqn::T, V = Eqn::V>>(
         &self,
@@ -144,7 +135,7 @@ impl<Eqn: OdeEquations> SdirkCallable<Eqn> {
         let phi_ref = self.phi.borrow();
         let phi = phi_ref.deref();
         let mut tmp = self.tmp.borrow_mut();
-        let c = self.c;
+        let c = self.c.clone();
 
         tmp.copy_from(phi);
         tmp.axpy(c, x, Eqn::T::one());
@@ -182,9 +173,17 @@ where
     fn call_inplace(&self, x: &Eqn::V, t: Eqn::T, y: &mut Eqn::V) {
         self.set_tmp(x);
         let tmp = self.tmp.borrow();
-        let h = *self.h.borrow().deref();
+        let h = self.h.borrow().deref().clone();
 
-        self.eqn.rhs().call_inplace(&tmp, t, y);
+        self.eqn.rhs().call_inplace(&tmp, t.clone(), y);
+
+        // y = y - f_e
+        if self.split_rhs {
+            let mut rhs_explicit = self.rhs_explicit.borrow_mut();
+            self.eqn
+                .rhs_explicit_inplace(&tmp, t.clone(), &mut rhs_explicit);
+            y.axpy(-Eqn::T::one(), &rhs_explicit, Eqn::T::one());
+        }
 
         // y = Mx - h y
         if let Some(mass) = self.eqn.mass() {
@@ -195,12 +194,13 @@ where
     }
     // (M - c * h * f'(phi + c * y)) v
     fn jac_mul_inplace(&self, x: &Eqn::V, t: Eqn::T, v: &Eqn::V, y: &mut Eqn::V) {
-        self.set_tmp(x);
-        let tmp = self.tmp.borrow();
-        let h = *self.h.borrow().deref();
-        let c = self.c;
+        let h = self.h.borrow().deref().clone();
+        let c = self.c.clone();
+        let mut tmp = self.jac_tmp.borrow_mut();
+        tmp.copy_from(&self.phi.borrow());
+        tmp.axpy(c.clone(), x, Eqn::T::one());
 
-        self.eqn.rhs().jac_mul_inplace(&tmp, t, v, y);
+        self.eqn.rhs().jac_mul_inplace(&tmp, t.clone(), v, y);
 
         // y = Mv - c h y
         if let Some(mass) = self.eqn.mass() {
@@ -212,14 +212,16 @@ where
 
     // M - c * h * f'(phi + c * y)
     fn jacobian_inplace(&self, x: &Self::V, t: Self::T, y: &mut Self::M) {
-        let c = self.c;
-        let h = *self.h.borrow().deref();
+        let c = self.c.clone();
+        let h = self.h.borrow().deref().clone();
         if *self.jacobian_is_stale.borrow() {
             // calculate the mass and rhs jacobians
             let mut rhs_jac = self.rhs_jac.borrow_mut();
             self.set_tmp(x);
             let tmp = self.tmp.borrow();
-            self.eqn.rhs().jacobian_inplace(&tmp, t, &mut rhs_jac);
+            self.eqn
+                .rhs()
+                .jacobian_inplace(&tmp, t.clone(), &mut rhs_jac);
 
             if self.eqn.mass().is_none() {
                 let mass_jac = self.mass_jac.borrow();
@@ -244,47 +246,52 @@ where
 #[cfg(test)]
 mod tests {
     use crate::ode_solver::test_models::exponential_decay::exponential_decay_problem;
-    use crate::ode_solver::test_models::robertson::robertson;
+    use crate::ode_solver::test_models::robertson::{robertson, robertson_colored};
     use crate::op::NonLinearOp;
     use crate::vector::Vector;
     use crate::Matrix;
+    use crate::{OdeEquations, OdeSolverProblem};
 
     use super::SdirkCallable;
     type Mcpu = nalgebra::DMatrix<f64>;
     type Vcpu = nalgebra::DVector<f64>;
 
+    fn check_robertson_jacobian<Eqn: OdeEquations<M = Mcpu, V = Vcpu, T = f64>>(
+        problem: &OdeSolverProblem<Eqn>,
+    ) {
+        let c = 0.1;
+        let h = 1.3;
+        let phi = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
+        let sdirk_callable = SdirkCallable::new(problem, c);
+        sdirk_callable.set_h(h);
+        sdirk_callable.set_phi_direct(&phi);
+        let t = 0.9;
+        let y = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
+
+        let v = Vcpu::from_vec(vec![2.0, 3.0, 4.0]);
+        let jac = sdirk_callable.jacobian(&y, t);
+        let jac_mul_v = sdirk_callable.jac_mul(&y, t, &v);
+        let mut jac_mul_v2 = Vcpu::from_vec(vec![0.0, 0.0, 0.0]);
+        jac.gemv(1.0, &v, 0.0, &mut jac_mul_v2);
+        jac_mul_v.assert_eq_st(&jac_mul_v2, 1e-10);
+    }
+
     #[test]
     fn test_sdirk_robertson_jacobian() {
-        for colored in [true, false] {
-            let (problem, _soln) = robertson::<Mcpu>(colored);
-            let c = 0.1;
-            let h = 1.3;
-            let phi = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
-            let sdirk_callable = SdirkCallable::new(&problem, c);
-            sdirk_callable.set_h(h);
-            sdirk_callable.set_phi_direct(phi);
-            let t = 0.9;
-            let y = Vcpu::from_vec(vec![1.1, 1.2, 1.3]);
-
-            let v = Vcpu::from_vec(vec![2.0, 3.0, 4.0]);
-            let jac = sdirk_callable.jacobian(&y, t);
-            let jac_mul_v = sdirk_callable.jac_mul(&y, t, &v);
-            let mut jac_mul_v2 = Vcpu::from_vec(vec![0.0, 0.0, 0.0]);
-            jac.gemv(1.0, &v, 0.0, &mut jac_mul_v2);
-            jac_mul_v.assert_eq_st(&jac_mul_v2, 1e-10);
-        }
+        check_robertson_jacobian(&robertson::<Mcpu>().0);
+        check_robertson_jacobian(&robertson_colored::<Mcpu>().0);
     }
 
     #[test]
     fn test_sdirk_callable() {
-        let (problem, _soln) = exponential_decay_problem::<Mcpu>(false);
+        let (problem, _soln) = exponential_decay_problem::<Mcpu>();
         let c = 0.1;
         let h = 1.0;
         let sdirk_callable = SdirkCallable::new(&problem, c);
         sdirk_callable.set_h(h);
 
         let phi = Vcpu::from_vec(vec![1.1, 1.2]);
-        sdirk_callable.set_phi_direct(phi);
+        sdirk_callable.set_phi_direct(&phi);
         // check that the function is correct
         let y = Vcpu::from_vec(vec![1.0, 1.0]);
         let t = 0.0;
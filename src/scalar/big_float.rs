@@ -0,0 +1,929 @@
+use std::{
+    cmp::Ordering,
+    fmt::{Debug, Display, Formatter},
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use approx::{AbsDiffEq, RelativeEq, UlpsEq};
+use dashu_base::{ParseError, Sign, SquareRoot};
+use dashu_float::{round::mode::HalfEven, DBig, FBig};
+use nalgebra::{ComplexField, Field, RealField, SimdValue};
+use num_traits::{FromPrimitive, Num, One, Pow, Signed, Zero};
+use simba::scalar::SubsetOf;
+
+use super::Scalar;
+
+type Big = FBig<HalfEven, 2>;
+
+/// Number of bits in the significand of a [BigFloat] (about 77 decimal digits).
+pub const BIG_FLOAT_PRECISION: usize = 256;
+
+/// Extra bits used for the intermediate values of the series expansions (e.g. `sin`, `atan`).
+const GUARD_BITS: usize = 32;
+
+/// An arbitrary-precision binary floating point [Scalar] (requires the `dashu` feature), based on
+/// the [dashu-float](https://docs.rs/dashu-float) `FBig` type with [BIG_FLOAT_PRECISION] bits.
+///
+/// This is intended for computing reference solutions to many more digits than `f64`, e.g. using
+/// [crate::Extrapolation] with a tight tolerance. dashu has no infinities or NaN, so these are
+/// stored as an `f64`, and any operation on them (or outside the domain of a function, e.g.
+/// dividing by zero or the log of a negative number) follows the `f64` semantics.
+#[derive(Clone)]
+pub struct BigFloat(Inner);
+
+#[derive(Clone)]
+enum Inner {
+    Finite(Big),
+    NonFinite(f64),
+}
+
+impl BigFloat {
+    fn finite(x: Big) -> Self {
+        if x.precision() == BIG_FLOAT_PRECISION {
+            Self(Inner::Finite(x))
+        } else {
+            Self(Inner::Finite(x.with_precision(BIG_FLOAT_PRECISION).value()))
+        }
+    }
+
+    /// Convert an `f64` exactly (see also [std::str::FromStr], to parse a decimal to the full precision)
+    pub fn from_f64(x: f64) -> Self {
+        match Big::try_from(x) {
+            Ok(b) if x.is_finite() => Self::finite(b),
+            _ => Self(Inner::NonFinite(x)),
+        }
+    }
+
+    /// Round to the nearest `f64`
+    pub fn to_f64(&self) -> f64 {
+        match &self.0 {
+            Inner::Finite(x) => x.to_f64().value(),
+            Inner::NonFinite(x) => *x,
+        }
+    }
+
+    /// Apply `big` if the value is finite and `big` returns a value (i.e. the argument is in its domain), otherwise `float`.
+    fn map(self, big: impl FnOnce(&Big) -> Option<Big>, float: impl FnOnce(f64) -> f64) -> Self {
+        if let Inner::Finite(x) = &self.0 {
+            if let Some(y) = big(x) {
+                return Self::finite(y);
+            }
+        }
+        Self::from_f64(float(self.to_f64()))
+    }
+
+    /// The binary version of [Self::map].
+    fn zip(
+        self,
+        rhs: Self,
+        big: impl FnOnce(&Big, &Big) -> Option<Big>,
+        float: impl FnOnce(f64, f64) -> f64,
+    ) -> Self {
+        if let (Inner::Finite(x), Inner::Finite(y)) = (&self.0, &rhs.0) {
+            if let Some(z) = big(x, y) {
+                return Self::finite(z);
+            }
+        }
+        Self::from_f64(float(self.to_f64(), rhs.to_f64()))
+    }
+
+    fn is_positive_finite(&self) -> bool {
+        matches!(&self.0, Inner::Finite(x) if is_positive(x))
+    }
+}
+
+fn is_positive(x: &Big) -> bool {
+    x.sign() == Sign::Positive && !x.repr().is_zero()
+}
+
+fn abs(x: &Big) -> Big {
+    match x.sign() {
+        Sign::Positive => x.clone(),
+        Sign::Negative => -x,
+    }
+}
+
+fn guarded(x: &Big) -> Big {
+    x.clone()
+        .with_precision(BIG_FLOAT_PRECISION + GUARD_BITS)
+        .value()
+}
+
+/// true if adding `term` to `sum` would not change it
+fn negligible(term: &Big, sum: &Big) -> bool {
+    term.repr().is_zero() || (!sum.repr().is_zero() && abs(term) < sum.ulp())
+}
+
+/// `atan(x)` (at the guarded precision), reducing the argument with `atan(x) = 2 atan(x / (1 + sqrt(1 + x^2)))`
+/// until the Taylor series converges quickly.
+fn atan(x: &Big) -> Big {
+    let one = guarded(&Big::ONE);
+    let small = Big::from_parts_const(Sign::Positive, 1, -4, None);
+    let mut x = guarded(x);
+    let mut doublings = 0;
+    while abs(&x) > small {
+        x = &x / (&one + (&one + &x * &x).sqrt());
+        doublings += 1;
+    }
+    let x2 = &x * &x;
+    let mut power = x.clone();
+    let mut sum = x;
+    let mut k = 1u32;
+    loop {
+        power = -(power * &x2);
+        let term = &power / Big::from(2 * k + 1);
+        if negligible(&term, &sum) {
+            break;
+        }
+        sum += term;
+        k += 1;
+    }
+    sum * Big::from(1u64 << doublings)
+}
+
+/// pi at the guarded precision
+fn pi() -> &'static Big {
+    static PI: OnceLock<Big> = OnceLock::new();
+    PI.get_or_init(|| atan(&Big::ONE) * Big::from(4u8))
+}
+
+/// `(sin(x), cos(x))` using the Taylor series, after reducing the argument to `[-pi, pi]`.
+fn sin_cos(x: &Big) -> (Big, Big) {
+    let two_pi = pi() * Big::from(2u8);
+    let x = guarded(x);
+    let r = &x - (&x / &two_pi).round() * two_pi;
+    let r2 = &r * &r;
+    let mut term = r.clone();
+    let mut sin = r;
+    let mut n = 1u32;
+    loop {
+        term = -(term * &r2) / Big::from((n + 1) * (n + 2));
+        if negligible(&term, &sin) {
+            break;
+        }
+        sin += &term;
+        n += 2;
+    }
+    let mut term = guarded(&Big::ONE);
+    let mut cos = term.clone();
+    let mut n = 0u32;
+    loop {
+        term = -(term * &r2) / Big::from((n + 1) * (n + 2));
+        if negligible(&term, &cos) {
+            break;
+        }
+        cos += &term;
+        n += 2;
+    }
+    (sin, cos)
+}
+
+impl Scalar for BigFloat {
+    const EPSILON: Self = Self(Inner::Finite(Big::from_parts_const(
+        Sign::Positive,
+        1,
+        1 - BIG_FLOAT_PRECISION as isize,
+        Some(BIG_FLOAT_PRECISION),
+    )));
+    const INFINITY: Self = Self(Inner::NonFinite(f64::INFINITY));
+    const NAN: Self = Self(Inner::NonFinite(f64::NAN));
+    fn is_nan(&self) -> bool {
+        matches!(self.0, Inner::NonFinite(x) if x.is_nan())
+    }
+    fn cast(x: f64) -> Self {
+        Self::from_f64(x)
+    }
+    fn to_f64(&self) -> f64 {
+        BigFloat::to_f64(self)
+    }
+}
+
+impl FromStr for BigFloat {
+    type Err = ParseError;
+
+    /// Parse a decimal number (e.g. `1.234e-5`) without first rounding to `f64`, or `inf`/`NaN`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match DBig::from_str(s) {
+            Ok(x) => Ok(Self::finite(
+                x.with_base_and_precision::<2>(BIG_FLOAT_PRECISION)
+                    .value()
+                    .with_rounding(),
+            )),
+            Err(err) => match s.parse::<f64>() {
+                Ok(x) if !x.is_finite() => Ok(Self::from_f64(x)),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+impl Display for BigFloat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Inner::Finite(x) => write!(f, "{}", x.to_decimal().value()),
+            Inner::NonFinite(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl Debug for BigFloat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(self, f)
+    }
+}
+
+impl Default for BigFloat {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl PartialEq for BigFloat {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Inner::Finite(x), Inner::Finite(y)) => x == y,
+            _ => self.to_f64() == other.to_f64(),
+        }
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (&self.0, &other.0) {
+            (Inner::Finite(x), Inner::Finite(y)) => x.partial_cmp(y),
+            _ => self.to_f64().partial_cmp(&other.to_f64()),
+        }
+    }
+}
+
+impl Neg for BigFloat {
+    type Output = Self;
+    fn neg(self) -> Self {
+        match self.0 {
+            Inner::Finite(x) => Self(Inner::Finite(-x)),
+            Inner::NonFinite(x) => Self(Inner::NonFinite(-x)),
+        }
+    }
+}
+
+macro_rules! impl_bin_op {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $big:expr, $float:expr) => {
+        impl $trait for BigFloat {
+            type Output = Self;
+            #[inline]
+            fn $method(self, rhs: Self) -> Self {
+                self.zip(rhs, $big, $float)
+            }
+        }
+
+        impl $assign_trait for BigFloat {
+            #[inline]
+            fn $assign_method(&mut self, rhs: Self) {
+                *self = std::mem::take(self).$method(rhs);
+            }
+        }
+    };
+}
+
+impl_bin_op!(
+    Add,
+    add,
+    AddAssign,
+    add_assign,
+    |x, y| Some(x + y),
+    |x, y| x + y
+);
+impl_bin_op!(
+    Sub,
+    sub,
+    SubAssign,
+    sub_assign,
+    |x, y| Some(x - y),
+    |x, y| x - y
+);
+impl_bin_op!(
+    Mul,
+    mul,
+    MulAssign,
+    mul_assign,
+    |x, y| Some(x * y),
+    |x, y| x * y
+);
+impl_bin_op!(
+    Div,
+    div,
+    DivAssign,
+    div_assign,
+    |x, y: &Big| (!y.repr().is_zero()).then(|| x / y),
+    |x, y| x / y
+);
+impl_bin_op!(
+    Rem,
+    rem,
+    RemAssign,
+    rem_assign,
+    |x: &Big, y: &Big| (!y.repr().is_zero()).then(|| x - (x / y).trunc() * y),
+    |x, y| x % y
+);
+
+impl Zero for BigFloat {
+    fn zero() -> Self {
+        Self::finite(Big::ZERO)
+    }
+    fn is_zero(&self) -> bool {
+        matches!(&self.0, Inner::Finite(x) if x.repr().is_zero())
+    }
+}
+
+impl One for BigFloat {
+    fn one() -> Self {
+        Self::finite(Big::ONE)
+    }
+}
+
+impl Num for BigFloat {
+    type FromStrRadixErr = ParseError;
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        if radix == 10 {
+            s.parse()
+        } else {
+            Err(ParseError::UnsupportedRadix)
+        }
+    }
+}
+
+impl Signed for BigFloat {
+    fn abs(&self) -> Self {
+        self.clone().map(|x| Some(abs(x)), f64::abs)
+    }
+    fn abs_sub(&self, other: &Self) -> Self {
+        if self <= other {
+            Self::zero()
+        } else {
+            self.clone() - other.clone()
+        }
+    }
+    fn signum(&self) -> Self {
+        self.clone().map(
+            |x| {
+                Some(if x.repr().is_zero() {
+                    Big::ZERO
+                } else {
+                    x.signum()
+                })
+            },
+            f64::signum,
+        )
+    }
+    fn is_positive(&self) -> bool {
+        match &self.0 {
+            Inner::Finite(x) => is_positive(x),
+            Inner::NonFinite(x) => *x > 0.0,
+        }
+    }
+    fn is_negative(&self) -> bool {
+        match &self.0 {
+            Inner::Finite(x) => x.sign() == Sign::Negative,
+            Inner::NonFinite(x) => *x < 0.0,
+        }
+    }
+}
+
+impl FromPrimitive for BigFloat {
+    fn from_i64(n: i64) -> Option<Self> {
+        Some(Self::finite(Big::from(n)))
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        Some(Self::finite(Big::from(n)))
+    }
+    fn from_f64(n: f64) -> Option<Self> {
+        Some(Self::from_f64(n))
+    }
+}
+
+impl Pow<BigFloat> for BigFloat {
+    type Output = Self;
+    fn pow(self, rhs: Self) -> Self {
+        self.powf(rhs)
+    }
+}
+
+impl Pow<i32> for BigFloat {
+    type Output = Self;
+    fn pow(self, rhs: i32) -> Self {
+        self.powi(rhs)
+    }
+}
+
+impl SubsetOf<BigFloat> for f64 {
+    fn to_superset(&self) -> BigFloat {
+        BigFloat::from_f64(*self)
+    }
+    fn from_superset_unchecked(element: &BigFloat) -> Self {
+        element.to_f64()
+    }
+    fn is_in_subset(_element: &BigFloat) -> bool {
+        true
+    }
+}
+
+impl SubsetOf<BigFloat> for BigFloat {
+    fn to_superset(&self) -> BigFloat {
+        self.clone()
+    }
+    fn from_superset_unchecked(element: &BigFloat) -> Self {
+        element.clone()
+    }
+    fn is_in_subset(_element: &BigFloat) -> bool {
+        true
+    }
+}
+
+impl AbsDiffEq for BigFloat {
+    type Epsilon = Self;
+    fn default_epsilon() -> Self {
+        Self::EPSILON
+    }
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self) -> bool {
+        (self.clone() - other.clone()).abs() <= epsilon
+    }
+}
+
+impl RelativeEq for BigFloat {
+    fn default_max_relative() -> Self {
+        Self::EPSILON
+    }
+    fn relative_eq(&self, other: &Self, epsilon: Self, max_relative: Self) -> bool {
+        if self == other {
+            return true;
+        }
+        let diff = (self.clone() - other.clone()).abs();
+        if diff <= epsilon {
+            return true;
+        }
+        let largest = RealField::max(self.clone().abs(), other.clone().abs());
+        diff <= largest * max_relative
+    }
+}
+
+impl UlpsEq for BigFloat {
+    fn default_max_ulps() -> u32 {
+        4
+    }
+    /// The difference is compared to `max_ulps` units in the last place of the larger value
+    fn ulps_eq(&self, other: &Self, epsilon: Self, max_ulps: u32) -> bool {
+        let max_relative = Self::EPSILON * Self::finite(Big::from(max_ulps));
+        self.relative_eq(other, epsilon, max_relative)
+    }
+}
+
+impl SimdValue for BigFloat {
+    type Element = Self;
+    type SimdBool = bool;
+
+    #[inline(always)]
+    fn lanes() -> usize {
+        1
+    }
+    #[inline(always)]
+    fn splat(val: Self::Element) -> Self {
+        val
+    }
+    #[inline(always)]
+    fn extract(&self, _: usize) -> Self::Element {
+        self.clone()
+    }
+    #[inline(always)]
+    unsafe fn extract_unchecked(&self, _: usize) -> Self::Element {
+        self.clone()
+    }
+    #[inline(always)]
+    fn replace(&mut self, _: usize, val: Self::Element) {
+        *self = val
+    }
+    #[inline(always)]
+    unsafe fn replace_unchecked(&mut self, _: usize, val: Self::Element) {
+        *self = val
+    }
+    #[inline(always)]
+    fn select(self, cond: Self::SimdBool, other: Self) -> Self {
+        if cond {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl Field for BigFloat {}
+
+impl ComplexField for BigFloat {
+    type RealField = Self;
+
+    fn from_real(re: Self) -> Self {
+        re
+    }
+    fn real(self) -> Self {
+        self
+    }
+    fn imaginary(self) -> Self {
+        Self::zero()
+    }
+    fn modulus(self) -> Self {
+        Signed::abs(&self)
+    }
+    fn modulus_squared(self) -> Self {
+        self.clone() * self
+    }
+    fn argument(self) -> Self {
+        if self >= Self::zero() {
+            Self::zero()
+        } else {
+            Self::pi()
+        }
+    }
+    fn norm1(self) -> Self {
+        Signed::abs(&self)
+    }
+    fn scale(self, factor: Self) -> Self {
+        self * factor
+    }
+    fn unscale(self, factor: Self) -> Self {
+        self / factor
+    }
+    fn floor(self) -> Self {
+        self.map(|x| Some(x.floor()), f64::floor)
+    }
+    fn ceil(self) -> Self {
+        self.map(|x| Some(x.ceil()), f64::ceil)
+    }
+    fn round(self) -> Self {
+        self.map(|x| Some(x.round()), f64::round)
+    }
+    fn trunc(self) -> Self {
+        self.map(|x| Some(x.trunc()), f64::trunc)
+    }
+    fn fract(self) -> Self {
+        self.map(|x| Some(x.fract()), f64::fract)
+    }
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+    fn abs(self) -> Self {
+        Signed::abs(&self)
+    }
+    fn hypot(self, other: Self) -> Self {
+        (self.clone() * self + other.clone() * other).sqrt()
+    }
+    fn recip(self) -> Self {
+        Self::one() / self
+    }
+    fn conjugate(self) -> Self {
+        self
+    }
+    fn sin(self) -> Self {
+        self.map(|x| Some(sin_cos(x).0), f64::sin)
+    }
+    fn cos(self) -> Self {
+        self.map(|x| Some(sin_cos(x).1), f64::cos)
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        match &self.0 {
+            Inner::Finite(x) => {
+                let (sin, cos) = sin_cos(x);
+                (Self::finite(sin), Self::finite(cos))
+            }
+            Inner::NonFinite(x) => (Self::from_f64(x.sin()), Self::from_f64(x.cos())),
+        }
+    }
+    fn tan(self) -> Self {
+        let (sin, cos) = self.sin_cos();
+        sin / cos
+    }
+    fn asin(self) -> Self {
+        let one = guarded(&Big::ONE);
+        self.map(
+            |x| (abs(x) < one).then(|| atan(&(x / (&one - x * x).sqrt()))),
+            f64::asin,
+        )
+    }
+    fn acos(self) -> Self {
+        Self::frac_pi_2() - self.asin()
+    }
+    fn atan(self) -> Self {
+        self.map(|x| Some(atan(x)), f64::atan)
+    }
+    fn sinh(self) -> Self {
+        self.map(
+            |x| Some((x.exp_m1() - (-x).exp_m1()) / Big::from(2u8)),
+            f64::sinh,
+        )
+    }
+    fn cosh(self) -> Self {
+        self.map(|x| Some((x.exp() + (-x).exp()) / Big::from(2u8)), f64::cosh)
+    }
+    fn tanh(self) -> Self {
+        self.map(
+            |x| {
+                let e = (x * Big::from(2u8)).exp_m1();
+                Some(&e / (&e + Big::from(2u8)))
+            },
+            f64::tanh,
+        )
+    }
+    fn asinh(self) -> Self {
+        self.map(
+            |x| {
+                let one = guarded(&Big::ONE);
+                let a = abs(x);
+                let a2 = &a * &a;
+                let y = (&a + &a2 / (&one + (&one + &a2).sqrt())).ln_1p();
+                Some(y * x.signum())
+            },
+            f64::asinh,
+        )
+    }
+    fn acosh(self) -> Self {
+        self.map(
+            |x| (*x >= Big::ONE).then(|| (x + (x * x - Big::ONE).sqrt()).ln()),
+            f64::acosh,
+        )
+    }
+    fn atanh(self) -> Self {
+        self.map(
+            |x| {
+                (abs(x) < Big::ONE)
+                    .then(|| (x * Big::from(2u8) / (Big::ONE - x)).ln_1p() / Big::from(2u8))
+            },
+            f64::atanh,
+        )
+    }
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+    fn log2(self) -> Self {
+        self.ln() / Self::ln_2()
+    }
+    fn log10(self) -> Self {
+        self.ln() / Self::ln_10()
+    }
+    fn ln(self) -> Self {
+        self.map(|x| is_positive(x).then(|| x.ln()), f64::ln)
+    }
+    fn ln_1p(self) -> Self {
+        self.map(|x| (*x > -Big::ONE).then(|| x.ln_1p()), f64::ln_1p)
+    }
+    fn sqrt(self) -> Self {
+        self.map(
+            |x| (x.sign() == Sign::Positive).then(|| x.sqrt()),
+            f64::sqrt,
+        )
+    }
+    fn exp(self) -> Self {
+        self.map(|x| Some(x.exp()), f64::exp)
+    }
+    fn exp2(self) -> Self {
+        (self * Self::ln_2()).exp()
+    }
+    fn exp_m1(self) -> Self {
+        self.map(|x| Some(x.exp_m1()), f64::exp_m1)
+    }
+    fn powi(self, n: i32) -> Self {
+        self.map(
+            |x| (n >= 0 || !x.repr().is_zero()).then(|| x.powi(n.into())),
+            |x| x.powi(n),
+        )
+    }
+    fn powf(self, n: Self) -> Self {
+        self.zip(
+            n,
+            |x, n| {
+                if is_positive(x) {
+                    Some(x.powf(n))
+                } else if !x.repr().is_zero() && n.fract().repr().is_zero() {
+                    Some(x.powi(n.to_int().value()))
+                } else {
+                    None
+                }
+            },
+            f64::powf,
+        )
+    }
+    fn powc(self, n: Self) -> Self {
+        self.powf(n)
+    }
+    fn cbrt(self) -> Self {
+        self.map(
+            |x| {
+                Some(if x.repr().is_zero() {
+                    x.clone()
+                } else {
+                    (abs(x).ln() / Big::from(3u8)).exp() * x.signum()
+                })
+            },
+            f64::cbrt,
+        )
+    }
+    fn is_finite(&self) -> bool {
+        matches!(self.0, Inner::Finite(_))
+    }
+    fn try_sqrt(self) -> Option<Self> {
+        if self >= Self::zero() {
+            Some(self.sqrt())
+        } else {
+            None
+        }
+    }
+}
+
+impl RealField for BigFloat {
+    fn is_sign_positive(&self) -> bool {
+        match &self.0 {
+            Inner::Finite(x) => x.sign() == Sign::Positive,
+            Inner::NonFinite(x) => x.is_sign_positive(),
+        }
+    }
+    fn is_sign_negative(&self) -> bool {
+        !self.is_sign_positive()
+    }
+    fn copysign(self, sign: Self) -> Self {
+        let abs = Signed::abs(&self);
+        if sign.is_sign_positive() {
+            abs
+        } else {
+            -abs
+        }
+    }
+    fn max(self, other: Self) -> Self {
+        if other > self || Scalar::is_nan(&self) {
+            other
+        } else {
+            self
+        }
+    }
+    fn min(self, other: Self) -> Self {
+        if other < self || Scalar::is_nan(&self) {
+            other
+        } else {
+            self
+        }
+    }
+    fn clamp(self, min: Self, max: Self) -> Self {
+        assert!(min <= max);
+        if self < min {
+            min
+        } else if self > max {
+            max
+        } else {
+            self
+        }
+    }
+    fn atan2(self, other: Self) -> Self {
+        if !self.is_finite() || !other.is_finite() {
+            return Self::from_f64(self.to_f64().atan2(other.to_f64()));
+        }
+        if other.is_zero() {
+            match self.partial_cmp(&Self::zero()) {
+                Some(Ordering::Greater) => Self::frac_pi_2(),
+                Some(Ordering::Less) => -Self::frac_pi_2(),
+                _ => Self::zero(),
+            }
+        } else if other.is_positive_finite() {
+            (self / other).atan()
+        } else if self.is_sign_positive() {
+            (self / other).atan() + Self::pi()
+        } else {
+            (self / other).atan() - Self::pi()
+        }
+    }
+
+    fn min_value() -> Option<Self> {
+        None
+    }
+    fn max_value() -> Option<Self> {
+        None
+    }
+
+    fn pi() -> Self {
+        Self::finite(pi().clone())
+    }
+    fn two_pi() -> Self {
+        Self::finite(pi() * Big::from(2u8))
+    }
+    fn frac_pi_2() -> Self {
+        Self::finite(pi() / Big::from(2u8))
+    }
+    fn frac_pi_3() -> Self {
+        Self::finite(pi() / Big::from(3u8))
+    }
+    fn frac_pi_4() -> Self {
+        Self::finite(pi() / Big::from(4u8))
+    }
+    fn frac_pi_6() -> Self {
+        Self::finite(pi() / Big::from(6u8))
+    }
+    fn frac_pi_8() -> Self {
+        Self::finite(pi() / Big::from(8u8))
+    }
+    fn frac_1_pi() -> Self {
+        Self::finite(guarded(&Big::ONE) / pi())
+    }
+    fn frac_2_pi() -> Self {
+        Self::finite(guarded(&Big::from(2u8)) / pi())
+    }
+    fn frac_2_sqrt_pi() -> Self {
+        Self::finite(guarded(&Big::from(2u8)) / pi().sqrt())
+    }
+
+    fn e() -> Self {
+        Self::one().exp()
+    }
+    fn log2_e() -> Self {
+        Self::one() / Self::ln_2()
+    }
+    fn log10_e() -> Self {
+        Self::one() / Self::ln_10()
+    }
+    fn ln_2() -> Self {
+        Self::finite(Big::from(2u8)).ln()
+    }
+    fn ln_10() -> Self {
+        Self::finite(Big::from(10u8)).ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> BigFloat {
+        s.parse().unwrap()
+    }
+
+    fn assert_close(x: BigFloat, expect: &str) {
+        let expect = parse(expect);
+        let tol = parse("1e-70") * RealField::max(BigFloat::one(), expect.clone().abs());
+        assert!(
+            (x.clone() - expect.clone()).abs() < tol,
+            "{} != {}",
+            x,
+            expect
+        );
+    }
+
+    #[test]
+    fn test_big_float_functions() {
+        let pi =
+            "3.14159265358979323846264338327950288419716939937510582097494459230781640628620899";
+        let e =
+            "2.71828182845904523536028747135266249775724709369995957496696762772407663035354759";
+        assert_close(BigFloat::pi(), pi);
+        assert_close(BigFloat::e(), e);
+        assert_close(
+            BigFloat::from_f64(2.0).sqrt(),
+            "1.41421356237309504880168872420969807856967187537694807317667973799073247846210704",
+        );
+        assert_close(BigFloat::one().atan() * BigFloat::from_f64(4.0), pi);
+        assert_close(
+            BigFloat::one().sin(),
+            "0.841470984807896506652502321630298999622563060798371065672751709991910404391239669",
+        );
+        assert_close(
+            BigFloat::from_f64(10.0).cos(),
+            "-0.839071529076452452258863947824064834519930165133168546835953731048792586866270768",
+        );
+        assert_close(BigFloat::from_f64(0.5).asin() * BigFloat::from_f64(6.0), pi);
+        assert_close(
+            BigFloat::from_f64(-1.0).atan2(BigFloat::from_f64(-1.0)),
+            "-2.35619449019234492884698253745962716314787704953132936573120844423086230471465675",
+        );
+        assert_close(BigFloat::from_f64(8.0).cbrt(), "2");
+        assert_close(
+            BigFloat::from_f64(2.0).powf(BigFloat::from_f64(0.5)),
+            "1.41421356237309504880168872420969807856967187537694807317667973799073247846210704",
+        );
+        assert_close(BigFloat::from_f64(-2.0).powi(-3), "-0.125");
+    }
+
+    #[test]
+    fn test_big_float_non_finite() {
+        let zero = BigFloat::zero();
+        let one = BigFloat::one();
+        assert_eq!(one.clone() / zero.clone(), BigFloat::INFINITY);
+        assert!((zero.clone() / zero.clone()).is_nan());
+        assert!(BigFloat::from_f64(-1.0).sqrt().is_nan());
+        assert_eq!(zero.clone().ln(), -BigFloat::INFINITY);
+        assert_eq!(one.clone() / BigFloat::INFINITY, zero);
+        assert!(BigFloat::INFINITY > BigFloat::from_f64(f64::MAX));
+        assert_eq!(BigFloat::NAN.partial_cmp(&one), None);
+        assert_eq!(BigFloat::cast(0.1).to_f64(), 0.1);
+        assert!(BigFloat::EPSILON > zero);
+        assert_eq!(
+            one.clone() + BigFloat::EPSILON / BigFloat::from_f64(4.0),
+            one
+        );
+        assert!(one.clone() + BigFloat::EPSILON > one);
+    }
+}
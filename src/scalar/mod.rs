@@ -1,57 +1,106 @@
 use std::{
-    fmt::Display,
+    cmp::Ordering,
     ops::{Add, AddAssign, Mul, MulAssign, Sub, SubAssign},
 };
 
-use nalgebra::{ClosedAdd, ClosedDiv, ClosedMul, ClosedSub, ComplexField, SimdRealField};
-use num_traits::{Pow, Signed};
+use nalgebra::RealField;
+use num_traits::Pow;
 
 use crate::vector::VectorView;
 
-pub trait Scalar:
-    nalgebra::Scalar
-    + faer::Entity
-    + faer::ComplexField
-    + faer::SimpleEntity
-    + faer::RealField
-    + From<f64>
-    + Display
-    + SimdRealField
-    + ComplexField<RealField = Self>
-    + Copy
-    + ClosedSub
-    + From<f64>
-    + ClosedMul
-    + ClosedDiv
-    + ClosedAdd
-    + Signed
-    + Into<f64>
-    + PartialOrd
-    + Pow<Self, Output = Self>
-    + Pow<i32, Output = Self>
-{
+#[cfg(feature = "dashu")]
+mod big_float;
+#[cfg(feature = "dashu")]
+pub use big_float::{BigFloat, BIG_FLOAT_PRECISION};
+
+/// Scalar type used by the solvers. The numerical requirements are given by [RealField] (from [simba](https://docs.rs/simba)),
+/// so any generic code in the solvers can rely on the usual real field operations (`sqrt`, `abs`, `max`, comparisons, etc.).
+/// Constants and solver options are converted from `f64` using [Self::cast], which by default uses the conversion from
+/// `f64` that every [RealField] provides, so types beyond `f64` and `f32` only need to implement [RealField], [Pow] and
+/// the constants below. The requirements of each matrix and vector backend are given on its impls (e.g. `FaerScalar`, which is
+/// only defined with the `faer` feature, so a scalar type does not need to implement the faer traits if this feature is disabled).
+pub trait Scalar: RealField + Pow<Self, Output = Self> + Pow<i32, Output = Self> {
     const EPSILON: Self;
     const INFINITY: Self;
     const NAN: Self;
-    fn is_nan(self) -> bool;
+    #[allow(clippy::eq_op)]
+    fn is_nan(&self) -> bool {
+        self != self
+    }
+    /// Convert an `f64` (e.g. a constant in a method or a solver option) to the scalar type
+    #[inline]
+    fn cast(x: f64) -> Self {
+        nalgebra::convert(x)
+    }
+    /// Convert the scalar to an `f64` (e.g. for statistics and step size heuristics), which may lose precision
+    #[inline]
+    fn to_f64(&self) -> f64 {
+        nalgebra::convert_unchecked(self.clone())
+    }
+    /// A total ordering of the scalars (as [f64::total_cmp]), so that values that may be NaN can be sorted without panicking.
+    /// The default implementation orders NaN after all other values.
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        match (Scalar::is_nan(self), Scalar::is_nan(other)) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            (false, false) => self.partial_cmp(other).unwrap(),
+        }
+    }
 }
 
+/// Scalar types that can be used as the element type of the faer matrices and vectors (requires the `faer` feature).
+#[cfg(feature = "faer")]
+pub trait FaerScalar: Scalar + faer::RealField + faer::SimpleEntity {}
+
+#[cfg(feature = "faer")]
+impl<T: Scalar + faer::RealField + faer::SimpleEntity> FaerScalar for T {}
+
 pub type IndexType = usize;
 
 impl Scalar for f64 {
     const EPSILON: Self = f64::EPSILON;
     const INFINITY: Self = f64::INFINITY;
     const NAN: Self = f64::NAN;
-    fn is_nan(self) -> bool {
-        self.is_nan()
+    fn is_nan(&self) -> bool {
+        f64::is_nan(*self)
+    }
+    fn cast(x: f64) -> Self {
+        x
+    }
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
     }
 }
 
+impl Scalar for f32 {
+    const EPSILON: Self = f32::EPSILON;
+    const INFINITY: Self = f32::INFINITY;
+    const NAN: Self = f32::NAN;
+    fn is_nan(&self) -> bool {
+        f32::is_nan(*self)
+    }
+    fn cast(x: f64) -> Self {
+        x as f32
+    }
+    fn to_f64(&self) -> f64 {
+        f64::from(*self)
+    }
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+}
+
+#[cfg(feature = "faer")]
 impl<T: Scalar> From<faer::Scale<T>> for Scale<T> {
     fn from(s: faer::Scale<T>) -> Self {
         Scale(s.value())
     }
 }
+#[cfg(feature = "faer")]
 impl<T: Scalar> From<Scale<T>> for faer::Scale<T> {
     fn from(s: Scale<T>) -> Self {
         faer::Scale(s.value())
@@ -73,6 +122,16 @@ pub fn scale<E: Scalar>(value: E) -> Scale<E> {
     Scale(value)
 }
 
+/// Compensated (Kahan) summation, returns `sum + x` and updates `compensation`, which holds the
+/// accumulated roundoff error of `sum` (i.e. the exact sum is approximately `sum - compensation`).
+#[inline]
+pub fn compensated_add<E: Scalar>(sum: E, x: E, compensation: &mut E) -> E {
+    let y = x - compensation.clone();
+    let new_sum = sum.clone() + y.clone();
+    *compensation = (new_sum.clone() - sum) - y;
+    new_sum
+}
+
 macro_rules! impl_bin_op {
     ($trait:ident, $method:ident, $operator:tt) => {
         impl<E: Scalar> $trait<Scale<E>> for Scale<E> {
@@ -91,7 +150,7 @@ macro_rules! impl_assign_bin_op {
         impl<E: Scalar> $trait<Scale<E>> for Scale<E> {
             #[inline]
             fn $method(&mut self, rhs: Scale<E>) {
-                self.0 = self.0 $operator rhs.0
+                self.0 = self.0.clone() $operator rhs.0
             }
         }
     };
@@ -124,3 +183,31 @@ impl<V: VectorView<'static, T = E>, E: Scalar> Mul<V> for Scale<E> {
 fn test_scale() {
     assert_eq!(scale(2.0) * scale(3.0), scale(6.0));
 }
+
+#[test]
+fn test_scalar_is_real_field() {
+    fn check<T: Scalar>() {
+        assert_eq!(T::cast(4.0).sqrt(), T::cast(2.0));
+        assert_eq!(RealField::max(T::cast(1.0), T::cast(2.0)), T::cast(2.0));
+        assert!(T::NAN.is_nan());
+        assert!(!T::INFINITY.is_nan());
+    }
+    check::<f64>();
+    check::<f32>();
+}
+
+#[test]
+fn test_compensated_add() {
+    let n = 1_000_000;
+    let h = 0.1;
+    let mut t = 0.0;
+    let mut t_naive = 0.0;
+    let mut compensation = 0.0;
+    for _ in 0..n {
+        t = compensated_add(t, h, &mut compensation);
+        t_naive += h;
+    }
+    let exact = n as f64 * h;
+    assert!((t - exact).abs() < (t_naive - exact).abs());
+    assert!((t - exact).abs() <= f64::EPSILON * exact);
+}
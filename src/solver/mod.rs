@@ -1,8 +1,10 @@
 use std::rc::Rc;
 
 use crate::{
+    linear_solver::kind::LinearSolverKind,
+    nonlinear_solver::convergence::ConvergenceCriterion,
     op::{linearise::LinearisedOp, Op},
-    IndexType, NonLinearOp, OdeEquations, OdeSolverProblem,
+    Constraints, IndexType, JacobianUpdatePolicy, NonLinearOp, OdeEquations, OdeSolverProblem,
 };
 
 pub struct SolverStatistics {
@@ -15,6 +17,24 @@ pub struct SolverProblem<C: Op> {
     pub f: Rc<C>,
     pub atol: Rc<C::V>,
     pub rtol: C::T,
+    /// Sizes of the diagonal blocks of the jacobian, if it is block-diagonal (see [OdeSolverProblem::block_sizes])
+    pub block_sizes: Option<Vec<IndexType>>,
+    /// Whether the linearised operator is symmetric (see [OdeEquations::is_symmetric])
+    pub symmetric: bool,
+    /// Size of the diagonal blocks of block preconditioners for iterative linear solvers (see [crate::Preconditioner::set_block_size])
+    pub preconditioner_block_size: Option<IndexType>,
+    /// The linear solver that the default linear solvers build (see [crate::SelectedLinearSolver] and [crate::OdeBuilder::linear_solver])
+    pub linear_solver: Option<LinearSolverKind>,
+    /// When the jacobian is re-evaluated (see [JacobianUpdatePolicy]), with [JacobianUpdatePolicy::EveryIteration] the
+    /// [crate::NewtonNonlinearSolver] refactorises at every iteration. Set by [crate::Bdf] from [crate::OdeBuilder::jacobian_update],
+    /// otherwise [JacobianUpdatePolicy::OnConvergenceFailure].
+    pub jacobian_update: JacobianUpdatePolicy<C::T>,
+    /// Bounds that the [crate::NewtonNonlinearSolver] keeps the solution within (see [Constraints]). Set by [crate::Bdf] from
+    /// [OdeSolverProblem::constraints], otherwise `None`.
+    pub constraints: Option<Rc<Constraints<C::V>>>,
+    /// The test used by the nonlinear solvers to decide whether the iteration has converged (see [ConvergenceCriterion]).
+    /// Set from [crate::OdeBuilder::convergence_criterion] for the nonlinear solves of an ODE solver.
+    pub convergence_criterion: ConvergenceCriterion,
 }
 
 impl<C: Op> Clone for SolverProblem<C> {
@@ -22,14 +42,32 @@ impl<C: Op> Clone for SolverProblem<C> {
         Self {
             f: self.f.clone(),
             atol: self.atol.clone(),
-            rtol: self.rtol,
+            rtol: self.rtol.clone(),
+            block_sizes: self.block_sizes.clone(),
+            symmetric: self.symmetric,
+            preconditioner_block_size: self.preconditioner_block_size,
+            linear_solver: self.linear_solver,
+            jacobian_update: self.jacobian_update.clone(),
+            constraints: self.constraints.clone(),
+            convergence_criterion: self.convergence_criterion,
         }
     }
 }
 
 impl<C: Op> SolverProblem<C> {
     pub fn new(f: Rc<C>, atol: Rc<C::V>, rtol: C::T) -> Self {
-        Self { f, rtol, atol }
+        Self {
+            f,
+            rtol,
+            atol,
+            block_sizes: None,
+            symmetric: false,
+            preconditioner_block_size: None,
+            linear_solver: None,
+            jacobian_update: JacobianUpdatePolicy::OnConvergenceFailure,
+            constraints: None,
+            convergence_criterion: ConvergenceCriterion::default(),
+        }
     }
     pub fn new_from_ode_problem(
         f: Rc<C>,
@@ -37,8 +75,15 @@ impl<C: Op> SolverProblem<C> {
     ) -> Self {
         Self {
             f,
-            rtol: other.rtol,
+            rtol: other.rtol.clone(),
             atol: other.atol.clone(),
+            block_sizes: other.block_sizes.clone(),
+            symmetric: other.eqn.is_symmetric(),
+            preconditioner_block_size: other.options.preconditioner_block_size,
+            linear_solver: other.options.linear_solver,
+            jacobian_update: JacobianUpdatePolicy::OnConvergenceFailure,
+            constraints: None,
+            convergence_criterion: other.options.convergence_criterion,
         }
     }
     pub fn new_from_problem<C2>(f: Rc<C>, other: &SolverProblem<C2>) -> Self
@@ -47,8 +92,15 @@ impl<C: Op> SolverProblem<C> {
     {
         Self {
             f,
-            rtol: other.rtol,
+            rtol: other.rtol.clone(),
             atol: other.atol.clone(),
+            block_sizes: other.block_sizes.clone(),
+            symmetric: other.symmetric,
+            preconditioner_block_size: other.preconditioner_block_size,
+            linear_solver: other.linear_solver,
+            jacobian_update: other.jacobian_update.clone(),
+            constraints: other.constraints.clone(),
+            convergence_criterion: other.convergence_criterion,
         }
     }
 }
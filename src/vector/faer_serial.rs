@@ -1,20 +1,20 @@
-use std::ops::{Div, Mul, MulAssign};
+use std::ops::{Div, Mul, MulAssign, Range};
 
 use faer::{unzipped, zipped, Col, ColMut, ColRef, Mat};
 
-use crate::{scalar::Scale, IndexType, Scalar, Vector};
+use crate::{scalar::Scale, FaerScalar, IndexType, Vector};
 
 use crate::{VectorCommon, VectorIndex, VectorView, VectorViewMut};
 
 use super::DefaultDenseMatrix;
 
-impl<T: Scalar> DefaultDenseMatrix for Col<T> {
+impl<T: FaerScalar> DefaultDenseMatrix for Col<T> {
     type M = Mat<T>;
 }
 
 macro_rules! impl_op_for_faer_struct {
     ($struct:ident, $trait_name:ident, $func_name:ident) => {
-        impl<'a, T: Scalar> $trait_name<Scale<T>> for $struct<'a, T> {
+        impl<'a, T: FaerScalar> $trait_name<Scale<T>> for $struct<'a, T> {
             type Output = Col<T>;
 
             fn $func_name(self, rhs: Scale<T>) -> Self::Output {
@@ -30,7 +30,7 @@ impl_op_for_faer_struct!(ColMut, Mul, mul);
 
 macro_rules! impl_mul_scale {
     ($col_type:ty) => {
-        impl<T: Scalar> Mul<Scale<T>> for $col_type {
+        impl<T: FaerScalar> Mul<Scale<T>> for $col_type {
             type Output = Col<T>;
             fn mul(self, rhs: Scale<T>) -> Self::Output {
                 let scale: faer::Scale<T> = rhs.into();
@@ -38,7 +38,7 @@ macro_rules! impl_mul_scale {
             }
         }
 
-        impl<T: Scalar> Mul<Scale<T>> for &$col_type {
+        impl<T: FaerScalar> Mul<Scale<T>> for &$col_type {
             type Output = Col<T>;
             fn mul(self, rhs: Scale<T>) -> Self::Output {
                 let scale: faer::Scale<T> = rhs.into();
@@ -50,7 +50,7 @@ macro_rules! impl_mul_scale {
 
 macro_rules! impl_div_scale {
     ($col_type:ty) => {
-        impl<'a, T: Scalar> Div<Scale<T>> for $col_type {
+        impl<'a, T: FaerScalar> Div<Scale<T>> for $col_type {
             type Output = Col<T>;
             fn div(self, rhs: Scale<T>) -> Self::Output {
                 zipped!(self).map(|unzipped!(xi)| *xi / rhs.value())
@@ -64,7 +64,7 @@ impl_div_scale!(faer::Col<T>);
 
 macro_rules! impl_mul_assign_scale {
     ($col_type:ty) => {
-        impl<'a, T: Scalar> MulAssign<Scale<T>> for $col_type {
+        impl<'a, T: FaerScalar> MulAssign<Scale<T>> for $col_type {
             fn mul_assign(&mut self, rhs: Scale<T>) {
                 let scale: faer::Scale<T> = rhs.into();
                 *self *= scale;
@@ -76,7 +76,7 @@ macro_rules! impl_mul_assign_scale {
 impl_mul_assign_scale!(ColMut<'a, T>);
 impl_mul_assign_scale!(Col<T>);
 
-impl<T: Scalar> Vector for Col<T> {
+impl<T: FaerScalar> Vector for Col<T> {
     type View<'a> = ColRef<'a, T>;
     type ViewMut<'a> = ColMut<'a, T>;
     type Index = Vec<IndexType>;
@@ -86,6 +86,11 @@ impl<T: Scalar> Vector for Col<T> {
     fn norm(&self) -> T {
         self.norm_l2()
     }
+    fn dot(&self, other: &Self) -> T {
+        let mut acc = T::zero();
+        zipped!(self, other).for_each(|unzipped!(xi, yi)| acc += *xi * *yi);
+        acc
+    }
     fn squared_norm(&self, y: &Self, atol: &Self, rtol: Self::T) -> Self::T {
         let mut acc = T::zero();
         if y.len() != self.len() || y.len() != atol.len() {
@@ -97,7 +102,7 @@ impl<T: Scalar> Vector for Col<T> {
             let xi = unsafe { self.read_unchecked(i) };
             acc += (xi / (yi.abs() * rtol + ai)).powi(2);
         }
-        acc / Self::T::from(self.len() as f64)
+        acc / Self::T::cast(self.len() as f64)
     }
     fn abs_to(&self, y: &mut Self) {
         zipped!(self, y.as_mut()).for_each(|unzipped!(xi, mut yi)| *yi = xi.faer_abs());
@@ -108,6 +113,15 @@ impl<T: Scalar> Vector for Col<T> {
     fn as_view_mut(&mut self) -> Self::ViewMut<'_> {
         self.as_mut()
     }
+    fn segment(&self, range: Range<IndexType>) -> Self::View<'_> {
+        self.as_ref().subrows(range.start, range.len())
+    }
+    fn segment_mut(&mut self, range: Range<IndexType>) -> Self::ViewMut<'_> {
+        self.as_mut().subrows_mut(range.start, range.len())
+    }
+    fn split_at_mut(&mut self, mid: IndexType) -> (Self::ViewMut<'_>, Self::ViewMut<'_>) {
+        self.as_mut().split_at_mut(mid)
+    }
     fn copy_from(&mut self, other: &Self) {
         self.copy_from(other)
     }
@@ -138,12 +152,58 @@ impl<T: Scalar> Vector for Col<T> {
     fn exp(&self) -> Self {
         zipped!(self).map(|unzipped!(xi)| xi.exp())
     }
+    fn ln(&self) -> Self {
+        zipped!(self).map(|unzipped!(xi)| xi.ln())
+    }
+    fn powf(&self, exponent: T) -> Self {
+        zipped!(self).map(|unzipped!(xi)| xi.powf(exponent))
+    }
+    fn sum(&self) -> T {
+        let mut acc = T::zero();
+        zipped!(self).for_each(|unzipped!(xi)| acc += *xi);
+        acc
+    }
+    fn min(&self) -> T {
+        let mut acc = T::INFINITY;
+        zipped!(self).for_each(|unzipped!(xi)| {
+            if *xi < acc {
+                acc = *xi
+            }
+        });
+        acc
+    }
+    fn max(&self) -> T {
+        let mut acc = -T::INFINITY;
+        zipped!(self).for_each(|unzipped!(xi)| {
+            if *xi > acc {
+                acc = *xi
+            }
+        });
+        acc
+    }
     fn component_mul_assign(&mut self, other: &Self) {
         zipped!(self.as_mut(), other.as_view()).for_each(|unzipped!(mut s, o)| *s *= *o);
     }
     fn component_div_assign(&mut self, other: &Self) {
         zipped!(self.as_mut(), other.as_view()).for_each(|unzipped!(mut s, o)| *s /= *o);
     }
+    fn component_max_assign(&mut self, other: &Self) {
+        zipped!(self.as_mut(), other.as_view()).for_each(|unzipped!(mut s, o)| {
+            if *o > *s {
+                *s = *o
+            }
+        });
+    }
+    fn component_min_assign(&mut self, other: &Self) {
+        zipped!(self.as_mut(), other.as_view()).for_each(|unzipped!(mut s, o)| {
+            if *o < *s {
+                *s = *o
+            }
+        });
+    }
+    fn binary_mask<F: Fn(Self::T) -> bool>(&self, f: F) -> Self {
+        zipped!(self).map(|unzipped!(xi)| if f(*xi) { T::cast(1.0) } else { T::cast(0.0) })
+    }
     fn filter_indices<F: Fn(Self::T) -> bool>(&self, f: F) -> Self::Index {
         let mut indices = vec![];
         for i in 0..self.len() {
@@ -197,7 +257,7 @@ impl VectorIndex for Vec<IndexType> {
 
 macro_rules! impl_vector_common {
     ($vector_type:ty) => {
-        impl<'a, T: Scalar> VectorCommon for $vector_type {
+        impl<'a, T: FaerScalar> VectorCommon for $vector_type {
             type T = T;
         }
     };
@@ -207,7 +267,7 @@ impl_vector_common!(Col<T>);
 impl_vector_common!(ColRef<'a, T>);
 impl_vector_common!(ColMut<'a, T>);
 
-impl<'a, T: Scalar> VectorView<'a> for ColRef<'a, T> {
+impl<'a, T: FaerScalar> VectorView<'a> for ColRef<'a, T> {
     type Owned = Col<T>;
     fn abs_to(&self, y: &mut Self::Owned) {
         zipped!(self, y.as_mut()).for_each(|unzipped!(xi, mut yi)| *yi = xi.faer_abs());
@@ -229,11 +289,11 @@ impl<'a, T: Scalar> VectorView<'a> for ColRef<'a, T> {
             let xi = unsafe { self.read_unchecked(i) };
             acc += (xi / (yi.abs() * rtol + ai)).powi(2);
         }
-        acc / Self::T::from(self.nrows() as f64)
+        acc / Self::T::cast(self.nrows() as f64)
     }
 }
 
-impl<'a, T: Scalar> VectorViewMut<'a> for ColMut<'a, T> {
+impl<'a, T: FaerScalar> VectorViewMut<'a> for ColMut<'a, T> {
     type Owned = Col<T>;
     type View = ColRef<'a, T>;
     fn abs_to(&self, y: &mut Self::Owned) {
@@ -253,6 +313,23 @@ mod tests {
     use super::*;
     use crate::scalar::scale;
 
+    #[test]
+    fn test_segment_and_split() {
+        let mut v = Col::from_vec(vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(v.segment(1..3).into_owned(), Col::from_vec(vec![2.0, 3.0]));
+        let (head, tail) = Vector::split_at(&v, 2);
+        assert_eq!(head.into_owned(), Col::from_vec(vec![1.0, 2.0]));
+        assert_eq!(tail.into_owned(), Col::from_vec(vec![3.0, 4.0, 5.0]));
+
+        let x = Col::from_vec(vec![1.0, 1.0, 1.0, 1.0, 1.0]);
+        let (mut species, mut temperature) = Vector::split_at_mut(&mut v, 4);
+        species += x.segment(0..4);
+        temperature[0] = -1.0;
+        let mut s = v.segment_mut(1..3);
+        s *= scale(2.0);
+        assert_eq!(v, Col::from_vec(vec![2.0, 6.0, 8.0, 5.0, -1.0]));
+    }
+
     #[test]
     fn test_abs() {
         let v = Col::from_vec(vec![1.0, -2.0, 3.0]);
@@ -261,6 +338,33 @@ mod tests {
         assert_eq!(v_abs, Col::from_vec(vec![1.0, 2.0, 3.0]));
     }
 
+    #[test]
+    fn test_component_min_max() {
+        let mut v = Col::from_vec(vec![1.0, -2.0, 3.0]);
+        let lower = Col::from_vec(vec![0.0, -1.0, 0.0]);
+        let upper = Col::from_vec(vec![2.0, 2.0, 2.0]);
+        v.component_clamp_assign(&lower, &upper);
+        assert_eq!(v, Col::from_vec(vec![1.0, -1.0, 2.0]));
+        let mask = v.binary_mask(|x| x > 0.0);
+        assert_eq!(mask, Col::from_vec(vec![1.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn test_reductions_and_elementwise() {
+        let v = Col::from_vec(vec![1.0, -2.0, 4.0]);
+        assert_eq!(Vector::sum(&v), 3.0);
+        assert_eq!(Vector::min(&v), -2.0);
+        assert_eq!(Vector::max(&v), 4.0);
+        assert_eq!(Vector::mean(&v), 1.0);
+        assert_eq!(v.element_iter().collect::<Vec<_>>(), vec![1.0, -2.0, 4.0]);
+        let v = Col::from_vec(vec![1.0, 2.0, 4.0]);
+        Vector::ln(&Vector::exp(&v)).assert_eq_st(&v, 1e-14);
+        assert_eq!(
+            Vector::powf(&v, 0.5),
+            Col::from_vec(vec![1.0, 2.0f64.sqrt(), 2.0])
+        );
+    }
+
     #[test]
     fn test_mult() {
         let v = Col::from_vec(vec![1.0, -2.0, 3.0]);
@@ -278,12 +382,19 @@ mod tests {
         assert_eq!(v, r);
     }
 
+    #[test]
+    fn test_dot() {
+        let v = Col::from_vec(vec![1.0, -2.0, 3.0]);
+        let w = Col::from_vec(vec![2.0, 1.0, 0.5]);
+        assert_eq!(Vector::dot(&v, &w), 1.5);
+    }
+
     #[test]
     fn test_error_norm() {
         let v = Col::from_vec(vec![1.0, -2.0, 3.0]);
         let y = Col::from_vec(vec![1.0, 2.0, 3.0]);
         let atol = Col::from_vec(vec![0.1, 0.2, 0.3]);
-        let rtol = 0.1;
+        let rtol = 0.1_f64;
         let mut tmp = y.clone() * scale(rtol);
         tmp += &atol;
         let mut r = v.clone();
@@ -3,7 +3,7 @@ use crate::scalar::Scale;
 use crate::{IndexType, Scalar};
 use num_traits::Zero;
 use std::fmt::Debug;
-use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Div, Index, IndexMut, Mul, MulAssign, Range, Sub, SubAssign};
 
 #[cfg(feature = "faer")]
 mod faer_serial;
@@ -26,14 +26,14 @@ pub trait VectorCommon: Sized + Debug {
     type T: Scalar;
 }
 
-impl<'a, V> VectorCommon for &'a V
+impl<V> VectorCommon for &V
 where
     V: VectorCommon,
 {
     type T = V::T;
 }
 
-impl<'a, V> VectorCommon for &'a mut V
+impl<V> VectorCommon for &mut V
 where
     V: VectorCommon,
 {
@@ -129,13 +129,43 @@ pub trait Vector:
         Self: 'a;
     type Index: VectorIndex;
     fn norm(&self) -> Self::T;
+    /// The euclidean inner product of `self` and `other`
+    fn dot(&self, other: &Self) -> Self::T;
     fn squared_norm(&self, y: &Self, atol: &Self, rtol: Self::T) -> Self::T;
     fn len(&self) -> IndexType;
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
     fn abs_to(&self, y: &mut Self);
+
+    /// The sum of the elements
+    fn sum(&self) -> Self::T;
+
+    /// The smallest element, or infinity if the vector is empty
+    fn min(&self) -> Self::T;
+
+    /// The largest element, or minus infinity if the vector is empty
+    fn max(&self) -> Self::T;
+
+    /// The mean of the elements (NaN if the vector is empty)
+    fn mean(&self) -> Self::T {
+        self.sum() / Self::T::cast(self.len() as f64)
+    }
+
+    /// An iterator over the values of the elements
+    fn element_iter(&self) -> impl Iterator<Item = Self::T> + '_ {
+        (0..self.len()).map(move |i| self[i].clone())
+    }
+
+    /// Return a vector with the exponential of each element
     fn exp(&self) -> Self;
+
+    /// Return a vector with the natural logarithm of each element
+    fn ln(&self) -> Self;
+
+    /// Return a vector with each element raised to the power `exponent`
+    fn powf(&self, exponent: Self::T) -> Self;
+
     fn from_element(nstates: usize, value: Self::T) -> Self;
     fn zeros(nstates: usize) -> Self {
         Self::from_element(nstates, Self::T::zero())
@@ -143,6 +173,20 @@ pub trait Vector:
     fn fill(&mut self, value: Self::T);
     fn as_view(&self) -> Self::View<'_>;
     fn as_view_mut(&mut self) -> Self::ViewMut<'_>;
+
+    /// A view of the elements in `range`, e.g. one block of a partitioned state, without copying
+    fn segment(&self, range: Range<IndexType>) -> Self::View<'_>;
+
+    /// A mutable view of the elements in `range`, e.g. to write one block of the output of a right-hand side function
+    fn segment_mut(&mut self, range: Range<IndexType>) -> Self::ViewMut<'_>;
+
+    /// Views of the elements before and after index `mid`
+    fn split_at(&self, mid: IndexType) -> (Self::View<'_>, Self::View<'_>) {
+        (self.segment(0..mid), self.segment(mid..self.len()))
+    }
+
+    /// Mutable views of the elements before and after index `mid`
+    fn split_at_mut(&mut self, mid: IndexType) -> (Self::ViewMut<'_>, Self::ViewMut<'_>);
     fn copy_from(&mut self, other: &Self);
     fn copy_from_view(&mut self, other: &Self::View<'_>);
     fn from_vec(vec: Vec<Self::T>) -> Self;
@@ -151,6 +195,22 @@ pub trait Vector:
     fn add_scalar_mut(&mut self, scalar: Self::T);
     fn component_mul_assign(&mut self, other: &Self);
     fn component_div_assign(&mut self, other: &Self);
+
+    /// Set each element of `self` to the maximum of itself and the corresponding element of `other`
+    fn component_max_assign(&mut self, other: &Self);
+
+    /// Set each element of `self` to the minimum of itself and the corresponding element of `other`
+    fn component_min_assign(&mut self, other: &Self);
+
+    /// Clamp each element of `self` to lie between the corresponding elements of `lower` and `upper`
+    fn component_clamp_assign(&mut self, lower: &Self, upper: &Self) {
+        self.component_max_assign(lower);
+        self.component_min_assign(upper);
+    }
+
+    /// Return a vector which is one where `f` is true for the corresponding element of `self`, and zero elsewhere
+    fn binary_mask<F: Fn(Self::T) -> bool>(&self, f: F) -> Self;
+
     fn filter_indices<F: Fn(Self::T) -> bool>(&self, f: F) -> Self::Index;
     fn binary_fold<B, F>(&self, other: &Self, init: B, f: F) -> B
     where
@@ -160,6 +220,8 @@ pub trait Vector:
         result.gather_from(self, indices);
         result
     }
+
+    /// Set the elements at the given indices to `value`, e.g. to zero the algebraic states (see [Self::filter_indices] to create the indices)
     fn assign_at_indices(&mut self, indices: &Self::Index, value: Self::T);
 
     // for i in 0..indices.len():
@@ -174,7 +236,7 @@ pub trait Vector:
     //  self[indices[i]] = value[indices[i]]
     fn copy_from_indices(&mut self, other: &Self, indices: &Self::Index) {
         for i in 0..indices.len() {
-            self[indices[i]] = other[indices[i]];
+            self[indices[i]] = other[indices[i]].clone();
         }
     }
 
@@ -191,7 +253,7 @@ pub trait Vector:
             other.len()
         );
         for i in 0..self.len() {
-            if num_traits::abs(self[i] - other[i]) > tol[i] {
+            if num_traits::abs(self[i].clone() - other[i].clone()) > tol[i] {
                 eprintln!(
                     "Vector element mismatch at index {}: {} != {}",
                     i, self[i], other[i]
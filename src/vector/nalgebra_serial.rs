@@ -1,4 +1,4 @@
-use std::ops::{Div, Mul, MulAssign};
+use std::ops::{Div, Mul, MulAssign, Range};
 
 use nalgebra::{DMatrix, DVector, DVectorView, DVectorViewMut};
 
@@ -71,9 +71,9 @@ impl<'a, T: Scalar> VectorView<'a> for DVectorView<'a, T> {
             let yi = unsafe { y.get_unchecked(i) };
             let ai = unsafe { atol.get_unchecked(i) };
             let xi = unsafe { self.get_unchecked(i) };
-            acc += (*xi / (yi.abs() * rtol + *ai)).powi(2);
+            acc += (xi.clone() / (yi.abs() * rtol.clone() + ai.clone())).powi(2);
         }
-        acc / Self::T::from(self.len() as f64)
+        acc / Self::T::cast(self.len() as f64)
     }
 }
 
@@ -140,6 +140,9 @@ impl<T: Scalar> Vector for DVector<T> {
     fn norm(&self) -> Self::T {
         self.norm()
     }
+    fn dot(&self, other: &Self) -> Self::T {
+        self.dot(other)
+    }
     fn squared_norm(&self, y: &Self, atol: &Self, rtol: Self::T) -> Self::T {
         let mut acc = T::zero();
         if y.len() != self.len() || y.len() != atol.len() {
@@ -149,9 +152,9 @@ impl<T: Scalar> Vector for DVector<T> {
             let yi = unsafe { y.get_unchecked(i) };
             let ai = unsafe { atol.get_unchecked(i) };
             let xi = unsafe { self.get_unchecked(i) };
-            acc += (*xi / (yi.abs() * rtol + *ai)).powi(2);
+            acc += (xi.clone() / (yi.abs() * rtol.clone() + ai.clone())).powi(2);
         }
-        acc / Self::T::from(self.len() as f64)
+        acc / Self::T::cast(self.len() as f64)
     }
     fn abs_to(&self, y: &mut Self) {
         y.zip_apply(self, |y, x| *y = x.abs());
@@ -165,12 +168,44 @@ impl<T: Scalar> Vector for DVector<T> {
     fn as_view_mut(&mut self) -> Self::ViewMut<'_> {
         self.as_view_mut()
     }
+    fn segment(&self, range: Range<IndexType>) -> Self::View<'_> {
+        self.rows_range(range)
+    }
+    fn segment_mut(&mut self, range: Range<IndexType>) -> Self::ViewMut<'_> {
+        self.rows_range_mut(range)
+    }
+    fn split_at_mut(&mut self, mid: IndexType) -> (Self::ViewMut<'_>, Self::ViewMut<'_>) {
+        let n = self.len();
+        self.rows_range_pair_mut(0..mid, mid..n)
+    }
     fn copy_from(&mut self, other: &Self) {
         self.copy_from(other);
     }
     fn exp(&self) -> Self {
         self.map(|x| x.exp())
     }
+    fn ln(&self) -> Self {
+        self.map(|x| x.ln())
+    }
+    fn powf(&self, exponent: T) -> Self {
+        self.map(|x| x.powf(exponent.clone()))
+    }
+    fn sum(&self) -> T {
+        self.iter().fold(T::zero(), |acc, x| acc + x.clone())
+    }
+    fn min(&self) -> T {
+        self.iter()
+            .fold(T::INFINITY, |acc, x| if *x < acc { x.clone() } else { acc })
+    }
+    fn max(&self) -> T {
+        self.iter().fold(
+            -T::INFINITY,
+            |acc, x| if *x > acc { x.clone() } else { acc },
+        )
+    }
+    fn element_iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.iter().cloned()
+    }
     fn copy_from_view(&mut self, other: &Self::View<'_>) {
         self.copy_from(other);
     }
@@ -198,10 +233,27 @@ impl<T: Scalar> Vector for DVector<T> {
     fn component_mul_assign(&mut self, other: &Self) {
         self.component_mul_assign(other);
     }
+    fn component_max_assign(&mut self, other: &Self) {
+        self.zip_apply(other, |s, o| {
+            if o > *s {
+                *s = o
+            }
+        });
+    }
+    fn component_min_assign(&mut self, other: &Self) {
+        self.zip_apply(other, |s, o| {
+            if o < *s {
+                *s = o
+            }
+        });
+    }
+    fn binary_mask<F: Fn(T) -> bool>(&self, f: F) -> Self {
+        self.map(|x| if f(x) { T::one() } else { T::zero() })
+    }
     fn filter_indices<F: Fn(T) -> bool>(&self, f: F) -> Self::Index {
         let mut indices = vec![];
-        for (i, &x) in self.iter().enumerate() {
-            if f(x) {
+        for (i, x) in self.iter().enumerate() {
+            if f(x.clone()) {
                 indices.push(i as IndexType);
             }
         }
@@ -209,17 +261,17 @@ impl<T: Scalar> Vector for DVector<T> {
     }
     fn gather_from(&mut self, other: &Self, indices: &Self::Index) {
         for (i, &index) in indices.iter().enumerate() {
-            self[i] = other[index];
+            self[i] = other[index].clone();
         }
     }
     fn scatter_from(&mut self, other: &Self, indices: &Self::Index) {
         for (i, &index) in indices.iter().enumerate() {
-            self[index] = other[i];
+            self[index] = other[i].clone();
         }
     }
     fn assign_at_indices(&mut self, indices: &Self::Index, value: Self::T) {
         for &index in indices.iter() {
-            self[index] = value;
+            self[index] = value.clone();
         }
     }
     fn binary_fold<B, F>(&self, other: &Self, init: B, f: F) -> B
@@ -228,7 +280,7 @@ impl<T: Scalar> Vector for DVector<T> {
     {
         let mut acc = init;
         for (i, (x, y)) in self.iter().zip(other.iter()).enumerate() {
-            acc = f(acc, *x, *y, i);
+            acc = f(acc, x.clone(), y.clone(), i);
         }
         acc
     }
@@ -238,6 +290,28 @@ impl<T: Scalar> Vector for DVector<T> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scale;
+
+    #[test]
+    fn test_segment_and_split() {
+        let mut v = DVector::from_vec(vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let s = Vector::segment(&v, 1..3);
+        assert_eq!(s.len(), 2);
+        assert_eq!(s[0], 2.0);
+        assert_eq!(s.into_owned(), DVector::from_vec(vec![2.0, 3.0]));
+        let (head, tail) = Vector::split_at(&v, 2);
+        assert_eq!(head.into_owned(), DVector::from_vec(vec![1.0, 2.0]));
+        assert_eq!(tail.into_owned(), DVector::from_vec(vec![3.0, 4.0, 5.0]));
+
+        // write each block of a partitioned state through its own view
+        let x = DVector::from_vec(vec![1.0, 1.0, 1.0, 1.0, 1.0]);
+        let (mut species, mut temperature) = Vector::split_at_mut(&mut v, 4);
+        species += Vector::segment(&x, 0..4);
+        temperature[0] = -1.0;
+        let mut s = Vector::segment_mut(&mut v, 1..3);
+        s *= scale(2.0);
+        assert_eq!(v, DVector::from_vec(vec![2.0, 6.0, 8.0, 5.0, -1.0]));
+    }
 
     #[test]
     fn test_abs() {
@@ -246,6 +320,54 @@ mod tests {
         assert_eq!(v_abs, DVector::from_vec(vec![1.0, 2.0, 3.0]));
     }
 
+    #[test]
+    fn test_component_min_max() {
+        let mut v = DVector::from_vec(vec![1.0, -2.0, 3.0]);
+        let lower = DVector::from_vec(vec![0.0, -1.0, 0.0]);
+        let upper = DVector::from_vec(vec![2.0, 2.0, 2.0]);
+        let mut v_max = v.clone();
+        v_max.component_max_assign(&lower);
+        assert_eq!(v_max, DVector::from_vec(vec![1.0, -1.0, 3.0]));
+        let mut v_min = v.clone();
+        v_min.component_min_assign(&upper);
+        assert_eq!(v_min, DVector::from_vec(vec![1.0, -2.0, 2.0]));
+        v.component_clamp_assign(&lower, &upper);
+        assert_eq!(v, DVector::from_vec(vec![1.0, -1.0, 2.0]));
+    }
+
+    #[test]
+    fn test_reductions() {
+        let v = DVector::from_vec(vec![1.0, -2.0, 4.0]);
+        assert_eq!(Vector::sum(&v), 3.0);
+        assert_eq!(Vector::min(&v), -2.0);
+        assert_eq!(Vector::max(&v), 4.0);
+        assert_eq!(Vector::mean(&v), 1.0);
+        assert_eq!(v.element_iter().collect::<Vec<_>>(), vec![1.0, -2.0, 4.0]);
+        let empty = DVector::<f64>::zeros(0);
+        assert_eq!(Vector::min(&empty), f64::INFINITY);
+        assert_eq!(Vector::max(&empty), -f64::INFINITY);
+    }
+
+    #[test]
+    fn test_elementwise() {
+        let v = DVector::from_vec(vec![1.0, 2.0, 4.0]);
+        Vector::ln(&Vector::exp(&v)).assert_eq_st(&v, 1e-14);
+        assert_eq!(
+            v.powf(0.5),
+            DVector::from_vec(vec![1.0, 2.0f64.sqrt(), 2.0])
+        );
+        let mut w = v.clone();
+        w.assign_at_indices(&v.filter_indices(|x| x > 1.5), 0.0);
+        assert_eq!(w, DVector::from_vec(vec![1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_binary_mask() {
+        let v = DVector::from_vec(vec![1.0, -2.0, 3.0]);
+        let mask = v.binary_mask(|x| x > 0.0);
+        assert_eq!(mask, DVector::from_vec(vec![1.0, 0.0, 1.0]));
+    }
+
     #[test]
     fn test_error_norm() {
         let v = DVector::from_vec(vec![1.0, -2.0, 3.0]);
@@ -1,13 +1,16 @@
 use std::ffi::c_void;
 use std::fmt::{Debug, Display, Formatter};
-use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
+use std::marker::PhantomData;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Range, Sub, SubAssign,
+};
 use std::ptr::{addr_of, addr_of_mut};
 use std::{fmt, ptr};
 
 use sundials_sys::{
-    realtype, N_VAbs, N_VAddConst, N_VClone, N_VConst, N_VDestroy, N_VDiv, N_VGetArrayPointer,
-    N_VGetLength_Serial, N_VLinearSum, N_VNew_Serial, N_VProd, N_VScale, N_VWL2Norm_Serial,
-    N_Vector, SUNContext, SUNContext_Create,
+    realtype, N_VAbs, N_VAddConst, N_VClone, N_VConst, N_VDestroy, N_VDiv, N_VDotProd,
+    N_VGetArrayPointer, N_VGetLength_Serial, N_VLinearSum, N_VMake_Serial, N_VNew_Serial, N_VProd,
+    N_VScale, N_VWL2Norm_Serial, N_Vector, SUNContext, SUNContext_Create,
 };
 
 use crate::{scale, IndexType, Scale};
@@ -58,6 +61,25 @@ impl SundialsVector {
     pub fn sundials_vector(&self) -> N_Vector {
         self.nv
     }
+    /// A vector that shares the data of the elements in `range` of `self`, only the vector header is destroyed on drop.
+    fn new_segment(&self, range: Range<IndexType>) -> Self {
+        if range.start > range.end || range.end > self.len() {
+            panic!(
+                "segment {:?} out of range for vector of length {}",
+                range,
+                self.len()
+            );
+        }
+        let ctx = get_suncontext();
+        let nv = unsafe {
+            N_VMake_Serial(
+                range.len() as i64,
+                N_VGetArrayPointer(self.nv).add(range.start),
+                *ctx,
+            )
+        };
+        SundialsVector { nv, owned: true }
+    }
 }
 
 impl Drop for SundialsVector {
@@ -69,7 +91,7 @@ impl Drop for SundialsVector {
 }
 
 #[derive(Debug)]
-pub struct SundialsVectorViewMut<'a>(&'a mut SundialsVector);
+pub struct SundialsVectorViewMut<'a>(SundialsVector, PhantomData<&'a mut SundialsVector>);
 
 impl<'a> SundialsVectorViewMut<'a> {
     fn sundials_vector(&self) -> N_Vector {
@@ -81,7 +103,7 @@ impl<'a> SundialsVectorViewMut<'a> {
 }
 
 #[derive(Debug)]
-pub struct SundialsVectorView<'a>(&'a SundialsVector);
+pub struct SundialsVectorView<'a>(SundialsVector, PhantomData<&'a SundialsVector>);
 
 impl<'a> SundialsVectorView<'a> {
     fn sundials_vector(&self) -> N_Vector {
@@ -453,8 +475,14 @@ impl VectorIndex for SundialsIndexVector {
 }
 
 impl Vector for SundialsVector {
-    type View<'a> = SundialsVectorView<'a> where Self: 'a;
-    type ViewMut<'a> = SundialsVectorViewMut<'a> where Self: 'a;
+    type View<'a>
+        = SundialsVectorView<'a>
+    where
+        Self: 'a;
+    type ViewMut<'a>
+        = SundialsVectorViewMut<'a>
+    where
+        Self: 'a;
     type Index = SundialsIndexVector;
     fn len(&self) -> IndexType {
         unsafe { N_VGetLength_Serial(self.sundials_vector()) as IndexType }
@@ -477,6 +505,9 @@ impl Vector for SundialsVector {
         let ones = SundialsVector::from_element(self.len(), 1.0);
         unsafe { N_VWL2Norm_Serial(self.sundials_vector(), ones.sundials_vector()) }
     }
+    fn dot(&self, other: &Self) -> Self::T {
+        unsafe { N_VDotProd(self.sundials_vector(), other.sundials_vector()) }
+    }
     fn is_empty(&self) -> bool {
         self.len() == 0
     }
@@ -490,10 +521,24 @@ impl Vector for SundialsVector {
         unsafe { N_VAddConst(self.sundials_vector(), scalar, self.sundials_vector()) }
     }
     fn as_view(&self) -> Self::View<'_> {
-        SundialsVectorView(self)
+        SundialsVectorView(SundialsVector::new_not_owned(self.nv), PhantomData)
     }
     fn as_view_mut(&mut self) -> Self::ViewMut<'_> {
-        SundialsVectorViewMut(self)
+        SundialsVectorViewMut(SundialsVector::new_not_owned(self.nv), PhantomData)
+    }
+    fn segment(&self, range: Range<IndexType>) -> Self::View<'_> {
+        SundialsVectorView(self.new_segment(range), PhantomData)
+    }
+    fn segment_mut(&mut self, range: Range<IndexType>) -> Self::ViewMut<'_> {
+        SundialsVectorViewMut(self.new_segment(range), PhantomData)
+    }
+    fn split_at_mut(&mut self, mid: IndexType) -> (Self::ViewMut<'_>, Self::ViewMut<'_>) {
+        // the two segments do not overlap
+        let (head, tail) = (self.new_segment(0..mid), self.new_segment(mid..self.len()));
+        (
+            SundialsVectorViewMut(head, PhantomData),
+            SundialsVectorViewMut(tail, PhantomData),
+        )
     }
     fn axpy(&mut self, alpha: Self::T, x: &Self, beta: Self::T) {
         unsafe {
@@ -535,6 +580,27 @@ impl Vector for SundialsVector {
             )
         };
     }
+    fn component_max_assign(&mut self, other: &Self) {
+        for i in 0..self.len() {
+            if other[i] > self[i] {
+                self[i] = other[i];
+            }
+        }
+    }
+    fn component_min_assign(&mut self, other: &Self) {
+        for i in 0..self.len() {
+            if other[i] < self[i] {
+                self[i] = other[i];
+            }
+        }
+    }
+    fn binary_mask<F: Fn(Self::T) -> bool>(&self, f: F) -> Self {
+        let mut ret = Self::new_serial(self.len());
+        for i in 0..self.len() {
+            ret[i] = if f(self[i]) { 1.0 } else { 0.0 };
+        }
+        ret
+    }
     fn copy_from(&mut self, other: &Self) {
         unsafe { N_VScale(1.0, other.sundials_vector(), self.sundials_vector()) }
     }
@@ -548,6 +614,33 @@ impl Vector for SundialsVector {
         }
         z
     }
+    fn ln(&self) -> Self {
+        let mut z = SundialsVector::new_clone(self);
+        for i in 0..self.len() {
+            z[i] = self[i].ln();
+        }
+        z
+    }
+    fn powf(&self, exponent: Self::T) -> Self {
+        let mut z = SundialsVector::new_clone(self);
+        for i in 0..self.len() {
+            z[i] = self[i].powf(exponent);
+        }
+        z
+    }
+    fn sum(&self) -> Self::T {
+        (0..self.len()).map(|i| self[i]).sum()
+    }
+    fn min(&self) -> Self::T {
+        (0..self.len())
+            .map(|i| self[i])
+            .fold(f64::INFINITY, f64::min)
+    }
+    fn max(&self) -> Self::T {
+        (0..self.len())
+            .map(|i| self[i])
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
     fn filter_indices<F: Fn(Self::T) -> bool>(&self, f: F) -> Self::Index {
         let mut indices = vec![];
         for i in 0..self.len() {
@@ -600,6 +693,24 @@ impl Vector for SundialsVector {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_segment_and_split() {
+        let mut v = SundialsVector::from_vec(vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+        let s = v.segment(1..3);
+        assert_eq!(s.len(), 2);
+        assert_eq!(s[1], 3.0);
+        let (head, tail) = v.split_at(2);
+        assert_eq!(head.len(), 2);
+        assert_eq!(tail[0], 3.0);
+
+        let (mut species, mut temperature) = v.split_at_mut(4);
+        species *= scale(2.0);
+        temperature[0] = -1.0;
+        assert_eq!(v[0], 2.0);
+        assert_eq!(v[3], 8.0);
+        assert_eq!(v[4], -1.0);
+    }
+
     #[test]
     fn test_indexing() {
         let mut v = SundialsVector::new_serial(2);
@@ -697,6 +808,21 @@ mod tests {
         assert_eq!(v2[1], 2.0_f64.exp());
     }
 
+    #[test]
+    fn test_reductions_and_elementwise() {
+        let v = SundialsVector::from_vec(vec![1.0, -2.0, 4.0]);
+        assert_eq!(v.sum(), 3.0);
+        assert_eq!(v.min(), -2.0);
+        assert_eq!(v.max(), 4.0);
+        assert_eq!(v.mean(), 1.0);
+        assert_eq!(v.element_iter().collect::<Vec<_>>(), vec![1.0, -2.0, 4.0]);
+        let v = SundialsVector::from_vec(vec![1.0, 2.0, 4.0]);
+        v.exp().ln().assert_eq_st(&v, 1e-14);
+        let v2 = v.powf(0.5);
+        assert_eq!(v2[0], 1.0);
+        assert_eq!(v2[2], 2.0);
+    }
+
     #[test]
     fn test_filter_indices() {
         let mut v = SundialsVector::new_serial(2);